@@ -0,0 +1,246 @@
+//! Quantum channels ρ → Σ Kᵢ ρ Kᵢ† given by their Kraus operators, with
+//! the standard single-qubit noise channels.
+
+use crate::circuit::Gate;
+use crate::ket::{KetBra, Operator};
+use crate::Complex;
+
+/// A completely positive, trace-preserving map on `num_qubits()` qubits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    name: &'static str,
+    kraus: Vec<Operator<Complex<f64>>>,
+}
+
+impl Channel {
+    /// The channel with Kraus operators `kraus`, which must act on the same
+    /// qubits and satisfy Σ Kᵢ†Kᵢ = I.
+    pub fn new(kraus: Vec<Operator<Complex<f64>>>) -> Self {
+        Self::named("kraus", kraus)
+    }
+
+    fn named(name: &'static str, mut kraus: Vec<Operator<Complex<f64>>>) -> Self {
+        kraus.retain(|k| k.terms().any(|(scalar, ..)| scalar.modulus() > 0.0));
+        assert!(!kraus.is_empty(), "a channel needs Kraus operators");
+        let n = kraus[0].num_qubits();
+        assert!(
+            kraus.iter().all(|k| k.num_qubits() == n),
+            "Kraus operators on different qubit counts"
+        );
+
+        let mut sum = Operator::identity(n) * Complex::new(-1.0, 0.0);
+        for k in &kraus {
+            sum = (sum + k.adjoint() * k.clone()).simplify();
+        }
+        assert!(
+            sum.terms().all(|(v, ..)| v.modulus() < 1e-9),
+            "Kraus operators do not sum to the identity"
+        );
+
+        Channel { name, kraus }
+    }
+
+    /// Name of the constructor, or `"kraus"` for [`Channel::new`].
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn kraus(&self) -> &[Operator<Complex<f64>>] {
+        &self.kraus
+    }
+
+    pub fn num_qubits(&self) -> u32 {
+        self.kraus[0].num_qubits()
+    }
+
+    /// ρ → (1 − p) ρ + p I/2, the qubit replaced by the maximally mixed state
+    /// with probability `p`.
+    pub fn depolarizing(p: f64) -> Self {
+        check_probability(p);
+        Self::named(
+            "depolarizing",
+            vec![
+                scaled(Gate::I, (1.0 - 0.75 * p).sqrt()),
+                scaled(Gate::X, (p / 4.0).sqrt()),
+                scaled(Gate::Y, (p / 4.0).sqrt()),
+                scaled(Gate::Z, (p / 4.0).sqrt()),
+            ],
+        )
+    }
+
+    /// Energy relaxation, |1⟩ decaying to |0⟩ with probability `gamma`.
+    pub fn amplitude_damping(gamma: f64) -> Self {
+        check_probability(gamma);
+        Self::named(
+            "amplitude_damping",
+            vec![
+                real_matrix([[1.0, 0.0], [0.0, (1.0 - gamma).sqrt()]]),
+                real_matrix([[0.0, gamma.sqrt()], [0.0, 0.0]]),
+            ],
+        )
+    }
+
+    /// Loss of coherence without loss of energy, the off-diagonal entries
+    /// shrinking by a factor √(1 − λ).
+    pub fn phase_damping(lambda: f64) -> Self {
+        check_probability(lambda);
+        Self::named(
+            "phase_damping",
+            vec![
+                real_matrix([[1.0, 0.0], [0.0, (1.0 - lambda).sqrt()]]),
+                real_matrix([[0.0, 0.0], [0.0, lambda.sqrt()]]),
+            ],
+        )
+    }
+
+    /// X with probability `p`.
+    pub fn bit_flip(p: f64) -> Self {
+        Self::pauli_flip("bit_flip", Gate::X, p)
+    }
+
+    /// Z with probability `p`.
+    pub fn phase_flip(p: f64) -> Self {
+        Self::pauli_flip("phase_flip", Gate::Z, p)
+    }
+
+    /// Y with probability `p`.
+    pub fn bit_phase_flip(p: f64) -> Self {
+        Self::pauli_flip("bit_phase_flip", Gate::Y, p)
+    }
+
+    fn pauli_flip(name: &'static str, pauli: Gate, p: f64) -> Self {
+        check_probability(p);
+        Self::named(
+            name,
+            vec![scaled(Gate::I, (1.0 - p).sqrt()), scaled(pauli, p.sqrt())],
+        )
+    }
+}
+
+fn check_probability(p: f64) {
+    assert!((0.0..=1.0).contains(&p), "probability {p} outside [0, 1]");
+}
+
+fn scaled(gate: Gate, factor: f64) -> Operator<Complex<f64>> {
+    gate.operator() * Complex::new(factor, 0.0)
+}
+
+/// The single-qubit operator with real entries `m`, indexed `[ket][bra]`.
+fn real_matrix(m: [[f64; 2]; 2]) -> Operator<Complex<f64>> {
+    let mut ones = vec![];
+    for (ket, row) in m.iter().enumerate() {
+        for (bra, &x) in row.iter().enumerate() {
+            if x != 0.0 {
+                ones.push(KetBra {
+                    scalar: Complex::new(x, 0.0),
+                    ket: ket as u64,
+                    bra: bra as u64,
+                    n: 1,
+                    d: 2,
+                });
+            }
+        }
+    }
+
+    Operator {
+        scalar: Complex::new(1.0, 0.0),
+        ones,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Channel;
+    use crate::density::DensityMatrix;
+    use crate::ket::{Operator, State};
+    use crate::Complex as C;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn plus() -> DensityMatrix<C<f64>> {
+        DensityMatrix::from_state(&State::parse("1/sqrt(2)(|0⟩ + |1⟩)").unwrap())
+    }
+
+    fn one() -> DensityMatrix<C<f64>> {
+        DensityMatrix::from_state(&State::parse("|1⟩").unwrap())
+    }
+
+    #[test]
+    fn depolarizing_shrinks_the_bloch_vector() {
+        let (x, y, z) = plus()
+            .apply_channel(&Channel::depolarizing(0.2), &[0])
+            .bloch_vector();
+        assert!((x - 0.8).abs() < EPSILON && y.abs() < EPSILON && z.abs() < EPSILON);
+
+        let (x, _, _) = plus()
+            .apply_channel(&Channel::depolarizing(1.0), &[0])
+            .bloch_vector();
+        assert!(x.abs() < EPSILON);
+    }
+
+    #[test]
+    fn amplitude_damping_relaxes_to_ground() {
+        let rho = one().apply_channel(&Channel::amplitude_damping(0.3), &[0]);
+        assert!((rho.probability(0).re - 0.3).abs() < EPSILON);
+        assert!((rho.probability(1).re - 0.7).abs() < EPSILON);
+    }
+
+    #[test]
+    fn phase_damping_keeps_populations() {
+        let (x, _, z) = plus()
+            .apply_channel(&Channel::phase_damping(0.36), &[0])
+            .bloch_vector();
+        assert!((x - 0.8).abs() < EPSILON);
+        assert!(z.abs() < EPSILON);
+    }
+
+    #[test]
+    fn flips() {
+        let (_, _, z) = one()
+            .apply_channel(&Channel::bit_flip(0.25), &[0])
+            .bloch_vector();
+        assert!((z + 0.5).abs() < EPSILON);
+
+        let (x, _, _) = plus()
+            .apply_channel(&Channel::phase_flip(0.25), &[0])
+            .bloch_vector();
+        assert!((x - 0.5).abs() < EPSILON);
+
+        let (x, _, z) = plus()
+            .apply_channel(&Channel::bit_phase_flip(0.5), &[0])
+            .bloch_vector();
+        assert!(x.abs() < EPSILON && z.abs() < EPSILON);
+    }
+
+    #[test]
+    fn zero_probability_terms_are_dropped() {
+        assert_eq!(Channel::bit_flip(0.0).kraus().len(), 1);
+        assert_eq!(Channel::phase_damping(0.0).kraus().len(), 1);
+        assert_eq!(Channel::bit_flip(0.0).num_qubits(), 1);
+    }
+
+    #[test]
+    fn acts_on_the_given_qubit() {
+        // |1⟩ on qubit 1, |0⟩ on qubit 0
+        let rho = DensityMatrix::from_state(&State::basis(0b10, 2))
+            .apply_channel(&Channel::amplitude_damping(1.0), &[1]);
+        assert!((rho.probability(0b00).re - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn custom_kraus_operators() {
+        let x = Operator::parse("|0⟩⟨1| + |1⟩⟨0|").unwrap();
+        let channel = Channel::new(vec![x]);
+        assert_eq!(channel.name(), "kraus");
+
+        let rho = one().apply_channel(&channel, &[0]);
+        assert!((rho.probability(0).re - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    #[should_panic(expected = "Kraus operators do not sum to the identity")]
+    fn rejects_trace_decreasing_maps() {
+        let p0 = Operator::projector(&State::basis(0, 1));
+        Channel::new(vec![p0]);
+    }
+}
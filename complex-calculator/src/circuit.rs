@@ -0,0 +1,1372 @@
+//! Quantum circuits as ordered lists of gates and measurements over an
+//! n-qubit register, built up with chained calls:
+//!
+//! ```
+//! use complex_calculator::circuit::Circuit;
+//!
+//! let mut bell = Circuit::new(2);
+//! bell.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+//! assert_eq!(bell.len(), 4);
+//! ```
+
+use std::collections::BTreeMap;
+use std::f64::consts::TAU;
+
+use crate::channel::Channel;
+use crate::ket::State;
+use crate::Complex;
+
+mod arithmetic;
+mod batch;
+mod bernstein_vazirani;
+mod compose;
+mod control;
+mod counts;
+mod decompose;
+mod deutsch_jozsa;
+mod encoding;
+mod gate;
+mod graph_state;
+mod grover;
+#[cfg(feature = "serde")]
+mod json;
+mod layout;
+#[cfg(feature = "mmap")]
+mod mapped;
+mod mixedstate;
+mod modular;
+mod mps;
+mod optimize;
+mod oracle;
+mod path_sum;
+mod permutation;
+mod qasm;
+mod qft;
+mod qmdd;
+mod quantikz;
+mod quantum_volume;
+mod quil;
+mod quirk;
+mod random;
+mod registers;
+mod routing;
+mod solovay_kitaev;
+mod sparse;
+mod statevector;
+mod stim;
+mod svg;
+mod synthesis;
+mod transpile;
+mod unitary;
+mod variational;
+pub use counts::Counts;
+pub use deutsch_jozsa::FunctionKind;
+pub use gate::{Gate, GateKind, Param};
+#[cfg(feature = "mmap")]
+pub use mapped::MappedStateVector;
+pub use mixedstate::MixedState;
+pub use mps::Mps;
+pub use qasm::QasmError;
+pub use qmdd::Qmdd;
+pub use quirk::QuirkError;
+pub use registers::{ClassicalRegister, QuantumRegister, Registers};
+pub use routing::{CouplingMap, Routing};
+pub use solovay_kitaev::SolovayKitaev;
+pub use sparse::SparseState;
+pub use statevector::{Real, StateVector};
+pub use stim::StimError;
+pub use svg::SvgStyle;
+pub use synthesis::{EulerAngles, KakDecomposition};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Gate {
+        gate: Gate,
+        qubits: Vec<usize>,
+    },
+    /// Measurement of `qubit` in the computational basis into `clbit`.
+    Measure {
+        qubit: usize,
+        clbit: usize,
+    },
+    /// Return of `qubit` to |0⟩, whatever its state: a measurement whose
+    /// result is discarded, followed by X if it read 1.
+    Reset {
+        qubit: usize,
+    },
+    /// A no-op separating the instructions before and after it on `qubits`.
+    Barrier {
+        qubits: Vec<usize>,
+    },
+    /// `gate` on `qubits`, applied only if `clbit` reads 1 at that point.
+    Conditional {
+        clbit: usize,
+        gate: Gate,
+        qubits: Vec<usize>,
+    },
+    /// A noise channel on `qubits`, its first qubit on `qubits[0]`.
+    Channel {
+        channel: Channel,
+        qubits: Vec<usize>,
+    },
+    /// `body`, a circuit over the same qubits and classical bits, run again
+    /// for as long as `clbit` reads `value`, but at most `max_iterations`
+    /// times.
+    While {
+        clbit: usize,
+        value: bool,
+        max_iterations: usize,
+        body: Box<Circuit>,
+    },
+    /// A copy of the state at this point, kept under `label` by the
+    /// simulators that record them. Like a barrier across every qubit, it
+    /// keeps gates from moving past it.
+    Snapshot {
+        label: String,
+    },
+}
+
+/// An ordered list of instructions on `num_qubits` qubits. The classical
+/// register grows to fit the bits measurements write to.
+///
+/// The circuit also carries a global phase e^(iφ), which the simulators
+/// apply to the final state. It is unobservable on its own, but becomes a
+/// relative phase once the circuit is [`controlled`](Circuit::controlled).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circuit {
+    num_qubits: u32,
+    num_clbits: usize,
+    instructions: Vec<Instruction>,
+    global_phase: f64,
+}
+
+impl Circuit {
+    pub fn new(num_qubits: u32) -> Self {
+        Circuit {
+            num_qubits,
+            num_clbits: 0,
+            instructions: vec![],
+            global_phase: 0.0,
+        }
+    }
+
+    /// A circuit with a classical register of `num_clbits` bits from the
+    /// start, rather than grown by measurements.
+    pub fn with_clbits(num_qubits: u32, num_clbits: usize) -> Self {
+        Circuit {
+            num_clbits,
+            ..Circuit::new(num_qubits)
+        }
+    }
+
+    pub fn num_qubits(&self) -> u32 {
+        self.num_qubits
+    }
+
+    pub fn num_clbits(&self) -> usize {
+        self.num_clbits
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// The angle φ of the global phase e^(iφ), in [0, 2π).
+    pub fn global_phase(&self) -> f64 {
+        self.global_phase
+    }
+
+    /// Multiplies the global phase by e^(iφ).
+    pub fn add_global_phase(&mut self, phi: f64) -> &mut Self {
+        self.global_phase = (self.global_phase + phi).rem_euclid(TAU);
+        self
+    }
+
+    /// Copy with the global phase dropped, for comparing circuits that differ
+    /// only by it.
+    pub fn without_global_phase(&self) -> Circuit {
+        Circuit {
+            global_phase: 0.0,
+            ..self.clone()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// Appends `gate` on `qubits`, its first qubit being `qubits[0]`.
+    pub fn append(&mut self, gate: Gate, qubits: &[usize]) -> &mut Self {
+        self.check_gate(&gate, qubits);
+        self.instructions.push(Instruction::Gate {
+            gate,
+            qubits: qubits.to_vec(),
+        });
+        self
+    }
+
+    /// Appends `gate` on `qubits` conditioned on the classical bit `clbit`
+    /// being 1 when it is reached, for feed-forward corrections.
+    pub fn c_if(&mut self, clbit: usize, gate: Gate, qubits: &[usize]) -> &mut Self {
+        self.check_gate(&gate, qubits);
+        self.num_clbits = self.num_clbits.max(clbit + 1);
+        self.instructions.push(Instruction::Conditional {
+            clbit,
+            gate,
+            qubits: qubits.to_vec(),
+        });
+        self
+    }
+
+    /// Measures `qubit` into the classical bit `clbit`.
+    pub fn measure(&mut self, qubit: usize, clbit: usize) -> &mut Self {
+        self.check_qubit(qubit);
+        self.num_clbits = self.num_clbits.max(clbit + 1);
+        self.instructions
+            .push(Instruction::Measure { qubit, clbit });
+        self
+    }
+
+    /// Resets `qubit` to |0⟩ mid-circuit, so that it can be reused.
+    pub fn reset(&mut self, qubit: usize) -> &mut Self {
+        self.check_qubit(qubit);
+        self.instructions.push(Instruction::Reset { qubit });
+        self
+    }
+
+    /// Number of time steps the circuit needs, the number of its
+    /// [`moments`](Circuit::moments).
+    pub fn depth(&self) -> usize {
+        self.moments().len()
+    }
+
+    /// The instructions grouped into time steps, or moments, each pushed as
+    /// early as the qubits and classical bits it uses allow. The instructions
+    /// of a moment touch disjoint qubits, so can run in parallel. Barriers
+    /// line their qubits up without taking a step, and are in no moment.
+    pub fn moments(&self) -> Vec<Vec<&Instruction>> {
+        let mut moments: Vec<Vec<&Instruction>> = vec![];
+        for (instruction, moment) in self.instructions.iter().zip(self.schedule()) {
+            if let Some(moment) = moment {
+                if moments.len() <= moment {
+                    moments.resize_with(moment + 1, Vec::new);
+                }
+                moments[moment].push(instruction);
+            }
+        }
+        moments
+    }
+
+    /// The moment of each instruction, in order, with `None` for barriers
+    /// and snapshots.
+    pub fn schedule(&self) -> Vec<Option<usize>> {
+        let mut qubits = vec![0; self.num_qubits as usize];
+        let mut clbits = vec![0; self.num_clbits];
+        let mut schedule = Vec::with_capacity(self.instructions.len());
+
+        for instruction in &self.instructions {
+            let (qs, cs): (&[usize], Option<usize>) = match instruction {
+                Instruction::Gate { qubits, .. } => (qubits, None),
+                Instruction::Measure { qubit, clbit } => {
+                    (std::slice::from_ref(qubit), Some(*clbit))
+                }
+                Instruction::Reset { qubit } => (std::slice::from_ref(qubit), None),
+                Instruction::Conditional { clbit, qubits, .. } => (qubits, Some(*clbit)),
+                Instruction::Channel { qubits, .. } => (qubits, None),
+                Instruction::Barrier { qubits: qs } => {
+                    let level = qs.iter().map(|&q| qubits[q]).max().unwrap_or(0);
+                    for &q in qs {
+                        qubits[q] = level;
+                    }
+                    schedule.push(None);
+                    continue;
+                }
+                Instruction::Snapshot { .. } => {
+                    let level = qubits.iter().copied().max().unwrap_or(0);
+                    qubits.fill(level);
+                    schedule.push(None);
+                    continue;
+                }
+                Instruction::While { .. } => {
+                    // a loop may touch anything, so it is a moment of its own
+                    let level = qubits.iter().chain(&clbits).copied().max().unwrap_or(0);
+                    qubits.fill(level + 1);
+                    clbits.fill(level + 1);
+                    schedule.push(Some(level));
+                    continue;
+                }
+            };
+
+            let level = qs
+                .iter()
+                .map(|&q| qubits[q])
+                .chain(cs.map(|c| clbits[c]))
+                .max()
+                .unwrap_or(0);
+            for &q in qs {
+                qubits[q] = level + 1;
+            }
+            if let Some(c) = cs {
+                clbits[c] = level + 1;
+            }
+            schedule.push(Some(level));
+        }
+
+        schedule
+    }
+
+    /// Number of instructions of each kind, keyed by gate name (conditional
+    /// gates included), channel name, `"measure"`, `"reset"`, `"barrier"`,
+    /// `"while"` and `"snapshot"`. The bodies of loops are not counted.
+    pub fn gate_counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for instruction in &self.instructions {
+            let name = match instruction {
+                Instruction::Gate { gate, .. } | Instruction::Conditional { gate, .. } => {
+                    gate.name()
+                }
+                Instruction::Channel { channel, .. } => channel.name(),
+                Instruction::Measure { .. } => "measure",
+                Instruction::Reset { .. } => "reset",
+                Instruction::Barrier { .. } => "barrier",
+                Instruction::While { .. } => "while",
+                Instruction::Snapshot { .. } => "snapshot",
+            };
+            *counts.entry(name).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Number of gates acting on exactly two qubits.
+    pub fn two_qubit_count(&self) -> usize {
+        self.instructions
+            .iter()
+            .filter(|instruction| match instruction {
+                Instruction::Gate { qubits, .. } | Instruction::Conditional { qubits, .. } => {
+                    qubits.len() == 2
+                }
+                _ => false,
+            })
+            .count()
+    }
+
+    /// Number of T and T† gates, classically conditioned ones included. Other
+    /// gates are not counted, however many T gates they need, so transpile to
+    /// Clifford+T first.
+    pub fn t_count(&self) -> usize {
+        self.instructions
+            .iter()
+            .filter(|instruction| match instruction {
+                Instruction::Gate { gate, .. } | Instruction::Conditional { gate, .. } => {
+                    is_t(gate)
+                }
+                _ => false,
+            })
+            .count()
+    }
+
+    /// Number of layers of T and T† gates, with the Clifford gates between
+    /// them taken to be free: the most T gates along any path through the
+    /// circuit. As for [`Circuit::t_count`], other gates are not counted.
+    pub fn t_depth(&self) -> usize {
+        let every: Vec<usize> = (0..self.num_qubits as usize).collect();
+        let mut qubits = vec![0; self.num_qubits as usize];
+        let mut clbits = vec![0; self.num_clbits];
+
+        for instruction in &self.instructions {
+            let (qs, cs, t): (&[usize], &[usize], bool) = match instruction {
+                Instruction::Gate { gate, qubits } => (qubits, &[], is_t(gate)),
+                Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => (qubits, std::slice::from_ref(clbit), is_t(gate)),
+                Instruction::Measure { qubit, clbit } => (
+                    std::slice::from_ref(qubit),
+                    std::slice::from_ref(clbit),
+                    false,
+                ),
+                Instruction::Reset { qubit } => (std::slice::from_ref(qubit), &[], false),
+                Instruction::Barrier { qubits } | Instruction::Channel { qubits, .. } => {
+                    (qubits, &[], false)
+                }
+                Instruction::While { clbit, .. } => (&every, std::slice::from_ref(clbit), false),
+                Instruction::Snapshot { .. } => (&[], &[], false),
+            };
+
+            let level = qs
+                .iter()
+                .map(|&q| qubits[q])
+                .chain(cs.iter().map(|&c| clbits[c]))
+                .max()
+                .unwrap_or(0)
+                + usize::from(t);
+            for &q in qs {
+                qubits[q] = level;
+            }
+            for &c in cs {
+                clbits[c] = level;
+            }
+        }
+
+        qubits.into_iter().chain(clbits).max().unwrap_or(0)
+    }
+
+    /// Whether every gate, classically conditioned ones and those of loop
+    /// bodies included, is a Clifford gate, so that a stabilizer simulator
+    /// could run the circuit. Measurements, resets and barriers are allowed,
+    /// noise channels are not.
+    pub fn is_clifford(&self) -> bool {
+        self.instructions
+            .iter()
+            .all(|instruction| match instruction {
+                Instruction::Gate { gate, .. } | Instruction::Conditional { gate, .. } => {
+                    gate.is_clifford()
+                }
+                Instruction::While { body, .. } => body.is_clifford(),
+                Instruction::Channel { .. } => false,
+                Instruction::Measure { .. }
+                | Instruction::Reset { .. }
+                | Instruction::Barrier { .. }
+                | Instruction::Snapshot { .. } => true,
+            })
+    }
+
+    /// Names of the symbolic parameters still unbound, sorted.
+    pub fn parameters(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = vec![];
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, .. } | Instruction::Conditional { gate, .. } => {
+                    names.extend(gate.params().iter().filter_map(|p| match p {
+                        Param::Symbol(name) => Some(name.as_str()),
+                        Param::Value(_) => None,
+                    }));
+                }
+                Instruction::While { body, .. } => names.extend(body.parameters()),
+                Instruction::Measure { .. }
+                | Instruction::Reset { .. }
+                | Instruction::Barrier { .. }
+                | Instruction::Channel { .. }
+                | Instruction::Snapshot { .. } => {}
+            }
+        }
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Copy with every symbol named in `values` replaced by its value. Symbols
+    /// not listed stay unbound.
+    pub fn bind_parameters(&self, values: &[(&str, f64)]) -> Circuit {
+        let mut bound = self.clone();
+        for instruction in &mut bound.instructions {
+            match instruction {
+                Instruction::Gate { gate, .. } | Instruction::Conditional { gate, .. } => {
+                    for param in gate.params_mut() {
+                        param.bind(values);
+                    }
+                }
+                Instruction::While { body, .. } => **body = body.bind_parameters(values),
+                _ => {}
+            }
+        }
+        bound
+    }
+
+    /// Runs the circuit on |0…0⟩ with a dense statevector and returns the
+    /// final state. Circuits with measurements or noise channels need
+    /// [`Circuit::run`].
+    pub fn simulate(&self) -> State<Complex<f64>> {
+        self.simulate_statevector::<f64>().to_state()
+    }
+
+    /// [`Circuit::simulate`], keeping the amplitudes as a [`StateVector`] of
+    /// `F`. With `f32` the state takes half the memory, and amplitudes are
+    /// good to around 1e-6 for circuits of moderate depth.
+    pub fn simulate_statevector<F: Real>(&self) -> StateVector<F> {
+        let (sv, _, _) = self.execute(None::<&mut dyn rand::RngCore>);
+        sv
+    }
+
+    /// Runs the circuit once on |0…0⟩, sampling measurement outcomes from
+    /// `rng`, and returns the collapsed final state with the classical bits.
+    ///
+    /// Noise channels are sampled too, one Kraus operator per channel, so the
+    /// run is a single quantum trajectory: averaged over many runs it
+    /// reproduces [`Circuit::simulate_density`] in 2^n rather than 4^n memory.
+    ///
+    /// All randomness comes from `rng`, so a seeded generator such as
+    /// `StdRng::seed_from_u64` makes runs exactly reproducible.
+    pub fn run<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Run {
+        let (sv, clbits, snapshots) = self.execute::<f64, _>(Some(rng));
+        Run {
+            state: sv.to_state(),
+            clbits,
+            snapshots,
+        }
+    }
+
+    /// Monte Carlo estimate of the probability of each basis state at the
+    /// end of the circuit, averaging |ψ|² over `shots` trajectories.
+    pub fn average_probabilities<R: rand::Rng + ?Sized>(
+        &self,
+        shots: usize,
+        rng: &mut R,
+    ) -> Vec<f64> {
+        let mut total = vec![0.0; 1 << self.num_qubits];
+        for _ in 0..shots {
+            let (sv, _, _) = self.execute::<f64, _>(Some(&mut *rng));
+            for (sum, a) in total.iter_mut().zip(sv.amplitudes()) {
+                *sum += a.mod_squared();
+            }
+        }
+        total.iter().map(|p| p / shots as f64).collect()
+    }
+
+    fn execute<F: Real, R: rand::Rng + ?Sized>(
+        &self,
+        mut rng: Option<&mut R>,
+    ) -> (StateVector<F>, Vec<bool>, Snapshots) {
+        let mut sv = StateVector::with_precision(self.num_qubits);
+        let mut clbits = vec![false; self.num_clbits];
+        let mut snapshots = vec![];
+        self.execute_on(&mut sv, &mut clbits, &mut snapshots, &mut rng);
+        (sv, clbits, snapshots)
+    }
+
+    /// Applies the instructions to `sv`, reading and writing `clbits` and
+    /// recording `snapshots`.
+    fn execute_on<F: Real, R: rand::Rng + ?Sized>(
+        &self,
+        sv: &mut StateVector<F>,
+        clbits: &mut [bool],
+        snapshots: &mut Snapshots,
+        rng: &mut Option<&mut R>,
+    ) {
+        let needs_rng = || -> ! {
+            panic!("simulate() runs circuits without measurements or channels, use run()")
+        };
+
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => sv.apply(gate, qubits),
+                Instruction::Measure { qubit, clbit } => {
+                    let rng = rng.as_deref_mut().unwrap_or_else(|| needs_rng());
+                    clbits[*clbit] = sv.measure(*qubit, rng);
+                }
+                Instruction::Reset { qubit } => {
+                    let one = match rng.as_deref_mut() {
+                        Some(rng) => sv.measure(*qubit, rng),
+                        None => definite(sv.probability_of_one(*qubit)).unwrap_or_else(|| {
+                            panic!("simulate() resets only qubits in a definite state, use run()")
+                        }),
+                    };
+                    if one {
+                        sv.apply(&Gate::X, &[*qubit]);
+                    }
+                }
+                Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => {
+                    if clbits[*clbit] {
+                        sv.apply(gate, qubits);
+                    }
+                }
+                Instruction::Channel { channel, qubits } => {
+                    let rng = rng.as_deref_mut().unwrap_or_else(|| needs_rng());
+                    sv.apply_channel(channel, qubits, rng);
+                }
+                Instruction::While {
+                    clbit,
+                    value,
+                    max_iterations,
+                    body,
+                } => {
+                    for _ in 0..*max_iterations {
+                        if clbits[*clbit] != *value {
+                            break;
+                        }
+                        body.execute_on(sv, clbits, snapshots, rng);
+                    }
+                }
+                Instruction::Snapshot { label } => snapshots.push((label.clone(), sv.to_state())),
+                Instruction::Barrier { .. } => {}
+            }
+        }
+
+        sv.apply_global_phase(self.global_phase);
+    }
+
+    /// Appends `channel` on `qubits`. Circuits with channels run under
+    /// [`Circuit::simulate_density`], or as sampled trajectories under
+    /// [`Circuit::run`].
+    pub fn channel(&mut self, channel: Channel, qubits: &[usize]) -> &mut Self {
+        assert_eq!(
+            qubits.len(),
+            channel.num_qubits() as usize,
+            "{} channel acts on {} qubits",
+            channel.name(),
+            channel.num_qubits()
+        );
+        for (i, &q) in qubits.iter().enumerate() {
+            self.check_qubit(q);
+            assert!(
+                !qubits[..i].contains(&q),
+                "duplicate qubit {q} for {} channel",
+                channel.name()
+            );
+        }
+        self.instructions.push(Instruction::Channel {
+            channel,
+            qubits: qubits.to_vec(),
+        });
+        self
+    }
+
+    /// Copy with the channel `noise(gate)` after every unconditional gate
+    /// for which it returns one. A channel on as many qubits as the gate
+    /// follows it on the same qubits, and a single-qubit channel follows it
+    /// on each of them.
+    pub fn with_noise(&self, noise: impl Fn(&Gate) -> Option<Channel>) -> Circuit {
+        let mut noisy = Circuit {
+            instructions: vec![],
+            ..self.clone()
+        };
+
+        for instruction in &self.instructions {
+            noisy.instructions.push(instruction.clone());
+            let Instruction::Gate { gate, qubits } = instruction else {
+                continue;
+            };
+            let Some(channel) = noise(gate) else {
+                continue;
+            };
+
+            if channel.num_qubits() as usize == qubits.len() {
+                noisy.channel(channel, qubits);
+            } else if channel.num_qubits() == 1 {
+                for &q in qubits {
+                    noisy.channel(channel.clone(), &[q]);
+                }
+            } else {
+                panic!(
+                    "{} channel on {} qubits does not fit {gate}",
+                    channel.name(),
+                    channel.num_qubits()
+                );
+            }
+        }
+
+        noisy
+    }
+
+    /// A barrier across `qubits`.
+    pub fn barrier(&mut self, qubits: &[usize]) -> &mut Self {
+        for &q in qubits {
+            self.check_qubit(q);
+        }
+        self.instructions.push(Instruction::Barrier {
+            qubits: qubits.to_vec(),
+        });
+        self
+    }
+
+    /// Records the state at this point under `label`, to be read back from
+    /// the [`Run`]. Snapshots are taken before the global phase is applied.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut c = Circuit::new(2);
+    /// c.h(0).snapshot("superposed").cnot(0, 1);
+    /// let run = c.run(&mut StdRng::seed_from_u64(0));
+    /// assert_eq!(run.snapshot("superposed").unwrap().terms().count(), 2);
+    /// ```
+    pub fn snapshot(&mut self, label: &str) -> &mut Self {
+        self.instructions.push(Instruction::Snapshot {
+            label: label.to_string(),
+        });
+        self
+    }
+
+    fn check_gate(&self, gate: &Gate, qubits: &[usize]) {
+        assert_eq!(
+            qubits.len(),
+            gate.num_qubits(),
+            "{gate} acts on {} qubits",
+            gate.num_qubits()
+        );
+        for (i, &q) in qubits.iter().enumerate() {
+            self.check_qubit(q);
+            assert!(!qubits[..i].contains(&q), "duplicate qubit {q} for {gate}");
+        }
+    }
+
+    fn check_qubit(&self, qubit: usize) {
+        assert!(
+            qubit < self.num_qubits as usize,
+            "qubit {qubit} out of range for {} qubits",
+            self.num_qubits
+        );
+    }
+
+    pub fn id(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::I, &[q])
+    }
+
+    pub fn h(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::H, &[q])
+    }
+
+    pub fn x(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::X, &[q])
+    }
+
+    pub fn y(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::Y, &[q])
+    }
+
+    pub fn z(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::Z, &[q])
+    }
+
+    pub fn s(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::S, &[q])
+    }
+
+    pub fn sdg(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::Sdg, &[q])
+    }
+
+    pub fn sx(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::Sx, &[q])
+    }
+
+    pub fn sxdg(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::Sxdg, &[q])
+    }
+
+    pub fn t(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::T, &[q])
+    }
+
+    pub fn tdg(&mut self, q: usize) -> &mut Self {
+        self.append(Gate::Tdg, &[q])
+    }
+
+    pub fn rx(&mut self, q: usize, theta: impl Into<Param>) -> &mut Self {
+        self.append(Gate::Rx(theta.into()), &[q])
+    }
+
+    pub fn ry(&mut self, q: usize, theta: impl Into<Param>) -> &mut Self {
+        self.append(Gate::Ry(theta.into()), &[q])
+    }
+
+    pub fn rz(&mut self, q: usize, theta: impl Into<Param>) -> &mut Self {
+        self.append(Gate::Rz(theta.into()), &[q])
+    }
+
+    pub fn p(&mut self, q: usize, lambda: impl Into<Param>) -> &mut Self {
+        self.append(Gate::Phase(lambda.into()), &[q])
+    }
+
+    pub fn u3(
+        &mut self,
+        q: usize,
+        theta: impl Into<Param>,
+        phi: impl Into<Param>,
+        lambda: impl Into<Param>,
+    ) -> &mut Self {
+        self.append(Gate::U3(theta.into(), phi.into(), lambda.into()), &[q])
+    }
+
+    pub fn cnot(&mut self, control: usize, target: usize) -> &mut Self {
+        self.append(Gate::Cx, &[control, target])
+    }
+
+    pub fn cz(&mut self, control: usize, target: usize) -> &mut Self {
+        self.append(Gate::Cz, &[control, target])
+    }
+
+    /// The controlled phase diag(1, 1, 1, e^(iλ)), built from two CNOTs and
+    /// phase gates, which is symmetric in its two qubits.
+    pub fn cp(&mut self, control: usize, target: usize, lambda: f64) -> &mut Self {
+        self.p(control, lambda / 2.0)
+            .cnot(control, target)
+            .p(target, -lambda / 2.0)
+            .cnot(control, target)
+            .p(target, lambda / 2.0)
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) -> &mut Self {
+        self.append(Gate::Swap, &[a, b])
+    }
+
+    pub fn ccx(&mut self, control0: usize, control1: usize, target: usize) -> &mut Self {
+        self.append(Gate::Ccx, &[control0, control1, target])
+    }
+
+    /// X on `target` controlled by all of `controls`, using the smallest gate
+    /// that fits: X, CNOT, Toffoli or [`Gate::Mcx`].
+    pub fn mcx(&mut self, controls: &[usize], target: usize) -> &mut Self {
+        let gate = match controls.len() {
+            0 => Gate::X,
+            1 => Gate::Cx,
+            2 => Gate::Ccx,
+            k => Gate::Mcx(k),
+        };
+        let mut qubits = controls.to_vec();
+        qubits.push(target);
+        self.append(gate, &qubits)
+    }
+}
+
+/// Labelled states recorded by snapshot instructions, in the order taken.
+type Snapshots = Vec<(String, State<Complex<f64>>)>;
+
+/// The result of one [`Circuit::run`]: the final quantum state, the
+/// classical register and any snapshots taken along the way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    state: State<Complex<f64>>,
+    clbits: Vec<bool>,
+    snapshots: Snapshots,
+}
+
+impl Run {
+    pub fn state(&self) -> &State<Complex<f64>> {
+        &self.state
+    }
+
+    pub fn clbits(&self) -> &[bool] {
+        &self.clbits
+    }
+
+    /// The classical register packed into an integer, bit i from clbit i.
+    pub fn register(&self) -> u64 {
+        self.clbits
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, &b)| acc | (b as u64) << i)
+    }
+
+    /// Every snapshot with its label, in the order taken. A snapshot in a
+    /// loop body is taken once per iteration.
+    pub fn snapshots(&self) -> &[(String, State<Complex<f64>>)] {
+        &self.snapshots
+    }
+
+    /// The state at the last snapshot labelled `label`.
+    pub fn snapshot(&self, label: &str) -> Option<&State<Complex<f64>>> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|(l, _)| l == label)
+            .map(|(_, state)| state)
+    }
+}
+
+fn is_t(gate: &Gate) -> bool {
+    matches!(gate, Gate::T | Gate::Tdg)
+}
+
+/// The value a qubit with probability `p1` of reading 1 is sure to read, if
+/// any, for resets without a random number generator.
+fn definite(p1: f64) -> Option<bool> {
+    const EPS: f64 = 1e-12;
+    match p1 {
+        p if p < EPS => Some(false),
+        p if p > 1.0 - EPS => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+    use super::{Circuit, Gate, Instruction, Run};
+    use crate::channel::Channel;
+    use crate::ket::{Operator, State};
+    use crate::Complex as C;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn builder_records_instructions() {
+        let mut c = Circuit::new(3);
+        c.h(0).cnot(0, 2).rz(1, 0.5).measure(2, 1);
+
+        assert_eq!(c.len(), 4);
+        assert_eq!(c.num_clbits(), 2);
+        assert_eq!(
+            c.instructions()[1],
+            Instruction::Gate {
+                gate: Gate::Cx,
+                qubits: vec![0, 2]
+            }
+        );
+        assert_eq!(
+            c.instructions()[3],
+            Instruction::Measure { qubit: 2, clbit: 1 }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "qubit 2 out of range for 2 qubits")]
+    fn qubit_out_of_range() {
+        Circuit::new(2).h(2);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate qubit 1 for cx")]
+    fn duplicate_qubits() {
+        Circuit::new(2).cnot(1, 1);
+    }
+
+    #[test]
+    fn simulate_bell_state() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1);
+
+        let state = c.simulate();
+        let expected = State::<C<f64>>::bell(0);
+        let overlap = expected.dagger() * state;
+        assert!((overlap - C::new(1.0, 0.0)).modulus() < EPSILON);
+    }
+
+    #[test]
+    fn simulate_matches_operator_product() {
+        let mut c = Circuit::new(3);
+        c.h(0)
+            .t(0)
+            .cnot(0, 2)
+            .rz(1, 0.7)
+            .h(1)
+            .cz(1, 2)
+            .swap(0, 1)
+            .s(2);
+
+        let mut u = Operator::identity(3);
+        for instruction in c.instructions() {
+            if let Instruction::Gate { gate, qubits } = instruction {
+                u = gate.operator().embed(qubits, 3) * u;
+            }
+        }
+        let expected = (u * State::basis(0, 3)).amplitudes(3);
+
+        let state = c.simulate().amplitudes(3);
+        for (a, b) in state.iter().zip(&expected) {
+            assert!((*a - *b).modulus() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn circuit_metrics() {
+        let mut c = Circuit::new(3);
+        c.h(0).h(1).cnot(0, 1).x(2).cnot(1, 2).h(0).measure(2, 0);
+
+        // h h | cx x | cx h | measure
+        assert_eq!(c.depth(), 4);
+        assert_eq!(c.two_qubit_count(), 2);
+        assert_eq!(
+            c.gate_counts().into_iter().collect::<Vec<_>>(),
+            vec![("cx", 2), ("h", 3), ("measure", 1), ("x", 1)]
+        );
+        assert_eq!(Circuit::new(2).depth(), 0);
+    }
+
+    #[test]
+    fn clifford_t_statistics() {
+        let mut c = Circuit::new(2);
+        // the T on qubit 1 waits for both before it, by way of the CNOT
+        c.t(0).t(0).h(1).cnot(0, 1).tdg(1).s(0).t(0);
+        assert_eq!((c.t_count(), c.t_depth()), (4, 3));
+        assert!(!c.is_clifford());
+
+        let mut toffoli = Circuit::new(3);
+        toffoli.ccx(0, 1, 2);
+        let toffoli = toffoli.decompose_multi_controlled();
+        assert_eq!((toffoli.t_count(), toffoli.t_depth()), (7, 4));
+
+        let mut c = Circuit::new(2);
+        c.h(0)
+            .s(1)
+            .cz(0, 1)
+            .rz(0, -PI / 2.0)
+            .sx(1)
+            .measure(0, 0)
+            .c_if(0, Gate::Y, &[1]);
+        assert!(c.is_clifford());
+        assert_eq!((c.t_count(), c.t_depth()), (0, 0));
+        c.rx(1, 0.1);
+        assert!(!c.is_clifford());
+    }
+
+    #[test]
+    fn global_phase_multiplies_the_final_state() {
+        let mut c = Circuit::new(1);
+        c.h(0).add_global_phase(PI / 2.0).add_global_phase(2.0 * PI);
+        assert!((c.global_phase() - PI / 2.0).abs() < EPSILON);
+
+        let s = FRAC_1_SQRT_2;
+        for a in c.simulate().amplitudes(1) {
+            assert!((a - C::new(0.0, s)).modulus() < EPSILON);
+        }
+        for a in c.without_global_phase().simulate().amplitudes(1) {
+            assert!((a - C::new(s, 0.0)).modulus() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn moments_group_parallel_instructions() {
+        let mut c = Circuit::new(3);
+        c.h(0)
+            .h(1)
+            .cnot(0, 1)
+            .x(2)
+            .barrier(&[1, 2])
+            .z(2)
+            .measure(0, 0);
+
+        assert_eq!(
+            c.schedule(),
+            [Some(0), Some(0), Some(1), Some(0), None, Some(2), Some(2)]
+        );
+        let moments = c.moments();
+        assert_eq!(moments.len(), 3);
+        assert_eq!(moments[0].len(), 3);
+        assert_eq!(moments[1], [&c.instructions()[2]]);
+        assert_eq!(moments[2], [&c.instructions()[5], &c.instructions()[6]]);
+    }
+
+    #[test]
+    fn depth_with_barriers_and_classical_bits() {
+        let mut c = Circuit::new(2);
+        c.h(0).h(0).barrier(&[0, 1]).x(1);
+        assert_eq!(c.depth(), 3);
+
+        // the conditional waits for the measurement writing its bit
+        let mut c = Circuit::new(2);
+        c.h(0).h(0).measure(0, 0).c_if(0, Gate::X, &[1]);
+        assert_eq!(c.depth(), 4);
+    }
+
+    #[test]
+    fn bind_symbolic_parameters() {
+        let mut c = Circuit::new(2);
+        c.ry(0, "theta")
+            .rx(1, "phi")
+            .cnot(0, 1)
+            .rz(1, "theta")
+            .p(0, 0.3);
+        assert_eq!(c.parameters(), vec!["phi", "theta"]);
+
+        let partial = c.bind_parameters(&[("theta", 1.2)]);
+        assert_eq!(partial.parameters(), vec!["phi"]);
+
+        let bound = partial.bind_parameters(&[("phi", -0.5), ("unused", 9.0)]);
+        assert!(bound.parameters().is_empty());
+
+        let mut expected = Circuit::new(2);
+        expected
+            .ry(0, 1.2)
+            .rx(1, -0.5)
+            .cnot(0, 1)
+            .rz(1, 1.2)
+            .p(0, 0.3);
+        assert_eq!(bound, expected);
+        assert_eq!(bound.simulate(), expected.simulate());
+    }
+
+    #[test]
+    fn ry_prepares_bloch_angle() {
+        let theta = 1.1;
+        let mut c = Circuit::new(1);
+        c.ry(0, theta);
+
+        let (x, y, z) = c.simulate().bloch_vector();
+        assert!((x - theta.sin()).abs() < EPSILON);
+        assert!(y.abs() < EPSILON);
+        assert!((z - theta.cos()).abs() < EPSILON);
+    }
+
+    #[test]
+    #[should_panic(expected = "without measurements")]
+    fn simulate_rejects_measurements() {
+        Circuit::new(1).h(0).measure(0, 0).simulate();
+    }
+
+    #[test]
+    #[should_panic(expected = "without measurements or channels")]
+    fn simulate_rejects_channels() {
+        Circuit::new(1)
+            .channel(Channel::bit_flip(0.1), &[0])
+            .simulate();
+    }
+
+    #[test]
+    fn reset_reuses_a_qubit() {
+        let mut c = Circuit::new(2);
+        c.h(0)
+            .cnot(0, 1)
+            .measure(0, 0)
+            .reset(0)
+            .measure(0, 1)
+            .x(0)
+            .reset(0);
+
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..10 {
+            let run = c.run(&mut rng);
+            assert!(!run.clbits()[1]);
+            assert!(run.state().amplitudes(2)[0b01].modulus() < EPSILON);
+        }
+
+        // a qubit known to be 1 needs no sampling
+        let mut c = Circuit::new(1);
+        c.x(0).reset(0);
+        assert!((c.simulate().amplitudes(1)[0].re - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    #[should_panic(expected = "resets only qubits in a definite state")]
+    fn simulate_rejects_random_resets() {
+        Circuit::new(1).h(0).reset(0).simulate();
+    }
+
+    /// The message `f` panics with, failing if it returns.
+    pub(super) fn panic_message<T>(f: impl FnOnce() -> T + std::panic::UnwindSafe) -> String {
+        let Err(panic) = std::panic::catch_unwind(f) else {
+            panic!("expected a panic");
+        };
+        panic
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| panic.downcast_ref::<&str>().map(|m| m.to_string()))
+            .unwrap_or_default()
+    }
+
+    /// Checks a backend on qubit 0 reset out of a Bell pair and reused for
+    /// another: `run` must leave either pair intact with both outcomes
+    /// turning up, and `simulate`, having no rng, must refuse the reset.
+    pub(super) fn assert_resets_superpositions(
+        run: impl Fn(&Circuit, &mut StdRng) -> State<C<f64>>,
+        simulate: impl FnOnce(&Circuit) -> State<C<f64>> + std::panic::UnwindSafe,
+    ) {
+        let mut c = Circuit::new(3);
+        c.h(0).cnot(0, 1).reset(0).h(0).cnot(0, 2);
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut ones = 0;
+        for _ in 0..40 {
+            let amplitudes = run(&c, &mut rng).amplitudes(3);
+            let p1: f64 = (0..8)
+                .filter(|x| x & 0b010 != 0)
+                .map(|x| amplitudes[x].mod_squared())
+                .sum();
+            assert!(!(EPSILON..=1.0 - EPSILON).contains(&p1), "{p1}");
+            let b = if p1 > 0.5 { 0b010 } else { 0 };
+            ones += b >> 1;
+            for x in [b, b | 0b101] {
+                assert!((amplitudes[x].modulus() - FRAC_1_SQRT_2).abs() < EPSILON);
+            }
+        }
+        assert!(ones > 10 && ones < 30, "{ones}");
+
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).reset(0);
+        let message = panic_message(|| simulate(&c));
+        assert!(
+            message.contains("resets only qubits in a definite state, use run"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn every_backend_resets_superpositions() {
+        assert_resets_superpositions(|c, rng| c.run(rng).state().clone(), |c| c.simulate());
+        assert_resets_superpositions(
+            |c, rng| c.run_sparse(rng).state().clone(),
+            |c| c.simulate_sparse().to_state(),
+        );
+        assert_resets_superpositions(
+            |c, rng| c.run_mps(4, rng).to_state(),
+            |c| c.simulate_mps(4).to_state(),
+        );
+        assert_resets_superpositions(
+            |c, rng| c.run_qmdd(rng).to_state(),
+            |c| c.simulate_qmdd().to_state(),
+        );
+    }
+
+    #[test]
+    fn backends_without_snapshots_reject_them() {
+        let mut c = Circuit::new(1);
+        c.h(0).snapshot("mid").h(0);
+        let message = panic_message(|| c.simulate_mps(2));
+        assert_eq!(message, "simulate_mps() records no snapshots, use run()");
+        let message = panic_message(|| c.simulate_qmdd());
+        assert_eq!(message, "simulate_qmdd() records no snapshots, use run()");
+    }
+
+    #[test]
+    fn trajectories_average_to_the_density_matrix() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).x(0);
+        let noisy = c.with_noise(|_| Some(Channel::amplitude_damping(0.3)));
+
+        let estimate = noisy.average_probabilities(4000, &mut StdRng::seed_from_u64(3));
+        let exact = noisy.simulate_density();
+        for (k, p) in estimate.iter().enumerate() {
+            assert!((p - exact.probability(k as u64).re).abs() < 0.03, "|{k}⟩");
+        }
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let mut c = Circuit::random(3, 4, &mut StdRng::seed_from_u64(5))
+            .with_noise(|_| Some(Channel::depolarizing(0.2)));
+        c.measure(0, 0).measure(1, 1).measure(2, 2);
+
+        let runs = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..20).map(|_| c.run(&mut rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(runs(11), runs(11));
+        assert_ne!(
+            runs(11).iter().map(Run::register).collect::<Vec<_>>(),
+            runs(12).iter().map(Run::register).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn run_records_correlated_bits() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let mut seen = [0; 4];
+        for _ in 0..100 {
+            let run = c.run(&mut rng);
+            assert_eq!(run.clbits()[0], run.clbits()[1]);
+            seen[run.register() as usize] += 1;
+
+            let (scalar, ket, _) = run.state().terms().next().unwrap();
+            assert_eq!(ket, run.register());
+            assert!((scalar.modulus() - 1.0).abs() < EPSILON);
+        }
+        assert!(seen[0] > 0 && seen[3] > 0);
+    }
+
+    #[test]
+    fn measure_into_chosen_clbits() {
+        let mut c = Circuit::new(2);
+        c.x(1).measure(1, 2).measure(0, 0);
+
+        let run = c.run(&mut StdRng::seed_from_u64(0));
+        assert_eq!(run.clbits(), [false, false, true]);
+        assert_eq!(run.register(), 0b100);
+    }
+
+    #[test]
+    fn teleportation_with_feed_forward() {
+        // teleport Rz(0.8)H|0⟩ from qubit 0 to qubit 2
+        let mut c = Circuit::new(3);
+        c.h(0).rz(0, 0.8);
+        c.h(1).cnot(1, 2);
+        c.cnot(0, 1).h(0).measure(0, 0).measure(1, 1);
+        c.c_if(1, Gate::X, &[2]).c_if(0, Gate::Z, &[2]);
+
+        let mut source = Circuit::new(1);
+        source.h(0).rz(0, 0.8);
+        let expected = source.simulate().amplitudes(1);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..20 {
+            let run = c.run(&mut rng);
+            let rho = run.state().trace_out(&[0, 1]);
+            for (i, a) in expected.iter().enumerate() {
+                let p = rho.probability(i as u64);
+                assert!((p.re - a.mod_squared()).abs() < EPSILON);
+            }
+
+            let bits = run.register();
+            let received: Vec<_> = (0..2)
+                .map(|b| run.state().amplitudes(3)[(b << 2 | bits) as usize])
+                .collect();
+            let overlap =
+                expected[0].conjugate() * received[0] + expected[1].conjugate() * received[1];
+            assert!((overlap.modulus() - 1.0).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn conditional_on_unset_bit_is_skipped() {
+        let mut c = Circuit::new(1);
+        c.c_if(0, Gate::X, &[0]);
+
+        assert_eq!(c.num_clbits(), 1);
+        assert_eq!(c.simulate(), State::basis(0, 1));
+    }
+
+    #[test]
+    fn snapshots_record_intermediate_states() {
+        let mut c = Circuit::new(2);
+        c.h(0)
+            .snapshot("superposed")
+            .cnot(0, 1)
+            .snapshot("entangled")
+            .measure(0, 0)
+            .snapshot("collapsed")
+            .add_global_phase(PI);
+
+        let run = c.run(&mut StdRng::seed_from_u64(3));
+        let labels: Vec<&str> = run.snapshots().iter().map(|(l, _)| l.as_str()).collect();
+        assert_eq!(labels, ["superposed", "entangled", "collapsed"]);
+
+        let superposed = run.snapshot("superposed").unwrap().amplitudes(2);
+        assert!((superposed[0b01] - C::new(FRAC_1_SQRT_2, 0.0)).modulus() < EPSILON);
+        let entangled = run.snapshot("entangled").unwrap().amplitudes(2);
+        assert!((entangled[0b11] - C::new(FRAC_1_SQRT_2, 0.0)).modulus() < EPSILON);
+
+        // the global phase comes only at the end
+        let x = if run.clbits()[0] { 0b11 } else { 0 };
+        assert!((run.snapshot("collapsed").unwrap().amplitudes(2)[x].re - 1.0).abs() < EPSILON);
+        assert!((run.state().amplitudes(2)[x].re + 1.0).abs() < EPSILON);
+        assert_eq!(run.snapshot("missing"), None);
+
+        // snapshots take no moment, and line up every qubit
+        assert_eq!(c.schedule(), [Some(0), None, Some(1), None, Some(2), None]);
+        assert_eq!(c.gate_counts()["snapshot"], 3);
+    }
+
+    #[test]
+    fn snapshots_in_loops_repeat() {
+        // qubit 0 always reads 0, so the body runs three times
+        let mut body = Circuit::new(2);
+        body.x(1).snapshot("attempt").measure(0, 0);
+        let mut c = Circuit::new(2);
+        c.while_loop(0, false, &body, 3);
+
+        let run = c.run(&mut StdRng::seed_from_u64(0));
+        let flipped: Vec<bool> = run
+            .snapshots()
+            .iter()
+            .map(|(_, state)| state.amplitudes(2)[0b10].re > 0.5)
+            .collect();
+        assert_eq!(flipped, [true, false, true]);
+        assert_eq!(run.snapshot("attempt"), Some(&run.snapshots()[2].1));
+    }
+}
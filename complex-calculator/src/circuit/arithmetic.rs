@@ -0,0 +1,268 @@
+//! Reversible arithmetic on registers of n qubits holding unsigned integers,
+//! least significant bit on the lowest qubit: the ripple-carry adder of
+//! Cuccaro, Draper, Kutin and Moulton, comparators built from its carry
+//! chain, the addition of classical constants, and Draper's adder, which
+//! adds in the Fourier basis with phases instead of carries.
+
+use std::f64::consts::PI;
+
+use super::Circuit;
+
+impl Circuit {
+    /// The MAJ block of the ripple-carry adder: the majority of the three
+    /// bits replaces `a`, with `b` and `c` left as a ⊕ b and a ⊕ c.
+    fn majority(&mut self, c: usize, b: usize, a: usize) -> &mut Self {
+        self.cnot(a, b).cnot(a, c).ccx(c, b, a)
+    }
+
+    /// The UMA block, undoing a MAJ block and leaving the sum bit in `b`.
+    fn unmajority(&mut self, c: usize, b: usize, a: usize) -> &mut Self {
+        self.ccx(c, b, a).cnot(a, c).cnot(c, b)
+    }
+
+    /// The carries of a + b, each MAJ block leaving the carry out of bit i
+    /// on a's qubit i, from the ancilla's carry in.
+    fn carries(&mut self, n: usize) -> &mut Self {
+        self.majority(2 * n, n, 0);
+        for i in 1..n {
+            self.majority(i - 1, n + i, i);
+        }
+        self
+    }
+
+    /// The ripple-carry adder |a⟩|b⟩|0⟩|z⟩ ↦ |a⟩|a + b mod 2^n⟩|0⟩|z ⊕ c⟩ on
+    /// 2n + 2 qubits, where c is the carry out of the sum: a on qubits 0 to
+    /// n − 1, b on n to 2n − 1, an ancilla on 2n that is returned to 0, and
+    /// z on 2n + 1. Takes 2n Toffolis, after Cuccaro et al., "A new quantum
+    /// ripple-carry addition circuit" (2004).
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// // 5 + 6 = 11 = 8 + 3 on three bits
+    /// let c = Circuit::adder(3);
+    /// assert_eq!(c.classical_output(5 | 6 << 3), 5 | 3 << 3 | 1 << 7);
+    /// ```
+    pub fn adder(n: u32) -> Circuit {
+        assert!(n > 0, "an adder needs at least one bit");
+        let n = n as usize;
+        let mut c = Circuit::new(2 * n as u32 + 2);
+        c.carries(n);
+        c.cnot(n - 1, 2 * n + 1);
+        for i in (1..n).rev() {
+            c.unmajority(i - 1, n + i, i);
+        }
+        c.unmajority(2 * n, n, 0);
+        c
+    }
+
+    /// The comparator |a⟩|b⟩|0⟩|z⟩ ↦ |a⟩|b⟩|0⟩|z ⊕ \[a < b\]⟩, on the layout
+    /// of [`Circuit::adder`]. The carry out of ¬a + b is 1 exactly when
+    /// b > a, so the carry chain is run on the complement of a, copied out,
+    /// and undone.
+    pub fn less_than(n: u32) -> Circuit {
+        assert!(n > 0, "a comparator needs at least one bit");
+        let n = n as usize;
+        let mut compute = Circuit::new(2 * n as u32 + 2);
+        for q in 0..n {
+            compute.x(q);
+        }
+        compute.carries(n);
+
+        let mut c = compute.clone();
+        c.cnot(n - 1, 2 * n + 1);
+        c.compose(&compute.inverse(), &(0..2 * n + 2).collect::<Vec<_>>());
+        c
+    }
+
+    /// |x⟩ ↦ |x + k mod 2^n⟩ on n qubits, without ancillas: each set bit j
+    /// of k increments the bits from j up, by multi-controlled X gates from
+    /// the top bit down.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// assert_eq!(Circuit::add_constant(4, 7).classical_output(12), 3);
+    /// ```
+    pub fn add_constant(n: u32, k: u64) -> Circuit {
+        let mut c = Circuit::new(n);
+        for j in (0..n as usize).filter(|&j| k >> j & 1 == 1) {
+            for target in (j..n as usize).rev() {
+                c.mcx(&(j..target).collect::<Vec<_>>(), target);
+            }
+        }
+        c
+    }
+
+    /// Draper's adder |a⟩|b⟩ ↦ |a⟩|a + b mod 2^n⟩ on 2n qubits, a on qubits 0
+    /// to n − 1 and b on n to 2n − 1, with no ancillas. The QFT takes b to
+    /// Σ_y e^(2πiby/2^n) |y⟩, where adding a multiplies each term by
+    /// e^(2πiay/2^n): a controlled phase 2π·2^(j+k)/2^n from each bit j of a
+    /// to each bit k of y, of which those with j + k ≥ n vanish. The inverse
+    /// QFT then reads out the sum. After Draper, "Addition on a quantum
+    /// computer" (2000).
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// // 5 + 6 = 11 = 3 mod 8
+    /// let mut c = Circuit::new(6);
+    /// c.x(0).x(2).x(4).x(5).compose(&Circuit::draper_adder(3), &[0, 1, 2, 3, 4, 5]);
+    /// let amplitudes = c.simulate().amplitudes(6);
+    /// assert!((amplitudes[5 | 3 << 3].modulus() - 1.0).abs() < 1e-9);
+    /// ```
+    pub fn draper_adder(n: u32) -> Circuit {
+        let n = n as usize;
+        let b: Vec<usize> = (n..2 * n).collect();
+        let mut c = Circuit::new(2 * n as u32);
+        c.compose(&Circuit::qft(n as u32), &b);
+        for j in 0..n {
+            for (k, &y) in b.iter().take(n - j).enumerate() {
+                c.cp(j, y, 2.0 * PI / (1u64 << (n - j - k)) as f64);
+            }
+        }
+        c.compose(&Circuit::iqft(n as u32), &b);
+        c
+    }
+
+    /// |x⟩|z⟩ ↦ |x⟩|z ⊕ \[x < k\]⟩ with x on qubits 0 to n − 1 and z on qubit
+    /// n. Adding 2^n − k to x on n + 1 bits carries into z exactly when
+    /// x ≥ k, after which x is restored by adding k back on its own bits.
+    pub fn less_than_constant(n: u32, k: u64) -> Circuit {
+        assert!(
+            k <= 1 << n,
+            "{k} is beyond the {n}-bit numbers it is compared with"
+        );
+        let x: Vec<usize> = (0..n as usize).collect();
+        let mut c = Circuit::new(n + 1);
+        c.compose(
+            &Circuit::add_constant(n + 1, (1 << n) - k),
+            &(0..=n as usize).collect::<Vec<_>>(),
+        )
+        .compose(&Circuit::add_constant(n, k), &x)
+        .x(n as usize);
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const EPSILON: f64 = 0.0000001;
+
+    /// The basis state `c` takes |x⟩ to, checking that it is one.
+    fn basis_output(c: &Circuit, x: u64) -> u64 {
+        let n = c.num_qubits();
+        let mut prepared = Circuit::new(n);
+        for q in (0..n as usize).filter(|&q| x >> q & 1 == 1) {
+            prepared.x(q);
+        }
+        prepared.compose(c, &(0..n as usize).collect::<Vec<_>>());
+        let amplitudes = prepared.simulate().amplitudes(n);
+        let y = (0..amplitudes.len())
+            .max_by(|&i, &j| amplitudes[i].modulus().total_cmp(&amplitudes[j].modulus()))
+            .unwrap();
+        assert!((amplitudes[y].modulus() - 1.0).abs() < EPSILON, "|{x}⟩");
+        y as u64
+    }
+
+    #[test]
+    fn adds_every_pair() {
+        for n in 1..=3u32 {
+            let c = Circuit::adder(n);
+            let mask = (1 << n) - 1;
+            for a in 0..1 << n {
+                for b in 0..1 << n {
+                    for z in 0..2 {
+                        let input = a | b << n | z << (2 * n + 1);
+                        let sum = a + b;
+                        let carry = sum >> n;
+                        let expected = a | (sum & mask) << n | (z ^ carry) << (2 * n + 1);
+                        assert_eq!(c.classical_output(input), expected, "{a} + {b}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn adder_size() {
+        let c = Circuit::adder(4);
+        assert_eq!(c.num_qubits(), 10);
+        assert_eq!(c.gate_counts()["ccx"], 8);
+    }
+
+    #[test]
+    fn compares_every_pair() {
+        let n = 3;
+        let c = Circuit::less_than(n);
+        for a in 0..8 {
+            for b in 0..8 {
+                let expected = a | b << n | u64::from(a < b) << (2 * n + 1);
+                assert_eq!(c.classical_output(a | b << n), expected, "{a} < {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn adds_every_constant() {
+        for k in 0..16 {
+            let c = Circuit::add_constant(4, k);
+            for x in 0..16 {
+                assert_eq!(c.classical_output(x), (x + k) % 16, "{x} + {k}");
+            }
+        }
+        assert!(Circuit::add_constant(3, 0).is_empty());
+    }
+
+    #[test]
+    fn compares_with_constants() {
+        for k in 0..=8 {
+            let c = Circuit::less_than_constant(3, k);
+            for x in 0..8 {
+                for z in 0..2 {
+                    let expected = x | (z ^ u64::from(x < k)) << 3;
+                    assert_eq!(c.classical_output(x | z << 3), expected, "{x} < {k}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn draper_adder_agrees_with_ripple_carry() {
+        let n = 4;
+        let (draper, ripple) = (Circuit::draper_adder(n), Circuit::adder(n));
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..20 {
+            let (a, b) = (rng.random_range(0..16), rng.random_range(0..16));
+            let sum = basis_output(&draper, a | b << n);
+            assert_eq!(sum, a | ((a + b) % 16) << n, "{a} + {b}");
+            assert_eq!(ripple.classical_output(a | b << n) & 0xff, sum);
+        }
+    }
+
+    #[test]
+    fn draper_adder_adds_superpositions() {
+        // (|0⟩ + e^(iφ)|1⟩)|3⟩ goes to |0⟩|3⟩ + e^(iφ)|1⟩|4⟩
+        let mut prepare = Circuit::new(6);
+        prepare.h(0).p(0, 0.3).x(3).x(4);
+        let before = prepare.simulate().amplitudes(6);
+        let after = prepare
+            .compose(&Circuit::draper_adder(3), &[0, 1, 2, 3, 4, 5])
+            .simulate()
+            .amplitudes(6);
+        assert!((after[3 << 3] - before[3 << 3]).modulus() < EPSILON);
+        assert!((after[1 | 4 << 3] - before[1 | 3 << 3]).modulus() < EPSILON);
+    }
+
+    #[test]
+    fn draper_adder_needs_no_toffolis() {
+        let c = Circuit::draper_adder(3);
+        assert_eq!(c.num_qubits(), 6);
+        assert!(!c.gate_counts().contains_key("ccx"));
+    }
+}
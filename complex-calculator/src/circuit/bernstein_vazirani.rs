@@ -0,0 +1,72 @@
+//! The Bernstein–Vazirani algorithm, which reads the n-bit secret s of the
+//! function f(x) = s·x mod 2 off with one query, where classically each
+//! query reveals a single bit of it.
+
+use super::Circuit;
+
+impl Circuit {
+    /// The Bernstein–Vazirani circuit for the secret `secret` on n input
+    /// qubits and one output qubit n, laid out as [`Circuit::deutsch_jozsa`]
+    /// with the [`Circuit::bit_oracle`] of f(x) = s·x mod 2. Input i is
+    /// measured into bit i, so a run's register is the secret.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let run = Circuit::bernstein_vazirani(5, 0b10110).run(&mut StdRng::seed_from_u64(1));
+    /// assert_eq!(run.register(), 0b10110);
+    /// ```
+    pub fn bernstein_vazirani(n: u32, secret: u32) -> Circuit {
+        assert!(
+            u64::from(secret) < 1 << n,
+            "secret {secret:b} has more than {n} bits"
+        );
+        Circuit::deutsch_jozsa(n, |x| (x & secret).count_ones() % 2 == 1)
+    }
+
+    /// Whether each of `shots` runs of [`Circuit::bernstein_vazirani`]
+    /// recovers `secret` exactly, as it should every time.
+    pub fn bernstein_vazirani_recovers<R: rand::Rng + ?Sized>(
+        n: u32,
+        secret: u32,
+        shots: usize,
+        rng: &mut R,
+    ) -> bool {
+        let counts = Circuit::bernstein_vazirani(n, secret).sample(shots, rng);
+        counts.get(secret.into()) == shots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn recovers_every_secret() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for n in 1..=4 {
+            for secret in 0..1 << n {
+                assert!(Circuit::bernstein_vazirani_recovers(n, secret, 4, &mut rng));
+            }
+        }
+    }
+
+    #[test]
+    fn uses_one_query() {
+        let c = Circuit::bernstein_vazirani(3, 0b011);
+        // s·x is odd on half the inputs, each one controlled X
+        assert_eq!(c.gate_counts()["mcx"], 4);
+        assert_eq!(c.num_clbits(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "secret 1000 has more than 3 bits")]
+    fn secrets_fit_the_register() {
+        Circuit::bernstein_vazirani(3, 8);
+    }
+}
@@ -0,0 +1,359 @@
+//! Building circuits out of other circuits: composition, inverses, powers and
+//! controlled versions, as phase estimation and uncomputation call for.
+
+use std::f64::consts::TAU;
+
+use super::{Circuit, EulerAngles, Gate, Instruction};
+
+impl Circuit {
+    /// Appends the instructions of `other`, its qubit i on `qubits[i]` and its
+    /// classical bits on the bits of the same index. Global phases add up.
+    pub fn compose(&mut self, other: &Circuit, qubits: &[usize]) -> &mut Self {
+        assert_eq!(
+            qubits.len(),
+            other.num_qubits as usize,
+            "composing a circuit on {} qubits",
+            other.num_qubits
+        );
+        for (i, &q) in qubits.iter().enumerate() {
+            self.check_qubit(q);
+            assert!(
+                !qubits[..i].contains(&q),
+                "duplicate qubit {q} to compose on"
+            );
+        }
+
+        let map = |qs: &[usize]| -> Vec<usize> { qs.iter().map(|&q| qubits[q]).collect() };
+        self.num_clbits = self.num_clbits.max(other.num_clbits);
+        self.add_global_phase(other.global_phase);
+        for instruction in &other.instructions {
+            self.instructions.push(match instruction.clone() {
+                Instruction::Gate { gate, qubits } => Instruction::Gate {
+                    gate,
+                    qubits: map(&qubits),
+                },
+                Instruction::Measure { qubit, clbit } => Instruction::Measure {
+                    qubit: qubits[qubit],
+                    clbit,
+                },
+                Instruction::Reset { qubit } => Instruction::Reset {
+                    qubit: qubits[qubit],
+                },
+                Instruction::Barrier { qubits } => Instruction::Barrier {
+                    qubits: map(&qubits),
+                },
+                Instruction::Snapshot { label } => Instruction::Snapshot { label },
+                Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits: map(&qubits),
+                },
+                Instruction::Channel { channel, qubits } => Instruction::Channel {
+                    channel,
+                    qubits: map(&qubits),
+                },
+                Instruction::While {
+                    clbit,
+                    value,
+                    max_iterations,
+                    body,
+                } => {
+                    let mut mapped = Circuit::with_clbits(self.num_qubits, body.num_clbits);
+                    mapped.compose(&body, qubits);
+                    Instruction::While {
+                        clbit,
+                        value,
+                        max_iterations,
+                        body: Box::new(mapped),
+                    }
+                }
+            });
+        }
+        self
+    }
+
+    /// The circuit undoing this one: its gates inverted in reverse order and
+    /// its global phase negated.
+    /// Panics on anything but gates and barriers, and on unbound parameters.
+    pub fn inverse(&self) -> Circuit {
+        let instructions = self
+            .instructions
+            .iter()
+            .rev()
+            .map(|instruction| match instruction {
+                Instruction::Gate { gate, qubits } => Instruction::Gate {
+                    gate: gate.inverse(),
+                    qubits: qubits.clone(),
+                },
+                Instruction::Barrier { .. } | Instruction::Snapshot { .. } => instruction.clone(),
+                _ => panic!("only circuits of gates can be inverted"),
+            })
+            .collect();
+
+        Circuit {
+            instructions,
+            global_phase: (-self.global_phase).rem_euclid(TAU),
+            ..self.clone()
+        }
+    }
+
+    /// The circuit repeated `k` times, the empty circuit for k = 0.
+    pub fn power(&self, k: usize) -> Circuit {
+        Circuit {
+            instructions: (0..k).flat_map(|_| self.instructions.clone()).collect(),
+            global_phase: (self.global_phase * k as f64).rem_euclid(TAU),
+            ..self.clone()
+        }
+    }
+
+    /// The circuit controlled by a new qubit 0, with qubit i of this circuit
+    /// becoming qubit i + 1. Controlled single-qubit gates are built from
+    /// their Euler angles with two CNOTs, keeping the phase exactly, and the
+    /// global phase becomes a phase gate on the control. Panics on anything
+    /// but gates and barriers, and on unbound parameters.
+    pub fn controlled(&self) -> Circuit {
+        let mut c = Circuit::new(self.num_qubits + 1);
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => {
+                    let qubits: Vec<usize> = qubits.iter().map(|q| q + 1).collect();
+                    for (gate, qubits) in controlled(gate, 0, &qubits) {
+                        c.append(gate, &qubits);
+                    }
+                }
+                Instruction::Barrier { qubits } => {
+                    let mut qubits: Vec<usize> = qubits.iter().map(|q| q + 1).collect();
+                    qubits.insert(0, 0);
+                    c.barrier(&qubits);
+                }
+                Instruction::Snapshot { label } => {
+                    c.snapshot(label);
+                }
+                _ => panic!("only circuits of gates can be controlled"),
+            }
+        }
+        if self.global_phase != 0.0 {
+            c.p(0, self.global_phase);
+        }
+        c
+    }
+}
+
+/// `gate` on `qubits` controlled by `control`, as gates of the crate.
+pub(super) fn controlled(gate: &Gate, control: usize, qubits: &[usize]) -> Vec<(Gate, Vec<usize>)> {
+    let with_control = |gate: Gate| {
+        let mut all = vec![control];
+        all.extend(qubits);
+        (gate, all)
+    };
+
+    match gate {
+        Gate::I => vec![],
+        Gate::X => vec![with_control(Gate::Cx)],
+        Gate::Z => vec![with_control(Gate::Cz)],
+        Gate::Cx => vec![with_control(Gate::Ccx)],
+        Gate::Ccx => vec![with_control(Gate::Mcx(3))],
+        Gate::Mcx(controls) => vec![with_control(Gate::Mcx(controls + 1))],
+        Gate::Cz => vec![
+            (Gate::H, vec![qubits[1]]),
+            with_control(Gate::Ccx),
+            (Gate::H, vec![qubits[1]]),
+        ],
+        Gate::Swap => {
+            let (a, b) = (qubits[0], qubits[1]);
+            vec![
+                (Gate::Cx, vec![b, a]),
+                (Gate::Ccx, vec![control, a, b]),
+                (Gate::Cx, vec![b, a]),
+            ]
+        }
+        _ => {
+            // U = e^(iα) A X B X C with ABC = I, for
+            // A = Rz(β) Ry(γ/2), B = Ry(−γ/2) Rz(−(δ+β)/2), C = Rz((δ−β)/2)
+            let EulerAngles {
+                alpha,
+                beta,
+                gamma,
+                delta,
+            } = EulerAngles::zyz(&gate.operator());
+            let t = qubits[0];
+            vec![
+                (Gate::Rz(((delta - beta) / 2.0).into()), vec![t]),
+                (Gate::Cx, vec![control, t]),
+                (Gate::Rz((-(delta + beta) / 2.0).into()), vec![t]),
+                (Gate::Ry((-gamma / 2.0).into()), vec![t]),
+                (Gate::Cx, vec![control, t]),
+                (Gate::Ry((gamma / 2.0).into()), vec![t]),
+                (Gate::Rz(beta.into()), vec![t]),
+                (Gate::Phase(alpha.into()), vec![control]),
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::circuit::{Circuit, Gate};
+    use crate::ket::State;
+    use crate::Complex as C;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn run(c: &Circuit, x: u64) -> Vec<C<f64>> {
+        let n = c.num_qubits();
+        (c.unitary() * State::basis(x, n)).amplitudes(n)
+    }
+
+    fn assert_close(a: &[C<f64>], b: &[C<f64>]) {
+        assert_eq!(a.len(), b.len());
+        for (p, q) in a.iter().zip(b) {
+            assert!((*p - *q).modulus() < EPSILON, "{a:?} != {b:?}");
+        }
+    }
+
+    fn every_gate() -> Circuit {
+        let mut c = Circuit::new(4);
+        c.h(0)
+            .x(1)
+            .y(2)
+            .z(3)
+            .s(0)
+            .sdg(1)
+            .t(2)
+            .tdg(3)
+            .rx(0, 0.3)
+            .ry(1, -1.2)
+            .rz(2, 2.5)
+            .p(3, 0.7)
+            .u3(0, 1.0, 2.0, 3.0)
+            .cnot(1, 2)
+            .cz(2, 3)
+            .swap(0, 3)
+            .ccx(0, 1, 2)
+            .mcx(&[0, 1, 2], 3)
+            .id(1);
+        c
+    }
+
+    #[test]
+    fn compose_maps_qubits_and_clbits() {
+        let mut bell = Circuit::new(2);
+        bell.h(0).cnot(0, 1).measure(1, 0);
+
+        let mut c = Circuit::new(3);
+        c.x(0).compose(&bell, &[2, 0]);
+
+        let mut expected = Circuit::new(3);
+        expected.x(0).h(2).cnot(2, 0).measure(0, 0);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "composing a circuit on 2 qubits")]
+    fn compose_needs_a_qubit_for_each() {
+        Circuit::new(3).compose(&Circuit::new(2), &[0]);
+    }
+
+    #[test]
+    fn inverse_undoes_every_gate() {
+        let c = every_gate();
+        let mut round_trip = c.clone();
+        round_trip.compose(&c.inverse(), &[0, 1, 2, 3]);
+        assert!(round_trip.equivalent_to(&Circuit::new(4), EPSILON));
+
+        // exactly, not only up to phase
+        for x in [0, 5, 10, 15] {
+            let mut expected = vec![C::new(0.0, 0.0); 16];
+            expected[x] = C::new(1.0, 0.0);
+            assert_close(&run(&round_trip, x as u64), &expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "only circuits of gates can be inverted")]
+    fn measurements_cannot_be_inverted() {
+        Circuit::new(1).measure(0, 0).inverse();
+    }
+
+    #[test]
+    fn power_repeats() {
+        let mut t = Circuit::new(1);
+        t.t(0);
+        let mut z = Circuit::new(1);
+        z.z(0);
+        assert!(t.power(4).equivalent_to(&z, EPSILON));
+        assert!(t.power(0).is_empty());
+        assert_eq!(t.power(3).len(), 3);
+    }
+
+    #[test]
+    fn controlled_acts_only_when_the_control_is_set() {
+        let u = every_gate();
+        let cu = u.controlled();
+        assert_eq!(cu.num_qubits(), 5);
+
+        for x in [0, 3, 6, 9, 15] {
+            let mut idle = vec![C::new(0.0, 0.0); 32];
+            idle[(x << 1) as usize] = C::new(1.0, 0.0);
+            assert_close(&run(&cu, x << 1), &idle);
+
+            let applied = run(&u, x);
+            let mut expected = vec![C::new(0.0, 0.0); 32];
+            for (y, a) in applied.into_iter().enumerate() {
+                expected[(y << 1) | 1] = a;
+            }
+            assert_close(&run(&cu, (x << 1) | 1), &expected);
+        }
+    }
+
+    #[test]
+    fn controlled_random_circuit() {
+        let u = Circuit::random(2, 3, &mut StdRng::seed_from_u64(4));
+        let cu = u.controlled();
+        for x in 0..4 {
+            let mut expected = vec![C::new(0.0, 0.0); 8];
+            for (y, a) in run(&u, x).into_iter().enumerate() {
+                expected[(y << 1) | 1] = a;
+            }
+            assert_close(&run(&cu, (x << 1) | 1), &expected);
+        }
+    }
+
+    #[test]
+    fn global_phase_matters_once_controlled() {
+        let mut minus = Circuit::new(1);
+        minus.x(0).add_global_phase(PI);
+        assert!(minus.equivalent_to(&minus.without_global_phase(), EPSILON));
+        assert_ne!(minus, minus.without_global_phase());
+
+        // −X controlled is CNOT followed by Z on the control
+        let mut expected = Circuit::new(2);
+        expected.cnot(0, 1).z(0);
+        assert!(minus.controlled().equivalent_to(&expected, EPSILON));
+        assert!(!minus
+            .without_global_phase()
+            .controlled()
+            .equivalent_to(&expected, EPSILON));
+
+        assert!((minus.inverse().global_phase() - PI).abs() < EPSILON);
+        assert!(minus.power(2).global_phase().abs() < EPSILON);
+    }
+
+    #[test]
+    #[should_panic(expected = "only circuits of gates can be controlled")]
+    fn conditionals_cannot_be_controlled() {
+        Circuit::new(2)
+            .measure(0, 0)
+            .c_if(0, Gate::X, &[1])
+            .controlled();
+    }
+}
@@ -0,0 +1,180 @@
+//! Classical control flow: loops that run a circuit again while a classical
+//! bit holds a value, as repeat-until-success schemes call for. Loops are
+//! bounded, so that every run of a circuit ends.
+
+use super::{Circuit, Instruction};
+
+impl Circuit {
+    /// Runs `body` for as long as `clbit` reads `value`, but at most
+    /// `max_iterations` times, checking the bit before each run. The body
+    /// acts on the same qubits and classical bits as the circuit, and any
+    /// bits it uses are added to the circuit.
+    pub fn while_loop(
+        &mut self,
+        clbit: usize,
+        value: bool,
+        body: &Circuit,
+        max_iterations: usize,
+    ) -> &mut Self {
+        assert_eq!(
+            body.num_qubits, self.num_qubits,
+            "loop body on {} qubits in a circuit on {}",
+            body.num_qubits, self.num_qubits
+        );
+        self.num_clbits = self.num_clbits.max(body.num_clbits).max(clbit + 1);
+        self.instructions.push(Instruction::While {
+            clbit,
+            value,
+            max_iterations,
+            body: Box::new(body.clone()),
+        });
+        self
+    }
+
+    /// Runs `body` until `clbit` reads `value`, at most `max_attempts` times
+    /// in all. The first attempt is unconditional, so the body is expected
+    /// to write the bit, typically by measuring a flag qubit.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// // toss a coin until it comes up 1
+    /// let mut toss = Circuit::new(1);
+    /// toss.reset(0).h(0).measure(0, 0);
+    ///
+    /// let mut c = Circuit::new(1);
+    /// c.repeat_until(0, true, &toss, 30);
+    /// assert_eq!(c.run(&mut StdRng::seed_from_u64(1)).clbits(), [true]);
+    /// ```
+    pub fn repeat_until(
+        &mut self,
+        clbit: usize,
+        value: bool,
+        body: &Circuit,
+        max_attempts: usize,
+    ) -> &mut Self {
+        assert!(max_attempts > 0, "repeat_until needs at least one attempt");
+        let qubits: Vec<usize> = (0..self.num_qubits as usize).collect();
+        self.compose(body, &qubits);
+        self.while_loop(clbit, !value, body, max_attempts - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{Circuit, Gate};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn toss() -> Circuit {
+        let mut toss = Circuit::new(2);
+        toss.reset(0).h(0).measure(0, 0);
+        toss
+    }
+
+    #[test]
+    fn repeats_until_success() {
+        // count the attempts, mod 2, on qubit 1
+        let mut body = toss();
+        body.x(1);
+        let mut c = Circuit::new(2);
+        c.repeat_until(0, true, &body, 40).measure(1, 1);
+
+        let mut attempts = [0; 2];
+        for seed in 0..50 {
+            let run = c.run(&mut StdRng::seed_from_u64(seed));
+            assert!(run.clbits()[0]);
+            attempts[run.clbits()[1] as usize] += 1;
+        }
+        // an odd number of attempts is twice as likely as an even one
+        assert!(attempts[0] > 5 && attempts[1] > attempts[0]);
+    }
+
+    #[test]
+    fn stops_after_max_iterations() {
+        // qubit 0 always reads 0, so the loop runs out its bound
+        let mut body = Circuit::new(2);
+        body.x(1).measure(0, 0);
+
+        for (bound, flipped) in [(0, false), (3, true), (4, false)] {
+            let mut c = Circuit::new(2);
+            c.while_loop(0, false, &body, bound).measure(1, 1);
+            let run = c.run(&mut StdRng::seed_from_u64(0));
+            assert_eq!(run.clbits(), [false, flipped]);
+        }
+    }
+
+    #[test]
+    fn conditions_are_checked_first() {
+        let mut body = Circuit::new(1);
+        body.x(0);
+        let mut c = Circuit::new(1);
+        c.x(0).measure(0, 0).while_loop(0, false, &body, 5);
+        assert_eq!(c.run(&mut StdRng::seed_from_u64(0)).clbits(), [true]);
+    }
+
+    #[test]
+    fn density_follows_every_branch() {
+        let mut c = Circuit::new(2);
+        c.repeat_until(0, true, &toss(), 3);
+
+        // fails only if all three tosses come up 0
+        let rho = c.simulate_density();
+        assert!((rho.probability(0b01).re - 7.0 / 8.0).abs() < EPSILON);
+        assert!((rho.probability(0b00).re - 1.0 / 8.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn loops_take_a_moment_of_their_own() {
+        let mut body = Circuit::new(3);
+        body.h(0).measure(0, 0);
+        let mut c = Circuit::new(3);
+        c.h(1)
+            .h(2)
+            .while_loop(0, false, &body, 2)
+            .h(2)
+            .c_if(0, Gate::X, &[1]);
+
+        assert_eq!(c.schedule(), [Some(0), Some(0), Some(1), Some(2), Some(2)]);
+        assert_eq!(c.gate_counts()["while"], 1);
+    }
+
+    #[test]
+    fn composing_maps_the_body() {
+        let mut body = Circuit::new(1);
+        body.h(0).measure(0, 0);
+        let mut inner = Circuit::new(1);
+        inner.while_loop(0, false, &body, 2);
+
+        let mut c = Circuit::new(2);
+        c.compose(&inner, &[1]);
+
+        let mut mapped = Circuit::with_clbits(2, 1);
+        mapped.h(1).measure(1, 0);
+        let mut expected = Circuit::new(2);
+        expected.while_loop(0, false, &mapped, 2);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn parameters_of_the_body() {
+        let mut body = Circuit::new(1);
+        body.rx(0, "theta").measure(0, 0);
+        let mut c = Circuit::new(1);
+        c.while_loop(0, false, &body, 2);
+
+        assert_eq!(c.parameters(), ["theta"]);
+        assert!(c.bind_parameters(&[("theta", 1.0)]).parameters().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "loop body on 1 qubits in a circuit on 2")]
+    fn bodies_span_the_circuit() {
+        Circuit::new(2).while_loop(0, true, &Circuit::new(1), 1);
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::Circuit;
+
+/// How often each value of an n-bit classical register came up over a number
+/// of shots. Outcomes are packed like [`Run::register`](super::Run::register),
+/// bit i from clbit i.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counts {
+    num_bits: usize,
+    counts: BTreeMap<u64, usize>,
+}
+
+impl Counts {
+    /// No shots yet, over a register of `num_bits` bits.
+    pub fn new(num_bits: usize) -> Self {
+        Counts {
+            num_bits,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// Adds `count` shots of `outcome`.
+    pub fn insert(&mut self, outcome: u64, count: usize) {
+        assert!(
+            self.num_bits >= 64 || outcome >> self.num_bits == 0,
+            "outcome {outcome} does not fit in {} bits",
+            self.num_bits
+        );
+        if count > 0 {
+            *self.counts.entry(outcome).or_insert(0) += count;
+        }
+    }
+
+    pub fn get(&self, outcome: u64) -> usize {
+        self.counts.get(&outcome).copied().unwrap_or(0)
+    }
+
+    /// Total number of shots.
+    pub fn shots(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// The outcomes seen, in increasing order, with their counts.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, usize)> + '_ {
+        self.counts
+            .iter()
+            .map(|(&outcome, &count)| (outcome, count))
+    }
+
+    /// The outcome seen most often, the smallest one on a tie.
+    pub fn most_frequent(&self) -> Option<u64> {
+        self.iter()
+            .max_by(|(a, m), (b, n)| m.cmp(n).then(b.cmp(a)))
+            .map(|(outcome, _)| outcome)
+    }
+
+    /// The fraction of shots that gave `outcome`.
+    pub fn probability(&self, outcome: u64) -> f64 {
+        self.get(outcome) as f64 / self.shots() as f64
+    }
+
+    /// The empirical distribution over the outcomes seen.
+    pub fn probabilities(&self) -> BTreeMap<u64, f64> {
+        let shots = self.shots() as f64;
+        self.iter()
+            .map(|(outcome, count)| (outcome, count as f64 / shots))
+            .collect()
+    }
+
+    /// The counts of the sub-register made of `bits`, its bit i taken from
+    /// bit `bits[i]` of each outcome.
+    pub fn marginal(&self, bits: &[usize]) -> Counts {
+        for &b in bits {
+            assert!(
+                b < self.num_bits,
+                "bit {b} out of range for {} bits",
+                self.num_bits
+            );
+        }
+        let mut marginal = Counts::new(bits.len());
+        for (outcome, count) in self.iter() {
+            let reduced = bits
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, &b)| acc | (outcome >> b & 1) << i);
+            marginal.insert(reduced, count);
+        }
+        marginal
+    }
+
+    /// ½ Σ |p(x) − q(x)| between the empirical distribution p and `expected`,
+    /// indexed by outcome as from [`Circuit::average_probabilities`].
+    pub fn total_variation_distance(&self, expected: &[f64]) -> f64 {
+        let observed = self.probabilities();
+        let unexpected: f64 = observed
+            .range(expected.len() as u64..)
+            .map(|(_, p)| p)
+            .sum();
+        let distance: f64 = expected
+            .iter()
+            .enumerate()
+            .map(|(x, q)| (observed.get(&(x as u64)).unwrap_or(&0.0) - q).abs())
+            .sum();
+        (distance + unexpected) / 2.0
+    }
+
+    /// The linear cross-entropy benchmarking fidelity D·⟨p(x)⟩ − 1 of the
+    /// shots x against the `ideal` probabilities p over D outcomes, indexed
+    /// by outcome: about 1 for samples of a random circuit's ideal output,
+    /// whose probabilities follow the Porter–Thomas distribution, and 0 for
+    /// uniform noise. Under global depolarising noise it estimates the
+    /// fraction of the ideal state that survives. After Arute et al.,
+    /// "Quantum supremacy using a programmable superconducting processor"
+    /// (2019).
+    pub fn linear_xeb_fidelity(&self, ideal: &[f64]) -> f64 {
+        let total: f64 = self
+            .iter()
+            .map(|(x, count)| ideal.get(x as usize).unwrap_or(&0.0) * count as f64)
+            .sum();
+        ideal.len() as f64 * total / self.shots() as f64 - 1.0
+    }
+
+    /// Adds the shots of `other`, over a register of the same size.
+    pub fn merge(&mut self, other: &Counts) {
+        assert_eq!(
+            self.num_bits, other.num_bits,
+            "merging counts over different registers"
+        );
+        for (outcome, count) in other.iter() {
+            self.insert(outcome, count);
+        }
+    }
+}
+
+/// Outcomes as bitstrings with clbit 0 rightmost, as in Qiskit:
+/// `{00: 51, 11: 49}`.
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, (outcome, count)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{outcome:0width$b}: {count}", width = self.num_bits)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl Circuit {
+    /// Runs the circuit `shots` times and tallies the classical register.
+    pub fn sample<R: rand::Rng + ?Sized>(&self, shots: usize, rng: &mut R) -> Counts {
+        let mut counts = Counts::new(self.num_clbits);
+        for _ in 0..shots {
+            counts.insert(self.run(&mut *rng).register(), 1);
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Counts;
+    use crate::channel::Channel;
+    use crate::circuit::Circuit;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn counts(num_bits: usize, entries: &[(u64, usize)]) -> Counts {
+        let mut counts = Counts::new(num_bits);
+        for &(outcome, count) in entries {
+            counts.insert(outcome, count);
+        }
+        counts
+    }
+
+    #[test]
+    fn bell_state_samples() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+
+        let counts = c.sample(1000, &mut StdRng::seed_from_u64(2));
+        assert_eq!(counts.shots(), 1000);
+        assert_eq!(counts.get(0b01) + counts.get(0b10), 0);
+        assert!(counts.total_variation_distance(&[0.5, 0.0, 0.0, 0.5]) < 0.05);
+    }
+
+    #[test]
+    fn most_frequent_prefers_smaller_on_ties() {
+        assert_eq!(Counts::new(2).most_frequent(), None);
+        assert_eq!(
+            counts(2, &[(3, 5), (1, 5), (2, 1)]).most_frequent(),
+            Some(1)
+        );
+        assert_eq!(counts(2, &[(3, 6), (1, 5)]).most_frequent(), Some(3));
+    }
+
+    #[test]
+    fn probabilities_sum_to_one() {
+        let c = counts(2, &[(0, 1), (2, 3)]);
+        assert!((c.probability(2) - 0.75).abs() < EPSILON);
+        assert_eq!(c.probability(1), 0.0);
+        assert!((c.probabilities().values().sum::<f64>() - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn marginal_reorders_bits() {
+        let c = counts(3, &[(0b110, 2), (0b010, 3), (0b001, 4)]);
+
+        let m = c.marginal(&[2, 0]);
+        assert_eq!(m.num_bits(), 2);
+        assert_eq!(m.get(0b01), 2);
+        assert_eq!(m.get(0b00), 3);
+        assert_eq!(m.get(0b10), 4);
+
+        assert_eq!(c.marginal(&[1]), counts(1, &[(1, 5), (0, 4)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "bit 3 out of range for 3 bits")]
+    fn marginal_rejects_missing_bits() {
+        counts(3, &[(0b001, 1)]).marginal(&[0, 3]);
+    }
+
+    #[test]
+    fn total_variation_counts_unexpected_outcomes() {
+        let c = counts(2, &[(0, 1), (3, 1)]);
+        assert!((c.total_variation_distance(&[0.5, 0.5]) - 0.5).abs() < EPSILON);
+        assert!((c.total_variation_distance(&[0.5, 0.0, 0.0, 0.5])).abs() < EPSILON);
+    }
+
+    #[test]
+    fn linear_xeb_of_ideal_and_uniform_samples() {
+        let ideal = [0.5, 0.25, 0.125, 0.125];
+        // D Σ p² − 1 when sampled exactly from p
+        let exact = counts(2, &[(0, 4), (1, 2), (2, 1), (3, 1)]);
+        let expected = 4.0 * (0.25 + 0.0625 + 2.0 * 0.015625) - 1.0;
+        assert!((exact.linear_xeb_fidelity(&ideal) - expected).abs() < EPSILON);
+        let uniform = counts(2, &[(0, 1), (1, 1), (2, 1), (3, 1)]);
+        assert!(uniform.linear_xeb_fidelity(&ideal).abs() < EPSILON);
+    }
+
+    #[test]
+    fn linear_xeb_falls_with_noise() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let c = Circuit::random(4, 6, &mut rng);
+        let ideal: Vec<f64> = c
+            .simulate()
+            .amplitudes(4)
+            .iter()
+            .map(|a| a.mod_squared())
+            .collect();
+        let measured = |c: &Circuit| {
+            let mut c = c.clone();
+            for q in 0..4 {
+                c.measure(q, q);
+            }
+            c
+        };
+
+        let clean = measured(&c)
+            .sample(4000, &mut rng)
+            .linear_xeb_fidelity(&ideal);
+        let porter_thomas = 16.0 * ideal.iter().map(|p| p * p).sum::<f64>() - 1.0;
+        assert!(
+            (clean - porter_thomas).abs() < 0.15,
+            "{clean} vs {porter_thomas}"
+        );
+
+        let noisy = c.with_noise(|_| Some(Channel::depolarizing(0.05)));
+        let density = noisy.simulate_density();
+        let expected = 16.0
+            * (0..16)
+                .map(|x| density.probability(x).re * ideal[x as usize])
+                .sum::<f64>()
+            - 1.0;
+        let estimate = measured(&noisy)
+            .sample(4000, &mut rng)
+            .linear_xeb_fidelity(&ideal);
+        assert!(
+            (estimate - expected).abs() < 0.15,
+            "{estimate} vs {expected}"
+        );
+        assert!(estimate < clean - 0.2, "{estimate} vs {clean}");
+    }
+
+    #[test]
+    fn merge_adds_shots() {
+        let mut a = counts(2, &[(0, 1), (1, 2)]);
+        a.merge(&counts(2, &[(1, 3), (2, 4)]));
+        assert_eq!(a, counts(2, &[(0, 1), (1, 5), (2, 4)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "different registers")]
+    fn merge_rejects_other_sizes() {
+        Counts::new(2).merge(&Counts::new(3));
+    }
+
+    #[test]
+    fn display_as_bitstrings() {
+        assert_eq!(
+            counts(3, &[(0b011, 2), (0, 1)]).to_string(),
+            "{000: 1, 011: 2}"
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Expansion of Toffoli and multi-controlled X gates into CNOTs and
+//! single-qubit gates.
+
+use std::f64::consts::PI;
+
+use super::{Circuit, Gate, Instruction};
+
+impl Circuit {
+    /// An equivalent circuit with every Toffoli and multi-controlled X
+    /// expanded into CNOTs and single-qubit gates, without extra qubits. A
+    /// gate with k controls becomes O(2^k) gates, from the Gray-code
+    /// construction of the multi-controlled phase gate.
+    pub fn decompose_multi_controlled(&self) -> Circuit {
+        self.decompose_multi_controlled_with(&[])
+    }
+
+    /// [`Circuit::decompose_multi_controlled`], except that a gate with k > 2
+    /// controls becomes a V-chain of 2k − 3 Toffolis when at least k − 2 of
+    /// `ancillas` are not among its qubits. The ancillas must hold |0⟩
+    /// wherever such a gate is applied, and are returned to |0⟩.
+    pub fn decompose_multi_controlled_with(&self, ancillas: &[usize]) -> Circuit {
+        for &q in ancillas {
+            self.check_qubit(q);
+        }
+
+        let mut instructions = vec![];
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => {
+                    instructions.extend(
+                        expand(gate, qubits, ancillas)
+                            .into_iter()
+                            .map(|(gate, qubits)| Instruction::Gate { gate, qubits }),
+                    );
+                }
+                Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => {
+                    instructions.extend(expand(gate, qubits, ancillas).into_iter().map(
+                        |(gate, qubits)| Instruction::Conditional {
+                            clbit: *clbit,
+                            gate,
+                            qubits,
+                        },
+                    ));
+                }
+                Instruction::While {
+                    clbit,
+                    value,
+                    max_iterations,
+                    body,
+                } => instructions.push(Instruction::While {
+                    clbit: *clbit,
+                    value: *value,
+                    max_iterations: *max_iterations,
+                    body: Box::new(body.decompose_multi_controlled_with(ancillas)),
+                }),
+                other => instructions.push(other.clone()),
+            }
+        }
+
+        Circuit {
+            instructions,
+            ..self.clone()
+        }
+    }
+}
+
+/// `gate` on `qubits` as a sequence of CNOTs and single-qubit gates.
+pub(super) fn expand(gate: &Gate, qubits: &[usize], ancillas: &[usize]) -> Vec<(Gate, Vec<usize>)> {
+    let mut out = vec![];
+
+    match gate {
+        Gate::Ccx => toffoli(&mut out, qubits[0], qubits[1], qubits[2]),
+        Gate::Mcx(_) => {
+            let (&target, controls) = qubits.split_last().unwrap();
+            let free: Vec<usize> = ancillas
+                .iter()
+                .copied()
+                .filter(|a| !qubits.contains(a))
+                .collect();
+
+            match controls.len() {
+                0 => out.push((Gate::X, vec![target])),
+                1 => out.push((Gate::Cx, qubits.to_vec())),
+                2 => toffoli(&mut out, controls[0], controls[1], target),
+                k if free.len() >= k - 2 => v_chain(&mut out, controls, target, &free),
+                _ => {
+                    out.push((Gate::H, vec![target]));
+                    multi_controlled_phase(&mut out, PI, qubits);
+                    out.push((Gate::H, vec![target]));
+                }
+            }
+        }
+        _ => out.push((gate.clone(), qubits.to_vec())),
+    }
+
+    out
+}
+
+/// The Toffoli gate in six CNOTs, as defined in `qelib1.inc`.
+fn toffoli(out: &mut Vec<(Gate, Vec<usize>)>, a: usize, b: usize, c: usize) {
+    out.extend([
+        (Gate::H, vec![c]),
+        (Gate::Cx, vec![b, c]),
+        (Gate::Tdg, vec![c]),
+        (Gate::Cx, vec![a, c]),
+        (Gate::T, vec![c]),
+        (Gate::Cx, vec![b, c]),
+        (Gate::Tdg, vec![c]),
+        (Gate::Cx, vec![a, c]),
+        (Gate::T, vec![b]),
+        (Gate::T, vec![c]),
+        (Gate::H, vec![c]),
+        (Gate::Cx, vec![a, b]),
+        (Gate::T, vec![a]),
+        (Gate::Tdg, vec![b]),
+        (Gate::Cx, vec![a, b]),
+    ]);
+}
+
+/// Multi-controlled X computing the AND of the controls into a chain of
+/// ancillas, then uncomputing it.
+fn v_chain(
+    out: &mut Vec<(Gate, Vec<usize>)>,
+    controls: &[usize],
+    target: usize,
+    ancillas: &[usize],
+) {
+    let k = controls.len();
+
+    let compute = |out: &mut Vec<(Gate, Vec<usize>)>| {
+        toffoli(out, controls[0], controls[1], ancillas[0]);
+        for i in 2..k - 1 {
+            toffoli(out, controls[i], ancillas[i - 2], ancillas[i - 1]);
+        }
+    };
+    let uncompute = |out: &mut Vec<(Gate, Vec<usize>)>| {
+        for i in (2..k - 1).rev() {
+            toffoli(out, controls[i], ancillas[i - 2], ancillas[i - 1]);
+        }
+        toffoli(out, controls[0], controls[1], ancillas[0]);
+    };
+
+    compute(out);
+    toffoli(out, controls[k - 1], ancillas[k - 3], target);
+    uncompute(out);
+}
+
+/// The phase e^(iλ) on the all-ones state of `qubits`, from
+///
+/// x₁x₂…xₙ = 2^(1−n) Σ (−1)^(|S|+1) ⊕_{i∈S} xᵢ
+///
+/// over the non-empty subsets S. Walking the subsets in Gray-code order, each
+/// parity is one CNOT away from the last and is kept on the highest qubit of
+/// the subset, where its phase is applied.
+fn multi_controlled_phase(out: &mut Vec<(Gate, Vec<usize>)>, lambda: f64, qubits: &[usize]) {
+    let n = qubits.len();
+    let angle = lambda / (1u64 << (n - 1)) as f64;
+
+    for i in 1..1usize << n {
+        let subset = i ^ (i >> 1);
+        let lead = (usize::BITS - 1 - subset.leading_zeros()) as usize;
+        let flipped = i.trailing_zeros() as usize;
+
+        if flipped == lead {
+            // first subset led by this qubit, which still holds only itself
+            for b in (0..lead).filter(|b| subset >> b & 1 == 1) {
+                out.push((Gate::Cx, vec![qubits[b], qubits[lead]]));
+            }
+        } else {
+            out.push((Gate::Cx, vec![qubits[flipped], qubits[lead]]));
+        }
+
+        let sign = if subset.count_ones() % 2 == 1 {
+            1.0
+        } else {
+            -1.0
+        };
+        out.push((Gate::Phase((sign * angle).into()), vec![qubits[lead]]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{Circuit, Gate, Instruction};
+    use crate::ket::State;
+    use crate::Complex as C;
+
+    const EPSILON: f64 = 0.0000001;
+
+    /// Whether `a` and `b` agree on the basis states `inputs`.
+    fn assert_same_action(a: &Circuit, b: &Circuit, inputs: impl Iterator<Item = u64>) {
+        let n = a.num_qubits();
+        let (ua, ub) = (a.unitary(), b.unitary());
+        for x in inputs {
+            let (sa, sb) = (
+                (ua.clone() * State::basis(x, n)).amplitudes(n),
+                (ub.clone() * State::basis(x, n)).amplitudes(n),
+            );
+            for (p, q) in sa.iter().zip(&sb) {
+                assert!((*p - *q).modulus() < EPSILON, "differs on |{x:b}⟩");
+            }
+        }
+    }
+
+    fn only_small_gates(c: &Circuit) -> bool {
+        c.instructions()
+            .iter()
+            .all(|instruction| match instruction {
+                Instruction::Gate { qubits, .. } => qubits.len() <= 2,
+                _ => true,
+            })
+    }
+
+    #[test]
+    fn mcx_picks_the_smallest_gate() {
+        let mut c = Circuit::new(5);
+        c.mcx(&[], 0)
+            .mcx(&[1], 0)
+            .mcx(&[1, 2], 0)
+            .mcx(&[1, 2, 3, 4], 0);
+
+        let gates: Vec<&Gate> = c
+            .instructions()
+            .iter()
+            .map(|instruction| match instruction {
+                Instruction::Gate { gate, .. } => gate,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(gates, [&Gate::X, &Gate::Cx, &Gate::Ccx, &Gate::Mcx(4)]);
+    }
+
+    #[test]
+    fn mcx_flips_only_when_all_controls_are_set() {
+        let mut c = Circuit::new(4);
+        c.x(0).x(2).x(3).mcx(&[0, 2, 3], 1);
+        assert_eq!(c.simulate().amplitudes(4)[0b1111], C::new(1.0, 0.0));
+
+        let mut c = Circuit::new(4);
+        c.x(0).x(3).mcx(&[0, 2, 3], 1);
+        assert_eq!(c.simulate().amplitudes(4)[0b1001], C::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn toffoli_decomposition() {
+        let mut c = Circuit::new(3);
+        c.ccx(2, 0, 1);
+
+        let decomposed = c.decompose_multi_controlled();
+        assert!(only_small_gates(&decomposed));
+        assert_eq!(decomposed.gate_counts()["cx"], 6);
+        assert_same_action(&c, &decomposed, 0..8);
+    }
+
+    #[test]
+    fn gray_code_decomposition() {
+        for (controls, target) in [(vec![0, 1, 2], 3), (vec![4, 0, 3, 1], 2)] {
+            let mut c = Circuit::new(5);
+            c.h(0).mcx(&controls, target);
+
+            let decomposed = c.decompose_multi_controlled();
+            assert!(only_small_gates(&decomposed));
+            assert_same_action(&c, &decomposed, 0..32);
+        }
+    }
+
+    #[test]
+    fn v_chain_decomposition() {
+        let mut c = Circuit::new(7);
+        c.mcx(&[0, 1, 2, 4], 3);
+
+        // qubit 3 is the target, leaving 5 and 6 as ancillas
+        let decomposed = c.decompose_multi_controlled_with(&[3, 5, 6]);
+        assert!(only_small_gates(&decomposed));
+        // 2k − 3 Toffolis of six CNOTs each
+        assert_eq!(decomposed.gate_counts()["cx"], 5 * 6);
+        assert!(decomposed.instructions().iter().any(|instruction| matches!(
+            instruction,
+            Instruction::Gate { qubits, .. } if qubits.contains(&5)
+        )));
+
+        // the ancillas start out in |0⟩
+        assert_same_action(&c, &decomposed, 0..32);
+    }
+
+    #[test]
+    fn too_few_ancillas_fall_back_to_gray_code() {
+        let mut c = Circuit::new(6);
+        c.mcx(&[0, 1, 2, 3, 4], 5);
+
+        let decomposed = c.decompose_multi_controlled_with(&[4]);
+        assert!(decomposed.gate_counts().contains_key("p"));
+        assert_same_action(&c, &decomposed, 0..64);
+    }
+
+    #[test]
+    fn conditional_gates_stay_conditional() {
+        let mut c = Circuit::new(3);
+        c.measure(0, 0).c_if(0, Gate::Ccx, &[0, 1, 2]);
+
+        let decomposed = c.decompose_multi_controlled();
+        assert_eq!(decomposed.len(), 16);
+        assert!(decomposed.instructions()[1..]
+            .iter()
+            .all(|instruction| matches!(instruction, Instruction::Conditional { clbit: 0, .. })));
+    }
+}
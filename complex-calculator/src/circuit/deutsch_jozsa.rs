@@ -0,0 +1,106 @@
+//! The Deutsch–Jozsa algorithm, which tells with one query whether a
+//! function on n bits promised to be constant or balanced is which, where
+//! a deterministic classical algorithm needs 2^(n−1) + 1.
+
+use super::Circuit;
+
+/// The two kinds of function the Deutsch–Jozsa promise allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionKind {
+    /// The same output for every input.
+    Constant,
+    /// 0 for exactly half the inputs and 1 for the other half.
+    Balanced,
+}
+
+impl FunctionKind {
+    /// The answer read from the classical register of a run of
+    /// [`Circuit::deutsch_jozsa`]: all zeros for a constant function, and
+    /// anything else for a balanced one.
+    pub fn from_register(register: u64) -> Self {
+        if register == 0 {
+            FunctionKind::Constant
+        } else {
+            FunctionKind::Balanced
+        }
+    }
+}
+
+impl Circuit {
+    /// The Deutsch–Jozsa circuit for `f` on n input qubits and one output
+    /// qubit n: Hadamards on the inputs and on the output in |1⟩, one
+    /// [`Circuit::bit_oracle`] query, and Hadamards again before input i is
+    /// measured into bit i. The output in |−⟩ kicks the phase (−1)^f(x)
+    /// back onto the inputs, which all read 0 only if f is constant.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::{Circuit, FunctionKind};
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let run = Circuit::deutsch_jozsa(4, |x| x.count_ones() % 2 == 1)
+    ///     .run(&mut StdRng::seed_from_u64(1));
+    /// assert_eq!(FunctionKind::from_register(run.register()), FunctionKind::Balanced);
+    /// ```
+    pub fn deutsch_jozsa(n: u32, f: impl Fn(u32) -> bool) -> Circuit {
+        let inputs: Vec<usize> = (0..n as usize).collect();
+        let output = n as usize;
+        let mut c = Circuit::new(n + 1);
+        c.x(output).h(output);
+        for &q in &inputs {
+            c.h(q);
+        }
+        c.compose(
+            &Circuit::bit_oracle(n, f),
+            &(0..=output).collect::<Vec<_>>(),
+        );
+        for &q in &inputs {
+            c.h(q).measure(q, q);
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FunctionKind;
+    use crate::circuit::Circuit;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn classify(n: u32, f: impl Fn(u32) -> bool, seed: u64) -> FunctionKind {
+        let c = Circuit::deutsch_jozsa(n, f);
+        FunctionKind::from_register(c.run(&mut StdRng::seed_from_u64(seed)).register())
+    }
+
+    #[test]
+    fn constant_functions() {
+        for n in 1..=4 {
+            for seed in 0..5 {
+                assert_eq!(classify(n, |_| false, seed), FunctionKind::Constant);
+                assert_eq!(classify(n, |_| true, seed), FunctionKind::Constant);
+            }
+        }
+    }
+
+    #[test]
+    fn balanced_functions() {
+        let functions: [fn(u32) -> bool; 3] =
+            [|x| x & 1 == 1, |x| x >= 4, |x| matches!(x, 0 | 3 | 5 | 6)];
+        for f in functions {
+            for seed in 0..5 {
+                assert_eq!(classify(3, f, seed), FunctionKind::Balanced);
+            }
+        }
+    }
+
+    #[test]
+    fn one_query() {
+        let c = Circuit::deutsch_jozsa(3, |x| x == 1 || x == 2 || x == 4 || x == 7);
+        assert_eq!(c.num_qubits(), 4);
+        assert_eq!(c.num_clbits(), 3);
+        // one controlled X per input f maps to 1
+        assert_eq!(c.gate_counts()["mcx"], 4);
+    }
+}
@@ -0,0 +1,125 @@
+//! Preparation of real amplitude encodings by recursive rotations, after
+//! Grover and Rudolph, "Creating superpositions that correspond to
+//! efficiently integrable probability distributions" (2002): each qubit from
+//! the top splits the weight of the half of the vector it is in by an Ry
+//! controlled on the qubits above it, and the last one also sets the signs.
+
+use super::Circuit;
+
+impl Circuit {
+    /// A circuit taking |0…0⟩ to [`State::from_data`] exactly, without a
+    /// global phase: an Ry on the top qubit, then on each qubit below an Ry
+    /// multiplexed on the qubits above. On n qubits that is at most 2^n − 1
+    /// rotations, as rotations by 0 are left out, and 2^(n+1) − 2n − 2 CNOTs.
+    ///
+    /// [`State::from_data`]: crate::ket::State::from_data
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// let amplitudes = Circuit::amplitude_encoding(&[1.0, -1.0, 1.0, 1.0]).simulate().amplitudes(2);
+    /// for (a, expected) in amplitudes.iter().zip([0.5, -0.5, 0.5, 0.5]) {
+    ///     assert!((a.re - expected).abs() < 1e-12);
+    /// }
+    /// ```
+    pub fn amplitude_encoding(data: &[f64]) -> Circuit {
+        let norm = data.iter().map(|x| x * x).sum::<f64>().sqrt();
+        assert!(norm > 0.0, "data of norm 0 encodes no state");
+        let mut amplitudes = data.to_vec();
+        amplitudes.resize(data.len().next_power_of_two().max(2), 0.0);
+        let n = amplitudes.len().trailing_zeros() as usize;
+
+        let mut c = Circuit::new(n as u32);
+        for target in (0..n).rev() {
+            // the blocks of 2^target entries sharing the bits above target,
+            // split in two by its bit, each rotated from |0⟩ to (cos, sin) of
+            // half the angle; weights above the last qubit, signed entries on it
+            let block = 1 << target;
+            let angles: Vec<f64> = amplitudes
+                .chunks(2 * block)
+                .map(|chunk| {
+                    let (low, high) = chunk.split_at(block);
+                    if target == 0 {
+                        2.0 * high[0].atan2(low[0])
+                    } else {
+                        let weight = |half: &[f64]| half.iter().map(|x| x * x).sum::<f64>().sqrt();
+                        2.0 * weight(high).atan2(weight(low))
+                    }
+                })
+                .collect();
+            let controls: Vec<usize> = (target + 1..n).collect();
+            c.multiplexed_ry(&angles, &controls, target);
+        }
+        c
+    }
+
+    /// Ry(`angles[j]`) on `target` when the `controls` read j, little-endian,
+    /// by halving on the last control: Ry((θ₀ + θ₁)/2) multiplexed on the
+    /// rest, a CNOT, Ry((θ₀ − θ₁)/2) multiplexed on the rest, and a CNOT, as
+    /// X Ry(φ) X = Ry(−φ).
+    fn multiplexed_ry(&mut self, angles: &[f64], controls: &[usize], target: usize) {
+        let Some((&last, rest)) = controls.split_last() else {
+            if angles[0] != 0.0 {
+                self.ry(target, angles[0]);
+            }
+            return;
+        };
+        let (zero, one) = angles.split_at(angles.len() / 2);
+        let sums: Vec<f64> = zero.iter().zip(one).map(|(a, b)| (a + b) / 2.0).collect();
+        let differences: Vec<f64> = zero.iter().zip(one).map(|(a, b)| (a - b) / 2.0).collect();
+        self.multiplexed_ry(&sums, rest, target);
+        self.cnot(last, target);
+        self.multiplexed_ry(&differences, rest, target);
+        self.cnot(last, target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+    use crate::ket::State;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn prepares_random_data() {
+        let mut rng = StdRng::seed_from_u64(8);
+        for len in [2, 3, 4, 7, 8, 16, 32] {
+            let data: Vec<f64> = (0..len).map(|_| rng.random_range(-1.0..1.0)).collect();
+            let c = Circuit::amplitude_encoding(&data);
+            let n = c.num_qubits();
+            let expected = State::from_data(&data).amplitudes(n);
+            for (a, b) in c.simulate().amplitudes(n).iter().zip(&expected) {
+                assert!((*a - *b).modulus() < EPSILON, "{data:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn gate_counts() {
+        let data: Vec<f64> = (1..=16).map(f64::from).collect();
+        let c = Circuit::amplitude_encoding(&data);
+        assert_eq!(c.gate_counts()["ry"], 15);
+        assert_eq!(c.gate_counts()["cx"], 2 + 6 + 14);
+    }
+
+    #[test]
+    fn pads_to_a_power_of_two() {
+        let c = Circuit::amplitude_encoding(&[0.0, 0.0, 0.0, 0.0, 0.0, 2.0]);
+        let amplitudes = c.simulate().amplitudes(3);
+        assert!((amplitudes[5].re - 1.0).abs() < EPSILON);
+        assert_eq!(
+            State::from_data(&[0.0, 0.0, 0.0, 0.0, 0.0, 2.0]).amplitudes(3)[5].re,
+            1.0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "data of norm 0 encodes no state")]
+    fn rejects_zero_data() {
+        Circuit::amplitude_encoding(&[0.0, 0.0]);
+    }
+}
@@ -0,0 +1,493 @@
+use crate::ket::{KetBra, Operator};
+use crate::Complex;
+
+/// A gate angle, either a number or a named symbol to be filled in later
+/// with [`Circuit::bind_parameters`](super::Circuit::bind_parameters).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(untagged)
+)]
+pub enum Param {
+    Value(f64),
+    Symbol(String),
+}
+
+impl Param {
+    pub fn symbol(name: &str) -> Self {
+        Param::Symbol(name.to_string())
+    }
+
+    /// The angle, panicking on an unbound symbol.
+    pub fn value(&self) -> f64 {
+        match self {
+            Param::Value(v) => *v,
+            Param::Symbol(name) => panic!("unbound parameter {name}"),
+        }
+    }
+
+    pub(super) fn bind(&mut self, values: &[(&str, f64)]) {
+        if let Param::Symbol(name) = self {
+            if let Some(&(_, v)) = values.iter().find(|(n, _)| n == name) {
+                *self = Param::Value(v);
+            }
+        }
+    }
+}
+
+impl From<f64> for Param {
+    fn from(v: f64) -> Self {
+        Param::Value(v)
+    }
+}
+
+impl From<&str> for Param {
+    fn from(name: &str) -> Self {
+        Param::symbol(name)
+    }
+}
+
+impl std::fmt::Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Param::Value(v) => write!(f, "{v}"),
+            Param::Symbol(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// A unitary gate, acting on the qubits it is applied to in the order given,
+/// so the first qubit of a controlled gate is its control.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Gate {
+    #[cfg_attr(feature = "serde", serde(rename = "id"))]
+    I,
+    H,
+    X,
+    Y,
+    Z,
+    S,
+    Sdg,
+    /// √X, equal to Rx(π/2) up to global phase.
+    Sx,
+    Sxdg,
+    T,
+    Tdg,
+    Rx(Param),
+    Ry(Param),
+    Rz(Param),
+    /// diag(1, e^(iλ)).
+    #[cfg_attr(feature = "serde", serde(rename = "p"))]
+    Phase(Param),
+    /// The general single-qubit gate U3(θ, φ, λ) of OpenQASM.
+    U3(Param, Param, Param),
+    Cx,
+    Cz,
+    Swap,
+    /// The Toffoli gate, X on the third qubit controlled by the first two.
+    Ccx,
+    /// X on the last qubit controlled by the given number of qubits before it.
+    Mcx(usize),
+}
+
+/// The kind of a [`Gate`], leaving out its parameters, as when naming the
+/// gates of a basis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GateKind {
+    I,
+    H,
+    X,
+    Y,
+    Z,
+    S,
+    Sdg,
+    Sx,
+    Sxdg,
+    T,
+    Tdg,
+    Rx,
+    Ry,
+    Rz,
+    Phase,
+    U3,
+    Cx,
+    Cz,
+    Swap,
+    Ccx,
+    Mcx,
+}
+
+impl Gate {
+    pub fn num_qubits(&self) -> usize {
+        match self {
+            Gate::Cx | Gate::Cz | Gate::Swap => 2,
+            Gate::Ccx => 3,
+            Gate::Mcx(controls) => controls + 1,
+            _ => 1,
+        }
+    }
+
+    /// Lower-case name as used in OpenQASM.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Gate::I => "id",
+            Gate::H => "h",
+            Gate::X => "x",
+            Gate::Y => "y",
+            Gate::Z => "z",
+            Gate::S => "s",
+            Gate::Sdg => "sdg",
+            Gate::Sx => "sx",
+            Gate::Sxdg => "sxdg",
+            Gate::T => "t",
+            Gate::Tdg => "tdg",
+            Gate::Rx(_) => "rx",
+            Gate::Ry(_) => "ry",
+            Gate::Rz(_) => "rz",
+            Gate::Phase(_) => "p",
+            Gate::U3(..) => "u3",
+            Gate::Cx => "cx",
+            Gate::Cz => "cz",
+            Gate::Swap => "swap",
+            Gate::Ccx => "ccx",
+            Gate::Mcx(_) => "mcx",
+        }
+    }
+
+    pub fn kind(&self) -> GateKind {
+        match self {
+            Gate::I => GateKind::I,
+            Gate::H => GateKind::H,
+            Gate::X => GateKind::X,
+            Gate::Y => GateKind::Y,
+            Gate::Z => GateKind::Z,
+            Gate::S => GateKind::S,
+            Gate::Sdg => GateKind::Sdg,
+            Gate::Sx => GateKind::Sx,
+            Gate::Sxdg => GateKind::Sxdg,
+            Gate::T => GateKind::T,
+            Gate::Tdg => GateKind::Tdg,
+            Gate::Rx(_) => GateKind::Rx,
+            Gate::Ry(_) => GateKind::Ry,
+            Gate::Rz(_) => GateKind::Rz,
+            Gate::Phase(_) => GateKind::Phase,
+            Gate::U3(..) => GateKind::U3,
+            Gate::Cx => GateKind::Cx,
+            Gate::Cz => GateKind::Cz,
+            Gate::Swap => GateKind::Swap,
+            Gate::Ccx => GateKind::Ccx,
+            Gate::Mcx(_) => GateKind::Mcx,
+        }
+    }
+
+    /// Whether the gate is a Clifford gate, taking Pauli operators to Pauli
+    /// operators. Rotations, and U3, count as Clifford when their angles are
+    /// multiples of π/2.
+    pub fn is_clifford(&self) -> bool {
+        let quarter_turns = |p: &Param| match p {
+            Param::Value(x) => {
+                let k = x / std::f64::consts::FRAC_PI_2;
+                (k - k.round()).abs() < 1e-9
+            }
+            Param::Symbol(_) => false,
+        };
+        match self {
+            Gate::Rx(p) | Gate::Ry(p) | Gate::Rz(p) | Gate::Phase(p) => quarter_turns(p),
+            Gate::U3(a, b, c) => [a, b, c].into_iter().all(quarter_turns),
+            Gate::T | Gate::Tdg | Gate::Ccx => false,
+            Gate::Mcx(controls) => *controls <= 1,
+            _ => true,
+        }
+    }
+
+    pub fn params(&self) -> Vec<&Param> {
+        match self {
+            Gate::Rx(p) | Gate::Ry(p) | Gate::Rz(p) | Gate::Phase(p) => vec![p],
+            Gate::U3(a, b, c) => vec![a, b, c],
+            _ => vec![],
+        }
+    }
+
+    pub(super) fn params_mut(&mut self) -> Vec<&mut Param> {
+        match self {
+            Gate::Rx(p) | Gate::Ry(p) | Gate::Rz(p) | Gate::Phase(p) => vec![p],
+            Gate::U3(a, b, c) => vec![a, b, c],
+            _ => vec![],
+        }
+    }
+
+    /// The gate undoing this one. Panics if a parameter is still symbolic.
+    pub fn inverse(&self) -> Gate {
+        let minus = |p: &Param| Param::Value(-p.value());
+        match self {
+            Gate::S => Gate::Sdg,
+            Gate::Sdg => Gate::S,
+            Gate::Sx => Gate::Sxdg,
+            Gate::Sxdg => Gate::Sx,
+            Gate::T => Gate::Tdg,
+            Gate::Tdg => Gate::T,
+            Gate::Rx(theta) => Gate::Rx(minus(theta)),
+            Gate::Ry(theta) => Gate::Ry(minus(theta)),
+            Gate::Rz(theta) => Gate::Rz(minus(theta)),
+            Gate::Phase(lambda) => Gate::Phase(minus(lambda)),
+            // U3(θ, φ, λ)† = U3(−θ, −λ, −φ)
+            Gate::U3(theta, phi, lambda) => Gate::U3(minus(theta), minus(lambda), minus(phi)),
+            _ => self.clone(),
+        }
+    }
+
+    /// The gate as an operator on `num_qubits()` qubits, with its first qubit
+    /// as qubit 0. Panics if a parameter is still symbolic.
+    pub fn operator(&self) -> Operator<Complex<f64>> {
+        let c = Complex::new;
+        let s = std::f64::consts::FRAC_1_SQRT_2;
+        let one = c(1.0, 0.0);
+        let zero = c(0.0, 0.0);
+
+        match self {
+            Gate::I => Operator::identity(1),
+            Gate::H => single([[c(s, 0.0), c(s, 0.0)], [c(s, 0.0), c(-s, 0.0)]]),
+            Gate::X => single([[zero, one], [one, zero]]),
+            Gate::Y => single([[zero, c(0.0, -1.0)], [c(0.0, 1.0), zero]]),
+            Gate::Z => single([[one, zero], [zero, -one]]),
+            Gate::S => single([[one, zero], [zero, c(0.0, 1.0)]]),
+            Gate::Sdg => single([[one, zero], [zero, c(0.0, -1.0)]]),
+            Gate::Sx => single([[c(0.5, 0.5), c(0.5, -0.5)], [c(0.5, -0.5), c(0.5, 0.5)]]),
+            Gate::Sxdg => single([[c(0.5, -0.5), c(0.5, 0.5)], [c(0.5, 0.5), c(0.5, -0.5)]]),
+            Gate::T => single([[one, zero], [zero, c(s, s)]]),
+            Gate::Tdg => single([[one, zero], [zero, c(s, -s)]]),
+            Gate::Rx(theta) => Operator::rx(theta.value()),
+            Gate::Ry(theta) => Operator::ry(theta.value()),
+            Gate::Rz(theta) => Operator::rz(theta.value()),
+            Gate::Phase(lambda) => Operator::phase(lambda.value()),
+            Gate::U3(theta, phi, lambda) => {
+                Operator::u3(theta.value(), phi.value(), lambda.value())
+            }
+            Gate::Cx => Operator::from_permutation(2, |x| x ^ ((x & 1) << 1)),
+            Gate::Cz => Operator::phase_oracle(2, |x| x == 0b11),
+            Gate::Swap => Operator::from_permutation(2, |x| (x >> 1) | ((x & 1) << 1)),
+            Gate::Ccx => controlled_x(2),
+            Gate::Mcx(controls) => controlled_x(*controls),
+        }
+    }
+}
+
+/// X on qubit `controls` controlled by all the qubits below it.
+fn controlled_x(controls: usize) -> Operator<Complex<f64>> {
+    let mask = (1 << controls) - 1;
+    Operator::from_permutation(controls as u32 + 1, |x| {
+        if x & mask == mask {
+            x ^ (1 << controls)
+        } else {
+            x
+        }
+    })
+}
+
+impl std::fmt::Display for Gate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())?;
+
+        let params = self.params();
+        if !params.is_empty() {
+            let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+            write!(f, "({})", params.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Operator<Complex<f64>> {
+    /// Rotation by θ about the X axis, exp(−iθX/2).
+    pub fn rx(theta: f64) -> Self {
+        let (cos, sin) = half_angle(theta);
+        single([
+            [Complex::new(cos, 0.0), Complex::new(0.0, -sin)],
+            [Complex::new(0.0, -sin), Complex::new(cos, 0.0)],
+        ])
+    }
+
+    /// Rotation by θ about the Y axis, exp(−iθY/2).
+    pub fn ry(theta: f64) -> Self {
+        let (cos, sin) = half_angle(theta);
+        single([
+            [Complex::new(cos, 0.0), Complex::new(-sin, 0.0)],
+            [Complex::new(sin, 0.0), Complex::new(cos, 0.0)],
+        ])
+    }
+
+    /// Rotation by θ about the Z axis, exp(−iθZ/2) = diag(e^(−iθ/2), e^(iθ/2)).
+    pub fn rz(theta: f64) -> Self {
+        single([
+            [unit(-theta / 2.0), Complex::new(0.0, 0.0)],
+            [Complex::new(0.0, 0.0), unit(theta / 2.0)],
+        ])
+    }
+
+    /// The phase gate diag(1, e^(iλ)), equal to Rz(λ) up to global phase.
+    pub fn phase(lambda: f64) -> Self {
+        single([
+            [Complex::new(1.0, 0.0), Complex::new(0.0, 0.0)],
+            [Complex::new(0.0, 0.0), unit(lambda)],
+        ])
+    }
+
+    /// U3(θ, φ, λ) = Rz(φ) Ry(θ) Rz(λ) up to global phase, with the top-left
+    /// entry real.
+    pub fn u3(theta: f64, phi: f64, lambda: f64) -> Self {
+        let (cos, sin) = half_angle(theta);
+        let real = |x: f64| Complex::new(x, 0.0);
+        single([
+            [real(cos), -unit(lambda) * real(sin)],
+            [unit(phi) * real(sin), unit(phi + lambda) * real(cos)],
+        ])
+    }
+}
+
+fn half_angle(theta: f64) -> (f64, f64) {
+    ((theta / 2.0).cos(), (theta / 2.0).sin())
+}
+
+/// e^(iφ).
+fn unit(phi: f64) -> Complex<f64> {
+    Complex::new(phi.cos(), phi.sin())
+}
+
+/// A single-qubit operator from its matrix, indexed `[ket][bra]`.
+fn single(m: [[Complex<f64>; 2]; 2]) -> Operator<Complex<f64>> {
+    let mut ones = vec![];
+    for (ket, row) in m.iter().enumerate() {
+        for (bra, &scalar) in row.iter().enumerate() {
+            if scalar != Complex::new(0.0, 0.0) {
+                ones.push(KetBra {
+                    scalar,
+                    ket: ket as u64,
+                    bra: bra as u64,
+                    n: 1,
+                    d: 2,
+                });
+            }
+        }
+    }
+
+    Operator {
+        scalar: Complex::new(1.0, 0.0),
+        ones,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Gate, Param};
+    use crate::ket::{Operator, State};
+    use crate::Complex as C;
+
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn assert_close(a: &Operator<C<f64>>, b: &Operator<C<f64>>) {
+        let diff = a.clone() + -b.clone();
+        for (scalar, ..) in diff.terms() {
+            assert!(scalar.modulus() < EPSILON, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn gate_operators_are_unitary() {
+        let gates = [
+            Gate::I,
+            Gate::H,
+            Gate::X,
+            Gate::Y,
+            Gate::Z,
+            Gate::S,
+            Gate::Sdg,
+            Gate::T,
+            Gate::Tdg,
+            Gate::Rx(0.3.into()),
+            Gate::Ry(2.1.into()),
+            Gate::Rz(1.3.into()),
+            Gate::Phase((-0.4).into()),
+            Gate::U3(0.1.into(), 0.2.into(), 0.3.into()),
+            Gate::Cx,
+            Gate::Cz,
+            Gate::Swap,
+        ];
+
+        for gate in gates {
+            let op = gate.operator();
+            assert!(op.is_unitary(EPSILON), "{gate} is not unitary");
+            assert_eq!(op.num_qubits() as usize, gate.num_qubits());
+        }
+    }
+
+    #[test]
+    fn gate_operators_match_parser() {
+        let h = Operator::parse("1/sqrt(2)(|0⟩⟨0| + |0⟩⟨1| + |1⟩⟨0| - |1⟩⟨1|)").unwrap();
+        let cnot = Operator::parse("|00⟩⟨00| + |11⟩⟨01| + |10⟩⟨10| + |01⟩⟨11|").unwrap();
+
+        assert_close(&Gate::H.operator(), &h);
+        assert_close(&Gate::Cx.operator(), &cnot);
+        assert_close(
+            &(Gate::T.operator() * Gate::T.operator()),
+            &Gate::S.operator(),
+        );
+    }
+
+    #[test]
+    fn cnot_is_controlled_by_first_qubit() {
+        let res = Gate::Cx.operator() * State::<C<f64>>::basis(0b01, 2);
+        assert_eq!(
+            res.terms().collect::<Vec<_>>(),
+            vec![(C::new(1.0, 0.0), 0b11, 2)]
+        );
+    }
+
+    #[test]
+    fn rotations_match_exponentials() {
+        let x = Gate::X.operator();
+        let y = Gate::Y.operator();
+        let z = Gate::Z.operator();
+
+        // exp(−iθP/2) is the propagator of P/2 for time θ
+        let half = C::new(0.5, 0.0);
+        assert_close(&Operator::rx(0.7), &(x * half).expm(0.7));
+        assert_close(&Operator::ry(0.7), &(y * half).expm(0.7));
+        assert_close(&Operator::rz(0.7), &(z * half).expm(0.7));
+    }
+
+    #[test]
+    fn special_angles() {
+        let i = C::new(0.0, 1.0);
+
+        // Rx(π) = −iX, P(π/2) = S, U3(π/2, 0, π) = H
+        assert_close(&Operator::rx(PI), &(Gate::X.operator() * -i));
+        assert_close(&Operator::phase(FRAC_PI_2), &Gate::S.operator());
+        assert_close(&Operator::u3(FRAC_PI_2, 0.0, PI), &Gate::H.operator());
+        assert_close(&Operator::u3(0.4, 0.0, 0.0), &Operator::ry(0.4));
+    }
+
+    #[test]
+    fn display_with_params() {
+        assert_eq!(Gate::Rx(0.5.into()).to_string(), "rx(0.5)");
+        assert_eq!(
+            Gate::U3("theta".into(), 0.0.into(), Param::symbol("l")).to_string(),
+            "u3(theta, 0, l)"
+        );
+        assert_eq!(Gate::Cx.to_string(), "cx");
+    }
+
+    #[test]
+    #[should_panic(expected = "unbound parameter theta")]
+    fn unbound_parameter_panics() {
+        Gate::Ry("theta".into()).operator();
+    }
+}
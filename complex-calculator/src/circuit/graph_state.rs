@@ -0,0 +1,105 @@
+//! Graph states, the stabilizer states of the form CZ^(edges) |+⟩^⊗n that
+//! measurement-based computation starts from.
+
+use super::Circuit;
+use crate::ket::graph_edges;
+
+impl Circuit {
+    /// The circuit preparing [`State::graph_state`] of `adjacency` from
+    /// |0…0⟩: a Hadamard on every qubit, then a CZ on every edge, in order.
+    ///
+    /// [`State::graph_state`]: crate::ket::State::graph_state
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// // the path 0 – 1 – 2
+    /// let path = [vec![false, true, false], vec![true, false, true], vec![false, true, false]];
+    /// let c = Circuit::graph_state(&path);
+    /// assert_eq!(c.gate_counts()["h"], 3);
+    /// assert_eq!(c.gate_counts()["cz"], 2);
+    /// ```
+    pub fn graph_state(adjacency: &[Vec<bool>]) -> Circuit {
+        let edges = graph_edges(adjacency);
+        let mut c = Circuit::new(adjacency.len() as u32);
+        for q in 0..adjacency.len() {
+            c.h(q);
+        }
+        for (i, j) in edges {
+            c.cz(i, j);
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+    use crate::ket::State;
+    use crate::pauli::{Pauli, PauliString};
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn adjacency(n: usize, edges: &[(usize, usize)]) -> Vec<Vec<bool>> {
+        let mut a = vec![vec![false; n]; n];
+        for &(i, j) in edges {
+            a[i][j] = true;
+            a[j][i] = true;
+        }
+        a
+    }
+
+    #[test]
+    fn circuit_prepares_the_state() {
+        for edges in [
+            vec![],
+            vec![(0, 1)],
+            vec![(0, 1), (1, 2), (2, 3), (3, 0)],
+            vec![(0, 3), (1, 3), (2, 3)],
+        ] {
+            let a = adjacency(4, &edges);
+            let expected = State::graph_state(&a).amplitudes(4);
+            let amplitudes = Circuit::graph_state(&a).simulate().amplitudes(4);
+            for (x, y) in amplitudes.iter().zip(&expected) {
+                assert!((*x - *y).modulus() < EPSILON, "{edges:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn stabilized_by_x_and_neighbouring_zs() {
+        // K_v = X_v Π_(w ~ v) Z_w has expectation 1 for every vertex v
+        let edges = [(0, 1), (1, 2), (2, 3), (1, 3)];
+        let a = adjacency(4, &edges);
+        let amplitudes = State::graph_state(&a).amplitudes(4);
+        for (v, neighbours) in a.iter().enumerate() {
+            let mut k = PauliString::single(4, v, Pauli::X);
+            for w in (0..4).filter(|&w| neighbours[w]) {
+                k = k * PauliString::single(4, w, Pauli::Z);
+            }
+            assert!((k.expectation(&amplitudes) - 1.0).abs() < EPSILON, "{k}");
+        }
+    }
+
+    #[test]
+    fn two_vertices_make_a_bell_pair_up_to_a_hadamard() {
+        let a = adjacency(2, &[(0, 1)]);
+        let mut c = Circuit::graph_state(&a);
+        c.h(1);
+        let amplitudes = c.simulate().amplitudes(2);
+        assert!((amplitudes[0b00].re - 0.5f64.sqrt()).abs() < EPSILON);
+        assert!((amplitudes[0b11].re - 0.5f64.sqrt()).abs() < EPSILON);
+    }
+
+    #[test]
+    #[should_panic(expected = "the adjacency matrix is not symmetric at (1, 0)")]
+    fn rejects_directed_graphs() {
+        Circuit::graph_state(&[vec![false, true], vec![false, false]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "vertex 1 has a self-loop")]
+    fn rejects_self_loops() {
+        State::graph_state(&[vec![false, false], vec![false, true]]);
+    }
+}
@@ -0,0 +1,214 @@
+//! Amplitude amplification, and Grover search as the case of it that
+//! starts from the uniform superposition. Amplifying a state whose good part
+//! has probability p takes about (π/4)/√p rounds, so Grover search finds one
+//! of M marked inputs out of N = 2^n in about (π/4)√(N/M) queries, against
+//! N/M classically.
+
+use std::f64::consts::PI;
+
+use super::Circuit;
+
+impl Circuit {
+    /// `state_prep` A followed by `iterations` rounds of
+    /// Q = −A S₀ A† S_f, where the phase oracle S_f is `oracle` and
+    /// S₀ = I − 2|0…0⟩⟨0…0| flips the sign of the all-zero state. If A|0⟩ has
+    /// a good part of probability sin²θ, which S_f flips the sign of, k rounds
+    /// raise it to sin²((2k + 1)θ). The −1 is kept as a global phase, so
+    /// that Q is exact when controlled. Both circuits must be of gates only,
+    /// on the same qubits.
+    pub fn amplitude_amplification(
+        state_prep: &Circuit,
+        oracle: &Circuit,
+        iterations: usize,
+    ) -> Circuit {
+        let n = state_prep.num_qubits;
+        assert_eq!(
+            oracle.num_qubits, n,
+            "the oracle acts on {} qubits, not the {n} of the state preparation",
+            oracle.num_qubits
+        );
+        let qubits: Vec<usize> = (0..n as usize).collect();
+        let unprepare = state_prep.inverse();
+
+        let mut c = state_prep.clone();
+        for _ in 0..iterations {
+            c.compose(oracle, &qubits).compose(&unprepare, &qubits);
+            for &q in &qubits {
+                c.x(q);
+            }
+            c.mcz(&qubits);
+            for &q in &qubits {
+                c.x(q);
+            }
+            c.compose(state_prep, &qubits).add_global_phase(PI);
+        }
+        c
+    }
+
+    /// The number of rounds of amplitude amplification that brings a good
+    /// part of probability `p` closest to 1: the k for which (2k + 1)θ is
+    /// nearest π/2, where sin²θ = p. None are needed if p is 0 or 1.
+    pub fn amplification_iterations(p: f64) -> usize {
+        if p <= 0.0 || p >= 1.0 {
+            return 0;
+        }
+        let theta = p.sqrt().asin();
+        (PI / (4.0 * theta) - 0.5).round() as usize
+    }
+
+    /// Grover search on n qubits for the inputs `oracle` marks, measuring
+    /// qubit i into bit i: [`Circuit::amplitude_amplification`] with
+    /// Hadamards on every qubit for A, so that A S₀ A† is the diffusion about
+    /// the uniform superposition, and the [`Circuit::phase_oracle`] of
+    /// `oracle` for S_f. With `iterations` `None`,
+    /// [`Circuit::grover_iterations`] are run for the number of inputs
+    /// marked. n must be below 32, as the inputs are counted in a `u32`.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let c = Circuit::grover(5, |x| x == 19, None);
+    /// let counts = c.sample(100, &mut StdRng::seed_from_u64(1));
+    /// assert_eq!(counts.most_frequent(), Some(19));
+    /// ```
+    pub fn grover(n: u32, oracle: impl Fn(u32) -> bool, iterations: Option<usize>) -> Circuit {
+        assert!(
+            n < 32,
+            "Grover search on {n} qubits, where at most 31 are supported"
+        );
+        let marked = (0..1u32 << n).filter(|&x| oracle(x)).count();
+        let iterations = iterations.unwrap_or_else(|| Circuit::grover_iterations(n, marked));
+        let mut uniform = Circuit::new(n);
+        for q in 0..n as usize {
+            uniform.h(q);
+        }
+
+        let mut c = Circuit::amplitude_amplification(
+            &uniform,
+            &Circuit::phase_oracle(n, oracle),
+            iterations,
+        );
+        for q in 0..n as usize {
+            c.measure(q, q);
+        }
+        c
+    }
+
+    /// The number of Grover iterations that brings the probability of
+    /// reading one of `marked` inputs out of 2^n closest to 1, by
+    /// [`Circuit::amplification_iterations`] for p = M/N.
+    pub fn grover_iterations(n: u32, marked: usize) -> usize {
+        Circuit::amplification_iterations(marked as f64 / f64::from(n).exp2())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    /// The probability of reading each input, from the circuit without its
+    /// measurements.
+    fn probabilities(c: &Circuit) -> Vec<f64> {
+        let n = c.num_qubits();
+        let mut gates = Circuit::new(n);
+        for instruction in c.instructions() {
+            if let crate::circuit::Instruction::Gate { gate, qubits } = instruction {
+                gates.append(gate.clone(), qubits);
+            }
+        }
+        let state = gates.simulate().amplitudes(n);
+        state.iter().map(|a| a.mod_squared()).collect()
+    }
+
+    #[test]
+    fn iterations_grow_as_the_square_root() {
+        // about (π/4)√N for one marked input
+        for n in 2..=10 {
+            let expected = (std::f64::consts::FRAC_PI_4 * f64::from(n).exp2().sqrt()).floor();
+            let k = Circuit::grover_iterations(n, 1) as f64;
+            assert!((k - expected).abs() <= 1.0, "{n}: {k}");
+        }
+        assert_eq!(Circuit::grover_iterations(2, 1), 1);
+        assert_eq!(Circuit::grover_iterations(3, 0), 0);
+        assert_eq!(Circuit::grover_iterations(3, 8), 0);
+        // a quarter marked is found by a single iteration
+        assert_eq!(Circuit::grover_iterations(6, 16), 1);
+    }
+
+    #[test]
+    fn finds_the_marked_input() {
+        for n in 2..=8 {
+            let target = (1u32 << n) - 2;
+            let c = Circuit::grover(n, |x| x == target, None);
+            let p = probabilities(&c)[target as usize];
+            assert!(p > 0.9, "{n} qubits: {p}");
+        }
+        // two iterations on two qubits overshoot
+        let c = Circuit::grover(2, |x| x == 1, Some(2));
+        assert!((probabilities(&c)[1] - 0.25).abs() < EPSILON);
+    }
+
+    #[test]
+    fn several_marked_inputs() {
+        let marked = [3, 10, 12];
+        let c = Circuit::grover(4, |x| marked.contains(&x), None);
+        let p: f64 = marked.iter().map(|&x| probabilities(&c)[x as usize]).sum();
+        assert!(p > 0.9);
+
+        let counts = c.sample(200, &mut StdRng::seed_from_u64(6));
+        let hits: usize = marked.iter().map(|&x| counts.get(x.into())).sum();
+        assert!(hits > 170);
+    }
+
+    #[test]
+    fn amplifies_a_biased_preparation() {
+        // A = Ry(1.0) on each of three qubits, with |111⟩ good
+        let mut prep = Circuit::new(3);
+        for q in 0..3 {
+            prep.ry(q, 1.0);
+        }
+        let oracle = Circuit::phase_oracle(3, |x| x == 7);
+        let theta = 0.5f64.sin().powi(3).asin();
+
+        for k in 0..4 {
+            let c = Circuit::amplitude_amplification(&prep, &oracle, k);
+            let p = c.simulate().amplitudes(3)[7].mod_squared();
+            let expected = ((2 * k + 1) as f64 * theta).sin().powi(2);
+            assert!((p - expected).abs() < EPSILON, "{k}: {p} != {expected}");
+        }
+        assert_eq!(Circuit::amplification_iterations(theta.sin().powi(2)), 7);
+    }
+
+    #[test]
+    fn rounds_keep_the_phase_exact() {
+        // Q acts on the good state |1⟩ of A = Ry(2θ) as a rotation by 2θ
+        let theta = 0.3;
+        let mut prep = Circuit::new(1);
+        prep.ry(0, 2.0 * theta);
+        let mut oracle = Circuit::new(1);
+        oracle.z(0);
+        let c = Circuit::amplitude_amplification(&prep, &oracle, 2);
+        let amplitudes = c.simulate().amplitudes(1);
+        assert!((amplitudes[0].re - (5.0 * theta).cos()).abs() < EPSILON);
+        assert!((amplitudes[1].re - (5.0 * theta).sin()).abs() < EPSILON);
+    }
+
+    #[test]
+    #[should_panic(expected = "the oracle acts on 2 qubits, not the 3 of the state preparation")]
+    fn oracle_must_match() {
+        Circuit::amplitude_amplification(&Circuit::new(3), &Circuit::new(2), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Grover search on 32 qubits")]
+    fn inputs_must_fit_a_u32() {
+        Circuit::grover(32, |x| x == 0, Some(0));
+    }
+}
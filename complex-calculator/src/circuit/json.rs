@@ -0,0 +1,411 @@
+//! A JSON format for circuits, behind the `serde` feature. A circuit is an
+//! object with its register sizes and a list of instructions tagged by `op`:
+//!
+//! ```json
+//! {
+//!   "num_qubits": 2,
+//!   "num_clbits": 1,
+//!   "instructions": [
+//!     { "op": "gate", "gate": "h", "qubits": [0] },
+//!     { "op": "gate", "gate": { "rx": "theta" }, "qubits": [1] },
+//!     { "op": "measure", "qubit": 0, "clbit": 0 },
+//!     { "op": "conditional", "clbit": 0, "gate": "x", "qubits": [1] },
+//!     { "op": "reset", "qubit": 0 },
+//!     { "op": "barrier", "qubits": [0, 1] },
+//!     { "op": "snapshot", "label": "end" }
+//!   ]
+//! }
+//! ```
+//!
+//! A loop is `{ "op": "while", "clbit": 0, "value": false, "max_iterations":
+//! 3, "body": { ... } }`, its body a circuit of its own on the same qubits.
+//! Every qubit and classical bit an instruction names must fall within the
+//! declared register sizes.
+//!
+//! Gates go by their OpenQASM names, with their angles, numbers or symbol
+//! names, as the value: `{ "u3": [1.0, 0.0, "phi"] }`, and `{ "mcx": 3 }`
+//! for X controlled by three qubits. A nonzero global phase is written as
+//! `"global_phase"`. Noise channels are not serialized.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{Circuit, Gate, Instruction};
+use crate::ket::MAX_QUBITS;
+
+#[derive(Serialize, Deserialize)]
+struct Schema {
+    num_qubits: u32,
+    #[serde(default)]
+    num_clbits: usize,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    global_phase: f64,
+    instructions: Vec<Entry>,
+}
+
+fn is_zero(phase: &f64) -> bool {
+    *phase == 0.0
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Entry {
+    Gate {
+        gate: Gate,
+        qubits: Vec<usize>,
+    },
+    Measure {
+        qubit: usize,
+        clbit: usize,
+    },
+    Reset {
+        qubit: usize,
+    },
+    Barrier {
+        qubits: Vec<usize>,
+    },
+    Conditional {
+        clbit: usize,
+        gate: Gate,
+        qubits: Vec<usize>,
+    },
+    While {
+        clbit: usize,
+        value: bool,
+        max_iterations: usize,
+        body: Circuit,
+    },
+    Snapshot {
+        label: String,
+    },
+}
+
+impl Serialize for Circuit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let instructions = self
+            .instructions
+            .iter()
+            .map(|instruction| match instruction.clone() {
+                Instruction::Gate { gate, qubits } => Ok(Entry::Gate { gate, qubits }),
+                Instruction::Measure { qubit, clbit } => Ok(Entry::Measure { qubit, clbit }),
+                Instruction::Reset { qubit } => Ok(Entry::Reset { qubit }),
+                Instruction::Barrier { qubits } => Ok(Entry::Barrier { qubits }),
+                Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => Ok(Entry::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                }),
+                Instruction::While {
+                    clbit,
+                    value,
+                    max_iterations,
+                    body,
+                } => Ok(Entry::While {
+                    clbit,
+                    value,
+                    max_iterations,
+                    body: *body,
+                }),
+                Instruction::Snapshot { label } => Ok(Entry::Snapshot { label }),
+                Instruction::Channel { channel, .. } => Err(S::Error::custom(format!(
+                    "{} channel cannot be serialized",
+                    channel.name()
+                ))),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Schema {
+            num_qubits: self.num_qubits,
+            num_clbits: self.num_clbits,
+            global_phase: self.global_phase,
+            instructions,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Circuit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let schema = Schema::deserialize(deserializer)?;
+        let n = schema.num_qubits as usize;
+        let m = schema.num_clbits;
+        let mut circuit = Circuit::with_clbits(schema.num_qubits, schema.num_clbits);
+        circuit.add_global_phase(schema.global_phase);
+
+        // the builders panic on bad qubits and overflow on huge clbits, so
+        // check them first
+        for entry in schema.instructions {
+            match entry {
+                Entry::Gate { gate, qubits } => {
+                    check(n, &qubits, Some(&gate)).map_err(D::Error::custom)?;
+                    circuit.append(gate, &qubits);
+                }
+                Entry::Measure { qubit, clbit } => {
+                    check(n, &[qubit], None).map_err(D::Error::custom)?;
+                    check_clbit(m, clbit).map_err(D::Error::custom)?;
+                    circuit.measure(qubit, clbit);
+                }
+                Entry::Reset { qubit } => {
+                    check(n, &[qubit], None).map_err(D::Error::custom)?;
+                    circuit.reset(qubit);
+                }
+                Entry::Barrier { qubits } => {
+                    check(n, &qubits, None).map_err(D::Error::custom)?;
+                    circuit.barrier(&qubits);
+                }
+                Entry::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => {
+                    check(n, &qubits, Some(&gate)).map_err(D::Error::custom)?;
+                    check_clbit(m, clbit).map_err(D::Error::custom)?;
+                    circuit.c_if(clbit, gate, &qubits);
+                }
+                Entry::While {
+                    clbit,
+                    value,
+                    max_iterations,
+                    body,
+                } => {
+                    if body.num_qubits != schema.num_qubits {
+                        return Err(D::Error::custom(format!(
+                            "loop body on {} qubits in a circuit on {n}",
+                            body.num_qubits
+                        )));
+                    }
+                    if body.num_clbits > m {
+                        return Err(D::Error::custom(format!(
+                            "loop body on {} clbits in a circuit on {m}",
+                            body.num_clbits
+                        )));
+                    }
+                    check_clbit(m, clbit).map_err(D::Error::custom)?;
+                    circuit.while_loop(clbit, value, &body, max_iterations);
+                }
+                Entry::Snapshot { label } => {
+                    circuit.snapshot(&label);
+                }
+            }
+        }
+        Ok(circuit)
+    }
+}
+
+fn check(num_qubits: usize, qubits: &[usize], gate: Option<&Gate>) -> Result<(), String> {
+    if let Some(&Gate::Mcx(controls)) = gate {
+        if controls >= MAX_QUBITS as usize {
+            return Err(format!(
+                "mcx with {controls} controls exceeds the {MAX_QUBITS}-qubit limit"
+            ));
+        }
+    }
+    if let Some(gate) = gate {
+        if qubits.len() != gate.num_qubits() {
+            return Err(format!("{gate} acts on {} qubits", gate.num_qubits()));
+        }
+    }
+    for (i, &q) in qubits.iter().enumerate() {
+        if q >= num_qubits {
+            return Err(format!("qubit {q} out of range for {num_qubits} qubits"));
+        }
+        if let (Some(gate), true) = (gate, qubits[..i].contains(&q)) {
+            return Err(format!("duplicate qubit {q} for {gate}"));
+        }
+    }
+    Ok(())
+}
+
+fn check_clbit(num_clbits: usize, clbit: usize) -> Result<(), String> {
+    if clbit >= num_clbits {
+        return Err(format!(
+            "clbit {clbit} out of range for {num_clbits} clbits"
+        ));
+    }
+    Ok(())
+}
+
+impl Circuit {
+    /// The circuit as pretty-printed JSON, failing on noise channels.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> Result<Circuit, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Writes the circuit to `path` as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut json = self.to_json()?;
+        json.push('\n');
+        fs::write(path, json)
+    }
+
+    /// Reads a circuit saved with [`Circuit::save`].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Circuit> {
+        Ok(Circuit::from_json(&fs::read_to_string(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channel::Channel;
+    use crate::circuit::{Circuit, Gate, Param};
+
+    #[test]
+    fn round_trip() {
+        let mut c = Circuit::new(4);
+        c.h(0)
+            .sdg(1)
+            .rx(2, 0.5)
+            .p(3, Param::symbol("lambda"))
+            .u3(0, 1.0, "phi", -2.0)
+            .cnot(0, 1)
+            .cz(1, 2)
+            .swap(2, 3)
+            .ccx(0, 1, 2)
+            .mcx(&[0, 1, 2], 3)
+            .id(3)
+            .barrier(&[0, 1, 2, 3])
+            .snapshot("before measuring")
+            .measure(0, 2)
+            .reset(0)
+            .c_if(2, Gate::Ry(Param::Value(0.25)), &[1])
+            .add_global_phase(1.5);
+        let mut body = Circuit::new(4);
+        body.h(3).measure(3, 1);
+        c.repeat_until(1, true, &body, 3);
+
+        let json = c.to_json().unwrap();
+        assert_eq!(Circuit::from_json(&json).unwrap(), c);
+    }
+
+    #[test]
+    fn schema() {
+        let mut c = Circuit::new(2);
+        c.h(0)
+            .rx(1, "theta")
+            .u3(0, 1.0, 0.0, 2.0)
+            .append(Gate::Mcx(1), &[0, 1])
+            .measure(0, 0)
+            .c_if(0, Gate::X, &[1]);
+        let value: serde_json::Value = serde_json::from_str(&c.to_json().unwrap()).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "num_qubits": 2,
+                "num_clbits": 1,
+                "instructions": [
+                    { "op": "gate", "gate": "h", "qubits": [0] },
+                    { "op": "gate", "gate": { "rx": "theta" }, "qubits": [1] },
+                    { "op": "gate", "gate": { "u3": [1.0, 0.0, 2.0] }, "qubits": [0] },
+                    { "op": "gate", "gate": { "mcx": 1 }, "qubits": [0, 1] },
+                    { "op": "measure", "qubit": 0, "clbit": 0 },
+                    { "op": "conditional", "clbit": 0, "gate": "x", "qubits": [1] }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_qubits() {
+        let json =
+            |instruction: &str| format!(r#"{{"num_qubits": 2, "instructions": [{instruction}]}}"#);
+
+        let error = Circuit::from_json(&json(r#"{"op": "gate", "gate": "h", "qubits": [2]}"#));
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("qubit 2 out of range for 2 qubits"));
+
+        let error = Circuit::from_json(&json(r#"{"op": "gate", "gate": "cx", "qubits": [1, 1]}"#));
+        assert!(error.unwrap_err().to_string().contains("duplicate qubit 1"));
+
+        let error = Circuit::from_json(&json(r#"{"op": "gate", "gate": "cz", "qubits": [0]}"#));
+        assert!(error.unwrap_err().to_string().contains("acts on 2 qubits"));
+
+        let error = Circuit::from_json(&json(
+            r#"{"op": "gate", "gate": {"mcx": 18446744073709551615}, "qubits": [0, 1]}"#,
+        ));
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("mcx with 18446744073709551615 controls exceeds the 64-qubit limit"));
+
+        assert!(Circuit::from_json(&json(r#"{"op": "wait", "qubit": 0}"#)).is_err());
+
+        let error = Circuit::from_json(&json(
+            r#"{"op": "while", "clbit": 0, "value": true, "max_iterations": 2,
+                "body": {"num_qubits": 1, "instructions": []}}"#,
+        ));
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("loop body on 1 qubits in a circuit on 2"));
+    }
+
+    #[test]
+    fn rejects_bad_clbits() {
+        let json = |instruction: &str| {
+            format!(r#"{{"num_qubits": 1, "num_clbits": 1, "instructions": [{instruction}]}}"#)
+        };
+
+        let error = Circuit::from_json(&json(
+            r#"{"op": "measure", "qubit": 0, "clbit": 18446744073709551615}"#,
+        ));
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("clbit 18446744073709551615 out of range for 1 clbits"));
+
+        let error = Circuit::from_json(&json(
+            r#"{"op": "conditional", "clbit": 1, "gate": "x", "qubits": [0]}"#,
+        ));
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("clbit 1 out of range for 1 clbits"));
+
+        let error = Circuit::from_json(&json(
+            r#"{"op": "while", "clbit": 0, "value": true, "max_iterations": 2,
+                "body": {"num_qubits": 1, "num_clbits": 2, "instructions": []}}"#,
+        ));
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("loop body on 2 clbits in a circuit on 1"));
+    }
+
+    #[test]
+    fn channels_are_not_serialized() {
+        let mut c = Circuit::new(1);
+        c.channel(Channel::bit_flip(0.1), &[0]);
+        assert!(c
+            .to_json()
+            .unwrap_err()
+            .to_string()
+            .contains("bit_flip channel cannot be serialized"));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+
+        let path = std::env::temp_dir().join(format!("bell-{}.json", std::process::id()));
+        c.save(&path).unwrap();
+        let loaded = Circuit::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), c);
+    }
+}
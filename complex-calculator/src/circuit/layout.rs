@@ -0,0 +1,105 @@
+//! The column layout shared by the circuit drawings. Instructions are placed
+//! in columns much as in [`Circuit::moments`], except that a multi-qubit
+//! instruction also claims the wires its vertical line crosses, and a loop
+//! claims every wire.
+
+use std::f64::consts::PI;
+
+use super::{Circuit, Instruction};
+
+/// A circuit's instructions by column, with the dividers between columns.
+pub(super) struct Layout<'a> {
+    pub(super) columns: Vec<Vec<&'a Instruction>>,
+    /// Dividers after a column across every wire, with their labels: empty
+    /// for barriers over every qubit, the label for snapshots.
+    pub(super) slices: Vec<(usize, &'a str)>,
+}
+
+impl Circuit {
+    pub(super) fn layout(&self) -> Layout<'_> {
+        let n = self.num_qubits as usize;
+        let mut layout = Layout {
+            columns: vec![],
+            slices: vec![],
+        };
+        let mut qubits = vec![0; n];
+        let mut clbits = vec![0; self.num_clbits];
+
+        for instruction in &self.instructions {
+            let clbit = match instruction {
+                Instruction::Barrier { qubits: qs } => {
+                    let level = qs.iter().map(|&q| qubits[q]).max().unwrap_or(0);
+                    for &q in qs {
+                        qubits[q] = level;
+                    }
+                    if level > 0 && (0..n).all(|q| qs.contains(&q)) {
+                        layout.slices.push((level - 1, ""));
+                    }
+                    continue;
+                }
+                Instruction::Snapshot { label } => {
+                    let level = qubits.iter().copied().max().unwrap_or(0);
+                    qubits.fill(level);
+                    if level > 0 {
+                        layout.slices.push((level - 1, label));
+                    }
+                    continue;
+                }
+                Instruction::Measure { clbit, .. }
+                | Instruction::Conditional { clbit, .. }
+                | Instruction::While { clbit, .. } => Some(*clbit),
+                _ => None,
+            };
+
+            let (top, bottom) = span(instruction, n);
+            let level = (top..=bottom)
+                .map(|q| qubits[q])
+                .chain(clbit.map(|c| clbits[c]))
+                .max()
+                .unwrap();
+            for level_of in &mut qubits[top..=bottom] {
+                *level_of = level + 1;
+            }
+            match instruction {
+                Instruction::Measure { .. } => clbits[clbit.unwrap()] = level + 1,
+                Instruction::While { .. } => clbits.fill(level + 1),
+                _ => {}
+            }
+
+            if layout.columns.len() <= level {
+                layout.columns.resize(level + 1, vec![]);
+            }
+            layout.columns[level].push(instruction);
+        }
+        layout
+    }
+}
+
+/// The topmost and bottommost of the n wires the drawing of `instruction`
+/// covers. Panics on barriers and snapshots, which are not drawn in a
+/// column.
+pub(super) fn span(instruction: &Instruction, n: usize) -> (usize, usize) {
+    let qubits: &[usize] = match instruction {
+        Instruction::Gate { qubits, .. }
+        | Instruction::Conditional { qubits, .. }
+        | Instruction::Channel { qubits, .. } => qubits,
+        Instruction::Measure { qubit, .. } | Instruction::Reset { qubit } => {
+            std::slice::from_ref(qubit)
+        }
+        Instruction::While { .. } => return (0, n - 1),
+        Instruction::Barrier { .. } | Instruction::Snapshot { .. } => {
+            unreachable!("barriers and snapshots are dividers")
+        }
+    };
+    (*qubits.iter().min().unwrap(), *qubits.iter().max().unwrap())
+}
+
+/// `x` as a fraction pπ/q of π in lowest terms, with q at most 16, if it is
+/// one.
+pub(super) fn pi_fraction(x: f64) -> Option<(i64, i64)> {
+    let turns = x / PI;
+    (1..=16).find_map(|q| {
+        let p = turns * q as f64;
+        ((p - p.round()).abs() < 1e-9).then_some((p.round() as i64, q))
+    })
+}
@@ -0,0 +1,360 @@
+//! Dense statevectors kept in a memory-mapped file rather than on the heap,
+//! so that registers too large for memory are paged to disk by the
+//! operating system instead of failing to allocate.
+//!
+//! Gates are applied a chunk of 2^16 amplitudes at a time. A gate on low
+//! qubits stays within each chunk; one on qubits above them reads the few
+//! chunks its amplitudes pair up across, so the file is swept in order
+//! rather than read at random.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+use memmap2::MmapMut;
+use num::Zero;
+
+use super::statevector::{apply_matrix, gate_matrix};
+use super::{definite, Circuit, Gate, Instruction};
+use crate::ket::State;
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// Qubits addressed within a chunk.
+const CHUNK_QUBITS: u32 = 16;
+
+/// Bytes of one amplitude, the real part then the imaginary part.
+const AMPLITUDE_BYTES: usize = 16;
+
+/// A dense n-qubit state in double precision whose 2^n amplitudes live in a
+/// memory-mapped file, `amplitude(i)` the amplitude of |i⟩.
+#[derive(Debug)]
+pub struct MappedStateVector {
+    n: u32,
+    chunk_qubits: u32,
+    map: MmapMut,
+    _file: File,
+}
+
+impl MappedStateVector {
+    /// The all-zero state |0…0⟩ in a new file at `path`, truncating any file
+    /// already there. The file takes 16 · 2^n bytes.
+    pub fn create(path: impl AsRef<Path>, n: u32) -> io::Result<Self> {
+        assert!(
+            n < usize::BITS - 4,
+            "{n} qubits do not fit in memory at all"
+        );
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len((AMPLITUDE_BYTES << n) as u64)?;
+        // SAFETY: the file was just created for this state and is kept open
+        // by it, and nothing else is expected to change it while mapped
+        let map = unsafe { MmapMut::map_mut(&file)? };
+
+        let mut sv = MappedStateVector {
+            n,
+            chunk_qubits: CHUNK_QUBITS.min(n),
+            map,
+            _file: file,
+        };
+        sv.write(0, &[C::new(1.0, 0.0)]);
+        Ok(sv)
+    }
+
+    pub fn num_qubits(&self) -> u32 {
+        self.n
+    }
+
+    /// The amplitude of |i⟩.
+    pub fn amplitude(&self, i: usize) -> C {
+        let mut a = [C::zero()];
+        self.read(i, &mut a);
+        a[0]
+    }
+
+    /// Applies `gate` with its first qubit on `qubits[0]` and so on.
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        let c = self.chunk_qubits as usize;
+        let matrix = gate_matrix(gate);
+
+        // the qubits above the chunk pick out which chunks pair up; inside
+        // the buffer of those chunks, the j-th of them becomes qubit c + j
+        let high: Vec<usize> = qubits.iter().copied().filter(|&q| q >= c).collect();
+        let local: Vec<usize> = qubits
+            .iter()
+            .map(|&q| match high.iter().position(|&h| h == q) {
+                Some(j) => c + j,
+                None => q,
+            })
+            .collect();
+        let chunks: Vec<usize> = (0..1 << high.len())
+            .map(|h: usize| {
+                high.iter()
+                    .enumerate()
+                    .map(|(j, &q)| (h >> j & 1) << (q - c))
+                    .sum()
+            })
+            .collect();
+        let high_mask: usize = chunks.iter().fold(0, |m, &o| m | o);
+
+        let chunk = 1 << c;
+        let mut buffer = vec![C::zero(); chunk << high.len()];
+        for first in (0..1 << (self.n as usize - c)).filter(|i| i & high_mask == 0) {
+            for (part, &offset) in buffer.chunks_mut(chunk).zip(&chunks) {
+                self.read((first | offset) * chunk, part);
+            }
+            apply_matrix(&mut buffer, &matrix, &local);
+            for (part, &offset) in buffer.chunks(chunk).zip(&chunks) {
+                self.write((first | offset) * chunk, part);
+            }
+        }
+    }
+
+    /// Multiplies every amplitude by e^(iφ).
+    pub fn apply_global_phase(&mut self, phi: f64) {
+        let phase = C::new(phi.cos(), phi.sin());
+        self.map_chunks(|_, a| *a = *a * phase);
+    }
+
+    /// Probability of reading 1 from `qubit`.
+    pub fn probability_of_one(&self, qubit: usize) -> f64 {
+        let chunk = 1 << self.chunk_qubits;
+        let mut buffer = vec![C::zero(); chunk];
+        let mut p = 0.0;
+        for start in (0..1 << self.n).step_by(chunk) {
+            self.read(start, &mut buffer);
+            p += buffer
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| (start + i) >> qubit & 1 == 1)
+                .map(|(_, a)| a.mod_squared())
+                .sum::<f64>();
+        }
+        p
+    }
+
+    /// Measures `qubit` in the computational basis, collapsing the state onto
+    /// the outcome, which is returned as a bit.
+    pub fn measure<R: rand::Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let p1 = self.probability_of_one(qubit);
+        let outcome = rng.random::<f64>() < p1;
+        let p = if outcome { p1 } else { 1.0 - p1 };
+
+        let scale = C::new(1.0 / p.sqrt(), 0.0);
+        self.map_chunks(|i, a| {
+            *a = if (i >> qubit & 1 == 1) == outcome {
+                *a * scale
+            } else {
+                C::zero()
+            };
+        });
+        outcome
+    }
+
+    /// Writes any changes still in memory out to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        self.map.flush()
+    }
+
+    /// The state as a [`State`], reading every amplitude.
+    pub fn to_state(&self) -> State<C> {
+        let mut amplitudes = vec![C::zero(); 1 << self.n];
+        self.read(0, &mut amplitudes);
+        State::from_amplitudes(&amplitudes)
+    }
+
+    /// Updates each amplitude with `f(i, a)`, a chunk at a time.
+    fn map_chunks(&mut self, mut f: impl FnMut(usize, &mut C)) {
+        let chunk = 1 << self.chunk_qubits;
+        let mut buffer = vec![C::zero(); chunk];
+        for start in (0..1 << self.n).step_by(chunk) {
+            self.read(start, &mut buffer);
+            for (i, a) in buffer.iter_mut().enumerate() {
+                f(start + i, a);
+            }
+            self.write(start, &buffer);
+        }
+    }
+
+    /// Reads the amplitudes from index `start` on into `out`.
+    fn read(&self, start: usize, out: &mut [C]) {
+        let bytes = &self.map[start * AMPLITUDE_BYTES..(start + out.len()) * AMPLITUDE_BYTES];
+        for (a, b) in out.iter_mut().zip(bytes.chunks_exact(AMPLITUDE_BYTES)) {
+            let (re, im) = b.split_at(8);
+            *a = C::new(
+                f64::from_le_bytes(re.try_into().unwrap()),
+                f64::from_le_bytes(im.try_into().unwrap()),
+            );
+        }
+    }
+
+    /// Writes `amplitudes` from index `start` on.
+    fn write(&mut self, start: usize, amplitudes: &[C]) {
+        let bytes =
+            &mut self.map[start * AMPLITUDE_BYTES..(start + amplitudes.len()) * AMPLITUDE_BYTES];
+        for (a, b) in amplitudes
+            .iter()
+            .zip(bytes.chunks_exact_mut(AMPLITUDE_BYTES))
+        {
+            b[..8].copy_from_slice(&a.re.to_le_bytes());
+            b[8..].copy_from_slice(&a.im.to_le_bytes());
+        }
+    }
+}
+
+impl Circuit {
+    /// [`Circuit::simulate`] on a [`MappedStateVector`] in a new file at
+    /// `path`. Toffoli and multi-controlled gates are decomposed first, so
+    /// that no gate reads more than four chunks at once. Measurements,
+    /// channels and snapshots, which would copy the whole file, are not
+    /// supported, and resets only on qubits sure to read 0 or 1, as
+    /// [`Circuit::run_mapped`] resets any.
+    pub fn simulate_mapped(&self, path: impl AsRef<Path>) -> io::Result<MappedStateVector> {
+        self.execute_mapped(path, &mut None::<&mut dyn rand::RngCore>)
+    }
+
+    /// [`Circuit::simulate_mapped`], resetting a qubit by measuring it with
+    /// outcomes sampled from `rng` and flipping it on reading 1, so that
+    /// mid-circuit resets work on states too large to hold in memory.
+    pub fn run_mapped<R: rand::Rng + ?Sized>(
+        &self,
+        path: impl AsRef<Path>,
+        rng: &mut R,
+    ) -> io::Result<MappedStateVector> {
+        self.execute_mapped(path, &mut Some(rng))
+    }
+
+    fn execute_mapped<R: rand::Rng + ?Sized>(
+        &self,
+        path: impl AsRef<Path>,
+        rng: &mut Option<&mut R>,
+    ) -> io::Result<MappedStateVector> {
+        let mut sv = MappedStateVector::create(path, self.num_qubits)?;
+        for instruction in &self.decompose_multi_controlled().instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => sv.apply(gate, qubits),
+                Instruction::Reset { qubit } => {
+                    let one = match rng.as_deref_mut() {
+                        Some(rng) => sv.measure(*qubit, rng),
+                        None => definite(sv.probability_of_one(*qubit)).unwrap_or_else(|| {
+                            panic!("simulate_mapped() resets only qubits in a definite state, use run_mapped()")
+                        }),
+                    };
+                    if one {
+                        sv.apply(&Gate::X, &[*qubit]);
+                    }
+                }
+                Instruction::Barrier { .. } => {}
+                Instruction::Snapshot { .. } => {
+                    panic!("simulate_mapped() records no snapshots, use run()")
+                }
+                _ => panic!("simulate_mapped() runs circuits of gates only"),
+            }
+        }
+        sv.apply_global_phase(self.global_phase);
+        sv.flush()?;
+        Ok(sv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MappedStateVector;
+    use crate::circuit::Circuit;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::path::PathBuf;
+
+    const EPSILON: f64 = 0.0000001;
+
+    /// A file in the temporary directory, removed when dropped.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let file = format!(
+                "complex-calculator-{}-{name}.amplitudes",
+                std::process::id()
+            );
+            Scratch(std::env::temp_dir().join(file))
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn assert_agrees(sv: &MappedStateVector, c: &Circuit) {
+        let n = c.num_qubits();
+        let (mapped, dense) = (sv.to_state().amplitudes(n), c.simulate().amplitudes(n));
+        for (x, y) in mapped.iter().zip(&dense) {
+            assert!((*x - *y).modulus() < EPSILON, "{x} != {y}");
+        }
+    }
+
+    #[test]
+    fn agrees_with_the_statevector() {
+        let scratch = Scratch::new("agrees");
+        let mut c = Circuit::random(5, 5, &mut StdRng::seed_from_u64(8));
+        c.ccx(4, 0, 2).add_global_phase(0.3);
+        let sv = c.simulate_mapped(&scratch.0).unwrap();
+        assert_eq!(std::fs::metadata(&scratch.0).unwrap().len(), 16 << 5);
+        assert_agrees(&sv, &c);
+    }
+
+    #[test]
+    fn gates_across_chunks() {
+        // chunks of four amplitudes, so that most gates pair up chunks
+        let scratch = Scratch::new("chunks");
+        let c = Circuit::random(6, 6, &mut StdRng::seed_from_u64(9));
+        let mut sv = MappedStateVector::create(&scratch.0, 6).unwrap();
+        sv.chunk_qubits = 2;
+        for instruction in c.instructions() {
+            if let crate::circuit::Instruction::Gate { gate, qubits } = instruction {
+                sv.apply(gate, qubits);
+            }
+        }
+        sv.apply_global_phase(c.global_phase());
+        assert_agrees(&sv, &c);
+    }
+
+    #[test]
+    fn measurement_collapses() {
+        let scratch = Scratch::new("measure");
+        let mut c = Circuit::new(18);
+        c.h(0).cnot(0, 17);
+        let mut sv = c.simulate_mapped(&scratch.0).unwrap();
+        assert!((sv.probability_of_one(17) - 0.5).abs() < EPSILON);
+
+        let one = sv.measure(0, &mut StdRng::seed_from_u64(3));
+        let x = if one { 1 | 1 << 17 } else { 0 };
+        assert!((sv.amplitude(x).re - 1.0).abs() < EPSILON);
+        assert!((sv.probability_of_one(17) - if one { 1.0 } else { 0.0 }).abs() < EPSILON);
+    }
+
+    #[test]
+    fn reset_reuses_entangled_qubits() {
+        let scratch = Scratch::new("reset");
+        crate::circuit::tests::assert_resets_superpositions(
+            |c, rng| c.run_mapped(&scratch.0, rng).unwrap().to_state(),
+            |c| c.simulate_mapped(&scratch.0).unwrap().to_state(),
+        );
+    }
+
+    #[test]
+    fn snapshots_are_rejected() {
+        let scratch = Scratch::new("snapshot");
+        let mut c = Circuit::new(1);
+        c.h(0).snapshot("mid");
+        let message = crate::circuit::tests::panic_message(|| c.simulate_mapped(&scratch.0));
+        assert_eq!(message, "simulate_mapped() records no snapshots, use run()");
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::{BTreeMap, HashMap};
+
+use num::Zero;
+
+use super::statevector::{apply_matrix, gate_matrix, operator_matrix};
+use super::{Circuit, Gate, Instruction};
+use crate::channel::Channel;
+use crate::density::DensityMatrix;
+use crate::Complex;
+
+/// A dense n-qubit density matrix, the mixed-state counterpart of
+/// [`StateVector`](super::StateVector). Entry ⟨ket|ρ|bra⟩ is stored at
+/// `ket | bra << n`, so ρ is a vector over 2n bits and U ρ U† is U applied to
+/// the ket bits and U* to the bra bits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedState {
+    n: u32,
+    entries: Vec<Complex<f64>>,
+}
+
+impl MixedState {
+    /// The pure state |0…0⟩⟨0…0|.
+    pub fn new(n: u32) -> Self {
+        let mut entries = vec![Complex::zero(); 1 << (2 * n)];
+        entries[0] = Complex::new(1.0, 0.0);
+        MixedState { n, entries }
+    }
+
+    pub fn num_qubits(&self) -> u32 {
+        self.n
+    }
+
+    /// ⟨ket|ρ|bra⟩.
+    pub fn entry(&self, ket: usize, bra: usize) -> Complex<f64> {
+        self.entries[ket | bra << self.n]
+    }
+
+    /// tr ρ, below 1 for the unnormalised branches of a measurement.
+    pub fn trace(&self) -> f64 {
+        (0..1 << self.n).map(|k| self.entry(k, k).re).sum()
+    }
+
+    /// Unitary evolution ρ → U ρ U† by `gate` on `qubits`.
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        self.sandwich(&gate_matrix(gate), qubits);
+    }
+
+    /// ρ → Σ Kᵢ ρ Kᵢ† with `channel` acting on `qubits`.
+    pub fn apply_channel(&mut self, channel: &Channel, qubits: &[usize]) {
+        let mut total = vec![Complex::zero(); self.entries.len()];
+        for k in channel.kraus() {
+            let mut branch = self.clone();
+            branch.sandwich(&operator_matrix(k, qubits.len()), qubits);
+            for (sum, x) in total.iter_mut().zip(branch.entries) {
+                *sum += x;
+            }
+        }
+        self.entries = total;
+    }
+
+    /// ρ → M ρ M† for the 2^k × 2^k `matrix` on `qubits`.
+    fn sandwich(&mut self, matrix: &[Complex<f64>], qubits: &[usize]) {
+        apply_matrix(&mut self.entries, matrix, qubits);
+
+        let conjugate: Vec<Complex<f64>> = matrix.iter().map(|x| x.conjugate()).collect();
+        let bra_qubits: Vec<usize> = qubits.iter().map(|q| q + self.n as usize).collect();
+        apply_matrix(&mut self.entries, &conjugate, &bra_qubits);
+    }
+
+    /// Probability of reading 1 from `qubit`.
+    pub fn probability_of_one(&self, qubit: usize) -> f64 {
+        (0..1 << self.n)
+            .filter(|k| k >> qubit & 1 == 1)
+            .map(|k| self.entry(k, k).re)
+            .sum()
+    }
+
+    /// The unnormalised state P ρ P after reading `outcome` from `qubit`, with
+    /// the probability of that outcome as its trace.
+    pub fn project(&self, qubit: usize, outcome: bool) -> MixedState {
+        let n = self.n as usize;
+        let entries = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                let (ket, bra) = (i >> qubit & 1 == 1, i >> (qubit + n) & 1 == 1);
+                if ket == outcome && bra == outcome {
+                    x
+                } else {
+                    Complex::zero()
+                }
+            })
+            .collect();
+        MixedState { n: self.n, entries }
+    }
+
+    pub fn to_density_matrix(&self) -> DensityMatrix<Complex<f64>> {
+        let n = self.n;
+        let ones: HashMap<(u64, u64), Complex<f64>> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| ((i as u64 & ((1 << n) - 1), i as u64 >> n), x))
+            .collect();
+        DensityMatrix::from_terms(ones, n, 2)
+    }
+
+    fn add(&mut self, other: &MixedState) {
+        for (a, b) in self.entries.iter_mut().zip(&other.entries) {
+            *a += *b;
+        }
+    }
+}
+
+impl Circuit {
+    /// Runs the circuit on |0…0⟩⟨0…0| as a density matrix. Rather than
+    /// sampling, every measurement record is followed as its own branch, so
+    /// the result is the exact mixture over outcomes, with classically
+    /// conditioned gates applied in the branches where their bit is set.
+    /// Noise channels are applied exactly.
+    pub fn simulate_density(&self) -> DensityMatrix<Complex<f64>> {
+        self.simulate_density_with_snapshots().0
+    }
+
+    /// [`Circuit::simulate_density`], also returning the mixture over every
+    /// branch at each snapshot, with its label, in the order taken. Inside a
+    /// loop body only the branches still looping are included, so the
+    /// snapshot there has trace below 1.
+    pub fn simulate_density_with_snapshots(
+        &self,
+    ) -> (DensityMatrix<Complex<f64>>, DensitySnapshots) {
+        let mut snapshots = vec![];
+        let branches = self.evolve_branches(
+            BTreeMap::from([(
+                vec![false; self.num_clbits],
+                MixedState::new(self.num_qubits),
+            )]),
+            &mut snapshots,
+        );
+        (total(&branches).to_density_matrix(), snapshots)
+    }
+
+    /// Applies the instructions to each branch, keyed by its classical bits,
+    /// recording `snapshots` of their mixture.
+    fn evolve_branches(
+        &self,
+        mut branches: Branches,
+        snapshots: &mut DensitySnapshots,
+    ) -> Branches {
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => {
+                    for rho in branches.values_mut() {
+                        rho.apply(gate, qubits);
+                    }
+                }
+                Instruction::Measure { qubit, clbit } => {
+                    let mut next = Branches::new();
+                    for (clbits, rho) in branches {
+                        for outcome in [false, true] {
+                            let projected = rho.project(*qubit, outcome);
+                            if projected.trace() < 1e-15 {
+                                continue;
+                            }
+
+                            let mut clbits = clbits.clone();
+                            clbits[*clbit] = outcome;
+                            merge(&mut next, clbits, projected);
+                        }
+                    }
+                    branches = next;
+                }
+                Instruction::Reset { qubit } => {
+                    for rho in branches.values_mut() {
+                        let mut flipped = rho.project(*qubit, true);
+                        flipped.apply(&Gate::X, &[*qubit]);
+                        let mut reset = rho.project(*qubit, false);
+                        reset.add(&flipped);
+                        *rho = reset;
+                    }
+                }
+                Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => {
+                    for (clbits, rho) in branches.iter_mut() {
+                        if clbits[*clbit] {
+                            rho.apply(gate, qubits);
+                        }
+                    }
+                }
+                Instruction::Channel { channel, qubits } => {
+                    for rho in branches.values_mut() {
+                        rho.apply_channel(channel, qubits);
+                    }
+                }
+                Instruction::While {
+                    clbit,
+                    value,
+                    max_iterations,
+                    body,
+                } => {
+                    // only the branches still looping run the body again
+                    for _ in 0..*max_iterations {
+                        let (looping, done): (Branches, Branches) = branches
+                            .into_iter()
+                            .partition(|(clbits, _)| clbits[*clbit] == *value);
+                        branches = done;
+                        if looping.is_empty() {
+                            break;
+                        }
+                        for (clbits, rho) in body.evolve_branches(looping, snapshots) {
+                            merge(&mut branches, clbits, rho);
+                        }
+                    }
+                }
+                Instruction::Snapshot { label } => {
+                    snapshots.push((label.clone(), total(&branches).to_density_matrix()))
+                }
+                Instruction::Barrier { .. } => {}
+            }
+        }
+        branches
+    }
+}
+
+type Branches = BTreeMap<Vec<bool>, MixedState>;
+
+/// Labelled density matrices recorded by snapshot instructions.
+type DensitySnapshots = Vec<(String, DensityMatrix<Complex<f64>>)>;
+
+/// The mixture of all `branches`.
+fn total(branches: &Branches) -> MixedState {
+    let mut branches = branches.values();
+    let mut total = branches.next().unwrap().clone();
+    for rho in branches {
+        total.add(rho);
+    }
+    total
+}
+
+/// Adds `rho` to the branch for `clbits`, starting it if there is none.
+fn merge(branches: &mut Branches, clbits: Vec<bool>, rho: MixedState) {
+    match branches.get_mut(&clbits) {
+        Some(existing) => existing.add(&rho),
+        None => {
+            branches.insert(clbits, rho);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MixedState;
+    use crate::channel::Channel;
+    use crate::circuit::{Circuit, Gate};
+    use crate::density::DensityMatrix;
+    use crate::ket::Operator;
+    use crate::Complex as C;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn assert_close(a: &DensityMatrix<C<f64>>, b: &DensityMatrix<C<f64>>) {
+        let n = a.num_qubits();
+        for ket in 0..1 << n {
+            for bra in 0..1 << n {
+                let entry = |rho: &DensityMatrix<C<f64>>| {
+                    rho.operator()
+                        .terms()
+                        .filter(|&(_, k, b, _)| (k, b) == (ket, bra))
+                        .map(|(s, ..)| s)
+                        .fold(C::new(0.0, 0.0), |sum, s| sum + s)
+                };
+                assert!(
+                    (entry(a) - entry(b)).modulus() < EPSILON,
+                    "differs at ({ket}, {bra})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unitary_circuit_stays_pure() {
+        let mut c = Circuit::new(3);
+        c.h(0).cnot(0, 2).ry(1, 0.7).t(2).swap(1, 2).cz(0, 1);
+
+        let expected = DensityMatrix::from_state(&c.simulate());
+        assert_close(&c.simulate_density(), &expected);
+    }
+
+    #[test]
+    fn apply_conjugates_the_bra_side() {
+        let mut rho = MixedState::new(2);
+        rho.apply(&Gate::H, &[1]);
+        rho.apply(&Gate::S, &[1]);
+        rho.apply(&Gate::Cx, &[1, 0]);
+
+        assert!((rho.entry(0b11, 0b00) - C::new(0.0, 0.5)).modulus() < EPSILON);
+        assert!((rho.entry(0b00, 0b11) - C::new(0.0, -0.5)).modulus() < EPSILON);
+        assert!((rho.trace() - 1.0).abs() < EPSILON);
+        assert!((rho.probability_of_one(0) - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn measurement_removes_coherences() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).measure(0, 0);
+
+        let rho = c.simulate_density();
+        assert_eq!(rho.operator().ones.len(), 2);
+        for (_, p) in rho.probabilities() {
+            assert!((p.re - 0.5).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn snapshots_of_the_mixture() {
+        let mut c = Circuit::new(2);
+        c.h(0)
+            .cnot(0, 1)
+            .snapshot("bell")
+            .measure(0, 0)
+            .snapshot("measured");
+
+        let (rho, snapshots) = c.simulate_density_with_snapshots();
+        assert_eq!(snapshots.len(), 2);
+        let mut bell = Circuit::new(2);
+        bell.h(0).cnot(0, 1);
+        assert_close(
+            &snapshots[0].1,
+            &DensityMatrix::from_state(&bell.simulate()),
+        );
+        assert_close(&snapshots[1].1, &rho);
+        assert!(snapshots[0].1.operator().ones.len() > rho.operator().ones.len());
+    }
+
+    #[test]
+    fn reset_leaves_the_partner_mixed() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).reset(0);
+
+        let rho = c.simulate_density();
+        assert!((rho.probability(0b00).re - 0.5).abs() < EPSILON);
+        assert!((rho.probability(0b10).re - 0.5).abs() < EPSILON);
+        assert_eq!(rho.operator().ones.len(), 2);
+    }
+
+    #[test]
+    fn project_gives_outcome_probability() {
+        let mut rho = MixedState::new(1);
+        rho.apply(&Gate::Ry(1.0.into()), &[0]);
+
+        let (zero, one) = (rho.project(0, false), rho.project(0, true));
+        assert!((zero.trace() - 0.5_f64.cos().powi(2)).abs() < EPSILON);
+        assert!((one.trace() - 0.5_f64.sin().powi(2)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn teleportation_is_deterministic() {
+        let mut c = Circuit::new(3);
+        c.u3(0, 1.2, 0.4, -0.3)
+            .h(1)
+            .cnot(1, 2)
+            .cnot(0, 1)
+            .h(0)
+            .measure(0, 0)
+            .measure(1, 1)
+            .c_if(1, Gate::X, &[2])
+            .c_if(0, Gate::Z, &[2]);
+
+        let mut input = Circuit::new(1);
+        input.u3(0, 1.2, 0.4, -0.3);
+        let expected = DensityMatrix::from_state(&input.simulate());
+
+        assert_close(&c.simulate_density().trace_out(&[0, 1]), &expected);
+    }
+
+    #[test]
+    fn noisy_circuit_matches_channel_application() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).x(1);
+        let noisy = c.with_noise(|gate| match gate {
+            Gate::Cx => Some(Channel::depolarizing(0.1)),
+            Gate::X => Some(Channel::amplitude_damping(0.2)),
+            _ => None,
+        });
+        assert_eq!(noisy.gate_counts()["depolarizing"], 2);
+        assert_eq!(noisy.gate_counts()["amplitude_damping"], 1);
+
+        let mut bell = Circuit::new(2);
+        bell.h(0).cnot(0, 1);
+        let mut flipped = Circuit::new(1);
+        flipped.x(0);
+        let expected = DensityMatrix::from_state(&bell.simulate())
+            .apply_channel(&Channel::depolarizing(0.1), &[0])
+            .apply_channel(&Channel::depolarizing(0.1), &[1])
+            .evolve(&Gate::X.operator().embed(&[1], 2))
+            .apply_channel(&Channel::amplitude_damping(0.2), &[1]);
+
+        assert_close(&noisy.simulate_density(), &expected);
+    }
+
+    #[test]
+    fn two_qubit_channel_follows_its_gate() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1);
+        let zz = Gate::Z.operator().tensor(&Gate::Z.operator());
+        let dephase = Channel::new(vec![
+            Operator::identity(2) * C::new(0.5_f64.sqrt(), 0.0),
+            zz * C::new(0.5_f64.sqrt(), 0.0),
+        ]);
+
+        let noisy = c.with_noise(|gate| (*gate == Gate::Cx).then(|| dephase.clone()));
+        assert_eq!(noisy.len(), 3);
+        // ZZ leaves the Bell state alone
+        assert_close(
+            &noisy.simulate_density(),
+            &DensityMatrix::from_state(&c.simulate()),
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Modular arithmetic for Shor's algorithm, built from the constant adders
+//! of the arithmetic module after Vedral, Barenco and Ekert, "Quantum
+//! networks for elementary arithmetic operations" (1996): modular addition
+//! of a constant, controlled modular multiplication in place, and modular
+//! exponentiation by repeated squaring of the base. Unlike
+//! [`Circuit::from_permutation`], their size grows polynomially with the
+//! number of bits of the modulus.
+//!
+//! The circuits are specified on inputs below the modulus with every
+//! ancilla at 0, and [`Circuit::permutation_counterexample`] checks them
+//! there.
+
+use super::Circuit;
+
+/// Bits needed to hold the numbers below `modulus`.
+fn bits(modulus: u64) -> u32 {
+    u64::BITS - (modulus - 1).leading_zeros()
+}
+
+/// The inverse of `a` modulo `modulus`, by the extended Euclidean algorithm.
+fn mod_inverse(a: u64, modulus: u64) -> u64 {
+    let (mut r, mut next_r) = (modulus as i128, a as i128 % modulus as i128);
+    let (mut t, mut next_t) = (0i128, 1i128);
+    while next_r != 0 {
+        let q = r / next_r;
+        (r, next_r) = (next_r, r - q * next_r);
+        (t, next_t) = (next_t, t - q * next_t);
+    }
+    assert!(r == 1, "{a} has no inverse modulo {modulus}");
+    t.rem_euclid(modulus as i128) as u64
+}
+
+fn check_modulus(modulus: u64) {
+    assert!(modulus >= 2, "modulus {modulus} leaves nothing to compute");
+}
+
+impl Circuit {
+    /// |x⟩|0⟩ ↦ |x + k mod N⟩|0⟩ for x < N = `modulus` on L + 2 qubits, with
+    /// L the bits of N: x on qubits 0 to L − 1, an overflow bit on L and a
+    /// flag on L + 1. Adding k and subtracting N leaves the overflow bit set
+    /// exactly when x + k < N, which is copied to the flag to add N back.
+    /// The flag is then 1 exactly when the result is at least k, which
+    /// clears it.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// // 4 + 5 = 9 = 2 mod 7, on three bits and two ancillas
+    /// assert_eq!(Circuit::add_constant_mod(5, 7).classical_output(4), 2);
+    /// ```
+    pub fn add_constant_mod(k: u64, modulus: u64) -> Circuit {
+        check_modulus(modulus);
+        let (l, k) = (bits(modulus), k % modulus);
+        let register: Vec<usize> = (0..=l as usize).collect();
+        let (overflow, flag) = (l as usize, l as usize + 1);
+
+        let mut c = Circuit::new(l + 2);
+        c.compose(&Circuit::add_constant(l + 1, k), &register)
+            .compose(
+                &Circuit::add_constant(l + 1, (1 << (l + 1)) - modulus),
+                &register,
+            )
+            .cnot(overflow, flag);
+        let mut controlled_register = vec![flag];
+        controlled_register.extend(&register);
+        c.compose(
+            &Circuit::add_constant(l + 1, modulus).controlled(),
+            &controlled_register,
+        );
+        let mut low = register[..l as usize].to_vec();
+        low.push(flag);
+        c.compose(&Circuit::less_than_constant(l, k), &low).x(flag);
+        c
+    }
+
+    /// |c⟩|x⟩|y⟩|0⟩ ↦ |c⟩|x⟩|y + c·ax mod N⟩|0⟩ for x, y < N: the control
+    /// on qubit 0, x on the L qubits from 1, y on the L + 1 qubits from
+    /// L + 1, its top bit an overflow bit at 0, and a flag on 2L + 2. Bit i
+    /// of x adds a·2^i mod N with [`Circuit::add_constant_mod`], controlled
+    /// on it and on c.
+    fn multiply_add_mod(a: u64, modulus: u64) -> Circuit {
+        let l = bits(modulus) as usize;
+        let mut c = Circuit::new(2 * l as u32 + 3);
+        let mut term = a % modulus;
+        for i in 0..l {
+            let mut qubits = vec![0, 1 + i];
+            qubits.extend(l + 1..=2 * l + 2);
+            c.compose(
+                &Circuit::add_constant_mod(term, modulus)
+                    .controlled()
+                    .controlled(),
+                &qubits,
+            );
+            term = 2 * term % modulus;
+        }
+        c
+    }
+
+    /// The controlled modular multiplication |c⟩|x⟩|0⟩ ↦ |c⟩|a^c x mod N⟩|0⟩
+    /// for x < N, with `a` coprime to N = `modulus`, on 2L + 3 qubits: the
+    /// control on qubit 0, x on the L qubits from 1, and L + 2 ancillas
+    /// after them. ax is added into the ancillas, swapped with x, and
+    /// a⁻¹·ax = x is subtracted back out of them, leaving them at 0.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// // 7 · 4 = 28 = 13 mod 15, with x on qubits 1 to 4
+    /// let c = Circuit::multiply_mod(7, 15);
+    /// assert_eq!(c.classical_output(1 | 4 << 1), 1 | 13 << 1);
+    /// assert_eq!(c.classical_output(4 << 1), 4 << 1);
+    /// ```
+    pub fn multiply_mod(a: u64, modulus: u64) -> Circuit {
+        check_modulus(modulus);
+        let l = bits(modulus) as usize;
+        let inverse = mod_inverse(a, modulus);
+        let all: Vec<usize> = (0..2 * l + 3).collect();
+
+        let mut swap = Circuit::new(2 * l as u32);
+        for i in 0..l {
+            swap.swap(i, l + i);
+        }
+        let mut swapped = vec![0];
+        swapped.extend(1..=l);
+        swapped.extend(l + 1..=2 * l);
+
+        let mut c = Circuit::new(2 * l as u32 + 3);
+        c.compose(&Circuit::multiply_add_mod(a, modulus), &all)
+            .compose(&swap.controlled(), &swapped)
+            .compose(&Circuit::multiply_add_mod(inverse, modulus).inverse(), &all);
+        c
+    }
+
+    /// |e⟩|y⟩|0⟩ ↦ |e⟩|a^e y mod N⟩|0⟩ for y < N, with `a` coprime to N =
+    /// `modulus`: the exponent on qubits 0 to t − 1 for t = `exponent_bits`,
+    /// y on the L qubits from t, and L + 2 ancillas after them. Bit j of the
+    /// exponent controls [`Circuit::multiply_mod`] by a^(2^j) mod N.
+    pub fn modular_exponentiation(exponent_bits: u32, a: u64, modulus: u64) -> Circuit {
+        check_modulus(modulus);
+        let (t, l) = (exponent_bits as usize, bits(modulus) as usize);
+        let mut c = Circuit::new((t + 2 * l + 2) as u32);
+        let mut factor = a % modulus;
+        for j in 0..t {
+            let mut qubits = vec![j];
+            qubits.extend(t..t + 2 * l + 2);
+            c.compose(&Circuit::multiply_mod(factor, modulus), &qubits);
+            factor = factor * factor % modulus;
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bits, mod_inverse};
+    use crate::circuit::Circuit;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn mod_pow(a: u64, e: u64, m: u64) -> u64 {
+        (0..e).fold(1 % m, |acc, _| acc * a % m)
+    }
+
+    #[test]
+    fn inverses() {
+        assert_eq!(mod_inverse(7, 15), 13);
+        assert_eq!(mod_inverse(2, 21), 11);
+        assert_eq!(mod_inverse(1, 2), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "6 has no inverse modulo 15")]
+    fn shared_factors_have_no_inverse() {
+        Circuit::multiply_mod(6, 15);
+    }
+
+    #[test]
+    fn adds_constants_modulo() {
+        for modulus in [2, 3, 5, 7, 8, 13] {
+            for k in 0..modulus {
+                let c = Circuit::add_constant_mod(k, modulus);
+                assert_eq!(c.num_qubits(), bits(modulus) + 2);
+                assert_eq!(
+                    c.permutation_counterexample(0..modulus, |x| (x + k) % modulus),
+                    None,
+                    "+ {k} mod {modulus}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn multiplies_in_place() {
+        for (a, modulus) in [(7, 15), (2, 15), (4, 21), (5, 21), (3, 7)] {
+            let l = bits(modulus);
+            let c = Circuit::multiply_mod(a, modulus);
+            assert_eq!(c.num_qubits(), 2 * l + 3);
+            let inputs = (0..modulus).flat_map(|x| [x << 1, x << 1 | 1]);
+            let f = |input: u64| {
+                let x = input >> 1;
+                if input & 1 == 1 {
+                    (a * x % modulus) << 1 | 1
+                } else {
+                    input
+                }
+            };
+            assert_eq!(
+                c.permutation_counterexample(inputs, f),
+                None,
+                "× {a} mod {modulus}"
+            );
+        }
+    }
+
+    #[test]
+    fn exponentiates() {
+        let (a, modulus, t) = (7, 15, 4);
+        let c = Circuit::modular_exponentiation(t, a, modulus);
+        assert_eq!(c.num_qubits(), 4 + 4 + 6);
+        let inputs = (0..1 << t).flat_map(|e| (0..modulus).map(move |y| e | y << t));
+        let f = |input: u64| {
+            let (e, y) = (input & 0xf, input >> t);
+            e | (mod_pow(a, e, modulus) * y % modulus) << t
+        };
+        assert_eq!(c.permutation_counterexample(inputs, f), None);
+    }
+
+    #[test]
+    fn sizes_grow_polynomially() {
+        // doubling the bits of the modulus multiplies the gates by less than
+        // 8, where the truth table of the multiplication grows 16 times
+        let gates = |modulus| Circuit::multiply_mod(2, modulus).len();
+        let (small, large) = (gates(15), gates(255));
+        assert!(large < small * 8, "{small} → {large}");
+    }
+
+    #[test]
+    fn runs_phase_estimation() {
+        // 7 has order 4 mod 15, so a 4-bit phase reads a multiple of 4
+        let (t, l) = (4, 4);
+        let mut c = Circuit::new(t + 2 * l + 2);
+        c.x(t as usize);
+        for q in 0..t as usize {
+            c.h(q);
+        }
+        c.compose(
+            &Circuit::modular_exponentiation(t, 7, 15),
+            &(0..(t + 2 * l + 2) as usize).collect::<Vec<_>>(),
+        )
+        .compose(&Circuit::iqft(t), &[0, 1, 2, 3]);
+        for q in 0..t as usize {
+            c.measure(q, q);
+        }
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut seen = [false; 4];
+        for _ in 0..30 {
+            let outcome = c.run_sparse(&mut rng).register();
+            assert_eq!(outcome % 4, 0, "{outcome}");
+            seen[outcome as usize / 4] = true;
+        }
+        assert_eq!(seen, [true; 4]);
+    }
+}
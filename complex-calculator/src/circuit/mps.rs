@@ -0,0 +1,613 @@
+use num::Zero;
+
+use super::statevector::gate_matrix;
+use super::{definite, Circuit, Gate, Instruction};
+use crate::ket::State;
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// Singular values below this, relative to the largest, are dropped.
+const CUTOFF: f64 = 1e-12;
+
+/// An n-qubit state as a matrix-product state, one rank-3 tensor per qubit,
+/// with bonds capped at `max_bond`. Circuits with little entanglement keep
+/// the bonds small, so they can be simulated on far more qubits than a
+/// statevector allows; a cap below the entanglement needed gives the best
+/// approximation of that bond dimension.
+///
+/// The state is kept in mixed canonical form around one site, so that the
+/// singular values at a bond are its Schmidt coefficients and truncating
+/// them is optimal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mps {
+    sites: Vec<Site>,
+    center: usize,
+    max_bond: usize,
+    truncation_error: f64,
+}
+
+/// A tensor `A[left][physical][right]`, stored in that order.
+#[derive(Debug, Clone, PartialEq)]
+struct Site {
+    left: usize,
+    right: usize,
+    data: Vec<C>,
+}
+
+impl Site {
+    fn at(&self, l: usize, s: usize, r: usize) -> C {
+        self.data[(l * 2 + s) * self.right + r]
+    }
+}
+
+impl Mps {
+    /// The product state |0…0⟩.
+    pub fn new(n: u32, max_bond: usize) -> Self {
+        assert!(n > 0, "an MPS needs at least one qubit");
+        assert!(max_bond > 0, "bond dimension must be positive");
+
+        let zero = Site {
+            left: 1,
+            right: 1,
+            data: vec![C::new(1.0, 0.0), C::zero()],
+        };
+        Mps {
+            sites: vec![zero; n as usize],
+            center: 0,
+            max_bond,
+            truncation_error: 0.0,
+        }
+    }
+
+    pub fn num_qubits(&self) -> u32 {
+        self.sites.len() as u32
+    }
+
+    /// Dimensions of the n − 1 bonds between neighbouring qubits.
+    pub fn bond_dimensions(&self) -> Vec<usize> {
+        self.sites[1..].iter().map(|site| site.left).collect()
+    }
+
+    /// Sum of the squared Schmidt coefficients discarded so far, as a
+    /// fraction of the state's weight at each split; around machine precision
+    /// while the state is exact.
+    pub fn truncation_error(&self) -> f64 {
+        self.truncation_error
+    }
+
+    /// Multiplies the state by e^(iφ).
+    pub fn apply_global_phase(&mut self, phi: f64) {
+        let phase = C::new(phi.cos(), phi.sin());
+        for a in &mut self.sites[self.center].data {
+            *a = *a * phase;
+        }
+    }
+
+    /// Applies a one- or two-qubit `gate` on `qubits`. Two-qubit gates on
+    /// qubits that are not neighbours are routed with swaps.
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        let matrix = gate_matrix(gate);
+        match *qubits {
+            [q] => self.apply_single(&matrix, q),
+            [a, b] => {
+                // bring b next to a, apply, and move it back
+                let swap = gate_matrix(&Gate::Swap);
+                let (mut b_now, step) = (b, if b > a { -1 } else { 1 });
+                while b_now.abs_diff(a) > 1 {
+                    let next = (b_now as isize + step) as usize;
+                    self.apply_adjacent(&swap, next.min(b_now), false);
+                    b_now = next;
+                }
+
+                self.apply_adjacent(&matrix, a.min(b_now), a > b_now);
+
+                while b_now != b {
+                    let next = (b_now as isize - step) as usize;
+                    self.apply_adjacent(&swap, next.min(b_now), false);
+                    b_now = next;
+                }
+            }
+            _ => panic!("MPS gates act on at most two qubits, decompose {gate} first"),
+        }
+    }
+
+    fn apply_single(&mut self, matrix: &[C], q: usize) {
+        let site = &mut self.sites[q];
+        let (left, right) = (site.left, site.right);
+        let old = site.data.clone();
+
+        for l in 0..left {
+            for r in 0..right {
+                for s in 0..2 {
+                    site.data[(l * 2 + s) * right + r] = (0..2)
+                        .map(|t| matrix[s * 2 + t] * old[(l * 2 + t) * right + r])
+                        .fold(C::zero(), |sum, x| sum + x);
+                }
+            }
+        }
+    }
+
+    /// Applies the 4 × 4 `matrix` to sites `p` and `p + 1`, with its first
+    /// qubit on `p` unless `reversed`.
+    fn apply_adjacent(&mut self, matrix: &[C], p: usize, reversed: bool) {
+        self.move_center(p);
+        let (a, b) = (&self.sites[p], &self.sites[p + 1]);
+        let (left, mid, right) = (a.left, a.right, b.right);
+
+        // θ[l][s][t][r] = Σ_m A[l][s][m] B[m][t][r]
+        let mut theta = vec![C::zero(); left * 4 * right];
+        for l in 0..left {
+            for s in 0..2 {
+                for m in 0..mid {
+                    let x = a.at(l, s, m);
+                    if x.is_zero() {
+                        continue;
+                    }
+                    for t in 0..2 {
+                        for r in 0..right {
+                            theta[((l * 2 + s) * 2 + t) * right + r] += x * b.at(m, t, r);
+                        }
+                    }
+                }
+            }
+        }
+
+        // the gate's local index has its first qubit as the low bit
+        let local = |s: usize, t: usize| if reversed { t | s << 1 } else { s | t << 1 };
+        let mut gated = vec![C::zero(); theta.len()];
+        for l in 0..left {
+            for r in 0..right {
+                for s in 0..2 {
+                    for t in 0..2 {
+                        let mut sum = C::zero();
+                        for s0 in 0..2 {
+                            for t0 in 0..2 {
+                                sum += matrix[local(s, t) * 4 + local(s0, t0)]
+                                    * theta[((l * 2 + s0) * 2 + t0) * right + r];
+                            }
+                        }
+                        gated[((l * 2 + s) * 2 + t) * right + r] = sum;
+                    }
+                }
+            }
+        }
+
+        // split θ as rows (l, s) by columns (t, r), keeping the largest
+        // Schmidt coefficients, with the centre moving on to p + 1
+        let (u, sigma, vh) = svd(&gated, left * 2, 2 * right);
+        let keep = sigma
+            .iter()
+            .take(self.max_bond)
+            .take_while(|&&x| x > CUTOFF * sigma[0])
+            .count()
+            .max(1);
+
+        let dropped: f64 = sigma[keep..].iter().map(|x| x * x).sum();
+        let kept: f64 = sigma[..keep].iter().map(|x| x * x).sum();
+        self.truncation_error += dropped / (kept + dropped);
+        let norm = kept.sqrt();
+
+        let k = sigma.len();
+        self.sites[p] = Site {
+            left,
+            right: keep,
+            data: (0..left * 2)
+                .flat_map(|row| (0..keep).map(move |j| (row, j)))
+                .map(|(row, j)| u[row * k + j])
+                .collect(),
+        };
+        self.sites[p + 1] = Site {
+            left: keep,
+            right,
+            data: (0..keep)
+                .flat_map(|j| (0..2 * right).map(move |col| (j, col)))
+                .map(|(j, col)| vh[j * 2 * right + col] * C::new(sigma[j] / norm, 0.0))
+                .collect(),
+        };
+        self.center = p + 1;
+    }
+
+    /// Moves the orthogonality centre to site `q` by splitting off isometries.
+    fn move_center(&mut self, q: usize) {
+        while self.center < q {
+            let c = self.center;
+            let site = &self.sites[c];
+            let (left, right) = (site.left, site.right);
+            let (u, sigma, vh) = svd(&site.data, left * 2, right);
+            let k = sigma.len();
+
+            // A = U (Σ V†), with Σ V† absorbed into the next site
+            let carry: Vec<C> = (0..k)
+                .flat_map(|j| (0..right).map(move |col| (j, col)))
+                .map(|(j, col)| vh[j * right + col] * C::new(sigma[j], 0.0))
+                .collect();
+            self.sites[c] = Site {
+                left,
+                right: k,
+                data: u,
+            };
+            let next = &self.sites[c + 1];
+            self.sites[c + 1] = Site {
+                left: k,
+                right: next.right,
+                data: matmul(&carry, &next.data, k, right, 2 * next.right),
+            };
+            self.center += 1;
+        }
+
+        while self.center > q {
+            let c = self.center;
+            let site = &self.sites[c];
+            let (left, right) = (site.left, site.right);
+            let (u, sigma, vh) = svd(&site.data, left, 2 * right);
+            let k = sigma.len();
+
+            // A = (U Σ) V†, with U Σ absorbed into the previous site
+            let carry: Vec<C> = (0..left)
+                .flat_map(|row| (0..k).map(move |j| (row, j)))
+                .map(|(row, j)| u[row * k + j] * C::new(sigma[j], 0.0))
+                .collect();
+            self.sites[c] = Site {
+                left: k,
+                right,
+                data: vh,
+            };
+            let previous = &self.sites[c - 1];
+            self.sites[c - 1] = Site {
+                left: previous.left,
+                right: k,
+                data: matmul(&previous.data, &carry, previous.left * 2, left, k),
+            };
+            self.center -= 1;
+        }
+    }
+
+    /// Probability of reading 1 from `qubit`.
+    pub fn probability_of_one(&mut self, qubit: usize) -> f64 {
+        self.move_center(qubit);
+        let site = &self.sites[qubit];
+        let weight = |s: usize| -> f64 {
+            (0..site.left)
+                .flat_map(|l| (0..site.right).map(move |r| (l, r)))
+                .map(|(l, r)| site.at(l, s, r).mod_squared())
+                .sum()
+        };
+        let one = weight(1);
+        one / (weight(0) + one)
+    }
+
+    /// Measures `qubit` in the computational basis, collapsing the state onto
+    /// the outcome, which is returned as a bit.
+    pub fn measure<R: rand::Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let p1 = self.probability_of_one(qubit);
+        let outcome = rng.random::<f64>() < p1;
+        let p = if outcome { p1 } else { 1.0 - p1 };
+
+        let site = &mut self.sites[qubit];
+        let right = site.right;
+        let scale = C::new(1.0 / p.sqrt(), 0.0);
+        for (i, x) in site.data.iter_mut().enumerate() {
+            *x = if (i / right % 2 == 1) == outcome {
+                *x * scale
+            } else {
+                C::zero()
+            };
+        }
+        outcome
+    }
+
+    /// The amplitude ⟨x|ψ⟩, a product of one matrix per qubit.
+    pub fn amplitude(&self, x: u64) -> C {
+        let mut row = vec![C::new(1.0, 0.0)];
+        for (q, site) in self.sites.iter().enumerate() {
+            let s = (x >> q & 1) as usize;
+            row = (0..site.right)
+                .map(|r| {
+                    (0..site.left)
+                        .map(|l| row[l] * site.at(l, s, r))
+                        .fold(C::zero(), |sum, v| sum + v)
+                })
+                .collect();
+        }
+        row[0]
+    }
+
+    /// The dense state, for checking small cases.
+    pub fn to_state(&self) -> State<C> {
+        let amplitudes: Vec<C> = (0..1u64 << self.sites.len())
+            .map(|x| self.amplitude(x))
+            .collect();
+        State::from_amplitudes(&amplitudes)
+    }
+}
+
+impl Circuit {
+    /// Runs the circuit on |0…0⟩ as a matrix-product state with bonds capped
+    /// at `max_bond`. Toffoli and multi-controlled gates are decomposed
+    /// first; measurements and channels are not supported, nor snapshots,
+    /// which would each need the full statevector, and resets only on qubits
+    /// sure to read 0 or 1, as [`Circuit::run_mps`] resets any.
+    pub fn simulate_mps(&self, max_bond: usize) -> Mps {
+        self.execute_mps(max_bond, &mut None::<&mut dyn rand::RngCore>)
+    }
+
+    /// [`Circuit::simulate_mps`], resetting a qubit by measuring it with
+    /// outcomes sampled from `rng` and flipping it back to 0 on reading 1,
+    /// so that qubits left entangled or in superposition can be reused.
+    pub fn run_mps<R: rand::Rng + ?Sized>(&self, max_bond: usize, rng: &mut R) -> Mps {
+        self.execute_mps(max_bond, &mut Some(rng))
+    }
+
+    fn execute_mps<R: rand::Rng + ?Sized>(&self, max_bond: usize, rng: &mut Option<&mut R>) -> Mps {
+        let mut mps = Mps::new(self.num_qubits, max_bond);
+        for instruction in &self.decompose_multi_controlled().instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => mps.apply(gate, qubits),
+                Instruction::Reset { qubit } => {
+                    let one = match rng.as_deref_mut() {
+                        Some(rng) => mps.measure(*qubit, rng),
+                        None => definite(mps.probability_of_one(*qubit)).unwrap_or_else(|| {
+                            panic!("simulate_mps() resets only qubits in a definite state, use run_mps()")
+                        }),
+                    };
+                    if one {
+                        mps.apply(&Gate::X, &[*qubit]);
+                    }
+                }
+                Instruction::Barrier { .. } => {}
+                Instruction::Snapshot { .. } => {
+                    panic!("simulate_mps() records no snapshots, use run()")
+                }
+                _ => panic!("simulate_mps() runs circuits of gates only"),
+            }
+        }
+        mps.apply_global_phase(self.global_phase);
+        mps
+    }
+}
+
+/// The rows × inner matrix `a` times the inner × cols matrix `b`, row-major.
+fn matmul(a: &[C], b: &[C], rows: usize, inner: usize, cols: usize) -> Vec<C> {
+    let mut out = vec![C::zero(); rows * cols];
+    for i in 0..rows {
+        for k in 0..inner {
+            let x = a[i * inner + k];
+            if x.is_zero() {
+                continue;
+            }
+            for j in 0..cols {
+                out[i * cols + j] += x * b[k * cols + j];
+            }
+        }
+    }
+    out
+}
+
+/// The thin singular value decomposition M = U Σ V† of a rows × cols matrix,
+/// with k = min(rows, cols) singular values in decreasing order, U rows × k
+/// and V† k × cols, by one-sided Jacobi rotations of the columns of M.
+fn svd(m: &[C], rows: usize, cols: usize) -> (Vec<C>, Vec<f64>, Vec<C>) {
+    // work on the transpose when it is wide, so there are at most `rows`
+    // columns to orthogonalise
+    if cols > rows {
+        let transposed: Vec<C> = (0..cols * rows)
+            .map(|i| m[(i % rows) * cols + i / rows].conjugate())
+            .collect();
+        let (u, sigma, vh) = svd(&transposed, cols, rows);
+        let k = sigma.len();
+        // M† = U Σ V†, so M = V Σ U†
+        let new_u = (0..rows * k)
+            .map(|i| vh[(i % k) * rows + i / k].conjugate())
+            .collect();
+        let new_vh = (0..k * cols)
+            .map(|i| u[(i % cols) * k + i / cols].conjugate())
+            .collect();
+        return (new_u, sigma, new_vh);
+    }
+
+    let mut a = m.to_vec();
+    let mut v = vec![C::zero(); cols * cols];
+    for i in 0..cols {
+        v[i * cols + i] = C::new(1.0, 0.0);
+    }
+
+    for _ in 0..60 {
+        let mut rotated = false;
+        for i in 0..cols {
+            for j in i + 1..cols {
+                let (mut alpha, mut beta, mut gamma) = (0.0, 0.0, C::zero());
+                for r in 0..rows {
+                    let (x, y) = (a[r * cols + i], a[r * cols + j]);
+                    alpha += x.mod_squared();
+                    beta += y.mod_squared();
+                    gamma += x.conjugate() * y;
+                }
+                if gamma.modulus() <= 1e-15 * (alpha * beta).sqrt() || gamma.modulus() < 1e-300 {
+                    continue;
+                }
+                rotated = true;
+
+                let g = gamma.modulus();
+                let phase = C::new(gamma.re / g, gamma.im / g);
+                let zeta = (beta - alpha) / (2.0 * g);
+                let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = c * t;
+                let (c, s) = (C::new(c, 0.0), C::new(s, 0.0));
+
+                for matrix in [&mut a, &mut v] {
+                    let n = matrix.len() / cols;
+                    for r in 0..n {
+                        let (x, y) = (matrix[r * cols + i], matrix[r * cols + j]);
+                        matrix[r * cols + i] = c * x - s * phase.conjugate() * y;
+                        matrix[r * cols + j] = s * phase * x + c * y;
+                    }
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+
+    let mut order: Vec<(f64, usize)> = (0..cols)
+        .map(|j| {
+            let norm: f64 = (0..rows).map(|r| a[r * cols + j].mod_squared()).sum();
+            (norm.sqrt(), j)
+        })
+        .collect();
+    order.sort_by(|x, y| y.0.total_cmp(&x.0));
+
+    let k = cols;
+    let mut u = vec![C::zero(); rows * k];
+    let mut vh = vec![C::zero(); k * cols];
+    for (new, &(sigma, j)) in order.iter().enumerate() {
+        for r in 0..rows {
+            u[r * k + new] = if sigma > 0.0 {
+                a[r * cols + j] * C::new(1.0 / sigma, 0.0)
+            } else {
+                C::zero()
+            };
+        }
+        for c in 0..cols {
+            vh[new * cols + c] = v[c * cols + j].conjugate();
+        }
+    }
+    (u, order.into_iter().map(|(sigma, _)| sigma).collect(), vh)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    use super::{svd, Mps, C};
+    use crate::circuit::{Circuit, Gate};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn assert_matches_statevector(c: &Circuit, max_bond: usize) {
+        let mps = c.simulate_mps(max_bond);
+        let expected = c.simulate().amplitudes(c.num_qubits());
+        for (x, a) in expected.iter().enumerate() {
+            assert!(
+                (mps.amplitude(x as u64) - *a).modulus() < EPSILON,
+                "|{x:b}⟩"
+            );
+        }
+    }
+
+    #[test]
+    fn svd_reconstructs() {
+        let m: Vec<C> = (0..12)
+            .map(|i| C::new((i as f64 * 0.7).sin(), (i as f64 * 1.3).cos()))
+            .collect();
+
+        for (rows, cols) in [(3, 4), (4, 3), (2, 6)] {
+            let (u, sigma, vh) = svd(&m, rows, cols);
+            let k = sigma.len();
+            assert!(sigma.windows(2).all(|w| w[0] >= w[1]));
+
+            for r in 0..rows {
+                for c in 0..cols {
+                    let x = (0..k)
+                        .map(|j| u[r * k + j] * C::new(sigma[j], 0.0) * vh[j * cols + c])
+                        .fold(C::new(0.0, 0.0), |sum, x| sum + x);
+                    assert!((x - m[r * cols + c]).modulus() < EPSILON);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn product_states_keep_bond_one() {
+        let mut c = Circuit::new(6);
+        for q in 0..6 {
+            c.ry(q, 0.3 * q as f64).h(q);
+        }
+        let mps = c.simulate_mps(4);
+        assert_eq!(mps.bond_dimensions(), [1; 5]);
+        assert_matches_statevector(&c, 4);
+    }
+
+    #[test]
+    fn exact_for_entangling_circuits() {
+        let mut c = Circuit::new(5);
+        c.h(0)
+            .cnot(0, 3)
+            .ry(2, 0.4)
+            .cz(4, 1)
+            .h(4)
+            .swap(1, 3)
+            .cnot(4, 0)
+            .t(3)
+            .u3(1, 0.5, -0.2, 1.1)
+            .cnot(2, 1)
+            .ccx(0, 4, 2);
+
+        assert_matches_statevector(&c, 16);
+        assert!(c.simulate_mps(16).truncation_error() < EPSILON);
+    }
+
+    #[test]
+    fn ghz_has_bond_two() {
+        let n = 40;
+        let mut c = Circuit::new(n);
+        c.h(0);
+        for q in 1..n as usize {
+            c.cnot(q - 1, q);
+        }
+
+        let mps = c.simulate_mps(2);
+        assert!(mps.bond_dimensions().iter().all(|&d| d == 2));
+        let all_ones = (1u64 << n) - 1;
+        assert!((mps.amplitude(0).modulus() - 0.5_f64.sqrt()).abs() < EPSILON);
+        assert!((mps.amplitude(all_ones).modulus() - 0.5_f64.sqrt()).abs() < EPSILON);
+        assert!(mps.amplitude(1).modulus() < EPSILON);
+    }
+
+    #[test]
+    fn truncation_caps_bonds() {
+        let mut c = Circuit::new(6);
+        for layer in 0..3 {
+            for q in 0..6 {
+                c.ry(q, 0.3 + 0.2 * (q + layer) as f64);
+            }
+            for q in 0..5 {
+                c.cnot(q, q + 1);
+            }
+        }
+
+        let mps = c.simulate_mps(2);
+        assert!(mps.bond_dimensions().iter().all(|&d| d <= 2));
+        assert!(mps.truncation_error() > 0.0);
+        let norm: f64 = (0..64).map(|x| mps.amplitude(x).mod_squared()).sum();
+        assert!((norm - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn reset_of_a_definite_qubit() {
+        let mut c = Circuit::new(3);
+        c.x(0).cnot(0, 2).reset(0).h(1);
+        let mps = c.simulate_mps(4);
+        assert!((mps.amplitude(0b100).re - FRAC_1_SQRT_2).abs() < EPSILON);
+        assert!((mps.amplitude(0b110).re - FRAC_1_SQRT_2).abs() < EPSILON);
+    }
+
+    #[test]
+    fn measurement_collapses() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..10 {
+            let mut mps = Mps::new(3, 4);
+            mps.apply(&Gate::H, &[0]);
+            mps.apply(&Gate::Cx, &[0, 2]);
+            assert!((mps.probability_of_one(2) - 0.5).abs() < EPSILON);
+
+            let first = mps.measure(2, &mut rng);
+            assert!((mps.probability_of_one(0) - if first { 1.0 } else { 0.0 }).abs() < EPSILON);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Peephole simplification of circuits: removing identities, cancelling
+//! adjacent inverse pairs and merging adjacent rotations about the same axis.
+//! Two gates count as adjacent when nothing between them touches any of
+//! their qubits.
+
+use std::f64::consts::PI;
+
+use super::{Circuit, Gate, Instruction, Param};
+
+const EPSILON: f64 = 1e-12;
+
+impl Circuit {
+    /// An equivalent circuit with the peephole rewrites applied until none
+    /// is left. Symbolic rotations are left alone, and rotations summing to
+    /// a multiple of 4π (2π for the phase gate) are dropped.
+    pub fn optimized(&self) -> Circuit {
+        let mut slots: Vec<Option<Instruction>> =
+            self.instructions.iter().cloned().map(Some).collect();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for i in 0..slots.len() {
+                let Some(Instruction::Gate { gate, qubits }) = &slots[i] else {
+                    continue;
+                };
+                if is_identity(gate) {
+                    slots[i] = None;
+                    changed = true;
+                    continue;
+                }
+
+                let Some(j) = previous_on(&slots, i, qubits) else {
+                    continue;
+                };
+                let Some(Instruction::Gate {
+                    gate: before,
+                    qubits: before_qubits,
+                }) = &slots[j]
+                else {
+                    continue;
+                };
+                if !same_qubits(gate, qubits, before_qubits) {
+                    continue;
+                }
+
+                if let Some(merged) = combine(before, gate) {
+                    slots[j] = merged
+                        .filter(|g| !is_identity(g))
+                        .map(|gate| Instruction::Gate {
+                            gate,
+                            qubits: before_qubits.clone(),
+                        });
+                    slots[i] = None;
+                    changed = true;
+                }
+            }
+        }
+
+        Circuit {
+            instructions: slots.into_iter().flatten().collect(),
+            ..self.clone()
+        }
+    }
+}
+
+/// The last live instruction before `i` touching any of `qubits`.
+fn previous_on(slots: &[Option<Instruction>], i: usize, qubits: &[usize]) -> Option<usize> {
+    (0..i).rev().find(|&j| {
+        slots[j]
+            .as_ref()
+            .is_some_and(|instruction| touches(instruction, qubits))
+    })
+}
+
+/// Whether `instruction` touches any of `qubits`. Snapshots touch every
+/// qubit, so that nothing moves past them.
+fn touches(instruction: &Instruction, qubits: &[usize]) -> bool {
+    match instruction {
+        Instruction::Gate { qubits: qs, .. }
+        | Instruction::Conditional { qubits: qs, .. }
+        | Instruction::Barrier { qubits: qs }
+        | Instruction::Channel { qubits: qs, .. } => qs.iter().any(|q| qubits.contains(q)),
+        Instruction::Measure { qubit, .. } | Instruction::Reset { qubit } => qubits.contains(qubit),
+        Instruction::While { body, .. } => body
+            .instructions
+            .iter()
+            .any(|instruction| touches(instruction, qubits)),
+        Instruction::Snapshot { .. } => true,
+    }
+}
+
+/// Whether two applications of `gate` line up qubit for qubit, in any order
+/// for gates symmetric in their qubits.
+fn same_qubits(gate: &Gate, a: &[usize], b: &[usize]) -> bool {
+    match gate {
+        Gate::Cz | Gate::Swap => {
+            let (mut a, mut b) = (a.to_vec(), b.to_vec());
+            a.sort_unstable();
+            b.sort_unstable();
+            a == b
+        }
+        _ => a == b,
+    }
+}
+
+/// `second · first` as a single gate, `Some(None)` if they cancel, or `None`
+/// if they do not combine.
+fn combine(first: &Gate, second: &Gate) -> Option<Option<Gate>> {
+    use Gate::*;
+
+    match (first, second) {
+        (H, H) | (X, X) | (Y, Y) | (Z, Z) | (Cx, Cx) | (Cz, Cz) | (Swap, Swap) | (Ccx, Ccx) => {
+            Some(None)
+        }
+        (Mcx(a), Mcx(b)) if a == b => Some(None),
+        (S, Sdg) | (Sdg, S) | (Sx, Sxdg) | (Sxdg, Sx) | (T, Tdg) | (Tdg, T) => Some(None),
+        (Sx, Sx) | (Sxdg, Sxdg) => Some(Some(X)),
+        (S, S) | (Sdg, Sdg) => Some(Some(Z)),
+        (T, T) => Some(Some(S)),
+        (Tdg, Tdg) => Some(Some(Sdg)),
+        (Rx(Param::Value(a)), Rx(Param::Value(b))) => Some(Some(Rx((a + b).into()))),
+        (Ry(Param::Value(a)), Ry(Param::Value(b))) => Some(Some(Ry((a + b).into()))),
+        (Rz(Param::Value(a)), Rz(Param::Value(b))) => Some(Some(Rz((a + b).into()))),
+        (Phase(Param::Value(a)), Phase(Param::Value(b))) => Some(Some(Phase((a + b).into()))),
+        _ => None,
+    }
+}
+
+fn is_identity(gate: &Gate) -> bool {
+    let multiple_of = |angle: f64, period: f64| {
+        let r = angle.rem_euclid(period);
+        r < EPSILON || period - r < EPSILON
+    };
+
+    match gate {
+        Gate::I => true,
+        Gate::Rx(Param::Value(a)) | Gate::Ry(Param::Value(a)) | Gate::Rz(Param::Value(a)) => {
+            multiple_of(*a, 4.0 * PI)
+        }
+        Gate::Phase(Param::Value(a)) => multiple_of(*a, 2.0 * PI),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{Circuit, Gate, Instruction};
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn assert_equivalent(a: &Circuit, b: &Circuit) {
+        let (a, b) = (
+            a.simulate().amplitudes(a.num_qubits()),
+            b.simulate().amplitudes(b.num_qubits()),
+        );
+        for (x, y) in a.iter().zip(&b) {
+            assert!((*x - *y).modulus() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn cancels_inverse_pairs() {
+        let mut c = Circuit::new(2);
+        c.h(0)
+            .h(0)
+            .cnot(0, 1)
+            .cnot(0, 1)
+            .s(1)
+            .sdg(1)
+            .t(0)
+            .x(1)
+            .tdg(0);
+
+        let mut expected = Circuit::new(2);
+        expected.x(1);
+        assert_eq!(c.optimized(), expected);
+    }
+
+    #[test]
+    fn cancellation_cascades() {
+        // removing the middle pair makes the outer H gates adjacent
+        let mut c = Circuit::new(2);
+        c.h(0).cz(0, 1).cz(1, 0).h(0).id(1);
+
+        assert!(c.optimized().is_empty());
+    }
+
+    #[test]
+    fn gates_in_between_block_cancellation() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).h(0).cnot(1, 0).cnot(0, 1);
+        assert_eq!(c.optimized(), c);
+
+        let mut c = Circuit::new(1);
+        c.h(0).measure(0, 0).h(0);
+        assert_eq!(c.optimized(), c);
+
+        // snapshots block every qubit
+        let mut c = Circuit::new(2);
+        c.x(0).snapshot("flipped").x(0);
+        assert_eq!(c.optimized(), c);
+    }
+
+    #[test]
+    fn merges_rotations() {
+        let mut c = Circuit::new(2);
+        c.rz(0, 0.25)
+            .x(1)
+            .rz(0, 0.5)
+            .rx(1, 1.0)
+            .rx(1, -1.0)
+            .t(0)
+            .t(0);
+
+        let optimized = c.optimized();
+        assert_eq!(
+            optimized.instructions(),
+            [
+                Instruction::Gate {
+                    gate: Gate::Rz(0.75.into()),
+                    qubits: vec![0]
+                },
+                Instruction::Gate {
+                    gate: Gate::X,
+                    qubits: vec![1]
+                },
+                Instruction::Gate {
+                    gate: Gate::S,
+                    qubits: vec![0]
+                },
+            ]
+        );
+        assert_equivalent(&c, &optimized);
+    }
+
+    #[test]
+    fn symbolic_rotations_are_kept() {
+        let mut c = Circuit::new(1);
+        c.rz(0, "a").rz(0, "a");
+        assert_eq!(c.optimized(), c);
+    }
+
+    #[test]
+    fn full_turns_are_dropped() {
+        let mut c = Circuit::new(1);
+        c.p(0, std::f64::consts::PI)
+            .p(0, std::f64::consts::PI)
+            .ry(0, 4.0 * std::f64::consts::PI);
+        assert!(c.optimized().is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+//! Oracles for a classical function f on n-bit inputs, built from its truth
+//! table with one multi-controlled gate per input it maps to 1. Building one
+//! calls f on all 2^n inputs, so they suit the small instances of textbook
+//! algorithms rather than functions with structure worth exploiting.
+
+use super::Circuit;
+
+impl Circuit {
+    /// The phase oracle Σ(−1)^f(x)|x⟩⟨x| on n qubits, as a Z controlled on
+    /// each marked input, with X on the qubits that input has 0.
+    pub fn phase_oracle(n: u32, f: impl Fn(u32) -> bool) -> Circuit {
+        let qubits: Vec<usize> = (0..n as usize).collect();
+        let mut c = Circuit::new(n);
+        for x in (0..1u32 << n).filter(|&x| f(x)) {
+            c.on_input(x, &qubits, |c| c.mcz(&qubits));
+        }
+        c
+    }
+
+    /// The bit oracle |x, y⟩ ↦ |x, y ⊕ f(x)⟩ on n + 1 qubits, with x on
+    /// qubits 0 to n − 1 and the output bit y on qubit n, as an X on the
+    /// output controlled on each marked input.
+    pub fn bit_oracle(n: u32, f: impl Fn(u32) -> bool) -> Circuit {
+        let inputs: Vec<usize> = (0..n as usize).collect();
+        let mut c = Circuit::new(n + 1);
+        for x in (0..1u32 << n).filter(|&x| f(x)) {
+            c.on_input(x, &inputs, |c| c.mcx(&inputs, n as usize));
+        }
+        c
+    }
+
+    /// `gate` conditioned on `qubits` holding x rather than all ones, by X
+    /// on the qubits whose bit of x is 0 before and after.
+    fn on_input(&mut self, x: u32, qubits: &[usize], gate: impl FnOnce(&mut Self) -> &mut Self) {
+        let zeros: Vec<usize> = qubits
+            .iter()
+            .copied()
+            .filter(|&q| x >> q & 1 == 0)
+            .collect();
+        for &q in &zeros {
+            self.x(q);
+        }
+        gate(self);
+        for &q in &zeros {
+            self.x(q);
+        }
+    }
+
+    /// Z on the last of `qubits` controlled by the others.
+    pub(super) fn mcz(&mut self, qubits: &[usize]) -> &mut Self {
+        let (&target, controls) = qubits.split_last().unwrap();
+        if controls.is_empty() {
+            return self.z(target);
+        }
+        self.h(target).mcx(controls, target).h(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+    use crate::ket::{Operator, State};
+    use crate::Complex;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn assert_implements(c: &Circuit, u: &Operator<Complex<f64>>) {
+        let n = c.num_qubits();
+        for x in 0..1 << n {
+            let actual = (c.unitary() * State::basis(x, n)).amplitudes(n);
+            let expected = (u.clone() * State::basis(x, n)).amplitudes(n);
+            for (a, b) in actual.iter().zip(&expected) {
+                assert!((*a - *b).modulus() < EPSILON, "|{x}⟩: {a} != {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn phase_oracle_flips_the_marked_signs() {
+        let f = |x: u32| x.is_multiple_of(3);
+        let u = Operator::phase_oracle(3, |x| f(x as u32));
+        assert_implements(&Circuit::phase_oracle(3, f), &u);
+        assert_implements(
+            &Circuit::phase_oracle(1, |x| x == 1),
+            &Operator::phase(std::f64::consts::PI),
+        );
+    }
+
+    #[test]
+    fn bit_oracle_writes_f_into_the_output() {
+        let f = |x: u32| x == 2 || x == 5 || x == 6;
+        let u = Operator::from_permutation(4, |x| x ^ u64::from(f(x as u32 & 7)) << 3);
+        assert_implements(&Circuit::bit_oracle(3, f), &u);
+        assert!(Circuit::bit_oracle(3, |_| false).is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+//! Single amplitudes ⟨x|C|0…0⟩ as Feynman path sums. Going backwards
+//! through the gates from |x⟩, each gate maps a basis state to the basis
+//! states it can have come from, and the amplitude is the sum over every
+//! path back to |0…0⟩ of the product of the matrix entries along it.
+//!
+//! Besides the gates themselves, memory grows only with the number of gates
+//! that branch, whatever the width of the circuit, while time grows with
+//! the number of paths: permutations and diagonal gates such as CNOT,
+//! Toffoli, T and Rz never branch, and each gate like H doubles the paths
+//! through it.
+
+use num::Zero;
+
+use super::statevector::gate_matrix;
+use super::{Circuit, Gate, Instruction};
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// A gate as seen going backwards through the circuit.
+enum Step {
+    /// Flips `target` when every qubit in `controls` is set.
+    Flip {
+        controls: u64,
+        target: usize,
+    },
+    Swap(usize, usize),
+    Matrix {
+        matrix: Vec<C>,
+        qubits: Vec<usize>,
+    },
+}
+
+impl Circuit {
+    /// The amplitude ⟨x|C|0…0⟩ of the circuit's output on basis state `x`,
+    /// summed over paths. Only gates, barriers and snapshots, which are
+    /// ignored, are supported.
+    pub fn path_sum_amplitude(&self, x: u64) -> C {
+        let steps: Vec<Step> = self
+            .instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Gate { gate, qubits } => Some(step(gate, qubits)),
+                Instruction::Barrier { .. } | Instruction::Snapshot { .. } => None,
+                _ => panic!("path_sum_amplitude() runs circuits of gates only"),
+            })
+            .collect();
+        let phase = C::new(self.global_phase.cos(), self.global_phase.sin());
+        phase * sum_paths(&steps, x)
+    }
+}
+
+fn step(gate: &Gate, qubits: &[usize]) -> Step {
+    match gate {
+        Gate::X | Gate::Cx | Gate::Ccx | Gate::Mcx(_) => {
+            let (&target, controls) = qubits.split_last().unwrap();
+            Step::Flip {
+                controls: controls.iter().fold(0, |m, &q| m | 1 << q),
+                target,
+            }
+        }
+        Gate::Swap => Step::Swap(qubits[0], qubits[1]),
+        _ => Step::Matrix {
+            matrix: gate_matrix(gate),
+            qubits: qubits.to_vec(),
+        },
+    }
+}
+
+/// The amplitude of |x⟩ after `steps`, starting from |0…0⟩. Paths are
+/// followed depth first, with flips and swaps taken in a loop, so only the
+/// branches still to visit at each matrix step are kept on the stack.
+fn sum_paths(steps: &[Step], x: u64) -> C {
+    let mut sum = C::zero();
+    // paths back through the first `len` steps from |x⟩, with their weight
+    let mut stack = vec![(steps.len(), x, C::new(1.0, 0.0))];
+    while let Some((mut len, mut x, weight)) = stack.pop() {
+        let branch = loop {
+            if len == 0 {
+                break None;
+            }
+            len -= 1;
+            match &steps[len] {
+                Step::Flip { controls, target } => {
+                    if x & controls == *controls {
+                        x ^= 1 << target;
+                    }
+                }
+                Step::Swap(a, b) => {
+                    if (x >> a ^ x >> b) & 1 == 1 {
+                        x ^= 1 << a | 1 << b;
+                    }
+                }
+                Step::Matrix { matrix, qubits } => break Some((matrix, qubits)),
+            }
+        };
+
+        let Some((matrix, qubits)) = branch else {
+            if x == 0 {
+                sum += weight;
+            }
+            continue;
+        };
+        let size = 1 << qubits.len();
+        let row: usize = qubits
+            .iter()
+            .enumerate()
+            .map(|(i, &q)| ((x >> q & 1) as usize) << i)
+            .sum();
+        let base = qubits.iter().fold(x, |y, &q| y & !(1 << q));
+        for column in 0..size {
+            let entry = matrix[row * size + column];
+            if entry.is_zero() {
+                continue;
+            }
+            let y = qubits
+                .iter()
+                .enumerate()
+                .fold(base, |y, (i, &q)| y | ((column >> i & 1) as u64) << q);
+            stack.push((len, y, weight * entry));
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{Circuit, Gate};
+    use crate::Complex;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn agrees_with_the_statevector() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..3 {
+            let mut c = Circuit::random(4, 3, &mut rng);
+            c.ccx(0, 1, 3).add_global_phase(0.7);
+            let amplitudes = c.simulate().amplitudes(4);
+            for (x, expected) in amplitudes.iter().enumerate() {
+                assert!((c.path_sum_amplitude(x as u64) - *expected).modulus() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn wide_circuits_with_few_branches() {
+        // a GHZ state on 60 qubits, with a T on each, has one path into
+        // each of its two basis states
+        let n = 60;
+        let mut c = Circuit::new(n);
+        c.h(0);
+        for q in 1..n as usize {
+            c.cnot(0, q);
+        }
+        for q in 0..n as usize {
+            c.t(q);
+        }
+        c.append(Gate::Mcx(2), &[0, 1, 2]).swap(2, 3);
+
+        let ones = (1u64 << n) - 1;
+        let phi = std::f64::consts::FRAC_PI_4 * f64::from(n);
+        let expected = Complex::new(phi.cos(), phi.sin()) * Complex::new(0.5f64.sqrt(), 0.0);
+        let flipped = ones ^ 1 << 3;
+        assert!((c.path_sum_amplitude(flipped) - expected).modulus() < EPSILON);
+        assert!((c.path_sum_amplitude(0).re - 0.5f64.sqrt()).abs() < EPSILON);
+        assert!(c.path_sum_amplitude(ones).modulus() < EPSILON);
+    }
+
+    #[test]
+    fn long_circuits_fit_on_the_stack() {
+        // a million CNOTs, an even number on each pair, cancel out
+        let mut c = Circuit::new(2);
+        c.h(0);
+        for _ in 0..1_000_000 {
+            c.cnot(0, 1);
+        }
+        assert!((c.path_sum_amplitude(0b01).re - 0.5f64.sqrt()).abs() < EPSILON);
+        assert!(c.path_sum_amplitude(0b11).modulus() < EPSILON);
+    }
+
+    #[test]
+    #[should_panic(expected = "path_sum_amplitude() runs circuits of gates only")]
+    fn measurements_are_not_summed() {
+        let mut c = Circuit::new(1);
+        c.h(0).measure(0, 0);
+        c.path_sum_amplitude(0);
+    }
+}
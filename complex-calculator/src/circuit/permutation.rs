@@ -0,0 +1,205 @@
+//! Reversible circuits for classical permutations of the basis, by the
+//! transformation-based synthesis of Miller, Maslov and Dueck: the truth
+//! table is walked in order, and each row is fixed with multi-controlled X
+//! gates that leave the rows before it alone.
+
+use super::{Circuit, Gate, Instruction};
+
+impl Circuit {
+    /// A circuit of X, CNOT, Toffoli and multi-controlled X gates taking |x⟩
+    /// to |f(x)⟩ on n qubits, as [`Operator::from_permutation`] does. Panics
+    /// if `f` is not a bijection on the n-bit inputs. Takes 2^n calls to `f`,
+    /// so is meant for the few qubits of textbook examples.
+    ///
+    /// [`Operator::from_permutation`]: crate::ket::Operator::from_permutation
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// // x ↦ x + 1 mod 8
+    /// let c = Circuit::from_permutation(3, |x| (x + 1) % 8);
+    /// let amplitudes = Circuit::new(3).x(0).x(2).compose(&c, &[0, 1, 2]).simulate().amplitudes(3);
+    /// assert!((amplitudes[0b110].re - 1.0).abs() < 1e-12);
+    /// ```
+    pub fn from_permutation(n: u32, f: impl Fn(u64) -> u64) -> Circuit {
+        let size = 1u64 << n;
+        let mut table: Vec<u64> = (0..size).map(&f).collect();
+        let mut seen = vec![false; size as usize];
+        for (x, &y) in table.iter().enumerate() {
+            assert!(y < size, "f({x}) = {y} is outside the {n}-qubit basis");
+            assert!(
+                !std::mem::replace(&mut seen[y as usize], true),
+                "f is not a bijection: f({x}) = {y} twice"
+            );
+        }
+
+        // gates g applied after the table, g₁…gₖ f = 1, so f = gₖ…g₁ as
+        // each is its own inverse: the circuit is their reverse
+        let mut gates: Vec<(u64, usize)> = vec![];
+        let mut flip = |table: &mut [u64], controls: u64, target: usize| {
+            for y in table.iter_mut() {
+                if *y & controls == controls {
+                    *y ^= 1 << target;
+                }
+            }
+            gates.push((controls, target));
+        };
+        for i in 0..size {
+            // every row before i maps to itself, so table[i] > i: setting its
+            // missing bits controlled on its ones, then clearing its extra
+            // bits controlled on the ones of i, touches no earlier row
+            let y = table[i as usize];
+            for target in (0..n as usize).filter(|&b| (i & !y) >> b & 1 == 1) {
+                let controls = table[i as usize];
+                flip(&mut table, controls, target);
+            }
+            let y = table[i as usize];
+            for target in (0..n as usize).filter(|&b| (y & !i) >> b & 1 == 1) {
+                flip(&mut table, i, target);
+            }
+        }
+
+        let mut c = Circuit::new(n);
+        for &(controls, target) in gates.iter().rev() {
+            let controls: Vec<usize> = (0..n as usize)
+                .filter(|&b| controls >> b & 1 == 1)
+                .collect();
+            c.mcx(&controls, target);
+        }
+        c
+    }
+
+    /// The basis state |y⟩ the circuit takes |x⟩ to, tracked bit by bit, for
+    /// circuits of X, CNOT, Toffoli, multi-controlled X and SWAP gates.
+    /// Barriers and snapshots are skipped; panics on anything else, which could take a
+    /// basis state out of the basis.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// let c = Circuit::from_permutation(3, |x| (x + 3) % 8);
+    /// assert_eq!(c.classical_output(6), 1);
+    /// ```
+    pub fn classical_output(&self, x: u64) -> u64 {
+        let mut y = x;
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate {
+                    gate: Gate::X | Gate::Cx | Gate::Ccx | Gate::Mcx(_),
+                    qubits,
+                } => {
+                    let (&target, controls) = qubits.split_last().unwrap();
+                    if controls.iter().all(|&q| y >> q & 1 == 1) {
+                        y ^= 1 << target;
+                    }
+                }
+                Instruction::Gate {
+                    gate: Gate::Swap,
+                    qubits,
+                } => {
+                    let (a, b) = (qubits[0], qubits[1]);
+                    if (y >> a ^ y >> b) & 1 == 1 {
+                        y ^= 1 << a | 1 << b;
+                    }
+                }
+                Instruction::Gate { gate: Gate::I, .. }
+                | Instruction::Barrier { .. }
+                | Instruction::Snapshot { .. } => {}
+                other => panic!("{other:?} does not permute the basis"),
+            }
+        }
+        y
+    }
+
+    /// The first of `inputs` that [`Circuit::classical_output`] does not take
+    /// to f(x), or `None` if the circuit agrees with `f` on all of them. The
+    /// inputs are usually the basis states with every ancilla at 0, where an
+    /// arithmetic circuit is specified.
+    pub fn permutation_counterexample(
+        &self,
+        inputs: impl IntoIterator<Item = u64>,
+        f: impl Fn(u64) -> u64,
+    ) -> Option<u64> {
+        inputs
+            .into_iter()
+            .find(|&x| self.classical_output(x) != f(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+    use crate::ket::State;
+
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn assert_permutes(c: &Circuit, f: impl Fn(u64) -> u64) {
+        let n = c.num_qubits();
+        let u = c.unitary();
+        for x in 0..1 << n {
+            let amplitudes = (u.clone() * State::basis(x, n)).amplitudes(n);
+            assert!(
+                (amplitudes[f(x) as usize].re - 1.0).abs() < EPSILON,
+                "|{x}⟩"
+            );
+        }
+    }
+
+    #[test]
+    fn random_permutations() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for n in 1..=4 {
+            let mut table: Vec<u64> = (0..1 << n).collect();
+            table.shuffle(&mut rng);
+            let c = Circuit::from_permutation(n, |x| table[x as usize]);
+            assert_permutes(&c, |x| table[x as usize]);
+        }
+    }
+
+    #[test]
+    fn simple_permutations_are_single_gates() {
+        assert!(Circuit::from_permutation(3, |x| x).is_empty());
+        let cnot = Circuit::from_permutation(2, |x| x ^ (x & 1) << 1);
+        assert_eq!(cnot.gate_counts()["cx"], 1);
+        assert_eq!(cnot.len(), 1);
+        let not = Circuit::from_permutation(2, |x| x ^ 0b11);
+        assert_eq!(not.gate_counts()["x"], 2);
+        assert_eq!(not.len(), 2);
+    }
+
+    #[test]
+    fn classical_output_follows_the_permutation() {
+        let c = Circuit::from_permutation(4, |x| (5 * x + 3) % 16);
+        for x in 0..16 {
+            assert_eq!(c.classical_output(x), (5 * x + 3) % 16);
+        }
+        assert_eq!(
+            c.permutation_counterexample(0..16, |x| (5 * x + 3) % 16),
+            None
+        );
+        assert_eq!(
+            c.permutation_counterexample(0..16, |x| (5 * x + 3) % 8),
+            Some(1)
+        );
+
+        let mut swap = Circuit::new(3);
+        swap.swap(0, 2).barrier(&[0, 1, 2]);
+        assert_eq!(swap.classical_output(0b001), 0b100);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not permute the basis")]
+    fn classical_output_needs_classical_gates() {
+        Circuit::new(1).h(0).classical_output(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "f is not a bijection: f(2) = 0 twice")]
+    fn rejects_non_bijections() {
+        Circuit::from_permutation(2, |x| x & 1);
+    }
+}
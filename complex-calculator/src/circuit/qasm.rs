@@ -0,0 +1,705 @@
+//! Reader for OpenQASM 2.0 programs over the `qelib1.inc` gate set.
+//!
+//! Registers are laid out one after another in declaration order, so with
+//! `qreg a[2]; qreg b[1];` the qubit `b[0]` is qubit 2 of the circuit, and
+//! all of them together may hold at most [`MAX_QUBITS`] qubits. The cregs
+//! share one 64-bit classical register in the same way. Controlled
+//! gates the crate has no variant for, such as `ch` and `cswap`, are built
+//! from the gates it has. Gate definitions and `opaque` are not supported.
+
+use std::collections::HashMap;
+
+use super::compose::controlled;
+use super::{Circuit, Gate};
+use crate::ket::MAX_QUBITS;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QasmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for QasmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} on line {}", self.message, self.line)
+    }
+}
+
+impl std::error::Error for QasmError {}
+
+impl Circuit {
+    pub fn from_qasm(source: &str) -> Result<Circuit, QasmError> {
+        Parser::new(source).program()
+    }
+}
+
+/// A declared register: its offset into the flat qubit or clbit list and size.
+#[derive(Clone, Copy)]
+struct Register {
+    offset: usize,
+    size: usize,
+}
+
+enum Op {
+    Gate(Gate, Vec<usize>),
+    Measure(usize, usize),
+    Reset(usize),
+    Barrier(Vec<usize>),
+    Conditional(usize, Gate, Vec<usize>),
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    qregs: HashMap<String, Register>,
+    cregs: HashMap<String, Register>,
+    num_qubits: usize,
+    num_clbits: usize,
+    ops: Vec<Op>,
+}
+
+impl Parser {
+    fn new(source: &str) -> Self {
+        Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+            line: 1,
+            qregs: HashMap::new(),
+            cregs: HashMap::new(),
+            num_qubits: 0,
+            num_clbits: 0,
+            ops: vec![],
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, QasmError> {
+        Err(QasmError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    /// Skips whitespace and `//` comments, then peeks at the next character.
+    fn peek(&mut self) -> Option<char> {
+        loop {
+            match self.chars.get(self.pos) {
+                Some('\n') => {
+                    self.line += 1;
+                    self.pos += 1;
+                }
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some('/') if self.chars.get(self.pos + 1) == Some(&'/') => {
+                    while self.chars.get(self.pos).is_some_and(|&c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                c => return c.copied(),
+            }
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), QasmError> {
+        if self.eat(c) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(found) => self.error(format!("expected '{c}', found '{found}'")),
+            None => self.error(format!("expected '{c}', found end of input")),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, QasmError> {
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let start = self.pos;
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    self.pos += 1;
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+            Some(c) => self.error(format!("expected an identifier, found '{c}'")),
+            None => self.error("expected an identifier, found end of input"),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, QasmError> {
+        self.peek();
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+        {
+            self.pos += 1;
+        }
+        // exponent, as in 1.5e-3
+        if self
+            .chars
+            .get(self.pos)
+            .is_some_and(|&c| c == 'e' || c == 'E')
+        {
+            self.pos += 1;
+            if self
+                .chars
+                .get(self.pos)
+                .is_some_and(|&c| c == '+' || c == '-')
+            {
+                self.pos += 1;
+            }
+            while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+                self.pos += 1;
+            }
+        }
+
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match text.parse() {
+            Ok(v) => Ok(v),
+            Err(_) => self.error(format!("invalid number '{text}'")),
+        }
+    }
+
+    fn integer(&mut self) -> Result<usize, QasmError> {
+        let v = self.number()?;
+        if v.fract() != 0.0 || v < 0.0 {
+            return self.error(format!("expected a non-negative integer, found {v}"));
+        }
+        Ok(v as usize)
+    }
+
+    fn program(mut self) -> Result<Circuit, QasmError> {
+        if self.identifier().ok().as_deref() != Some("OPENQASM") {
+            return self.error("expected an OPENQASM 2.0 header");
+        }
+        let version = self.number()?;
+        if version != 2.0 {
+            return self.error(format!("unsupported OpenQASM version {version}"));
+        }
+        self.expect(';')?;
+
+        while self.peek().is_some() {
+            self.statement()?;
+        }
+
+        let num_qubits =
+            u32::try_from(self.num_qubits).expect("qregs are checked against MAX_QUBITS");
+        let mut circuit = Circuit::with_clbits(num_qubits, self.num_clbits);
+        for op in self.ops {
+            match op {
+                Op::Gate(gate, qubits) => circuit.append(gate, &qubits),
+                Op::Measure(qubit, clbit) => circuit.measure(qubit, clbit),
+                Op::Reset(qubit) => circuit.reset(qubit),
+                Op::Barrier(qubits) => circuit.barrier(&qubits),
+                Op::Conditional(clbit, gate, qubits) => circuit.c_if(clbit, gate, &qubits),
+            };
+        }
+        Ok(circuit)
+    }
+
+    fn statement(&mut self) -> Result<(), QasmError> {
+        let keyword = self.identifier()?;
+        match keyword.as_str() {
+            "include" => {
+                self.expect('"')?;
+                while self.chars.get(self.pos).is_some_and(|&c| c != '"') {
+                    self.pos += 1;
+                }
+                self.expect('"')?;
+            }
+            "qreg" | "creg" => {
+                let name = self.identifier()?;
+                self.expect('[')?;
+                let size = self.integer()?;
+                self.expect(']')?;
+
+                let (registers, total) = if keyword == "qreg" {
+                    (&mut self.qregs, &mut self.num_qubits)
+                } else {
+                    (&mut self.cregs, &mut self.num_clbits)
+                };
+                let register = Register {
+                    offset: *total,
+                    size,
+                };
+                // classical bits are read back as one u64
+                let (limit, unit) = if keyword == "qreg" {
+                    (MAX_QUBITS, "qubit limit")
+                } else {
+                    (u64::BITS, "bit classical register")
+                };
+                match total.checked_add(size) {
+                    Some(sum) if sum <= limit as usize => *total = sum,
+                    _ => {
+                        return self.error(format!(
+                            "{keyword} {name}[{size}] exceeds the {limit}-{unit}"
+                        ))
+                    }
+                }
+                if registers.insert(name.clone(), register).is_some() {
+                    return self.error(format!("register {name} declared twice"));
+                }
+            }
+            "measure" => {
+                let qubits = self.argument(true)?;
+                self.expect('-')?;
+                self.expect('>')?;
+                let clbits = self.argument(false)?;
+                if qubits.len() != clbits.len() {
+                    return self.error("measure between registers of different sizes");
+                }
+                for (&q, &c) in qubits.iter().zip(&clbits) {
+                    self.ops.push(Op::Measure(q, c));
+                }
+            }
+            "reset" => {
+                for q in self.argument(true)? {
+                    self.ops.push(Op::Reset(q));
+                }
+            }
+            "barrier" => {
+                let mut qubits = vec![];
+                loop {
+                    qubits.extend(self.argument(true)?);
+                    if !self.eat(',') {
+                        break;
+                    }
+                }
+                self.ops.push(Op::Barrier(qubits));
+            }
+            "if" => {
+                self.expect('(')?;
+                let name = self.identifier()?;
+                self.expect('=')?;
+                self.expect('=')?;
+                let value = self.integer()?;
+                self.expect(')')?;
+
+                let Some(&register) = self.cregs.get(&name) else {
+                    return self.error(format!("undeclared classical register {name}"));
+                };
+                if register.size != 1 || value != 1 {
+                    return self.error(
+                        "only conditions of the form if(c==1) on a one-bit register are supported",
+                    );
+                }
+
+                let gate_name = self.identifier()?;
+                for (gate, qubits) in self.gate_call(&gate_name)? {
+                    self.ops
+                        .push(Op::Conditional(register.offset, gate, qubits));
+                }
+                return Ok(());
+            }
+            "gate" | "opaque" => {
+                return self.error(format!("{keyword} is not supported"));
+            }
+            name => {
+                for (gate, qubits) in self.gate_call(name)? {
+                    self.ops.push(Op::Gate(gate, qubits));
+                }
+                return Ok(());
+            }
+        }
+
+        self.expect(';')
+    }
+
+    /// The parameters and qubit arguments of a gate application, up to and
+    /// including the `;`, broadcast over whole registers.
+    fn gate_call(&mut self, name: &str) -> Result<Vec<(Gate, Vec<usize>)>, QasmError> {
+        let mut params = vec![];
+        if self.eat('(') {
+            loop {
+                params.push(self.expression()?);
+                if !self.eat(',') {
+                    break;
+                }
+            }
+            self.expect(')')?;
+        }
+
+        let mut args = vec![];
+        loop {
+            args.push(self.argument(true)?);
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.expect(';')?;
+
+        let (controls, gate) = match gate(name, &params) {
+            Ok(gate) => gate,
+            Err(message) => return self.error(message),
+        };
+        if args.len() != controls + gate.num_qubits() {
+            return self.error(format!(
+                "{name} takes {} qubit arguments, found {}",
+                controls + gate.num_qubits(),
+                args.len()
+            ));
+        }
+
+        // registers are zipped elementwise, with single qubits repeated, as in
+        // `cx q[0], r;`
+        let len = args.iter().map(Vec::len).max().unwrap_or(0);
+        if args.iter().any(|a| a.len() != 1 && a.len() != len) {
+            return self.error("register arguments of different sizes");
+        }
+
+        let mut calls = vec![];
+        for i in 0..len {
+            let qubits: Vec<usize> = args
+                .iter()
+                .map(|a| if a.len() == 1 { a[0] } else { a[i] })
+                .collect();
+            if (1..qubits.len()).any(|j| qubits[..j].contains(&qubits[j])) {
+                return self.error(format!("{name} applied to the same qubit twice"));
+            }
+            match controls {
+                0 => calls.push((gate.clone(), qubits)),
+                _ => calls.extend(controlled(&gate, qubits[0], &qubits[1..])),
+            }
+        }
+        Ok(calls)
+    }
+
+    /// `name` or `name[i]`, resolved to flat qubit (or clbit) indices.
+    fn argument(&mut self, quantum: bool) -> Result<Vec<usize>, QasmError> {
+        let name = self.identifier()?;
+        let registers = if quantum { &self.qregs } else { &self.cregs };
+        let Some(&register) = registers.get(&name) else {
+            let kind = if quantum { "quantum" } else { "classical" };
+            return self.error(format!("undeclared {kind} register {name}"));
+        };
+
+        if self.eat('[') {
+            let i = self.integer()?;
+            self.expect(']')?;
+            if i >= register.size {
+                return self.error(format!(
+                    "index {i} out of range for {name}[{}]",
+                    register.size
+                ));
+            }
+            Ok(vec![register.offset + i])
+        } else {
+            Ok((register.offset..register.offset + register.size).collect())
+        }
+    }
+
+    fn expression(&mut self) -> Result<f64, QasmError> {
+        let mut v = self.term()?;
+        loop {
+            if self.eat('+') {
+                v += self.term()?;
+            } else if self.eat('-') {
+                v -= self.term()?;
+            } else {
+                return Ok(v);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<f64, QasmError> {
+        let mut v = self.power()?;
+        loop {
+            if self.eat('*') {
+                v *= self.power()?;
+            } else if self.eat('/') {
+                v /= self.power()?;
+            } else {
+                return Ok(v);
+            }
+        }
+    }
+
+    fn power(&mut self) -> Result<f64, QasmError> {
+        let base = self.unary()?;
+        if self.eat('^') {
+            Ok(base.powf(self.power()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, QasmError> {
+        if self.eat('-') {
+            return Ok(-self.unary()?);
+        }
+        if self.eat('(') {
+            let v = self.expression()?;
+            self.expect(')')?;
+            return Ok(v);
+        }
+
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(_) => {
+                let name = self.identifier()?;
+                if name == "pi" {
+                    return Ok(std::f64::consts::PI);
+                }
+
+                let f: fn(f64) -> f64 = match name.as_str() {
+                    "sin" => f64::sin,
+                    "cos" => f64::cos,
+                    "tan" => f64::tan,
+                    "exp" => f64::exp,
+                    "ln" => f64::ln,
+                    "sqrt" => f64::sqrt,
+                    _ => return self.error(format!("unknown identifier {name} in expression")),
+                };
+                self.expect('(')?;
+                let v = self.expression()?;
+                self.expect(')')?;
+                Ok(f(v))
+            }
+            None => self.error("expected an expression, found end of input"),
+        }
+    }
+}
+
+/// The gate `name` of `qelib1.inc` with the given parameters, as a gate of
+/// the crate and the number of controls to put in front of it.
+fn gate(name: &str, params: &[f64]) -> Result<(usize, Gate), String> {
+    use std::f64::consts::FRAC_PI_2;
+
+    let (controls, base) = match name {
+        "cy" => (1, "y"),
+        "ch" => (1, "h"),
+        "crz" => (1, "rz"),
+        "cu1" => (1, "u1"),
+        "cu3" => (1, "u3"),
+        "cswap" => (1, "swap"),
+        _ => (0, name),
+    };
+    let expected = match base {
+        "id" | "h" | "x" | "y" | "z" | "s" | "sdg" | "sx" | "sxdg" | "t" | "tdg" => 0,
+        "cx" | "CX" | "cz" | "swap" | "ccx" => 0,
+        "rx" | "ry" | "rz" | "p" | "u1" => 1,
+        "u2" => 2,
+        "u3" | "u" | "U" => 3,
+        _ => return Err(format!("unknown gate {name}")),
+    };
+    if params.len() != expected {
+        return Err(format!(
+            "{name} takes {expected} parameters, found {}",
+            params.len()
+        ));
+    }
+
+    let gate = match base {
+        "id" => Gate::I,
+        "h" => Gate::H,
+        "x" => Gate::X,
+        "y" => Gate::Y,
+        "z" => Gate::Z,
+        "s" => Gate::S,
+        "sdg" => Gate::Sdg,
+        "sx" => Gate::Sx,
+        "sxdg" => Gate::Sxdg,
+        "t" => Gate::T,
+        "tdg" => Gate::Tdg,
+        "rx" => Gate::Rx(params[0].into()),
+        "ry" => Gate::Ry(params[0].into()),
+        "rz" => Gate::Rz(params[0].into()),
+        "p" | "u1" => Gate::Phase(params[0].into()),
+        "u2" => Gate::U3(FRAC_PI_2.into(), params[0].into(), params[1].into()),
+        "u3" | "u" | "U" => Gate::U3(params[0].into(), params[1].into(), params[2].into()),
+        "cx" | "CX" => Gate::Cx,
+        "cz" => Gate::Cz,
+        "swap" => Gate::Swap,
+        "ccx" => Gate::Ccx,
+        _ => unreachable!("unknown gates are rejected above"),
+    };
+    Ok((controls, gate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QasmError;
+    use crate::circuit::{Circuit, Gate, Instruction};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn bell_circuit() {
+        let c = Circuit::from_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            // prepare a Bell pair
+            qreg q[2];
+            creg c[2];
+            h q[0];
+            cx q[0], q[1];
+            barrier q;
+            measure q -> c;
+            "#,
+        )
+        .unwrap();
+
+        let mut expected = Circuit::with_clbits(2, 2);
+        expected
+            .h(0)
+            .cnot(0, 1)
+            .barrier(&[0, 1])
+            .measure(0, 0)
+            .measure(1, 1);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn registers_are_concatenated() {
+        let c = Circuit::from_qasm(
+            "OPENQASM 2.0; qreg a[2]; qreg b[2]; creg m[3]; cx a, b; x b[1]; measure b[0] -> m[2];",
+        )
+        .unwrap();
+
+        assert_eq!(c.num_qubits(), 4);
+        assert_eq!(c.num_clbits(), 3);
+        let mut expected = Circuit::with_clbits(4, 3);
+        expected.cnot(0, 2).cnot(1, 3).x(3).measure(2, 2);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn parameter_expressions() {
+        let c = Circuit::from_qasm(
+            "OPENQASM 2.0;\nqreg q[1];\nrz(-pi/4) q[0];\nu3(2*pi/3, 0.5e-1, sqrt(2)^2) q[0];\nu2(0, pi) q;",
+        )
+        .unwrap();
+
+        let gates: Vec<&Gate> = c
+            .instructions()
+            .iter()
+            .map(|i| match i {
+                Instruction::Gate { gate, .. } => gate,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(*gates[0], Gate::Rz((-std::f64::consts::FRAC_PI_4).into()));
+        let Gate::U3(theta, phi, lambda) = gates[1] else {
+            panic!("expected u3, found {}", gates[1]);
+        };
+        assert!((theta.value() - 2.0 * std::f64::consts::PI / 3.0).abs() < 1e-12);
+        assert!((phi.value() - 0.05).abs() < 1e-12);
+        assert!((lambda.value() - 2.0).abs() < 1e-12);
+        assert_eq!(gates[2].name(), "u3");
+    }
+
+    #[test]
+    fn controlled_gates() {
+        let qasm =
+            |call: &str| Circuit::from_qasm(&format!("OPENQASM 2.0; qreg q[3]; {call};")).unwrap();
+        let mut expected = Circuit::new(3);
+        expected.sdg(2).cnot(0, 2).s(2);
+        assert!(qasm("cy q[0], q[2]").equivalent_to(&expected, EPSILON));
+
+        let mut expected = Circuit::new(3);
+        expected.s(1).h(1).t(1).cnot(2, 1).tdg(1).h(1).sdg(1);
+        assert!(qasm("ch q[2], q[1]").equivalent_to(&expected, EPSILON));
+
+        let mut expected = Circuit::new(3);
+        expected.rz(1, 0.35).cnot(0, 1).rz(1, -0.35).cnot(0, 1);
+        assert!(qasm("crz(0.7) q[0], q[1]").equivalent_to(&expected, EPSILON));
+
+        let mut expected = Circuit::new(3);
+        expected.cp(1, 0, 0.7);
+        assert!(qasm("cu1(0.7) q[1], q[0]").equivalent_to(&expected, EPSILON));
+
+        let mut expected = Circuit::new(3);
+        expected.cnot(2, 1).ccx(0, 1, 2).cnot(2, 1);
+        assert!(qasm("cswap q[0], q[1], q[2]").equivalent_to(&expected, EPSILON));
+
+        // the definition of cu3 in qelib1.inc
+        let expected = qasm(
+            "u1(0.25) q[0]; u1(0.05) q[2]; cx q[0], q[2];
+             u3(-0.4, 0, -0.25) q[2]; cx q[0], q[2]; u3(0.4, 0.2, 0) q[2]",
+        );
+        assert!(qasm("cu3(0.8, 0.2, 0.3) q[0], q[2]").equivalent_to(&expected, EPSILON));
+    }
+
+    #[test]
+    fn conditional_correction() {
+        let c = Circuit::from_qasm(
+            "OPENQASM 2.0; qreg q[2]; creg c0[1]; creg c1[1];
+             x q[0]; measure q[0] -> c0[0]; if(c0==1) x q[1]; measure q[1] -> c1[0];",
+        )
+        .unwrap();
+
+        let run = c.run(&mut StdRng::seed_from_u64(0));
+        assert_eq!(run.clbits(), [true, true]);
+    }
+
+    #[test]
+    fn reset_whole_registers() {
+        let c = Circuit::from_qasm("OPENQASM 2.0; qreg q[2]; x q; reset q; reset q[1];").unwrap();
+        assert_eq!(c.gate_counts()["reset"], 3);
+        assert_eq!(c.simulate().amplitudes(2)[0], crate::Complex::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn toffoli() {
+        let c =
+            Circuit::from_qasm("OPENQASM 2.0; qreg q[3]; x q[0]; x q[2]; ccx q[0], q[2], q[1];")
+                .unwrap();
+        assert_eq!(
+            c.simulate().amplitudes(3)[0b111],
+            crate::Complex::new(1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn errors_report_line() {
+        let err = Circuit::from_qasm("OPENQASM 2.0;\nqreg q[1];\nfrob(1, 2) q[0];").unwrap_err();
+        assert_eq!(
+            err,
+            QasmError {
+                line: 3,
+                message: "unknown gate frob".to_string()
+            }
+        );
+
+        let err = Circuit::from_qasm("OPENQASM 2.0; qreg q[99999999999];").unwrap_err();
+        assert_eq!(
+            err.message,
+            "qreg q[99999999999] exceeds the 64-qubit limit"
+        );
+        let err = Circuit::from_qasm("OPENQASM 2.0; qreg a[40]; qreg b[40];").unwrap_err();
+        assert_eq!(err.message, "qreg b[40] exceeds the 64-qubit limit");
+        let err =
+            Circuit::from_qasm("OPENQASM 2.0; qreg q[1]; creg a[60]; creg b[5];").unwrap_err();
+        assert_eq!(
+            err.message,
+            "creg b[5] exceeds the 64-bit classical register"
+        );
+        assert!(Circuit::from_qasm("OPENQASM 2.0; qreg q[1]; creg a[60]; creg b[4];").is_ok());
+
+        let err = Circuit::from_qasm("OPENQASM 2.0;\nqreg q[1];\nh r[0];").unwrap_err();
+        assert_eq!(err.to_string(), "undeclared quantum register r on line 3");
+
+        let err = Circuit::from_qasm("OPENQASM 2.0; qreg q[2]; h q[2];").unwrap_err();
+        assert_eq!(err.message, "index 2 out of range for q[2]");
+
+        assert!(Circuit::from_qasm("qreg q[1];").is_err());
+        assert!(Circuit::from_qasm("OPENQASM 3.0;").is_err());
+        assert!(Circuit::from_qasm("OPENQASM 2.0; qreg q[2]; cx q;").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! The quantum Fourier transform |x⟩ ↦ 2^(−n/2) Σ_y e^(2πixy/2^n) |y⟩, with
+//! x and y read as integers in the crate's little-endian order, as a circuit
+//! and, for checking it on a few qubits, as an operator.
+
+use std::f64::consts::PI;
+
+use super::Circuit;
+use crate::ket::{KetBra, Operator};
+use crate::Complex;
+
+impl Circuit {
+    /// The QFT on n qubits: a Hadamard and controlled phases on each qubit
+    /// from the top, then the swaps that put the output back in order.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    ///
+    /// // |0⟩ goes to the uniform superposition
+    /// let amplitudes = Circuit::qft(3).simulate().amplitudes(3);
+    /// for a in amplitudes {
+    ///     assert!((a.re - 8f64.sqrt().recip()).abs() < 1e-12);
+    /// }
+    /// ```
+    pub fn qft(n: u32) -> Circuit {
+        Circuit::approximate_qft(n, 0.0)
+    }
+
+    /// The inverse QFT, [`Circuit::qft`] run backwards.
+    pub fn iqft(n: u32) -> Circuit {
+        Circuit::qft(n).inverse()
+    }
+
+    /// The QFT with controlled phases of angle below `threshold` dropped, as
+    /// in Coppersmith's approximate QFT. The phase between qubits d apart is
+    /// π/2^d, so a threshold of π/2^k keeps those at most k apart: the gate
+    /// count grows as nk rather than n²/2, at an error of order n·2^(−k).
+    pub fn approximate_qft(n: u32, threshold: f64) -> Circuit {
+        let mut c = Circuit::new(n);
+        let n = n as usize;
+        for target in (0..n).rev() {
+            c.h(target);
+            for control in (0..target).rev() {
+                let lambda = PI / (1u64 << (target - control)) as f64;
+                if lambda >= threshold {
+                    c.cp(control, target, lambda);
+                }
+            }
+        }
+        for q in 0..n / 2 {
+            c.swap(q, n - 1 - q);
+        }
+        c
+    }
+
+    /// The inverse of [`Circuit::approximate_qft`].
+    pub fn approximate_iqft(n: u32, threshold: f64) -> Circuit {
+        Circuit::approximate_qft(n, threshold).inverse()
+    }
+}
+
+impl Operator<Complex<f64>> {
+    /// The QFT on n qubits as its 4^n matrix entries, for small n.
+    pub fn qft(n: u32) -> Self {
+        Operator::fourier(n, 1.0)
+    }
+
+    /// The inverse QFT, the adjoint of [`Operator::qft`].
+    pub fn iqft(n: u32) -> Self {
+        Operator::fourier(n, -1.0)
+    }
+
+    /// Σ e^(2πi·sign·xy/N)/√N |y⟩⟨x|, reducing xy mod N before scaling to
+    /// keep the angles exact.
+    fn fourier(n: u32, sign: f64) -> Self {
+        let dim = 1u64 << n;
+        let norm = (dim as f64).sqrt().recip();
+        let mut ones = Vec::with_capacity((dim * dim) as usize);
+        for x in 0..dim {
+            for y in 0..dim {
+                let angle = sign * 2.0 * PI * ((x * y) % dim) as f64 / dim as f64;
+                ones.push(KetBra {
+                    scalar: Complex::new(norm * angle.cos(), norm * angle.sin()),
+                    ket: y,
+                    bra: x,
+                    n,
+                    d: 2,
+                });
+            }
+        }
+        Operator {
+            scalar: Complex::new(1.0, 0.0),
+            ones,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::circuit::Circuit;
+    use crate::ket::{Operator, State};
+    use crate::{Complex, Conjugate};
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn assert_implements(c: &Circuit, u: &Operator<Complex<f64>>) {
+        let n = c.num_qubits();
+        for x in 0..1 << n {
+            let input = State::basis(x, n);
+            let expected = (u.clone() * input.clone()).amplitudes(n);
+            let actual = (c.unitary() * input).amplitudes(n);
+            for (a, b) in actual.iter().zip(&expected) {
+                assert!((*a - *b).modulus() < EPSILON, "|{x}⟩: {a} != {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn circuits_match_the_operators() {
+        for n in 1..=4 {
+            assert_implements(&Circuit::qft(n), &Operator::qft(n));
+            assert_implements(&Circuit::iqft(n), &Operator::iqft(n));
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let mut c = Circuit::new(3);
+        c.x(0).x(2);
+        c.compose(&Circuit::qft(3), &[0, 1, 2])
+            .compose(&Circuit::iqft(3), &[0, 1, 2]);
+        let amplitudes = c.simulate().amplitudes(3);
+        assert!((amplitudes[0b101].re - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn periodic_inputs_peak_at_multiples_of_the_frequency() {
+        // (|0⟩ + |4⟩ + |8⟩ + |12⟩)/2 has period 4, so its transform is spread
+        // evenly over the multiples of 16/4
+        let mut c = Circuit::new(4);
+        c.h(2).h(3).compose(&Circuit::qft(4), &[0, 1, 2, 3]);
+        let amplitudes = c.simulate().amplitudes(4);
+        for (y, a) in amplitudes.iter().enumerate() {
+            let expected = if y % 4 == 0 { 0.25 } else { 0.0 };
+            assert!((a.mod_squared() - expected).abs() < EPSILON, "{y}");
+        }
+    }
+
+    #[test]
+    fn approximation_drops_small_rotations() {
+        let n = 6;
+        let exact = Circuit::qft(n);
+        let approximate = Circuit::approximate_qft(n, PI / 8.0);
+        // a controlled phase has two CNOTs, and only those between qubits
+        // at most three apart remain
+        let count = |c: &Circuit| c.gate_counts()["cx"] / 2;
+        assert_eq!(count(&exact), 15);
+        assert_eq!(count(&approximate), 5 + 4 + 3);
+        assert_eq!(Circuit::approximate_qft(n, 0.0), exact);
+
+        // still close to the exact transform
+        let u = exact.unitary();
+        for x in [0, 5, 42, 63] {
+            let input = State::basis(x, n);
+            let a = (u.clone() * input.clone()).amplitudes(n);
+            let b = (approximate.unitary() * input).amplitudes(n);
+            let overlap: Complex<f64> = a
+                .iter()
+                .zip(&b)
+                .map(|(p, q)| p.conj() * *q)
+                .fold(Complex::new(0.0, 0.0), |s, t| s + t);
+            assert!(overlap.modulus() > 0.9, "|{x}⟩: {}", overlap.modulus());
+        }
+    }
+}
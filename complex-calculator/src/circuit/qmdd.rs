@@ -0,0 +1,634 @@
+//! Quantum multiple-valued decision diagrams. A state is a binary tree over
+//! its qubits, the highest at the root, whose edges carry complex weights;
+//! an amplitude is the product of the weights along the path of its basis
+//! state. Subtrees equal up to a factor are stored once, so states with
+//! repeated structure, such as product states, GHZ states or the outputs of
+//! QFTs and adders, take a handful of nodes per qubit however large 2^n is.
+//!
+//! Gates are diagrams too, with four children per node, one per entry of a
+//! 2 × 2 block, and a gate is applied by multiplying its diagram into the
+//! state's, recursively and with results cached per pair of nodes.
+
+use std::collections::HashMap;
+
+use num::Zero;
+
+use super::statevector::gate_matrix;
+use super::{definite, Circuit, Gate, Instruction};
+use crate::ket::{Ket, State, MAX_QUBITS};
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// Weights within this of each other are taken to be equal when nodes are
+/// shared, and smaller ones to be zero.
+const TOLERANCE: f64 = 1e-10;
+
+/// The terminal node, below qubit 0.
+const TERMINAL: usize = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Edge {
+    weight: C,
+    node: usize,
+}
+
+impl Edge {
+    const ZERO: Edge = Edge {
+        weight: C { re: 0.0, im: 0.0 },
+        node: TERMINAL,
+    };
+
+    fn is_zero(&self) -> bool {
+        self.weight.is_zero()
+    }
+
+    fn scaled(self, factor: C) -> Edge {
+        if factor.is_zero() {
+            return Edge::ZERO;
+        }
+        Edge {
+            weight: self.weight * factor,
+            node: self.node,
+        }
+    }
+}
+
+/// A node on `qubit`, with two children for a state, |0⟩ then |1⟩, or four
+/// for an operator, ⟨r|·|c⟩ at 2r + c. The terminal has none.
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    qubit: u32,
+    children: Vec<Edge>,
+}
+
+type Key = (u32, Vec<(i64, i64, usize)>);
+
+/// An n-qubit state as a quantum multiple-valued decision diagram.
+///
+/// Nodes are interned as they are made and never freed, so the diagram
+/// grows with the work done on it; [`Qmdd::node_count`] counts only those
+/// the current state uses.
+#[derive(Debug, Clone)]
+pub struct Qmdd {
+    n: u32,
+    root: Edge,
+    nodes: Vec<Node>,
+    unique: HashMap<Key, usize>,
+    add_cache: HashMap<(usize, usize, (i64, i64)), Edge>,
+    multiply_cache: HashMap<(usize, usize), Edge>,
+}
+
+impl Qmdd {
+    /// The basis state |0…0⟩.
+    pub fn new(n: u32) -> Self {
+        assert!(
+            (1..=MAX_QUBITS).contains(&n),
+            "a decision diagram holds 1 to {MAX_QUBITS} qubits, not {n}"
+        );
+        let mut dd = Qmdd {
+            n,
+            root: Edge::ZERO,
+            nodes: vec![Node {
+                qubit: u32::MAX,
+                children: vec![],
+            }],
+            unique: HashMap::new(),
+            add_cache: HashMap::new(),
+            multiply_cache: HashMap::new(),
+        };
+        let one = C::new(1.0, 0.0);
+        let mut edge = Edge {
+            weight: one,
+            node: TERMINAL,
+        };
+        for q in 0..n {
+            edge = dd.make_node(q, vec![edge, Edge::ZERO]);
+        }
+        dd.root = edge;
+        dd
+    }
+
+    pub fn num_qubits(&self) -> u32 {
+        self.n
+    }
+
+    /// Number of nodes in the diagram of the state, the terminal left out.
+    pub fn node_count(&self) -> usize {
+        let mut seen = std::collections::HashSet::new();
+        let mut stack = vec![self.root];
+        while let Some(edge) = stack.pop() {
+            if edge.is_zero() || edge.node == TERMINAL || !seen.insert(edge.node) {
+                continue;
+            }
+            stack.extend(&self.nodes[edge.node].children);
+        }
+        seen.len()
+    }
+
+    /// Multiplies the state by e^(iφ).
+    pub fn apply_global_phase(&mut self, phi: f64) {
+        self.root = self.root.scaled(C::new(phi.cos(), phi.sin()));
+    }
+
+    /// Applies `gate` on `qubits`. X gates with any number of controls are
+    /// built as I + P ⊗ (X − I), with P the projector onto the controls
+    /// reading 1, so they take one node per qubit; other gates are built
+    /// from their matrix.
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        let operator = match gate {
+            Gate::X | Gate::Cx | Gate::Ccx | Gate::Mcx(_) => {
+                let (&target, controls) = qubits.split_last().unwrap();
+                self.controlled_x(controls, target)
+            }
+            _ => self.operator(&gate_matrix(gate), qubits),
+        };
+        self.apply_operator(operator);
+    }
+
+    fn apply_operator(&mut self, operator: Edge) {
+        self.root = self.multiply(operator, self.root);
+        self.add_cache.clear();
+        self.multiply_cache.clear();
+    }
+
+    /// The amplitude ⟨x|ψ⟩, the product of the weights along the path of x.
+    pub fn amplitude(&self, x: u64) -> C {
+        let mut edge = self.root;
+        let mut amplitude = C::new(1.0, 0.0);
+        for q in (0..self.n).rev() {
+            amplitude = amplitude * edge.weight;
+            if edge.is_zero() {
+                return C::zero();
+            }
+            edge = self.nodes[edge.node].children[(x >> q & 1) as usize];
+        }
+        amplitude * edge.weight
+    }
+
+    /// Probability of reading 1 from `qubit`.
+    pub fn probability_of_one(&self, qubit: usize) -> f64 {
+        let mut norms = HashMap::new();
+        let mut ones = HashMap::new();
+        self.root.weight.mod_squared()
+            * self.weight_of_one(self.root.node, qubit as u32, &mut norms, &mut ones)
+    }
+
+    /// Squared norm of the state below `node`.
+    fn norm(&self, node: usize, norms: &mut HashMap<usize, f64>) -> f64 {
+        if node == TERMINAL {
+            return 1.0;
+        }
+        if let Some(&norm) = norms.get(&node) {
+            return norm;
+        }
+        let norm = self.nodes[node]
+            .children
+            .iter()
+            .filter(|e| !e.is_zero())
+            .map(|e| e.weight.mod_squared() * self.norm(e.node, norms))
+            .sum();
+        norms.insert(node, norm);
+        norm
+    }
+
+    /// Squared norm of the part of the state below `node` with `qubit` at 1.
+    fn weight_of_one(
+        &self,
+        node: usize,
+        qubit: u32,
+        norms: &mut HashMap<usize, f64>,
+        ones: &mut HashMap<usize, f64>,
+    ) -> f64 {
+        if let Some(&weight) = ones.get(&node) {
+            return weight;
+        }
+        let Node { qubit: q, children } = &self.nodes[node];
+        let weight = if *q == qubit {
+            let one = children[1];
+            if one.is_zero() {
+                0.0
+            } else {
+                one.weight.mod_squared() * self.norm(one.node, norms)
+            }
+        } else {
+            children
+                .iter()
+                .filter(|e| !e.is_zero())
+                .map(|e| e.weight.mod_squared() * self.weight_of_one(e.node, qubit, norms, ones))
+                .sum()
+        };
+        ones.insert(node, weight);
+        weight
+    }
+
+    /// Measures `qubit` in the computational basis, collapsing the state onto
+    /// the outcome, which is returned as a bit.
+    pub fn measure<R: rand::Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let p1 = self.probability_of_one(qubit);
+        let outcome = rng.random::<f64>() < p1;
+        let p = if outcome { p1 } else { 1.0 - p1 };
+
+        let zero = C::zero();
+        let one = C::new(1.0, 0.0);
+        let projector = if outcome {
+            [zero, zero, zero, one]
+        } else {
+            [one, zero, zero, zero]
+        };
+        let operator = self.operator(&projector, &[qubit]);
+        self.apply_operator(operator);
+        self.root = self.root.scaled(C::new(1.0 / p.sqrt(), 0.0));
+        outcome
+    }
+
+    /// The state as a [`State`], visiting only the nonzero paths, so that it
+    /// is cheap when few amplitudes are nonzero.
+    pub fn to_state(&self) -> State<C> {
+        let mut superpositions = vec![];
+        let mut stack = vec![(self.root, self.n, 0u64)];
+        while let Some((edge, level, x)) = stack.pop() {
+            if edge.is_zero() {
+                continue;
+            }
+            if level == 0 {
+                superpositions.push(Ket {
+                    scalar: edge.weight,
+                    ket: x,
+                    n: self.n,
+                    d: 2,
+                });
+                continue;
+            }
+            let q = level - 1;
+            for (bit, child) in self.nodes[edge.node].children.iter().enumerate().rev() {
+                stack.push((child.scaled(edge.weight), q, x | (bit as u64) << q));
+            }
+        }
+        State {
+            scalar: C::new(1.0, 0.0),
+            superpositions,
+        }
+    }
+
+    /// The node on `qubit` with the given children, normalised so that the
+    /// first child of largest weight has weight 1, and the factor taken out
+    /// as the weight of the edge returned.
+    fn make_node(&mut self, qubit: u32, children: Vec<Edge>) -> Edge {
+        let largest = children
+            .iter()
+            .map(|e| e.weight.modulus())
+            .fold(0.0, f64::max);
+        if largest < TOLERANCE {
+            return Edge::ZERO;
+        }
+        let factor = children
+            .iter()
+            .find(|e| e.weight.modulus() > largest * (1.0 - TOLERANCE))
+            .unwrap()
+            .weight;
+
+        let children: Vec<Edge> = children
+            .into_iter()
+            .map(|e| {
+                let weight = snap(e.weight / factor);
+                if weight.is_zero() {
+                    Edge::ZERO
+                } else {
+                    Edge {
+                        weight,
+                        node: e.node,
+                    }
+                }
+            })
+            .collect();
+        let key = (
+            qubit,
+            children
+                .iter()
+                .map(|e| {
+                    let (re, im) = rounded(e.weight);
+                    (re, im, e.node)
+                })
+                .collect(),
+        );
+
+        let node = match self.unique.get(&key) {
+            Some(&node) => node,
+            None => {
+                self.nodes.push(Node { qubit, children });
+                self.unique.insert(key, self.nodes.len() - 1);
+                self.nodes.len() - 1
+            }
+        };
+        Edge {
+            weight: factor,
+            node,
+        }
+    }
+
+    /// The sum of two diagrams on the same qubits.
+    fn add(&mut self, a: Edge, b: Edge) -> Edge {
+        if a.is_zero() {
+            return b;
+        }
+        if b.is_zero() {
+            return a;
+        }
+        if a.node == TERMINAL {
+            let weight = snap(a.weight + b.weight);
+            return if weight.is_zero() {
+                Edge::ZERO
+            } else {
+                Edge {
+                    weight,
+                    node: TERMINAL,
+                }
+            };
+        }
+
+        // a + b = a.weight (A + r B), with |r| ≤ 1 so that the key is sound
+        let (a, b) = if a.weight.modulus() >= b.weight.modulus() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let ratio = b.weight / a.weight;
+        let key = (a.node, b.node, rounded(ratio));
+        if let Some(&sum) = self.add_cache.get(&key) {
+            return sum.scaled(a.weight);
+        }
+
+        let Node {
+            qubit,
+            children: a_children,
+        } = self.nodes[a.node].clone();
+        let b_children = self.nodes[b.node].children.clone();
+        let children = a_children
+            .into_iter()
+            .zip(b_children)
+            .map(|(x, y)| self.add(x, y.scaled(ratio)))
+            .collect();
+        let sum = self.make_node(qubit, children);
+        self.add_cache.insert(key, sum);
+        sum.scaled(a.weight)
+    }
+
+    /// The operator `m` applied to the state `v`.
+    fn multiply(&mut self, m: Edge, v: Edge) -> Edge {
+        if m.is_zero() || v.is_zero() {
+            return Edge::ZERO;
+        }
+        let factor = m.weight * v.weight;
+        if v.node == TERMINAL {
+            return Edge {
+                weight: factor,
+                node: TERMINAL,
+            };
+        }
+        if let Some(&product) = self.multiply_cache.get(&(m.node, v.node)) {
+            return product.scaled(factor);
+        }
+
+        let Node {
+            qubit,
+            children: block,
+        } = self.nodes[m.node].clone();
+        let column = self.nodes[v.node].children.clone();
+        let mut children = vec![];
+        for row in 0..2 {
+            let first = self.multiply(block[2 * row], column[0]);
+            let second = self.multiply(block[2 * row + 1], column[1]);
+            children.push(self.add(first, second));
+        }
+        let product = self.make_node(qubit, children);
+        self.multiply_cache.insert((m.node, v.node), product);
+        product.scaled(factor)
+    }
+
+    /// The operator applying the 2^k × 2^k `matrix` to `qubits`, the first
+    /// qubit on `qubits[0]`, and the identity elsewhere.
+    fn operator(&mut self, matrix: &[C], qubits: &[usize]) -> Edge {
+        let size = 1 << qubits.len();
+        let mut memo = HashMap::new();
+        self.build(self.n, 0, 0, matrix, size, qubits, &mut memo)
+    }
+
+    /// The operator below `level`, given the rows and columns of `matrix`
+    /// its target qubits above have picked out.
+    #[allow(clippy::too_many_arguments)]
+    fn build(
+        &mut self,
+        level: u32,
+        row: usize,
+        column: usize,
+        matrix: &[C],
+        size: usize,
+        qubits: &[usize],
+        memo: &mut HashMap<(u32, usize, usize), Edge>,
+    ) -> Edge {
+        if level == 0 {
+            let weight = matrix[row * size + column];
+            return if weight.is_zero() {
+                Edge::ZERO
+            } else {
+                Edge {
+                    weight,
+                    node: TERMINAL,
+                }
+            };
+        }
+        if let Some(&edge) = memo.get(&(level, row, column)) {
+            return edge;
+        }
+
+        let q = level - 1;
+        let children = match qubits.iter().position(|&t| t == q as usize) {
+            Some(i) => (0..4)
+                .map(|rc| {
+                    let (r, c) = (rc >> 1, rc & 1);
+                    self.build(q, row | r << i, column | c << i, matrix, size, qubits, memo)
+                })
+                .collect(),
+            None => {
+                let below = self.build(q, row, column, matrix, size, qubits, memo);
+                vec![below, Edge::ZERO, Edge::ZERO, below]
+            }
+        };
+        let edge = self.make_node(q, children);
+        memo.insert((level, row, column), edge);
+        edge
+    }
+
+    /// X on `target` controlled by `controls`, as the sum of the identity
+    /// and P ⊗ (X − I).
+    fn controlled_x(&mut self, controls: &[usize], target: usize) -> Edge {
+        let one = C::new(1.0, 0.0);
+        let mut identity = Edge {
+            weight: one,
+            node: TERMINAL,
+        };
+        let mut flip = identity;
+        for q in 0..self.n {
+            let (i, f) = (identity, flip);
+            flip = if q as usize == target {
+                let minus = f.scaled(-one);
+                self.make_node(q, vec![minus, f, f, minus])
+            } else if controls.contains(&(q as usize)) {
+                self.make_node(q, vec![Edge::ZERO, Edge::ZERO, Edge::ZERO, f])
+            } else {
+                self.make_node(q, vec![f, Edge::ZERO, Edge::ZERO, f])
+            };
+            identity = self.make_node(q, vec![i, Edge::ZERO, Edge::ZERO, i]);
+        }
+        self.add(identity, flip)
+    }
+}
+
+/// `w` with parts within the tolerance of 0 or ±1 set to them, so that
+/// nearly equal weights are stored equal.
+fn snap(w: C) -> C {
+    let part = |x: f64| {
+        for exact in [0.0, 1.0, -1.0] {
+            if (x - exact).abs() < TOLERANCE {
+                return exact;
+            }
+        }
+        x
+    };
+    C::new(part(w.re), part(w.im))
+}
+
+fn rounded(w: C) -> (i64, i64) {
+    (
+        (w.re / TOLERANCE).round() as i64,
+        (w.im / TOLERANCE).round() as i64,
+    )
+}
+
+impl Circuit {
+    /// Runs the circuit on |0…0⟩ as a [`Qmdd`]. Measurements, channels and
+    /// snapshots are not supported, and resets only on qubits sure to read 0
+    /// or 1, as [`Circuit::run_qmdd`] resets any.
+    pub fn simulate_qmdd(&self) -> Qmdd {
+        self.execute_qmdd(&mut None::<&mut dyn rand::RngCore>)
+    }
+
+    /// [`Circuit::simulate_qmdd`], resetting a qubit by measuring it with
+    /// outcomes sampled from `rng` and flipping it on reading 1.
+    pub fn run_qmdd<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Qmdd {
+        self.execute_qmdd(&mut Some(rng))
+    }
+
+    fn execute_qmdd<R: rand::Rng + ?Sized>(&self, rng: &mut Option<&mut R>) -> Qmdd {
+        let mut dd = Qmdd::new(self.num_qubits);
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => dd.apply(gate, qubits),
+                Instruction::Reset { qubit } => {
+                    let one = match rng.as_deref_mut() {
+                        Some(rng) => dd.measure(*qubit, rng),
+                        None => definite(dd.probability_of_one(*qubit)).unwrap_or_else(|| {
+                            panic!("simulate_qmdd() resets only qubits in a definite state, use run_qmdd()")
+                        }),
+                    };
+                    if one {
+                        dd.apply(&Gate::X, &[*qubit]);
+                    }
+                }
+                Instruction::Barrier { .. } => {}
+                Instruction::Snapshot { .. } => {
+                    panic!("simulate_qmdd() records no snapshots, use run()")
+                }
+                _ => panic!("simulate_qmdd() runs circuits of gates only"),
+            }
+        }
+        dd.apply_global_phase(self.global_phase);
+        dd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Qmdd;
+    use crate::circuit::{Circuit, Gate};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn assert_agrees(c: &Circuit) {
+        let n = c.num_qubits();
+        let (dd, dense) = (c.simulate_qmdd().to_state(), c.simulate());
+        for (x, y) in dd.amplitudes(n).iter().zip(&dense.amplitudes(n)) {
+            assert!((*x - *y).modulus() < EPSILON, "{x} != {y}");
+        }
+    }
+
+    #[test]
+    fn agrees_with_the_statevector() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..5 {
+            assert_agrees(&Circuit::random(5, 6, &mut rng));
+        }
+
+        let mut c = Circuit::new(4);
+        c.h(0)
+            .h(3)
+            .ccx(0, 3, 1)
+            .append(Gate::Mcx(3), &[1, 0, 3, 2])
+            .swap(0, 2)
+            .cz(2, 1)
+            .add_global_phase(0.4);
+        assert_agrees(&c);
+    }
+
+    #[test]
+    fn structured_states_stay_small() {
+        assert_eq!(Qmdd::new(40).node_count(), 40);
+
+        // one node per qubit for a uniform superposition, and two per qubit
+        // for a GHZ state
+        let mut c = Circuit::new(60);
+        for q in 0..60 {
+            c.h(q);
+        }
+        assert_eq!(c.simulate_qmdd().node_count(), 60);
+
+        let mut c = Circuit::new(60);
+        c.h(59);
+        for q in 0..59 {
+            c.cnot(59, q);
+        }
+        let dd = c.simulate_qmdd();
+        assert_eq!(dd.node_count(), 1 + 2 * 59);
+        assert!((dd.amplitude(u64::MAX >> 4).re - 0.5f64.sqrt()).abs() < EPSILON);
+        assert!((dd.probability_of_one(30) - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn product_states_take_a_node_per_qubit() {
+        let n = 16;
+        let mut c = Circuit::new(n);
+        c.x(0).x(5).x(11);
+        for q in 0..n as usize {
+            c.h(q).t(q).rz(q, 0.1 * q as f64);
+        }
+        assert_eq!(c.simulate_qmdd().node_count(), n as usize);
+    }
+
+    #[test]
+    fn measurement_collapses() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..10 {
+            let mut dd = Qmdd::new(30);
+            dd.apply(&Gate::H, &[3]);
+            dd.apply(&Gate::Cx, &[3, 27]);
+            let one = dd.measure(27, &mut rng);
+            let expected = if one { 1.0 } else { 0.0 };
+            assert!((dd.probability_of_one(3) - expected).abs() < EPSILON);
+            assert_eq!(dd.to_state().terms().count(), 1);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! LaTeX drawings of circuits with the `quantikz` package, in the column
+//! layout shared with [`Circuit::to_svg`]. There is one wire per qubit, with
+//! qubit 0 at the top.
+
+use super::layout::{pi_fraction, span};
+use super::{Circuit, Gate, Instruction, Param};
+use crate::Latex;
+
+const GREEK: [&str; 14] = [
+    "alpha", "beta", "gamma", "delta", "epsilon", "eta", "theta", "kappa", "lambda", "mu", "phi",
+    "psi", "omega", "tau",
+];
+
+impl Circuit {
+    /// The circuit as a `quantikz` environment, e.g. for a Bell pair
+    ///
+    /// ```text
+    /// \begin{quantikz}
+    /// \lstick{$q_{0}$} & \gate{H} & \ctrl{1} & \qw \\
+    /// \lstick{$q_{1}$} & \qw & \targ{} & \qw
+    /// \end{quantikz}
+    /// ```
+    ///
+    /// Angles are written as multiples of π where they are small fractions of
+    /// it. Classical wires are not drawn: a classically conditioned gate is
+    /// labelled with its bit instead, a loop is a box across every wire
+    /// labelled with its condition, barriers across every qubit become a
+    /// `\slice`, and snapshots a `\slice` with their label.
+    pub fn to_quantikz(&self) -> String {
+        let n = self.num_qubits as usize;
+        let layout = self.layout();
+        let mut columns = vec![vec!["\\qw".to_string(); n]; layout.columns.len()];
+
+        for (column, instructions) in columns.iter_mut().zip(&layout.columns) {
+            for instruction in instructions {
+                let (top, bottom) = span(instruction, n);
+                match instruction {
+                    Instruction::Gate { gate, qubits } => draw(column, gate, qubits, ""),
+                    Instruction::Measure { qubit, .. } => column[*qubit] = "\\meter{}".to_string(),
+                    Instruction::Reset { qubit } => {
+                        column[*qubit] = "\\gate{|0\\rangle}".to_string()
+                    }
+                    Instruction::Conditional {
+                        clbit,
+                        gate,
+                        qubits,
+                    } => draw(
+                        column,
+                        gate,
+                        qubits,
+                        &format!(" \\text{{ if }} c_{{{clbit}}}"),
+                    ),
+                    Instruction::Channel { channel, .. } => {
+                        let name = channel.name().replace('_', " ");
+                        let label = format!("\\mathcal{{E}}_{{\\text{{{name}}}}}");
+                        column[top] = match bottom - top {
+                            0 => format!("\\gate{{{label}}}"),
+                            span => format!("\\gate[wires={}]{{{label}}}", span + 1),
+                        };
+                    }
+                    Instruction::While {
+                        clbit,
+                        value,
+                        max_iterations,
+                        ..
+                    } => {
+                        let value = u8::from(*value);
+                        column[0] = format!(
+                            "\\gate[wires={n}]{{\\text{{while }} c_{{{clbit}}} = {value} \\text{{ (at most {max_iterations})}}}}"
+                        );
+                    }
+                    Instruction::Barrier { .. } | Instruction::Snapshot { .. } => unreachable!(),
+                }
+            }
+        }
+
+        for (level, label) in layout.slices {
+            columns[level][0].push_str(&format!(" \\slice{{{label}}}"));
+        }
+
+        let rows: Vec<String> = (0..n)
+            .map(|q| {
+                let mut row = format!("\\lstick{{$q_{{{q}}}$}}");
+                for column in &columns {
+                    row.push_str(" & ");
+                    row.push_str(&column[q]);
+                }
+                row.push_str(" & \\qw");
+                row
+            })
+            .collect();
+        format!(
+            "\\begin{{quantikz}}\n{}\n\\end{{quantikz}}\n",
+            rows.join(" \\\\\n")
+        )
+    }
+}
+
+/// Fills in the cells of `gate`, appending `condition` to the label of its
+/// box.
+fn draw(column: &mut [String], gate: &Gate, qubits: &[usize], condition: &str) {
+    let (&target, controls) = qubits.split_last().unwrap();
+    let boxed = |label: &str| format!("\\gate{{{label}{condition}}}");
+
+    match gate {
+        Gate::Cx | Gate::Cz | Gate::Ccx | Gate::Mcx(_) => {
+            for &c in controls {
+                column[c] = format!("\\ctrl{{{}}}", target as isize - c as isize);
+            }
+            column[target] = match (gate, condition) {
+                (Gate::Cz, "") => "\\control{}".to_string(),
+                (Gate::Cz, _) => boxed("Z"),
+                (_, "") => "\\targ{}".to_string(),
+                _ => boxed("X"),
+            };
+        }
+        Gate::Swap => {
+            let first = qubits[0];
+            column[first] = format!("\\swap{{{}}}", target as isize - first as isize);
+            column[target] = match condition {
+                "" => "\\targX{}".to_string(),
+                _ => boxed("\\text{SWAP}"),
+            };
+        }
+        _ => column[target] = boxed(&label(gate)),
+    }
+}
+
+/// The label of a single-qubit gate.
+fn label(gate: &Gate) -> String {
+    match gate {
+        Gate::I => "I".to_string(),
+        Gate::H => "H".to_string(),
+        Gate::X => "X".to_string(),
+        Gate::Y => "Y".to_string(),
+        Gate::Z => "Z".to_string(),
+        Gate::S => "S".to_string(),
+        Gate::Sdg => "S^\\dagger".to_string(),
+        Gate::T => "T".to_string(),
+        Gate::Tdg => "T^\\dagger".to_string(),
+        Gate::Sx => "\\sqrt{X}".to_string(),
+        Gate::Sxdg => "\\sqrt{X}^\\dagger".to_string(),
+        Gate::Rx(theta) => format!("R_x({})", angle(theta)),
+        Gate::Ry(theta) => format!("R_y({})", angle(theta)),
+        Gate::Rz(theta) => format!("R_z({})", angle(theta)),
+        Gate::Phase(lambda) => format!("P({})", angle(lambda)),
+        Gate::U3(theta, phi, lambda) => {
+            format!("U({}, {}, {})", angle(theta), angle(phi), angle(lambda))
+        }
+        Gate::Cx | Gate::Cz | Gate::Swap | Gate::Ccx | Gate::Mcx(_) => {
+            unreachable!("{} is not a single-qubit gate", gate.name())
+        }
+    }
+}
+
+/// An angle as a multiple of π where it is a small fraction of it, e.g.
+/// `\frac{3\pi}{4}`, and a symbol with a Greek name as that letter.
+fn angle(param: &Param) -> String {
+    let x = match param {
+        Param::Value(x) => *x,
+        Param::Symbol(name) if GREEK.contains(&name.as_str()) => return format!("\\{name}"),
+        Param::Symbol(name) => return name.clone(),
+    };
+
+    match pi_fraction(x) {
+        Some((0, _)) => "0".to_string(),
+        Some((p, q)) => {
+            let sign = if p < 0 { "-" } else { "" };
+            let numerator = match p.abs() {
+                1 => "\\pi".to_string(),
+                p => format!("{p}\\pi"),
+            };
+            match q {
+                1 => format!("{sign}{numerator}"),
+                _ => format!("{sign}\\frac{{{numerator}}}{{{q}}}"),
+            }
+        }
+        None => x.to_latex(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::channel::Channel;
+    use crate::circuit::{Circuit, Gate, Param};
+
+    fn body(c: &Circuit) -> String {
+        let tikz = c.to_quantikz();
+        tikz.strip_prefix("\\begin{quantikz}\n")
+            .and_then(|t| t.strip_suffix("\n\\end{quantikz}\n"))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn bell_pair() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+        assert_eq!(
+            c.to_quantikz(),
+            "\\begin{quantikz}\n\
+             \\lstick{$q_{0}$} & \\gate{H} & \\ctrl{1} & \\meter{} & \\qw \\\\\n\
+             \\lstick{$q_{1}$} & \\qw & \\targ{} & \\meter{} & \\qw\n\
+             \\end{quantikz}\n"
+        );
+    }
+
+    #[test]
+    fn angles_as_multiples_of_pi() {
+        let mut c = Circuit::new(1);
+        c.rz(0, PI / 4.0)
+            .rx(0, -3.0 * PI / 4.0)
+            .ry(0, PI)
+            .p(0, 0.5)
+            .u3(0, Param::symbol("theta"), 0.0, Param::symbol("b"))
+            .sdg(0);
+        assert_eq!(
+            body(&c),
+            "\\lstick{$q_{0}$} & \\gate{R_z(\\frac{\\pi}{4})} & \\gate{R_x(-\\frac{3\\pi}{4})} \
+             & \\gate{R_y(\\pi)} & \\gate{P(\\frac{1}{2})} & \\gate{U(\\theta, 0, b)} \
+             & \\gate{S^\\dagger} & \\qw"
+        );
+    }
+
+    #[test]
+    fn controls_point_at_their_target() {
+        let mut c = Circuit::new(3);
+        c.cz(2, 0).swap(0, 1).mcx(&[0, 2], 1);
+        assert_eq!(
+            body(&c),
+            "\\lstick{$q_{0}$} & \\control{} & \\swap{1} & \\ctrl{1} & \\qw \\\\\n\
+             \\lstick{$q_{1}$} & \\qw & \\targX{} & \\targ{} & \\qw \\\\\n\
+             \\lstick{$q_{2}$} & \\ctrl{-2} & \\qw & \\ctrl{-1} & \\qw"
+        );
+    }
+
+    #[test]
+    fn gates_share_columns_unless_crossed() {
+        let mut c = Circuit::new(3);
+        // the CNOT's line crosses qubit 1, so the X waits for it
+        c.h(1).cnot(0, 2).x(1).h(2);
+        assert_eq!(
+            body(&c),
+            "\\lstick{$q_{0}$} & \\qw & \\ctrl{2} & \\qw & \\qw \\\\\n\
+             \\lstick{$q_{1}$} & \\gate{H} & \\qw & \\gate{X} & \\qw \\\\\n\
+             \\lstick{$q_{2}$} & \\qw & \\targ{} & \\gate{H} & \\qw"
+        );
+    }
+
+    #[test]
+    fn conditionals_and_barriers() {
+        let mut c = Circuit::new(2);
+        c.h(0)
+            .barrier(&[0, 1])
+            .measure(0, 0)
+            .c_if(0, Gate::X, &[1])
+            .channel(Channel::phase_flip(0.1), &[0]);
+        assert_eq!(
+            body(&c),
+            "\\lstick{$q_{0}$} & \\gate{H} \\slice{} & \\meter{} \
+             & \\gate{\\mathcal{E}_{\\text{phase flip}}} & \\qw \\\\\n\
+             \\lstick{$q_{1}$} & \\qw & \\qw & \\gate{X \\text{ if } c_{0}} & \\qw"
+        );
+    }
+
+    #[test]
+    fn snapshots_are_labelled_slices() {
+        let mut c = Circuit::new(2);
+        c.h(0).snapshot("psi").x(1);
+        assert_eq!(
+            body(&c),
+            "\\lstick{$q_{0}$} & \\gate{H} \\slice{psi} & \\qw & \\qw \\\\\n\
+             \\lstick{$q_{1}$} & \\qw & \\gate{X} & \\qw"
+        );
+    }
+
+    #[test]
+    fn loops_span_every_wire() {
+        let mut toss = Circuit::new(2);
+        toss.h(0).measure(0, 0);
+        let mut c = Circuit::new(2);
+        c.x(1).while_loop(0, false, &toss, 3).x(1);
+        assert_eq!(
+            body(&c),
+            "\\lstick{$q_{0}$} & \\qw \
+             & \\gate[wires=2]{\\text{while } c_{0} = 0 \\text{ (at most 3)}} & \\qw & \\qw \\\\\n\
+             \\lstick{$q_{1}$} & \\gate{X} & \\qw & \\gate{X} & \\qw"
+        );
+    }
+}
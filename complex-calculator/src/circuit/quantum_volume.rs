@@ -0,0 +1,221 @@
+//! Model circuits of the quantum volume benchmark, after Cross et al.,
+//! "Validating quantum computers using randomized model circuits" (2019):
+//! as many layers as qubits, each pairing the qubits up by a random
+//! permutation and applying a Haar-random SU(4) to every pair. A device
+//! passes at a width when, over many such circuits, it lands on the heavy
+//! outputs — those more likely than the median under the ideal distribution
+//! — more than two thirds of the time. An ideal simulator lands on them
+//! about (1 + ln 2)/2 ≈ 0.85 of the time, and uniform noise half of it.
+
+use std::f64::consts::PI;
+
+use num::{One, Zero};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use super::{Circuit, Counts, KakDecomposition};
+use crate::ket::{KetBra, Operator};
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// A standard complex Gaussian, by the Box–Muller transform.
+fn gaussian<R: Rng + ?Sized>(rng: &mut R) -> C {
+    let radius = (-(1.0 - rng.random::<f64>()).ln()).sqrt();
+    let angle = 2.0 * PI * rng.random::<f64>();
+    C::new(radius * angle.cos(), radius * angle.sin())
+}
+
+/// A Haar-random unitary on two qubits, indexed `[ket][bra]`: Gram–Schmidt
+/// on the columns of a complex Gaussian matrix, which is Haar distributed
+/// as the diagonal of R comes out positive.
+fn haar_unitary<R: Rng + ?Sized>(rng: &mut R) -> [[C; 4]; 4] {
+    let mut columns = [[C::zero(); 4]; 4];
+    for j in 0..4 {
+        let mut v: [C; 4] = std::array::from_fn(|_| gaussian(rng));
+        for u in &columns[..j] {
+            let overlap = (0..4).fold(C::zero(), |acc, i| acc + u[i].conjugate() * v[i]);
+            for (x, y) in v.iter_mut().zip(u) {
+                *x = *x - overlap * *y;
+            }
+        }
+        let norm = v.iter().map(|x| x.mod_squared()).sum::<f64>().sqrt();
+        columns[j] = v.map(|x| x / C::new(norm, 0.0));
+    }
+    std::array::from_fn(|ket| std::array::from_fn(|bra| columns[bra][ket]))
+}
+
+impl Circuit {
+    /// A quantum volume model circuit on `num_qubits` qubits, of as many
+    /// layers. Each layer shuffles the qubits and applies a Haar-random
+    /// SU(4), synthesised by [`KakDecomposition`] into at most three CNOTs,
+    /// to each of the pairs, leaving the last qubit idle for odd widths.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let c = Circuit::quantum_volume(4, &mut StdRng::seed_from_u64(1));
+    /// let heavy = c.heavy_outputs();
+    /// assert_eq!(heavy.len(), 8);
+    /// assert!(c.heavy_output_probability() > 2.0 / 3.0);
+    /// ```
+    pub fn quantum_volume<R: Rng + ?Sized>(num_qubits: u32, rng: &mut R) -> Circuit {
+        let mut c = Circuit::new(num_qubits);
+        let mut qubits: Vec<usize> = (0..num_qubits as usize).collect();
+        for _ in 0..num_qubits {
+            qubits.shuffle(rng);
+            for pair in qubits.chunks_exact(2) {
+                let m = haar_unitary(rng);
+                let u = Operator {
+                    scalar: C::one(),
+                    ones: (0..4)
+                        .flat_map(|ket| (0..4).map(move |bra| (ket, bra)))
+                        .map(|(ket, bra)| KetBra {
+                            scalar: m[ket][bra],
+                            ket: ket as u64,
+                            bra: bra as u64,
+                            n: 2,
+                            d: 2,
+                        })
+                        .collect(),
+                };
+                c.compose(&KakDecomposition::new(&u).circuit(), pair);
+            }
+        }
+        c
+    }
+
+    /// The ideal probability of every basis state at the end of the
+    /// circuit, which should have no measurements.
+    fn ideal_probabilities(&self) -> Vec<f64> {
+        self.simulate_statevector::<f64>()
+            .amplitudes()
+            .iter()
+            .map(|a| a.mod_squared())
+            .collect()
+    }
+
+    /// The heavy outputs of the circuit: the basis states whose ideal
+    /// probability is above the median, in increasing order.
+    pub fn heavy_outputs(&self) -> Vec<u64> {
+        let probabilities = self.ideal_probabilities();
+        let mut sorted = probabilities.clone();
+        sorted.sort_by(f64::total_cmp);
+        let mid = sorted.len() / 2;
+        let median = (sorted[mid - 1] + sorted[mid]) / 2.0;
+        (0..probabilities.len() as u64)
+            .filter(|&x| probabilities[x as usize] > median)
+            .collect()
+    }
+
+    /// The probability an ideal run of the circuit ends on a heavy output.
+    pub fn heavy_output_probability(&self) -> f64 {
+        let probabilities = self.ideal_probabilities();
+        self.heavy_outputs()
+            .iter()
+            .map(|&x| probabilities[x as usize])
+            .sum()
+    }
+}
+
+impl Counts {
+    /// The fraction of shots landing on one of the `heavy` outputs, as from
+    /// [`Circuit::heavy_outputs`].
+    pub fn heavy_output_probability(&self, heavy: &[u64]) -> f64 {
+        let hits: usize = heavy.iter().map(|&x| self.get(x)).sum();
+        hits as f64 / self.shots() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::haar_unitary;
+    use crate::circuit::{Circuit, Counts};
+
+    use num::Zero;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn haar_unitaries_are_unitary() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..10 {
+            let u = haar_unitary(&mut rng);
+            for i in 0..4 {
+                for j in 0..4 {
+                    let product = (0..4).fold(crate::Complex::zero(), |acc, k| {
+                        acc + u[k][i].conjugate() * u[k][j]
+                    });
+                    let expected = if i == j { 1.0 } else { 0.0 };
+                    assert!((product - crate::Complex::new(expected, 0.0)).modulus() < EPSILON);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn square_layers_of_pairs() {
+        let c = Circuit::quantum_volume(5, &mut StdRng::seed_from_u64(3));
+        assert_eq!(c.num_qubits(), 5);
+        // two pairs in each of five layers, of at most three CNOTs each
+        assert!(c.gate_counts()["cx"] <= 5 * 2 * 3);
+        assert!(c.gate_counts()["cx"] > 5 * 2);
+        assert_eq!(c, Circuit::quantum_volume(5, &mut StdRng::seed_from_u64(3)));
+    }
+
+    #[test]
+    fn ideal_runs_pass_the_threshold() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mean = (0..20)
+            .map(|_| Circuit::quantum_volume(5, &mut rng).heavy_output_probability())
+            .sum::<f64>()
+            / 20.0;
+        assert!(mean > 0.8 && mean < 0.9, "{mean}");
+    }
+
+    #[test]
+    fn heavy_outputs_are_half_the_basis() {
+        let c = Circuit::quantum_volume(4, &mut StdRng::seed_from_u64(5));
+        let heavy = c.heavy_outputs();
+        assert_eq!(heavy.len(), 8);
+        let probabilities: Vec<f64> = c
+            .simulate()
+            .amplitudes(4)
+            .iter()
+            .map(|a| a.mod_squared())
+            .collect();
+        let lightest_heavy = heavy.iter().map(|&x| probabilities[x as usize]);
+        let heaviest_light = (0..16)
+            .filter(|x| !heavy.contains(x))
+            .map(|x| probabilities[x as usize]);
+        assert!(lightest_heavy.fold(1.0, f64::min) > heaviest_light.fold(0.0, f64::max));
+    }
+
+    #[test]
+    fn sampled_heavy_output_probability() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let c = Circuit::quantum_volume(4, &mut rng);
+        let mut measured = c.clone();
+        for q in 0..4 {
+            measured.measure(q, q);
+        }
+        let counts = measured.sample(2000, &mut rng);
+        let heavy = c.heavy_outputs();
+        let sampled = counts.heavy_output_probability(&heavy);
+        assert!(
+            (sampled - c.heavy_output_probability()).abs() < 0.05,
+            "{sampled}"
+        );
+
+        // uniformly random outcomes, as from a fully depolarised device
+        let mut noise = Counts::new(4);
+        for _ in 0..2000 {
+            noise.insert(rng.random_range(0..16), 1);
+        }
+        assert!((noise.heavy_output_probability(&heavy) - 0.5).abs() < 0.05);
+    }
+}
@@ -0,0 +1,202 @@
+//! Writer for Rigetti's Quil. Classical bits live in one `ro` register,
+//! symbolic parameters become `REAL` memory of the same name, and
+//! classically conditioned gates are jumped over when their bit is 0.
+
+use std::fmt::Write;
+
+use super::{Circuit, Gate, Instruction, Param};
+
+impl Circuit {
+    /// The circuit as a Quil program. U3 is written as RZ RY RZ and √X as RX, which drops
+    /// its global phase, as is the circuit's own, and barriers, which Quil
+    /// lacks, as comments. Bounded loops are unrolled. Panics on noise channels,
+    /// which have no Quil form.
+    pub fn to_quil(&self) -> String {
+        let mut out = String::new();
+        if self.num_clbits > 0 {
+            writeln!(out, "DECLARE ro BIT[{}]", self.num_clbits).unwrap();
+        }
+        for name in self.parameters() {
+            writeln!(out, "DECLARE {name} REAL").unwrap();
+        }
+
+        write_instructions(&mut out, &self.instructions, "");
+        out
+    }
+}
+
+/// Writes `instructions`, with `prefix` keeping the labels of loop bodies
+/// apart from those around them.
+fn write_instructions(out: &mut String, instructions: &[Instruction], prefix: &str) {
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::Gate { gate, qubits } => write_gate(out, gate, qubits),
+            Instruction::Measure { qubit, clbit } => {
+                writeln!(out, "MEASURE {qubit} ro[{clbit}]").unwrap()
+            }
+            Instruction::Reset { qubit } => writeln!(out, "RESET {qubit}").unwrap(),
+            Instruction::Barrier { qubits } => writeln!(out, "# barrier {}", join(qubits)).unwrap(),
+            Instruction::Snapshot { label } => writeln!(out, "# snapshot {label}").unwrap(),
+            Instruction::Conditional {
+                clbit,
+                gate,
+                qubits,
+            } => {
+                writeln!(out, "JUMP-UNLESS @skip{prefix}{i} ro[{clbit}]").unwrap();
+                write_gate(out, gate, qubits);
+                writeln!(out, "LABEL @skip{prefix}{i}").unwrap();
+            }
+            Instruction::Channel { channel, .. } => {
+                panic!("{} channel has no Quil form", channel.name())
+            }
+            Instruction::While {
+                clbit,
+                value,
+                max_iterations,
+                body,
+            } => {
+                // bounded, so unrolled, leaving as soon as the bit changes
+                let jump = if *value { "JUMP-UNLESS" } else { "JUMP-WHEN" };
+                for k in 0..*max_iterations {
+                    writeln!(out, "{jump} @end{prefix}{i} ro[{clbit}]").unwrap();
+                    write_instructions(out, &body.instructions, &format!("{prefix}{i}_{k}_"));
+                }
+                writeln!(out, "LABEL @end{prefix}{i}").unwrap();
+            }
+        }
+    }
+}
+
+fn write_gate(out: &mut String, gate: &Gate, qubits: &[usize]) {
+    let q = join(qubits);
+    let line = match gate {
+        Gate::I => format!("I {q}"),
+        Gate::H => format!("H {q}"),
+        Gate::X => format!("X {q}"),
+        Gate::Y => format!("Y {q}"),
+        Gate::Z => format!("Z {q}"),
+        Gate::S => format!("S {q}"),
+        Gate::Sdg => format!("DAGGER S {q}"),
+        Gate::Sx => format!("RX(pi/2) {q}"),
+        Gate::Sxdg => format!("RX(-pi/2) {q}"),
+        Gate::T => format!("T {q}"),
+        Gate::Tdg => format!("DAGGER T {q}"),
+        Gate::Rx(theta) => format!("RX({}) {q}", angle(theta)),
+        Gate::Ry(theta) => format!("RY({}) {q}", angle(theta)),
+        Gate::Rz(theta) => format!("RZ({}) {q}", angle(theta)),
+        Gate::Phase(lambda) => format!("PHASE({}) {q}", angle(lambda)),
+        Gate::U3(theta, phi, lambda) => format!(
+            "RZ({}) {q}\nRY({}) {q}\nRZ({}) {q}",
+            angle(lambda),
+            angle(theta),
+            angle(phi)
+        ),
+        Gate::Cx => format!("CNOT {q}"),
+        Gate::Cz => format!("CZ {q}"),
+        Gate::Swap => format!("SWAP {q}"),
+        Gate::Ccx => format!("CCNOT {q}"),
+        Gate::Mcx(controls) => format!("{}X {q}", "CONTROLLED ".repeat(*controls)),
+    };
+    writeln!(out, "{line}").unwrap();
+}
+
+fn angle(param: &Param) -> String {
+    match param {
+        Param::Value(x) => x.to_string(),
+        Param::Symbol(name) => name.clone(),
+    }
+}
+
+fn join(qubits: &[usize]) -> String {
+    qubits
+        .iter()
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channel::Channel;
+    use crate::circuit::{Circuit, Gate, Param};
+
+    #[test]
+    fn bell_pair() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+        assert_eq!(
+            c.to_quil(),
+            "DECLARE ro BIT[2]\nH 0\nCNOT 0 1\nMEASURE 0 ro[0]\nMEASURE 1 ro[1]\n"
+        );
+    }
+
+    #[test]
+    fn gate_set() {
+        let mut c = Circuit::new(4);
+        c.sdg(0)
+            .tdg(1)
+            .rx(2, 0.5)
+            .p(3, 0.25)
+            .cz(1, 2)
+            .swap(0, 3)
+            .ccx(0, 1, 2)
+            .mcx(&[0, 1, 2], 3)
+            .u3(0, 1.0, 2.0, 3.0)
+            .barrier(&[0, 1]);
+        assert_eq!(
+            c.to_quil(),
+            "DAGGER S 0\nDAGGER T 1\nRX(0.5) 2\nPHASE(0.25) 3\nCZ 1 2\nSWAP 0 3\n\
+             CCNOT 0 1 2\nCONTROLLED CONTROLLED CONTROLLED X 0 1 2 3\n\
+             RZ(3) 0\nRY(1) 0\nRZ(2) 0\n# barrier 0 1\n"
+        );
+    }
+
+    #[test]
+    fn conditionals_jump() {
+        let mut c = Circuit::new(2);
+        c.measure(0, 0).c_if(0, Gate::X, &[1]);
+        assert_eq!(
+            c.to_quil(),
+            "DECLARE ro BIT[1]\nMEASURE 0 ro[0]\nJUMP-UNLESS @skip1 ro[0]\nX 1\nLABEL @skip1\n"
+        );
+    }
+
+    #[test]
+    fn loops_are_unrolled() {
+        let mut body = Circuit::new(2);
+        body.h(0).measure(0, 0).c_if(0, Gate::X, &[1]);
+        let mut c = Circuit::new(2);
+        c.while_loop(0, false, &body, 2);
+        assert_eq!(
+            c.to_quil(),
+            "DECLARE ro BIT[1]\n\
+             JUMP-WHEN @end0 ro[0]\nH 0\nMEASURE 0 ro[0]\n\
+             JUMP-UNLESS @skip0_0_2 ro[0]\nX 1\nLABEL @skip0_0_2\n\
+             JUMP-WHEN @end0 ro[0]\nH 0\nMEASURE 0 ro[0]\n\
+             JUMP-UNLESS @skip0_1_2 ro[0]\nX 1\nLABEL @skip0_1_2\n\
+             LABEL @end0\n"
+        );
+    }
+
+    #[test]
+    fn reset() {
+        let mut c = Circuit::new(1);
+        c.x(0).reset(0);
+        assert_eq!(c.to_quil(), "X 0\nRESET 0\n");
+    }
+
+    #[test]
+    fn parameters_are_declared() {
+        let mut c = Circuit::new(1);
+        c.ry(0, Param::symbol("theta"));
+        assert_eq!(c.to_quil(), "DECLARE theta REAL\nRY(theta) 0\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "depolarizing channel has no Quil form")]
+    fn channels_have_no_quil_form() {
+        Circuit::new(1)
+            .channel(Channel::depolarizing(0.1), &[0])
+            .to_quil();
+    }
+}
@@ -0,0 +1,698 @@
+//! Conversion to and from the circuit JSON of Quirk, the drag-and-drop
+//! simulator at algassert.com/quirk, as found after `#circuit=` in its share
+//! URLs.
+//!
+//! A Quirk circuit is a list of columns, each listing the gate on every wire
+//! from the top, with wire i being qubit i. Controls (`•`) and anti-controls
+//! (`◦`) apply to every gate in their column. Custom gates, initial states
+//! and Quirk's displays and arithmetic gates are not supported, and
+//! measurements are read into the classical bit of the same index.
+
+use std::f64::consts::PI;
+
+use super::{Circuit, Gate, Instruction, Param};
+use crate::ket::MAX_QUBITS;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkError {
+    pub message: String,
+}
+
+impl std::fmt::Display for QuirkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for QuirkError {}
+
+fn error<T>(message: impl Into<String>) -> Result<T, QuirkError> {
+    Err(QuirkError {
+        message: message.into(),
+    })
+}
+
+const URL: &str = "https://algassert.com/quirk#circuit=";
+
+impl Circuit {
+    /// Reads a circuit from Quirk JSON, or from a whole share URL.
+    pub fn from_quirk(source: &str) -> Result<Circuit, QuirkError> {
+        let json = match source.split_once("circuit=") {
+            Some((_, encoded)) => percent_decode(encoded)?,
+            None => source.to_string(),
+        };
+
+        let mut reader = Reader {
+            chars: json.chars().collect(),
+            pos: 0,
+        };
+        let value = reader.value()?;
+        reader.skip_whitespace();
+        if reader.pos < reader.chars.len() {
+            return error("trailing characters after the circuit");
+        }
+
+        let Json::Object(fields) = value else {
+            return error("expected a JSON object");
+        };
+        let mut cols = None;
+        for (key, value) in fields {
+            match (key.as_str(), value) {
+                ("cols", Json::Array(value)) => cols = Some(value),
+                ("cols", _) => return error("cols is not an array"),
+                ("init", _) => return error("initial states are not supported"),
+                ("gates", _) => return error("custom gates are not supported"),
+                _ => {}
+            }
+        }
+        let Some(cols) = cols else {
+            return error("missing cols");
+        };
+
+        let mut columns = vec![];
+        for col in cols {
+            let Json::Array(entries) = col else {
+                return error("column is not an array");
+            };
+            columns.push(
+                entries
+                    .into_iter()
+                    .map(Entry::read)
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
+        }
+
+        let n = columns.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        if n > MAX_QUBITS as usize {
+            return error(format!("{n} wires exceed the {MAX_QUBITS}-qubit limit"));
+        }
+        let mut circuit = Circuit::new(n as u32);
+        for column in &columns {
+            read_column(&mut circuit, column)?;
+        }
+        Ok(circuit)
+    }
+
+    /// The circuit as Quirk JSON. Single-qubit gates share columns where they
+    /// can, U3 becomes Rz Ry Rz, global phases are dropped and barriers
+    /// start a new column. Resets, classically conditioned gates, loops,
+    /// noise channels and unbound parameters have no Quirk form.
+    pub fn to_quirk(&self) -> Result<String, QuirkError> {
+        let n = self.num_qubits as usize;
+        let mut columns: Vec<Vec<String>> = vec![];
+        // whether the last column has room for more single-qubit gates
+        let mut open = false;
+
+        let single = |columns: &mut Vec<Vec<String>>, open: &mut bool, q: usize, entry| {
+            if !*open || columns.last().is_none_or(|column| column[q] != "1") {
+                columns.push(vec!["1".to_string(); n]);
+                *open = true;
+            }
+            columns.last_mut().unwrap()[q] = entry;
+        };
+
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => match gate {
+                    Gate::Cx | Gate::Cz | Gate::Swap | Gate::Ccx | Gate::Mcx(_) => {
+                        let mut column = vec!["1".to_string(); n];
+                        let (&target, controls) = qubits.split_last().unwrap();
+                        for &c in controls {
+                            column[c] = quoted("•");
+                        }
+                        column[target] = quoted(match gate {
+                            Gate::Cz => "Z",
+                            Gate::Swap => "Swap",
+                            _ => "X",
+                        });
+                        if *gate == Gate::Swap {
+                            column[qubits[0]] = quoted("Swap");
+                        }
+                        columns.push(column);
+                        open = false;
+                    }
+                    Gate::U3(theta, phi, lambda) => {
+                        let q = qubits[0];
+                        for (id, angle) in [("Rzft", lambda), ("Ryft", theta), ("Rzft", phi)] {
+                            single(&mut columns, &mut open, q, formula(id, angle, 1.0)?);
+                        }
+                    }
+                    _ => single(&mut columns, &mut open, qubits[0], single_entry(gate)?),
+                },
+                Instruction::Measure { qubit, .. } => {
+                    single(&mut columns, &mut open, *qubit, quoted("Measure"))
+                }
+                Instruction::Barrier { .. } | Instruction::Snapshot { .. } => open = false,
+                Instruction::Reset { .. } => return error("resets have no Quirk form"),
+                Instruction::Conditional { .. } => {
+                    return error("classically conditioned gates have no Quirk form")
+                }
+                Instruction::Channel { .. } => return error("noise channels have no Quirk form"),
+                Instruction::While { .. } => return error("loops have no Quirk form"),
+            }
+        }
+
+        let columns: Vec<String> = columns
+            .into_iter()
+            .map(|mut column| {
+                while column.last().is_some_and(|entry| entry == "1") {
+                    column.pop();
+                }
+                format!("[{}]", column.join(","))
+            })
+            .collect();
+        Ok(format!("{{\"cols\":[{}]}}", columns.join(",")))
+    }
+
+    /// A share URL opening the circuit in Quirk.
+    pub fn to_quirk_url(&self) -> Result<String, QuirkError> {
+        Ok(format!("{URL}{}", percent_encode(&self.to_quirk()?)))
+    }
+}
+
+/// One cell of a column.
+enum Entry {
+    Empty,
+    Gate { id: String, arg: Option<f64> },
+}
+
+impl Entry {
+    fn read(json: Json) -> Result<Entry, QuirkError> {
+        match json {
+            Json::Number(1.0) => Ok(Entry::Empty),
+            Json::String(id) => Ok(Entry::Gate { id, arg: None }),
+            Json::Object(fields) => {
+                let (mut id, mut arg) = (None, None);
+                for (key, value) in fields {
+                    match (key.as_str(), value) {
+                        ("id", Json::String(value)) => id = Some(value),
+                        ("arg", Json::String(value)) => match parse_formula(&value) {
+                            Some(x) => arg = Some(x),
+                            None => return error(format!("unsupported formula {value:?}")),
+                        },
+                        ("arg", Json::Number(value)) => arg = Some(value),
+                        _ => {}
+                    }
+                }
+                match id {
+                    Some(id) => Ok(Entry::Gate { id, arg }),
+                    None => error("gate without an id"),
+                }
+            }
+            _ => error("unexpected column entry"),
+        }
+    }
+}
+
+fn read_column(circuit: &mut Circuit, column: &[Entry]) -> Result<(), QuirkError> {
+    let mut controls = vec![];
+    let mut anti_controls = vec![];
+    let mut swaps = vec![];
+    let mut gates = vec![];
+    for (q, entry) in column.iter().enumerate() {
+        let Entry::Gate { id, arg } = entry else {
+            continue;
+        };
+        match id.as_str() {
+            "•" => controls.push(q),
+            "◦" => anti_controls.push(q),
+            "Swap" => swaps.push(q),
+            "…" => {}
+            _ => gates.push((q, id.as_str(), *arg)),
+        }
+    }
+
+    for &q in &anti_controls {
+        circuit.x(q);
+    }
+    controls.extend(&anti_controls);
+
+    match (swaps.as_slice(), controls.is_empty()) {
+        ([], _) => {}
+        (&[a, b], true) => {
+            circuit.swap(a, b);
+        }
+        (&[_, _], false) => return error("controlled swaps are not supported"),
+        _ => return error("a column needs exactly two Swap gates"),
+    }
+
+    for (q, id, arg) in gates {
+        let angle = || {
+            arg.ok_or_else(|| QuirkError {
+                message: format!("{id} without an argument"),
+            })
+        };
+        let gate = match id {
+            "Measure" if controls.is_empty() => {
+                circuit.measure(q, q);
+                continue;
+            }
+            "H" => Gate::H,
+            "X" => Gate::X,
+            "Y" => Gate::Y,
+            "Z" => Gate::Z,
+            "Z^½" => Gate::S,
+            "Z^-½" => Gate::Sdg,
+            "Z^¼" => Gate::T,
+            "Z^-¼" => Gate::Tdg,
+            "X^½" => Gate::Sx,
+            "X^-½" => Gate::Sxdg,
+            "Rxft" => Gate::Rx(angle()?.into()),
+            "Ryft" => Gate::Ry(angle()?.into()),
+            "Rzft" => Gate::Rz(angle()?.into()),
+            "Z^ft" => Gate::Phase((angle()? * PI).into()),
+            _ if !controls.is_empty() => return error(format!("controlled {id} is not supported")),
+            _ => return error(format!("unsupported gate {id}")),
+        };
+
+        match (&gate, controls.len()) {
+            (_, 0) => {
+                circuit.append(gate, &[q]);
+            }
+            (Gate::X, _) => {
+                circuit.mcx(&controls, q);
+            }
+            (Gate::Z, 1) => {
+                circuit.cz(controls[0], q);
+            }
+            (Gate::Z, _) => {
+                circuit.h(q).mcx(&controls, q).h(q);
+            }
+            _ => return error(format!("controlled {id} is not supported")),
+        }
+    }
+
+    for &q in &anti_controls {
+        circuit.x(q);
+    }
+    Ok(())
+}
+
+/// The JSON of an uncontrolled single-qubit gate.
+fn single_entry(gate: &Gate) -> Result<String, QuirkError> {
+    Ok(match gate {
+        Gate::I => quoted("…"),
+        Gate::H => quoted("H"),
+        Gate::X => quoted("X"),
+        Gate::Y => quoted("Y"),
+        Gate::Z => quoted("Z"),
+        Gate::S => quoted("Z^½"),
+        Gate::Sdg => quoted("Z^-½"),
+        Gate::T => quoted("Z^¼"),
+        Gate::Tdg => quoted("Z^-¼"),
+        Gate::Sx => quoted("X^½"),
+        Gate::Sxdg => quoted("X^-½"),
+        Gate::Rx(theta) => formula("Rxft", theta, 1.0)?,
+        Gate::Ry(theta) => formula("Ryft", theta, 1.0)?,
+        Gate::Rz(theta) => formula("Rzft", theta, 1.0)?,
+        // Z^t is diag(1, e^(iπt))
+        Gate::Phase(lambda) => formula("Z^ft", lambda, 1.0 / PI)?,
+        _ => unreachable!("{gate} is not a single-qubit gate"),
+    })
+}
+
+fn quoted(id: &str) -> String {
+    format!("\"{id}\"")
+}
+
+/// A gate taking the formula argument `param * scale`.
+fn formula(id: &str, param: &Param, scale: f64) -> Result<String, QuirkError> {
+    match param {
+        Param::Value(x) => Ok(format!("{{\"id\":\"{id}\",\"arg\":\"{}\"}}", x * scale)),
+        Param::Symbol(name) => error(format!("unbound parameter {name}")),
+    }
+}
+
+/// The value of a constant Quirk formula such as `0.25`, `-pi/2`, `3π/4` or
+/// `2*pi`.
+fn parse_formula(source: &str) -> Option<f64> {
+    let source: String = source.chars().filter(|c| !c.is_whitespace()).collect();
+    let source = source.replace('π', "pi");
+    let (sign, source) = match source.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, source.as_str()),
+    };
+    let (numerator, denominator) = match source.split_once('/') {
+        Some((n, d)) => (n, d.parse::<f64>().ok()?),
+        None => (source, 1.0),
+    };
+
+    let numerator = match numerator.strip_suffix("pi") {
+        Some("") => PI,
+        Some(factor) => {
+            factor
+                .strip_suffix('*')
+                .unwrap_or(factor)
+                .parse::<f64>()
+                .ok()?
+                * PI
+        }
+        None => numerator.parse::<f64>().ok()?,
+    };
+    Some(sign * numerator / denominator)
+}
+
+fn percent_decode(source: &str) -> Result<String, QuirkError> {
+    let bytes = source.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = source
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match byte {
+                Some(byte) => decoded.push(byte),
+                None => return error("malformed percent escape in URL"),
+            }
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).or_else(|_| error("URL is not valid UTF-8"))
+}
+
+fn percent_encode(source: &str) -> String {
+    source
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The subset of JSON needed for Quirk circuits.
+enum Json {
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+    /// `true`, `false` or `null`, which no supported field uses.
+    Literal,
+}
+
+struct Reader {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Reader {
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), QuirkError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            error(format!("expected '{c}' at character {}", self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, QuirkError> {
+        self.skip_whitespace();
+        match self.chars.get(self.pos) {
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = vec![];
+                if !self.eat('}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.expect(':')?;
+                        fields.push((key, self.value()?));
+                        if !self.eat(',') {
+                            break;
+                        }
+                    }
+                    self.expect('}')?;
+                }
+                Ok(Json::Object(fields))
+            }
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                if !self.eat(']') {
+                    loop {
+                        items.push(self.value()?);
+                        if !self.eat(',') {
+                            break;
+                        }
+                    }
+                    self.expect(']')?;
+                }
+                Ok(Json::Array(items))
+            }
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some(c) if c.is_ascii_alphabetic() => {
+                for literal in ["true", "false", "null"] {
+                    if self.chars[self.pos..].starts_with(&literal.chars().collect::<Vec<_>>()) {
+                        self.pos += literal.len();
+                        return Ok(Json::Literal);
+                    }
+                }
+                error(format!("unexpected '{c}' at character {}", self.pos))
+            }
+            Some(_) => {
+                let start = self.pos;
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|&c| c.is_ascii_digit() || "+-.eE".contains(c))
+                {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                match number.parse() {
+                    Ok(x) => Ok(Json::Number(x)),
+                    Err(_) => error(format!("unexpected character at {start}")),
+                }
+            }
+            None => error("unexpected end of input"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, QuirkError> {
+        if self.chars.get(self.pos) != Some(&'"') {
+            return error(format!("expected a string at character {}", self.pos));
+        }
+        self.pos += 1;
+
+        let mut s = String::new();
+        loop {
+            let Some(&c) = self.chars.get(self.pos) else {
+                return error("unterminated string");
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let Some(&escape) = self.chars.get(self.pos) else {
+                        return error("unterminated string");
+                    };
+                    self.pos += 1;
+                    s.push(match escape {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => {
+                            let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+                            self.pos += 4;
+                            match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                                Some(c) => c,
+                                None => return error(format!("unsupported escape \\u{hex}")),
+                            }
+                        }
+                        c => c,
+                    });
+                }
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::parse_formula;
+    use crate::circuit::{Circuit, Gate, Instruction, Param};
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn bell_pair() {
+        let c = Circuit::from_quirk(r#"{"cols":[["H"],["•","X"],["Measure","Measure"]]}"#).unwrap();
+
+        let mut expected = Circuit::new(2);
+        expected.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn share_urls() {
+        let mut expected = Circuit::new(2);
+        expected.h(1).s(0);
+
+        for url in [
+            "https://algassert.com/quirk#circuit=%7B%22cols%22%3A%5B%5B1%2C%22H%22%5D%2C%5B%22Z%5E%C2%BD%22%5D%5D%7D",
+            "https://algassert.com/quirk#circuit={%22cols%22:[[1,%22H%22],[%22Z^%C2%BD%22]]}",
+        ] {
+            assert_eq!(Circuit::from_quirk(url).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn controls_apply_to_the_whole_column() {
+        let c = Circuit::from_quirk(r#"{"cols":[["X","•","X","•"],["◦","Z"]]}"#).unwrap();
+
+        let mut expected = Circuit::new(4);
+        expected.ccx(1, 3, 0).ccx(1, 3, 2).x(0).cz(0, 1).x(0);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn multi_controlled_z() {
+        let c = Circuit::from_quirk(r#"{"cols":[["•","•","Z"]]}"#).unwrap();
+        let mut expected = Circuit::new(3);
+        expected.h(2).ccx(0, 1, 2).h(2);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn rotations_with_formulas() {
+        let c = Circuit::from_quirk(
+            r#"{"cols":[[{"id":"Rzft","arg":"pi/2"},{"id":"Rxft","arg":"-3π/4"}],
+                        [{"id":"Z^ft","arg":"0.25"},{"id":"Ryft","arg":0.5}]]}"#,
+        )
+        .unwrap();
+
+        let angles: Vec<f64> = c
+            .instructions()
+            .iter()
+            .map(|instruction| match instruction {
+                Instruction::Gate { gate, .. } => gate.params()[0].value(),
+                _ => unreachable!(),
+            })
+            .collect();
+        for (a, b) in angles
+            .iter()
+            .zip([PI / 2.0, -3.0 * PI / 4.0, PI / 4.0, 0.5])
+        {
+            assert!((a - b).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn formulas() {
+        assert_eq!(parse_formula("0.25"), Some(0.25));
+        assert_eq!(parse_formula("pi"), Some(PI));
+        assert_eq!(parse_formula("2*pi"), Some(2.0 * PI));
+        assert_eq!(parse_formula("- π / 4"), Some(-PI / 4.0));
+        assert_eq!(parse_formula("t"), None);
+    }
+
+    #[test]
+    fn unsupported_input() {
+        for (json, message) in [
+            (r#"{"cols":[["QFT3"]]}"#, "unsupported gate QFT3"),
+            (r#"{"cols":[["•","H"]]}"#, "controlled H is not supported"),
+            (
+                r#"{"cols":[["Swap"]]}"#,
+                "a column needs exactly two Swap gates",
+            ),
+            (
+                r#"{"cols":[],"init":[1]}"#,
+                "initial states are not supported",
+            ),
+            (r#"{"cols":[["H"]"#, "expected ']' at character 14"),
+        ] {
+            assert_eq!(Circuit::from_quirk(json).unwrap_err().message, message);
+        }
+
+        let wide = format!(r#"{{"cols":[[{}]]}}"#, vec!["1"; 65].join(","));
+        assert_eq!(
+            Circuit::from_quirk(&wide).unwrap_err().message,
+            "65 wires exceed the 64-qubit limit"
+        );
+    }
+
+    #[test]
+    fn export_packs_single_qubit_gates() {
+        let mut c = Circuit::new(3);
+        c.h(0).h(2).cnot(0, 1).t(1).s(1).swap(0, 2).measure(2, 0);
+
+        assert_eq!(
+            c.to_quirk().unwrap(),
+            r#"{"cols":[["H",1,"H"],["•","X"],[1,"Z^¼"],[1,"Z^½"],["Swap",1,"Swap"],[1,1,"Measure"]]}"#
+        );
+    }
+
+    #[test]
+    fn barrier_starts_a_new_column() {
+        let mut c = Circuit::new(2);
+        c.h(0).barrier(&[0, 1]).h(1);
+        assert_eq!(c.to_quirk().unwrap(), r#"{"cols":[["H"],[1,"H"]]}"#);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut c = Circuit::new(4);
+        c.h(0)
+            .rx(1, 0.3)
+            .p(2, 1.1)
+            .cz(3, 0)
+            .ccx(0, 1, 3)
+            .mcx(&[0, 1, 2], 3)
+            .tdg(2)
+            .ry(3, -0.7)
+            .swap(1, 2);
+
+        let url = c.to_quirk_url().unwrap();
+        assert!(url.starts_with("https://algassert.com/quirk#circuit=%7B"));
+
+        let back = Circuit::from_quirk(&url).unwrap();
+        assert_eq!(back.gate_counts(), c.gate_counts());
+        assert!(back.equivalent_to(&c, EPSILON));
+    }
+
+    #[test]
+    fn u3_becomes_euler_rotations() {
+        let mut c = Circuit::new(1);
+        c.u3(0, 0.4, 1.2, -0.5);
+        let back = Circuit::from_quirk(&c.to_quirk().unwrap()).unwrap();
+        assert_eq!(back.len(), 3);
+        assert!(c.equivalent_to(&back, EPSILON));
+    }
+
+    #[test]
+    fn export_rejects_what_quirk_cannot_show() {
+        let mut c = Circuit::new(1);
+        c.measure(0, 0).c_if(0, Gate::X, &[0]);
+        assert!(c.to_quirk().is_err());
+
+        let mut c = Circuit::new(1);
+        c.rz(0, Param::symbol("theta"));
+        assert_eq!(c.to_quirk().unwrap_err().message, "unbound parameter theta");
+    }
+}
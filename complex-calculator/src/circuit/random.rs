@@ -0,0 +1,68 @@
+//! Random circuits for benchmarking and testing, drawn from a caller-supplied
+//! RNG so that a seeded generator always gives the same circuit.
+
+use std::f64::consts::PI;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use super::Circuit;
+
+impl Circuit {
+    /// A random circuit of `depth` layers on `num_qubits` qubits. Each layer
+    /// is a U3 gate with uniformly drawn angles on every qubit, followed by
+    /// CNOTs between the qubits paired up by a random permutation.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let a = Circuit::random(4, 3, &mut StdRng::seed_from_u64(1));
+    /// let b = Circuit::random(4, 3, &mut StdRng::seed_from_u64(1));
+    /// assert_eq!(a, b);
+    /// ```
+    pub fn random<R: Rng + ?Sized>(num_qubits: u32, depth: usize, rng: &mut R) -> Circuit {
+        let mut c = Circuit::new(num_qubits);
+        let mut qubits: Vec<usize> = (0..num_qubits as usize).collect();
+
+        for _ in 0..depth {
+            for q in 0..num_qubits as usize {
+                let theta = rng.random::<f64>() * PI;
+                let phi = rng.random::<f64>() * 2.0 * PI;
+                let lambda = rng.random::<f64>() * 2.0 * PI;
+                c.u3(q, theta, phi, lambda);
+            }
+
+            qubits.shuffle(rng);
+            for pair in qubits.chunks_exact(2) {
+                c.cnot(pair[0], pair[1]);
+            }
+        }
+
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn layers_have_every_qubit_and_disjoint_cnots() {
+        let c = Circuit::random(5, 4, &mut StdRng::seed_from_u64(3));
+        assert_eq!(c.gate_counts()["u3"], 20);
+        assert_eq!(c.gate_counts()["cx"], 8);
+        assert_eq!(c.depth(), 8);
+    }
+
+    #[test]
+    fn different_seeds_give_different_circuits() {
+        let a = Circuit::random(3, 2, &mut StdRng::seed_from_u64(1));
+        let b = Circuit::random(3, 2, &mut StdRng::seed_from_u64(2));
+        assert_ne!(a, b);
+    }
+}
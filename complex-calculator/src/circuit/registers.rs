@@ -0,0 +1,275 @@
+//! Named registers laid out one after another in the flat qubit and
+//! classical bit indices of a [`Circuit`], so that larger circuits can say
+//! `ancilla[0]` rather than `5`.
+
+use std::ops::Index;
+
+use super::Circuit;
+
+/// A named run of qubits, indexed from 0 within the register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuantumRegister {
+    name: String,
+    qubits: Vec<usize>,
+}
+
+/// A named run of classical bits, indexed from 0 within the register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassicalRegister {
+    name: String,
+    bits: Vec<usize>,
+}
+
+impl QuantumRegister {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.qubits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.qubits.is_empty()
+    }
+
+    /// The circuit qubits of the register, in order.
+    pub fn qubits(&self) -> &[usize] {
+        &self.qubits
+    }
+}
+
+impl ClassicalRegister {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// The circuit classical bits of the register, in order.
+    pub fn bits(&self) -> &[usize] {
+        &self.bits
+    }
+}
+
+impl Index<usize> for QuantumRegister {
+    type Output = usize;
+
+    fn index(&self, i: usize) -> &usize {
+        &self.qubits[i]
+    }
+}
+
+impl Index<usize> for ClassicalRegister {
+    type Output = usize;
+
+    fn index(&self, i: usize) -> &usize {
+        &self.bits[i]
+    }
+}
+
+/// Allocates registers in the order they are asked for, the first starting
+/// at qubit or bit 0.
+///
+/// ```
+/// use complex_calculator::circuit::Registers;
+///
+/// let mut regs = Registers::new();
+/// let data = regs.qreg("data", 3);
+/// let ancilla = regs.qreg("ancilla", 2);
+/// let out = regs.creg("out", 3);
+///
+/// let mut c = regs.circuit();
+/// c.cnot(data[0], ancilla[1]).measure_register(&data, &out);
+/// assert_eq!(c.num_qubits(), 5);
+/// assert_eq!(regs["ancilla"][1], 4);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Registers {
+    quantum: Vec<QuantumRegister>,
+    classical: Vec<ClassicalRegister>,
+    num_qubits: usize,
+    num_clbits: usize,
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Registers::default()
+    }
+
+    /// Allocates `size` qubits after those already allocated. Panics if the
+    /// name is taken.
+    pub fn qreg(&mut self, name: &str, size: usize) -> QuantumRegister {
+        assert!(
+            self.qreg_named(name).is_none(),
+            "quantum register {name} already exists"
+        );
+        let register = QuantumRegister {
+            name: name.to_string(),
+            qubits: (self.num_qubits..self.num_qubits + size).collect(),
+        };
+        self.num_qubits += size;
+        self.quantum.push(register.clone());
+        register
+    }
+
+    /// Allocates `size` classical bits after those already allocated. Panics
+    /// if the name is taken.
+    pub fn creg(&mut self, name: &str, size: usize) -> ClassicalRegister {
+        assert!(
+            self.creg_named(name).is_none(),
+            "classical register {name} already exists"
+        );
+        let register = ClassicalRegister {
+            name: name.to_string(),
+            bits: (self.num_clbits..self.num_clbits + size).collect(),
+        };
+        self.num_clbits += size;
+        self.classical.push(register.clone());
+        register
+    }
+
+    pub fn qreg_named(&self, name: &str) -> Option<&QuantumRegister> {
+        self.quantum.iter().find(|r| r.name == name)
+    }
+
+    pub fn creg_named(&self, name: &str) -> Option<&ClassicalRegister> {
+        self.classical.iter().find(|r| r.name == name)
+    }
+
+    pub fn quantum(&self) -> &[QuantumRegister] {
+        &self.quantum
+    }
+
+    pub fn classical(&self) -> &[ClassicalRegister] {
+        &self.classical
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+
+    pub fn num_clbits(&self) -> usize {
+        self.num_clbits
+    }
+
+    /// The register holding `qubit` and its index there.
+    pub fn locate(&self, qubit: usize) -> Option<(&QuantumRegister, usize)> {
+        self.quantum.iter().find_map(|r| {
+            let first = *r.qubits.first()?;
+            (first..first + r.len())
+                .contains(&qubit)
+                .then(|| (r, qubit - first))
+        })
+    }
+
+    /// An empty circuit over all the registers allocated so far.
+    pub fn circuit(&self) -> Circuit {
+        Circuit::with_clbits(self.num_qubits as u32, self.num_clbits)
+    }
+}
+
+/// The quantum register of the given name, panicking if there is none.
+impl Index<&str> for Registers {
+    type Output = QuantumRegister;
+
+    fn index(&self, name: &str) -> &QuantumRegister {
+        self.qreg_named(name)
+            .unwrap_or_else(|| panic!("no quantum register {name}"))
+    }
+}
+
+impl Circuit {
+    /// Measures each qubit of `qubits` into the bit of the same index in
+    /// `clbits`.
+    pub fn measure_register(
+        &mut self,
+        qubits: &QuantumRegister,
+        clbits: &ClassicalRegister,
+    ) -> &mut Self {
+        assert_eq!(
+            qubits.len(),
+            clbits.len(),
+            "measuring {} qubits into {} bits",
+            qubits.len(),
+            clbits.len()
+        );
+        for (&q, &c) in qubits.qubits.iter().zip(&clbits.bits) {
+            self.measure(q, c);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Registers;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn registers_are_laid_out_in_order() {
+        let mut regs = Registers::new();
+        let a = regs.qreg("a", 2);
+        let b = regs.qreg("b", 3);
+        let c = regs.creg("c", 1);
+        let d = regs.creg("d", 2);
+
+        assert_eq!(a.qubits(), [0, 1]);
+        assert_eq!(b.qubits(), [2, 3, 4]);
+        assert_eq!(c.bits(), [0]);
+        assert_eq!(d[1], 2);
+        assert_eq!(regs["b"][0], 2);
+        assert_eq!((regs.num_qubits(), regs.num_clbits()), (5, 3));
+
+        let (register, i) = regs.locate(3).unwrap();
+        assert_eq!((register.name(), i), ("b", 1));
+        assert!(regs.locate(5).is_none());
+    }
+
+    #[test]
+    fn measure_register_into_named_bits() {
+        let mut regs = Registers::new();
+        let ancilla = regs.qreg("ancilla", 1);
+        let data = regs.qreg("data", 2);
+        let _ = regs.creg("flag", 1);
+        let out = regs.creg("out", 2);
+
+        let mut c = regs.circuit();
+        c.x(data[1]).cnot(data[1], ancilla[0]);
+        c.measure_register(&data, &out);
+
+        let run = c.run(&mut StdRng::seed_from_u64(0));
+        assert_eq!(run.clbits(), [false, false, true]);
+    }
+
+    #[test]
+    #[should_panic(expected = "quantum register q already exists")]
+    fn names_are_unique() {
+        let mut regs = Registers::new();
+        regs.qreg("q", 1);
+        regs.qreg("q", 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "no quantum register missing")]
+    fn unknown_names_panic() {
+        let _ = Registers::new()["missing"][0];
+    }
+
+    #[test]
+    #[should_panic(expected = "measuring 2 qubits into 1 bits")]
+    fn measure_register_sizes_must_match() {
+        let mut regs = Registers::new();
+        let q = regs.qreg("q", 2);
+        let c = regs.creg("c", 1);
+        regs.circuit().measure_register(&q, &c);
+    }
+}
@@ -0,0 +1,319 @@
+//! Routing circuits onto hardware where only some pairs of qubits can
+//! interact. SWAPs are inserted greedily, moving one qubit of each
+//! two-qubit gate along a shortest path towards the other, so that the
+//! qubits of the circuit wander over the physical ones as it runs.
+
+use std::collections::VecDeque;
+
+use super::{Circuit, Gate, Instruction};
+
+/// Which pairs of physical qubits can take a two-qubit gate, in either
+/// direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CouplingMap {
+    neighbours: Vec<Vec<usize>>,
+}
+
+impl CouplingMap {
+    /// Qubits `num_qubits` and the given pairs between them. Panics on pairs
+    /// out of range.
+    pub fn new(num_qubits: usize, edges: &[(usize, usize)]) -> Self {
+        let mut neighbours = vec![vec![]; num_qubits];
+        for &(a, b) in edges {
+            assert!(
+                a < num_qubits && b < num_qubits,
+                "edge ({a}, {b}) out of range for {num_qubits} qubits"
+            );
+            if a != b && !neighbours[a].contains(&b) {
+                neighbours[a].push(b);
+                neighbours[b].push(a);
+            }
+        }
+        CouplingMap { neighbours }
+    }
+
+    /// Qubits 0 to n − 1 in a line.
+    pub fn line(n: usize) -> Self {
+        let edges: Vec<_> = (1..n).map(|q| (q - 1, q)).collect();
+        CouplingMap::new(n, &edges)
+    }
+
+    /// Qubits 0 to n − 1 in a line, with n − 1 joined back to 0.
+    pub fn ring(n: usize) -> Self {
+        let mut edges: Vec<_> = (1..n).map(|q| (q - 1, q)).collect();
+        if n > 2 {
+            edges.push((n - 1, 0));
+        }
+        CouplingMap::new(n, &edges)
+    }
+
+    /// A `rows` by `columns` grid, numbered row by row.
+    pub fn grid(rows: usize, columns: usize) -> Self {
+        let mut edges = vec![];
+        for r in 0..rows {
+            for c in 0..columns {
+                let q = r * columns + c;
+                if c + 1 < columns {
+                    edges.push((q, q + 1));
+                }
+                if r + 1 < rows {
+                    edges.push((q, q + columns));
+                }
+            }
+        }
+        CouplingMap::new(rows * columns, &edges)
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.neighbours.len()
+    }
+
+    pub fn is_adjacent(&self, a: usize, b: usize) -> bool {
+        self.neighbours[a].contains(&b)
+    }
+
+    /// A shortest path from `from` to `to`, both included, if they are
+    /// connected at all.
+    pub fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut previous = vec![None; self.num_qubits()];
+        let mut queue = VecDeque::from([from]);
+        previous[from] = Some(from);
+        while let Some(q) = queue.pop_front() {
+            if q == to {
+                let mut path = vec![to];
+                while *path.last().unwrap() != from {
+                    path.push(previous[*path.last().unwrap()].unwrap());
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &next in &self.neighbours[q] {
+                if previous[next].is_none() {
+                    previous[next] = Some(q);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A circuit routed onto a [`CouplingMap`], with what it cost.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Routing {
+    /// The routed circuit, on the physical qubits.
+    pub circuit: Circuit,
+    /// The physical qubit holding each qubit at the end, those of the
+    /// original circuit first and the spare physical qubits after. Each
+    /// qubit starts on the physical qubit of the same index.
+    pub final_layout: Vec<usize>,
+    /// Number of SWAPs inserted.
+    pub swaps: usize,
+}
+
+impl Routing {
+    /// The extra CNOTs the SWAPs take, three each.
+    pub fn cnot_overhead(&self) -> usize {
+        3 * self.swaps
+    }
+}
+
+impl Circuit {
+    /// The circuit on the physical qubits of `coupling`, with SWAPs inserted
+    /// so that every two-qubit gate, conditional ones included, acts on
+    /// adjacent qubits. Measurements keep their classical bits, so runs give
+    /// the same results. Panics on gates on more than two qubits, which
+    /// should be decomposed first, on loops, on too few physical qubits and
+    /// on gates between unconnected ones.
+    pub fn route(&self, coupling: &CouplingMap) -> Routing {
+        let physical = coupling.num_qubits();
+        assert!(
+            self.num_qubits as usize <= physical,
+            "routing {} qubits onto {physical}",
+            self.num_qubits
+        );
+
+        // layout[i] holds the physical qubit of qubit i, and holder the
+        // inverse
+        let mut layout: Vec<usize> = (0..physical).collect();
+        let mut holder = layout.clone();
+        let mut c = Circuit::with_clbits(physical as u32, self.num_clbits);
+        c.add_global_phase(self.global_phase);
+        let mut swaps = 0;
+
+        for instruction in &self.instructions {
+            let (Instruction::Gate { qubits, .. } | Instruction::Conditional { qubits, .. }) =
+                instruction
+            else {
+                c.instructions.push(relabel(instruction, &layout));
+                continue;
+            };
+            match qubits.len() {
+                1 => {}
+                2 => {
+                    let (a, b) = (layout[qubits[0]], layout[qubits[1]]);
+                    let path = coupling
+                        .shortest_path(a, b)
+                        .unwrap_or_else(|| panic!("qubits {a} and {b} are not connected"));
+                    // move the first qubit along until it is next to the
+                    // second
+                    for step in path.windows(2).take(path.len() - 2) {
+                        let (p, q) = (step[0], step[1]);
+                        c.append(Gate::Swap, &[p, q]);
+                        holder.swap(p, q);
+                        layout[holder[p]] = p;
+                        layout[holder[q]] = q;
+                        swaps += 1;
+                    }
+                }
+                n => panic!("cannot route a gate on {n} qubits, decompose it first"),
+            }
+            c.instructions.push(relabel(instruction, &layout));
+        }
+
+        Routing {
+            circuit: c,
+            final_layout: layout,
+            swaps,
+        }
+    }
+}
+
+/// `instruction` with each qubit i replaced by `layout[i]`.
+fn relabel(instruction: &Instruction, layout: &[usize]) -> Instruction {
+    let map = |qubits: &[usize]| -> Vec<usize> { qubits.iter().map(|&q| layout[q]).collect() };
+    match instruction.clone() {
+        Instruction::Gate { gate, qubits } => Instruction::Gate {
+            gate,
+            qubits: map(&qubits),
+        },
+        Instruction::Measure { qubit, clbit } => Instruction::Measure {
+            qubit: layout[qubit],
+            clbit,
+        },
+        Instruction::Reset { qubit } => Instruction::Reset {
+            qubit: layout[qubit],
+        },
+        Instruction::Barrier { qubits } => Instruction::Barrier {
+            qubits: map(&qubits),
+        },
+        Instruction::Conditional {
+            clbit,
+            gate,
+            qubits,
+        } => Instruction::Conditional {
+            clbit,
+            gate,
+            qubits: map(&qubits),
+        },
+        Instruction::Channel { channel, qubits } => Instruction::Channel {
+            channel,
+            qubits: map(&qubits),
+        },
+        Instruction::Snapshot { label } => Instruction::Snapshot { label },
+        Instruction::While { .. } => panic!("loops cannot be routed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CouplingMap;
+    use crate::circuit::{Circuit, Gate, Instruction};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn adjacent_only(c: &Circuit, coupling: &CouplingMap) -> bool {
+        c.instructions()
+            .iter()
+            .all(|instruction| match instruction {
+                Instruction::Gate { qubits, .. } | Instruction::Conditional { qubits, .. } => {
+                    qubits.len() < 2 || coupling.is_adjacent(qubits[0], qubits[1])
+                }
+                _ => true,
+            })
+    }
+
+    #[test]
+    fn coupling_maps() {
+        let line = CouplingMap::line(4);
+        assert!(line.is_adjacent(2, 1));
+        assert!(!line.is_adjacent(0, 3));
+        assert_eq!(line.shortest_path(3, 0), Some(vec![3, 2, 1, 0]));
+
+        assert!(CouplingMap::ring(4).is_adjacent(0, 3));
+        assert_eq!(
+            CouplingMap::grid(2, 3).shortest_path(0, 5).map(|p| p.len()),
+            Some(4)
+        );
+        assert_eq!(CouplingMap::new(3, &[(0, 1)]).shortest_path(0, 2), None);
+    }
+
+    #[test]
+    fn swaps_bring_qubits_together() {
+        let mut c = Circuit::new(4);
+        c.h(0).cnot(0, 3).cnot(0, 2);
+
+        let line = CouplingMap::line(4);
+        let routing = c.route(&line);
+        assert_eq!(routing.swaps, 2);
+        assert_eq!(routing.cnot_overhead(), 6);
+        assert_eq!(routing.final_layout, [2, 0, 1, 3]);
+        assert!(adjacent_only(&routing.circuit, &line));
+
+        // round the ring, only the second CNOT needs a SWAP
+        assert_eq!(c.route(&CouplingMap::ring(4)).swaps, 1);
+    }
+
+    #[test]
+    fn routed_circuits_are_equivalent_up_to_the_layout() {
+        let c = Circuit::random(5, 4, &mut StdRng::seed_from_u64(7));
+        for coupling in [CouplingMap::line(5), CouplingMap::ring(5)] {
+            let routing = c.route(&coupling);
+            assert!(adjacent_only(&routing.circuit, &coupling));
+
+            let expected = c.simulate().permute_qubits(&routing.final_layout);
+            let (a, b) = (
+                routing.circuit.simulate().amplitudes(5),
+                expected.amplitudes(5),
+            );
+            for (x, y) in a.iter().zip(&b) {
+                assert!((*x - *y).modulus() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn measurements_keep_their_bits() {
+        let mut c = Circuit::new(3);
+        c.x(0)
+            .cnot(0, 2)
+            .measure(0, 0)
+            .measure(2, 1)
+            .c_if(1, Gate::X, &[1])
+            .measure(1, 2);
+
+        let routing = c.route(&CouplingMap::grid(2, 2));
+        assert_eq!(routing.circuit.num_qubits(), 4);
+        let run = routing.circuit.run(&mut StdRng::seed_from_u64(0));
+        assert_eq!(run.clbits(), [true, true, true]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot route a gate on 3 qubits, decompose it first")]
+    fn toffolis_are_decomposed_first() {
+        let mut c = Circuit::new(3);
+        c.ccx(0, 1, 2);
+        c.route(&CouplingMap::line(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "qubits 0 and 2 are not connected")]
+    fn disconnected_qubits_cannot_meet() {
+        let mut c = Circuit::new(3);
+        c.cnot(0, 2);
+        c.route(&CouplingMap::new(3, &[(0, 1)]));
+    }
+}
@@ -0,0 +1,354 @@
+//! The Solovay–Kitaev algorithm, approximating any single-qubit unitary by a
+//! product of H and T gates. A net of every short product is searched for
+//! the nearest one, and its error is then corrected recursively with group
+//! commutators of coarser approximations, after Dawson and Nielsen,
+//! "The Solovay-Kitaev algorithm" (2005).
+
+use std::collections::HashSet;
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+use std::sync::OnceLock;
+
+use super::synthesis::{normalized, single_qubit_matrix};
+use super::{Circuit, Gate};
+use crate::ket::Operator;
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// An element of SU(2) as a unit quaternion (w, x, y, z), standing for
+/// w I − i(x X + y Y + z Z). A rotation by θ about the unit axis n has
+/// w = cos(θ/2) and (x, y, z) = sin(θ/2) n.
+type Quaternion = [f64; 4];
+
+const IDENTITY: Quaternion = [1.0, 0.0, 0.0, 0.0];
+
+/// A product of H and T, in time order.
+type Word = Vec<Gate>;
+
+/// Products of H and T approximating single-qubit unitaries, built from a
+/// net of all the distinct products up to some length.
+#[derive(Debug, Clone)]
+pub struct SolovayKitaev {
+    net: Vec<(Quaternion, Word)>,
+}
+
+impl SolovayKitaev {
+    /// A net of every distinct product of at most `max_length` H and T gates,
+    /// up to global phase. The net grows exponentially with the length:
+    /// about 12 000 products for a length of 24.
+    pub fn new(max_length: usize) -> Self {
+        let h = [0.0, FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2];
+        let t = [(PI / 8.0).cos(), 0.0, 0.0, (PI / 8.0).sin()];
+
+        let mut seen = HashSet::from([key(&IDENTITY)]);
+        let mut net = vec![(IDENTITY, vec![])];
+        let mut frontier = 0..1;
+        for _ in 0..max_length {
+            let start = net.len();
+            for i in frontier {
+                for (gate, g) in [(Gate::H, &h), (Gate::T, &t)] {
+                    let q = multiply(g, &net[i].0);
+                    if seen.insert(key(&q)) {
+                        let mut word = net[i].1.clone();
+                        word.push(gate.clone());
+                        net.push((q, word));
+                    }
+                }
+            }
+            frontier = start..net.len();
+        }
+
+        SolovayKitaev { net }
+    }
+
+    /// Number of products in the net.
+    pub fn len(&self) -> usize {
+        self.net.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.net.is_empty()
+    }
+
+    /// A circuit of H and T gates approximating the single-qubit unitary
+    /// `u`, with `depth` rounds of correction after the nearest product in
+    /// the net. Each round multiplies the length by about five and takes the
+    /// error ε to about ε^(3/2). The global phase of the circuit matches
+    /// that of `u`.
+    pub fn approximate(&self, u: &Operator<C>, depth: usize) -> Circuit {
+        assert_eq!(
+            u.num_qubits(),
+            1,
+            "only single-qubit unitaries are approximated"
+        );
+        let (word, _) = self.search(&quaternion(u), depth);
+
+        let mut c = Circuit::new(1);
+        for gate in word {
+            c.append(gate, &[0]);
+        }
+        let overlap = (c.unitary().adjoint() * u.clone()).trace();
+        c.add_global_phase(overlap.arg());
+        c
+    }
+
+    fn search(&self, u: &Quaternion, depth: usize) -> (Word, Quaternion) {
+        if depth == 0 {
+            let (q, word) = self
+                .net
+                .iter()
+                .min_by(|(a, _), (b, _)| distance(a, u).total_cmp(&distance(b, u)))
+                .unwrap();
+            return (word.clone(), *q);
+        }
+
+        let (previous, approximation) = self.search(u, depth - 1);
+        let (v, w) = balanced_commutator(&multiply(u, &conjugate(&approximation)));
+        let (v_word, v) = self.search(&v, depth - 1);
+        let (w_word, w) = self.search(&w, depth - 1);
+
+        // V W V† W† times the previous approximation, in time order
+        let mut word = vec![];
+        for gate in previous
+            .iter()
+            .chain(&inverse(&w_word))
+            .chain(&inverse(&v_word))
+            .chain(&w_word)
+            .chain(&v_word)
+        {
+            push(&mut word, gate);
+        }
+        let commutator = multiply(&multiply(&v, &w), &multiply(&conjugate(&v), &conjugate(&w)));
+        (word, multiply(&commutator, &approximation))
+    }
+}
+
+impl Circuit {
+    /// A circuit of H and T gates within `epsilon` of the single-qubit
+    /// unitary `u` in operator norm, global phase included, found with
+    /// [`SolovayKitaev`] over a net of products of up to 20 gates.
+    /// Panics if four rounds of correction do not reach `epsilon`.
+    pub fn approximate(u: &Operator<C>, epsilon: f64) -> Circuit {
+        static NET: OnceLock<SolovayKitaev> = OnceLock::new();
+        let sk = NET.get_or_init(|| SolovayKitaev::new(20));
+
+        let target = quaternion(u);
+        for depth in 0..=4 {
+            let (_, q) = sk.search(&target, depth);
+            if distance(&q, &target) <= epsilon {
+                return sk.approximate(u, depth);
+            }
+        }
+        panic!("no approximation within {epsilon} after 4 rounds")
+    }
+}
+
+/// `u` scaled into SU(2).
+fn quaternion(u: &Operator<C>) -> Quaternion {
+    let [[a, _], [b, _]] = normalized(&single_qubit_matrix(u));
+    [a.re, -b.im, b.re, -a.im]
+}
+
+fn multiply(p: &Quaternion, q: &Quaternion) -> Quaternion {
+    let [a, b, c, d] = *p;
+    let [e, f, g, h] = *q;
+    [
+        a * e - b * f - c * g - d * h,
+        a * f + b * e + c * h - d * g,
+        a * g - b * h + c * e + d * f,
+        a * h + b * g - c * f + d * e,
+    ]
+}
+
+fn conjugate(q: &Quaternion) -> Quaternion {
+    [q[0], -q[1], -q[2], -q[3]]
+}
+
+/// Distance in operator norm between the unitaries, minimised over their
+/// global phase: √(2 − |tr(U†V)|).
+fn distance(p: &Quaternion, q: &Quaternion) -> f64 {
+    let dot: f64 = p.iter().zip(q).map(|(a, b)| a * b).sum();
+    (2.0 - 2.0 * dot.abs()).max(0.0).sqrt()
+}
+
+/// `q` up to sign, rounded, to tell products apart by.
+fn key(q: &Quaternion) -> [i64; 4] {
+    let sign = match q.iter().find(|x| x.abs() > 1e-9) {
+        Some(x) if *x < 0.0 => -1.0,
+        _ => 1.0,
+    };
+    q.map(|x| (sign * x * 1e8).round() as i64)
+}
+
+/// Rotation by `angle` about the unit `axis`.
+fn rotation(axis: [f64; 3], angle: f64) -> Quaternion {
+    let (sin, cos) = (angle / 2.0).sin_cos();
+    [cos, sin * axis[0], sin * axis[1], sin * axis[2]]
+}
+
+/// The axis and angle in [0, π] of a rotation, with the X axis for the
+/// identity.
+fn axis_angle(q: &Quaternion) -> ([f64; 3], f64) {
+    let q = if q[0] < 0.0 { q.map(|x| -x) } else { *q };
+    let sin = (q[1] * q[1] + q[2] * q[2] + q[3] * q[3]).sqrt();
+    if sin < 1e-15 {
+        return ([1.0, 0.0, 0.0], 0.0);
+    }
+    ([q[1] / sin, q[2] / sin, q[3] / sin], 2.0 * sin.atan2(q[0]))
+}
+
+/// V and W with V W V† W† = `delta`, rotations by the same small angle, so
+/// that their approximations' errors mostly cancel in the commutator.
+fn balanced_commutator(delta: &Quaternion) -> (Quaternion, Quaternion) {
+    let (axis, theta) = axis_angle(delta);
+
+    // rotations by φ about X and Y commute to a rotation by θ when
+    // sin(θ/2) = 2 sin²(φ/2) √(1 − sin⁴(φ/2))
+    let sin_squared = ((1.0 - (theta / 2.0).cos()) / 2.0).sqrt();
+    let phi = 2.0 * sin_squared.sqrt().asin();
+    let v = rotation([1.0, 0.0, 0.0], phi);
+    let w = rotation([0.0, 1.0, 0.0], phi);
+
+    // turn the axis of the commutator onto that of delta
+    let commutator = multiply(&multiply(&v, &w), &multiply(&conjugate(&v), &conjugate(&w)));
+    let (from, _) = axis_angle(&commutator);
+    let cross = [
+        from[1] * axis[2] - from[2] * axis[1],
+        from[2] * axis[0] - from[0] * axis[2],
+        from[0] * axis[1] - from[1] * axis[0],
+    ];
+    let dot: f64 = from.iter().zip(&axis).map(|(a, b)| a * b).sum();
+    let norm = cross.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let s = if norm < 1e-12 {
+        // parallel, or opposite, when a half turn about any normal will do
+        if dot > 0.0 {
+            IDENTITY
+        } else {
+            let normal = if from[0].abs() < 0.9 {
+                [0.0, -from[2], from[1]]
+            } else {
+                [-from[1], from[0], 0.0]
+            };
+            let length = normal.iter().map(|x| x * x).sum::<f64>().sqrt();
+            rotation(normal.map(|x| x / length), PI)
+        }
+    } else {
+        rotation(cross.map(|x| x / norm), norm.atan2(dot))
+    };
+
+    let turned = |q: &Quaternion| multiply(&multiply(&s, q), &conjugate(&s));
+    (turned(&v), turned(&w))
+}
+
+/// The word undoing `word`, with T† as seven T gates.
+fn inverse(word: &[Gate]) -> Word {
+    let mut inverse = vec![];
+    for gate in word.iter().rev() {
+        let copies = if *gate == Gate::T { 7 } else { 1 };
+        for _ in 0..copies {
+            push(&mut inverse, gate);
+        }
+    }
+    inverse
+}
+
+/// Appends `gate` to `word`, cancelling H H and T⁸.
+fn push(word: &mut Word, gate: &Gate) {
+    if *gate == Gate::H && word.last() == Some(&Gate::H) {
+        word.pop();
+        return;
+    }
+    word.push(gate.clone());
+    if word.len() >= 8 && word[word.len() - 8..].iter().all(|g| *g == Gate::T) {
+        word.truncate(word.len() - 8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{distance, quaternion, SolovayKitaev};
+    use crate::circuit::synthesis::single_qubit_matrix;
+    use crate::circuit::{Circuit, Gate};
+    use crate::ket::Operator;
+    use crate::Complex as C;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Frobenius distance, global phase included, at most √2 times that in
+    /// operator norm.
+    fn error(a: &Operator<C<f64>>, b: &Operator<C<f64>>) -> f64 {
+        let (a, b) = (single_qubit_matrix(a), single_qubit_matrix(b));
+        let mut squares = 0.0;
+        for x in 0..2 {
+            for y in 0..2 {
+                squares += (a[x][y] - b[x][y]).modulus().powi(2);
+            }
+        }
+        squares.sqrt()
+    }
+
+    fn only_h_and_t(c: &Circuit) -> bool {
+        c.gate_counts()
+            .keys()
+            .all(|name| *name == "h" || *name == "t")
+    }
+
+    #[test]
+    fn net_holds_distinct_products() {
+        let sk = SolovayKitaev::new(10);
+        for (i, (p, _)) in sk.net.iter().enumerate().skip(1).step_by(37) {
+            for (q, _) in &sk.net[..i] {
+                assert!(distance(p, q) > 1e-6);
+            }
+        }
+        // the identity, H and T
+        assert_eq!(SolovayKitaev::new(1).len(), 3);
+    }
+
+    #[test]
+    fn products_in_the_net_are_found_exactly() {
+        let mut c = Circuit::new(1);
+        c.h(0).t(0).h(0).t(0).t(0).h(0);
+        let u = c.unitary();
+
+        let found = SolovayKitaev::new(8).approximate(&u, 0);
+        assert!(only_h_and_t(&found));
+        assert!(error(&found.unitary(), &u) < 1e-9);
+    }
+
+    #[test]
+    fn rounds_of_correction_shrink_the_error() {
+        let sk = SolovayKitaev::new(12);
+        let u = Operator::rz(0.3) * Operator::rx(1.1);
+
+        let errors: Vec<f64> = (0..4)
+            .map(|depth| {
+                let c = sk.approximate(&u, depth);
+                assert!(only_h_and_t(&c));
+                error(&c.unitary(), &u)
+            })
+            .collect();
+        assert!(errors[3] < errors[0] / 4.0, "{errors:?}");
+        assert!(errors[3] < 0.02, "{errors:?}");
+    }
+
+    #[test]
+    fn approximate_to_epsilon() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..3 {
+            let u = Circuit::random(1, 4, &mut rng).unitary();
+            let c = Circuit::approximate(&u, 1e-2);
+            assert!(only_h_and_t(&c));
+            assert!(distance(&quaternion(&c.unitary()), &quaternion(&u)) <= 1e-2);
+            assert!(error(&c.unitary(), &u) < 2e-2);
+        }
+
+        let mut t = Circuit::new(1);
+        t.t(0);
+        assert_eq!(
+            Circuit::approximate(&t.unitary(), 1e-9).gate_counts()[Gate::T.name()],
+            1
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+
+use num::Zero;
+
+use super::statevector::gate_matrix;
+use super::{definite, Circuit, Gate, Instruction, Run, Snapshots};
+use crate::ket::{Ket, State, MAX_QUBITS};
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// Amplitudes smaller than this in modulus are dropped after each gate.
+const CUTOFF: f64 = 1e-12;
+
+/// An n-qubit state keeping only its nonzero amplitudes, in a hash map from
+/// basis state to amplitude. Memory and time grow with the number of basis
+/// states in the superposition rather than with 2^n, so circuits that
+/// permute basis states, or branch into only a few of them, can be
+/// simulated on up to 64 qubits.
+///
+/// X, CNOT, Toffoli, multi-controlled X and SWAP only relabel basis states,
+/// and diagonal gates only change phases, so neither grows the support.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseState {
+    n: u32,
+    amplitudes: HashMap<u64, C>,
+}
+
+impl SparseState {
+    /// The basis state |0…0⟩.
+    pub fn new(n: u32) -> Self {
+        SparseState::basis(0, n)
+    }
+
+    /// The basis state |x⟩ on n qubits.
+    pub fn basis(x: u64, n: u32) -> Self {
+        assert!(
+            n <= MAX_QUBITS,
+            "a sparse state holds at most {MAX_QUBITS} qubits, not {n}"
+        );
+        assert!(
+            n == MAX_QUBITS || x >> n == 0,
+            "basis state {x} out of range for {n} qubits"
+        );
+        SparseState {
+            n,
+            amplitudes: HashMap::from([(x, C::new(1.0, 0.0))]),
+        }
+    }
+
+    pub fn num_qubits(&self) -> u32 {
+        self.n
+    }
+
+    /// Number of basis states with a nonzero amplitude.
+    pub fn support_size(&self) -> usize {
+        self.amplitudes.len()
+    }
+
+    /// The amplitude ⟨x|ψ⟩.
+    pub fn amplitude(&self, x: u64) -> C {
+        self.amplitudes.get(&x).copied().unwrap_or_else(C::zero)
+    }
+
+    /// Multiplies every amplitude by e^(iφ).
+    pub fn apply_global_phase(&mut self, phi: f64) {
+        let phase = C::new(phi.cos(), phi.sin());
+        for a in self.amplitudes.values_mut() {
+            *a = *a * phase;
+        }
+    }
+
+    /// Applies `gate` on `qubits`. Permutations of basis states and diagonal
+    /// gates act on each amplitude in place; other gates mix the amplitudes
+    /// of the basis states that differ only on `qubits`.
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        let mask = |q: &[usize]| q.iter().fold(0u64, |m, &q| m | 1 << q);
+        match *gate {
+            Gate::X | Gate::Cx | Gate::Ccx | Gate::Mcx(_) => {
+                let (&target, controls) = qubits.split_last().unwrap();
+                let controls = mask(controls);
+                self.permute(|x| {
+                    if x & controls == controls {
+                        x ^ 1 << target
+                    } else {
+                        x
+                    }
+                });
+            }
+            Gate::Swap => {
+                let (a, b) = (qubits[0], qubits[1]);
+                self.permute(|x| {
+                    if (x >> a ^ x >> b) & 1 == 1 {
+                        x ^ (1 << a | 1 << b)
+                    } else {
+                        x
+                    }
+                });
+            }
+            _ => self.apply_matrix(&gate_matrix(gate), qubits),
+        }
+    }
+
+    fn permute(&mut self, f: impl Fn(u64) -> u64) {
+        self.amplitudes = self.amplitudes.drain().map(|(x, a)| (f(x), a)).collect();
+    }
+
+    /// Multiplies the 2^k × 2^k `matrix` into `qubits`, the first qubit on
+    /// `qubits[0]`.
+    fn apply_matrix(&mut self, matrix: &[C], qubits: &[usize]) {
+        let size = 1 << qubits.len();
+        let local = |x: u64| -> usize {
+            qubits
+                .iter()
+                .enumerate()
+                .map(|(i, &q)| ((x >> q & 1) as usize) << i)
+                .sum()
+        };
+        let spread = |l: usize| -> u64 {
+            qubits
+                .iter()
+                .enumerate()
+                .map(|(i, &q)| ((l >> i & 1) as u64) << q)
+                .sum()
+        };
+
+        let diagonal = (0..size * size).all(|i| i % (size + 1) == 0 || matrix[i].is_zero());
+        if diagonal {
+            for (&x, a) in &mut self.amplitudes {
+                let l = local(x);
+                *a = *a * matrix[l * size + l];
+            }
+            self.prune();
+            return;
+        }
+
+        let targets = spread(size - 1);
+        let mut out: HashMap<u64, C> = HashMap::with_capacity(self.amplitudes.len());
+        for (&x, &a) in &self.amplitudes {
+            let (base, column) = (x & !targets, local(x));
+            for row in 0..size {
+                let m = matrix[row * size + column];
+                if !m.is_zero() {
+                    *out.entry(base | spread(row)).or_insert_with(C::zero) += m * a;
+                }
+            }
+        }
+        self.amplitudes = out;
+        self.prune();
+    }
+
+    fn prune(&mut self) {
+        self.amplitudes.retain(|_, a| a.modulus() >= CUTOFF);
+    }
+
+    /// Probability of reading 1 from `qubit`.
+    pub fn probability_of_one(&self, qubit: usize) -> f64 {
+        self.amplitudes
+            .iter()
+            .filter(|(x, _)| *x >> qubit & 1 == 1)
+            .map(|(_, a)| a.mod_squared())
+            .sum()
+    }
+
+    /// Measures `qubit` in the computational basis, collapsing the state onto
+    /// the outcome, which is returned as a bit.
+    pub fn measure<R: rand::Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let p1 = self.probability_of_one(qubit);
+        let outcome = rng.random::<f64>() < p1;
+        let p = if outcome { p1 } else { 1.0 - p1 };
+
+        let scale = C::new(1.0 / p.sqrt(), 0.0);
+        self.amplitudes
+            .retain(|x, _| (x >> qubit & 1 == 1) == outcome);
+        for a in self.amplitudes.values_mut() {
+            *a = *a * scale;
+        }
+        outcome
+    }
+
+    /// The state as a [`State`], its basis states in increasing order.
+    pub fn to_state(&self) -> State<C> {
+        let mut superpositions: Vec<Ket<C>> = self
+            .amplitudes
+            .iter()
+            .map(|(&ket, &scalar)| Ket {
+                scalar,
+                ket,
+                n: self.n,
+                d: 2,
+            })
+            .collect();
+        superpositions.sort_by_key(|k| k.ket);
+        State {
+            scalar: C::new(1.0, 0.0),
+            superpositions,
+        }
+    }
+}
+
+impl Circuit {
+    /// Runs the circuit on |0…0⟩ as a [`SparseState`]. Like
+    /// [`Circuit::simulate`], measurements and channels are not supported,
+    /// and resets only on qubits sure to read 0 or 1.
+    pub fn simulate_sparse(&self) -> SparseState {
+        let mut state = SparseState::new(self.num_qubits);
+        let mut clbits = vec![false; self.num_clbits];
+        self.execute_sparse(
+            &mut state,
+            &mut clbits,
+            &mut vec![],
+            &mut None::<&mut dyn rand::RngCore>,
+        );
+        state
+    }
+
+    /// [`Circuit::run`] on a [`SparseState`], sampling measurement outcomes
+    /// from `rng` and recording snapshots. Channels are not supported.
+    pub fn run_sparse<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Run {
+        let mut state = SparseState::new(self.num_qubits);
+        let mut clbits = vec![false; self.num_clbits];
+        let mut snapshots = vec![];
+        self.execute_sparse(&mut state, &mut clbits, &mut snapshots, &mut Some(rng));
+        Run {
+            state: state.to_state(),
+            clbits,
+            snapshots,
+        }
+    }
+
+    fn execute_sparse<R: rand::Rng + ?Sized>(
+        &self,
+        state: &mut SparseState,
+        clbits: &mut [bool],
+        snapshots: &mut Snapshots,
+        rng: &mut Option<&mut R>,
+    ) {
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => state.apply(gate, qubits),
+                Instruction::Measure { qubit, clbit } => {
+                    let rng = rng.as_deref_mut().unwrap_or_else(|| {
+                        panic!("simulate_sparse() runs circuits without measurements, use run_sparse()")
+                    });
+                    clbits[*clbit] = state.measure(*qubit, rng);
+                }
+                Instruction::Reset { qubit } => {
+                    let one = match rng.as_deref_mut() {
+                        Some(rng) => state.measure(*qubit, rng),
+                        None => definite(state.probability_of_one(*qubit)).unwrap_or_else(|| {
+                            panic!("simulate_sparse() resets only qubits in a definite state, use run_sparse()")
+                        }),
+                    };
+                    if one {
+                        state.apply(&Gate::X, &[*qubit]);
+                    }
+                }
+                Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => {
+                    if clbits[*clbit] {
+                        state.apply(gate, qubits);
+                    }
+                }
+                Instruction::While {
+                    clbit,
+                    value,
+                    max_iterations,
+                    body,
+                } => {
+                    for _ in 0..*max_iterations {
+                        if clbits[*clbit] != *value {
+                            break;
+                        }
+                        body.execute_sparse(state, clbits, snapshots, rng);
+                    }
+                }
+                Instruction::Channel { .. } => {
+                    panic!("the sparse simulator does not sample channels")
+                }
+                Instruction::Snapshot { label } => {
+                    snapshots.push((label.clone(), state.to_state()))
+                }
+                Instruction::Barrier { .. } => {}
+            }
+        }
+        state.apply_global_phase(self.global_phase);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SparseState;
+    use crate::circuit::{Circuit, Gate};
+    use crate::Complex;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn agrees_with_the_statevector() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..5 {
+            let c = Circuit::random(5, 6, &mut rng);
+            let (sparse, dense) = (c.simulate_sparse().to_state(), c.simulate());
+            for (x, y) in sparse.amplitudes(5).iter().zip(&dense.amplitudes(5)) {
+                assert!((*x - *y).modulus() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn permutations_on_many_qubits() {
+        let mut c = Circuit::new(64);
+        c.x(0)
+            .x(59)
+            .swap(0, 45)
+            .cnot(45, 50)
+            .ccx(45, 50, 10)
+            .append(Gate::Mcx(4), &[59, 45, 50, 10, 63])
+            .append(Gate::Mcx(2), &[0, 59, 1]);
+
+        let state = c.simulate_sparse();
+        assert_eq!(state.support_size(), 1);
+        let x = 1 << 10 | 1 << 45 | 1 << 50 | 1 << 59 | 1 << 63;
+        assert!((state.amplitude(x).re - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn few_branches_on_many_qubits() {
+        // a GHZ state on 50 qubits has two basis states
+        let mut c = Circuit::new(50);
+        c.h(0);
+        for q in 1..50 {
+            c.cnot(0, q);
+        }
+        c.t(0).rz(49, 0.3);
+        let state = c.simulate_sparse();
+        assert_eq!(state.support_size(), 2);
+        assert!((state.amplitude(0).modulus() - 0.5f64.sqrt()).abs() < EPSILON);
+        assert!((state.probability_of_one(17) - 0.5).abs() < EPSILON);
+
+        // interference brings the support back down
+        let mut c = Circuit::new(50);
+        c.h(7).s(7).sdg(7).h(7);
+        assert_eq!(c.simulate_sparse().support_size(), 1);
+    }
+
+    #[test]
+    fn measurements_collapse() {
+        let mut c = Circuit::new(40);
+        c.h(0).cnot(0, 39).measure(39, 0).c_if(0, Gate::X, &[20]);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..10 {
+            let run = c.run_sparse(&mut rng);
+            let expected = if run.clbits()[0] {
+                1 | 1 << 20 | 1 << 39
+            } else {
+                0
+            };
+            let kets: Vec<u64> = run.state().terms().map(|(_, ket, _)| ket).collect();
+            assert_eq!(kets, [expected]);
+        }
+    }
+
+    #[test]
+    fn basis_states() {
+        let state = SparseState::basis(0b101, 3);
+        assert_eq!(state.amplitude(0b101), Complex::new(1.0, 0.0));
+        assert_eq!(state.to_state(), crate::ket::State::basis(0b101, 3));
+    }
+}
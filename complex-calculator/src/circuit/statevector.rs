@@ -0,0 +1,605 @@
+use num::Zero;
+
+use super::Gate;
+use crate::channel::Channel;
+use crate::ket::{Operator, State};
+use crate::Complex;
+
+/// Floating-point types a [`StateVector`] can hold its amplitudes in. `f32`
+/// halves the memory of a state, at about seven significant digits.
+pub trait Real: num::Float + std::ops::AddAssign + std::fmt::Debug + Send + Sync {}
+
+impl Real for f32 {}
+impl Real for f64 {}
+
+/// A dense n-qubit state, with `amplitudes[i]` the amplitude of `|i⟩`, in
+/// double precision unless `F` says otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateVector<F = f64> {
+    n: u32,
+    amplitudes: Vec<Complex<F>>,
+}
+
+impl StateVector {
+    /// The all-zero state |0…0⟩.
+    pub fn new(n: u32) -> Self {
+        Self::with_precision(n)
+    }
+}
+
+impl<F: Real> StateVector<F> {
+    /// The all-zero state |0…0⟩ with amplitudes of type `F`, as in
+    /// `StateVector::<f32>::with_precision(n)`.
+    pub fn with_precision(n: u32) -> Self {
+        let mut amplitudes = vec![Complex::new(F::zero(), F::zero()); 1 << n];
+        amplitudes[0] = Complex::new(F::one(), F::zero());
+        StateVector { n, amplitudes }
+    }
+
+    /// The state with the given amplitudes, 2^n of them for n qubits.
+    pub(crate) fn from_amplitudes(amplitudes: Vec<Complex<F>>) -> Self {
+        assert!(
+            amplitudes.len().is_power_of_two(),
+            "{} amplitudes is not a power of two",
+            amplitudes.len()
+        );
+        let n = amplitudes.len().trailing_zeros();
+        StateVector { n, amplitudes }
+    }
+
+    pub fn num_qubits(&self) -> u32 {
+        self.n
+    }
+
+    /// Returns the state to |0…0⟩, keeping its allocation.
+    pub(super) fn reset_to_zero(&mut self) {
+        self.amplitudes.fill(Complex::new(F::zero(), F::zero()));
+        self.amplitudes[0] = Complex::new(F::one(), F::zero());
+    }
+
+    pub fn amplitudes(&self) -> &[Complex<F>] {
+        &self.amplitudes
+    }
+
+    /// Applies `gate` with its first qubit on `qubits[0]` and so on.
+    ///
+    /// Only the gate's own 2^k × 2^k matrix is built. The amplitudes are
+    /// visited in groups of 2^k that differ only in the target bits, so a
+    /// gate costs O(2^n · 2^k) rather than the cost of a 2^n × 2^n operator.
+    pub fn apply(&mut self, gate: &Gate, qubits: &[usize]) {
+        apply_matrix(&mut self.amplitudes, &cast_all(&gate_matrix(gate)), qubits);
+    }
+
+    /// Multiplies every amplitude by e^(iφ).
+    pub fn apply_global_phase(&mut self, phi: f64) {
+        let phase = cast(Complex::new(phi.cos(), phi.sin()));
+        for a in &mut self.amplitudes {
+            *a = *a * phase;
+        }
+    }
+
+    /// Probability of reading 1 from `qubit`.
+    pub fn probability_of_one(&self, qubit: usize) -> f64 {
+        #[cfg(feature = "parallel")]
+        if self.amplitudes.len() >= parallel::THRESHOLD {
+            return parallel::probability_of_one(&self.amplitudes, qubit);
+        }
+
+        self.amplitudes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i >> qubit & 1 == 1)
+            .map(|(_, a)| weight(a))
+            .sum()
+    }
+
+    /// Measures `qubit` in the computational basis, collapsing the state onto
+    /// the outcome, which is returned as a bit.
+    pub fn measure<R: rand::Rng + ?Sized>(&mut self, qubit: usize, rng: &mut R) -> bool {
+        let p1 = self.probability_of_one(qubit);
+        let outcome = rng.random::<f64>() < p1;
+        self.collapse(qubit, outcome, if outcome { p1 } else { 1.0 - p1 });
+        outcome
+    }
+
+    /// Collapses the state onto `qubit` reading `outcome`, whatever its
+    /// probability, which is returned. The state is renormalised unless the
+    /// outcome is impossible.
+    pub(crate) fn project(&mut self, qubit: usize, outcome: bool) -> f64 {
+        let p1 = self.probability_of_one(qubit);
+        let p = if outcome { p1 } else { 1.0 - p1 };
+        if p > 0.0 {
+            self.collapse(qubit, outcome, p);
+        }
+        p
+    }
+
+    /// Zeroes the amplitudes where `qubit` does not read `outcome`, and
+    /// scales the rest by 1/√p.
+    fn collapse(&mut self, qubit: usize, outcome: bool, p: f64) {
+        let scale = cast(Complex::new(1.0 / p.sqrt(), 0.0));
+        let collapse = |(i, a): (usize, &mut Complex<F>)| {
+            *a = if (i >> qubit & 1 == 1) == outcome {
+                *a * scale
+            } else {
+                Complex::new(F::zero(), F::zero())
+            };
+        };
+
+        #[cfg(feature = "parallel")]
+        if self.amplitudes.len() >= parallel::THRESHOLD {
+            use rayon::prelude::*;
+            self.amplitudes
+                .par_iter_mut()
+                .enumerate()
+                .for_each(collapse);
+            return;
+        }
+
+        self.amplitudes.iter_mut().enumerate().for_each(collapse);
+    }
+
+    /// Applies one Kraus operator Kᵢ of `channel` on `qubits`, chosen with
+    /// probability ‖Kᵢψ‖², and renormalises. Returns i.
+    pub fn apply_channel<R: rand::Rng + ?Sized>(
+        &mut self,
+        channel: &Channel,
+        qubits: &[usize],
+        rng: &mut R,
+    ) -> usize {
+        let r = rng.random::<f64>();
+        let mut cumulative = 0.0;
+        let mut chosen = None;
+
+        for (i, k) in channel.kraus().iter().enumerate() {
+            let mut amplitudes = self.amplitudes.clone();
+            let matrix = cast_all(&operator_matrix(k, qubits.len()));
+            apply_matrix(&mut amplitudes, &matrix, qubits);
+            let p: f64 = amplitudes.iter().map(weight).sum();
+            if p <= 0.0 {
+                continue;
+            }
+
+            cumulative += p;
+            chosen = Some((i, amplitudes, p));
+            if r < cumulative {
+                break;
+            }
+        }
+
+        // rounding can leave r just above the final cumulative sum, in which
+        // case the last possible operator is taken
+        let (i, amplitudes, p) = chosen.expect("channel annihilates the state");
+        let scale = cast(Complex::new(1.0 / p.sqrt(), 0.0));
+        self.amplitudes = amplitudes.into_iter().map(|a| a * scale).collect();
+        i
+    }
+
+    pub fn to_state(&self) -> State<Complex<f64>> {
+        let amplitudes: Vec<Complex<f64>> = self
+            .amplitudes
+            .iter()
+            .map(|a| Complex::new(a.re.to_f64().unwrap(), a.im.to_f64().unwrap()))
+            .collect();
+        State::from_amplitudes(&amplitudes)
+    }
+}
+
+/// |a|² in double precision.
+fn weight<F: Real>(a: &Complex<F>) -> f64 {
+    a.mod_squared().to_f64().unwrap()
+}
+
+fn cast<F: Real>(c: Complex<f64>) -> Complex<F> {
+    Complex::new(F::from(c.re).unwrap(), F::from(c.im).unwrap())
+}
+
+fn cast_all<F: Real>(matrix: &[Complex<f64>]) -> Vec<Complex<F>> {
+    matrix.iter().map(|&c| cast(c)).collect()
+}
+
+/// The 2^k × 2^k matrix of `gate`, row-major.
+pub(super) fn gate_matrix(gate: &Gate) -> Vec<Complex<f64>> {
+    operator_matrix(&gate.operator(), gate.num_qubits())
+}
+
+/// The 2^k × 2^k matrix of a k-qubit operator, row-major.
+pub(super) fn operator_matrix(op: &Operator<Complex<f64>>, k: usize) -> Vec<Complex<f64>> {
+    let size = 1 << k;
+    let mut matrix = vec![Complex::zero(); size * size];
+    for (scalar, ket, bra, _) in op.terms() {
+        matrix[ket as usize * size + bra as usize] += scalar;
+    }
+    matrix
+}
+
+/// Multiplies the 2^k × 2^k `matrix` into the bits `qubits` of the index of
+/// `amplitudes`, its first qubit on `qubits[0]`.
+pub(super) fn apply_matrix<F: Real>(
+    amplitudes: &mut [Complex<F>],
+    matrix: &[Complex<F>],
+    qubits: &[usize],
+) {
+    #[cfg(feature = "parallel")]
+    if amplitudes.len() >= parallel::THRESHOLD {
+        return parallel::apply_matrix(amplitudes, matrix, qubits);
+    }
+    apply_groups(amplitudes, matrix, qubits);
+}
+
+/// The sequential [`apply_matrix`], one group of amplitudes differing only
+/// in the target bits at a time.
+fn apply_groups<F: Real>(amplitudes: &mut [Complex<F>], matrix: &[Complex<F>], qubits: &[usize]) {
+    let k = qubits.len();
+    let size = 1 << k;
+
+    // offsets[local] is the local basis state |local⟩ spread out onto the
+    // target bits, so a group is base | offsets[0..size]
+    let offsets: Vec<usize> = (0..size)
+        .map(|local| {
+            qubits
+                .iter()
+                .enumerate()
+                .map(|(i, &q)| (local >> i & 1) << q)
+                .sum()
+        })
+        .collect();
+
+    let mut sorted = qubits.to_vec();
+    sorted.sort_unstable();
+
+    let mut group = vec![Complex::zero(); size];
+    for rest in 0..amplitudes.len() >> k {
+        let base = insert_zero_bits(rest, &sorted);
+
+        for (a, &offset) in group.iter_mut().zip(&offsets) {
+            *a = amplitudes[base | offset];
+        }
+        for (row, &offset) in offsets.iter().enumerate() {
+            let mut sum = Complex::zero();
+            for (col, &a) in group.iter().enumerate() {
+                sum += matrix[row * size + col] * a;
+            }
+            amplitudes[base | offset] = sum;
+        }
+    }
+}
+
+/// Multithreaded versions of the updates above for large states. Every
+/// output is computed by one thread in the same order as the sequential code,
+/// and sums are taken over fixed chunks, so results do not depend on how the
+/// work is scheduled.
+#[cfg(feature = "parallel")]
+mod parallel {
+    use num::Zero;
+    use rayon::prelude::*;
+
+    use super::{insert_zero_bits, weight, Real};
+    use crate::Complex;
+
+    /// States with fewer amplitudes are not worth splitting up.
+    pub(super) const THRESHOLD: usize = 1 << 14;
+
+    const CHUNK: usize = 1 << 12;
+
+    /// Like the sequential [`super::apply_matrix`], and as it in place: the
+    /// amplitudes are split in halves on their highest bit, down to chunks,
+    /// keeping the halves apart on a target bit, as they hold the two sides
+    /// of each group, and handing them to different threads otherwise.
+    pub(super) fn apply_matrix<F: Real>(
+        amplitudes: &mut [Complex<F>],
+        matrix: &[Complex<F>],
+        qubits: &[usize],
+    ) {
+        split(vec![(0, amplitudes)], matrix, qubits);
+    }
+
+    /// Applies the matrix to `slices` of equal length, each paired with the
+    /// local value its target bits above the length have been split on.
+    fn split<F: Real>(
+        slices: Vec<(usize, &mut [Complex<F>])>,
+        matrix: &[Complex<F>],
+        qubits: &[usize],
+    ) {
+        let len = slices[0].1.len();
+        if len <= CHUNK {
+            return update_groups(slices, matrix, qubits);
+        }
+        let (half, top) = (len / 2, len.trailing_zeros() as usize - 1);
+        let halves = slices.into_iter().map(|(local, slice)| {
+            let (low, high) = slice.split_at_mut(half);
+            ((local, low), (local, high))
+        });
+        match qubits.iter().position(|&q| q == top) {
+            Some(i) => {
+                let slices = halves
+                    .flat_map(|((local, low), (_, high))| [(local, low), (local | 1 << i, high)])
+                    .collect();
+                split(slices, matrix, qubits);
+            }
+            None => {
+                let (low, high): (Vec<_>, Vec<_>) = halves.unzip();
+                rayon::join(
+                    || split(low, matrix, qubits),
+                    || split(high, matrix, qubits),
+                );
+            }
+        }
+    }
+
+    /// The groups spread across `slices` and over the target bits left
+    /// inside them, updated one at a time as by [`super::apply_groups`].
+    fn update_groups<F: Real>(
+        mut slices: Vec<(usize, &mut [Complex<F>])>,
+        matrix: &[Complex<F>],
+        qubits: &[usize],
+    ) {
+        let (len, size) = (slices[0].1.len(), 1 << qubits.len());
+        let inside: Vec<(usize, usize)> = qubits
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, q)| 1 << q < len)
+            .collect();
+        // each choice of the bits inside, as an offset in a slice and as
+        // bits of the local value
+        let spread: Vec<(usize, usize)> = (0..1usize << inside.len())
+            .map(|r| {
+                inside
+                    .iter()
+                    .enumerate()
+                    .fold((0, 0), |(offset, local), (t, &(i, q))| {
+                        let bit = r >> t & 1;
+                        (offset | bit << q, local | bit << i)
+                    })
+            })
+            .collect();
+        let mut sorted: Vec<usize> = inside.iter().map(|&(_, q)| q).collect();
+        sorted.sort_unstable();
+
+        let mut group = vec![Complex::zero(); size];
+        for rest in 0..len >> inside.len() {
+            let base = insert_zero_bits(rest, &sorted);
+            for (local, slice) in &slices {
+                for &(offset, bits) in &spread {
+                    group[local | bits] = slice[base | offset];
+                }
+            }
+            for (local, slice) in &mut slices {
+                for &(offset, bits) in &spread {
+                    let row = *local | bits;
+                    let mut sum = Complex::zero();
+                    for (col, &a) in group.iter().enumerate() {
+                        sum += matrix[row * size + col] * a;
+                    }
+                    slice[base | offset] = sum;
+                }
+            }
+        }
+    }
+
+    pub(super) fn probability_of_one<F: Real>(amplitudes: &[Complex<F>], qubit: usize) -> f64 {
+        let partial: Vec<f64> = amplitudes
+            .par_chunks(CHUNK)
+            .enumerate()
+            .map(|(c, chunk)| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| (c * CHUNK + i) >> qubit & 1 == 1)
+                    .map(|(_, a)| weight(a))
+                    .sum::<f64>()
+            })
+            .collect();
+        partial.iter().sum()
+    }
+}
+
+/// Spreads the bits of `x` out so that each position in `zeros` (ascending)
+/// holds a 0.
+fn insert_zero_bits(mut x: usize, zeros: &[usize]) -> usize {
+    for &q in zeros {
+        let low = x & ((1 << q) - 1);
+        x = (x >> q << (q + 1)) | low;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insert_zero_bits, StateVector};
+    use crate::channel::Channel;
+    use crate::circuit::{Circuit, Gate};
+    use crate::Complex as C;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn insert_zero_bits_around_targets() {
+        assert_eq!(insert_zero_bits(0b111, &[1]), 0b1101);
+        assert_eq!(insert_zero_bits(0b111, &[0, 3]), 0b10110);
+        assert_eq!(insert_zero_bits(0, &[0, 1]), 0);
+    }
+
+    #[test]
+    fn apply_on_non_adjacent_qubits() {
+        let mut sv = StateVector::new(3);
+        sv.apply(&Gate::X, &[0]);
+        sv.apply(&Gate::Cx, &[0, 2]);
+
+        let mut expected = vec![C::new(0.0, 0.0); 8];
+        expected[0b101] = C::new(1.0, 0.0);
+        assert_eq!(sv.amplitudes(), expected);
+    }
+
+    #[test]
+    fn apply_matches_embedded_operator() {
+        let gates = [
+            (Gate::H, vec![2]),
+            (Gate::T, vec![0]),
+            (Gate::Cx, vec![3, 1]),
+            (Gate::Swap, vec![0, 2]),
+            (Gate::Rz(0.4.into()), vec![1]),
+            (Gate::Cz, vec![2, 0]),
+        ];
+
+        let mut sv = StateVector::new(4);
+        for q in 0..4 {
+            sv.apply(&Gate::H, &[q]);
+        }
+        sv.apply(&Gate::T, &[1]);
+        sv.apply(&Gate::S, &[3]);
+
+        for (gate, qubits) in &gates {
+            let mut expected = vec![C::new(0.0, 0.0); 16];
+            for (scalar, ket, bra, _) in gate.operator().embed(qubits, 4).terms() {
+                expected[ket as usize] += scalar * sv.amplitudes()[bra as usize];
+            }
+
+            sv.apply(gate, qubits);
+            for (a, b) in sv.amplitudes().iter().zip(&expected) {
+                assert!((*a - *b).modulus() < 1e-12, "{gate} on {qubits:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn measurement_collapses() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _ in 0..20 {
+            let mut sv = StateVector::new(2);
+            sv.apply(&Gate::H, &[0]);
+            sv.apply(&Gate::Cx, &[0, 1]);
+            assert!((sv.probability_of_one(1) - 0.5).abs() < 1e-12);
+
+            let first = sv.measure(0, &mut rng);
+            let expected = if first { 0b11 } else { 0b00 };
+            assert!((sv.amplitudes()[expected].re - 1.0).abs() < 1e-12);
+            assert_eq!(sv.measure(1, &mut rng), first);
+        }
+    }
+
+    #[test]
+    fn channel_picks_a_possible_kraus_operator() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for _ in 0..10 {
+            let mut sv = StateVector::new(2);
+            sv.apply(&Gate::X, &[1]);
+            // full decay can only take the jump operator |0⟩⟨1|
+            assert_eq!(
+                sv.apply_channel(&Channel::amplitude_damping(1.0), &[1], &mut rng),
+                1
+            );
+            assert_eq!(sv.amplitudes()[0], C::new(1.0, 0.0));
+
+            // and on |0⟩ only the no-jump operator
+            assert_eq!(
+                sv.apply_channel(&Channel::amplitude_damping(0.5), &[0], &mut rng),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn single_precision_tracks_double() {
+        let mut c = Circuit::new(5);
+        for q in 0..5 {
+            c.h(q).rz(q, 0.3 * q as f64);
+        }
+        for q in 0..4 {
+            c.cnot(q, q + 1).ry(q, 0.7).cz(4, q);
+        }
+        c.ccx(0, 2, 4).t(3);
+
+        let single = c.simulate_statevector::<f32>();
+        let double = c.simulate_statevector::<f64>();
+        for (a, b) in single.amplitudes().iter().zip(double.amplitudes()) {
+            assert!((a.re as f64 - b.re).abs() < 1e-6);
+            assert!((a.im as f64 - b.im).abs() < 1e-6);
+        }
+        for q in 0..5 {
+            assert!((single.probability_of_one(q) - double.probability_of_one(q)).abs() < 1e-6);
+        }
+
+        assert_eq!(
+            std::mem::size_of_val(single.amplitudes()) * 2,
+            std::mem::size_of_val(double.amplitudes())
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_updates_in_place_on_high_and_low_qubits() {
+        use super::{apply_groups, gate_matrix, parallel};
+
+        let mut sv = StateVector::new(16);
+        for q in 0..16 {
+            sv.apply(&Gate::Ry((0.2 + 0.1 * q as f64).into()), &[q]);
+        }
+        assert!(sv.amplitudes.len() > parallel::THRESHOLD);
+        let gates = [
+            (Gate::H, vec![15]),
+            (Gate::Ry(0.7.into()), vec![0]),
+            (Gate::Cx, vec![15, 0]),
+            (Gate::Cx, vec![0, 14]),
+            (Gate::Ccx, vec![1, 15, 13]),
+        ];
+        for (gate, qubits) in &gates {
+            let matrix = gate_matrix(gate);
+            let mut expected = sv.amplitudes.clone();
+            apply_groups(&mut expected, &matrix, qubits);
+            parallel::apply_matrix(&mut sv.amplitudes, &matrix, qubits);
+            assert_eq!(sv.amplitudes, expected, "{gate:?} on {qubits:?}");
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_updates_match_sequential() {
+        use super::parallel;
+
+        let mut sv = StateVector::new(15);
+        for q in 0..15 {
+            sv.apply(&Gate::Ry((0.1 * q as f64).into()), &[q]);
+        }
+        let mut actual = sv.amplitudes.clone();
+        for (gate, qubits) in [
+            (Gate::Cx, vec![12, 3]),
+            (Gate::H, vec![14]),
+            (Gate::Ccx, vec![0, 14, 7]),
+        ] {
+            let matrix = super::gate_matrix(&gate);
+            let mut expected = actual.clone();
+            super::apply_groups(&mut expected, &matrix, &qubits);
+            parallel::apply_matrix(&mut actual, &matrix, &qubits);
+            assert_eq!(actual, expected, "{gate}");
+        }
+
+        // sums over fixed chunks come out the same every time
+        let p = parallel::probability_of_one(&actual, 3);
+        assert_eq!(p, parallel::probability_of_one(&actual, 3));
+        let sequential: f64 = actual
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i >> 3 & 1 == 1)
+            .map(|(_, a)| a.mod_squared())
+            .sum();
+        assert!((p - sequential).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sixteen_qubit_ghz() {
+        let mut sv = StateVector::new(16);
+        sv.apply(&Gate::H, &[0]);
+        for q in 1..16 {
+            sv.apply(&Gate::Cx, &[q - 1, q]);
+        }
+
+        let a = sv.amplitudes();
+        assert!((a[0].re - a[(1 << 16) - 1].re).abs() < 1e-12);
+        assert!((a[0].mod_squared() - 0.5_f64).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,560 @@
+//! Reader for Stim's `.stim` circuit files.
+//!
+//! The crate has no stabilizer backend, so Stim circuits are read into an
+//! ordinary [`Circuit`] and run on the statevector or density-matrix
+//! simulators. Measurement `k` writes classical bit `k`, and `rec[-j]`
+//! targets refer back to those bits. Pauli and depolarizing noise become
+//! [`Channel`]s, as does the misread result of a measure-and-reset `MR(p)`.
+//! A plain `M(p)` is rejected, as it would have to flip the recorded bit but
+//! not the qubit. Resets of qubits nothing has touched yet are dropped, as
+//! every qubit starts in |0⟩, and annotations such as `DETECTOR` and `TICK`
+//! are skipped.
+
+use super::{Circuit, Gate};
+use crate::channel::Channel;
+use crate::ket::{Operator, MAX_QUBITS};
+use crate::Complex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StimError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for StimError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} on line {}", self.message, self.line)
+    }
+}
+
+impl std::error::Error for StimError {}
+
+impl Circuit {
+    pub fn from_stim(source: &str) -> Result<Circuit, StimError> {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut reader = Reader {
+            ops: vec![],
+            num_qubits: 0,
+            num_measurements: 0,
+            touched: vec![],
+        };
+
+        let end = reader.block(&lines, 0)?;
+        if end < lines.len() {
+            return Err(StimError {
+                line: end + 1,
+                message: "unmatched '}'".to_string(),
+            });
+        }
+
+        let num_qubits =
+            u32::try_from(reader.num_qubits).expect("qubits are checked against MAX_QUBITS");
+        let mut circuit = Circuit::with_clbits(num_qubits, reader.num_measurements);
+        for op in reader.ops {
+            match op {
+                Op::Gate(gate, qubits) => circuit.append(gate, &qubits),
+                Op::Measure(qubit, clbit) => circuit.measure(qubit, clbit),
+                Op::Reset(qubit) => circuit.reset(qubit),
+                Op::Conditional(clbit, gate, qubit) => circuit.c_if(clbit, gate, &[qubit]),
+                Op::Channel(channel, qubits) => circuit.channel(channel, &qubits),
+            };
+        }
+        Ok(circuit)
+    }
+}
+
+enum Op {
+    Gate(Gate, Vec<usize>),
+    Measure(usize, usize),
+    Reset(usize),
+    Conditional(usize, Gate, usize),
+    Channel(Channel, Vec<usize>),
+}
+
+/// A target of an instruction: a qubit or a measurement record.
+enum Target {
+    Qubit(usize),
+    Record(usize),
+}
+
+struct Reader {
+    ops: Vec<Op>,
+    num_qubits: usize,
+    num_measurements: usize,
+    touched: Vec<bool>,
+}
+
+impl Reader {
+    /// Reads instructions from `lines[start..]` up to a closing `}` or the
+    /// end, returning the index of the line where it stopped.
+    fn block(&mut self, lines: &[&str], start: usize) -> Result<usize, StimError> {
+        let mut i = start;
+        while i < lines.len() {
+            let line = lines[i].split('#').next().unwrap().trim();
+            let error = |message: String| StimError {
+                line: i + 1,
+                message,
+            };
+
+            if line.is_empty() {
+                i += 1;
+                continue;
+            }
+            if line == "}" {
+                return Ok(i);
+            }
+
+            if let Some(rest) = line.strip_prefix("REPEAT") {
+                let count = rest
+                    .trim()
+                    .strip_suffix('{')
+                    .and_then(|count| count.trim().parse::<usize>().ok())
+                    .ok_or_else(|| error("expected REPEAT <count> {".to_string()))?;
+                if count == 0 {
+                    return Err(error("REPEAT 0 is not allowed".to_string()));
+                }
+
+                let mut end = i + 1;
+                for _ in 0..count {
+                    end = self.block(lines, i + 1)?;
+                }
+                if end >= lines.len() {
+                    return Err(error("REPEAT block is never closed".to_string()));
+                }
+                i = end + 1;
+                continue;
+            }
+
+            self.instruction(line).map_err(error)?;
+            i += 1;
+        }
+        Ok(i)
+    }
+
+    fn instruction(&mut self, line: &str) -> Result<(), String> {
+        let (head, targets) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let (name, args) = match head.split_once('(') {
+            Some((name, args)) => {
+                let args = args
+                    .strip_suffix(')')
+                    .ok_or_else(|| format!("unclosed arguments in {head}"))?;
+                let args = args
+                    .split(',')
+                    .map(|a| a.trim().parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| format!("malformed arguments in {head}"))?;
+                (name, args)
+            }
+            None => (head, vec![]),
+        };
+        let name = name.to_ascii_uppercase();
+        let targets = targets
+            .split_whitespace()
+            .map(|t| self.target(t))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let probability = |i: usize| -> Result<f64, String> {
+            match args.get(i) {
+                Some(&p) if (0.0..=1.0).contains(&p) => Ok(p),
+                Some(p) => Err(format!("probability {p} outside [0, 1]")),
+                None => Err(format!("{name} needs a probability")),
+            }
+        };
+
+        match name.as_str() {
+            "TICK" | "DETECTOR" | "OBSERVABLE_INCLUDE" | "QUBIT_COORDS" | "SHIFT_COORDS" => {}
+            "I" | "X" | "Y" | "Z" | "H" | "S" | "S_DAG" | "SQRT_X" | "SQRT_X_DAG" => {
+                for q in self.qubits(&targets, &name)? {
+                    for gate in single(&name) {
+                        self.gate(gate, vec![q]);
+                    }
+                }
+            }
+            "CX" | "CNOT" | "ZCX" | "CY" | "ZCY" | "CZ" | "ZCZ" | "SWAP" => {
+                if targets.len() % 2 != 0 {
+                    return Err(format!("{name} needs pairs of targets"));
+                }
+                for pair in targets.chunks(2) {
+                    self.two_qubit(&name, &pair[0], &pair[1])?;
+                }
+            }
+            "M" | "MZ" | "MR" | "MRZ" => {
+                let flip = if args.is_empty() {
+                    None
+                } else {
+                    Some(probability(0)?)
+                };
+                if flip.is_some_and(|p| p > 0.0) && !name.starts_with("MR") {
+                    return Err(format!(
+                        "{name}(p) is not supported, as only the recorded result may flip"
+                    ));
+                }
+                for q in self.qubits(&targets, &name)? {
+                    if let Some(p) = flip {
+                        // the qubit is reset straight after, so a flip just
+                        // before an exact read only misreads the result
+                        self.channel(Channel::bit_flip(p), vec![q]);
+                    }
+                    self.ops.push(Op::Measure(q, self.num_measurements));
+                    self.num_measurements += 1;
+                    self.touched[q] = true;
+                    if name.starts_with("MR") {
+                        self.ops.push(Op::Reset(q));
+                    }
+                }
+            }
+            "R" | "RZ" => {
+                for q in self.qubits(&targets, &name)? {
+                    if self.touched[q] {
+                        self.ops.push(Op::Reset(q));
+                    }
+                }
+            }
+            "X_ERROR" | "Y_ERROR" | "Z_ERROR" | "DEPOLARIZE1" | "PAULI_CHANNEL_1" => {
+                let channel = match name.as_str() {
+                    "X_ERROR" => Channel::bit_flip(probability(0)?),
+                    "Y_ERROR" => Channel::bit_phase_flip(probability(0)?),
+                    "Z_ERROR" => Channel::phase_flip(probability(0)?),
+                    "DEPOLARIZE1" => {
+                        let p = probability(0)? / 3.0;
+                        pauli_channel(&[Gate::X, Gate::Y, Gate::Z].map(|g| (vec![g], p)))?
+                    }
+                    _ => pauli_channel(&[
+                        (vec![Gate::X], probability(0)?),
+                        (vec![Gate::Y], probability(1)?),
+                        (vec![Gate::Z], probability(2)?),
+                    ])?,
+                };
+                for q in self.qubits(&targets, &name)? {
+                    self.channel(channel.clone(), vec![q]);
+                }
+            }
+            "DEPOLARIZE2" => {
+                let p = probability(0)? / 15.0;
+                let paulis = [Gate::I, Gate::X, Gate::Y, Gate::Z];
+                let mut terms = vec![];
+                for a in &paulis {
+                    for b in &paulis {
+                        if (a, b) != (&Gate::I, &Gate::I) {
+                            terms.push((vec![a.clone(), b.clone()], p));
+                        }
+                    }
+                }
+                let channel = pauli_channel(&terms)?;
+
+                let qubits = self.qubits(&targets, &name)?;
+                if qubits.len() % 2 != 0 {
+                    return Err(format!("{name} needs pairs of targets"));
+                }
+                for pair in qubits.chunks(2) {
+                    self.channel(channel.clone(), pair.to_vec());
+                }
+            }
+            _ => return Err(format!("unsupported instruction {name}")),
+        }
+        Ok(())
+    }
+
+    fn target(&mut self, token: &str) -> Result<Target, String> {
+        if let Some(back) = token
+            .strip_prefix("rec[-")
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            let back: usize = back
+                .parse()
+                .map_err(|_| format!("malformed target {token}"))?;
+            if back == 0 || back > self.num_measurements {
+                return Err(format!(
+                    "{token} refers to a measurement that has not happened"
+                ));
+            }
+            return Ok(Target::Record(self.num_measurements - back));
+        }
+
+        let q: usize = token
+            .parse()
+            .map_err(|_| format!("unsupported target {token}"))?;
+        if q >= MAX_QUBITS as usize {
+            return Err(format!("qubit {q} exceeds the {MAX_QUBITS}-qubit limit"));
+        }
+        if q >= self.num_qubits {
+            self.num_qubits = q + 1;
+            self.touched.resize(q + 1, false);
+        }
+        Ok(Target::Qubit(q))
+    }
+
+    fn qubits(&self, targets: &[Target], name: &str) -> Result<Vec<usize>, String> {
+        targets
+            .iter()
+            .map(|t| match t {
+                Target::Qubit(q) => Ok(*q),
+                Target::Record(_) => Err(format!("{name} cannot target a measurement record")),
+            })
+            .collect()
+    }
+
+    fn gate(&mut self, gate: Gate, qubits: Vec<usize>) {
+        for &q in &qubits {
+            self.touched[q] = true;
+        }
+        self.ops.push(Op::Gate(gate, qubits));
+    }
+
+    fn conditional(&mut self, clbit: usize, gate: Gate, qubit: usize) {
+        self.touched[qubit] = true;
+        self.ops.push(Op::Conditional(clbit, gate, qubit));
+    }
+
+    fn channel(&mut self, channel: Channel, qubits: Vec<usize>) {
+        for &q in &qubits {
+            self.touched[q] = true;
+        }
+        self.ops.push(Op::Channel(channel, qubits));
+    }
+
+    /// A two-qubit gate, or a classically controlled Pauli when the control
+    /// is a measurement record.
+    fn two_qubit(&mut self, name: &str, a: &Target, b: &Target) -> Result<(), String> {
+        let pauli = match name {
+            "CY" | "ZCY" => Gate::Y,
+            "CZ" | "ZCZ" => Gate::Z,
+            _ => Gate::X,
+        };
+
+        match (a, b) {
+            (Target::Qubit(a), Target::Qubit(b)) => {
+                if a == b {
+                    return Err(format!("{name} applied to the same qubit twice"));
+                }
+                let (a, b) = (*a, *b);
+                match name {
+                    "SWAP" => self.gate(Gate::Swap, vec![a, b]),
+                    "CZ" | "ZCZ" => self.gate(Gate::Cz, vec![a, b]),
+                    "CY" | "ZCY" => {
+                        self.gate(Gate::Sdg, vec![b]);
+                        self.gate(Gate::Cx, vec![a, b]);
+                        self.gate(Gate::S, vec![b]);
+                    }
+                    _ => self.gate(Gate::Cx, vec![a, b]),
+                }
+            }
+            // Z is symmetric, so its record may come second
+            (Target::Record(clbit), Target::Qubit(q)) if name != "SWAP" => {
+                self.conditional(*clbit, pauli, *q)
+            }
+            (Target::Qubit(q), Target::Record(clbit)) if pauli == Gate::Z => {
+                self.conditional(*clbit, pauli, *q)
+            }
+            _ => return Err(format!("unsupported use of measurement records in {name}")),
+        }
+        Ok(())
+    }
+}
+
+/// The gates making up the Stim single-qubit gate `name`, up to phase.
+fn single(name: &str) -> Vec<Gate> {
+    match name {
+        "I" => vec![Gate::I],
+        "X" => vec![Gate::X],
+        "Y" => vec![Gate::Y],
+        "Z" => vec![Gate::Z],
+        "H" => vec![Gate::H],
+        "S" => vec![Gate::S],
+        "S_DAG" => vec![Gate::Sdg],
+        "SQRT_X" => vec![Gate::Sx],
+        "SQRT_X_DAG" => vec![Gate::Sxdg],
+        _ => unreachable!(),
+    }
+}
+
+/// The channel applying each Pauli string in `terms` with its probability,
+/// and the identity otherwise.
+fn pauli_channel(terms: &[(Vec<Gate>, f64)]) -> Result<Channel, String> {
+    let total: f64 = terms.iter().map(|(_, p)| p).sum();
+    if total > 1.0 + 1e-12 {
+        return Err(format!("error probabilities sum to {total} > 1"));
+    }
+
+    let n = terms[0].0.len() as u32;
+    let mut kraus = vec![Operator::identity(n) * Complex::new((1.0 - total).max(0.0).sqrt(), 0.0)];
+    for (paulis, p) in terms {
+        let pauli = paulis
+            .iter()
+            .map(Gate::operator)
+            .reduce(|a, b| a.tensor(&b))
+            .unwrap();
+        kraus.push(pauli * Complex::new(p.sqrt(), 0.0));
+    }
+    Ok(Channel::new(kraus))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StimError;
+    use crate::channel::Channel;
+    use crate::circuit::{Circuit, Gate, Instruction};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn bell_pair() {
+        let c = Circuit::from_stim(
+            "# a Bell pair\nR 0 1\nH 0\nTICK\nCNOT 0 1\nM 0 1\nDETECTOR rec[-1] rec[-2]\n",
+        )
+        .unwrap();
+
+        let mut expected = Circuit::new(2);
+        expected.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn resets_after_use() {
+        let c = Circuit::from_stim("R 0\nH 0\nMR 0\nR 0 1\nM 1").unwrap();
+        let mut expected = Circuit::new(2);
+        expected.h(0).measure(0, 0).reset(0).reset(0).measure(1, 1);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn pairs_and_broadcasts() {
+        let c = Circuit::from_stim("H 0 1 2\nCX 0 1 2 3\nS_DAG 3\nSWAP 1 2").unwrap();
+        let mut expected = Circuit::new(4);
+        expected
+            .h(0)
+            .h(1)
+            .h(2)
+            .cnot(0, 1)
+            .cnot(2, 3)
+            .sdg(3)
+            .swap(1, 2);
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn repeat_blocks_unroll() {
+        let c = Circuit::from_stim("REPEAT 3 {\n    H 0\n    M 0\n}\nX 1").unwrap();
+        assert_eq!(c.gate_counts()["h"], 3);
+        assert_eq!(c.num_clbits(), 3);
+        assert_eq!(c.len(), 7);
+    }
+
+    #[test]
+    fn records_control_paulis() {
+        let c = Circuit::from_stim("H 0\nM 0\nM 1\nCX rec[-2] 2\nCZ 1 rec[-1]").unwrap();
+        assert!(matches!(
+            c.instructions()[3],
+            Instruction::Conditional {
+                clbit: 0,
+                gate: Gate::X,
+                ..
+            }
+        ));
+        assert!(matches!(
+            c.instructions()[4],
+            Instruction::Conditional {
+                clbit: 1,
+                gate: Gate::Z,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn noise_matches_stim_definitions() {
+        // DEPOLARIZE1(p) applies X, Y or Z with probability p/3 each, which is
+        // ρ → (1 − 4p/3) ρ + (4p/3) I/2
+        let stim = Circuit::from_stim("H 0\nDEPOLARIZE1(0.3) 0\nX_ERROR(0.1) 1").unwrap();
+        let mut expected = Circuit::new(2);
+        expected
+            .h(0)
+            .channel(Channel::depolarizing(0.4), &[0])
+            .channel(Channel::bit_flip(0.1), &[1]);
+
+        let (a, b) = (stim.simulate_density(), expected.simulate_density());
+        for x in 0..4 {
+            assert!((a.probability(x) - b.probability(x)).modulus() < EPSILON);
+        }
+        let (ax, ..) = a.trace_out(&[1]).bloch_vector();
+        let (bx, ..) = b.trace_out(&[1]).bloch_vector();
+        assert!((ax - bx).abs() < EPSILON);
+    }
+
+    #[test]
+    fn two_qubit_depolarizing() {
+        let c = Circuit::from_stim("DEPOLARIZE2(0.15) 0 1").unwrap();
+        let Instruction::Channel { channel, .. } = &c.instructions()[0] else {
+            panic!("expected a channel");
+        };
+        assert_eq!(channel.kraus().len(), 16);
+
+        // |00⟩ stays put under the 3 of 15 Paulis made of I and Z only
+        let p = c.simulate_density().probability(0).re;
+        assert!((p - (1.0 - 12.0 * 0.01)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn noisy_measurement_and_reset_misreads() {
+        let c = Circuit::from_stim("MR(0.2) 0\nM 0").unwrap();
+        assert_eq!(c.gate_counts()["bit_flip"], 1);
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut misread = 0;
+        for _ in 0..2000 {
+            let run = c.run(&mut rng);
+            // the qubit itself is never left flipped
+            assert!(!run.clbits()[1]);
+            misread += usize::from(run.clbits()[0]);
+        }
+        assert!((300..500).contains(&misread), "{misread}");
+    }
+
+    #[test]
+    fn noisy_measurement_without_reset_is_rejected() {
+        // a flipped qubit would also flip the second measurement, which in
+        // Stim always agrees with the first exact result
+        let error = Circuit::from_stim("H 0\nM(0.5) 0\nM 0").unwrap_err();
+        assert_eq!(
+            error,
+            StimError {
+                line: 2,
+                message: "M(p) is not supported, as only the recorded result may flip".to_string()
+            }
+        );
+        assert!(Circuit::from_stim("M(0) 0\nMZ 0").is_ok());
+    }
+
+    #[test]
+    fn errors() {
+        for (source, line, message) in [
+            ("H 0\nCCZ 0 1 2", 2, "unsupported instruction CCZ"),
+            (
+                "CX 0 rec[-1]",
+                1,
+                "rec[-1] refers to a measurement that has not happened",
+            ),
+            ("REPEAT 2 {\nH 0", 1, "REPEAT block is never closed"),
+            ("M 0\nREPEAT 0 {\nM 1\n}", 2, "REPEAT 0 is not allowed"),
+            ("H 0\n}", 2, "unmatched '}'"),
+            ("X_ERROR(1.5) 0", 1, "probability 1.5 outside [0, 1]"),
+            ("CX 0 1 2", 1, "CX needs pairs of targets"),
+            (
+                "H 0\nH 99999999999",
+                2,
+                "qubit 99999999999 exceeds the 64-qubit limit",
+            ),
+        ] {
+            assert_eq!(
+                Circuit::from_stim(source).unwrap_err(),
+                StimError {
+                    line,
+                    message: message.to_string()
+                },
+                "{source}"
+            );
+        }
+    }
+}
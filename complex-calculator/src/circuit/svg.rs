@@ -0,0 +1,492 @@
+//! SVG drawings of circuits, in the column layout shared with
+//! [`Circuit::to_quantikz`]. There is one wire per qubit, with qubit 0 at
+//! the top, and each column is as wide as its widest label.
+
+use std::fmt::Write;
+
+use super::layout::{pi_fraction, span};
+use super::{Circuit, Gate, Instruction, Param};
+
+/// Sizes, font and colours of an SVG drawing. Lengths are in pixels, and
+/// colours anything SVG accepts, such as `"black"` or `"#1f77b4"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgStyle {
+    /// Distance between neighbouring wires.
+    pub wire_spacing: f64,
+    /// Height of a gate box on one wire, and the least width of a column.
+    pub gate_size: f64,
+    /// Space between columns.
+    pub column_gap: f64,
+    pub font_family: String,
+    pub font_size: f64,
+    pub line_colour: String,
+    pub gate_fill: String,
+    pub text_colour: String,
+    /// Fill behind the whole drawing, transparent if `None`.
+    pub background: Option<String>,
+}
+
+impl Default for SvgStyle {
+    fn default() -> Self {
+        SvgStyle {
+            wire_spacing: 48.0,
+            gate_size: 32.0,
+            column_gap: 16.0,
+            font_family: "sans-serif".to_string(),
+            font_size: 14.0,
+            line_colour: "black".to_string(),
+            gate_fill: "white".to_string(),
+            text_colour: "black".to_string(),
+            background: None,
+        }
+    }
+}
+
+impl Circuit {
+    /// The circuit as an SVG image in the default [`SvgStyle`].
+    pub fn to_svg(&self) -> String {
+        self.to_svg_with(&SvgStyle::default())
+    }
+
+    /// The circuit as an SVG image in `style`. Gates are drawn as in
+    /// [`Circuit::to_quantikz`]: classical wires are left out, a classically
+    /// conditioned gate is labelled with its bit, a loop is a box across
+    /// every wire, and barriers across every qubit and snapshots become
+    /// dashed dividers, snapshots labelled.
+    pub fn to_svg_with(&self, style: &SvgStyle) -> String {
+        let n = self.num_qubits as usize;
+        let layout = self.layout();
+        let mut svg = Svg {
+            style,
+            body: String::new(),
+        };
+
+        let labels: Vec<Vec<String>> = layout
+            .columns
+            .iter()
+            .map(|column| column.iter().map(|i| box_label(i)).collect())
+            .collect();
+        let widths: Vec<f64> = labels
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .map(|label| svg.text_width(label) + style.font_size)
+                    .fold(style.gate_size, f64::max)
+            })
+            .collect();
+
+        let left = 2.5 * style.font_size + style.column_gap;
+        let mut edges = vec![left];
+        for width in &widths {
+            edges.push(edges.last().unwrap() + width + style.column_gap);
+        }
+        let width = edges.last().unwrap() + style.column_gap;
+        let height = svg.wire(n) - style.wire_spacing / 2.0 + style.font_size;
+
+        for q in 0..n {
+            let y = svg.wire(q);
+            svg.text(style.font_size, y, "start", &format!("q{q}"));
+            svg.line(left - style.column_gap / 2.0, y, width, y, "");
+        }
+
+        for (c, (column, labels)) in layout.columns.iter().zip(&labels).enumerate() {
+            let x = edges[c] + widths[c] / 2.0;
+            for (instruction, label) in column.iter().zip(labels) {
+                svg.instruction(instruction, label, x, widths[c], n);
+            }
+        }
+
+        let bottom = svg.wire(n - 1) + style.wire_spacing / 2.0;
+        for (c, label) in layout.slices {
+            let x = edges[c + 1] - style.column_gap / 2.0;
+            svg.line(
+                x,
+                style.font_size * 1.5,
+                x,
+                bottom,
+                " stroke-dasharray=\"4 3\"",
+            );
+            if !label.is_empty() {
+                svg.text(x, style.font_size, "middle", label);
+            }
+        }
+
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+             viewBox=\"0 0 {width} {height}\" font-family=\"{}\" font-size=\"{}\">\n",
+            escape(&style.font_family),
+            style.font_size
+        );
+        if let Some(background) = &style.background {
+            writeln!(
+                out,
+                "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+                escape(background)
+            )
+            .unwrap();
+        }
+        out.push_str(&svg.body);
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+/// The elements of a drawing being built.
+struct Svg<'a> {
+    style: &'a SvgStyle,
+    body: String,
+}
+
+impl Svg<'_> {
+    /// The height of wire q, leaving room above for divider labels.
+    fn wire(&self, q: usize) -> f64 {
+        2.0 * self.style.font_size + (q as f64 + 0.5) * self.style.wire_spacing
+    }
+
+    /// A rough width for `text`, the font's metrics being unknown.
+    fn text_width(&self, text: &str) -> f64 {
+        0.6 * self.style.font_size * text.chars().count() as f64
+    }
+
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, extra: &str) {
+        writeln!(
+            self.body,
+            "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"{}\"{extra}/>",
+            escape(&self.style.line_colour)
+        )
+        .unwrap();
+    }
+
+    fn text(&mut self, x: f64, y: f64, anchor: &str, text: &str) {
+        writeln!(
+            self.body,
+            "<text x=\"{x}\" y=\"{y}\" text-anchor=\"{anchor}\" dominant-baseline=\"central\" \
+             fill=\"{}\">{}</text>",
+            escape(&self.style.text_colour),
+            escape(text)
+        )
+        .unwrap();
+    }
+
+    fn circle(&mut self, x: f64, y: f64, r: f64, fill: &str) {
+        writeln!(
+            self.body,
+            "<circle cx=\"{x}\" cy=\"{y}\" r=\"{r}\" fill=\"{}\" stroke=\"{}\"/>",
+            escape(fill),
+            escape(&self.style.line_colour)
+        )
+        .unwrap();
+    }
+
+    /// A box `width` wide centred on `x`, from wire `top` to wire `bottom`,
+    /// with `label` in the middle.
+    fn boxed(&mut self, x: f64, width: f64, top: usize, bottom: usize, label: &str) {
+        let half = self.style.gate_size / 2.0;
+        let (y1, y2) = (self.wire(top) - half, self.wire(bottom) + half);
+        writeln!(
+            self.body,
+            "<rect x=\"{}\" y=\"{y1}\" width=\"{width}\" height=\"{}\" fill=\"{}\" stroke=\"{}\"/>",
+            x - width / 2.0,
+            y2 - y1,
+            escape(&self.style.gate_fill),
+            escape(&self.style.line_colour)
+        )
+        .unwrap();
+        if !label.is_empty() {
+            self.text(x, (y1 + y2) / 2.0, "middle", label);
+        }
+    }
+
+    fn instruction(
+        &mut self,
+        instruction: &Instruction,
+        label: &str,
+        x: f64,
+        width: f64,
+        n: usize,
+    ) {
+        let (top, bottom) = span(instruction, n);
+        match instruction {
+            Instruction::Gate { gate, qubits } => self.gate(gate, qubits, label, x, width, false),
+            Instruction::Conditional { gate, qubits, .. } => {
+                self.gate(gate, qubits, label, x, width, true)
+            }
+            Instruction::Measure { qubit, .. } => {
+                self.boxed(x, self.style.gate_size, *qubit, *qubit, "");
+                let (y, r) = (self.wire(*qubit), self.style.gate_size / 3.0);
+                writeln!(
+                    self.body,
+                    "<path d=\"M {} {} A {r} {r} 0 0 1 {} {}\" fill=\"none\" stroke=\"{}\"/>",
+                    x - r,
+                    y + r / 2.0,
+                    x + r,
+                    y + r / 2.0,
+                    escape(&self.style.line_colour)
+                )
+                .unwrap();
+                self.line(x, y + r / 2.0, x + r * 0.7, y - r * 0.6, "");
+            }
+            Instruction::Reset { .. } | Instruction::Channel { .. } | Instruction::While { .. } => {
+                self.boxed(x, width, top, bottom, label)
+            }
+            Instruction::Barrier { .. } | Instruction::Snapshot { .. } => unreachable!(),
+        }
+    }
+
+    /// Draws `gate`; controlled gates as dots and a target, unless
+    /// `conditional`, when the target is a box naming the gate and its bit.
+    fn gate(
+        &mut self,
+        gate: &Gate,
+        qubits: &[usize],
+        label: &str,
+        x: f64,
+        width: f64,
+        conditional: bool,
+    ) {
+        let (&target, controls) = qubits.split_last().unwrap();
+        let (top, bottom) = (*qubits.iter().min().unwrap(), *qubits.iter().max().unwrap());
+        if top != bottom {
+            let (y1, y2) = (self.wire(top), self.wire(bottom));
+            self.line(x, y1, x, y2, "");
+        }
+
+        let line = self.style.line_colour.clone();
+        match gate {
+            Gate::Cx | Gate::Cz | Gate::Ccx | Gate::Mcx(_) => {
+                for &c in controls {
+                    self.circle(x, self.wire(c), 4.0, &line);
+                }
+                let y = self.wire(target);
+                match (gate, conditional) {
+                    (_, true) => self.boxed(x, width, target, target, label),
+                    (Gate::Cz, false) => self.circle(x, y, 4.0, &line),
+                    _ => {
+                        let r = self.style.gate_size / 3.0;
+                        let fill = self.style.gate_fill.clone();
+                        self.circle(x, y, r, &fill);
+                        self.line(x - r, y, x + r, y, "");
+                        self.line(x, y - r, x, y + r, "");
+                    }
+                }
+            }
+            Gate::Swap if !conditional => {
+                let d = self.style.gate_size / 5.0;
+                for q in [qubits[0], target] {
+                    let y = self.wire(q);
+                    self.line(x - d, y - d, x + d, y + d, "");
+                    self.line(x - d, y + d, x + d, y - d, "");
+                }
+            }
+            Gate::Swap => {
+                self.circle(x, self.wire(qubits[0]), 4.0, &line);
+                self.boxed(x, width, target, target, label);
+            }
+            _ => self.boxed(x, width, target, target, label),
+        }
+    }
+}
+
+/// The text in the box drawn for `instruction`, empty if there is none.
+fn box_label(instruction: &Instruction) -> String {
+    match instruction {
+        Instruction::Gate { gate, .. } => gate_label(gate),
+        Instruction::Conditional { clbit, gate, .. } => {
+            let name = match gate {
+                Gate::Cx | Gate::Ccx | Gate::Mcx(_) => "X".to_string(),
+                Gate::Cz => "Z".to_string(),
+                Gate::Swap => "SWAP".to_string(),
+                _ => gate_label(gate),
+            };
+            format!("{name} if c{clbit}")
+        }
+        Instruction::Reset { .. } => "|0⟩".to_string(),
+        Instruction::Channel { channel, .. } => format!("ℰ {}", channel.name().replace('_', " ")),
+        Instruction::While {
+            clbit,
+            value,
+            max_iterations,
+            ..
+        } => format!(
+            "while c{clbit} = {} (at most {max_iterations})",
+            u8::from(*value)
+        ),
+        Instruction::Measure { .. } => String::new(),
+        Instruction::Barrier { .. } | Instruction::Snapshot { .. } => unreachable!(),
+    }
+}
+
+/// The label of a gate drawn as a box, empty for gates drawn otherwise.
+fn gate_label(gate: &Gate) -> String {
+    match gate {
+        Gate::I => "I".to_string(),
+        Gate::H => "H".to_string(),
+        Gate::X => "X".to_string(),
+        Gate::Y => "Y".to_string(),
+        Gate::Z => "Z".to_string(),
+        Gate::S => "S".to_string(),
+        Gate::Sdg => "S†".to_string(),
+        Gate::T => "T".to_string(),
+        Gate::Tdg => "T†".to_string(),
+        Gate::Sx => "√X".to_string(),
+        Gate::Sxdg => "√X†".to_string(),
+        Gate::Rx(theta) => format!("Rx({})", angle(theta)),
+        Gate::Ry(theta) => format!("Ry({})", angle(theta)),
+        Gate::Rz(theta) => format!("Rz({})", angle(theta)),
+        Gate::Phase(lambda) => format!("P({})", angle(lambda)),
+        Gate::U3(theta, phi, lambda) => {
+            format!("U({}, {}, {})", angle(theta), angle(phi), angle(lambda))
+        }
+        Gate::Cx | Gate::Cz | Gate::Swap | Gate::Ccx | Gate::Mcx(_) => String::new(),
+    }
+}
+
+/// An angle as a multiple of π where it is a small fraction of it, e.g.
+/// `3π/4`, and a symbol with a Greek name as that letter.
+fn angle(param: &Param) -> String {
+    const GREEK: [(&str, &str); 14] = [
+        ("alpha", "α"),
+        ("beta", "β"),
+        ("gamma", "γ"),
+        ("delta", "δ"),
+        ("epsilon", "ε"),
+        ("eta", "η"),
+        ("theta", "θ"),
+        ("kappa", "κ"),
+        ("lambda", "λ"),
+        ("mu", "μ"),
+        ("phi", "φ"),
+        ("psi", "ψ"),
+        ("omega", "ω"),
+        ("tau", "τ"),
+    ];
+    let x = match param {
+        Param::Value(x) => *x,
+        Param::Symbol(name) => {
+            return GREEK
+                .iter()
+                .find(|(greek, _)| greek == name)
+                .map_or_else(|| name.clone(), |(_, letter)| letter.to_string())
+        }
+    };
+
+    match pi_fraction(x) {
+        Some((0, _)) => "0".to_string(),
+        Some((p, q)) => {
+            let sign = if p < 0 { "-" } else { "" };
+            let numerator = match p.abs() {
+                1 => "π".to_string(),
+                p => format!("{p}π"),
+            };
+            match q {
+                1 => format!("{sign}{numerator}"),
+                _ => format!("{sign}{numerator}/{q}"),
+            }
+        }
+        None => format!("{}", (x * 1000.0).round() / 1000.0),
+    }
+}
+
+/// `text` with the characters special to XML escaped.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::SvgStyle;
+    use crate::channel::Channel;
+    use crate::circuit::{Circuit, Gate};
+
+    fn count(svg: &str, element: &str) -> usize {
+        svg.matches(&format!("<{element} ")).count()
+    }
+
+    #[test]
+    fn bell_pair() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+        let svg = c.to_svg();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        // H and the two meters are boxes
+        assert_eq!(count(&svg, "rect"), 3);
+        // a control dot and the target
+        assert_eq!(count(&svg, "circle"), 2);
+        assert_eq!(count(&svg, "path"), 2);
+        assert!(svg.contains(">H</text>"));
+        assert!(svg.contains(">q1</text>"));
+    }
+
+    #[test]
+    fn labels() {
+        let mut c = Circuit::new(3);
+        c.rx(0, PI / 2.0)
+            .p(1, "theta")
+            .u3(2, -PI, 0.25, "c")
+            .sdg(0)
+            .reset(1)
+            .c_if(0, Gate::Cx, &[1, 2])
+            .channel(Channel::depolarizing(0.1), &[0]);
+        let svg = c.to_svg();
+        for label in [
+            "Rx(π/2)",
+            "P(θ)",
+            "U(-π, 0.25, c)",
+            "S†",
+            "|0⟩",
+            "X if c0",
+            "ℰ depolarizing",
+        ] {
+            assert!(svg.contains(&format!(">{label}</text>")), "{label}");
+        }
+    }
+
+    #[test]
+    fn dividers_and_loops() {
+        let mut body = Circuit::new(2);
+        body.h(0).measure(0, 0);
+        let mut c = Circuit::new(2);
+        c.h(1)
+            .barrier(&[0, 1])
+            .snapshot("a < b")
+            .while_loop(0, false, &body, 3);
+        let svg = c.to_svg();
+
+        assert_eq!(svg.matches("stroke-dasharray").count(), 2);
+        assert!(svg.contains(">a &lt; b</text>"));
+        assert!(svg.contains(">while c0 = 0 (at most 3)</text>"));
+    }
+
+    #[test]
+    fn styles() {
+        let mut c = Circuit::new(1);
+        c.h(0);
+        let style = SvgStyle {
+            font_family: "Fira Sans".to_string(),
+            gate_fill: "#ffeeaa".to_string(),
+            background: Some("white".to_string()),
+            ..SvgStyle::default()
+        };
+        let svg = c.to_svg_with(&style);
+        assert!(svg.contains("font-family=\"Fira Sans\""));
+        assert!(svg.contains("fill=\"#ffeeaa\""));
+        assert!(svg.contains("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>"));
+
+        // columns widen to fit their labels
+        let mut wide = Circuit::new(1);
+        wide.u3(0, 0.123, 0.456, 0.789);
+        let width = |svg: &str| -> f64 {
+            let start = svg.find("width=\"").unwrap() + 7;
+            svg[start..].split('"').next().unwrap().parse().unwrap()
+        };
+        assert!(width(&wide.to_svg()) > width(&c.to_svg()) + 100.0);
+    }
+}
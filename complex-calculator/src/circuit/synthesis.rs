@@ -0,0 +1,516 @@
+//! Compiling unitaries given as operators into circuits.
+
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+
+use super::Circuit;
+use crate::ket::Operator;
+use crate::matrix::Matrix;
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// Angles of the ZYZ Euler decomposition U = e^(iα) Rz(β) Ry(γ) Rz(δ) of a
+/// single-qubit unitary.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EulerAngles {
+    pub alpha: f64,
+    pub beta: f64,
+    pub gamma: f64,
+    pub delta: f64,
+}
+
+impl EulerAngles {
+    /// Decomposes a single-qubit unitary. When γ is 0 or π only β ± δ is
+    /// determined, and δ is taken to be 0.
+    pub fn zyz(u: &Operator<C>) -> Self {
+        assert_eq!(
+            u.num_qubits(),
+            1,
+            "ZYZ decomposition needs a single-qubit operator"
+        );
+        Self::from_matrix(&Matrix(single_qubit_matrix(u)))
+    }
+
+    /// [`EulerAngles::zyz`] of a matrix indexed `[row][column]`.
+    pub fn from_matrix(m: &Matrix<C, 2, 2>) -> Self {
+        let [[u00, u01], [u10, u11]] = m.0;
+
+        // divide out the global phase so that V = e^(−iα) U has determinant 1,
+        // V = [[a, −b*], [b, a*]] with a = e^(−i(β+δ)/2) cos(γ/2) and
+        // b = e^(i(β−δ)/2) sin(γ/2)
+        let det = u00 * u11 - u01 * u10;
+        let alpha = det.arg() / 2.0;
+        let phase = C::new(alpha.cos(), -alpha.sin());
+        let (a, b) = (u00 * phase, u10 * phase);
+
+        let gamma = 2.0 * b.modulus().atan2(a.modulus());
+        let (beta, delta) = if b.modulus() < 1e-12 {
+            (-2.0 * a.arg(), 0.0)
+        } else if a.modulus() < 1e-12 {
+            (2.0 * b.arg(), 0.0)
+        } else {
+            (b.arg() - a.arg(), -a.arg() - b.arg())
+        };
+
+        EulerAngles {
+            alpha,
+            beta,
+            gamma,
+            delta,
+        }
+    }
+
+    /// The one-qubit circuit Rz(δ), Ry(γ), Rz(β) in time order, with global
+    /// phase α, equal to the decomposed unitary.
+    pub fn circuit(&self) -> Circuit {
+        let mut c = Circuit::new(1);
+        c.rz(0, self.delta).ry(0, self.gamma).rz(0, self.beta);
+        c.add_global_phase(self.alpha);
+        c
+    }
+}
+
+/// The KAK (Cartan) decomposition of a two-qubit unitary,
+///
+/// U = e^(iφ) (A₁ ⊗ B₁) exp(i(a XX + b YY + c ZZ)) (A₀ ⊗ B₀),
+///
+/// where each `[qubit 0, qubit 1]` pair of local factors is given by its
+/// Euler angles and `coefficients` holds (a, b, c).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KakDecomposition {
+    pub phase: f64,
+    pub before: [EulerAngles; 2],
+    pub coefficients: [f64; 3],
+    pub after: [EulerAngles; 2],
+}
+
+impl KakDecomposition {
+    /// Decomposes a two-qubit unitary.
+    pub fn new(u: &Operator<C>) -> Self {
+        assert_eq!(
+            u.num_qubits(),
+            2,
+            "KAK decomposition needs a two-qubit operator"
+        );
+
+        let mut u4 = [[C::new(0.0, 0.0); 4]; 4];
+        for (scalar, ket, bra, _) in u.terms() {
+            u4[ket as usize][bra as usize] += scalar;
+        }
+
+        // scale into SU(4) and move to the magic basis, where local unitaries
+        // are real orthogonal matrices and the interaction is diagonal
+        let mut phase = det(&u4).arg() / 4.0;
+        let u4 = scaled(&u4, unit(-phase));
+        let magic = magic_basis();
+        let m = mul(&mul(&adjoint(&magic), &u4), &magic);
+
+        // M = K₁ D K₂ with K₁, K₂ ∈ SO(4), so MᵀM = K₂ᵀ D² K₂ is diagonalised
+        // by a real orthogonal matrix
+        let mtm = mul(&transpose(&m), &m);
+        let mut p = real_eigenvectors(&mtm);
+        if real_det(&p) < 0.0 {
+            p.iter_mut().for_each(|row| row[0] = -row[0]);
+        }
+        let p = p.map(|row| row.map(|x| C::new(x, 0.0)));
+        let squares = mul(&mul(&transpose(&p), &mtm), &p);
+
+        let mut theta: [f64; 4] = std::array::from_fn(|k| squares[k][k].arg() / 2.0);
+        // the θₖ sum to a multiple of π, pick the branch making det K₁ = 1
+        let total: f64 = theta.iter().sum();
+        if (total / std::f64::consts::PI).round().rem_euclid(2.0) == 1.0 {
+            theta[0] += std::f64::consts::PI;
+        }
+
+        let mut d_inverse = [[C::new(0.0, 0.0); 4]; 4];
+        for k in 0..4 {
+            d_inverse[k][k] = unit(-theta[k]);
+        }
+        let k1 = mul(&mul(&m, &p), &d_inverse);
+        let k2 = transpose(&p);
+
+        // exp(i(a XX + b YY + c ZZ)) is diagonal in the magic basis with
+        // entries e^(iλₖ), λ = a x + b y + c z for the ±1 diagonals x, y, z,
+        // which are orthogonal to each other and to (1, 1, 1, 1)
+        let diagonals = [PAULI_X, PAULI_Y, PAULI_Z].map(|pauli| {
+            let pp = kron(&pauli, &pauli);
+            let d = mul(&mul(&adjoint(&magic), &pp), &magic);
+            std::array::from_fn::<f64, 4, _>(|k| d[k][k].re)
+        });
+        let coefficients = diagonals.map(|d| (0..4).map(|k| d[k] * theta[k]).sum::<f64>() / 4.0);
+        phase += theta.iter().sum::<f64>() / 4.0;
+
+        let local = |k: &[[C; 4]; 4]| {
+            let (a, b, factor_phase) = local_factors(&mul(&mul(&magic, k), &adjoint(&magic)));
+            (
+                [
+                    EulerAngles::from_matrix(&Matrix(b)),
+                    EulerAngles::from_matrix(&Matrix(a)),
+                ],
+                factor_phase,
+            )
+        };
+        let (before, before_phase) = local(&k2);
+        let (after, after_phase) = local(&k1);
+        phase += before_phase + after_phase;
+        for angles in before.iter().chain(&after) {
+            phase += angles.alpha;
+        }
+
+        KakDecomposition {
+            phase,
+            before,
+            coefficients,
+            after,
+        }
+    }
+
+    /// A circuit with at most three CNOTs equal to the decomposed unitary,
+    /// global phase included.
+    pub fn circuit(&self) -> Circuit {
+        let mut c = Circuit::new(2);
+        for (q, angles) in self.before.iter().enumerate() {
+            c.rz(q, angles.delta).ry(q, angles.gamma).rz(q, angles.beta);
+        }
+
+        // exp(i(a XX + b YY + c ZZ)) = e^(iπ/4) times the circuit below,
+        // after Vatan and Williams; the phase is absorbed into φ
+        let [a, b, cc] = self.coefficients;
+        if [a, b, cc].iter().any(|x| x.abs() > 1e-12) {
+            c.rz(0, -FRAC_PI_2)
+                .cnot(0, 1)
+                .rz(1, FRAC_PI_2 - 2.0 * cc)
+                .ry(0, 2.0 * a - FRAC_PI_2)
+                .cnot(1, 0)
+                .ry(0, FRAC_PI_2 - 2.0 * b)
+                .cnot(0, 1)
+                .rz(1, FRAC_PI_2);
+        }
+
+        for (q, angles) in self.after.iter().enumerate() {
+            c.rz(q, angles.delta).ry(q, angles.gamma).rz(q, angles.beta);
+        }
+        c.add_global_phase(self.circuit_phase());
+        c.optimized()
+    }
+
+    /// The global phase of [`KakDecomposition::circuit`] relative to the
+    /// decomposed unitary.
+    pub fn circuit_phase(&self) -> f64 {
+        if self.coefficients.iter().any(|x| x.abs() > 1e-12) {
+            self.phase + FRAC_PI_4
+        } else {
+            self.phase
+        }
+    }
+}
+
+/// The entries of a single-qubit operator, indexed `[ket][bra]`.
+pub(super) fn single_qubit_matrix(u: &Operator<C>) -> [[C; 2]; 2] {
+    let mut m = [[C::new(0.0, 0.0); 2]; 2];
+    for (scalar, ket, bra, _) in u.terms() {
+        m[ket as usize][bra as usize] += scalar;
+    }
+    m
+}
+
+type M4 = [[C; 4]; 4];
+
+const PAULI_X: [[C; 2]; 2] = [
+    [C { re: 0.0, im: 0.0 }, C { re: 1.0, im: 0.0 }],
+    [C { re: 1.0, im: 0.0 }, C { re: 0.0, im: 0.0 }],
+];
+const PAULI_Y: [[C; 2]; 2] = [
+    [C { re: 0.0, im: 0.0 }, C { re: 0.0, im: -1.0 }],
+    [C { re: 0.0, im: 1.0 }, C { re: 0.0, im: 0.0 }],
+];
+const PAULI_Z: [[C; 2]; 2] = [
+    [C { re: 1.0, im: 0.0 }, C { re: 0.0, im: 0.0 }],
+    [C { re: 0.0, im: 0.0 }, C { re: -1.0, im: 0.0 }],
+];
+
+fn unit(phi: f64) -> C {
+    C::new(phi.cos(), phi.sin())
+}
+
+/// The columns of the magic (Bell-like) basis.
+fn magic_basis() -> M4 {
+    let (o, r, i) = (
+        C::new(0.0, 0.0),
+        C::new(std::f64::consts::FRAC_1_SQRT_2, 0.0),
+        C::new(0.0, std::f64::consts::FRAC_1_SQRT_2),
+    );
+    [[r, o, o, i], [o, i, r, o], [o, i, -r, o], [r, o, o, -i]]
+}
+
+fn mul(a: &M4, b: &M4) -> M4 {
+    std::array::from_fn(|i| {
+        std::array::from_fn(|j| (0..4).fold(C::new(0.0, 0.0), |sum, k| sum + a[i][k] * b[k][j]))
+    })
+}
+
+fn scaled(a: &M4, s: C) -> M4 {
+    a.map(|row| row.map(|x| x * s))
+}
+
+fn transpose(a: &M4) -> M4 {
+    std::array::from_fn(|i| std::array::from_fn(|j| a[j][i]))
+}
+
+fn adjoint(a: &M4) -> M4 {
+    std::array::from_fn(|i| std::array::from_fn(|j| a[j][i].conjugate()))
+}
+
+/// The matrix of `a ⊗ b`, with `a` on the high bit of the index.
+fn kron(a: &[[C; 2]; 2], b: &[[C; 2]; 2]) -> M4 {
+    std::array::from_fn(|r| std::array::from_fn(|c| a[r >> 1][c >> 1] * b[r & 1][c & 1]))
+}
+
+/// Laplace expansion along the first row.
+fn det(a: &M4) -> C {
+    let minor = |skip: usize| {
+        let cols: Vec<usize> = (0..4).filter(|&c| c != skip).collect();
+        let m = |r: usize, c: usize| a[r][cols[c]];
+        m(1, 0) * (m(2, 1) * m(3, 2) - m(2, 2) * m(3, 1))
+            - m(1, 1) * (m(2, 0) * m(3, 2) - m(2, 2) * m(3, 0))
+            + m(1, 2) * (m(2, 0) * m(3, 1) - m(2, 1) * m(3, 0))
+    };
+    (0..4).fold(C::new(0.0, 0.0), |sum, c| {
+        let term = a[0][c] * minor(c);
+        if c % 2 == 0 {
+            sum + term
+        } else {
+            sum - term
+        }
+    })
+}
+
+fn real_det(a: &[[f64; 4]; 4]) -> f64 {
+    det(&a.map(|row| row.map(|x| C::new(x, 0.0)))).re
+}
+
+/// A real orthogonal matrix whose columns are eigenvectors of the complex
+/// symmetric unitary `s`. Its real and imaginary parts are commuting real
+/// symmetric matrices, so a generic combination of the two has the same
+/// eigenvectors; a few combinations are tried in case one is degenerate.
+fn real_eigenvectors(s: &M4) -> [[f64; 4]; 4] {
+    for weight in [0.618, 1.387, 2.213, 0.291] {
+        let p = jacobi(&s.map(|row| row.map(|x| x.re + weight * x.im)));
+        let pc = p.map(|row| row.map(|x| C::new(x, 0.0)));
+        let d = mul(&mul(&transpose(&pc), s), &pc);
+        let off: f64 = (0..4)
+            .flat_map(|i| (0..4).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| d[i][j].modulus())
+            .sum();
+        if off < 1e-9 {
+            return p;
+        }
+    }
+    panic!("KAK decomposition needs a unitary operator")
+}
+
+/// The eigenvectors, as columns, of a real symmetric matrix by cyclic Jacobi
+/// rotations.
+fn jacobi(a: &[[f64; 4]; 4]) -> [[f64; 4]; 4] {
+    let mut a = *a;
+    let mut v: [[f64; 4]; 4] =
+        std::array::from_fn(|i| std::array::from_fn(|j| (i == j) as u8 as f64));
+
+    for _ in 0..100 {
+        let off: f64 = (0..4)
+            .flat_map(|i| (i + 1..4).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        if off < 1e-30 {
+            break;
+        }
+
+        for p in 0..4 {
+            for q in p + 1..4 {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (row_p, row_q) = (a[p], a[q]);
+                for k in 0..4 {
+                    a[p][k] = c * row_p[k] - s * row_q[k];
+                    a[q][k] = s * row_p[k] + c * row_q[k];
+                }
+                for row in v.iter_mut() {
+                    let (vp, vq) = (row[p], row[q]);
+                    row[p] = c * vp - s * vq;
+                    row[q] = s * vp + c * vq;
+                }
+            }
+        }
+    }
+    v
+}
+
+/// Splits `k = e^(iψ) a ⊗ b` into determinant-one factors, with `a` on the
+/// high bit of the index.
+fn local_factors(k: &M4) -> ([[C; 2]; 2], [[C; 2]; 2], f64) {
+    let (row, col) = (0..16)
+        .map(|x| (x / 4, x % 4))
+        .max_by(|&(r1, c1), &(r2, c2)| k[r1][c1].modulus().total_cmp(&k[r2][c2].modulus()))
+        .unwrap();
+    let (i, j) = (row >> 1, col >> 1);
+
+    let block: [[C; 2]; 2] =
+        std::array::from_fn(|r| std::array::from_fn(|c| k[2 * i + r][2 * j + c]));
+    let b = normalized(&block);
+    let (r, c) = (row & 1, col & 1);
+    let a: [[C; 2]; 2] =
+        std::array::from_fn(|x| std::array::from_fn(|y| k[2 * x + r][2 * y + c] / b[r][c]));
+    let a_normalized = normalized(&a);
+
+    let ratio = a[i][j] / a_normalized[i][j];
+    (a_normalized, b, ratio.arg())
+}
+
+/// `m` scaled to determinant one.
+pub(super) fn normalized(m: &[[C; 2]; 2]) -> [[C; 2]; 2] {
+    let det = m[0][0] * m[1][1] - m[0][1] * m[1][0];
+    let scale = C::new(det.modulus().sqrt(), 0.0) * unit(det.arg() / 2.0);
+    m.map(|row| row.map(|x| x / scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{single_qubit_matrix, EulerAngles, KakDecomposition};
+    use crate::circuit::{Circuit, Gate};
+    use crate::ket::Operator;
+    use crate::Complex as C;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn recompose(angles: &EulerAngles) -> Operator<C<f64>> {
+        let phase = C::new(angles.alpha.cos(), angles.alpha.sin());
+        Operator::rz(angles.beta) * Operator::ry(angles.gamma) * Operator::rz(angles.delta) * phase
+    }
+
+    fn assert_close(a: &Operator<C<f64>>, b: &Operator<C<f64>>) {
+        let (a, b) = (single_qubit_matrix(a), single_qubit_matrix(b));
+        for (x, y) in a.iter().flatten().zip(b.iter().flatten()) {
+            assert!((*x - *y).modulus() < EPSILON, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn decomposes_standard_gates() {
+        for gate in [
+            Gate::H,
+            Gate::X,
+            Gate::Y,
+            Gate::Z,
+            Gate::S,
+            Gate::T,
+            Gate::I,
+        ] {
+            let u = gate.operator();
+            assert_close(&recompose(&EulerAngles::zyz(&u)), &u);
+        }
+    }
+
+    #[test]
+    fn decomposes_arbitrary_unitaries() {
+        for (theta, phi, lambda, global) in [
+            (0.3, 1.2, -0.7, 0.0),
+            (2.5, -3.0, 0.1, 1.9),
+            (3.1, 0.4, 2.2, -2.8),
+            (1e-3, 0.5, 0.5, 0.3),
+        ] {
+            let u = Operator::u3(theta, phi, lambda) * C::new(f64::cos(global), f64::sin(global));
+            let angles = EulerAngles::zyz(&u);
+            assert_close(&recompose(&angles), &u);
+        }
+    }
+
+    #[test]
+    fn known_angles() {
+        let angles = EulerAngles::zyz(&Operator::ry(0.8));
+        assert!(angles.alpha.abs() < EPSILON);
+        assert!((angles.gamma - 0.8).abs() < EPSILON);
+        assert!(angles.beta.abs() < EPSILON && angles.delta.abs() < EPSILON);
+    }
+
+    #[test]
+    fn circuit_keeps_the_global_phase() {
+        let u = Operator::u3(1.1, 0.6, -2.0);
+        let angles = EulerAngles::zyz(&u);
+
+        let state = angles.circuit().simulate().amplitudes(1);
+        let expected = (u * crate::ket::State::basis(0, 1)).amplitudes(1);
+        for (a, b) in state.iter().zip(&expected) {
+            assert!((*a - *b).modulus() < EPSILON);
+        }
+    }
+
+    fn assert_kak(u: &Operator<C<f64>>) -> Circuit {
+        let kak = KakDecomposition::new(u);
+        let circuit = kak.circuit();
+        let v = circuit.unitary();
+
+        let (mut a, mut b) = ([[C::new(0.0, 0.0); 4]; 4], [[C::new(0.0, 0.0); 4]; 4]);
+        for (scalar, ket, bra, _) in u.terms() {
+            a[ket as usize][bra as usize] += scalar;
+        }
+        for (scalar, ket, bra, _) in v.terms() {
+            b[ket as usize][bra as usize] += scalar;
+        }
+        for (x, y) in a.iter().flatten().zip(b.iter().flatten()) {
+            assert!((*x - *y).modulus() < EPSILON, "{a:?} != {b:?}");
+        }
+        circuit
+    }
+
+    fn cnots(c: &Circuit) -> usize {
+        c.gate_counts().get("cx").copied().unwrap_or(0)
+    }
+
+    #[test]
+    fn kak_of_standard_gates() {
+        for gate in [Gate::Cx, Gate::Cz, Gate::Swap] {
+            assert!(cnots(&assert_kak(&gate.operator())) <= 3);
+        }
+    }
+
+    #[test]
+    fn kak_of_local_unitaries_needs_no_cnots() {
+        let u = Operator::u3(0.4, 1.0, -0.3).embed(&[0], 2)
+            * Operator::u3(2.0, 0.1, 0.9).embed(&[1], 2);
+        assert_eq!(cnots(&assert_kak(&u)), 0);
+    }
+
+    #[test]
+    fn kak_of_arbitrary_unitaries() {
+        let mut c = Circuit::new(2);
+        c.u3(0, 0.3, 1.2, -0.7)
+            .u3(1, 2.5, -3.0, 0.1)
+            .cnot(0, 1)
+            .ry(0, 0.8)
+            .rz(1, -1.3)
+            .cnot(1, 0)
+            .rx(0, 0.2)
+            .ry(1, 2.9)
+            .cz(0, 1)
+            .u3(0, 1.7, 0.5, 0.5)
+            .h(1)
+            .cnot(0, 1)
+            .t(1);
+        let u = c.unitary() * C::new(f64::cos(0.7), f64::sin(0.7));
+        assert_eq!(cnots(&assert_kak(&u)), 3);
+
+        let u = Gate::Cx.operator() * Operator::rx(0.9).embed(&[1], 2) * Gate::Swap.operator();
+        assert_kak(&u);
+    }
+}
@@ -0,0 +1,416 @@
+//! Rewriting circuits into a basis gate set, such as {Rz, √X, CNOT} for
+//! hardware or {H, T, CNOT} for counting gates. Every gate goes through Rz
+//! rotations and Hadamards on its way to the basis, so that these can be
+//! merged and cancelled with [`Circuit::optimized`] before they are rewritten
+//! in turn.
+
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use super::decompose::expand;
+use super::{Circuit, EulerAngles, Gate, GateKind, Instruction};
+
+impl Circuit {
+    /// An equivalent circuit, global phase included, using only gates of the
+    /// `basis` kinds. The basis needs CNOT or CZ for multi-qubit gates, and
+    /// for single-qubit gates a Z rotation (Rz, P or U3) with something to
+    /// turn the axis (H, √X, Rx, Ry or U3). Bases without a continuous Z
+    /// rotation, such as {H, T, CNOT}, only fit angles that are multiples
+    /// of π/4; [`Circuit::approximate`] gets close to the others.
+    ///
+    /// Panics on angles the basis cannot express exactly, and on unbound
+    /// parameters unless the basis has their gate. The phase picked up by a
+    /// classically conditioned gate is dropped, as it is global to the
+    /// branch where the gate applies.
+    pub fn transpile(&self, basis: &[GateKind]) -> Circuit {
+        let mut loose = basis.to_vec();
+        loose.extend([GateKind::H, GateKind::Rz]);
+        self.rewritten(&loose)
+            .optimized()
+            .rewritten(basis)
+            .optimized()
+            .rewritten(basis)
+    }
+
+    /// The circuit with every gate outside `basis` rewritten into it.
+    fn rewritten(&self, basis: &[GateKind]) -> Circuit {
+        let mut c = Circuit {
+            instructions: vec![],
+            ..self.clone()
+        };
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => {
+                    let mut gates = vec![];
+                    let phase = rewrite(gate, qubits, basis, &mut gates);
+                    c.instructions.extend(
+                        gates
+                            .into_iter()
+                            .map(|(gate, qubits)| Instruction::Gate { gate, qubits }),
+                    );
+                    c.add_global_phase(phase);
+                }
+                Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => {
+                    let mut gates = vec![];
+                    rewrite(gate, qubits, basis, &mut gates);
+                    c.instructions
+                        .extend(
+                            gates
+                                .into_iter()
+                                .map(|(gate, qubits)| Instruction::Conditional {
+                                    clbit: *clbit,
+                                    gate,
+                                    qubits,
+                                }),
+                        );
+                }
+                Instruction::While {
+                    clbit,
+                    value,
+                    max_iterations,
+                    body,
+                } => c.instructions.push(Instruction::While {
+                    clbit: *clbit,
+                    value: *value,
+                    max_iterations: *max_iterations,
+                    body: Box::new(body.transpile(basis)),
+                }),
+                other => c.instructions.push(other.clone()),
+            }
+        }
+        c
+    }
+}
+
+/// Appends `gate` on `qubits` to `out` as gates of `basis`, returning the
+/// global phase picked up on the way.
+fn rewrite(
+    gate: &Gate,
+    qubits: &[usize],
+    basis: &[GateKind],
+    out: &mut Vec<(Gate, Vec<usize>)>,
+) -> f64 {
+    if basis.contains(&gate.kind()) {
+        out.push((gate.clone(), qubits.to_vec()));
+        return 0.0;
+    }
+
+    let (gates, mut phase) = step(gate, qubits, basis);
+    for (gate, qubits) in gates {
+        phase += rewrite(&gate, &qubits, basis, out);
+    }
+    phase
+}
+
+/// One rewrite of a gate outside the basis, towards Rz, H and CNOT, and from
+/// those into the basis, with the global phase it leaves behind.
+fn step(gate: &Gate, qubits: &[usize], basis: &[GateKind]) -> (Vec<(Gate, Vec<usize>)>, f64) {
+    let has = |kind| basis.contains(&kind);
+    let q = qubits[0];
+    let rz = |theta: f64| (Gate::Rz(theta.into()), vec![q]);
+    let h = || (Gate::H, vec![q]);
+
+    match gate {
+        Gate::Ccx | Gate::Mcx(_) => (expand(gate, qubits, &[]), 0.0),
+        Gate::Swap => {
+            let (a, b) = (qubits[0], qubits[1]);
+            let cx = |c, t| (Gate::Cx, vec![c, t]);
+            (vec![cx(a, b), cx(b, a), cx(a, b)], 0.0)
+        }
+        Gate::Cz | Gate::Cx => {
+            let other = match gate {
+                Gate::Cz => Gate::Cx,
+                _ if has(GateKind::Cz) => Gate::Cz,
+                _ => panic!("the basis has no two-qubit gate"),
+            };
+            let t = (Gate::H, vec![qubits[1]]);
+            (vec![t.clone(), (other, qubits.to_vec()), t], 0.0)
+        }
+        Gate::I => (vec![], 0.0),
+        Gate::H => {
+            if has(GateKind::Sx) {
+                (
+                    vec![rz(FRAC_PI_2), (Gate::Sx, vec![q]), rz(FRAC_PI_2)],
+                    FRAC_PI_4,
+                )
+            } else if has(GateKind::Rx) {
+                let rx = (Gate::Rx(FRAC_PI_2.into()), vec![q]);
+                (vec![rz(FRAC_PI_2), rx, rz(FRAC_PI_2)], FRAC_PI_2)
+            } else if has(GateKind::Ry) {
+                (
+                    vec![rz(PI), (Gate::Ry(FRAC_PI_2.into()), vec![q])],
+                    FRAC_PI_2,
+                )
+            } else if has(GateKind::U3) {
+                let u3 = Gate::U3(FRAC_PI_2.into(), 0.0.into(), PI.into());
+                (vec![(u3, vec![q])], 0.0)
+            } else {
+                panic!("the basis has no gate to stand in for h")
+            }
+        }
+        Gate::Rz(theta) => z_rotation(theta.value(), q, basis),
+        Gate::Phase(lambda) => (
+            vec![(Gate::Rz(lambda.clone()), vec![q])],
+            lambda.value() / 2.0,
+        ),
+        Gate::Z => (vec![rz(PI)], FRAC_PI_2),
+        Gate::S => (vec![rz(FRAC_PI_2)], FRAC_PI_4),
+        Gate::Sdg => (vec![rz(-FRAC_PI_2)], -FRAC_PI_4),
+        Gate::T => (vec![rz(FRAC_PI_4)], PI / 8.0),
+        Gate::Tdg => (vec![rz(-FRAC_PI_4)], -PI / 8.0),
+        Gate::X => (vec![h(), rz(PI), h()], FRAC_PI_2),
+        Gate::Sx => (vec![h(), rz(FRAC_PI_2), h()], FRAC_PI_4),
+        Gate::Sxdg => (vec![h(), rz(-FRAC_PI_2), h()], -FRAC_PI_4),
+        Gate::Rx(theta) => (vec![h(), (Gate::Rz(theta.clone()), vec![q]), h()], 0.0),
+        // Ry(θ) = S Rx(θ) S†
+        Gate::Ry(theta) => (
+            vec![
+                rz(-FRAC_PI_2),
+                h(),
+                (Gate::Rz(theta.clone()), vec![q]),
+                h(),
+                rz(FRAC_PI_2),
+            ],
+            0.0,
+        ),
+        Gate::Y | Gate::U3(..) => {
+            let EulerAngles {
+                alpha,
+                beta,
+                gamma,
+                delta,
+            } = EulerAngles::zyz(&gate.operator());
+            let ry = (Gate::Ry(gamma.into()), vec![q]);
+            (vec![rz(delta), ry, rz(beta)], alpha)
+        }
+    }
+}
+
+/// Rz(θ) = e^(−iθ/2) P(θ) on `q`, as a phase gate of the basis or, failing
+/// that, as the fewest of T, S, Z and their inverses.
+fn z_rotation(theta: f64, q: usize, basis: &[GateKind]) -> (Vec<(Gate, Vec<usize>)>, f64) {
+    let phase = -theta / 2.0;
+    if basis.contains(&GateKind::Phase) {
+        return (vec![(Gate::Phase(theta.into()), vec![q])], phase);
+    }
+    if basis.contains(&GateKind::U3) {
+        let u3 = Gate::U3(0.0.into(), 0.0.into(), theta.into());
+        return (vec![(u3, vec![q])], phase);
+    }
+
+    let eighths = theta / FRAC_PI_4;
+    let no_exact_form = || -> ! { panic!("rz({theta}) has no exact form in the basis") };
+    if (eighths - eighths.round()).abs() > 1e-9 {
+        no_exact_form();
+    }
+    let k = (eighths.round() as i64).rem_euclid(8) as usize;
+
+    // breadth first over the eighths of a turn reachable so far
+    let steps: Vec<(Gate, usize)> = [
+        (Gate::T, 1),
+        (Gate::S, 2),
+        (Gate::Z, 4),
+        (Gate::Sdg, 6),
+        (Gate::Tdg, 7),
+    ]
+    .into_iter()
+    .filter(|(gate, _)| basis.contains(&gate.kind()))
+    .collect();
+    let mut paths: Vec<Option<Vec<Gate>>> = vec![None; 8];
+    paths[0] = Some(vec![]);
+    let mut frontier = vec![0];
+    while paths[k].is_none() && !frontier.is_empty() {
+        let mut next = vec![];
+        for from in frontier {
+            for (gate, turn) in &steps {
+                let to = (from + turn) % 8;
+                if paths[to].is_none() {
+                    let mut path = paths[from].clone().unwrap();
+                    path.push(gate.clone());
+                    paths[to] = Some(path);
+                    next.push(to);
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    let Some(path) = paths[k].take() else {
+        no_exact_form()
+    };
+    (
+        path.into_iter().map(|gate| (gate, vec![q])).collect(),
+        phase,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::circuit::{Circuit, Gate, GateKind, Instruction};
+    use crate::ket::State;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    const IBM: [GateKind; 3] = [GateKind::Rz, GateKind::Sx, GateKind::Cx];
+    const CLIFFORD_T: [GateKind; 3] = [GateKind::H, GateKind::T, GateKind::Cx];
+
+    /// Whether `a` and `b` have the same unitary, global phase included.
+    fn same_unitary(a: &Circuit, b: &Circuit) -> bool {
+        let n = a.num_qubits();
+        let (ua, ub) = (a.unitary(), b.unitary());
+        (0..1 << n).all(|x| {
+            let pa = (ua.clone() * State::basis(x, n)).amplitudes(n);
+            let pb = (ub.clone() * State::basis(x, n)).amplitudes(n);
+            pa.iter()
+                .zip(&pb)
+                .all(|(p, q)| (*p - *q).modulus() < EPSILON)
+        })
+    }
+
+    fn in_basis(c: &Circuit, basis: &[GateKind]) -> bool {
+        c.instructions()
+            .iter()
+            .all(|instruction| match instruction {
+                Instruction::Gate { gate, .. } | Instruction::Conditional { gate, .. } => {
+                    basis.contains(&gate.kind())
+                }
+                Instruction::While { body, .. } => in_basis(body, basis),
+                _ => true,
+            })
+    }
+
+    fn clifford_t() -> Circuit {
+        let mut c = Circuit::new(3);
+        c.h(0)
+            .x(1)
+            .y(2)
+            .z(0)
+            .s(1)
+            .sdg(2)
+            .t(0)
+            .tdg(1)
+            .sx(2)
+            .sxdg(0)
+            .rz(1, PI / 4.0)
+            .rx(2, -PI / 2.0)
+            .ry(0, 3.0 * PI / 4.0)
+            .p(1, PI)
+            .cnot(0, 1)
+            .cz(1, 2)
+            .swap(0, 2)
+            .ccx(0, 1, 2)
+            .id(1);
+        c
+    }
+
+    #[test]
+    fn every_gate_into_the_ibm_basis() {
+        let mut c = Circuit::new(4);
+        c.compose(&clifford_t(), &[0, 1, 2])
+            .u3(0, 1.0, 2.0, 3.0)
+            .rx(1, 0.3)
+            .mcx(&[0, 1, 2], 3)
+            .add_global_phase(0.5);
+
+        let t = c.transpile(&IBM);
+        assert!(in_basis(&t, &IBM));
+        assert!(same_unitary(&t, &c));
+    }
+
+    #[test]
+    fn clifford_t_gates_into_h_t_cnot() {
+        let c = clifford_t();
+        let t = c.transpile(&CLIFFORD_T);
+        assert!(in_basis(&t, &CLIFFORD_T));
+        assert!(same_unitary(&t, &c));
+    }
+
+    #[test]
+    fn other_bases() {
+        let c = Circuit::random(3, 4, &mut StdRng::seed_from_u64(2));
+        for basis in [
+            vec![GateKind::U3, GateKind::Cx],
+            vec![GateKind::Rx, GateKind::Rz, GateKind::Cz],
+            vec![GateKind::Ry, GateKind::Phase, GateKind::Cx],
+            vec![GateKind::H, GateKind::Phase, GateKind::Cz],
+        ] {
+            let t = c.transpile(&basis);
+            assert!(in_basis(&t, &basis), "{basis:?}");
+            assert!(same_unitary(&t, &c), "{basis:?}");
+        }
+    }
+
+    #[test]
+    fn rotations_merge_and_hadamards_cancel() {
+        let mut c = Circuit::new(1);
+        c.h(0).h(0).t(0).t(0);
+        let t = c.transpile(&IBM);
+        assert_eq!(t.len(), 1);
+        assert!(same_unitary(&t, &c));
+
+        // √X on the way in, not three gates for each H
+        let mut c = Circuit::new(1);
+        c.h(0);
+        assert_eq!(c.transpile(&IBM).gate_counts()["sx"], 1);
+    }
+
+    #[test]
+    fn gates_of_the_basis_are_kept() {
+        let mut c = Circuit::new(2);
+        c.t(0).cnot(0, 1).t(1);
+        assert_eq!(c.transpile(&CLIFFORD_T), c);
+    }
+
+    #[test]
+    fn symbolic_rotations_keep_their_parameter() {
+        let mut c = Circuit::new(1);
+        c.rx(0, "theta").ry(0, "phi");
+        let t = c.transpile(&IBM);
+        assert!(in_basis(&t, &IBM));
+        assert_eq!(t.parameters(), ["phi", "theta"]);
+
+        let values = [("theta", 0.4), ("phi", -1.1)];
+        assert!(same_unitary(
+            &t.bind_parameters(&values),
+            &c.bind_parameters(&values)
+        ));
+    }
+
+    #[test]
+    fn conditionals_and_loops_are_rewritten() {
+        let mut body = Circuit::new(2);
+        body.h(0).measure(0, 0);
+        let mut c = Circuit::new(2);
+        c.measure(0, 0)
+            .c_if(0, Gate::Cz, &[0, 1])
+            .while_loop(0, false, &body, 3);
+
+        let t = c.transpile(&IBM);
+        assert!(in_basis(&t, &IBM));
+        assert_eq!(t.num_clbits(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "has no exact form in the basis")]
+    fn discrete_bases_need_multiples_of_pi_over_4() {
+        let mut c = Circuit::new(1);
+        c.rz(0, 0.3);
+        c.transpile(&CLIFFORD_T);
+    }
+
+    #[test]
+    #[should_panic(expected = "the basis has no two-qubit gate")]
+    fn two_qubit_gates_need_cnot_or_cz() {
+        let mut c = Circuit::new(2);
+        c.cnot(0, 1);
+        c.transpile(&[GateKind::Rz, GateKind::Sx]);
+    }
+}
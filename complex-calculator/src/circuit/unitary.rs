@@ -0,0 +1,261 @@
+use num::Zero;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{Circuit, Instruction, StateVector};
+use crate::ket::{KetBra, Operator};
+use crate::Complex;
+
+/// Up to this many qubits circuits are compared on every basis state, which
+/// determines the whole unitary.
+const DENSE_QUBITS: u32 = 8;
+
+/// Random input states tried on larger circuits.
+const PROBES: usize = 8;
+
+impl Circuit {
+    /// The operator U implemented by the circuit, with column x the final
+    /// state of a run from |x⟩. Takes 2^n runs and up to 4^n terms, so is
+    /// meant for small circuits; the circuit may hold only gates and barriers.
+    pub fn unitary(&self) -> Operator<Complex<f64>> {
+        let n = self.num_qubits;
+        let mut ones = vec![];
+        for (x, input) in basis_states(n).enumerate() {
+            for (y, scalar) in self.evolve(input).into_iter().enumerate() {
+                if !scalar.is_zero() {
+                    ones.push(KetBra {
+                        scalar,
+                        ket: y as u64,
+                        bra: x as u64,
+                        n,
+                        d: 2,
+                    });
+                }
+            }
+        }
+
+        Operator {
+            scalar: Complex::new(1.0, 0.0),
+            ones,
+        }
+    }
+
+    /// Whether the two circuits implement the same unitary up to a global
+    /// phase, every output amplitude agreeing to within `eps`.
+    ///
+    /// Up to eight qubits this compares the full unitaries, column by column.
+    /// Beyond that both circuits are run on a few random states instead: a
+    /// unitary that agrees with e^(iφ) V on a random state is e^(iφ) V with
+    /// probability 1, so a difference is missed only by numerical accident.
+    ///
+    /// Both circuits may hold only gates and barriers.
+    pub fn equivalent_to(&self, other: &Circuit, eps: f64) -> bool {
+        if self.num_qubits != other.num_qubits {
+            return false;
+        }
+
+        let n = self.num_qubits;
+        let inputs: Vec<Vec<Complex<f64>>> = if n <= DENSE_QUBITS {
+            basis_states(n).collect()
+        } else {
+            // fixed seed, so that the answer is reproducible
+            let mut rng = StdRng::seed_from_u64(0);
+            (0..PROBES).map(|_| random_state(n, &mut rng)).collect()
+        };
+
+        let mut phase = None;
+        for input in inputs {
+            let a = self.evolve(input.clone());
+            let b = other.evolve(input);
+
+            // e^(iφ) from the first pair, as ⟨b|a⟩ / |⟨b|a⟩|
+            let phase = *phase.get_or_insert_with(|| {
+                let overlap = a
+                    .iter()
+                    .zip(&b)
+                    .fold(Complex::zero(), |sum: Complex<f64>, (x, y)| {
+                        sum + y.conjugate() * *x
+                    });
+                if overlap.modulus() < eps {
+                    Complex::new(1.0, 0.0)
+                } else {
+                    overlap * Complex::new(1.0 / overlap.modulus(), 0.0)
+                }
+            });
+
+            if a.iter()
+                .zip(&b)
+                .any(|(x, y)| (*x - phase * *y).modulus() > eps)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The state `amplitudes` after the gates of the circuit.
+    fn evolve(&self, amplitudes: Vec<Complex<f64>>) -> Vec<Complex<f64>> {
+        let mut sv = StateVector::from_amplitudes(amplitudes);
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => sv.apply(gate, qubits),
+                Instruction::Barrier { .. } | Instruction::Snapshot { .. } => {}
+                _ => panic!("only circuits of gates have a unitary"),
+            }
+        }
+        sv.apply_global_phase(self.global_phase);
+        sv.amplitudes().to_vec()
+    }
+}
+
+/// The amplitudes of |0⟩, |1⟩, … |2^n − 1⟩.
+fn basis_states(n: u32) -> impl Iterator<Item = Vec<Complex<f64>>> {
+    (0..1 << n).map(move |x| {
+        let mut amplitudes = vec![Complex::zero(); 1 << n];
+        amplitudes[x] = Complex::new(1.0, 0.0);
+        amplitudes
+    })
+}
+
+/// A normalised state with uniformly drawn real and imaginary parts.
+fn random_state<R: Rng + ?Sized>(n: u32, rng: &mut R) -> Vec<Complex<f64>> {
+    let amplitudes: Vec<Complex<f64>> = (0..1 << n)
+        .map(|_| Complex::new(rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0)))
+        .collect();
+    let norm = amplitudes
+        .iter()
+        .map(|a| a.mod_squared())
+        .sum::<f64>()
+        .sqrt();
+    amplitudes
+        .into_iter()
+        .map(|a| a * Complex::new(1.0 / norm, 0.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::circuit::{Circuit, Gate};
+    use crate::ket::{Operator, State};
+    use crate::Complex as C;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn assert_close(a: &Operator<C<f64>>, b: &Operator<C<f64>>) {
+        let difference = (a.clone() + b.clone() * C::new(-1.0, 0.0)).simplify();
+        assert!(difference.terms().all(|(v, ..)| v.modulus() < EPSILON));
+    }
+
+    #[test]
+    fn unitary_of_a_bell_circuit() {
+        let mut c = Circuit::new(2);
+        c.h(0).cnot(0, 1);
+
+        let u = c.unitary();
+        assert!(u.is_unitary(EPSILON));
+        let bell = (u * State::basis(0, 2)).amplitudes(2);
+        let s = 0.5_f64.sqrt();
+        for (a, b) in bell.iter().zip([s, 0.0, 0.0, s]) {
+            assert!((*a - C::new(b, 0.0)).modulus() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn unitary_matches_embedded_gates() {
+        let mut c = Circuit::new(3);
+        c.h(2).cnot(2, 0).t(1).cz(0, 1).ry(2, 0.3);
+
+        let mut expected = Operator::identity(3);
+        for (gate, qubits) in [
+            (Gate::H, vec![2]),
+            (Gate::Cx, vec![2, 0]),
+            (Gate::T, vec![1]),
+            (Gate::Cz, vec![0, 1]),
+            (Gate::Ry(0.3.into()), vec![2]),
+        ] {
+            expected = gate.operator().embed(&qubits, 3) * expected;
+        }
+        assert_close(&c.unitary(), &expected);
+    }
+
+    #[test]
+    fn empty_circuit_is_the_identity() {
+        assert_close(&Circuit::new(2).unitary(), &Operator::identity(2));
+    }
+
+    #[test]
+    fn swap_is_three_cnots() {
+        let mut swap = Circuit::new(2);
+        swap.swap(0, 1);
+        let mut cnots = Circuit::new(2);
+        cnots.cnot(0, 1).cnot(1, 0).cnot(0, 1);
+        assert!(swap.equivalent_to(&cnots, EPSILON));
+
+        let mut two = Circuit::new(2);
+        two.cnot(0, 1).cnot(1, 0);
+        assert!(!swap.equivalent_to(&two, EPSILON));
+    }
+
+    #[test]
+    fn ignores_global_phase() {
+        // Rz(θ) = e^(−iθ/2) P(θ)
+        let mut rz = Circuit::new(1);
+        rz.rz(0, 0.9);
+        let mut p = Circuit::new(1);
+        p.p(0, 0.9);
+        assert!(rz.equivalent_to(&p, EPSILON));
+
+        // but not relative phase
+        let mut z = Circuit::new(1);
+        z.z(0);
+        assert!(!z.equivalent_to(&Circuit::new(1), EPSILON));
+    }
+
+    #[test]
+    fn catches_differences_away_from_zero() {
+        // agree on |00⟩, where the controls are off
+        let mut cz = Circuit::new(2);
+        cz.cz(0, 1);
+        assert!(!cz.equivalent_to(&Circuit::new(2), EPSILON));
+    }
+
+    #[test]
+    fn large_circuits_are_probed() {
+        let mut a = Circuit::new(10);
+        let mut b = Circuit::new(10);
+        for q in 0..10 {
+            a.h(q);
+            b.ry(q, PI / 2.0).x(q);
+        }
+        a.cz(3, 9).rz(9, 0.4);
+        b.h(9).cnot(3, 9).h(9).rz(9, 0.4);
+        assert!(a.equivalent_to(&b, EPSILON));
+
+        b.t(0);
+        assert!(!a.equivalent_to(&b, EPSILON));
+    }
+
+    #[test]
+    fn different_widths_differ() {
+        assert!(!Circuit::new(1).equivalent_to(&Circuit::new(2), EPSILON));
+    }
+
+    #[test]
+    #[should_panic(expected = "only circuits of gates")]
+    fn rejects_measurements() {
+        let mut c = Circuit::new(1);
+        c.measure(0, 0);
+        c.equivalent_to(&c, EPSILON);
+    }
+
+    #[test]
+    fn barriers_are_ignored() {
+        let mut a = Circuit::new(2);
+        a.h(0).barrier(&[0, 1]).append(Gate::Cx, &[0, 1]);
+        let mut b = Circuit::new(2);
+        b.h(0).cnot(0, 1);
+        assert!(a.equivalent_to(&b, EPSILON));
+    }
+}
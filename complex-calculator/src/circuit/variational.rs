@@ -0,0 +1,171 @@
+//! Parameterised circuits as variational ansätze: expectation values of a
+//! Hamiltonian in the state they prepare, and their gradients by the
+//! parameter-shift rule.
+
+use std::f64::consts::FRAC_PI_2;
+
+use super::{Circuit, Instruction, Param};
+use crate::pauli::PauliSum;
+
+impl Circuit {
+    /// A hardware-efficient ansatz on n qubits: `layers` rounds of an Ry on
+    /// every qubit followed by a chain of CNOTs, then a last round of Ry.
+    /// The angles are the parameters `theta0`, `theta1`, and so on, in the
+    /// order the gates are applied.
+    pub fn hardware_efficient_ansatz(n: u32, layers: usize) -> Circuit {
+        let mut c = Circuit::new(n);
+        let mut k = 0;
+        for layer in 0..=layers {
+            for q in 0..n as usize {
+                c.ry(q, format!("theta{k}").as_str());
+                k += 1;
+            }
+            if layer < layers {
+                for q in 1..n as usize {
+                    c.cnot(q - 1, q);
+                }
+            }
+        }
+        c
+    }
+
+    /// ⟨ψ|H|ψ⟩ for the state ψ the circuit prepares from |0…0⟩ with
+    /// `values` bound, by [`Circuit::simulate`].
+    pub fn expectation(&self, hamiltonian: &PauliSum, values: &[(&str, f64)]) -> f64 {
+        assert_eq!(
+            hamiltonian.num_qubits(),
+            self.num_qubits as usize,
+            "the Hamiltonian acts on {} qubits, not the circuit's {}",
+            hamiltonian.num_qubits(),
+            self.num_qubits
+        );
+        let state = self.bind_parameters(values).simulate();
+        hamiltonian.expectation(&state.amplitudes(self.num_qubits))
+    }
+
+    /// The gradient of [`Circuit::expectation`] at `values`, one entry per
+    /// parameter in the order of [`Circuit::parameters`], by the
+    /// parameter-shift rule: every angle θ enters as a rotation whose
+    /// generator has eigenvalues ±1/2, so the derivative along it is
+    /// (E(θ + π/2) − E(θ − π/2))/2, exactly. A parameter used by several
+    /// gates sums the shifts of each use.
+    pub fn parameter_shift_gradient(
+        &self,
+        hamiltonian: &PauliSum,
+        values: &[(&str, f64)],
+    ) -> Vec<f64> {
+        self.parameters()
+            .into_iter()
+            .map(|name| {
+                let value = values
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .unwrap_or_else(|| panic!("no value for parameter {name}"))
+                    .1;
+                (0..self.uses(name))
+                    .map(|k| {
+                        let shifted = |shift: f64| {
+                            self.with_use_bound(name, k, value + shift)
+                                .expectation(hamiltonian, values)
+                        };
+                        (shifted(FRAC_PI_2) - shifted(-FRAC_PI_2)) / 2.0
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// The number of angles given by the parameter `name`. Only gates are
+    /// searched, since an ansatz has nothing else.
+    fn uses(&self, name: &str) -> usize {
+        self.instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Gate { gate, .. } => Some(gate.params()),
+                _ => None,
+            })
+            .flatten()
+            .filter(|p| matches!(p, Param::Symbol(s) if s == name))
+            .count()
+    }
+
+    /// Copy with the k-th use of the parameter `name` set to `value`, and the
+    /// others left symbolic.
+    fn with_use_bound(&self, name: &str, k: usize, value: f64) -> Circuit {
+        let mut c = self.clone();
+        let param = c
+            .instructions
+            .iter_mut()
+            .filter_map(|instruction| match instruction {
+                Instruction::Gate { gate, .. } => Some(gate.params_mut()),
+                _ => None,
+            })
+            .flatten()
+            .filter(|p| matches!(p, Param::Symbol(s) if s == name))
+            .nth(k)
+            .unwrap();
+        *param = Param::Value(value);
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+    use crate::pauli::PauliSum;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn hamiltonian(terms: &[(f64, &str)]) -> PauliSum {
+        let mut h = PauliSum::new();
+        for &(c, p) in terms {
+            h.add(c, p.parse().unwrap());
+        }
+        h
+    }
+
+    #[test]
+    fn ansatz_layout() {
+        let c = Circuit::hardware_efficient_ansatz(3, 2);
+        assert_eq!(c.parameters().len(), 9);
+        assert_eq!(c.gate_counts()["ry"], 9);
+        assert_eq!(c.gate_counts()["cx"], 4);
+    }
+
+    #[test]
+    fn expectation_of_a_rotated_qubit() {
+        // Ry(θ)|0⟩ has ⟨Z⟩ = cos θ and ⟨X⟩ = sin θ
+        let mut c = Circuit::new(1);
+        c.ry(0, "t");
+        let h = hamiltonian(&[(2.0, "Z"), (-1.0, "X")]);
+        let e = c.expectation(&h, &[("t", 0.4)]);
+        assert!((e - (2.0 * 0.4f64.cos() - 0.4f64.sin())).abs() < EPSILON);
+    }
+
+    #[test]
+    fn gradient_matches_finite_differences() {
+        let mut c = Circuit::hardware_efficient_ansatz(2, 1);
+        // a parameter used twice, and one inside a U3
+        c.rz(1, "theta0").u3(0, "phi", 0.3, "theta1");
+        let h = hamiltonian(&[(0.5, "ZZ"), (0.8, "XI"), (-0.3, "YX"), (0.1, "IZ")]);
+        let names = c.parameters();
+        let values: Vec<(&str, f64)> = names
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| (n, 0.2 + 0.37 * i as f64))
+            .collect();
+
+        let gradient = c.parameter_shift_gradient(&h, &values);
+        assert_eq!(gradient.len(), names.len());
+        let h_step = 1e-5;
+        for (i, g) in gradient.iter().enumerate() {
+            let nudged = |d: f64| {
+                let mut v = values.clone();
+                v[i].1 += d;
+                c.expectation(&h, &v)
+            };
+            let numeric = (nudged(h_step) - nudged(-h_step)) / (2.0 * h_step);
+            assert!((g - numeric).abs() < 1e-6, "{}: {g} != {numeric}", names[i]);
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+
+use num::{One, Zero};
+
+use crate::channel::Channel;
+use crate::ket::{KetBra, Operator, State};
+use crate::{Complex, Conjugate};
+
+/// A mixed state ρ = Σ pᵢ |ψᵢ⟩⟨ψᵢ|, stored as a sparse [`Operator`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityMatrix<T> {
+    op: Operator<T>,
+    n: u32,
+}
+
+impl<T> DensityMatrix<T>
+where
+    T: Copy + Conjugate + Zero + One + std::ops::Mul<Output = T> + std::ops::AddAssign + PartialEq,
+{
+    /// The pure state |ψ⟩⟨ψ|.
+    pub fn from_state(state: &State<T>) -> Self {
+        let mut ones = HashMap::new();
+        add_outer(&mut ones, T::one(), state);
+        Self::from_terms(ones, num_qubits(state), state.superpositions[0].d)
+    }
+
+    /// The mixture Σ pᵢ |ψᵢ⟩⟨ψᵢ| of an ensemble of (probability, state) pairs.
+    pub fn from_ensemble(ensemble: &[(f64, State<T>)]) -> Self
+    where
+        T: From<f64>,
+    {
+        assert!(!ensemble.is_empty(), "empty ensemble");
+        let n = num_qubits(&ensemble[0].1);
+        let mut ones = HashMap::new();
+
+        for (p, state) in ensemble {
+            assert_eq!(
+                num_qubits(state),
+                n,
+                "ensemble states on different qubit counts"
+            );
+            add_outer(&mut ones, T::from(*p), state);
+        }
+
+        Self::from_terms(ones, n, ensemble[0].1.superpositions[0].d)
+    }
+
+    pub(crate) fn from_terms(ones: HashMap<(u64, u64), T>, n: u32, d: u32) -> Self {
+        let mut ones: Vec<KetBra<T>> = ones
+            .into_iter()
+            .filter(|(_, scalar)| *scalar != T::zero())
+            .map(|((ket, bra), scalar)| KetBra {
+                scalar,
+                ket,
+                bra,
+                n,
+                d,
+            })
+            .collect();
+        ones.sort_by_key(|kb| (kb.ket, kb.bra));
+
+        DensityMatrix {
+            op: Operator {
+                scalar: T::one(),
+                ones,
+            },
+            n,
+        }
+    }
+
+    /// Unitary evolution ρ → U ρ U†.
+    pub fn evolve(&self, u: &Operator<T>) -> Self {
+        DensityMatrix {
+            op: u.clone() * self.op.clone() * u.adjoint(),
+            n: self.n,
+        }
+    }
+
+    pub fn trace(&self) -> T {
+        self.op.trace()
+    }
+
+    /// Probability ⟨k|ρ|k⟩ of measuring the basis state |k⟩.
+    pub fn probability(&self, ket: u64) -> T {
+        let mut sum = T::zero();
+        for kb in self
+            .op
+            .ones
+            .iter()
+            .filter(|kb| kb.ket == ket && kb.bra == ket)
+        {
+            sum += kb.scalar;
+        }
+        self.op.scalar * sum
+    }
+
+    /// Probabilities of every basis state with a nonzero diagonal entry,
+    /// ordered by basis index.
+    pub fn probabilities(&self) -> Vec<(u64, T)> {
+        let mut kets: Vec<u64> = self
+            .op
+            .ones
+            .iter()
+            .filter(|kb| kb.ket == kb.bra)
+            .map(|kb| kb.ket)
+            .collect();
+        kets.sort();
+        kets.dedup();
+
+        kets.into_iter().map(|k| (k, self.probability(k))).collect()
+    }
+
+    /// The reduced density matrix of the qubits not listed in `qubits`, which
+    /// are renumbered from 0 in their original order.
+    pub fn trace_out(&self, qubits: &[usize]) -> Self {
+        for (i, &q) in qubits.iter().enumerate() {
+            assert!(
+                q < self.n as usize,
+                "qubit {q} out of range for {} qubits",
+                self.n
+            );
+            assert!(!qubits[..i].contains(&q), "qubit {q} traced out twice");
+        }
+
+        let d = self.op.ones.first().map_or(2, |kb| kb.d);
+        let kept: Vec<usize> = (0..self.n as usize)
+            .filter(|q| !qubits.contains(q))
+            .collect();
+
+        // ρ_A = Σ_e ⟨e|ρ|e⟩ over basis states e of the traced qubits, so only
+        // terms agreeing on those qubits survive
+        let mut ones = HashMap::new();
+        for kb in &self.op.ones {
+            if gather(kb.ket, qubits, d) == gather(kb.bra, qubits, d) {
+                let key = (gather(kb.ket, &kept, d), gather(kb.bra, &kept, d));
+                *ones.entry(key).or_insert(T::zero()) += self.op.scalar * kb.scalar;
+            }
+        }
+
+        Self::from_terms(ones, kept.len() as u32, d)
+    }
+}
+
+impl<T> State<T>
+where
+    T: Copy + Conjugate + Zero + One + std::ops::Mul<Output = T> + std::ops::AddAssign + PartialEq,
+{
+    /// The reduced density matrix left after tracing out `qubits`, see
+    /// [`DensityMatrix::trace_out`].
+    pub fn trace_out(&self, qubits: &[usize]) -> DensityMatrix<T> {
+        DensityMatrix::from_state(self).trace_out(qubits)
+    }
+}
+
+/// The digits of `index` at `positions`, packed in that order.
+fn gather(index: u64, positions: &[usize], d: u32) -> u64 {
+    let d = d as u64;
+    positions
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| index / d.pow(p as u32) % d * d.pow(i as u32))
+        .sum()
+}
+
+impl<T: Copy + Into<Complex<f64>>> DensityMatrix<T> {
+    /// Bloch-sphere coordinates (x, y, z) of a single-qubit density matrix,
+    /// inside the unit ball for mixed states.
+    pub fn bloch_vector(&self) -> (f64, f64, f64) {
+        assert!(
+            self.n == 1,
+            "Bloch vectors are only defined for single qubits"
+        );
+
+        let scalar: Complex<f64> = self.op.scalar.into();
+        let mut rho = [[Complex::zero(); 2]; 2];
+        for kb in &self.op.ones {
+            rho[kb.ket as usize][kb.bra as usize] += scalar * kb.scalar.into();
+        }
+
+        let trace = (rho[0][0] + rho[1][1]).re;
+        assert!(trace != 0.0, "zero density matrix has no Bloch vector");
+
+        (
+            2.0 * rho[1][0].re / trace,
+            2.0 * rho[1][0].im / trace,
+            (rho[0][0] - rho[1][1]).re / trace,
+        )
+    }
+}
+
+impl<T> DensityMatrix<T> {
+    pub fn operator(&self) -> &Operator<T> {
+        &self.op
+    }
+
+    pub fn num_qubits(&self) -> u32 {
+        self.n
+    }
+}
+
+impl DensityMatrix<Complex<f64>> {
+    /// ρ → Σ Kᵢ ρ Kᵢ† with `channel` acting on `qubits`, its first qubit on
+    /// `qubits[0]`.
+    pub fn apply_channel(&self, channel: &Channel, qubits: &[usize]) -> Self {
+        assert_eq!(
+            qubits.len(),
+            channel.num_qubits() as usize,
+            "{} channel acts on {} qubits",
+            channel.name(),
+            channel.num_qubits()
+        );
+
+        let mut op = self.op.clone() * Complex::new(0.0, 0.0);
+        for k in channel.kraus() {
+            let k = k.embed(qubits, self.n);
+            op = op + k.clone() * self.op.clone() * k.adjoint();
+        }
+        DensityMatrix { op, n: self.n }
+    }
+}
+
+fn num_qubits<T>(state: &State<T>) -> u32 {
+    state
+        .superpositions
+        .first()
+        .map(|k| k.n)
+        .expect("state has no terms")
+}
+
+/// Accumulates `weight |ψ⟩⟨ψ|` into `ones`, keyed by (ket, bra).
+fn add_outer<T>(ones: &mut HashMap<(u64, u64), T>, weight: T, state: &State<T>)
+where
+    T: Copy + Conjugate + Zero + std::ops::Mul<Output = T> + std::ops::AddAssign,
+{
+    let weight = weight * state.scalar * state.scalar.conj();
+
+    for a in &state.superpositions {
+        for b in &state.superpositions {
+            *ones.entry((a.ket, b.ket)).or_insert(T::zero()) += weight * a.scalar * b.scalar.conj();
+        }
+    }
+}
+
+impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for DensityMatrix<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DensityMatrix;
+    use crate::ket::{Operator, State};
+    use crate::Complex as C;
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn pure_plus_state() {
+        let plus = State::parse("1/sqrt(2)(|0⟩ + |1⟩)").unwrap();
+        let rho = DensityMatrix::from_state(&plus);
+
+        assert_eq!(rho.operator().ones.len(), 4);
+        assert!((rho.trace().re - 1.0).abs() < EPSILON);
+        for (_, p) in rho.probabilities() {
+            assert!((p.re - 0.5).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn complex_coherences() {
+        let s = State::parse("1/sqrt(2)(|0⟩ + i|1⟩)").unwrap();
+        let rho = DensityMatrix::from_state(&s);
+
+        for kb in &rho.operator().ones {
+            let expected = match (kb.ket, kb.bra) {
+                (0, 1) => C::new(0.0, -0.5),
+                (1, 0) => C::new(0.0, 0.5),
+                _ => C::new(0.5, 0.0),
+            };
+            assert!((kb.scalar - expected).modulus() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn mixed_ensemble() {
+        let rho = DensityMatrix::from_ensemble(&[
+            (0.5, State::<f64>::basis(0, 1)),
+            (0.5, State::<f64>::basis(1, 1)),
+        ]);
+
+        assert_eq!(rho.probabilities(), vec![(0, 0.5), (1, 0.5)]);
+        assert_eq!(rho.operator().ones.len(), 2);
+        assert_eq!(rho.trace(), 1.0);
+    }
+
+    #[test]
+    fn bloch_vector_of_mixed_states() {
+        let plus = State::parse("1/sqrt(2)(|0⟩ + i|1⟩)").unwrap();
+        let (x, y, z) = DensityMatrix::from_state(&plus).bloch_vector();
+        assert!(x.abs() < EPSILON && (y - 1.0).abs() < EPSILON && z.abs() < EPSILON);
+
+        let mixed = DensityMatrix::from_ensemble(&[
+            (0.5, State::<f64>::basis(0, 1)),
+            (0.5, State::<f64>::basis(1, 1)),
+        ]);
+        assert_eq!(mixed.bloch_vector(), (0.0, 0.0, 0.0));
+
+        let biased = DensityMatrix::from_ensemble(&[
+            (0.75, State::<f64>::basis(0, 1)),
+            (0.25, State::<f64>::basis(1, 1)),
+        ]);
+        assert_eq!(biased.bloch_vector(), (0.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn bloch_vector_is_normalised_by_the_trace() {
+        let unnormalised = State::parse("|0⟩ + i|1⟩").unwrap();
+        let (x, y, z) = DensityMatrix::from_state(&unnormalised).bloch_vector();
+        assert!(x.abs() < EPSILON && (y - 1.0).abs() < EPSILON && z.abs() < EPSILON);
+    }
+
+    #[test]
+    fn evolve_with_unitary() {
+        let x = Operator::parse("|0⟩⟨1| + |1⟩⟨0|").unwrap();
+        let rho = DensityMatrix::from_state(&State::parse("|0⟩").unwrap()).evolve(&x);
+
+        assert_eq!(rho.probability(0), C::new(0.0, 0.0));
+        assert_eq!(rho.probability(1), C::new(1.0, 0.0));
+        assert_eq!(rho.num_qubits(), 1);
+    }
+
+    #[test]
+    fn bell_state_is_locally_mixed() {
+        let rho = State::<f64>::bell(0).trace_out(&[1]);
+
+        assert_eq!(rho.num_qubits(), 1);
+        assert_eq!(rho.operator().ones.len(), 2);
+        for (_, p) in rho.probabilities() {
+            assert!((p - 0.5).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn product_state_traces_to_factor() {
+        // |1⟩ on qubit 2, |+⟩ on qubit 1 and |0⟩ on qubit 0
+        let plus = State::parse("1/sqrt(2)(|0⟩ + |1⟩)").unwrap();
+        let one = State::parse("|1⟩").unwrap();
+        let zero = State::parse("|0⟩").unwrap();
+        let psi = one.tensor(&plus).tensor(&zero);
+
+        let rho = psi.trace_out(&[0, 2]);
+        let expected = DensityMatrix::from_state(&plus);
+        assert_eq!(rho.num_qubits(), 1);
+        for (a, b) in rho.operator().ones.iter().zip(&expected.operator().ones) {
+            assert_eq!((a.ket, a.bra), (b.ket, b.bra));
+            assert!((a.scalar - b.scalar).modulus() < EPSILON);
+        }
+
+        let rho = psi.trace_out(&[1]);
+        let probabilities = rho.probabilities();
+        assert_eq!(probabilities.len(), 1);
+        assert_eq!(probabilities[0].0, 0b10);
+        assert!((probabilities[0].1.re - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn trace_out_everything_leaves_trace() {
+        let rho = State::<f64>::ghz(3).trace_out(&[2, 0, 1]);
+        assert_eq!(rho.num_qubits(), 0);
+        assert!((rho.trace() - 1.0).abs() < EPSILON);
+    }
+}
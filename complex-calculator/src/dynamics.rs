@@ -0,0 +1,22 @@
+//! The classical precursors of quantum dynamics, as in the chapter leaping
+//! from classical to quantum systems: marbles on the vertices of a graph,
+//! moved each time step by a matrix whose entry (i, j) says how they go from
+//! vertex j to vertex i. States are column vectors, indexed by vertex, and
+//! evolve by X(t + 1) = M X(t).
+
+mod amplitude;
+mod boolean;
+mod graph;
+mod slits;
+mod stochastic;
+mod validate;
+
+pub use amplitude::AmplitudeMatrix;
+pub use boolean::BoolMatrix;
+pub use graph::{Edge, Graph, GraphError};
+pub use slits::MultiSlit;
+pub use stochastic::StochasticMatrix;
+pub use validate::{is_doubly_stochastic, is_stochastic, is_unitary_dynamics, DynamicsError};
+
+/// The tolerance on sums that should come to exactly one.
+const TOLERANCE: f64 = 1e-9;
@@ -0,0 +1,178 @@
+//! Quantum dynamics: matrices of complex amplitudes, the probability of
+//! going from vertex j to vertex i being the squared modulus of entry
+//! (i, j). Amplitudes arriving at a vertex along different paths add before
+//! they are squared, so they may cancel, which probabilities never do.
+
+use num::{One, Zero};
+
+use super::validate::is_square;
+use super::{is_unitary_dynamics, DynamicsError};
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// A matrix of complex amplitudes, entry (i, j) the amplitude of going from
+/// vertex j to vertex i in one step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmplitudeMatrix {
+    entries: Vec<Vec<C>>,
+}
+
+impl AmplitudeMatrix {
+    /// The dynamics of `entries`, given by rows, which must form a square
+    /// matrix. It need not be unitary, as the slit experiments are not.
+    pub fn new(entries: Vec<Vec<C>>) -> Self {
+        AmplitudeMatrix::try_new(entries).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// The dynamics of `entries`, or why they are not a square matrix.
+    pub fn try_new(entries: Vec<Vec<C>>) -> Result<Self, DynamicsError> {
+        is_square(&entries)?;
+        Ok(AmplitudeMatrix { entries })
+    }
+
+    /// The identity, leaving every amplitude where it is.
+    pub fn identity(n: usize) -> Self {
+        AmplitudeMatrix::new(
+            (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| if i == j { C::one() } else { C::zero() })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// The number of vertices.
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> &[Vec<C>] {
+        &self.entries
+    }
+
+    /// Whether the dynamics is unitary, and so legal quantum dynamics; see
+    /// [`is_unitary_dynamics`] for where it fails.
+    ///
+    /// [`is_unitary_dynamics`]: super::is_unitary_dynamics
+    pub fn is_unitary(&self) -> bool {
+        is_unitary_dynamics(&self.entries).is_ok()
+    }
+
+    /// The state one step after `state`.
+    pub fn apply(&self, state: &[C]) -> Vec<C> {
+        assert_eq!(
+            state.len(),
+            self.size(),
+            "a state on {} vertices is not on {}",
+            state.len(),
+            self.size()
+        );
+        self.entries
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(state)
+                    .fold(C::zero(), |acc, (&m, &x)| acc + m * x)
+            })
+            .collect()
+    }
+
+    /// The state `steps` steps after `state`.
+    pub fn evolve(&self, state: &[C], steps: usize) -> Vec<C> {
+        (0..steps).fold(state.to_vec(), |x, _| self.apply(&x))
+    }
+
+    /// The dynamics of `steps` steps at once, M^steps, by repeated squaring.
+    pub fn power(&self, steps: usize) -> AmplitudeMatrix {
+        let mut result = AmplitudeMatrix::identity(self.size());
+        let (mut square, mut steps) = (self.clone(), steps);
+        while steps > 0 {
+            if steps & 1 == 1 {
+                result = &result * &square;
+            }
+            square = &square * &square;
+            steps >>= 1;
+        }
+        result
+    }
+
+    /// The probabilities of the single steps, the squared moduli of the
+    /// entries. Those of several steps are not the powers of these, as they
+    /// miss the interference between paths.
+    pub fn probabilities(&self) -> Vec<Vec<f64>> {
+        self.entries
+            .iter()
+            .map(|row| row.iter().map(|a| a.mod_squared()).collect())
+            .collect()
+    }
+}
+
+/// The matrix product: `a * b` is the dynamics of `b` followed by `a`.
+impl std::ops::Mul for &AmplitudeMatrix {
+    type Output = AmplitudeMatrix;
+
+    fn mul(self, rhs: &AmplitudeMatrix) -> AmplitudeMatrix {
+        assert_eq!(self.size(), rhs.size(), "dynamics on different vertices");
+        let n = self.size();
+        let entries = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| {
+                        (0..n).fold(C::zero(), |acc, k| {
+                            acc + self.entries[i][k] * rhs.entries[k][j]
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        AmplitudeMatrix { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::{One, Zero};
+
+    use super::{AmplitudeMatrix, C};
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn hadamard() -> AmplitudeMatrix {
+        let h = C::new(0.5f64.sqrt(), 0.0);
+        AmplitudeMatrix::new(vec![vec![h, h], vec![h, -h]])
+    }
+
+    #[test]
+    fn paths_interfere() {
+        let h = hadamard();
+        let start = [C::one(), C::zero()];
+        let once = h.apply(&start);
+        assert!((once[1].mod_squared() - 0.5).abs() < EPSILON);
+        // the two paths to vertex 1 cancel, where probabilities would not
+        let twice = h.evolve(&start, 2);
+        assert!((twice[0] - C::one()).modulus() < EPSILON);
+        assert!(twice[1].modulus() < EPSILON);
+        assert!(h.is_unitary());
+        for p in h.probabilities().concat() {
+            assert!((p - 0.5).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn powers_match_products() {
+        let i = C::new(0.0, 1.0);
+        let m = AmplitudeMatrix::new(vec![
+            vec![C::zero(), i, C::zero()],
+            vec![C::zero(), C::zero(), C::one()],
+            vec![C::one(), C::zero(), C::zero()],
+        ]);
+        assert_eq!(m.power(0), AmplitudeMatrix::identity(3));
+        assert_eq!(m.power(3), &(&m * &m) * &m);
+        let start = [C::one(), C::zero(), C::zero()];
+        assert_eq!(m.evolve(&start, 3), [i, C::zero(), C::zero()]);
+        assert_eq!(m.power(3).apply(&start), m.evolve(&start, 3));
+    }
+}
@@ -0,0 +1,194 @@
+//! Deterministic dynamics: Boolean matrices saying which vertex leads to
+//! which, multiplied with OR for addition and AND for multiplication. Each
+//! state says which vertices hold marbles. When every column has exactly one
+//! entry set, each marble has exactly one place to go, as in the marble
+//! exercises; in general a marble may go to several vertices, or none.
+
+use super::validate::is_square;
+use super::DynamicsError;
+
+/// A Boolean matrix, entry (i, j) set when there is an edge from vertex j to
+/// vertex i.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoolMatrix {
+    entries: Vec<Vec<bool>>,
+}
+
+impl BoolMatrix {
+    /// The dynamics of `entries`, given by rows, which must form a square
+    /// matrix.
+    ///
+    /// ```
+    /// use complex_calculator::dynamics::BoolMatrix;
+    ///
+    /// // the marbles on vertex 0 go to 1, those on 1 to 2, and 2 keeps its own
+    /// let m = BoolMatrix::new(vec![
+    ///     vec![false, false, false],
+    ///     vec![true, false, false],
+    ///     vec![false, true, true],
+    /// ]);
+    /// assert!(m.is_deterministic());
+    /// assert_eq!(m.apply(&[true, false, false]), [false, true, false]);
+    /// assert_eq!(m.evolve(&[true, false, false], 2), [false, false, true]);
+    /// ```
+    pub fn new(entries: Vec<Vec<bool>>) -> Self {
+        BoolMatrix::try_new(entries).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// The dynamics of `entries`, or why they are not a square matrix.
+    pub fn try_new(entries: Vec<Vec<bool>>) -> Result<Self, DynamicsError> {
+        is_square(&entries)?;
+        Ok(BoolMatrix { entries })
+    }
+
+    /// The identity, leaving every marble where it is.
+    pub fn identity(n: usize) -> Self {
+        BoolMatrix::new((0..n).map(|i| (0..n).map(|j| i == j).collect()).collect())
+    }
+
+    /// The number of vertices.
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> &[Vec<bool>] {
+        &self.entries
+    }
+
+    /// Whether every column has exactly one entry set, so that each vertex
+    /// leads to exactly one other.
+    pub fn is_deterministic(&self) -> bool {
+        (0..self.size()).all(|j| self.entries.iter().filter(|row| row[j]).count() == 1)
+    }
+
+    /// Whether the matrix is a permutation, deterministic and reversible: as
+    /// well as the columns, every row has exactly one entry set.
+    pub fn is_permutation(&self) -> bool {
+        self.is_deterministic()
+            && self
+                .entries
+                .iter()
+                .all(|row| row.iter().filter(|&&e| e).count() == 1)
+    }
+
+    /// The state one step after `state`: vertex i is occupied when some
+    /// occupied vertex leads to it.
+    pub fn apply(&self, state: &[bool]) -> Vec<bool> {
+        assert_eq!(
+            state.len(),
+            self.size(),
+            "a state on {} vertices is not on {}",
+            state.len(),
+            self.size()
+        );
+        self.entries
+            .iter()
+            .map(|row| row.iter().zip(state).any(|(&m, &x)| m && x))
+            .collect()
+    }
+
+    /// The state `steps` steps after `state`.
+    pub fn evolve(&self, state: &[bool], steps: usize) -> Vec<bool> {
+        (0..steps).fold(state.to_vec(), |x, _| self.apply(&x))
+    }
+
+    /// The dynamics of `steps` steps at once, M^steps, by repeated squaring.
+    /// Entry (i, j) is set when there is a path of exactly `steps` edges from
+    /// vertex j to vertex i.
+    pub fn power(&self, steps: usize) -> BoolMatrix {
+        let mut result = BoolMatrix::identity(self.size());
+        let (mut square, mut steps) = (self.clone(), steps);
+        while steps > 0 {
+            if steps & 1 == 1 {
+                result = &result * &square;
+            }
+            square = &square * &square;
+            steps >>= 1;
+        }
+        result
+    }
+}
+
+/// The Boolean product, OR of ANDs: `a * b` is the dynamics of `b` followed
+/// by `a`.
+impl std::ops::Mul for &BoolMatrix {
+    type Output = BoolMatrix;
+
+    fn mul(self, rhs: &BoolMatrix) -> BoolMatrix {
+        assert_eq!(self.size(), rhs.size(), "dynamics on different vertices");
+        let n = self.size();
+        let entries = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| (0..n).any(|k| self.entries[i][k] && rhs.entries[k][j]))
+                    .collect()
+            })
+            .collect();
+        BoolMatrix { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BoolMatrix;
+
+    /// The six vertex marble dynamics of the textbook, with vertex 2 keeping
+    /// its marbles and 0 → 4, 1 → 3, 3 → 5, 4 → 1 and 5 → 0.
+    fn marbles() -> BoolMatrix {
+        let targets = [4, 3, 2, 5, 1, 0];
+        BoolMatrix::new(
+            (0..6)
+                .map(|i| (0..6).map(|j| targets[j] == i).collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn marbles_move_deterministically() {
+        let m = marbles();
+        assert!(m.is_deterministic() && m.is_permutation());
+        let start = [true, false, false, false, false, false];
+        assert_eq!(m.apply(&start), [false, false, false, false, true, false]);
+        // 0 → 4 → 1 → 3 → 5 → 0 is a cycle of five
+        assert_eq!(m.evolve(&start, 5), start);
+        assert_eq!(m.power(5), BoolMatrix::identity(6));
+        assert_eq!(m.power(0), BoolMatrix::identity(6));
+        assert_eq!(m.power(3), &(&m * &m) * &m);
+    }
+
+    #[test]
+    fn products_are_or_of_ands() {
+        let a = BoolMatrix::new(vec![vec![true, true], vec![false, false]]);
+        let b = BoolMatrix::new(vec![vec![false, true], vec![true, false]]);
+        assert_eq!(
+            &a * &b,
+            BoolMatrix::new(vec![vec![true, true], vec![false, false]])
+        );
+        assert_eq!(
+            &b * &a,
+            BoolMatrix::new(vec![vec![false, false], vec![true, true]])
+        );
+        for x in [[true, false], [false, true], [true, true]] {
+            assert_eq!((&a * &b).apply(&x), a.apply(&b.apply(&x)));
+        }
+    }
+
+    #[test]
+    fn branching_and_vanishing_marbles() {
+        // vertex 0 leads to both 1 and 2, which lead nowhere
+        let m = BoolMatrix::new(vec![
+            vec![false, false, false],
+            vec![true, false, false],
+            vec![true, false, false],
+        ]);
+        assert!(!m.is_deterministic());
+        assert_eq!(m.apply(&[true, false, false]), [false, true, true]);
+        assert_eq!(m.evolve(&[true, false, false], 2), [false; 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "row 1 has 1 entries in a square matrix")]
+    fn rejects_ragged_rows() {
+        BoolMatrix::new(vec![vec![true, false], vec![true]]);
+    }
+}
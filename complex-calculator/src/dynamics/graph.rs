@@ -0,0 +1,692 @@
+//! Directed graphs for the dynamics, as the textbook draws them: vertices
+//! joined by edges weighted with probabilities or amplitudes. They are read
+//! from Graphviz DOT or from a plain edge list, and turned into the Boolean,
+//! stochastic or amplitude matrix of their dynamics.
+//!
+//! Of DOT, a `digraph` of node and edge statements is read, edges chained
+//! as `a -> b -> c` included. An edge's weight is its `weight` attribute,
+//! or failing that its `label`, as a scalar expression such as `1/3` or
+//! `(-1 + i)/√6`. Other attributes, and graph, node and edge defaults, are
+//! skipped; subgraphs and ports are not supported.
+//!
+//! An edge list has an edge `from to [weight]` or a lone vertex on each
+//! line, with `#` starting a comment.
+//!
+//! Vertices are numbered in order of first appearance, so declaring them
+//! first fixes the numbering.
+
+use num::Zero;
+
+use super::{AmplitudeMatrix, BoolMatrix, DynamicsError, StochasticMatrix};
+use crate::ket::parse_scalar;
+use crate::Complex;
+
+type C = Complex<f64>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} on line {}", self.message, self.line)
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// An edge between the vertices numbered `from` and `to`, with its weight
+/// if it has one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub weight: Option<C>,
+}
+
+/// A directed graph of named vertices, with optionally weighted edges.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Graph {
+    vertices: Vec<String>,
+    edges: Vec<Edge>,
+}
+
+/// The weight of an edge from its DOT attributes.
+fn weight(attributes: &[(String, String)], line: usize) -> Result<Option<C>, GraphError> {
+    let value = ["weight", "label"]
+        .iter()
+        .find_map(|key| attributes.iter().find(|(k, _)| k == key));
+    value
+        .map(|(key, value)| {
+            parse_scalar(value).map_err(|e| GraphError {
+                line,
+                message: format!("bad {key} \"{value}\": {}", e.message),
+            })
+        })
+        .transpose()
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Graph::default()
+    }
+
+    /// The number of the vertex `name`, added if it is new.
+    pub fn add_vertex(&mut self, name: &str) -> usize {
+        self.index(name).unwrap_or_else(|| {
+            self.vertices.push(name.to_string());
+            self.vertices.len() - 1
+        })
+    }
+
+    /// Adds an edge from `from` to `to`, adding the vertices if they are
+    /// new.
+    pub fn add_edge(&mut self, from: &str, to: &str, weight: Option<C>) -> &mut Self {
+        let from = self.add_vertex(from);
+        let to = self.add_vertex(to);
+        self.edges.push(Edge { from, to, weight });
+        self
+    }
+
+    pub fn vertices(&self) -> &[String] {
+        &self.vertices
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// The number of the vertex `name`.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.vertices.iter().position(|v| v == name)
+    }
+
+    /// The number of vertices.
+    pub fn size(&self) -> usize {
+        self.vertices.len()
+    }
+
+    /// Reads an edge list, with an edge `from to [weight]` or a lone vertex
+    /// on each line.
+    ///
+    /// ```
+    /// use complex_calculator::dynamics::Graph;
+    ///
+    /// let g = Graph::from_edge_list("a b 1/2\na a 1/2\nb a").unwrap();
+    /// let m = g.stochastic_matrix().unwrap();
+    /// assert_eq!(m.entries(), [[0.5, 1.0], [0.5, 0.0]]);
+    /// ```
+    pub fn from_edge_list(source: &str) -> Result<Graph, GraphError> {
+        let mut graph = Graph::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                [] => {}
+                [vertex] => {
+                    graph.add_vertex(vertex);
+                }
+                [from, to, ref rest @ ..] => {
+                    let weight = if rest.is_empty() {
+                        None
+                    } else {
+                        let expression = rest.join(" ");
+                        Some(parse_scalar(&expression).map_err(|e| GraphError {
+                            line: i + 1,
+                            message: format!("bad weight \"{expression}\": {}", e.message),
+                        })?)
+                    };
+                    graph.add_edge(from, to, weight);
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Reads a Graphviz DOT `digraph`.
+    ///
+    /// ```
+    /// use complex_calculator::dynamics::Graph;
+    ///
+    /// let g = Graph::from_dot(
+    ///     r#"digraph marbles {
+    ///         a -> b -> c;
+    ///         c -> c [label="1"];
+    ///         d;
+    ///     }"#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(g.vertices(), ["a", "b", "c", "d"]);
+    /// let m = g.bool_matrix().unwrap();
+    /// assert_eq!(m.evolve(&[true, false, false, false], 2), [false, false, true, false]);
+    /// ```
+    pub fn from_dot(source: &str) -> Result<Graph, GraphError> {
+        let mut parser = DotParser {
+            tokens: lex(source)?,
+            pos: 0,
+            last_line: source.lines().count().max(1),
+            graph: Graph::new(),
+        };
+        parser.graph()?;
+        Ok(parser.graph)
+    }
+
+    /// The weight of every edge, in a matrix indexed (to, from), the
+    /// weights of parallel edges added up and unweighted edges weighted by
+    /// `default` of the number of unweighted edges leaving their vertex.
+    fn weights(&self, default: impl Fn(usize) -> C) -> Vec<Vec<C>> {
+        let n = self.size();
+        let mut out_degree = vec![0; n];
+        for edge in self.edges.iter().filter(|edge| edge.weight.is_none()) {
+            out_degree[edge.from] += 1;
+        }
+        let mut entries = vec![vec![C::zero(); n]; n];
+        for edge in &self.edges {
+            let w = edge
+                .weight
+                .unwrap_or_else(|| default(out_degree[edge.from]));
+            entries[edge.to][edge.from] += w;
+        }
+        entries
+    }
+
+    /// The Boolean dynamics, with an entry wherever an edge of nonzero
+    /// weight, or of none, leads. Fails only on a graph with no vertices.
+    pub fn bool_matrix(&self) -> Result<BoolMatrix, DynamicsError> {
+        let n = self.size();
+        let mut entries = vec![vec![false; n]; n];
+        for edge in &self.edges {
+            entries[edge.to][edge.from] |= edge.weight.is_none_or(|w| !w.is_zero());
+        }
+        BoolMatrix::try_new(entries)
+    }
+
+    /// The stochastic dynamics, the weights being probabilities and the
+    /// unweighted edges from a vertex sharing its probability evenly, as a
+    /// random walk. Fails on complex weights, as well as where the matrix
+    /// is not stochastic: where the weights from a vertex, none at all
+    /// included, do not sum to one.
+    pub fn stochastic_matrix(&self) -> Result<StochasticMatrix, DynamicsError> {
+        let weights = self.weights(|k| C::new(1.0 / k as f64, 0.0));
+        let mut entries = vec![];
+        for (row, ws) in weights.iter().enumerate() {
+            if let Some(column) = ws.iter().position(|w| w.im != 0.0) {
+                return Err(DynamicsError::NotReal {
+                    row,
+                    column,
+                    value: ws[column],
+                });
+            }
+            entries.push(ws.iter().map(|w| w.re).collect());
+        }
+        StochasticMatrix::try_new(entries)
+    }
+
+    /// The quantum dynamics, the weights being amplitudes and each of the k
+    /// unweighted edges from a vertex having amplitude 1/√k. Fails only on
+    /// a graph with no vertices, as the dynamics need not be unitary.
+    pub fn amplitude_matrix(&self) -> Result<AmplitudeMatrix, DynamicsError> {
+        AmplitudeMatrix::try_new(self.weights(|k| C::new(1.0 / (k as f64).sqrt(), 0.0)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Id(String),
+    Arrow,
+    Line,
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+    Equals,
+    Separator,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Id(id) => write!(f, "\"{id}\""),
+            Token::Arrow => write!(f, "'->'"),
+            Token::Line => write!(f, "'--'"),
+            Token::Open => write!(f, "'{{'"),
+            Token::Close => write!(f, "'}}'"),
+            Token::OpenBracket => write!(f, "'['"),
+            Token::CloseBracket => write!(f, "']'"),
+            Token::Equals => write!(f, "'='"),
+            Token::Separator => write!(f, "';'"),
+        }
+    }
+}
+
+/// Whether `c` can be part of an unquoted DOT identifier or number.
+fn id_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '√')
+}
+
+/// Splits DOT source into tokens, each with its line, dropping comments.
+fn lex(source: &str) -> Result<Vec<(usize, Token)>, GraphError> {
+    let chars: Vec<char> = source.chars().collect();
+    let (mut tokens, mut line, mut i) = (vec![], 1, 0);
+    let mut at_line_start = true;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let error = move |message: String| GraphError { line, message };
+        if c == '\n' {
+            line += 1;
+            at_line_start = true;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let starts_line = std::mem::replace(&mut at_line_start, false);
+        let token = match (c, next) {
+            ('#', _) if starts_line => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    line += usize::from(chars[i] == '\n');
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return Err(error("unclosed comment".to_string()));
+                }
+                i += 2;
+                continue;
+            }
+            ('-', Some('>')) => {
+                i += 2;
+                Token::Arrow
+            }
+            ('-', Some('-')) => {
+                i += 2;
+                Token::Line
+            }
+            ('"', _) => {
+                let (start, mut id) = (line, String::new());
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => {
+                            return Err(GraphError {
+                                line: start,
+                                message: "unclosed string".to_string(),
+                            })
+                        }
+                        Some('"') => break,
+                        Some('\\') if chars.get(i + 1) == Some(&'"') => {
+                            id.push('"');
+                            i += 1;
+                        }
+                        Some(&c) => {
+                            line += usize::from(c == '\n');
+                            id.push(c);
+                        }
+                    }
+                    i += 1;
+                }
+                i += 1;
+                Token::Id(id)
+            }
+            _ if id_char(c) || c == '-' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && id_char(chars[i]) {
+                    i += 1;
+                }
+                Token::Id(chars[start..i].iter().collect())
+            }
+            _ => {
+                i += 1;
+                match c {
+                    '{' => Token::Open,
+                    '}' => Token::Close,
+                    '[' => Token::OpenBracket,
+                    ']' => Token::CloseBracket,
+                    '=' => Token::Equals,
+                    ';' | ',' => Token::Separator,
+                    _ => return Err(error(format!("unexpected '{c}'"))),
+                }
+            }
+        };
+        tokens.push((line, token));
+    }
+    Ok(tokens)
+}
+
+struct DotParser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// The line reported for errors at the end of the input.
+    last_line: usize,
+    graph: Graph,
+}
+
+impl DotParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.last_line, |&(line, _)| line)
+    }
+
+    fn error(&self, message: String) -> GraphError {
+        GraphError {
+            line: self.line(),
+            message,
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, GraphError> {
+        match self.tokens.get(self.pos) {
+            Some((_, token)) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => Err(self.error(format!("expected {expected}, found end of input"))),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), GraphError> {
+        let line = self.line();
+        match self.next(&expected.to_string())? {
+            token if token == expected => Ok(()),
+            token => Err(GraphError {
+                line,
+                message: format!("expected {expected}, found {token}"),
+            }),
+        }
+    }
+
+    fn id(&mut self) -> Result<String, GraphError> {
+        let line = self.line();
+        match self.next("an identifier")? {
+            Token::Id(id) => Ok(id),
+            token => Err(GraphError {
+                line,
+                message: format!("expected an identifier, found {token}"),
+            }),
+        }
+    }
+
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id(id)) if id.eq_ignore_ascii_case(keyword))
+    }
+
+    /// `[strict] digraph [name] { statements }`
+    fn graph(&mut self) -> Result<(), GraphError> {
+        if self.keyword("strict") {
+            self.pos += 1;
+        }
+        if self.keyword("graph") {
+            return Err(self.error("undirected graphs are not supported, use digraph".to_string()));
+        }
+        if !self.keyword("digraph") {
+            return Err(self.error("expected digraph".to_string()));
+        }
+        self.pos += 1;
+        if let Some(Token::Id(_)) = self.peek() {
+            self.pos += 1;
+        }
+        self.expect(Token::Open)?;
+        while self.peek() != Some(&Token::Close) {
+            self.statement()?;
+        }
+        self.pos += 1;
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(self.error(format!("unexpected {token} after the graph"))),
+        }
+    }
+
+    fn statement(&mut self) -> Result<(), GraphError> {
+        let line = self.line();
+        match self.peek() {
+            None => return Err(self.error("expected '}', found end of input".to_string())),
+            Some(Token::Separator) => {
+                self.pos += 1;
+                return Ok(());
+            }
+            Some(Token::Open) => return Err(self.error("subgraphs are not supported".to_string())),
+            _ if self.keyword("subgraph") => {
+                return Err(self.error("subgraphs are not supported".to_string()))
+            }
+            _ if ["graph", "node", "edge"].iter().any(|k| self.keyword(k)) => {
+                self.pos += 1;
+                self.attributes()?;
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let mut vertices = vec![self.id()?];
+        if self.peek() == Some(&Token::Equals) {
+            self.pos += 1;
+            self.id()?;
+            return Ok(());
+        }
+        loop {
+            match self.peek() {
+                Some(Token::Arrow) => {
+                    self.pos += 1;
+                    vertices.push(self.id()?);
+                }
+                Some(Token::Line) => {
+                    return Err(self.error("'--' edges belong to undirected graphs".to_string()))
+                }
+                _ => break,
+            }
+        }
+        let attributes = self.attributes()?;
+        if let [vertex] = &vertices[..] {
+            self.graph.add_vertex(vertex);
+        } else {
+            let weight = weight(&attributes, line)?;
+            for pair in vertices.windows(2) {
+                self.graph.add_edge(&pair[0], &pair[1], weight);
+            }
+        }
+        Ok(())
+    }
+
+    /// Any number of attribute lists `[key = value, …]`.
+    fn attributes(&mut self) -> Result<Vec<(String, String)>, GraphError> {
+        let mut attributes = vec![];
+        while self.peek() == Some(&Token::OpenBracket) {
+            self.pos += 1;
+            loop {
+                match self.peek() {
+                    Some(Token::CloseBracket) => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(Token::Separator) => self.pos += 1,
+                    _ => {
+                        let key = self.id()?;
+                        self.expect(Token::Equals)?;
+                        attributes.push((key, self.id()?));
+                    }
+                }
+            }
+        }
+        Ok(attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Graph, GraphError, C};
+    use crate::dynamics::{DynamicsError, MultiSlit, StochasticMatrix};
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn reads_weighted_dot() {
+        let g = Graph::from_dot(
+            r#"
+            /* the billiard balls of the probabilistic chapter */
+            strict digraph billiards {
+                rankdir = LR;
+                node [shape=circle];
+                0; 1; 2
+                0 -> 1 [label="1/3"];
+                0 -> 2 [label="2/3", color=red];
+                1 -> 0 [weight="1/6"]
+                1 -> 1 [weight="1/2"; label="half"]  // the weight beats the label
+                1 -> 2 [label="1/3"]
+                2 -> 0 [label="5/6"]
+                2 -> 1 [label="1/6"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(g.vertices(), ["0", "1", "2"]);
+        let expected = StochasticMatrix::new(vec![
+            vec![0.0, 1.0 / 6.0, 5.0 / 6.0],
+            vec![1.0 / 3.0, 1.0 / 2.0, 1.0 / 6.0],
+            vec![2.0 / 3.0, 1.0 / 3.0, 0.0],
+        ]);
+        let m = g.stochastic_matrix().unwrap();
+        for (a, b) in m.entries().concat().iter().zip(expected.entries().concat()) {
+            assert!((a - b).abs() < EPSILON);
+        }
+        assert!(m.is_doubly_stochastic());
+    }
+
+    #[test]
+    fn edge_lists_give_the_double_slit() {
+        let g = Graph::from_edge_list(
+            "# gun, slits, then targets
+            0
+            1
+            2
+            0 1 1/√2
+            0 2 1/√2
+            1 3 (-1 + i)/√6
+            1 4 (-1 - i)/√6
+            1 5 (1 - i)/√6
+            2 5 (-1 + i)/√6
+            2 6 (-1 - i)/√6
+            2 7 (1 - i)/√6
+            3 3 1
+            4 4 1
+            5 5 1
+            6 6 1
+            7 7 1",
+        )
+        .unwrap();
+        let m = g.amplitude_matrix().unwrap();
+        let expected = MultiSlit::double_slit().amplitude_matrix();
+        for (a, b) in m.entries().concat().iter().zip(expected.entries().concat()) {
+            assert!((*a - b).modulus() < EPSILON);
+        }
+        assert_eq!(
+            g.bool_matrix().unwrap(),
+            MultiSlit::double_slit().bool_matrix()
+        );
+    }
+
+    #[test]
+    fn unweighted_edges_share_evenly() {
+        let mut g = Graph::new();
+        g.add_edge("a", "b", None)
+            .add_edge("a", "c", None)
+            .add_edge("b", "a", None)
+            .add_edge("c", "a", None)
+            .add_edge("c", "c", Some(C::new(0.0, 0.0)));
+        let m = g.stochastic_matrix().unwrap();
+        assert_eq!(m.entries()[1][0], 0.5);
+        assert_eq!(m.entries()[0][2], 1.0);
+        let a = g.amplitude_matrix().unwrap();
+        assert!((a.entries()[2][0].modulus() - 0.5f64.sqrt()).abs() < EPSILON);
+        // the edge of weight zero is no edge
+        let b = g.bool_matrix().unwrap();
+        assert!(!b.entries()[2][2]);
+        assert!(b.entries()[2][0]);
+    }
+
+    #[test]
+    fn errors_give_lines() {
+        let error = |message: &str, line| GraphError {
+            line,
+            message: message.to_string(),
+        };
+        assert_eq!(
+            Graph::from_dot("graph { a -- b }"),
+            Err(error("undirected graphs are not supported, use digraph", 1))
+        );
+        assert_eq!(
+            Graph::from_dot("digraph {\n a -> b [label=\"x/2\"]\n}")
+                .unwrap_err()
+                .line,
+            2
+        );
+        assert_eq!(
+            Graph::from_dot("digraph {\n a -> b\n"),
+            Err(error("expected '}', found end of input", 2))
+        );
+        assert_eq!(
+            Graph::from_dot("digraph {\n subgraph x { a }\n}"),
+            Err(error("subgraphs are not supported", 2))
+        );
+        assert_eq!(
+            Graph::from_dot("digraph { a -> [label=1] }"),
+            Err(error("expected an identifier, found '['", 1))
+        );
+        let e = Graph::from_edge_list("a b\nb a |0⟩").unwrap_err();
+        assert_eq!(e.line, 2);
+        assert_eq!(
+            e.to_string(),
+            "bad weight \"|0⟩\": expected a scalar, found a ket on line 2"
+        );
+    }
+
+    #[test]
+    fn bad_dynamics_are_errors() {
+        let empty = Graph::from_dot("digraph {}").unwrap();
+        assert_eq!(empty.bool_matrix(), Err(DynamicsError::Empty));
+        assert_eq!(empty.amplitude_matrix(), Err(DynamicsError::Empty));
+        assert_eq!(empty.stochastic_matrix(), Err(DynamicsError::Empty));
+
+        let complex = Graph::from_edge_list("a b i\nb b 1").unwrap();
+        assert_eq!(
+            complex.stochastic_matrix(),
+            Err(DynamicsError::NotReal {
+                row: 1,
+                column: 0,
+                value: C::new(0.0, 1.0)
+            })
+        );
+        assert!(complex.amplitude_matrix().is_ok());
+
+        // b has nowhere to go
+        let sink = Graph::from_edge_list("a b").unwrap();
+        assert_eq!(
+            sink.stochastic_matrix(),
+            Err(DynamicsError::ColumnSum {
+                column: 1,
+                sum: 0.0
+            })
+        );
+        assert!(sink.bool_matrix().is_ok());
+    }
+}
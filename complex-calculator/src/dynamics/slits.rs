@@ -0,0 +1,224 @@
+//! The slit experiments: a gun at vertex 0 fires through one of the slits,
+//! vertices 1 to s, each leading on to its own run of targets, with
+//! neighbouring slits sharing the target between them, and the targets
+//! keeping whatever reaches them. Two steps from the gun, the classical
+//! probabilities of the targets add up over the slits, but the quantum
+//! amplitudes may cancel on the shared targets: the interference pattern of
+//! the double-slit experiment.
+
+use num::{One, Zero};
+
+use super::{AmplitudeMatrix, BoolMatrix, StochasticMatrix, TOLERANCE};
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// A slit experiment, with the same amplitudes from every slit to its
+/// targets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiSlit {
+    slits: usize,
+    amplitudes: Vec<C>,
+}
+
+impl MultiSlit {
+    /// The experiment with `slits` slits, each leading to
+    /// `amplitudes.len()` targets with `amplitudes` in order, whose squared
+    /// moduli must sum to one. The gun reaches every slit with amplitude
+    /// 1/√slits.
+    pub fn new(slits: usize, amplitudes: Vec<C>) -> Self {
+        assert!(slits > 0, "an experiment needs a slit");
+        assert!(!amplitudes.is_empty(), "a slit needs a target");
+        let total: f64 = amplitudes.iter().map(|a| a.mod_squared()).sum();
+        assert!(
+            (total - 1.0).abs() < TOLERANCE,
+            "the amplitudes to the targets have total probability {total}, not 1"
+        );
+        MultiSlit { slits, amplitudes }
+    }
+
+    /// The double-slit experiment of the textbook: two slits of three
+    /// targets each, with amplitudes (−1 + i)/√6, (−1 − i)/√6 and
+    /// (1 − i)/√6, which cancel on the shared middle target.
+    ///
+    /// ```
+    /// use complex_calculator::dynamics::MultiSlit;
+    ///
+    /// let experiment = MultiSlit::double_slit();
+    /// let classical = experiment.classical_targets();
+    /// let quantum = experiment.quantum_targets();
+    /// assert!((classical[2] - 1.0 / 3.0).abs() < 1e-12);
+    /// assert!(quantum[2] < 1e-12);
+    /// assert!((quantum[0] - classical[0]).abs() < 1e-12);
+    /// ```
+    pub fn double_slit() -> Self {
+        let a = 1.0 / 6f64.sqrt();
+        MultiSlit::new(2, vec![C::new(-a, a), C::new(-a, -a), C::new(a, -a)])
+    }
+
+    pub fn num_slits(&self) -> usize {
+        self.slits
+    }
+
+    pub fn targets_per_slit(&self) -> usize {
+        self.amplitudes.len()
+    }
+
+    /// The number of targets, neighbouring slits sharing one.
+    pub fn num_targets(&self) -> usize {
+        self.slits * (self.targets_per_slit() - 1) + 1
+    }
+
+    /// The number of vertices: the gun, the slits and the targets.
+    pub fn size(&self) -> usize {
+        1 + self.slits + self.num_targets()
+    }
+
+    /// The amplitude matrix of one step: the gun to the slits, the slits to
+    /// their targets, and each target to itself.
+    pub fn amplitude_matrix(&self) -> AmplitudeMatrix {
+        let (n, first_target) = (self.size(), 1 + self.slits);
+        let mut entries = vec![vec![C::zero(); n]; n];
+        let to_slit = C::new(1.0 / (self.slits as f64).sqrt(), 0.0);
+        for slit in 0..self.slits {
+            entries[1 + slit][0] = to_slit;
+            let offset = first_target + slit * (self.targets_per_slit() - 1);
+            for (k, &a) in self.amplitudes.iter().enumerate() {
+                entries[offset + k][1 + slit] = a;
+            }
+        }
+        for (target, row) in entries.iter_mut().enumerate().skip(first_target) {
+            row[target] = C::one();
+        }
+        AmplitudeMatrix::new(entries)
+    }
+
+    /// The probabilistic experiment, with the squared moduli of the
+    /// amplitudes as probabilities.
+    pub fn stochastic_matrix(&self) -> StochasticMatrix {
+        StochasticMatrix::new(self.amplitude_matrix().probabilities())
+    }
+
+    /// The deterministic experiment's edges, wherever an amplitude is not
+    /// zero.
+    pub fn bool_matrix(&self) -> BoolMatrix {
+        BoolMatrix::new(
+            self.amplitude_matrix()
+                .probabilities()
+                .iter()
+                .map(|row| row.iter().map(|&p| p > 0.0).collect())
+                .collect(),
+        )
+    }
+
+    /// The targets reachable from the gun in two steps.
+    pub fn reachable_targets(&self) -> Vec<bool> {
+        let mut start = vec![false; self.size()];
+        start[0] = true;
+        self.bool_matrix().evolve(&start, 2)[1 + self.slits..].to_vec()
+    }
+
+    /// The probability of each target two steps from the gun, classically,
+    /// adding the probabilities of the paths through the slits.
+    pub fn classical_targets(&self) -> Vec<f64> {
+        let mut start = vec![0.0; self.size()];
+        start[0] = 1.0;
+        self.stochastic_matrix().evolve(&start, 2)[1 + self.slits..].to_vec()
+    }
+
+    /// The probability of each target two steps from the gun, quantumly,
+    /// adding the amplitudes of the paths before squaring. They need not sum
+    /// to one: the dynamics is not unitary, and what cancels on a shared
+    /// target is lost.
+    pub fn quantum_targets(&self) -> Vec<f64> {
+        let mut start = vec![C::zero(); self.size()];
+        start[0] = C::one();
+        self.amplitude_matrix().evolve(&start, 2)[1 + self.slits..]
+            .iter()
+            .map(|a| a.mod_squared())
+            .collect()
+    }
+
+    /// The interference term of each target: its quantum probability less
+    /// its classical one, negative where the paths cancel.
+    pub fn interference(&self) -> Vec<f64> {
+        self.quantum_targets()
+            .iter()
+            .zip(self.classical_targets())
+            .map(|(q, c)| q - c)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MultiSlit, C};
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn close(a: &[f64], b: &[f64]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() < EPSILON)
+    }
+
+    #[test]
+    fn double_slit_interferes_in_the_middle() {
+        let experiment = MultiSlit::double_slit();
+        assert_eq!(experiment.size(), 8);
+        assert_eq!(experiment.reachable_targets(), [true; 5]);
+        let sixth = 1.0 / 6.0;
+        assert!(close(
+            &experiment.classical_targets(),
+            &[sixth, sixth, 2.0 * sixth, sixth, sixth]
+        ));
+        assert!(close(
+            &experiment.quantum_targets(),
+            &[sixth, sixth, 0.0, sixth, sixth]
+        ));
+        assert!(close(
+            &experiment.interference(),
+            &[0.0, 0.0, -2.0 * sixth, 0.0, 0.0]
+        ));
+        // the targets absorb, so the second step is as far as it goes
+        let m = experiment.stochastic_matrix();
+        assert!(close(
+            &m.power(2).entries().concat(),
+            &m.power(5).entries().concat()
+        ));
+    }
+
+    #[test]
+    fn equal_phases_add_constructively() {
+        let a = 0.5f64.sqrt();
+        let experiment = MultiSlit::new(3, vec![C::new(a, 0.0), C::new(a, 0.0)]);
+        assert_eq!(experiment.num_targets(), 4);
+        let third = 1.0 / 3.0;
+        assert!(close(
+            &experiment.classical_targets(),
+            &[third / 2.0, third, third, third / 2.0]
+        ));
+        // amplitudes √(1/6) twice on the shared targets, so 4/6
+        assert!(close(
+            &experiment.quantum_targets(),
+            &[third / 2.0, 2.0 * third, 2.0 * third, third / 2.0]
+        ));
+    }
+
+    #[test]
+    fn boolean_experiment_only_sees_edges() {
+        let experiment = MultiSlit::new(2, vec![C::new(1.0, 0.0), C::new(0.0, 0.0)]);
+        assert_eq!(experiment.reachable_targets(), [true, true, false]);
+        // the gun leads to both slits, but each slit to one target
+        let m = experiment.bool_matrix();
+        assert!(!m.is_deterministic());
+        assert_eq!(m.entries().iter().filter(|row| row[0]).count(), 2);
+        for slit in 1..=2 {
+            assert_eq!(m.entries().iter().filter(|row| row[slit]).count(), 1);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "total probability 2")]
+    fn rejects_unnormalised_amplitudes() {
+        MultiSlit::new(2, vec![C::new(1.0, 0.0), C::new(0.0, 1.0)]);
+    }
+}
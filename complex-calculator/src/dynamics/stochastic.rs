@@ -0,0 +1,327 @@
+//! Probabilistic dynamics: matrices of real, non-negative entries whose
+//! columns each sum to one, moving probability distributions over the
+//! vertices. Doubly stochastic ones, whose rows also sum to one, are the
+//! classical analogue of unitary matrices, and keep the uniform
+//! distribution fixed.
+
+use super::{is_doubly_stochastic, is_stochastic, DynamicsError};
+
+/// A column stochastic matrix, entry (i, j) the probability of going from
+/// vertex j to vertex i in one step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StochasticMatrix {
+    entries: Vec<Vec<f64>>,
+}
+
+/// The probability vector M x.
+fn apply(entries: &[Vec<f64>], x: &[f64]) -> Vec<f64> {
+    entries
+        .iter()
+        .map(|row| row.iter().zip(x).map(|(m, x)| m * x).sum())
+        .collect()
+}
+
+/// The solution of the square system a x = b, by Gaussian elimination with
+/// partial pivoting, for a nonsingular a.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&r, &s| a[r][column].abs().total_cmp(&a[s][column].abs()))
+            .unwrap();
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let pivot_row = a[column].clone();
+        for r in column + 1..n {
+            let factor = a[r][column] / pivot_row[column];
+            for (x, p) in a[r][column..].iter_mut().zip(&pivot_row[column..]) {
+                *x -= factor * p;
+            }
+            b[r] -= factor * b[column];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for r in (0..n).rev() {
+        let rest: f64 = (r + 1..n).map(|c| a[r][c] * x[c]).sum();
+        x[r] = (b[r] - rest) / a[r][r];
+    }
+    x
+}
+
+impl StochasticMatrix {
+    /// The dynamics of `entries`, given by rows, which must form a square
+    /// matrix of non-negative entries with every column summing to one.
+    ///
+    /// ```
+    /// use complex_calculator::dynamics::StochasticMatrix;
+    ///
+    /// let m = StochasticMatrix::new(vec![
+    ///     vec![0.0, 1.0 / 6.0, 5.0 / 6.0],
+    ///     vec![1.0 / 3.0, 1.0 / 2.0, 1.0 / 6.0],
+    ///     vec![2.0 / 3.0, 1.0 / 3.0, 0.0],
+    /// ]);
+    /// assert!(m.is_doubly_stochastic());
+    /// let x = m.apply(&[1.0 / 6.0, 1.0 / 6.0, 2.0 / 3.0]);
+    /// assert!((x[0] - 21.0 / 36.0).abs() < 1e-12);
+    /// ```
+    pub fn new(entries: Vec<Vec<f64>>) -> Self {
+        StochasticMatrix::try_new(entries).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// The dynamics of `entries`, or where they fail [`is_stochastic`].
+    pub fn try_new(entries: Vec<Vec<f64>>) -> Result<Self, DynamicsError> {
+        is_stochastic(&entries)?;
+        Ok(StochasticMatrix { entries })
+    }
+
+    /// The number of vertices.
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> &[Vec<f64>] {
+        &self.entries
+    }
+
+    /// Whether the rows sum to one as well as the columns.
+    pub fn is_doubly_stochastic(&self) -> bool {
+        is_doubly_stochastic(&self.entries).is_ok()
+    }
+
+    /// The distribution one step after `state`.
+    pub fn apply(&self, state: &[f64]) -> Vec<f64> {
+        assert_eq!(
+            state.len(),
+            self.size(),
+            "a state on {} vertices is not on {}",
+            state.len(),
+            self.size()
+        );
+        apply(&self.entries, state)
+    }
+
+    /// The distribution `steps` steps after `state`.
+    pub fn evolve(&self, state: &[f64], steps: usize) -> Vec<f64> {
+        (0..steps).fold(state.to_vec(), |x, _| self.apply(&x))
+    }
+
+    /// The dynamics of first `self`, then `next`: the matrix product
+    /// `next` · `self`.
+    pub fn then(&self, next: &StochasticMatrix) -> StochasticMatrix {
+        assert_eq!(self.size(), next.size(), "dynamics on different vertices");
+        let n = self.size();
+        let entries = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| {
+                        (0..n)
+                            .map(|k| next.entries[i][k] * self.entries[k][j])
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect();
+        StochasticMatrix { entries }
+    }
+
+    /// The dynamics of `steps` steps at once, M^steps, by repeated squaring.
+    pub fn power(&self, steps: usize) -> StochasticMatrix {
+        let n = self.size();
+        let mut result = StochasticMatrix {
+            entries: (0..n)
+                .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+                .collect(),
+        };
+        let (mut square, mut steps) = (self.clone(), steps);
+        while steps > 0 {
+            if steps & 1 == 1 {
+                result = result.then(&square);
+            }
+            square = square.then(&square);
+            steps >>= 1;
+        }
+        result
+    }
+
+    /// Whether `vertex` is reachable from `start` by steps of positive
+    /// probability, for every vertex, `start` included.
+    fn reachable(&self, start: usize) -> Vec<bool> {
+        let mut seen = vec![false; self.size()];
+        let mut stack = vec![start];
+        seen[start] = true;
+        while let Some(j) = stack.pop() {
+            for (i, row) in self.entries.iter().enumerate() {
+                if row[j] > 0.0 && !seen[i] {
+                    seen[i] = true;
+                    stack.push(i);
+                }
+            }
+        }
+        seen
+    }
+
+    /// The stationary distributions x = M x that every other one is a
+    /// mixture of: one for each closed class of vertices, which reach each
+    /// other and nothing else, in the order of their first vertices. The
+    /// chain restricted to such a class is irreducible, so it has exactly
+    /// one, found by solving (M − I) x = 0 with the entries of x summing to
+    /// one. Vertices outside the closed classes are transient, and have no
+    /// probability left in the long run.
+    ///
+    /// ```
+    /// use complex_calculator::dynamics::StochasticMatrix;
+    ///
+    /// // two absorbing vertices either side of one that moves at random
+    /// let m = StochasticMatrix::new(vec![
+    ///     vec![1.0, 0.5, 0.0],
+    ///     vec![0.0, 0.0, 0.0],
+    ///     vec![0.0, 0.5, 1.0],
+    /// ]);
+    /// assert_eq!(
+    ///     m.stationary_distributions(),
+    ///     [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]
+    /// );
+    /// assert_eq!(m.stationary(), None);
+    /// ```
+    pub fn stationary_distributions(&self) -> Vec<Vec<f64>> {
+        let n = self.size();
+        let reach: Vec<Vec<bool>> = (0..n).map(|v| self.reachable(v)).collect();
+        let mut classified = vec![false; n];
+        let mut distributions = vec![];
+        for v in 0..n {
+            if classified[v] {
+                continue;
+            }
+            let class: Vec<usize> = (0..n).filter(|&u| reach[v][u] && reach[u][v]).collect();
+            for &u in &class {
+                classified[u] = true;
+            }
+            if (0..n).any(|u| reach[v][u] && !reach[u][v]) {
+                continue;
+            }
+            // the rows of M − I sum to zero, so one is replaced by the sum
+            let k = class.len();
+            let mut a: Vec<Vec<f64>> = class
+                .iter()
+                .map(|&i| {
+                    class
+                        .iter()
+                        .map(|&j| self.entries[i][j] - if i == j { 1.0 } else { 0.0 })
+                        .collect()
+                })
+                .collect();
+            let mut b = vec![0.0; k];
+            a[k - 1] = vec![1.0; k];
+            b[k - 1] = 1.0;
+            let mut distribution = vec![0.0; n];
+            for (&i, p) in class.iter().zip(solve(a, b)) {
+                distribution[i] = p;
+            }
+            distributions.push(distribution);
+        }
+        distributions
+    }
+
+    /// The stationary distribution, when there is only one: when a single
+    /// class of vertices is closed.
+    pub fn stationary(&self) -> Option<Vec<f64>> {
+        let mut distributions = self.stationary_distributions();
+        if distributions.len() == 1 {
+            distributions.pop()
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{solve, StochasticMatrix};
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn close(a: &[f64], b: &[f64]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() < EPSILON)
+    }
+
+    fn billiard() -> StochasticMatrix {
+        StochasticMatrix::new(vec![
+            vec![0.0, 1.0 / 6.0, 5.0 / 6.0],
+            vec![1.0 / 3.0, 1.0 / 2.0, 1.0 / 6.0],
+            vec![2.0 / 3.0, 1.0 / 3.0, 0.0],
+        ])
+    }
+
+    #[test]
+    fn solves_linear_systems() {
+        let x = solve(
+            vec![
+                vec![0.0, 2.0, 1.0],
+                vec![1.0, 1.0, 0.0],
+                vec![3.0, 0.0, 1.0],
+            ],
+            vec![5.0, 3.0, 6.0],
+        );
+        assert!(close(&x, &[1.4, 1.6, 1.8]), "{x:?}");
+    }
+
+    #[test]
+    fn evolution_matches_powers() {
+        let m = billiard();
+        let x = [1.0 / 6.0, 1.0 / 6.0, 2.0 / 3.0];
+        assert!(close(
+            &m.evolve(&x, 1),
+            &[21.0 / 36.0, 9.0 / 36.0, 6.0 / 36.0]
+        ));
+        assert_eq!(m.evolve(&x, 0), x);
+        for t in [2, 5, 8] {
+            assert!(close(&m.evolve(&x, t), &m.power(t).apply(&x)));
+        }
+        assert!(close(
+            &m.then(&m).then(&m).entries().concat(),
+            &m.power(3).entries().concat()
+        ));
+        let total: f64 = m.evolve(&x, 20).iter().sum();
+        assert!((total - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn doubly_stochastic_matrices_fix_the_uniform_distribution() {
+        let m = billiard();
+        assert!(m.is_doubly_stochastic());
+        let uniform = [1.0 / 3.0; 3];
+        assert!(close(&m.apply(&uniform), &uniform));
+        assert!(close(&m.stationary().unwrap(), &uniform));
+
+        let weather = StochasticMatrix::new(vec![vec![0.9, 0.5], vec![0.1, 0.5]]);
+        assert!(!weather.is_doubly_stochastic());
+        let stationary = weather.stationary().unwrap();
+        assert!(close(&stationary, &[5.0 / 6.0, 1.0 / 6.0]));
+        assert!(close(&weather.evolve(&[0.0, 1.0], 100), &stationary));
+    }
+
+    #[test]
+    fn transient_vertices_drain() {
+        // vertex 0 leaks into the cycle 1 → 2 → 1, which never settles
+        let m = StochasticMatrix::new(vec![
+            vec![0.5, 0.0, 0.0],
+            vec![0.5, 0.0, 1.0],
+            vec![0.0, 1.0, 0.0],
+        ]);
+        assert_eq!(m.stationary(), Some(vec![0.0, 0.5, 0.5]));
+        assert!(close(&m.apply(&[0.0, 0.5, 0.5]), &[0.0, 0.5, 0.5]));
+        assert!(close(&m.evolve(&[0.0, 1.0, 0.0], 7), &[0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    #[should_panic(expected = "column 1 sums to 0.9")]
+    fn rejects_leaking_columns() {
+        StochasticMatrix::new(vec![vec![1.0, 0.4], vec![0.0, 0.5]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "entry (1, 0) is negative")]
+    fn rejects_negative_entries() {
+        StochasticMatrix::new(vec![vec![1.5, 0.0], vec![-0.5, 1.0]]);
+    }
+}
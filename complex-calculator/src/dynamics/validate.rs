@@ -0,0 +1,262 @@
+//! Checks of whether a matrix is legal dynamics of each kind, reporting
+//! where it fails: the row or column, and what it comes to instead.
+
+use num::Zero;
+
+use super::TOLERANCE;
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// Why a matrix is not legal dynamics, at the first place found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicsError {
+    /// There are no rows.
+    Empty,
+    /// A row is of the wrong length for a square matrix.
+    NotSquare { row: usize, len: usize },
+    /// A probability is not real.
+    NotReal { row: usize, column: usize, value: C },
+    /// A probability is negative, or not a number.
+    Negative {
+        row: usize,
+        column: usize,
+        value: f64,
+    },
+    /// The probabilities leaving a vertex do not sum to one.
+    ColumnSum { column: usize, sum: f64 },
+    /// The probabilities entering a vertex do not sum to one.
+    RowSum { row: usize, sum: f64 },
+    /// A column is not of unit length: its squared norm is entry
+    /// (column, column) of M†M.
+    ColumnNorm { column: usize, norm_squared: f64 },
+    /// Two columns are not orthogonal: their inner product is entry
+    /// (columns.0, columns.1) of M†M.
+    ColumnOverlap { columns: (usize, usize), overlap: C },
+}
+
+impl std::fmt::Display for DynamicsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DynamicsError::Empty => write!(f, "the matrix has no vertices"),
+            DynamicsError::NotSquare { row, len } => {
+                write!(f, "row {row} has {len} entries in a square matrix")
+            }
+            DynamicsError::NotReal { row, column, value } => {
+                write!(f, "entry ({row}, {column}) is not real: {value}")
+            }
+            DynamicsError::Negative { row, column, value } => {
+                write!(f, "entry ({row}, {column}) is negative: {value}")
+            }
+            DynamicsError::ColumnSum { column, sum } => {
+                write!(f, "column {column} sums to {sum}, not 1")
+            }
+            DynamicsError::RowSum { row, sum } => write!(f, "row {row} sums to {sum}, not 1"),
+            DynamicsError::ColumnNorm {
+                column,
+                norm_squared,
+            } => write!(f, "column {column} has squared norm {norm_squared}, not 1"),
+            DynamicsError::ColumnOverlap {
+                columns: (i, j),
+                overlap,
+            } => write!(f, "columns {i} and {j} have inner product {overlap}, not 0"),
+        }
+    }
+}
+
+impl std::error::Error for DynamicsError {}
+
+/// Whether `x` is further than the tolerance from `target`, or not a
+/// number at all.
+fn differs(x: f64, target: f64) -> bool {
+    x.is_nan() || (x - target).abs() >= TOLERANCE
+}
+
+/// Checks that `m`, given by rows, is square.
+pub(super) fn is_square<T>(m: &[Vec<T>]) -> Result<(), DynamicsError> {
+    if m.is_empty() {
+        return Err(DynamicsError::Empty);
+    }
+    match m.iter().position(|row| row.len() != m.len()) {
+        Some(row) => Err(DynamicsError::NotSquare {
+            row,
+            len: m[row].len(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Checks that `m`, given by rows, is legal probabilistic dynamics: square,
+/// with non-negative entries and every column summing to one.
+///
+/// ```
+/// use complex_calculator::dynamics::{is_stochastic, DynamicsError};
+///
+/// assert_eq!(is_stochastic(&[vec![0.5, 1.0], vec![0.5, 0.0]]), Ok(()));
+/// assert_eq!(
+///     is_stochastic(&[vec![0.5, 1.0], vec![0.25, 0.0]]),
+///     Err(DynamicsError::ColumnSum { column: 0, sum: 0.75 })
+/// );
+/// ```
+pub fn is_stochastic(m: &[Vec<f64>]) -> Result<(), DynamicsError> {
+    is_square(m)?;
+    for (row, entries) in m.iter().enumerate() {
+        if let Some(column) = entries.iter().position(|&p| p < 0.0 || p.is_nan()) {
+            return Err(DynamicsError::Negative {
+                row,
+                column,
+                value: entries[column],
+            });
+        }
+    }
+    for column in 0..m.len() {
+        let sum: f64 = m.iter().map(|row| row[column]).sum();
+        if differs(sum, 1.0) {
+            return Err(DynamicsError::ColumnSum { column, sum });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `m` is stochastic, and that its rows sum to one as well,
+/// as for dynamics that could be reversed.
+pub fn is_doubly_stochastic(m: &[Vec<f64>]) -> Result<(), DynamicsError> {
+    is_stochastic(m)?;
+    for (row, entries) in m.iter().enumerate() {
+        let sum: f64 = entries.iter().sum();
+        if differs(sum, 1.0) {
+            return Err(DynamicsError::RowSum { row, sum });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `m`, given by rows, is legal quantum dynamics: unitary, so
+/// that M†M = I and its columns are orthonormal.
+///
+/// ```
+/// use complex_calculator::dynamics::{is_unitary_dynamics, DynamicsError};
+/// use complex_calculator::Complex;
+///
+/// let r = Complex::new(0.5f64.sqrt(), 0.0);
+/// assert_eq!(is_unitary_dynamics(&[vec![r, r], vec![r, -r]]), Ok(()));
+/// assert!(matches!(
+///     is_unitary_dynamics(&[vec![r, r], vec![r, r]]),
+///     Err(DynamicsError::ColumnOverlap { columns: (0, 1), .. })
+/// ));
+/// ```
+pub fn is_unitary_dynamics(m: &[Vec<C>]) -> Result<(), DynamicsError> {
+    is_square(m)?;
+    let n = m.len();
+    for i in 0..n {
+        for j in i..n {
+            let product = m
+                .iter()
+                .fold(C::zero(), |acc, row| acc + row[i].conjugate() * row[j]);
+            if i == j && differs(product.re, 1.0) {
+                return Err(DynamicsError::ColumnNorm {
+                    column: i,
+                    norm_squared: product.re,
+                });
+            }
+            if i != j && differs(product.modulus(), 0.0) {
+                return Err(DynamicsError::ColumnOverlap {
+                    columns: (i, j),
+                    overlap: product,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_doubly_stochastic, is_stochastic, is_unitary_dynamics, DynamicsError, C};
+    use crate::dynamics::MultiSlit;
+
+    const EPSILON: f64 = 0.0000001;
+
+    #[test]
+    fn stochastic_failures_are_located() {
+        assert_eq!(is_stochastic(&[]), Err(DynamicsError::Empty));
+        assert_eq!(
+            is_stochastic(&[vec![1.0, 0.0], vec![0.0]]),
+            Err(DynamicsError::NotSquare { row: 1, len: 1 })
+        );
+        assert_eq!(
+            is_stochastic(&[vec![1.5, 0.0], vec![-0.5, 1.0]]),
+            Err(DynamicsError::Negative {
+                row: 1,
+                column: 0,
+                value: -0.5
+            })
+        );
+        let error = is_stochastic(&[vec![1.0, 0.5], vec![0.0, 0.25]]).unwrap_err();
+        assert_eq!(
+            error,
+            DynamicsError::ColumnSum {
+                column: 1,
+                sum: 0.75
+            }
+        );
+        assert_eq!(error.to_string(), "column 1 sums to 0.75, not 1");
+
+        let Err(DynamicsError::Negative { row, column, value }) =
+            is_stochastic(&[vec![f64::NAN, 0.0], vec![0.0, 1.0]])
+        else {
+            panic!("a matrix with NaN passes as stochastic");
+        };
+        assert_eq!((row, column), (0, 0));
+        assert!(value.is_nan());
+        assert!(matches!(
+            is_stochastic(&[vec![f64::INFINITY, 0.0], vec![0.0, 1.0]]),
+            Err(DynamicsError::ColumnSum { column: 0, .. })
+        ));
+        let nan = C::new(f64::NAN, 0.0);
+        assert!(matches!(
+            is_unitary_dynamics(&[vec![nan]]),
+            Err(DynamicsError::ColumnNorm { column: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn doubly_stochastic_rows() {
+        let billiard = [
+            vec![0.0, 1.0 / 6.0, 5.0 / 6.0],
+            vec![1.0 / 3.0, 1.0 / 2.0, 1.0 / 6.0],
+            vec![2.0 / 3.0, 1.0 / 3.0, 0.0],
+        ];
+        assert_eq!(is_doubly_stochastic(&billiard), Ok(()));
+        let weather = [vec![0.9, 0.5], vec![0.1, 0.5]];
+        assert_eq!(is_stochastic(&weather), Ok(()));
+        let Err(DynamicsError::RowSum { row, sum }) = is_doubly_stochastic(&weather) else {
+            panic!("the rows of a non-doubly stochastic matrix pass");
+        };
+        assert_eq!(row, 0);
+        assert!((sum - 1.4).abs() < EPSILON);
+    }
+
+    #[test]
+    fn unitary_failures_are_located() {
+        let (zero, one, i) = (C::new(0.0, 0.0), C::new(1.0, 0.0), C::new(0.0, 1.0));
+        assert_eq!(is_unitary_dynamics(&[vec![zero, i], vec![i, zero]]), Ok(()));
+        assert_eq!(
+            is_unitary_dynamics(&[vec![one, zero], vec![zero, one + one]]),
+            Err(DynamicsError::ColumnNorm {
+                column: 1,
+                norm_squared: 4.0
+            })
+        );
+
+        // the slits lead to the same middle target
+        let slits = MultiSlit::double_slit().amplitude_matrix();
+        let Err(DynamicsError::ColumnOverlap { columns, overlap }) =
+            is_unitary_dynamics(slits.entries())
+        else {
+            panic!("the double slit passes as unitary");
+        };
+        assert_eq!(columns, (1, 2));
+        assert!((overlap - C::new(-1.0 / 3.0, 0.0)).modulus() < EPSILON);
+    }
+}
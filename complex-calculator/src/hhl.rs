@@ -0,0 +1,407 @@
+//! The Harrow–Hassidim–Lloyd algorithm on toy systems of one or two qubits,
+//! that is 2×2 and 4×4 Hermitian matrices: phase estimation of e^(iAt)
+//! writes the eigenvalues of A into a clock register, an ancilla is rotated
+//! by an angle depending on the clock so that its |1⟩ amplitude goes as
+//! 1/λ, and the phase estimation is undone. Post-selected on the ancilla
+//! reading 1, the system holds A⁻¹|b⟩ up to normalisation.
+//!
+//! Real hardware would simulate e^(iAt) from a sparse description of A. Here
+//! A is diagonalised classically instead, and its powers are applied as
+//! controlled phases in the eigenbasis, between a change of basis synthesised
+//! by [`EulerAngles`] or [`KakDecomposition`]. The circuit then only checks
+//! the phase estimation, the conditional rotations and the uncomputation,
+//! which are what the algorithm is about.
+
+use num::{One, Zero};
+
+use crate::circuit::{Circuit, EulerAngles, KakDecomposition};
+use crate::ket::{KetBra, Operator};
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// The entries of the operator on n qubits, indexed `[ket][bra]`.
+fn dense(a: &Operator<C>) -> Vec<Vec<C>> {
+    let dim = 1 << a.num_qubits();
+    let mut m = vec![vec![C::zero(); dim]; dim];
+    for (scalar, ket, bra, _) in a.terms() {
+        m[ket as usize][bra as usize] += scalar;
+    }
+    m
+}
+
+fn mul(a: &[Vec<C>], b: &[Vec<C>]) -> Vec<Vec<C>> {
+    let dim = a.len();
+    (0..dim)
+        .map(|i| {
+            (0..dim)
+                .map(|j| (0..dim).fold(C::zero(), |acc, k| acc + a[i][k] * b[k][j]))
+                .collect()
+        })
+        .collect()
+}
+
+fn adjoint(a: &[Vec<C>]) -> Vec<Vec<C>> {
+    let dim = a.len();
+    (0..dim)
+        .map(|i| (0..dim).map(|j| a[j][i].conjugate()).collect())
+        .collect()
+}
+
+/// The eigenvalues of a Hermitian matrix and its eigenvectors as the columns
+/// of a unitary, by Jacobi rotations: each off-diagonal entry in turn is
+/// made real by a phase and then cleared by a rotation, until none is left.
+fn eigen(a: &Operator<C>) -> (Vec<f64>, Vec<Vec<C>>) {
+    assert!(a.is_hermitian(1e-9), "HHL requires a Hermitian matrix");
+    let mut m = dense(a);
+    let dim = m.len();
+    let mut v: Vec<Vec<C>> = (0..dim)
+        .map(|i| {
+            (0..dim)
+                .map(|j| if i == j { C::one() } else { C::zero() })
+                .collect()
+        })
+        .collect();
+
+    for _ in 0..50 {
+        let off: f64 = (0..dim)
+            .flat_map(|i| (0..dim).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| m[i][j].modulus())
+            .sum();
+        if off < 1e-14 {
+            break;
+        }
+        for p in 0..dim {
+            for q in p + 1..dim {
+                if m[p][q].modulus() < 1e-15 {
+                    continue;
+                }
+                // G = diag(1, e^(−iφ)) then a rotation by θ on (p, q), so that
+                // G†MG has a real entry |m_pq| before the rotation clears it
+                let phi = m[p][q].arg();
+                let theta = 0.5 * (2.0 * m[p][q].modulus()).atan2(m[q][q].re - m[p][p].re);
+                let (c, s) = (theta.cos(), theta.sin());
+                let phase = C::new(phi.cos(), -phi.sin());
+                let mut g: Vec<Vec<C>> = (0..dim)
+                    .map(|i| {
+                        (0..dim)
+                            .map(|j| if i == j { C::one() } else { C::zero() })
+                            .collect()
+                    })
+                    .collect();
+                g[p][p] = C::new(c, 0.0);
+                g[p][q] = C::new(s, 0.0);
+                g[q][p] = phase * C::new(-s, 0.0);
+                g[q][q] = phase * C::new(c, 0.0);
+                m = mul(&mul(&adjoint(&g), &m), &g);
+                v = mul(&v, &g);
+            }
+        }
+    }
+    ((0..dim).map(|k| m[k][k].re).collect(), v)
+}
+
+/// The circuit of the operator with entries `m[ket][bra]` on one or two
+/// qubits.
+fn synthesise(m: &[Vec<C>]) -> Circuit {
+    let n = m.len().trailing_zeros();
+    let u = Operator {
+        scalar: C::one(),
+        ones: (0..m.len())
+            .flat_map(|ket| (0..m.len()).map(move |bra| (ket, bra)))
+            .map(|(ket, bra)| KetBra {
+                scalar: m[ket][bra],
+                ket: ket as u64,
+                bra: bra as u64,
+                n,
+                d: 2,
+            })
+            .collect(),
+    };
+    match n {
+        1 => EulerAngles::zyz(&u).circuit(),
+        _ => KakDecomposition::new(&u).circuit(),
+    }
+}
+
+/// The HHL circuit solving A|x⟩ = |b⟩ for a Hermitian `a` on s = 1 or 2
+/// qubits, with |b⟩ prepared from |0…0⟩ by `prepare`. Qubits 0 to s − 1 hold
+/// the system, s to s + m − 1 a clock of m = `clock` qubits, and qubit s + m
+/// the ancilla, which reads 1 when the inversion succeeds.
+///
+/// Phase estimation of e^(iAt) for t = `time` reads an eigenvalue λ as the
+/// clock value k = 2^m λt/2π, so the eigenvalues must lie in (0, 2π/t), and
+/// the solution is exact when every k is a whole number. The ancilla is
+/// rotated by Ry(2 arcsin(1/k)), that is by C/λ for the smallest eigenvalue
+/// C = 2π/2^m t the clock can represent.
+pub fn hhl(a: &Operator<C>, prepare: &Circuit, clock: u32, time: f64) -> Circuit {
+    let s = a.num_qubits();
+    assert!(
+        (1..=2).contains(&s),
+        "HHL is implemented for one or two system qubits, not {s}"
+    );
+    assert_eq!(
+        prepare.num_qubits(),
+        s,
+        "|b⟩ is prepared on {} qubits, but A acts on {s}",
+        prepare.num_qubits()
+    );
+    let (values, vectors) = eigen(a);
+    let system: Vec<usize> = (0..s as usize).collect();
+    let counting: Vec<usize> = (s as usize..(s + clock) as usize).collect();
+    let ancilla = (s + clock) as usize;
+
+    // phase estimation, with every controlled power diagonal in the eigenbasis
+    let mut estimate = Circuit::new(s + clock + 1);
+    for &q in &counting {
+        estimate.h(q);
+    }
+    estimate.compose(&synthesise(&adjoint(&vectors)), &system);
+    for (j, &control) in counting.iter().enumerate() {
+        let theta: Vec<f64> = values
+            .iter()
+            .map(|&l| l * time * f64::from(1u32 << j))
+            .collect();
+        // e^(iθₖ) on |k⟩ as a phase, one controlled phase per system qubit
+        // and, for two, a doubly controlled correction
+        estimate.p(control, theta[0]);
+        estimate.cp(control, system[0], theta[1] - theta[0]);
+        if s == 2 {
+            estimate.cp(control, system[1], theta[2] - theta[0]);
+            let lambda = theta[3] - theta[2] - theta[1] + theta[0];
+            estimate
+                .cp(system[0], system[1], lambda / 2.0)
+                .cnot(control, system[0])
+                .cp(system[0], system[1], -lambda / 2.0)
+                .cnot(control, system[0])
+                .cp(control, system[1], lambda / 2.0);
+        }
+    }
+    estimate.compose(&synthesise(&vectors), &system);
+    estimate.compose(&Circuit::iqft(clock), &counting);
+
+    let mut c = Circuit::new(s + clock + 1);
+    c.compose(prepare, &system);
+    c.compose(&estimate, &(0..=ancilla).collect::<Vec<_>>());
+    for k in 1u64..1 << clock {
+        // Ry(θ) on the ancilla controlled on the clock reading k, as
+        // Ry(θ/2), a multi-controlled X, Ry(−θ/2) and another one
+        let theta = 2.0 * (1.0 / k as f64).asin();
+        let zeros: Vec<usize> = counting
+            .iter()
+            .enumerate()
+            .filter(|&(b, _)| k >> b & 1 == 0)
+            .map(|(_, &q)| q)
+            .collect();
+        for &q in &zeros {
+            c.x(q);
+        }
+        c.ry(ancilla, theta / 2.0)
+            .mcx(&counting, ancilla)
+            .ry(ancilla, -theta / 2.0)
+            .mcx(&counting, ancilla);
+        for &q in &zeros {
+            c.x(q);
+        }
+    }
+    c.compose(&estimate.inverse(), &(0..=ancilla).collect::<Vec<_>>());
+    c
+}
+
+/// The outcome of [`solve`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    /// The normalised system state once the ancilla reads 1 and the clock
+    /// is back to 0.
+    pub state: Vec<C>,
+    /// The probability of the ancilla reading 1.
+    pub success_probability: f64,
+}
+
+impl Solution {
+    /// |⟨x|ψ⟩|² against the normalised state `x`, 1 when the solution is the
+    /// same state up to a global phase.
+    pub fn fidelity(&self, x: &[C]) -> f64 {
+        self.state
+            .iter()
+            .zip(x)
+            .fold(C::zero(), |acc, (&a, &b)| acc + b.conjugate() * a)
+            .modulus()
+            .powi(2)
+    }
+}
+
+/// Simulates [`hhl`] and post-selects its outcome.
+///
+/// ```
+/// use std::f64::consts::PI;
+///
+/// use complex_calculator::circuit::Circuit;
+/// use complex_calculator::hhl;
+/// use complex_calculator::pauli::PauliSum;
+///
+/// // A = 3/2 I + 1/2 X has eigenvalues 1 and 2, read by a two-qubit clock as
+/// // 1 and 2 for t = 2π/4
+/// let mut a = PauliSum::new();
+/// a.add(1.5, "I".parse().unwrap()).add(0.5, "X".parse().unwrap());
+/// let a = a.to_operator();
+///
+/// let solution = hhl::solve(&a, &Circuit::new(1), 2, PI / 2.0);
+/// let b = Circuit::new(1).simulate().amplitudes(1);
+/// assert!((solution.fidelity(&hhl::classical_solution(&a, &b)) - 1.0).abs() < 1e-9);
+/// ```
+pub fn solve(a: &Operator<C>, prepare: &Circuit, clock: u32, time: f64) -> Solution {
+    let s = a.num_qubits();
+    let c = hhl(a, prepare, clock, time);
+    let amplitudes = c.simulate().amplitudes(c.num_qubits());
+    let flag = 1 << (s + clock);
+
+    let success_probability = amplitudes
+        .iter()
+        .enumerate()
+        .filter(|&(x, _)| x & flag != 0)
+        .map(|(_, a)| a.modulus().powi(2))
+        .sum();
+    let state: Vec<C> = (0..1 << s).map(|x| amplitudes[flag | x]).collect();
+    let norm = state
+        .iter()
+        .map(|a| a.modulus().powi(2))
+        .sum::<f64>()
+        .sqrt();
+    assert!(
+        norm > 1e-12,
+        "the ancilla never reads 1 with the clock at 0"
+    );
+    Solution {
+        state: state
+            .into_iter()
+            .map(|a| a * C::new(1.0 / norm, 0.0))
+            .collect(),
+        success_probability,
+    }
+}
+
+/// A⁻¹|b⟩, normalised, computed classically for comparison with [`solve`].
+pub fn classical_solution(a: &Operator<C>, b: &[C]) -> Vec<C> {
+    let (values, vectors) = eigen(a);
+    let dim = values.len();
+    assert_eq!(b.len(), dim, "b has {} entries, not {dim}", b.len());
+    let mut x = vec![C::zero(); dim];
+    for (k, &l) in values.iter().enumerate() {
+        assert!(l.abs() > 1e-12, "A is singular");
+        let overlap = (0..dim).fold(C::zero(), |acc, i| acc + vectors[i][k].conjugate() * b[i]);
+        for (i, xi) in x.iter_mut().enumerate() {
+            *xi += vectors[i][k] * overlap * C::new(1.0 / l, 0.0);
+        }
+    }
+    let norm = x.iter().map(|a| a.modulus().powi(2)).sum::<f64>().sqrt();
+    x.into_iter().map(|a| a * C::new(1.0 / norm, 0.0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::{classical_solution, eigen, hhl, solve};
+    use crate::circuit::Circuit;
+    use crate::ket::Operator;
+    use crate::pauli::PauliSum;
+    use crate::Complex;
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn hamiltonian(terms: &[(f64, &str)]) -> Operator<Complex<f64>> {
+        let mut h = PauliSum::new();
+        for &(c, p) in terms {
+            h.add(c, p.parse().unwrap());
+        }
+        h.to_operator()
+    }
+
+    #[test]
+    fn eigendecomposition() {
+        // 2.5 + 0.5 XX + ZZ is diagonal in the Bell basis with eigenvalues 1 to 4
+        let (mut values, _) = eigen(&hamiltonian(&[(2.5, "II"), (0.5, "XX"), (1.0, "ZZ")]));
+        values.sort_by(f64::total_cmp);
+        for (l, expected) in values.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((l - expected).abs() < EPSILON);
+        }
+        let (values, v) = eigen(&hamiltonian(&[(1.5, "I"), (0.5, "Y")]));
+        // A v = λ v for each column
+        let a = [[1.5, 0.0, 0.0, -0.5], [0.0, 0.5, 1.5, 0.0]];
+        for k in 0..2 {
+            for i in 0..2 {
+                let av = (0..2).fold(Complex::new(0.0, 0.0), |acc, j| {
+                    acc + Complex::new(a[i][2 * j], a[i][2 * j + 1]) * v[j][k]
+                });
+                let lv = v[i][k] * Complex::new(values[k], 0.0);
+                assert!((av - lv).modulus() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn solves_two_by_two_systems() {
+        // eigenvalues 1 and 2, a real and a complex matrix
+        for a in [
+            hamiltonian(&[(1.5, "I"), (0.5, "X")]),
+            hamiltonian(&[(1.5, "I"), (0.3, "Y"), (0.4, "Z")]),
+        ] {
+            let mut prepare = Circuit::new(1);
+            prepare.ry(0, 1.1).rz(0, 0.4);
+            let b = prepare.simulate().amplitudes(1);
+            let solution = solve(&a, &prepare, 2, PI / 2.0);
+            let x = classical_solution(&a, &b);
+            assert!((solution.fidelity(&x) - 1.0).abs() < EPSILON, "{a}");
+            assert!(solution.success_probability > 0.25);
+        }
+    }
+
+    #[test]
+    fn solves_a_four_by_four_system() {
+        let a = hamiltonian(&[(2.5, "II"), (0.5, "XX"), (1.0, "ZZ")]);
+        let mut prepare = Circuit::new(2);
+        prepare.h(0).ry(1, 0.7).cnot(0, 1);
+        let b = prepare.simulate().amplitudes(2);
+        let solution = solve(&a, &prepare, 3, PI / 4.0);
+        assert!((solution.fidelity(&classical_solution(&a, &b)) - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn clock_and_ancilla_are_uncomputed() {
+        // the whole state is |x⟩|0⟩|1⟩ on success and |·⟩|0⟩|0⟩ otherwise
+        let a = hamiltonian(&[(1.5, "I"), (0.5, "X")]);
+        let amplitudes = hhl(&a, &Circuit::new(1), 2, PI / 2.0)
+            .simulate()
+            .amplitudes(4);
+        for (x, amplitude) in amplitudes.iter().enumerate() {
+            if x & 0b0110 != 0 {
+                assert!(amplitude.modulus() < EPSILON, "{x:04b}");
+            }
+        }
+        // and the ancilla reads 1 with probability Σ |βₖ|²/k² = 1/2 + 1/8
+        let p: f64 = [0b1000, 0b1001]
+            .iter()
+            .map(|&x| amplitudes[x].modulus().powi(2))
+            .sum();
+        assert!((p - 0.625).abs() < EPSILON);
+    }
+
+    #[test]
+    fn inexact_eigenvalues_lose_fidelity() {
+        // eigenvalues 1 and 2.5, the second between clock values
+        let a = hamiltonian(&[(1.75, "I"), (0.75, "X")]);
+        let x = classical_solution(&a, &Circuit::new(1).simulate().amplitudes(1));
+        let solution = solve(&a, &Circuit::new(1), 2, PI / 2.0);
+        let fidelity = solution.fidelity(&x);
+        assert!(fidelity < 0.9999, "{fidelity}");
+        assert!(fidelity > 0.99, "{fidelity}");
+    }
+
+    #[test]
+    #[should_panic(expected = "HHL requires a Hermitian matrix")]
+    fn rejects_non_hermitian_matrices() {
+        let a = Operator::from_permutation(1, |x| x) * Complex::new(0.0, 1.0);
+        hhl(&a, &Circuit::new(1), 2, 1.0);
+    }
+}
@@ -2,48 +2,119 @@ use std::collections::HashMap;
 
 use num::{One, Zero};
 
+use crate::matrix::Vector;
+use crate::{Complex, Conjugate, Latex};
+
+mod parse;
+
+pub(crate) use parse::parse_scalar;
+pub use parse::ParseError;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct UnitKetBra {
-    ket: u32,
-    bra: u32,
+    ket: u64,
+    bra: u64,
     n: u32,
+    d: u32,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct KetBra<T> {
-    scalar: T,
-    ket: u32,
-    bra: u32,
-    n: u32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KetBra<T> {
+    pub(crate) scalar: T,
+    pub(crate) ket: u64,
+    pub(crate) bra: u64,
+    pub(crate) n: u32,
+    pub(crate) d: u32,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-struct State<T> {
-    scalar: T,
-    superpositions: Vec<Ket<T>>,
+pub struct State<T> {
+    pub(crate) scalar: T,
+    pub(crate) superpositions: Vec<Ket<T>>,
 }
 
+/// The dual ⟨ψ| of a state. Coefficients are taken as written, so a bra
+/// built from scratch is not conjugated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bra<T>(pub(crate) State<T>);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct UnitKet {
-    ket: u32,
+    ket: u64,
     n: u32,
+    d: u32,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct Ket<T> {
-    scalar: T,
-    ket: u32,
-    n: u32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ket<T> {
+    pub(crate) scalar: T,
+    pub(crate) ket: u64,
+    pub(crate) n: u32,
+    /// Local dimension of each site, 2 for qubits.
+    pub(crate) d: u32,
+}
+
+/// Largest register the packed `u64` basis indices can address.
+pub const MAX_QUBITS: u32 = 64;
+
+/// Which end of a basis label qubit 0 sits at. Kets are stored and printed
+/// [`Little`](Endianness::Little)-endian: qubit `i` is digit `i` of the index
+/// counting from the right, as in Qiskit. Textbooks usually number qubits from
+/// the left instead, which is [`Big`](Endianness::Big)-endian.
+///
+/// Tensor products pack the same way under both conventions, with the digits
+/// of `a` to the left of those of `b` in `a.tensor(&b)`; only the qubit number
+/// given to each digit differs. The `*_with` methods take qubit numbers and
+/// produce indices and labels in the requested convention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+/// Number of sites of `m ⊗ n`, if the basis indices of its `d`-level sites
+/// still fit in a `u64`.
+fn checked_width(m: u32, n: u32, d: u32) -> Option<u32> {
+    let n = m.checked_add(n)?;
+    let size = (d as u128).checked_pow(n)?;
+    (size <= 1 << 64).then_some(n)
+}
+
+fn tensor_overflow(m: u32, n: u32, d: u32) -> ! {
+    if d == 2 {
+        panic!("tensor product of {m} and {n} qubits exceeds the {MAX_QUBITS}-qubit limit of the basis index")
+    }
+    panic!("tensor product of {m} and {n} qudits of dimension {d} overflows the basis index")
+}
+
+/// Packs `|hi⟩ ⊗ |lo⟩`, where `lo` spans `lo_n` sites of dimension `d`.
+fn pack(hi: u64, lo: u64, lo_n: u32, d: u32) -> u64 {
+    // widened so that a full 64-qubit `lo` with an empty `hi` does not overflow
+    (hi as u128 * (d as u128).pow(lo_n) + lo as u128) as u64
 }
 
 impl<T: Copy + std::ops::Mul<Output = T>> KetBra<T> {
-    fn tensor(&self, other: &KetBra<T>) -> KetBra<T> {
-        KetBra {
+    /// `self ⊗ other`, or `None` if the result would exceed [`MAX_QUBITS`].
+    pub fn checked_tensor(&self, other: &KetBra<T>) -> Option<KetBra<T>> {
+        assert_eq!(
+            self.d, other.d,
+            "tensor product of qudits of different dimensions"
+        );
+        let n = checked_width(self.n, other.n, self.d)?;
+
+        Some(KetBra {
             scalar: self.scalar * other.scalar,
-            ket: self.ket * 2u32.pow(other.n) + other.ket,
-            bra: self.bra * 2u32.pow(other.n) + other.bra,
-            n: self.n + other.n,
-        }
+            ket: pack(self.ket, other.ket, other.n, self.d),
+            bra: pack(self.bra, other.bra, other.n, self.d),
+            n,
+            d: self.d,
+        })
+    }
+
+    pub fn tensor(&self, other: &KetBra<T>) -> KetBra<T> {
+        self.checked_tensor(other)
+            .unwrap_or_else(|| tensor_overflow(self.n, other.n, self.d))
     }
 }
 
@@ -56,33 +127,317 @@ impl<T: std::ops::Neg<Output = T>> std::ops::Neg for KetBra<T> {
             ket: self.ket,
             bra: self.bra,
             n: self.n,
+            d: self.d,
+        }
+    }
+}
+
+impl<T: Copy + std::ops::Mul<Output = T>> Ket<T> {
+    /// `self ⊗ other`, or `None` if the result would exceed [`MAX_QUBITS`].
+    pub fn checked_tensor(&self, other: &Ket<T>) -> Option<Ket<T>> {
+        assert_eq!(
+            self.d, other.d,
+            "tensor product of qudits of different dimensions"
+        );
+        let n = checked_width(self.n, other.n, self.d)?;
+
+        Some(Ket {
+            scalar: self.scalar * other.scalar,
+            ket: pack(self.ket, other.ket, other.n, self.d),
+            n,
+            d: self.d,
+        })
+    }
+
+    pub fn tensor(&self, other: &Ket<T>) -> Ket<T> {
+        self.checked_tensor(other)
+            .unwrap_or_else(|| tensor_overflow(self.n, other.n, self.d))
+    }
+}
+
+impl<T: Copy + std::ops::Mul<Output = T>> State<T> {
+    /// `self ⊗ other`, or `None` if the result would exceed [`MAX_QUBITS`].
+    pub fn checked_tensor(&self, other: &State<T>) -> Option<State<T>> {
+        let mut superpositions = Vec::new();
+
+        for k in &self.superpositions {
+            for other_k in &other.superpositions {
+                superpositions.push(k.checked_tensor(other_k)?);
+            }
+        }
+
+        Some(State {
+            scalar: self.scalar * other.scalar,
+            superpositions,
+        })
+    }
+
+    pub fn tensor(&self, other: &State<T>) -> State<T> {
+        self.checked_tensor(other).unwrap_or_else(|| {
+            let n = |s: &State<T>| s.superpositions.first().map_or(0, |k| k.n);
+            let d = self.superpositions.first().map_or(2, |k| k.d);
+            tensor_overflow(n(self), n(other), d)
+        })
+    }
+}
+
+impl<T: std::ops::Neg<Output = T>> std::ops::Neg for Ket<T> {
+    type Output = Ket<T>;
+
+    fn neg(self) -> Self::Output {
+        Ket {
+            scalar: -self.scalar,
+            ket: self.ket,
+            n: self.n,
+            d: self.d,
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-struct Operator<T> {
-    scalar: T,
-    ones: Vec<KetBra<T>>,
+pub struct Operator<T> {
+    pub(crate) scalar: T,
+    pub(crate) ones: Vec<KetBra<T>>,
 }
 
 impl<T: Copy + std::ops::Mul<Output = T>> Operator<T> {
-    fn tensor(&self, other: &Operator<T>) -> Operator<T> {
+    /// `self ⊗ other`, or `None` if the result would exceed [`MAX_QUBITS`].
+    pub fn checked_tensor(&self, other: &Operator<T>) -> Option<Operator<T>> {
         let mut ones = Vec::new();
 
         for kb in &self.ones {
             for other_kb in &other.ones {
-                ones.push(kb.tensor(other_kb));
+                ones.push(kb.checked_tensor(other_kb)?);
             }
         }
 
-        Operator {
+        Some(Operator {
             scalar: self.scalar * other.scalar,
             ones,
+        })
+    }
+
+    pub fn tensor(&self, other: &Operator<T>) -> Operator<T> {
+        self.checked_tensor(other).unwrap_or_else(|| {
+            let n = |op: &Operator<T>| op.ones.first().map_or(0, |kb| kb.n);
+            let d = self.ones.first().map_or(2, |kb| kb.d);
+            tensor_overflow(n(self), n(other), d)
+        })
+    }
+}
+
+impl<T: Copy> Operator<T> {
+    /// Lifts a k-qubit operator to an n-qubit one, with qubit `i` of `self`
+    /// acting on qubit `targets[i]` of the register and the identity acting on
+    /// every other qubit. Qubit `i` is bit `i` of the basis index, so
+    /// `op.embed(&[0], 2)` equals `id.tensor(&op)`. Qudit operators embed the
+    /// same way, digit by digit.
+    pub fn embed(&self, targets: &[usize], n: u32) -> Operator<T> {
+        let k = targets.len() as u32;
+        assert!(k <= n, "cannot embed {k} target qubits into {n} qubits");
+        assert!(
+            n <= MAX_QUBITS,
+            "cannot embed into more than {MAX_QUBITS} qubits"
+        );
+        for (i, &t) in targets.iter().enumerate() {
+            assert!(
+                t < n as usize,
+                "target qubit {t} out of range for {n} qubits"
+            );
+            assert!(!targets[..i].contains(&t), "duplicate target qubit {t}");
+        }
+
+        let idle: Vec<usize> = (0..n as usize).filter(|q| !targets.contains(q)).collect();
+        let mut ones = Vec::new();
+
+        for kb in &self.ones {
+            assert_eq!(kb.n, k, "operator acts on {} qubits, got {k} targets", kb.n);
+            assert!(
+                checked_width(n, 0, kb.d).is_some(),
+                "{n} qudits of dimension {} overflow the basis index",
+                kb.d
+            );
+            let ket = scatter(kb.ket, targets, kb.d);
+            let bra = scatter(kb.bra, targets, kb.d);
+            let combinations = (kb.d as u64)
+                .checked_pow(idle.len() as u32)
+                .expect("embedded operator overflows the basis index");
+
+            for rest in 0..combinations {
+                let rest = scatter(rest, &idle, kb.d);
+                ones.push(KetBra {
+                    scalar: kb.scalar,
+                    ket: ket + rest,
+                    bra: bra + rest,
+                    n,
+                    d: kb.d,
+                });
+            }
+        }
+
+        Operator {
+            scalar: self.scalar,
+            ones,
+        }
+    }
+}
+
+impl<T: Copy> Operator<T> {
+    /// Relabels the qubits so that qubit `i` becomes qubit `perm[i]`.
+    pub fn permute_qubits(&self, perm: &[usize]) -> Operator<T> {
+        Operator {
+            scalar: self.scalar,
+            ones: self
+                .ones
+                .iter()
+                .map(|kb| {
+                    check_permutation(perm, kb.n);
+                    KetBra {
+                        ket: scatter(kb.ket, perm, kb.d),
+                        bra: scatter(kb.bra, perm, kb.d),
+                        ..*kb
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<T: Copy> State<T> {
+    /// Relabels the qubits so that qubit `i` becomes qubit `perm[i]`.
+    pub fn permute_qubits(&self, perm: &[usize]) -> State<T> {
+        State {
+            scalar: self.scalar,
+            superpositions: self
+                .superpositions
+                .iter()
+                .map(|k| {
+                    check_permutation(perm, k.n);
+                    Ket {
+                        ket: scatter(k.ket, perm, k.d),
+                        ..*k
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<T: Copy> Operator<T> {
+    /// Copy with the qubit order reversed, converting between the two
+    /// [`Endianness`] conventions.
+    pub fn reversed_qubits(&self) -> Operator<T> {
+        match self.ones.first() {
+            Some(kb) => self.permute_qubits(&reversal(kb.n)),
+            None => self.clone(),
+        }
+    }
+
+    /// [`Operator::embed`] with `targets` and the operator's own qubits
+    /// numbered in the given convention.
+    pub fn embed_with(&self, targets: &[usize], n: u32, endianness: Endianness) -> Operator<T> {
+        match endianness {
+            Endianness::Little => self.embed(targets, n),
+            Endianness::Big => {
+                let targets: Vec<usize> = targets
+                    .iter()
+                    .rev()
+                    .map(|&t| {
+                        assert!(
+                            t < n as usize,
+                            "target qubit {t} out of range for {n} qubits"
+                        );
+                        n as usize - 1 - t
+                    })
+                    .collect();
+                self.embed(&targets, n)
+            }
         }
     }
 }
 
+impl<T: Copy> State<T> {
+    /// Copy with the qubit order reversed, converting between the two
+    /// [`Endianness`] conventions.
+    pub fn reversed_qubits(&self) -> State<T> {
+        match self.superpositions.first() {
+            Some(k) => self.permute_qubits(&reversal(k.n)),
+            None => self.clone(),
+        }
+    }
+}
+
+impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + Zero> State<T> {
+    /// [`State::amplitudes`] indexed in the given convention.
+    pub fn amplitudes_with(&self, n: u32, endianness: Endianness) -> Vec<T> {
+        match endianness {
+            Endianness::Little => self.amplitudes(n),
+            Endianness::Big => self.reversed_qubits().amplitudes(n),
+        }
+    }
+}
+
+impl<T: Copy + std::fmt::Display + One + PartialEq> State<T> {
+    /// The Display form with labels written in the given convention, so that
+    /// `Big` puts qubit 0 leftmost.
+    pub fn to_string_with(&self, endianness: Endianness) -> String {
+        match endianness {
+            Endianness::Little => self.to_string(),
+            Endianness::Big => self.reversed_qubits().to_string(),
+        }
+    }
+}
+
+impl<T: Copy + std::fmt::Display + One + PartialEq> Operator<T> {
+    /// The Display form with labels written in the given convention, so that
+    /// `Big` puts qubit 0 leftmost.
+    pub fn to_string_with(&self, endianness: Endianness) -> String {
+        match endianness {
+            Endianness::Little => self.to_string(),
+            Endianness::Big => self.reversed_qubits().to_string(),
+        }
+    }
+}
+
+fn reversal(n: u32) -> Vec<usize> {
+    (0..n as usize).rev().collect()
+}
+
+fn check_permutation(perm: &[usize], n: u32) {
+    assert_eq!(
+        perm.len(),
+        n as usize,
+        "permutation of {} qubits applied to {n} qubits",
+        perm.len()
+    );
+    for (i, &p) in perm.iter().enumerate() {
+        assert!(
+            p < perm.len(),
+            "{p} is out of range for a permutation of {n} qubits"
+        );
+        assert!(
+            !perm[..i].contains(&p),
+            "{p} appears twice in the permutation"
+        );
+    }
+}
+
+/// Moves base-`d` digit `i` of `index` to digit `positions[i]`.
+fn scatter(index: u64, positions: &[usize], d: u32) -> u64 {
+    let d = d as u64;
+    let place = |i: usize| {
+        u32::try_from(i)
+            .ok()
+            .and_then(|i| d.checked_pow(i))
+            .unwrap_or_else(|| panic!("digit {i} of dimension {d} overflows the basis index"))
+    };
+    positions
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| index / place(i) % d * place(p))
+        .sum()
+}
+
 impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + Zero + PartialEq>
     std::ops::Mul<Operator<T>> for Operator<T>
 {
@@ -99,6 +454,7 @@ impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + Zero + PartialE
                         ket: kb.ket,
                         bra: other_kb.bra,
                         n: kb.n, // TODO assert kb.n == other_kb.n
+                        d: kb.d,
                     };
                     let scalar = kb.scalar * other_kb.scalar;
 
@@ -117,6 +473,7 @@ impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + Zero + PartialE
                     ket: k.ket,
                     bra: k.bra,
                     n: k.n,
+                    d: k.d,
                 })
                 .collect(),
         }
@@ -131,15 +488,18 @@ impl<T: Copy + std::ops::AddAssign + num::Zero + num::One + PartialEq> std::ops:
     fn add(self, rhs: Operator<T>) -> Self::Output {
         let mut ones: HashMap<UnitKetBra, T> = Default::default();
 
-        for kb in self.ones.iter().chain(rhs.ones.iter()) {
+        let lhs = self.ones.iter().map(|kb| (kb, self.scalar));
+        let rhs = rhs.ones.iter().map(|kb| (kb, rhs.scalar));
+
+        for (kb, scalar) in lhs.chain(rhs) {
             let unit = UnitKetBra {
                 ket: kb.ket,
                 bra: kb.bra,
                 n: kb.n,
+                d: kb.d,
             };
 
-            // TODO test operator scalar affecting result
-            *ones.entry(unit).or_insert(T::zero()) += kb.scalar * self.scalar;
+            *ones.entry(unit).or_insert(T::zero()) += kb.scalar * scalar;
         }
 
         Operator {
@@ -152,6 +512,7 @@ impl<T: Copy + std::ops::AddAssign + num::Zero + num::One + PartialEq> std::ops:
                     ket: k.ket,
                     bra: k.bra,
                     n: k.n,
+                    d: k.d,
                 })
                 .collect(),
         }
@@ -173,6 +534,7 @@ impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + num::Zero + Par
                     let unit = UnitKet {
                         ket: kb.ket,
                         n: kb.n, // TODO assert kb.n == pos.n
+                        d: kb.d,
                     };
                     let scalar = kb.scalar * pos.scalar;
 
@@ -190,12 +552,71 @@ impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + num::Zero + Par
                     scalar,
                     ket: k.ket,
                     n: k.n,
+                    d: k.d,
                 })
                 .collect(),
         }
     }
 }
 
+impl<T: Copy + std::ops::Mul<Output = T>> std::ops::Mul<Bra<T>> for State<T> {
+    type Output = Operator<T>;
+
+    fn mul(self, rhs: Bra<T>) -> Self::Output {
+        let mut ones = Vec::new();
+
+        for k in &self.superpositions {
+            for b in &rhs.0.superpositions {
+                assert_eq!(k.n, b.n, "outer product of kets on different qubit counts");
+                assert_eq!(k.d, b.d, "outer product of qudits of different dimensions");
+                ones.push(KetBra {
+                    scalar: k.scalar * b.scalar,
+                    ket: k.ket,
+                    bra: b.ket,
+                    n: k.n,
+                    d: k.d,
+                });
+            }
+        }
+
+        Operator {
+            scalar: self.scalar * rhs.0.scalar,
+            ones,
+        }
+    }
+}
+
+impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + Zero> std::ops::Mul<State<T>>
+    for Bra<T>
+{
+    type Output = T;
+
+    fn mul(self, rhs: State<T>) -> Self::Output {
+        let mut sum = T::zero();
+
+        for b in &self.0.superpositions {
+            for k in &rhs.superpositions {
+                if b.ket == k.ket {
+                    sum += b.scalar * k.scalar;
+                }
+            }
+        }
+
+        self.0.scalar * rhs.scalar * sum
+    }
+}
+
+impl<T: std::ops::Neg<Output = T>> std::ops::Neg for Operator<T> {
+    type Output = Operator<T>;
+
+    fn neg(self) -> Self::Output {
+        Operator {
+            scalar: self.scalar,
+            ones: self.ones.into_iter().map(|kb| -kb).collect(),
+        }
+    }
+}
+
 impl<T: std::ops::Mul<Output = T>> std::ops::Mul<T> for Operator<T> {
     type Output = Operator<T>;
 
@@ -208,215 +629,1819 @@ impl<T: std::ops::Mul<Output = T>> std::ops::Mul<T> for Operator<T> {
 }
 
 impl<T: One> Operator<T> {
-    fn identity(n: u32) -> Self {
+    /// The identity on n qubits.
+    pub fn identity(n: u32) -> Self {
+        Self::qudit_identity(n, 2)
+    }
+
+    /// The identity on n sites of local dimension d.
+    pub fn qudit_identity(n: u32, d: u32) -> Self {
+        let dim = (d as u64)
+            .checked_pow(n)
+            .expect("identity overflows the basis index");
+
         Operator {
             scalar: T::one(),
-            // TODO this should depend on n (currently only works for 2x2 identity)
-            ones: vec![
-                KetBra {
-                    scalar: T::one(),
-                    ket: 0,
-                    bra: 0,
-                    n,
-                },
-                KetBra {
+            ones: (0..dim)
+                .map(|x| KetBra {
                     scalar: T::one(),
-                    ket: 1,
-                    bra: 1,
+                    ket: x,
+                    bra: x,
                     n,
-                },
-            ],
+                    d,
+                })
+                .collect(),
         }
     }
-}
 
-impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for Operator<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.scalar != T::one() {
-            write!(f, "{}(", self.scalar)?;
+    /// The operator Σ|f(x)⟩⟨x| on n qubits of a classical reversible function,
+    /// e.g. `|x| x ^ ((x & 1) << 1)` for a CNOT controlled by qubit 0. `f` is
+    /// only checked to stay inside the basis; see
+    /// [`Operator::checked_from_permutation`] to also check it is a bijection.
+    pub fn from_permutation(n: u32, f: impl Fn(u64) -> u64) -> Self {
+        let dim = qubit_dimension(n);
+
+        Operator {
+            scalar: T::one(),
+            ones: (0..dim)
+                .map(|x| {
+                    let y = f(x);
+                    assert!(y < dim, "f({x}) = {y} is outside the {n}-qubit basis");
+                    KetBra {
+                        scalar: T::one(),
+                        ket: y,
+                        bra: x,
+                        n,
+                        d: 2,
+                    }
+                })
+                .collect(),
         }
+    }
 
-        write!(f, "{}", self.ones[0])?;
+    /// [`Operator::from_permutation`], or `None` if `f` is not a bijection on
+    /// the n-qubit basis.
+    pub fn checked_from_permutation(n: u32, f: impl Fn(u64) -> u64) -> Option<Self> {
+        let dim = qubit_dimension(n);
+        let mut seen = vec![false; dim as usize];
 
-        for kb in self.ones.iter().skip(1) {
-            write!(f, " + {}", kb)?;
+        for x in 0..dim {
+            let y = f(x);
+            if y >= dim || std::mem::replace(&mut seen[y as usize], true) {
+                return None;
+            }
         }
 
-        if self.scalar != T::one() {
-            write!(f, ")")?;
-        }
+        Some(Self::from_permutation(n, f))
+    }
+}
 
-        Ok(())
+impl<T: One + std::ops::Neg<Output = T>> Operator<T> {
+    /// The diagonal phase oracle Σ(−1)^pred(x)|x⟩⟨x| on n qubits, flipping the
+    /// sign of every basis state the predicate marks.
+    pub fn phase_oracle(n: u32, pred: impl Fn(u64) -> bool) -> Self {
+        Operator {
+            scalar: T::one(),
+            ones: (0..qubit_dimension(n))
+                .map(|x| KetBra {
+                    scalar: if pred(x) { -T::one() } else { T::one() },
+                    ket: x,
+                    bra: x,
+                    n,
+                    d: 2,
+                })
+                .collect(),
+        }
     }
 }
 
-impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for State<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.scalar != T::one() {
-            write!(f, "{}(", self.scalar)?;
+/// The edges (i, j), i < j, of the graph with adjacency matrix `adjacency`,
+/// checked to be square, symmetric and free of self-loops.
+pub(crate) fn graph_edges(adjacency: &[Vec<bool>]) -> Vec<(usize, usize)> {
+    let n = adjacency.len();
+    for (i, row) in adjacency.iter().enumerate() {
+        assert_eq!(
+            row.len(),
+            n,
+            "row {i} of the adjacency matrix has {} entries, not {n}",
+            row.len()
+        );
+        assert!(!row[i], "vertex {i} has a self-loop");
+        for j in 0..i {
+            assert_eq!(
+                row[j], adjacency[j][i],
+                "the adjacency matrix is not symmetric at ({i}, {j})"
+            );
         }
+    }
+    (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .filter(|&(i, j)| adjacency[i][j])
+        .collect()
+}
 
-        write!(f, "{}", self.superpositions[0])?;
+fn qubit_dimension(n: u32) -> u64 {
+    2u64.checked_pow(n)
+        .expect("operator overflows the basis index")
+}
 
-        for kb in self.superpositions.iter().skip(1) {
-            write!(f, " + {}", kb)?;
-        }
+impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + Zero + One + PartialEq>
+    Operator<T>
+{
+    /// `self` multiplied by itself `k` times, by repeated squaring. Powers
+    /// k ≥ 1 of the zero operator are zero, but its power 0 panics, as an
+    /// operator with no terms gives no size for the identity.
+    pub fn pow(&self, k: u32) -> Operator<T> {
+        let Some(first) = self.ones.first() else {
+            assert!(k > 0, "zeroth power of an operator with no terms");
+            return self.clone();
+        };
+        let mut result = Operator::qudit_identity(first.n, first.d);
+        let mut base = self.clone();
+        let mut k = k;
 
-        if self.scalar != T::one() {
-            write!(f, ")")?;
+        while k > 0 {
+            if k & 1 == 1 {
+                result = result * base.clone();
+            }
+            k >>= 1;
+            if k > 0 {
+                base = base.clone() * base;
+            }
         }
 
-        Ok(())
+        result
     }
 }
 
-impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for KetBra<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.scalar != T::one() {
-            write!(f, "{}", self.scalar)?;
+impl<T: Copy + std::ops::AddAssign + Zero + One + PartialEq> Operator<T> {
+    /// Canonical form: the outer scalar distributed over the terms, duplicate
+    /// ketbras merged, zero terms dropped and the rest sorted by (ket, bra),
+    /// so that equal operators compare equal.
+    pub fn simplify(&self) -> Operator<T> {
+        let mut ones: HashMap<UnitKetBra, T> = Default::default();
+        for kb in &self.ones {
+            let unit = UnitKetBra {
+                ket: kb.ket,
+                bra: kb.bra,
+                n: kb.n,
+                d: kb.d,
+            };
+            *ones.entry(unit).or_insert(T::zero()) += self.scalar * kb.scalar;
         }
-        write!(
-            f,
-            "|{:0>width$b}⟩⟨{:0>width$b}|",
-            self.ket,
-            self.bra,
-            width = self.n as usize
-        )
-    }
-}
 
-impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for Ket<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.scalar != T::one() {
-            write!(f, "{}", self.scalar)?;
+        let mut ones: Vec<KetBra<T>> = ones
+            .into_iter()
+            .filter(|(_, scalar)| *scalar != T::zero())
+            .map(|(k, scalar)| KetBra {
+                scalar,
+                ket: k.ket,
+                bra: k.bra,
+                n: k.n,
+                d: k.d,
+            })
+            .collect();
+        ones.sort_by_key(|kb| (kb.ket, kb.bra));
+
+        Operator {
+            scalar: T::one(),
+            ones,
         }
-        write!(f, "|{:0>width$b}⟩", self.ket, width = self.n as usize)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::ket::{Ket, State};
-
-    use super::{KetBra, Operator};
-
-    fn k(ket: u32, n: u32) -> Ket<i32> {
-        Ket { scalar: 1, ket, n }
+impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + Zero> Operator<T> {
+    /// Sum of the diagonal ketbra scalars, times the outer scalar.
+    pub fn trace(&self) -> T {
+        let mut sum = T::zero();
+        for kb in self.ones.iter().filter(|kb| kb.ket == kb.bra) {
+            sum += kb.scalar;
+        }
+        self.scalar * sum
     }
+}
 
-    fn k_f64(ket: u32, n: u32) -> Ket<f64> {
-        Ket {
-            scalar: 1.0,
-            ket,
-            n,
+impl<T: Copy + Conjugate> Operator<T> {
+    /// The conjugate transpose A†.
+    pub fn adjoint(&self) -> Operator<T> {
+        Operator {
+            scalar: self.scalar.conj(),
+            ones: self
+                .ones
+                .iter()
+                .map(|kb| KetBra {
+                    scalar: kb.scalar.conj(),
+                    ket: kb.bra,
+                    bra: kb.ket,
+                    n: kb.n,
+                    d: kb.d,
+                })
+                .collect(),
         }
     }
+}
 
-    fn kb(ket: u32, bra: u32, n: u32) -> KetBra<i32> {
+impl<T: Copy + Conjugate> State<T> {
+    /// The bra ⟨ψ| dual to this state.
+    pub fn dagger(&self) -> Bra<T> {
+        Bra(State {
+            scalar: self.scalar.conj(),
+            superpositions: self
+                .superpositions
+                .iter()
+                .map(|k| Ket {
+                    scalar: k.scalar.conj(),
+                    ..*k
+                })
+                .collect(),
+        })
+    }
+}
+
+impl<T: From<f64>> State<T> {
+    /// The Bell states Φ⁺, Φ⁻, Ψ⁺ and Ψ⁻ for `i` = 0 to 3.
+    pub fn bell(i: usize) -> Self {
+        let (ket, sign) = match i {
+            0 => (0b00, 1.0),
+            1 => (0b00, -1.0),
+            2 => (0b01, 1.0),
+            3 => (0b01, -1.0),
+            _ => panic!("there are only four Bell states, got index {i}"),
+        };
+
+        State {
+            scalar: T::from(1.0 / 2.0_f64.sqrt()),
+            superpositions: vec![
+                Ket {
+                    scalar: T::from(1.0),
+                    ket,
+                    n: 2,
+                    d: 2,
+                },
+                Ket {
+                    scalar: T::from(sign),
+                    ket: ket ^ 0b11,
+                    n: 2,
+                    d: 2,
+                },
+            ],
+        }
+    }
+
+    /// (|0…0⟩ + |1…1⟩)/√2 on n qubits.
+    pub fn ghz(n: u32) -> Self {
+        assert!((1..=MAX_QUBITS).contains(&n), "GHZ state on {n} qubits");
+        let ones = u64::MAX >> (MAX_QUBITS - n);
+
+        State {
+            scalar: T::from(1.0 / 2.0_f64.sqrt()),
+            superpositions: [0, ones]
+                .into_iter()
+                .map(|ket| Ket {
+                    scalar: T::from(1.0),
+                    ket,
+                    n,
+                    d: 2,
+                })
+                .collect(),
+        }
+    }
+
+    /// The equal superposition of the n single-excitation states, 1/√n Σ |0…1…0⟩.
+    pub fn w(n: u32) -> Self {
+        assert!((1..=MAX_QUBITS).contains(&n), "W state on {n} qubits");
+
+        State {
+            scalar: T::from(1.0 / (n as f64).sqrt()),
+            superpositions: (0..n)
+                .map(|q| Ket {
+                    scalar: T::from(1.0),
+                    ket: 1 << q,
+                    n,
+                    d: 2,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<T: Copy + std::ops::Mul<Output = T>> State<T> {
+    /// The terms as (amplitude, ket, n) with the outer scalar folded into each
+    /// amplitude. Duplicate kets are not merged.
+    pub fn terms(&self) -> impl Iterator<Item = (T, u64, u32)> + '_ {
+        self.superpositions
+            .iter()
+            .map(|k| (self.scalar * k.scalar, k.ket, k.n))
+    }
+}
+
+impl<T: Copy + std::ops::Mul<Output = T>> Operator<T> {
+    /// The terms as (scalar, ket, bra, n) with the outer scalar folded into
+    /// each. Duplicate ketbras are not merged, see [`Operator::simplify`].
+    pub fn terms(&self) -> impl Iterator<Item = (T, u64, u64, u32)> + '_ {
+        self.ones
+            .iter()
+            .map(|kb| (self.scalar * kb.scalar, kb.ket, kb.bra, kb.n))
+    }
+}
+
+impl<T> State<T> {
+    /// Number of qubits (or qudits) the state is defined on, 0 if it has no
+    /// terms.
+    pub fn num_qubits(&self) -> u32 {
+        self.superpositions.first().map_or(0, |k| k.n)
+    }
+}
+
+impl<T> Operator<T> {
+    /// Number of qubits (or qudits) the operator acts on, 0 if it has no
+    /// terms.
+    pub fn num_qubits(&self) -> u32 {
+        self.ones.first().map_or(0, |kb| kb.n)
+    }
+}
+
+impl<T: One> State<T> {
+    /// The basis state |ket⟩ on n qubits.
+    pub fn basis(ket: u64, n: u32) -> Self {
+        Self::qudit_basis(ket, n, 2)
+    }
+
+    /// The basis state |ket⟩ on n sites of local dimension d, with `ket`
+    /// read as a base-d number.
+    pub fn qudit_basis(ket: u64, n: u32, d: u32) -> Self {
+        assert!(d >= 2, "qudit dimension must be at least 2");
+        assert!(
+            checked_width(n, 0, d).is_some(),
+            "{n} qudits of dimension {d} overflow the basis index"
+        );
+
+        State {
+            scalar: T::one(),
+            superpositions: vec![Ket {
+                scalar: T::one(),
+                ket,
+                n,
+                d,
+            }],
+        }
+    }
+}
+
+impl<T: One> Bra<T> {
+    /// The basis bra ⟨bra| on n qubits.
+    pub fn basis(bra: u64, n: u32) -> Self {
+        Bra(State::basis(bra, n))
+    }
+
+    /// The basis bra ⟨bra| on n sites of local dimension d.
+    pub fn qudit_basis(bra: u64, n: u32, d: u32) -> Self {
+        Bra(State::qudit_basis(bra, n, d))
+    }
+}
+
+/// Packs a binary basis label such as `"011"` into `(index, n)`, ignoring
+/// spaces and quotes so that it accepts `stringify!` output. Used by the
+/// `ket!` family of macros to do the packing at compile time.
+#[doc(hidden)]
+pub const fn basis_label(label: &str) -> (u64, u32) {
+    let bytes = label.as_bytes();
+    let mut i = 0;
+    let mut ket = 0;
+    let mut n = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'0' => {
+                ket *= 2;
+                n += 1;
+            }
+            b'1' => {
+                ket = ket * 2 + 1;
+                n += 1;
+            }
+            b' ' | b'"' => {}
+            _ => panic!("basis labels may only contain the digits 0 and 1"),
+        }
+        assert!(n <= MAX_QUBITS, "basis label exceeds the qubit limit");
+        i += 1;
+    }
+
+    assert!(n > 0, "empty basis label");
+    (ket, n)
+}
+
+impl<T: Copy + std::ops::Mul<Output = T> + std::ops::AddAssign + Zero> State<T> {
+    /// The full array of amplitudes on n qubits ordered by basis index, with
+    /// missing terms zero-filled and the outer scalar folded in.
+    pub fn amplitudes(&self, n: u32) -> Vec<T> {
+        let d = self.superpositions.first().map_or(2, |k| k.d);
+        let dim = (d as usize)
+            .checked_pow(n)
+            .expect("too many amplitudes to materialize");
+        let mut amplitudes = vec![T::zero(); dim];
+
+        for k in &self.superpositions {
+            assert_eq!(k.n, n, "term on {} qubits in a {n}-qubit export", k.n);
+            amplitudes[k.ket as usize] += self.scalar * k.scalar;
+        }
+
+        amplitudes
+    }
+}
+
+impl<T: Copy + Into<Complex<f64>>> State<T> {
+    /// Dense column vector of the state, with entry `i` holding the amplitude
+    /// of `|i⟩` (the outer scalar folded in).
+    pub fn to_vector<const N: usize>(&self) -> Vector<Complex<f64>, N> {
+        let scalar: Complex<f64> = self.scalar.into();
+        let mut v = [Complex::zero(); N];
+
+        for ket in &self.superpositions {
+            let i = ket.ket as usize;
+            assert!(
+                i < N,
+                "basis ket |{}⟩ does not fit in a vector of length {N}",
+                i
+            );
+            v[i] += scalar * ket.scalar.into();
+        }
+
+        Vector(v)
+    }
+
+    /// Bloch-sphere coordinates (x, y, z) of a single-qubit state, which is
+    /// normalized first.
+    pub fn bloch_vector(&self) -> (f64, f64, f64) {
+        for k in &self.superpositions {
+            assert!(
+                k.n == 1 && k.d == 2,
+                "Bloch vectors are only defined for single qubits"
+            );
+        }
+
+        let Vector([a, b]) = self.to_vector::<2>();
+        let norm = a.mod_squared() + b.mod_squared();
+        assert!(norm > 0.0, "zero state has no Bloch vector");
+
+        // ρ₁₀ = b a*, x = 2 Re ρ₁₀, y = 2 Im ρ₁₀, z = ρ₀₀ - ρ₁₁
+        let coherence = b * a.conjugate();
+        (
+            2.0 * coherence.re / norm,
+            2.0 * coherence.im / norm,
+            (a.mod_squared() - b.mod_squared()) / norm,
+        )
+    }
+
+    /// One line per basis state for the `k` largest amplitudes, with the
+    /// probability of each after normalizing, and a count of the terms left
+    /// out. Meant for eyeballing states too large for [`Display`].
+    ///
+    /// [`Display`]: std::fmt::Display
+    pub fn summary(&self, k: usize) -> String {
+        let scalar: Complex<f64> = self.scalar.into();
+        let mut amplitudes: HashMap<UnitKet, Complex<f64>> = HashMap::new();
+        for ket in &self.superpositions {
+            let unit = UnitKet {
+                ket: ket.ket,
+                n: ket.n,
+                d: ket.d,
+            };
+            *amplitudes.entry(unit).or_insert(Complex::zero()) += scalar * ket.scalar.into();
+        }
+
+        let norm: f64 = amplitudes.values().map(|a| a.mod_squared()).sum();
+        let mut terms: Vec<(UnitKet, Complex<f64>)> = amplitudes
+            .into_iter()
+            .filter(|(_, a)| !a.is_zero())
+            .collect();
+        terms.sort_by(|(u, a), (v, b)| {
+            b.mod_squared()
+                .total_cmp(&a.mod_squared())
+                .then(u.ket.cmp(&v.ket))
+        });
+
+        let mut out = String::new();
+        for (unit, a) in terms.iter().take(k) {
+            let sign = if a.im < 0.0 { '-' } else { '+' };
+            out += &format!(
+                "|{}⟩  {:.4} {sign} {:.4}i  p = {:.4}\n",
+                Label(unit.ket, unit.n, unit.d),
+                a.re,
+                a.im.abs(),
+                a.mod_squared() / norm
+            );
+        }
+        if terms.len() > k {
+            let rest = terms.len() - k;
+            let plural = if rest == 1 { "" } else { "s" };
+            out += &format!("… and {rest} more term{plural}\n");
+        }
+
+        out
+    }
+}
+
+impl Operator<Complex<f64>> {
+    /// Matrix entries keyed by (ket, bra), with the outer scalar folded in and
+    /// duplicate terms merged.
+    fn entries(&self) -> HashMap<(u64, u64), Complex<f64>> {
+        let mut entries: HashMap<(u64, u64), Complex<f64>> = HashMap::new();
+        for kb in &self.ones {
+            *entries.entry((kb.ket, kb.bra)).or_insert(Complex::zero()) += self.scalar * kb.scalar;
+        }
+        entries
+    }
+
+    /// Copy with `c` and the outer scalar folded into the terms.
+    fn folded(&self, c: Complex<f64>) -> Self {
+        Operator {
+            scalar: Complex::one(),
+            ones: self
+                .ones
+                .iter()
+                .map(|kb| KetBra {
+                    scalar: self.scalar * c * kb.scalar,
+                    ..*kb
+                })
+                .collect(),
+        }
+    }
+
+    /// The propagator exp(−iHt) of a Hermitian operator H, from a truncated
+    /// Taylor series with scaling and squaring.
+    pub fn expm(&self, t: f64) -> Operator<Complex<f64>> {
+        assert!(
+            self.is_hermitian(1e-9),
+            "expm requires a Hermitian operator"
+        );
+        let first = self
+            .ones
+            .first()
+            .expect("expm of an operator with no terms");
+
+        // scale A = -iHt down until the series converges quickly, then square
+        // the result back up: exp(A) = exp(A / 2^s)^(2^s)
+        let a = self.folded(Complex::new(0.0, -t));
+        let norm: f64 = a.ones.iter().map(|kb| kb.scalar.modulus()).sum();
+        let squarings = if norm > 0.5 {
+            (norm / 0.5).log2().ceil() as u32
+        } else {
+            0
+        };
+        let a = a.folded(Complex::new(0.5_f64.powi(squarings as i32), 0.0));
+
+        let mut result: Operator<Complex<f64>> = Operator::qudit_identity(first.n, first.d);
+        let mut term = result.clone();
+        for k in 1..=30 {
+            term = (term * a.clone()).folded(Complex::new(1.0 / k as f64, 0.0));
+            if term.ones.iter().all(|kb| kb.scalar.modulus() < 1e-18) {
+                break;
+            }
+            result = result + term.clone();
+        }
+
+        for _ in 0..squarings {
+            result = result.clone() * result;
+        }
+
+        result
+    }
+
+    /// The commutator [A, B] = AB − BA, with cancelling terms left in place.
+    pub fn commutator(&self, other: &Operator<Complex<f64>>) -> Operator<Complex<f64>> {
+        let ab = (self.clone() * other.clone()).folded(Complex::one());
+        let ba = (other.clone() * self.clone()).folded(Complex::new(-1.0, 0.0));
+        ab + ba
+    }
+
+    /// Whether [A, B] vanishes up to `eps` in every entry.
+    pub fn commutes_with(&self, other: &Operator<Complex<f64>>, eps: f64) -> bool {
+        self.commutator(other)
+            .entries()
+            .values()
+            .all(|v| v.modulus() <= eps)
+    }
+
+    /// Whether A = A† up to `eps` in every entry.
+    pub fn is_hermitian(&self, eps: f64) -> bool {
+        let entries = self.entries();
+
+        entries.iter().all(|(&(ket, bra), &v)| {
+            let mirror = entries.get(&(bra, ket)).copied().unwrap_or(Complex::zero());
+            (v - mirror.conjugate()).modulus() <= eps
+        })
+    }
+
+    /// Whether U†U = I up to `eps` in every entry, computed row by row on the
+    /// nonzero terms only: the product must hold one diagonal entry per
+    /// basis state, each close to 1, and nothing else above `eps`.
+    pub fn is_unitary(&self, eps: f64) -> bool {
+        let Some(first) = self.ones.first() else {
+            return false;
+        };
+        // no operator stores entries for all of a larger space
+        let Some(dim) = (first.d as u64).checked_pow(first.n) else {
+            return false;
+        };
+
+        let mut rows: HashMap<u64, Vec<(u64, Complex<f64>)>> = HashMap::new();
+        for (&(ket, bra), &v) in &self.entries() {
+            rows.entry(ket).or_default().push((bra, v));
+        }
+
+        // (U†U)_ij = Σ_k conj(U_ki) U_kj
+        let mut product: HashMap<(u64, u64), Complex<f64>> = HashMap::new();
+        for row in rows.values() {
+            for &(i, a) in row {
+                for &(j, b) in row {
+                    *product.entry((i, j)).or_insert(Complex::zero()) += a.conjugate() * b;
+                }
+            }
+        }
+
+        // every column needs a diagonal entry, and so a nonzero term
+        let diagonal = product.keys().filter(|(i, j)| i == j).count() as u64;
+        diagonal == dim
+            && product.iter().all(|(&(i, j), v)| {
+                if i == j {
+                    (*v - Complex::new(1.0, 0.0)).modulus() <= eps
+                } else {
+                    v.modulus() <= eps
+                }
+            })
+    }
+}
+
+impl<T: Copy + Conjugate + std::ops::Mul<Output = T>> Operator<T> {
+    /// The projector |ψ⟩⟨ψ| onto `state`, which should be normalized.
+    pub fn projector(state: &State<T>) -> Self {
+        state.clone() * state.dagger()
+    }
+}
+
+impl State<Complex<f64>> {
+    /// Probabilities |⟨bᵢ|ψ⟩|² of each outcome of a measurement in `basis`,
+    /// which should be orthonormal.
+    pub fn measurement_probabilities(&self, basis: &[State<Complex<f64>>]) -> Vec<f64> {
+        basis
+            .iter()
+            .map(|b| (b.dagger() * self.clone()).mod_squared())
+            .collect()
+    }
+
+    /// Measures in an orthonormal `basis`, returning the index of the outcome
+    /// and the collapsed state, which is that basis state.
+    pub fn measure_in_basis<R: rand::Rng + ?Sized>(
+        &self,
+        basis: &[State<Complex<f64>>],
+        rng: &mut R,
+    ) -> (usize, State<Complex<f64>>) {
+        let probabilities = self.measurement_probabilities(basis);
+        let total: f64 = probabilities.iter().sum();
+        assert!(total > 0.0, "state is orthogonal to every basis state");
+
+        let mut r = rng.random::<f64>() * total;
+        let outcome = probabilities
+            .iter()
+            .position(|&p| {
+                r -= p;
+                r < 0.0
+            })
+            .unwrap_or(basis.len() - 1);
+
+        (outcome, basis[outcome].clone())
+    }
+
+    /// Inverse of [`State::to_vector`]; `N` must be a power of two.
+    pub fn from_vector<const N: usize>(v: &Vector<Complex<f64>, N>) -> Self {
+        Self::from_amplitudes(&v.0)
+    }
+
+    /// The graph state of the simple undirected graph with `adjacency[i][j]`
+    /// set for each edge (i, j), one qubit per vertex:
+    /// 2^(−n/2) Σ_x (−1)^(Σ_(i,j) xᵢxⱼ) |x⟩, the result of CZ on every edge of
+    /// |+⟩^⊗n. [`Circuit::graph_state`] prepares it.
+    ///
+    /// [`Circuit::graph_state`]: crate::circuit::Circuit::graph_state
+    pub fn graph_state(adjacency: &[Vec<bool>]) -> Self {
+        let n = adjacency.len() as u32;
+        let edges = graph_edges(adjacency);
+        let norm = 0.5f64.powf(f64::from(n) / 2.0);
+        let amplitudes: Vec<Complex<f64>> = (0..qubit_dimension(n))
+            .map(|x| {
+                let parity = edges
+                    .iter()
+                    .filter(|&&(i, j)| x >> i & x >> j & 1 == 1)
+                    .count();
+                Complex::new(if parity % 2 == 0 { norm } else { -norm }, 0.0)
+            })
+            .collect();
+        Self::from_amplitudes(&amplitudes)
+    }
+
+    /// The amplitude encoding of real `data`: the state with amplitude
+    /// `data[i] / |data|` on `|i⟩`, padded with zeros up to a power of two.
+    /// [`Circuit::amplitude_encoding`] prepares it.
+    ///
+    /// [`Circuit::amplitude_encoding`]: crate::circuit::Circuit::amplitude_encoding
+    ///
+    /// ```
+    /// use complex_calculator::ket::State;
+    ///
+    /// let amplitudes = State::from_data(&[3.0, 0.0, 4.0]).amplitudes(2);
+    /// assert_eq!(amplitudes[2].re, 0.8);
+    /// ```
+    pub fn from_data(data: &[f64]) -> Self {
+        let norm = data.iter().map(|x| x * x).sum::<f64>().sqrt();
+        assert!(norm > 0.0, "data of norm 0 encodes no state");
+        let mut amplitudes: Vec<Complex<f64>> =
+            data.iter().map(|&x| Complex::new(x / norm, 0.0)).collect();
+        amplitudes.resize(
+            data.len().next_power_of_two().max(2),
+            Complex::new(0.0, 0.0),
+        );
+        Self::from_amplitudes(&amplitudes)
+    }
+
+    /// The state with amplitude `amplitudes[i]` on `|i⟩`, skipping zeros. The
+    /// inverse of [`State::amplitudes`].
+    pub fn from_amplitudes(amplitudes: &[Complex<f64>]) -> Self {
+        assert!(
+            amplitudes.len().is_power_of_two(),
+            "vector length {} is not a power of two",
+            amplitudes.len()
+        );
+        let n = amplitudes.len().trailing_zeros();
+
+        State {
+            scalar: Complex::new(1.0, 0.0),
+            superpositions: amplitudes
+                .iter()
+                .enumerate()
+                .filter(|(_, scalar)| !scalar.is_zero())
+                .map(|(i, &scalar)| Ket {
+                    scalar,
+                    ket: i as u64,
+                    n,
+                    d: 2,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A basis index written as `n` base-`d` digits, most significant first.
+/// Dimensions beyond 36 run out of digit characters, so their digits are
+/// written in decimal and comma-separated.
+struct Label(u64, u32, u32);
+
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Label(index, n, d) = *self;
+        if d == 2 {
+            return write!(f, "{:0>width$b}", index, width = n as usize);
+        }
+
+        for i in (0..n).rev() {
+            let digit = (index / (d as u64).pow(i) % d as u64) as u32;
+            // from_digit panics on radices past 36
+            let c = if d <= 36 {
+                char::from_digit(digit, d)
+            } else {
+                None
+            };
+            match c {
+                Some(c) => write!(f, "{c}")?,
+                None if i > 0 => write!(f, "{digit},")?,
+                None => write!(f, "{digit}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for Operator<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.scalar != T::one() {
+            write!(f, "{}(", self.scalar)?;
+        }
+
+        write!(f, "{}", self.ones[0])?;
+
+        for kb in self.ones.iter().skip(1) {
+            write!(f, " + {}", kb)?;
+        }
+
+        if self.scalar != T::one() {
+            write!(f, ")")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for State<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.scalar != T::one() {
+            write!(f, "{}(", self.scalar)?;
+        }
+
+        write!(f, "{}", self.superpositions[0])?;
+
+        for kb in self.superpositions.iter().skip(1) {
+            write!(f, " + {}", kb)?;
+        }
+
+        if self.scalar != T::one() {
+            write!(f, ")")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for KetBra<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.scalar != T::one() {
+            write!(f, "{}", self.scalar)?;
+        }
+        write!(
+            f,
+            "|{}⟩⟨{}|",
+            Label(self.ket, self.n, self.d),
+            Label(self.bra, self.n, self.d)
+        )
+    }
+}
+
+impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for Ket<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.scalar != T::one() {
+            write!(f, "{}", self.scalar)?;
+        }
+        write!(f, "|{}⟩", Label(self.ket, self.n, self.d))
+    }
+}
+
+impl<T: std::fmt::Display + One + PartialEq> std::fmt::Display for Bra<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.scalar != T::one() {
+            write!(f, "{}(", self.0.scalar)?;
+        }
+
+        for (i, b) in self.0.superpositions.iter().enumerate() {
+            if i > 0 {
+                write!(f, " + ")?;
+            }
+            if b.scalar != T::one() {
+                write!(f, "{}", b.scalar)?;
+            }
+            write!(f, "⟨{}|", Label(b.ket, b.n, b.d))?;
+        }
+
+        if self.0.scalar != T::one() {
+            write!(f, ")")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Latex + One + PartialEq> Ket<T> {
+    /// LaTeX markup using the `braket` package, e.g. `\frac{1}{\sqrt{2}}\ket{01}`.
+    pub fn to_latex(&self) -> String {
+        format!(
+            "{}\\ket{{{}}}",
+            latex_coefficient(&self.scalar),
+            Label(self.ket, self.n, self.d)
+        )
+    }
+}
+
+impl<T: Latex + One + PartialEq> KetBra<T> {
+    /// LaTeX markup using the `braket` package, e.g. `i\ket{01}\bra{10}`.
+    pub fn to_latex(&self) -> String {
+        format!(
+            "{}\\ket{{{}}}\\bra{{{}}}",
+            latex_coefficient(&self.scalar),
+            Label(self.ket, self.n, self.d),
+            Label(self.bra, self.n, self.d)
+        )
+    }
+}
+
+impl<T: Latex + One + PartialEq> State<T> {
+    /// LaTeX markup for the whole superposition, see [`Ket::to_latex`].
+    pub fn to_latex(&self) -> String {
+        latex_scaled(&self.scalar, self.superpositions.iter().map(Ket::to_latex))
+    }
+}
+
+impl<T: Latex + One + PartialEq> Operator<T> {
+    /// LaTeX markup for the sum of ketbras, see [`KetBra::to_latex`].
+    pub fn to_latex(&self) -> String {
+        latex_scaled(&self.scalar, self.ones.iter().map(KetBra::to_latex))
+    }
+}
+
+/// A scalar prefix for a ket or ketbra: empty for 1, `-` for −1, and
+/// parenthesised when it is itself a sum.
+fn latex_coefficient<T: Latex + One + PartialEq>(scalar: &T) -> String {
+    if *scalar == T::one() {
+        return String::new();
+    }
+    let s = scalar.to_latex();
+    if s == "-1" {
+        "-".to_string()
+    } else if s.contains(" + ") || s.contains(" - ") {
+        format!("({s})")
+    } else {
+        s
+    }
+}
+
+/// Joins terms into a sum, folding a leading minus into the operator, and
+/// wraps it in the outer scalar.
+fn latex_scaled<T: Latex + One + PartialEq>(
+    scalar: &T,
+    terms: impl Iterator<Item = String>,
+) -> String {
+    let mut sum = String::new();
+    for (i, term) in terms.enumerate() {
+        match term.strip_prefix('-') {
+            Some(rest) if i > 0 => sum += &format!(" - {rest}"),
+            _ if i > 0 => sum += &format!(" + {term}"),
+            _ => sum += &term,
+        }
+    }
+
+    if *scalar == T::one() {
+        sum
+    } else {
+        format!("{}\\left({sum}\\right)", latex_coefficient(scalar))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ket::{Ket, State};
+
+    use num::Zero;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::{Endianness, KetBra, Operator};
+    use crate::matrix::Vector;
+    use crate::Complex as C;
+
+    fn k(ket: u64, n: u32) -> Ket<i32> {
+        Ket {
+            scalar: 1,
+            ket,
+            n,
+            d: 2,
+        }
+    }
+
+    fn k_f64(ket: u64, n: u32) -> Ket<f64> {
+        Ket {
+            scalar: 1.0,
+            ket,
+            n,
+            d: 2,
+        }
+    }
+
+    fn kb(ket: u64, bra: u64, n: u32) -> KetBra<i32> {
+        KetBra {
+            scalar: 1,
+            ket,
+            bra,
+            n,
+            d: 2,
+        }
+    }
+
+    fn kb_f64(ket: u64, bra: u64, n: u32) -> KetBra<f64> {
         KetBra {
+            scalar: 1.0,
+            ket,
+            bra,
+            n,
+            d: 2,
+        }
+    }
+
+    #[test]
+    fn ket_bra_tensor() {
+        let a = kb(0, 0, 1);
+        let b = kb(0, 0, 1);
+        let c = a.tensor(&b);
+
+        assert_eq!(c, kb(0, 0, 2));
+
+        let a = kb(0, 0, 2);
+        let b = kb(0, 0, 1);
+        let c = a.tensor(&b);
+
+        assert_eq!(c, kb(0, 0, 3));
+
+        let a = kb(0, 1, 1);
+        let b = kb(0, 0, 1);
+        let c = a.tensor(&b);
+
+        assert_eq!(c, kb(0, 2, 2));
+
+        let a = kb(0, 0, 1);
+        let b = kb(0, 1, 1);
+        let c = a.tensor(&b);
+
+        assert_eq!(c, kb(0, 1, 2));
+
+        let a = kb(0, 1, 1);
+        let b = kb(0, 1, 1);
+
+        assert_eq!(a.tensor(&b), kb(0, 3, 2));
+
+        let a = kb(1, 0, 1);
+        let b = kb(0, 0, 1);
+
+        assert_eq!(a.tensor(&b), kb(2, 0, 2));
+
+        let a = kb(0, 0, 1);
+        let b = kb(1, 0, 1);
+
+        assert_eq!(a.tensor(&b), kb(1, 0, 2));
+
+        let a = kb(1, 0, 1);
+        let b = kb(1, 0, 1);
+
+        assert_eq!(a.tensor(&b), kb(3, 0, 2));
+
+        let a = kb(1, 0, 1);
+        let b = kb(0, 1, 1);
+
+        assert_eq!(a.tensor(&b), kb(2, 1, 2));
+    }
+
+    #[test]
+    fn tensor_beyond_32_qubits() {
+        let a = k((1 << 31) | 1, 32);
+        let b = k(0b101, 8);
+
+        assert_eq!(a.tensor(&b), k((1 << 39) | (1 << 8) | 0b101, 40));
+        assert_eq!(kb(1, 0, 40).tensor(&kb(0, 1, 24)), kb(1 << 24, 1, 64));
+        assert_eq!(k(0, 0).tensor(&k(u64::MAX, 64)), k(u64::MAX, 64));
+    }
+
+    #[test]
+    fn tensor_overflow_is_checked() {
+        assert_eq!(k(0, 40).checked_tensor(&k(0, 25)), None);
+
+        let a = Operator {
+            scalar: 1,
+            ones: vec![kb(0, 0, 40)],
+        };
+        assert_eq!(a.checked_tensor(&a), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "tensor product of 40 and 25 qubits exceeds the 64-qubit limit")]
+    fn tensor_overflow_panics() {
+        kb(0, 0, 40).tensor(&kb(0, 0, 25));
+    }
+
+    #[test]
+    fn qutrit_tensor_and_display() {
+        let a = State::<i32>::qudit_basis(2, 1, 3);
+        let b = State::<i32>::qudit_basis(1, 1, 3);
+        let ab = a.tensor(&b);
+
+        assert_eq!(ab, State::qudit_basis(7, 2, 3));
+        assert_eq!(format!("{}", ab), "|21⟩");
+        assert_eq!(format!("{}", State::<i32>::qudit_basis(5, 3, 3)), "|012⟩");
+    }
+
+    #[test]
+    fn wide_qudit_digits_are_comma_separated() {
+        assert_eq!(format!("{}", State::<i32>::qudit_basis(35, 1, 36)), "|z⟩");
+        let s = State::<i32>::qudit_basis(39 * 40 + 7, 2, 40);
+        assert_eq!(format!("{s}"), "|39,7⟩");
+        assert_eq!(s.to_latex(), "\\ket{39,7}");
+    }
+
+    #[test]
+    fn qutrit_embed() {
+        // cyclic shift |x⟩ -> |x + 1 mod 3⟩ on a single qutrit
+        let shift = Operator {
+            scalar: 1,
+            ones: (0..3)
+                .map(|x| KetBra {
+                    scalar: 1,
+                    ket: (x + 1) % 3,
+                    bra: x,
+                    n: 1,
+                    d: 3,
+                })
+                .collect(),
+        };
+        let shift1 = shift.embed(&[1], 2);
+
+        assert_eq!(shift1.ones.len(), 9);
+        // |02⟩ -> |12⟩: the high digit is site 1
+        let res = shift1 * State::qudit_basis(2, 2, 3);
+        assert_eq!(res, State::qudit_basis(5, 2, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "41 qudits of dimension 3 overflow the basis index")]
+    fn qutrit_embed_overflow_panics() {
+        // 3^41 is past 2^64
+        Operator::<i32>::qudit_identity(1, 3).embed(&[40], 41);
+    }
+
+    #[test]
+    #[should_panic(expected = "tensor product of qudits of different dimensions")]
+    fn mixed_dimension_tensor_panics() {
+        State::<i32>::qudit_basis(0, 1, 3).tensor(&State::basis(0, 1));
+    }
+
+    #[test]
+    fn operator_tensor_product() {
+        let b = Operator {
+            scalar: 1,
+            ones: vec![kb(1, 1, 1)],
+        };
+        let id = Operator::<i32>::identity(1);
+
+        let b0 = id.tensor(&b);
+
+        println!("{}", b0);
+    }
+
+    #[test]
+    fn operator_product() {
+        let b = Operator {
+            scalar: 1,
+            ones: vec![kb(1, 1, 1)],
+        };
+        let id = Operator::<i32>::identity(1);
+
+        let b0 = id.tensor(&b);
+        let b1 = b.tensor(&id);
+
+        let b1b0 = b1 * b0;
+
+        println!("{}", b1b0);
+
+        assert_eq!(
+            b1b0,
+            Operator {
+                scalar: 1,
+                ones: vec![kb(3, 3, 2)],
+            }
+        );
+    }
+
+    fn sorted<T>(mut op: Operator<T>) -> Operator<T> {
+        op.ones.sort_by_key(|kb| (kb.ket, kb.bra));
+        op
+    }
+
+    #[test]
+    fn operator_embed_matches_tensor() {
+        let b = Operator {
+            scalar: 1,
+            ones: vec![kb(1, 1, 1)],
+        };
+        let id = Operator::<i32>::identity(1);
+
+        assert_eq!(sorted(b.embed(&[0], 2)), sorted(id.tensor(&b)));
+        assert_eq!(sorted(b.embed(&[1], 2)), sorted(b.tensor(&id)));
+    }
+
+    #[test]
+    fn operator_embed_non_adjacent() {
+        // control on qubit 1, target on qubit 0
+        let c10 = Operator {
+            scalar: 1,
+            ones: vec![kb(0, 0, 2), kb(1, 1, 2), kb(3, 2, 2), kb(2, 3, 2)],
+        };
+        // control on qubit 2, target on qubit 0, qubit 1 untouched
+        let c20 = c10.embed(&[0, 2], 3);
+
+        assert_eq!(c20.ones.len(), 8);
+
+        let cases = [
+            (0b100, 0b101),
+            (0b110, 0b111),
+            (0b111, 0b110),
+            (0b010, 0b010),
+        ];
+        for (inp, out) in cases {
+            let inp = State {
+                scalar: 1,
+                superpositions: vec![k(inp, 3)],
+            };
+            let res = c20.clone() * inp;
+            assert_eq!(res.superpositions, vec![k(out, 3)]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate target qubit 1")]
+    fn operator_embed_duplicate_targets() {
+        let b = Operator {
+            scalar: 1,
+            ones: vec![kb(1, 1, 2)],
+        };
+        b.embed(&[1, 1], 3);
+    }
+
+    #[test]
+    fn state_vector_round_trip() {
+        let s = State {
+            scalar: 1.0 / 2.0_f64.sqrt(),
+            superpositions: vec![k_f64(0, 2), -k_f64(3, 2)],
+        };
+        let r = 1.0 / 2.0_f64.sqrt();
+        let v = s.to_vector::<4>();
+
+        assert_eq!(
+            v,
+            Vector([C::new(r, 0.0), C::zero(), C::zero(), C::new(-r, 0.0)])
+        );
+
+        let back = State::from_vector(&v);
+        assert_eq!(back.to_vector::<4>(), v);
+        assert_eq!(back.superpositions.len(), 2);
+    }
+
+    #[test]
+    fn dense_amplitudes() {
+        let s = State {
+            scalar: 2,
+            superpositions: vec![k(0b101, 3), k(0b001, 3), k(0b101, 3)],
+        };
+        assert_eq!(s.amplitudes(3), vec![0, 2, 0, 0, 0, 4, 0, 0]);
+
+        let w = State::<f64>::w(2).amplitudes(2);
+        let r = 1.0 / 2.0_f64.sqrt();
+        assert_eq!(w, vec![0.0, r, r, 0.0]);
+    }
+
+    #[test]
+    fn state_vector_matches_dense_product() {
+        let x = Operator {
+            scalar: 1.0,
+            ones: vec![kb_f64(0, 1, 1), kb_f64(1, 0, 1)],
+        };
+        let s = State {
+            scalar: 1.0,
+            superpositions: vec![k_f64(0, 1)],
+        };
+
+        let res = (x * s).to_vector::<2>();
+        assert_eq!(res, Vector([C::zero(), C::new(1.0, 0.0)]));
+    }
+
+    #[test]
+    fn permute_state_qubits() {
+        let s = State {
             scalar: 1,
-            ket,
-            bra,
-            n,
+            superpositions: vec![k(0b001, 3), k(0b110, 3)],
+        };
+        let p = s.permute_qubits(&[2, 0, 1]);
+
+        assert_eq!(p.superpositions, vec![k(0b100, 3), k(0b011, 3)]);
+    }
+
+    #[test]
+    fn permute_operator_qubits() {
+        // control on qubit 1, target on qubit 0
+        let c10 = Operator {
+            scalar: 1,
+            ones: vec![kb(0, 0, 2), kb(1, 1, 2), kb(3, 2, 2), kb(2, 3, 2)],
+        };
+        let c01 = c10.permute_qubits(&[1, 0]);
+
+        assert_eq!(sorted(c01), sorted(c10.embed(&[1, 0], 2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "1 appears twice in the permutation")]
+    fn permute_rejects_non_permutation() {
+        State::<i32>::basis(0, 2).permute_qubits(&[1, 1]);
+    }
+
+    #[test]
+    fn projector_onto_plus() {
+        let plus = State::parse("1/sqrt(2)(|0⟩ + |1⟩)").unwrap();
+        let p = Operator::projector(&plus);
+
+        assert!(p.is_hermitian(1e-12));
+        assert!((p.trace().re - 1.0).abs() < 1e-12);
+        let res = p * State::parse("|0⟩").unwrap();
+        assert!((res.to_vector::<2>().0[1].re - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn measure_in_plus_minus_basis() {
+        let basis = [
+            State::parse("1/sqrt(2)(|0⟩ + |1⟩)").unwrap(),
+            State::parse("1/sqrt(2)(|0⟩ - |1⟩)").unwrap(),
+        ];
+        let zero = State::parse("|0⟩").unwrap();
+
+        for p in zero.measurement_probabilities(&basis) {
+            assert!((p - 0.5).abs() < 1e-12);
+        }
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = [0; 2];
+        for _ in 0..1000 {
+            let (outcome, collapsed) = zero.measure_in_basis(&basis, &mut rng);
+            assert_eq!(collapsed, basis[outcome]);
+            counts[outcome] += 1;
         }
+        assert!(counts[0] > 400 && counts[1] > 400);
     }
 
-    fn kb_f64(ket: u32, bra: u32, n: u32) -> KetBra<f64> {
-        KetBra {
-            scalar: 1.0,
-            ket,
-            bra,
-            n,
+    #[test]
+    fn measure_in_bell_basis_is_deterministic() {
+        let basis = [
+            State::parse("1/sqrt(2)(|00⟩ + |11⟩)").unwrap(),
+            State::parse("1/sqrt(2)(|00⟩ - |11⟩)").unwrap(),
+            State::parse("1/sqrt(2)(|01⟩ + |10⟩)").unwrap(),
+            State::parse("1/sqrt(2)(|01⟩ - |10⟩)").unwrap(),
+        ];
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for (i, b) in basis.iter().enumerate() {
+            assert_eq!(b.measure_in_basis(&basis, &mut rng).0, i);
         }
     }
 
     #[test]
-    fn ket_bra_tensor() {
-        let a = kb(0, 0, 1);
-        let b = kb(0, 0, 1);
-        let c = a.tensor(&b);
+    fn bell_states_match_parser() {
+        let expected = [
+            "1/sqrt(2)(|00⟩ + |11⟩)",
+            "1/sqrt(2)(|00⟩ - |11⟩)",
+            "1/sqrt(2)(|01⟩ + |10⟩)",
+            "1/sqrt(2)(|01⟩ - |10⟩)",
+        ];
 
-        assert_eq!(c, kb(0, 0, 2));
+        for (i, e) in expected.into_iter().enumerate() {
+            let bell = State::<C<f64>>::bell(i);
+            assert_eq!(
+                bell.to_vector::<4>(),
+                State::parse(e).unwrap().to_vector::<4>()
+            );
+        }
+    }
 
-        let a = kb(0, 0, 2);
-        let b = kb(0, 0, 1);
-        let c = a.tensor(&b);
+    #[test]
+    fn ghz_and_w_states() {
+        let ghz = State::<f64>::ghz(3);
+        assert_eq!(ghz.superpositions, vec![k_f64(0b000, 3), k_f64(0b111, 3)]);
+        assert_eq!(State::<f64>::ghz(64).superpositions[1].ket, u64::MAX);
 
-        assert_eq!(c, kb(0, 0, 3));
+        let w = State::<f64>::w(3);
+        assert_eq!(w.scalar, 1.0 / 3.0_f64.sqrt());
+        assert_eq!(
+            w.superpositions,
+            vec![k_f64(0b001, 3), k_f64(0b010, 3), k_f64(0b100, 3)]
+        );
+    }
 
-        let a = kb(0, 1, 1);
-        let b = kb(0, 0, 1);
-        let c = a.tensor(&b);
+    #[test]
+    fn bloch_vectors() {
+        let cases = [
+            ("|0⟩", (0.0, 0.0, 1.0)),
+            ("|1⟩", (0.0, 0.0, -1.0)),
+            ("1/sqrt(2)(|0⟩ + |1⟩)", (1.0, 0.0, 0.0)),
+            ("1/sqrt(2)(|0⟩ - |1⟩)", (-1.0, 0.0, 0.0)),
+            ("1/sqrt(2)(|0⟩ + i|1⟩)", (0.0, 1.0, 0.0)),
+            ("|0⟩ + |1⟩", (1.0, 0.0, 0.0)),
+        ];
 
-        assert_eq!(c, kb(0, 2, 2));
+        for (s, (x, y, z)) in cases {
+            let (bx, by, bz) = State::parse(s).unwrap().bloch_vector();
+            assert!((bx - x).abs() < 1e-12 && (by - y).abs() < 1e-12 && (bz - z).abs() < 1e-12);
+        }
+    }
 
-        let a = kb(0, 0, 1);
-        let b = kb(0, 1, 1);
-        let c = a.tensor(&b);
+    /// The columns of an operator on one qubit, as dense vectors.
+    fn columns(op: &Operator<C<f64>>) -> [Vector<C<f64>, 2>; 2] {
+        [0, 1].map(|x| (op.clone() * State::basis(x, 1)).to_vector::<2>())
+    }
 
-        assert_eq!(c, kb(0, 1, 2));
+    fn assert_close(a: &Operator<C<f64>>, b: &Operator<C<f64>>) {
+        for (ca, cb) in columns(a).iter().zip(columns(b).iter()) {
+            for (x, y) in ca.0.iter().zip(cb.0.iter()) {
+                assert!((*x - *y).modulus() < 1e-9, "{} != {}", a, b);
+            }
+        }
+    }
 
-        let a = kb(0, 1, 1);
-        let b = kb(0, 1, 1);
+    #[test]
+    fn identity_depends_on_n() {
+        let id = Operator::<i32>::identity(3);
+        assert_eq!(id.ones.len(), 8);
+        assert_eq!(id.trace(), 8);
+        assert_eq!(Operator::<i32>::qudit_identity(2, 3).trace(), 9);
+    }
 
-        assert_eq!(a.tensor(&b), kb(0, 3, 2));
+    #[test]
+    fn operator_powers() {
+        let x = Operator {
+            scalar: 1,
+            ones: vec![kb(0, 1, 1), kb(1, 0, 1)],
+        };
+        assert_eq!(sorted(x.pow(2)), sorted(Operator::identity(1)));
+        assert_eq!(sorted(x.pow(3)), sorted(x.clone()));
+        assert_eq!(sorted(x.pow(0)), sorted(Operator::identity(1)));
 
-        let a = kb(1, 0, 1);
-        let b = kb(0, 0, 1);
+        let h = Operator::parse("1/sqrt(2)(|0⟩⟨0| + |0⟩⟨1| + |1⟩⟨0| - |1⟩⟨1|)").unwrap();
+        assert_close(&h.pow(3), &h);
+    }
 
-        assert_eq!(a.tensor(&b), kb(2, 0, 2));
+    #[test]
+    fn powers_of_the_zero_operator() {
+        // X − X simplifies to no terms at all
+        let x = Operator {
+            scalar: 1,
+            ones: vec![kb(0, 1, 1), kb(1, 0, 1)],
+        };
+        let zero = (x.clone() + -x).simplify();
+        assert!(zero.ones.is_empty());
+        assert!(zero.pow(1).ones.is_empty());
+        assert!(zero.pow(5).ones.is_empty());
+    }
 
-        let a = kb(0, 0, 1);
-        let b = kb(1, 0, 1);
+    #[test]
+    #[should_panic(expected = "zeroth power of an operator with no terms")]
+    fn zeroth_power_of_the_zero_operator_panics() {
+        Operator::<i32> {
+            scalar: 1,
+            ones: vec![],
+        }
+        .pow(0);
+    }
 
-        assert_eq!(a.tensor(&b), kb(1, 0, 2));
+    #[test]
+    fn commutators_of_paulis() {
+        let x = Operator::parse("|0⟩⟨1| + |1⟩⟨0|").unwrap();
+        let y = Operator::parse("-i|0⟩⟨1| + i|1⟩⟨0|").unwrap();
+        let z = Operator::parse("|0⟩⟨0| - |1⟩⟨1|").unwrap();
 
-        let a = kb(1, 0, 1);
-        let b = kb(1, 0, 1);
+        // [X, Y] = 2iZ
+        let expected = z.clone() * C::new(0.0, 2.0);
+        assert_close(&x.commutator(&y), &expected);
 
-        assert_eq!(a.tensor(&b), kb(3, 0, 2));
+        assert!(!x.commutes_with(&z, 1e-12));
+        assert!(z.commutes_with(&z, 1e-12));
+        assert!(x.commutes_with(&Operator::identity(1), 1e-12));
 
-        let a = kb(1, 0, 1);
-        let b = kb(0, 1, 1);
+        // Z⊗Z and X⊗X commute although neither factor pair does
+        assert!(z.tensor(&z).commutes_with(&x.tensor(&x), 1e-12));
+    }
 
-        assert_eq!(a.tensor(&b), kb(2, 1, 2));
+    #[test]
+    fn exponential_of_pauli_z() {
+        let z = Operator::parse("|0⟩⟨0| - |1⟩⟨1|").unwrap();
+        let u = z.expm(std::f64::consts::FRAC_PI_2);
+
+        assert_close(&u, &Operator::parse("-i|0⟩⟨0| + i|1⟩⟨1|").unwrap());
+        assert!(u.is_unitary(1e-9));
     }
 
     #[test]
-    fn operator_tensor_product() {
-        let b = Operator {
-            scalar: 1,
-            ones: vec![kb(1, 1, 1)],
+    fn exponential_of_pauli_x() {
+        let x = Operator::parse("|0⟩⟨1| + |1⟩⟨0|").unwrap();
+        let t = 2.7_f64;
+        let (c, sn) = (C::new(t.cos(), 0.0), C::new(0.0, -t.sin()));
+        let term = |ket, bra, scalar| KetBra {
+            scalar,
+            ket,
+            bra,
+            n: 1,
+            d: 2,
+        };
+        let expected = Operator {
+            scalar: C::new(1.0, 0.0),
+            ones: vec![term(0, 0, c), term(1, 1, c), term(0, 1, sn), term(1, 0, sn)],
         };
-        let id = Operator::<i32>::identity(1);
 
-        let b0 = id.tensor(&b);
+        assert_close(&x.expm(t), &expected);
+    }
 
-        println!("{}", b0);
+    #[test]
+    fn operator_sum_uses_both_scalars() {
+        let a = Operator {
+            scalar: 2,
+            ones: vec![kb(0, 0, 1)],
+        };
+        let b = Operator {
+            scalar: 3,
+            ones: vec![kb(1, 1, 1), kb(0, 0, 1)],
+        };
+
+        let sum = sorted(a.clone() + b.clone());
+        assert_eq!(sum.scalar, 1);
+        assert_eq!(
+            sum.ones,
+            vec![
+                KetBra {
+                    scalar: 5,
+                    ..kb(0, 0, 1)
+                },
+                KetBra {
+                    scalar: 3,
+                    ..kb(1, 1, 1)
+                }
+            ]
+        );
+        assert_eq!(sorted(b + a), sum);
     }
 
     #[test]
-    fn operator_product() {
+    fn operator_difference_cancels() {
+        let a = Operator {
+            scalar: 2,
+            ones: vec![kb(0, 1, 1)],
+        };
         let b = Operator {
             scalar: 1,
-            ones: vec![kb(1, 1, 1)],
+            ones: vec![KetBra {
+                scalar: 2,
+                ..kb(0, 1, 1)
+            }],
         };
-        let id = Operator::<i32>::identity(1);
 
-        let b0 = id.tensor(&b);
-        let b1 = b.tensor(&id);
+        assert!((a + -b).ones.is_empty());
+    }
 
-        let b1b0 = b1 * b0;
+    #[test]
+    fn simplify_is_canonical() {
+        let op = Operator {
+            scalar: -2,
+            ones: vec![
+                kb(1, 0, 1),
+                kb(0, 1, 1),
+                kb(1, 0, 1),
+                KetBra {
+                    scalar: -1,
+                    ..kb(1, 1, 1)
+                },
+                kb(1, 1, 1),
+            ],
+        };
 
-        println!("{}", b1b0);
+        let simple = op.simplify();
+        assert_eq!(simple.scalar, 1);
+        assert_eq!(
+            simple.ones,
+            vec![
+                KetBra {
+                    scalar: -2,
+                    ..kb(0, 1, 1)
+                },
+                KetBra {
+                    scalar: -4,
+                    ..kb(1, 0, 1)
+                }
+            ]
+        );
+        assert_eq!(simple.simplify(), simple);
+    }
 
+    #[test]
+    fn term_accessors() {
+        let s = State {
+            scalar: 2,
+            superpositions: vec![
+                k(0b01, 2),
+                Ket {
+                    scalar: -1,
+                    ..k(0b10, 2)
+                },
+            ],
+        };
         assert_eq!(
-            b1b0,
-            Operator {
+            s.terms().collect::<Vec<_>>(),
+            vec![(2, 0b01, 2), (-2, 0b10, 2)]
+        );
+        assert_eq!(s.num_qubits(), 2);
+
+        let op = Operator {
+            scalar: 3,
+            ones: vec![kb(0, 1, 1)],
+        };
+        assert_eq!(op.terms().collect::<Vec<_>>(), vec![(3, 0, 1, 1)]);
+        assert_eq!(op.num_qubits(), 1);
+        assert_eq!(
+            Operator::<i32> {
                 scalar: 1,
-                ones: vec![kb(3, 3, 2)],
+                ones: vec![]
             }
+            .num_qubits(),
+            0
+        );
+    }
+
+    #[test]
+    fn operator_from_permutation() {
+        let cnot = Operator::<i32>::from_permutation(2, |x| x ^ ((x & 1) << 1));
+        assert_eq!(
+            cnot.ones,
+            vec![kb(0, 0, 2), kb(3, 1, 2), kb(2, 2, 2), kb(1, 3, 2)]
+        );
+
+        // Toffoli flipping qubit 2 when qubits 0 and 1 are set
+        let toffoli =
+            Operator::<i32>::from_permutation(
+                3,
+                |x| {
+                    if x & 0b011 == 0b011 {
+                        x ^ 0b100
+                    } else {
+                        x
+                    }
+                },
+            );
+        let res = toffoli * State::basis(0b011, 3);
+        assert_eq!(res.superpositions, vec![k(0b111, 3)]);
+
+        assert!(Operator::<i32>::checked_from_permutation(2, |x| (x + 1) % 4).is_some());
+        assert!(Operator::<i32>::checked_from_permutation(2, |x| x & 1).is_none());
+        assert!(Operator::<i32>::checked_from_permutation(2, |x| x + 1).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "f(3) = 4 is outside the 2-qubit basis")]
+    fn permutation_outside_basis_panics() {
+        Operator::<i32>::from_permutation(2, |x| x + 1);
+    }
+
+    #[test]
+    fn phase_oracle_marks_states() {
+        let oracle = Operator::<i32>::phase_oracle(2, |x| x == 0b10);
+        assert_eq!(oracle.trace(), 2);
+
+        let plus = State {
+            scalar: 1,
+            superpositions: (0..4).map(|x| k(x, 2)).collect(),
+        };
+        let mut res = oracle * plus;
+        res.superpositions.sort_by_key(|k| k.ket);
+        assert_eq!(
+            res.superpositions,
+            vec![
+                k(0, 2),
+                k(1, 2),
+                Ket {
+                    scalar: -1,
+                    ..k(2, 2)
+                },
+                k(3, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_of_largest_terms() {
+        let s = State::parse("|00⟩ + 2i|01⟩ - 2|10⟩ + |11⟩ - |00⟩").unwrap();
+
+        assert_eq!(
+            s.summary(2),
+            "|01⟩  0.0000 + 2.0000i  p = 0.4444\n\
+             |10⟩  -2.0000 + 0.0000i  p = 0.4444\n\
+             … and 1 more term\n"
+        );
+        assert_eq!(s.summary(5).lines().count(), 3);
+    }
+
+    #[test]
+    fn latex_output() {
+        let bell = State::<C<f64>>::bell(1);
+        assert_eq!(
+            bell.to_latex(),
+            "\\frac{1}{\\sqrt{2}}\\left(\\ket{00} - \\ket{11}\\right)"
+        );
+
+        let y = Operator::parse("-i|0⟩⟨1| + i|1⟩⟨0|").unwrap();
+        assert_eq!(
+            sorted(y).to_latex(),
+            "-i\\ket{0}\\bra{1} + i\\ket{1}\\bra{0}"
+        );
+
+        let s = State {
+            scalar: 2,
+            superpositions: vec![
+                k(0b10, 2),
+                Ket {
+                    scalar: -1,
+                    ..k(1, 2)
+                },
+            ],
+        };
+        assert_eq!(s.to_latex(), "2\\left(\\ket{10} - \\ket{01}\\right)");
+
+        let kb = KetBra {
+            scalar: C::new(1.0, 1.0),
+            ket: 0b01,
+            bra: 0b10,
+            n: 2,
+            d: 2,
+        };
+        assert_eq!(kb.to_latex(), "(1 + i)\\ket{01}\\bra{10}");
+    }
+
+    #[test]
+    fn big_endian_conversions() {
+        // qubit 0 is set, qubit 1 is not
+        let s = State {
+            scalar: 1,
+            superpositions: vec![k(0b01, 2)],
+        };
+
+        assert_eq!(s.amplitudes_with(2, Endianness::Little), vec![0, 1, 0, 0]);
+        assert_eq!(s.amplitudes_with(2, Endianness::Big), vec![0, 0, 1, 0]);
+        assert_eq!(s.to_string_with(Endianness::Little), "|01⟩");
+        assert_eq!(s.to_string_with(Endianness::Big), "|10⟩");
+        assert_eq!(s.reversed_qubits().reversed_qubits(), s);
+    }
+
+    #[test]
+    fn big_endian_embed() {
+        // textbook CNOT, control on the leftmost qubit
+        let cnot = Operator {
+            scalar: 1,
+            ones: vec![kb(0, 0, 2), kb(1, 1, 2), kb(3, 2, 2), kb(2, 3, 2)],
+        };
+
+        assert_eq!(
+            sorted(cnot.embed_with(&[0, 1], 2, Endianness::Big)),
+            sorted(cnot.clone())
+        );
+        assert_eq!(
+            sorted(cnot.embed_with(&[1, 0], 2, Endianness::Big)),
+            sorted(cnot.embed(&[1, 0], 2))
         );
+
+        // control on qubit 0 and target on qubit 2 of |q0 q1 q2⟩
+        let c02 = cnot.embed_with(&[0, 2], 3, Endianness::Big);
+        let res = c02 * State::basis(0b100, 3);
+        assert_eq!(res.to_string_with(Endianness::Big), "|101⟩");
+    }
+
+    #[test]
+    fn operator_trace() {
+        let op = Operator {
+            scalar: 3,
+            ones: vec![kb(0, 0, 1), kb(1, 1, 1), kb(0, 1, 1), kb(1, 1, 1)],
+        };
+        assert_eq!(op.trace(), 9);
+
+        let id = Operator::<i32>::identity(1);
+        assert_eq!(id.tensor(&id).trace(), 4);
+
+        let h = Operator::parse("1/sqrt(2)(|0⟩⟨0| + |0⟩⟨1| + |1⟩⟨0| - |1⟩⟨1|)").unwrap();
+        assert!(h.trace().modulus() < 1e-12);
+    }
+
+    #[test]
+    fn unitary_and_hermitian_checks() {
+        const EPS: f64 = 1e-10;
+
+        let h = Operator::parse("1/sqrt(2)(|0⟩⟨0| + |0⟩⟨1| + |1⟩⟨0| - |1⟩⟨1|)").unwrap();
+        assert!(h.is_unitary(EPS));
+        assert!(h.is_hermitian(EPS));
+
+        let s = Operator::parse("|0⟩⟨0| + i|1⟩⟨1|").unwrap();
+        assert!(s.is_unitary(EPS));
+        assert!(!s.is_hermitian(EPS));
+
+        let y = Operator::parse("-i|0⟩⟨1| + i|1⟩⟨0|").unwrap();
+        assert!(y.is_hermitian(EPS));
+
+        let p0 = Operator::parse("|0⟩⟨0|").unwrap();
+        assert!(p0.is_hermitian(EPS));
+        assert!(!p0.is_unitary(EPS));
+
+        let cnot = Operator::parse("|00⟩⟨00| + |01⟩⟨01| + |11⟩⟨10| + |10⟩⟨11|").unwrap();
+        assert!(cnot.embed(&[2, 0], 3).is_unitary(EPS));
+
+        let not_unitary = Operator::parse("|0⟩⟨0| + |0⟩⟨1|").unwrap();
+        assert!(!not_unitary.is_unitary(EPS));
+        assert!(!(h * C::new(2.0, 0.0)).is_unitary(EPS));
+
+        // answered from the stored terms, without visiting all 2^60 columns
+        let wide = Operator::parse(&format!("|{0}⟩⟨{0}|", "0".repeat(60))).unwrap();
+        assert!(!wide.is_unitary(EPS));
     }
 
     #[test]
@@ -430,10 +2455,8 @@ mod tests {
                 -kb_f64(1, 1, 1),
             ],
         };
-        let id = Operator::<f64>::identity(1);
-
-        let h0 = id.tensor(&h);
-        let h1 = h.tensor(&id);
+        let h0 = h.embed(&[0], 2);
+        let h1 = h.embed(&[1], 2);
 
         println!("h0 = {}", h0);
         println!("h1 = {}", h1);
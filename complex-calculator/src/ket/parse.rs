@@ -0,0 +1,633 @@
+//! Parser for Dirac-notation expressions such as `(1/sqrt(2))(|00⟩ + |11⟩)`
+//! or `|0><1| + |1><0|`.
+//!
+//! Juxtaposition is multiplication, so `|0⟩⟨1|` is an outer product, `⟨0|ψ`
+//! an inner product and `|0⟩|1⟩` a tensor product.
+
+use std::collections::HashMap;
+
+use num::{One, Zero};
+
+use super::{Ket, KetBra, Operator, State, MAX_QUBITS};
+use crate::Complex;
+
+type C = Complex<f64>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub pos: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.pos)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl std::str::FromStr for State<C> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Parser::new(s).parse()? {
+            Value::Ket(state) => Ok(state),
+            v => Err(ParseError {
+                pos: 0,
+                message: format!("expected a ket, found {}", v.kind()),
+            }),
+        }
+    }
+}
+
+impl std::str::FromStr for Operator<C> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Parser::new(s).parse()? {
+            Value::Op(op) => Ok(op),
+            v => Err(ParseError {
+                pos: 0,
+                message: format!("expected an operator, found {}", v.kind()),
+            }),
+        }
+    }
+}
+
+impl State<C> {
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        s.parse()
+    }
+}
+
+impl Operator<C> {
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        s.parse()
+    }
+}
+
+/// Parses a scalar expression such as `(-1 + i)/√6`, for the weights and
+/// labels of other formats.
+pub(crate) fn parse_scalar(s: &str) -> Result<C, ParseError> {
+    match Parser::new(s).parse()? {
+        Value::Scalar(c) => Ok(c),
+        v => Err(ParseError {
+            pos: 0,
+            message: format!("expected a scalar, found {}", v.kind()),
+        }),
+    }
+}
+
+enum Value {
+    Scalar(C),
+    Ket(State<C>),
+    Bra(State<C>),
+    Op(Operator<C>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Scalar(_) => "a scalar",
+            Value::Ket(_) => "a ket",
+            Value::Bra(_) => "a bra",
+            Value::Op(_) => "an operator",
+        }
+    }
+
+    /// Number of qubits the value acts on, if it has any terms.
+    fn width(&self) -> Option<u32> {
+        match self {
+            Value::Scalar(_) => None,
+            Value::Ket(s) | Value::Bra(s) => s.superpositions.first().map(|k| k.n),
+            Value::Op(op) => op.ones.first().map(|kb| kb.n),
+        }
+    }
+
+    fn scale(self, c: C) -> Value {
+        match self {
+            Value::Scalar(s) => Value::Scalar(s * c),
+            Value::Ket(s) => Value::Ket(scale_state(s, c)),
+            Value::Bra(s) => Value::Bra(scale_state(s, c)),
+            Value::Op(op) => Value::Op(Operator {
+                scalar: C::one(),
+                ones: op
+                    .ones
+                    .into_iter()
+                    .map(|kb| KetBra {
+                        scalar: kb.scalar * c,
+                        ..kb
+                    })
+                    .collect(),
+            }),
+        }
+    }
+}
+
+fn scale_state(s: State<C>, c: C) -> State<C> {
+    State {
+        scalar: C::one(),
+        superpositions: s
+            .superpositions
+            .into_iter()
+            .map(|k| Ket {
+                scalar: k.scalar * c,
+                ..k
+            })
+            .collect(),
+    }
+}
+
+/// Merges terms on the same basis ket, keeping the order of first appearance.
+fn merge_kets(kets: Vec<Ket<C>>) -> Vec<Ket<C>> {
+    let mut index: HashMap<u64, usize> = HashMap::new();
+    let mut merged: Vec<Ket<C>> = Vec::new();
+
+    for k in kets {
+        match index.get(&k.ket) {
+            Some(&i) => merged[i].scalar += k.scalar,
+            None => {
+                index.insert(k.ket, merged.len());
+                merged.push(k);
+            }
+        }
+    }
+
+    merged.retain(|k| !k.scalar.is_zero());
+    merged
+}
+
+/// Merges terms on the same ketbra, keeping the order of first appearance.
+fn merge_ket_bras(ones: Vec<KetBra<C>>) -> Vec<KetBra<C>> {
+    let mut index: HashMap<(u64, u64), usize> = HashMap::new();
+    let mut merged: Vec<KetBra<C>> = Vec::new();
+
+    for kb in ones {
+        match index.get(&(kb.ket, kb.bra)) {
+            Some(&i) => merged[i].scalar += kb.scalar,
+            None => {
+                index.insert((kb.ket, kb.bra), merged.len());
+                merged.push(kb);
+            }
+        }
+    }
+
+    merged.retain(|kb| !kb.scalar.is_zero());
+    merged
+}
+
+fn add(a: Value, b: Value) -> Result<Value, String> {
+    if let (Some(m), Some(n)) = (a.width(), b.width()) {
+        if m != n {
+            return Err(format!("cannot add terms on {m} and {n} qubits"));
+        }
+    }
+
+    Ok(match (a, b) {
+        (Value::Scalar(s), Value::Scalar(t)) => Value::Scalar(s + t),
+        (Value::Ket(s), Value::Ket(t)) => Value::Ket(State {
+            scalar: C::one(),
+            superpositions: merge_kets([s.superpositions, t.superpositions].concat()),
+        }),
+        (Value::Bra(s), Value::Bra(t)) => Value::Bra(State {
+            scalar: C::one(),
+            superpositions: merge_kets([s.superpositions, t.superpositions].concat()),
+        }),
+        (Value::Op(s), Value::Op(t)) => Value::Op(Operator {
+            scalar: C::one(),
+            ones: merge_ket_bras([s.ones, t.ones].concat()),
+        }),
+        (a, b) => return Err(format!("cannot add {} and {}", a.kind(), b.kind())),
+    })
+}
+
+fn tensor(s: &State<C>, t: &State<C>) -> Result<State<C>, String> {
+    s.checked_tensor(t)
+        .ok_or_else(|| format!("tensor product exceeds the {MAX_QUBITS}-qubit limit"))
+}
+
+fn mul(a: Value, b: Value) -> Result<Value, String> {
+    let (wa, wb) = (a.width(), b.width());
+
+    Ok(match (a, b) {
+        (Value::Scalar(s), v) | (v, Value::Scalar(s)) => v.scale(s),
+        (Value::Ket(s), Value::Ket(t)) => Value::Ket(tensor(&s, &t)?),
+        (Value::Bra(s), Value::Bra(t)) => Value::Bra(tensor(&s, &t)?),
+        (Value::Ket(s), Value::Bra(t)) => {
+            let mut ones = Vec::new();
+            for k in &s.superpositions {
+                for b in &t.superpositions {
+                    if k.n != b.n {
+                        return Err(format!(
+                            "cannot form an operator from a {}-qubit ket and a {}-qubit bra",
+                            k.n, b.n
+                        ));
+                    }
+                    ones.push(KetBra {
+                        scalar: k.scalar * b.scalar,
+                        ket: k.ket,
+                        bra: b.ket,
+                        n: k.n,
+                        d: k.d,
+                    });
+                }
+            }
+            Value::Op(Operator {
+                scalar: C::one(),
+                ones: merge_ket_bras(ones),
+            })
+        }
+        (a, b) => {
+            if let (Some(m), Some(n)) = (wa, wb) {
+                if m != n {
+                    return Err(format!(
+                        "cannot multiply {} on {m} qubits by {} on {n} qubits",
+                        a.kind(),
+                        b.kind()
+                    ));
+                }
+            }
+
+            match (a, b) {
+                (Value::Bra(s), Value::Ket(t)) => {
+                    let mut sum = C::zero();
+                    for b in &s.superpositions {
+                        for k in &t.superpositions {
+                            if b.ket == k.ket {
+                                sum += b.scalar * k.scalar;
+                            }
+                        }
+                    }
+                    Value::Scalar(sum)
+                }
+                (Value::Bra(s), Value::Op(op)) => {
+                    let mut kets = Vec::new();
+                    for b in &s.superpositions {
+                        for kb in &op.ones {
+                            if b.ket == kb.ket {
+                                kets.push(Ket {
+                                    scalar: b.scalar * kb.scalar,
+                                    ket: kb.bra,
+                                    n: kb.n,
+                                    d: kb.d,
+                                });
+                            }
+                        }
+                    }
+                    Value::Bra(State {
+                        scalar: C::one(),
+                        superpositions: merge_kets(kets),
+                    })
+                }
+                (Value::Op(s), Value::Op(t)) => Value::Op(s * t),
+                (Value::Op(s), Value::Ket(t)) => Value::Ket(s * t),
+                (a, b) => return Err(format!("cannot multiply {} by {}", a.kind(), b.kind())),
+            }
+        }
+    })
+}
+
+fn div(a: Value, b: Value) -> Result<Value, String> {
+    match b {
+        Value::Scalar(s) if s.is_zero() => Err("division by zero".to_string()),
+        Value::Scalar(s) => Ok(a.scale(C::one() / s)),
+        b => Err(format!("cannot divide by {}", b.kind())),
+    }
+}
+
+fn sqrt(v: Value) -> Result<Value, String> {
+    match v {
+        Value::Scalar(s) if s.im == 0.0 && s.re >= 0.0 => {
+            Ok(Value::Scalar(C::new(s.re.sqrt(), 0.0)))
+        }
+        Value::Scalar(s) if s.im == 0.0 => Ok(Value::Scalar(C::new(0.0, (-s.re).sqrt()))),
+        Value::Scalar(_) => Err("sqrt is only supported for real arguments".to_string()),
+        v => Err(format!("cannot take the square root of {}", v.kind())),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(s: &str) -> Self {
+        Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn parse(&mut self) -> Result<Value, ParseError> {
+        let v = self.sum()?;
+        match self.peek() {
+            None => Ok(v),
+            Some(c) => Err(self.error(format!("unexpected '{c}'"))),
+        }
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError {
+            pos: self.pos,
+            message,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: &[char]) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(c) if expected.contains(&c) => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{}', found '{c}'", expected[0]))),
+            None => Err(self.error(format!("expected '{}', found end of input", expected[0]))),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Value, ParseError> {
+        let mut v = self.product()?;
+
+        loop {
+            let negate = match self.peek() {
+                Some('+') => false,
+                Some('-') => true,
+                _ => return Ok(v),
+            };
+            let pos = self.pos;
+            self.pos += 1;
+
+            let mut rhs = self.product()?;
+            if negate {
+                rhs = rhs.scale(-C::one());
+            }
+            v = add(v, rhs).map_err(|message| ParseError { pos, message })?;
+        }
+    }
+
+    fn product(&mut self) -> Result<Value, ParseError> {
+        let mut v = self.unary()?;
+
+        loop {
+            let pos = self.pos;
+            v = match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    mul(v, self.unary()?)
+                }
+                Some('/') => {
+                    self.pos += 1;
+                    div(v, self.unary()?)
+                }
+                Some(c) if starts_primary(c) => mul(v, self.primary()?),
+                _ => return Ok(v),
+            }
+            .map_err(|message| ParseError { pos, message })?;
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Ok(self.unary()?.scale(-C::one()))
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Value, ParseError> {
+        let pos = self.pos;
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some('i') => {
+                self.pos += 1;
+                Ok(Value::Scalar(C::new(0.0, 1.0)))
+            }
+            Some('s') => {
+                for c in "sqrt".chars() {
+                    if self.chars.get(self.pos) != Some(&c) {
+                        return Err(self.error("expected 'sqrt'".to_string()));
+                    }
+                    self.pos += 1;
+                }
+                self.expect(&['('])?;
+                let v = self.sum()?;
+                self.expect(&[')'])?;
+                sqrt(v).map_err(|message| ParseError { pos, message })
+            }
+            Some('√') => {
+                self.pos += 1;
+                let v = self.primary()?;
+                sqrt(v).map_err(|message| ParseError { pos, message })
+            }
+            Some('(') => {
+                self.pos += 1;
+                let v = self.sum()?;
+                self.expect(&[')'])?;
+                Ok(v)
+            }
+            Some('|') => {
+                self.pos += 1;
+                let ket = self.basis()?;
+                self.expect(&['⟩', '>'])?;
+                Ok(Value::Ket(State {
+                    scalar: C::one(),
+                    superpositions: vec![ket],
+                }))
+            }
+            Some('⟨' | '<') => {
+                self.pos += 1;
+                let bra = Value::Bra(State {
+                    scalar: C::one(),
+                    superpositions: vec![self.basis()?],
+                });
+                self.expect(&['|'])?;
+                if !self.closes_basis_label() {
+                    return Ok(bra);
+                }
+                // the bar is shared with a ket, as in ⟨0|1⟩
+                let ket = Value::Ket(State {
+                    scalar: C::one(),
+                    superpositions: vec![self.basis()?],
+                });
+                self.expect(&['⟩', '>'])?;
+                mul(bra, ket).map_err(|message| ParseError { pos, message })
+            }
+            Some(c) => Err(self.error(format!("unexpected '{c}'"))),
+            None => Err(self.error("unexpected end of input".to_string())),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+        {
+            self.pos += 1;
+        }
+
+        let s: String = self.chars[start..self.pos].iter().collect();
+        s.parse::<f64>()
+            .map(|re| Value::Scalar(C::new(re, 0.0)))
+            .map_err(|_| ParseError {
+                pos: start,
+                message: format!("invalid number '{s}'"),
+            })
+    }
+
+    /// Whether a basis label closed by '⟩' or '>' comes next.
+    fn closes_basis_label(&self) -> bool {
+        let digits = self.chars[self.pos..]
+            .iter()
+            .take_while(|&&c| c == '0' || c == '1')
+            .count();
+        digits > 0 && matches!(self.chars.get(self.pos + digits), Some('⟩' | '>'))
+    }
+
+    /// The digits of a basis ket or bra, without the delimiters.
+    fn basis(&mut self) -> Result<Ket<C>, ParseError> {
+        let mut ket = 0;
+        let mut n = 0;
+
+        while let Some(&c) = self.chars.get(self.pos) {
+            let bit = match c {
+                '0' => 0,
+                '1' => 1,
+                _ => break,
+            };
+            if n == MAX_QUBITS {
+                return Err(self.error(format!("basis label longer than {MAX_QUBITS} qubits")));
+            }
+            ket = ket * 2 + bit;
+            n += 1;
+            self.pos += 1;
+        }
+
+        if n == 0 {
+            return Err(self.error("expected a binary basis label".to_string()));
+        }
+
+        Ok(Ket {
+            scalar: C::one(),
+            ket,
+            n,
+            d: 2,
+        })
+    }
+}
+
+fn starts_primary(c: char) -> bool {
+    c.is_ascii_digit() || matches!(c, '.' | 'i' | 's' | '√' | '(' | '|' | '⟨' | '<')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ket::{Operator, State};
+    use crate::matrix::Vector;
+    use crate::Complex as C;
+
+    #[test]
+    fn parse_bell_state() {
+        let s = State::parse("(1/sqrt(2))(|00⟩ + |11⟩)").unwrap();
+        let r = C::new(1.0 / 2.0_f64.sqrt(), 0.0);
+        let z = C::new(0.0, 0.0);
+
+        assert_eq!(s.to_vector::<4>(), Vector([r, z, z, r]));
+    }
+
+    #[test]
+    fn parse_complex_scalars() {
+        let s = State::parse("1/√2 (|0⟩ - i|1⟩)").unwrap();
+        let r = 1.0 / 2.0_f64.sqrt();
+
+        assert_eq!(
+            s.to_vector::<2>(),
+            Vector([C::new(r, 0.0), C::new(0.0, -r)])
+        );
+    }
+
+    #[test]
+    fn parse_tensor_of_kets() {
+        let s = State::parse("|0⟩|1⟩").unwrap();
+        assert_eq!(s, State::parse("|01⟩").unwrap());
+    }
+
+    #[test]
+    fn parse_operator_ascii_and_unicode() {
+        let x = Operator::parse("|0><1| + |1><0|").unwrap();
+        assert_eq!(x, Operator::parse("|0⟩⟨1| + |1⟩⟨0|").unwrap());
+
+        let res = x * State::parse("|0>").unwrap();
+        assert_eq!(
+            res.to_vector::<2>(),
+            Vector([C::new(0.0, 0.0), C::new(1.0, 0.0)])
+        );
+    }
+
+    #[test]
+    fn parse_operator_applied_inline() {
+        let s = State::parse("(|0⟩⟨1| + |1⟩⟨0|)|1⟩").unwrap();
+        assert_eq!(
+            s.to_vector::<2>(),
+            Vector([C::new(1.0, 0.0), C::new(0.0, 0.0)])
+        );
+    }
+
+    #[test]
+    fn parse_scalars() {
+        let a = super::parse_scalar("(-1 + i)/√6").unwrap();
+        let r = 1.0 / 6.0_f64.sqrt();
+        assert!((a - C::new(-r, r)).modulus() < 1e-12);
+        assert_eq!(super::parse_scalar("1/4").unwrap(), C::new(0.25, 0.0));
+        let err = super::parse_scalar("|0⟩").unwrap_err();
+        assert_eq!(err.message, "expected a scalar, found a ket");
+    }
+
+    #[test]
+    fn parse_bra_kets() {
+        assert_eq!(super::parse_scalar("⟨0|1⟩").unwrap(), C::new(0.0, 0.0));
+        assert_eq!(super::parse_scalar("⟨1|1⟩").unwrap(), C::new(1.0, 0.0));
+        assert_eq!(super::parse_scalar("<10|10>").unwrap(), C::new(1.0, 0.0));
+        assert_eq!(
+            State::parse("⟨1|1⟩|0⟩ + ⟨0|1⟩|1⟩").unwrap(),
+            State::parse("|0⟩").unwrap()
+        );
+        // a bra still applies to what follows the bar
+        let s = State::parse("|0⟩⟨1|(|0⟩ + |1⟩)").unwrap();
+        assert_eq!(s, State::parse("|0⟩").unwrap());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = State::parse("|02⟩").unwrap_err();
+        assert_eq!(err.to_string(), "expected '⟩', found '2' at position 2");
+
+        let err = State::parse("|0⟩ + |01⟩").unwrap_err();
+        assert_eq!(err.message, "cannot add terms on 1 and 2 qubits");
+
+        let err = State::parse("(|0⟩").unwrap_err();
+        assert_eq!(err.message, "expected ')', found end of input");
+
+        let err = State::parse("⟨0|(|0⟩ + |1⟩)").unwrap_err();
+        assert_eq!(err.message, "expected a ket, found a scalar");
+
+        let wide = format!("|{}⟩", "0".repeat(40));
+        let err = State::parse(&format!("{wide}{wide}")).unwrap_err();
+        assert_eq!(err.message, "tensor product exceeds the 64-qubit limit");
+    }
+}
@@ -1,6 +1,19 @@
-mod ket;
-mod matrix;
-mod polar;
+pub mod channel;
+pub mod circuit;
+pub mod density;
+pub mod dynamics;
+pub mod hhl;
+pub mod ket;
+mod macros;
+pub mod matrix;
+pub mod mbqc;
+pub mod pauli;
+pub mod polar;
+pub mod protocols;
+pub mod qaoa;
+pub mod qec;
+pub mod shor;
+pub mod vqe;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Complex<T> {
@@ -14,6 +27,12 @@ impl<T> Complex<T> {
     }
 }
 
+impl<T: num::Zero> From<T> for Complex<T> {
+    fn from(re: T) -> Self {
+        Complex { re, im: T::zero() }
+    }
+}
+
 impl<T> PartialEq for Complex<T>
 where
     T: PartialEq,
@@ -130,6 +149,133 @@ impl<T> Complex<T> {
     }
 }
 
+/// Complex conjugation, which is the identity on real scalars.
+pub trait Conjugate {
+    fn conj(&self) -> Self;
+}
+
+macro_rules! real_conjugate {
+    ($($t:ty),*) => {
+        $(impl Conjugate for $t {
+            fn conj(&self) -> Self {
+                *self
+            }
+        })*
+    };
+}
+
+real_conjugate!(i32, i64, f32, f64);
+
+impl<T: Copy + std::ops::Neg<Output = T>> Conjugate for Complex<T> {
+    fn conj(&self) -> Self {
+        self.conjugate()
+    }
+}
+
+/// LaTeX markup for a scalar, with common surds and small fractions written
+/// out exactly, e.g. `\frac{1}{\sqrt{2}}` rather than `0.7071067811865476`.
+pub trait Latex {
+    fn to_latex(&self) -> String;
+}
+
+macro_rules! integer_latex {
+    ($($t:ty),*) => {
+        $(impl Latex for $t {
+            fn to_latex(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+integer_latex!(i32, i64);
+
+impl Latex for f64 {
+    fn to_latex(&self) -> String {
+        const EPS: f64 = 1e-9;
+        let x = *self;
+        if x < 0.0 {
+            return format!("-{}", (-x).to_latex());
+        }
+        if (x - x.round()).abs() < EPS {
+            return format!("{}", x.round() + 0.0);
+        }
+
+        if let Some((p, q)) = small_fraction(x) {
+            return format!("\\frac{{{p}}}{{{q}}}");
+        }
+
+        // surds as they turn up in gate amplitudes: 1/(q√r), else p√r/q
+        for r in [2, 3, 5, 6] {
+            let root = (r as f64).sqrt();
+            if let Some((1, q)) = small_fraction(x * root) {
+                return match q {
+                    1 => format!("\\frac{{1}}{{\\sqrt{{{r}}}}}"),
+                    _ => format!("\\frac{{1}}{{{q}\\sqrt{{{r}}}}}"),
+                };
+            }
+            if let Some((p, q)) = small_fraction(x / root) {
+                let numerator = match p {
+                    1 => format!("\\sqrt{{{r}}}"),
+                    _ => format!("{p}\\sqrt{{{r}}}"),
+                };
+                return match q {
+                    1 => numerator,
+                    _ => format!("\\frac{{{numerator}}}{{{q}}}"),
+                };
+            }
+        }
+
+        let s = format!("{x:.4}");
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// `x` as p/q in lowest terms for a denominator up to 16, if it is one.
+fn small_fraction(x: f64) -> Option<(i64, i64)> {
+    (1..=16).find_map(|q| {
+        let p = x * q as f64;
+        ((p - p.round()).abs() < 1e-9).then_some((p.round() as i64, q))
+    })
+}
+
+impl Latex for f32 {
+    fn to_latex(&self) -> String {
+        (*self as f64).to_latex()
+    }
+}
+
+impl<T> Latex for Complex<T>
+where
+    T: Copy + Latex + num::Zero + num::One + PartialOrd + std::ops::Neg<Output = T>,
+{
+    fn to_latex(&self) -> String {
+        let imaginary = |im: T| {
+            if im == T::one() {
+                "i".to_string()
+            } else {
+                format!("{}i", im.to_latex())
+            }
+        };
+
+        if self.im.is_zero() {
+            return self.re.to_latex();
+        }
+        if self.re.is_zero() {
+            return if self.im < T::zero() {
+                format!("-{}", imaginary(-self.im))
+            } else {
+                imaginary(self.im)
+            };
+        }
+        if self.im < T::zero() {
+            format!("{} - {}", self.re.to_latex(), imaginary(-self.im))
+        } else {
+            format!("{} + {}", self.re.to_latex(), imaginary(self.im))
+        }
+    }
+}
+
 impl<T> std::ops::Neg for Complex<T>
 where
     T: std::ops::Neg<Output = T>,
@@ -160,15 +306,41 @@ where
     }
 }
 
+impl<T> num::One for Complex<T>
+where
+    T: Copy + num::Zero + num::One + std::ops::Sub<Output = T>,
+{
+    fn one() -> Self {
+        Complex {
+            re: T::one(),
+            im: T::zero(),
+        }
+    }
+}
+
+impl<T> std::ops::AddAssign for Complex<T>
+where
+    T: Copy + std::ops::Add<Output = T>,
+{
+    fn add_assign(&mut self, rhs: Complex<T>) {
+        *self = *self + rhs;
+    }
+}
+
 impl Complex<f64> {
     pub fn modulus(&self) -> f64 {
         self.mod_squared().sqrt()
     }
+
+    /// The argument in (−π, π].
+    pub fn arg(&self) -> f64 {
+        self.im.atan2(self.re)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Complex;
+    use super::{Complex, Latex};
 
     type C<T> = Complex<T>;
 
@@ -262,4 +434,25 @@ mod tests {
         let res = a * a * a * a * a;
         println!("{}", res);
     }
+
+    #[test]
+    fn latex_scalars() {
+        assert_eq!(3.to_latex(), "3");
+        assert_eq!((-2.0).to_latex(), "-2");
+        assert_eq!(0.5.to_latex(), "\\frac{1}{2}");
+        assert_eq!((1.0 / 2f64.sqrt()).to_latex(), "\\frac{1}{\\sqrt{2}}");
+        assert_eq!((-1.0 / 8f64.sqrt()).to_latex(), "-\\frac{1}{2\\sqrt{2}}");
+        assert_eq!(3f64.sqrt().to_latex(), "\\sqrt{3}");
+        assert_eq!((3f64.sqrt() / 2.0).to_latex(), "\\frac{\\sqrt{3}}{2}");
+        assert_eq!(0.123456.to_latex(), "0.1235");
+    }
+
+    #[test]
+    fn latex_complex() {
+        assert_eq!(C::new(0.0, 1.0).to_latex(), "i");
+        assert_eq!(C::new(0.0, -0.5).to_latex(), "-\\frac{1}{2}i");
+        assert_eq!(C::new(1.0, -1.0).to_latex(), "1 - i");
+        assert_eq!(C::new(2, 3).to_latex(), "2 + 3i");
+        assert_eq!(C::new(-1.0, 0.0).to_latex(), "-1");
+    }
 }
@@ -0,0 +1,90 @@
+//! Compile-time shorthands for the [`ket`](mod@crate::ket) types. Basis labels
+//! are binary and may be written as a string (`ket!("011")`), a single
+//! literal (`ket!(011)`) or separate digits (`ket!(0 1 1)`).
+
+/// A basis [`State`](crate::ket::State), e.g. `ket!("01")` for |01⟩.
+#[macro_export]
+macro_rules! ket {
+    ($($label:tt)+) => {{
+        let (ket, n) = const { $crate::ket::basis_label(stringify!($($label)+)) };
+        $crate::ket::State::basis(ket, n)
+    }};
+}
+
+/// A basis [`Bra`](crate::ket::Bra), e.g. `bra!("01")` for ⟨01|.
+#[macro_export]
+macro_rules! bra {
+    ($($label:tt)+) => {{
+        let (bra, n) = const { $crate::ket::basis_label(stringify!($($label)+)) };
+        $crate::ket::Bra::basis(bra, n)
+    }};
+}
+
+/// An [`Operator`](crate::ket::Operator) written as a sum of ASCII ketbras,
+/// e.g. `op!(|0><1| + |1><0|)`.
+#[macro_export]
+macro_rules! op {
+    (@sum $acc:expr;) => {
+        $acc
+    };
+    (@sum $acc:expr; + | $ket:literal > < $bra:literal | $($rest:tt)*) => {
+        $crate::op!(@sum $acc + $crate::ket!($ket) * $crate::bra!($bra); $($rest)*)
+    };
+    (@sum $acc:expr; - | $ket:literal > < $bra:literal | $($rest:tt)*) => {
+        $crate::op!(@sum $acc + -($crate::ket!($ket) * $crate::bra!($bra)); $($rest)*)
+    };
+    (- | $ket:literal > < $bra:literal | $($rest:tt)*) => {
+        $crate::op!(@sum -($crate::ket!($ket) * $crate::bra!($bra)); $($rest)*)
+    };
+    (| $ket:literal > < $bra:literal | $($rest:tt)*) => {
+        $crate::op!(@sum $crate::ket!($ket) * $crate::bra!($bra); $($rest)*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ket::{Bra, Operator, State};
+    use crate::Complex as C;
+
+    #[test]
+    fn ket_forms_agree() {
+        let a: State<i32> = ket!("011");
+        let b: State<i32> = ket!(0 1 1);
+        let c: State<i32> = ket!(011);
+
+        assert_eq!(a, State::basis(3, 3));
+        assert_eq!(a, b);
+        assert_eq!(a, c);
+    }
+
+    #[test]
+    fn bra_ket_inner_product() {
+        let b: Bra<i32> = bra!("01");
+        assert_eq!(b.clone() * ket!("01"), 1);
+        assert_eq!(b * ket!("10"), 0);
+    }
+
+    #[test]
+    fn op_matches_parser() {
+        let cases: [(Operator<C<f64>>, &str); 3] = [
+            (op!(|0><1| + |1><0|), "|0><1| + |1><0|"),
+            (op!(-|1><1| + |0><0|), "-|1><1| + |0><0|"),
+            (op!(|"01"><"10"| - |"10"><"01"|), "|01><10| - |10><01|"),
+        ];
+        for (built, source) in cases {
+            let parsed = Operator::parse(source).unwrap();
+            assert_eq!(built.simplify(), parsed.simplify(), "{source}");
+        }
+    }
+
+    #[test]
+    fn op_applies_to_kets() {
+        let x: Operator<f64> = op!(|0><1| + |1><0|);
+        let res = x * ket!(0);
+        assert_eq!(res, ket!(1));
+
+        let z: Operator<i32> = op!(-|1><1| + |0><0|);
+        assert_eq!(bra!(0) * (z.clone() * ket!(0)), 1);
+        assert_eq!(bra!(1) * (z * ket!(1)), -1);
+    }
+}
@@ -1,8 +1,8 @@
 #[derive(Debug, Clone)]
-pub struct Vector<T, const N: usize>([T; N]);
+pub struct Vector<T, const N: usize>(pub [T; N]);
 
 #[derive(Debug, Clone)]
-pub struct Matrix<T, const M: usize, const N: usize>([[T; N]; M]);
+pub struct Matrix<T, const M: usize, const N: usize>(pub [[T; N]; M]);
 
 impl<T, const N: usize> std::ops::Add<Vector<T, N>> for Vector<T, N>
 where
@@ -0,0 +1,544 @@
+//! Measurement-based quantum computation in the measurement calculus of
+//! Danos, Kashefi and Panangaden, "The measurement calculus" (2007). A
+//! pattern prepares qubits in |+⟩, entangles them with CZs into a graph
+//! state, measures all but the outputs in the XY plane, and corrects the
+//! outputs with Pauli byproducts. Measurement angles and corrections depend
+//! on earlier outcomes through their domains, which is what makes the
+//! computation deterministic.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use num::Zero;
+use rand::Rng;
+
+use crate::circuit::{Circuit, Gate, StateVector};
+use crate::ket::{graph_edges, State};
+use crate::Complex;
+
+type C = Complex<f64>;
+
+/// A command of a [`Pattern`], on qubits named by vertex numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// N: prepares the qubit in |+⟩.
+    Prepare(usize),
+    /// E: a CZ between the two qubits.
+    Entangle(usize, usize),
+    /// M: measures the qubit in the basis |±_α⟩ = (|0⟩ ± e^(iα)|1⟩)/√2 for
+    /// the adapted angle α = (−1)^s `angle` + tπ, where s and t are the
+    /// parities of the outcomes of `s_domain` and `t_domain`. Outcome 0 is
+    /// |+_α⟩. The qubit is then discarded.
+    Measure {
+        qubit: usize,
+        angle: f64,
+        s_domain: Vec<usize>,
+        t_domain: Vec<usize>,
+    },
+    /// X on the qubit if the outcomes of `domain` have odd parity.
+    X { qubit: usize, domain: Vec<usize> },
+    /// Z on the qubit if the outcomes of `domain` have odd parity.
+    Z { qubit: usize, domain: Vec<usize> },
+}
+
+/// A measurement pattern: its commands in the order they run, and the
+/// vertices whose qubits hold the input and the output, in order.
+///
+/// ```
+/// use complex_calculator::circuit::Circuit;
+/// use complex_calculator::mbqc::Pattern;
+///
+/// // J(α) = H P(α) by teleporting qubit 0 to qubit 1
+/// let alpha = 0.7;
+/// let mut j = Pattern::new(&[0], &[1]);
+/// j.prepare(1).entangle(0, 1).measure(0, -alpha, &[], &[]).x(1, &[0]);
+///
+/// let mut circuit = Circuit::new(1);
+/// circuit.p(0, alpha).h(0);
+/// assert!(j.equivalent_to(&circuit, 1e-9));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    commands: Vec<Command>,
+}
+
+/// The outcome of [`Pattern::run`].
+#[derive(Debug, Clone)]
+pub struct PatternRun {
+    /// The outcome of each measured vertex.
+    pub outcomes: BTreeMap<usize, bool>,
+    /// The state of the outputs, the k-th output on qubit k.
+    pub output: State<C>,
+}
+
+impl Pattern {
+    /// An empty pattern with the given input and output vertices.
+    pub fn new(inputs: &[usize], outputs: &[usize]) -> Self {
+        Pattern {
+            inputs: inputs.to_vec(),
+            outputs: outputs.to_vec(),
+            commands: vec![],
+        }
+    }
+
+    /// The pattern starting from the graph state of `adjacency` on its
+    /// vertices: every vertex but the inputs prepared in |+⟩, then a CZ on
+    /// every edge. Measurements and corrections are then added to it.
+    pub fn on_graph(adjacency: &[Vec<bool>], inputs: &[usize], outputs: &[usize]) -> Self {
+        let mut pattern = Pattern::new(inputs, outputs);
+        for v in (0..adjacency.len()).filter(|v| !inputs.contains(v)) {
+            pattern.prepare(v);
+        }
+        for (a, b) in graph_edges(adjacency) {
+            pattern.entangle(a, b);
+        }
+        pattern
+    }
+
+    /// The standard pattern on a linear cluster of n + 1 vertices, input 0
+    /// and output n, for J(αₙ₋₁)…J(α₀) with J(α) = H P(α) and `angles` the
+    /// αₖ. Vertex k is measured at −αₖ, its angle flipped by the outcome of
+    /// k − 1 and shifted by π by that of k − 2, the byproducts of the
+    /// earlier measurements carried through to it. Four angles give any
+    /// single-qubit unitary up to a phase: U = J(0) J(β) J(γ) J(δ) for the
+    /// rotations Rz(β) Rx(γ) Rz(δ).
+    pub fn linear(angles: &[f64]) -> Self {
+        let n = angles.len();
+        let mut path = vec![vec![false; n + 1]; n + 1];
+        for k in 0..n {
+            path[k][k + 1] = true;
+            path[k + 1][k] = true;
+        }
+        let mut pattern = Pattern::on_graph(&path, &[0], &[n]);
+        for (k, &alpha) in angles.iter().enumerate() {
+            let s: Vec<usize> = k.checked_sub(1).into_iter().collect();
+            let t: Vec<usize> = k.checked_sub(2).into_iter().collect();
+            pattern.measure(k, -alpha, &s, &t);
+        }
+        if n > 0 {
+            pattern.x(n, &[n - 1]);
+        }
+        if n > 1 {
+            pattern.z(n, &[n - 2]);
+        }
+        pattern
+    }
+
+    pub fn inputs(&self) -> &[usize] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    pub fn prepare(&mut self, qubit: usize) -> &mut Self {
+        self.commands.push(Command::Prepare(qubit));
+        self
+    }
+
+    pub fn entangle(&mut self, a: usize, b: usize) -> &mut Self {
+        self.commands.push(Command::Entangle(a, b));
+        self
+    }
+
+    /// Measures `qubit` at `angle`, adapted by the outcomes of the two
+    /// domains as described at [`Command::Measure`].
+    pub fn measure(
+        &mut self,
+        qubit: usize,
+        angle: f64,
+        s_domain: &[usize],
+        t_domain: &[usize],
+    ) -> &mut Self {
+        self.commands.push(Command::Measure {
+            qubit,
+            angle,
+            s_domain: s_domain.to_vec(),
+            t_domain: t_domain.to_vec(),
+        });
+        self
+    }
+
+    pub fn x(&mut self, qubit: usize, domain: &[usize]) -> &mut Self {
+        self.commands.push(Command::X {
+            qubit,
+            domain: domain.to_vec(),
+        });
+        self
+    }
+
+    pub fn z(&mut self, qubit: usize, domain: &[usize]) -> &mut Self {
+        self.commands.push(Command::Z {
+            qubit,
+            domain: domain.to_vec(),
+        });
+        self
+    }
+
+    /// One more than the largest vertex the pattern mentions.
+    fn num_vertices(&self) -> usize {
+        let mut vertices: Vec<usize> = self.inputs.iter().chain(&self.outputs).copied().collect();
+        for command in &self.commands {
+            match command {
+                Command::Prepare(q) => vertices.push(*q),
+                Command::Entangle(a, b) => vertices.extend([*a, *b]),
+                Command::Measure { qubit, .. }
+                | Command::X { qubit, .. }
+                | Command::Z { qubit, .. } => vertices.push(*qubit),
+            }
+        }
+        vertices.into_iter().max().map_or(0, |v| v + 1)
+    }
+
+    /// Panics unless every command acts on a live qubit, every domain names
+    /// earlier measurements, and every qubit but the outputs is measured.
+    fn check(&self) {
+        let mut live: Vec<usize> = self.inputs.clone();
+        let mut measured: Vec<usize> = vec![];
+        let use_qubit = |q: &usize, live: &[usize]| {
+            assert!(live.contains(q), "qubit {q} is used while not prepared");
+        };
+        let check_domain = |domain: &[usize], measured: &[usize]| {
+            for q in domain {
+                assert!(
+                    measured.contains(q),
+                    "qubit {q} is in a domain before it is measured"
+                );
+            }
+        };
+        for command in &self.commands {
+            match command {
+                Command::Prepare(q) => {
+                    assert!(
+                        !live.contains(q) && !measured.contains(q),
+                        "qubit {q} is prepared twice"
+                    );
+                    live.push(*q);
+                }
+                Command::Entangle(a, b) => {
+                    use_qubit(a, &live);
+                    use_qubit(b, &live);
+                    assert_ne!(a, b, "qubit {a} is entangled with itself");
+                }
+                Command::Measure {
+                    qubit,
+                    s_domain,
+                    t_domain,
+                    ..
+                } => {
+                    use_qubit(qubit, &live);
+                    assert!(
+                        !self.outputs.contains(qubit),
+                        "output qubit {qubit} is measured"
+                    );
+                    check_domain(s_domain, &measured);
+                    check_domain(t_domain, &measured);
+                    live.retain(|q| q != qubit);
+                    measured.push(*qubit);
+                }
+                Command::X { qubit, domain } | Command::Z { qubit, domain } => {
+                    use_qubit(qubit, &live);
+                    check_domain(domain, &measured);
+                }
+            }
+        }
+        live.sort();
+        let mut outputs = self.outputs.clone();
+        outputs.sort();
+        assert_eq!(
+            live, outputs,
+            "the qubits left unmeasured are not the outputs"
+        );
+    }
+
+    /// Runs the pattern on `input`, with the k-th input on vertex
+    /// `inputs[k]` and any further qubits of `input` carried along
+    /// untouched as references, after the vertices. `choose` picks the
+    /// outcome of each measurement from its vertex and its probability of
+    /// reading 1. `None` if a chosen outcome has probability 0.
+    fn execute(
+        &self,
+        input: &[C],
+        mut choose: impl FnMut(usize, f64) -> bool,
+    ) -> Option<(BTreeMap<usize, bool>, Vec<C>)> {
+        self.check();
+        let vertices = self.num_vertices();
+        let k = self.inputs.len();
+        let references = input.len().trailing_zeros() as usize - k;
+
+        // input qubit j on vertex inputs[j], reference r after the vertices
+        let place = |x: usize, on: &[usize]| {
+            let mut index = 0;
+            for (j, &v) in on.iter().enumerate() {
+                index |= (x >> j & 1) << v;
+            }
+            index | (x >> on.len()) << vertices
+        };
+        let mut amplitudes = vec![C::zero(); 1 << (vertices + references)];
+        for (x, &a) in input.iter().enumerate() {
+            amplitudes[place(x, &self.inputs)] = a;
+        }
+        let mut state = StateVector::from_amplitudes(amplitudes);
+
+        let mut outcomes = BTreeMap::new();
+        let parity = |domain: &[usize], outcomes: &BTreeMap<usize, bool>| {
+            domain.iter().filter(|q| outcomes[q]).count() % 2 == 1
+        };
+        for command in &self.commands {
+            match command {
+                Command::Prepare(q) => state.apply(&Gate::H, &[*q]),
+                Command::Entangle(a, b) => state.apply(&Gate::Cz, &[*a, *b]),
+                Command::Measure {
+                    qubit,
+                    angle,
+                    s_domain,
+                    t_domain,
+                } => {
+                    let mut alpha = *angle;
+                    if parity(s_domain, &outcomes) {
+                        alpha = -alpha;
+                    }
+                    if parity(t_domain, &outcomes) {
+                        alpha += PI;
+                    }
+                    // |±_α⟩ to |±⟩ to |0⟩ or |1⟩, then the qubit is reset
+                    state.apply(&Gate::Phase((-alpha).into()), &[*qubit]);
+                    state.apply(&Gate::H, &[*qubit]);
+                    let outcome = choose(*qubit, state.probability_of_one(*qubit));
+                    if state.project(*qubit, outcome) < 1e-12 {
+                        return None;
+                    }
+                    if outcome {
+                        state.apply(&Gate::X, &[*qubit]);
+                    }
+                    outcomes.insert(*qubit, outcome);
+                }
+                Command::X { qubit, domain } => {
+                    if parity(domain, &outcomes) {
+                        state.apply(&Gate::X, &[*qubit]);
+                    }
+                }
+                Command::Z { qubit, domain } => {
+                    if parity(domain, &outcomes) {
+                        state.apply(&Gate::Z, &[*qubit]);
+                    }
+                }
+            }
+        }
+
+        let output = (0..1 << (self.outputs.len() + references))
+            .map(|y| state.amplitudes()[place(y, &self.outputs)])
+            .collect();
+        Some((outcomes, output))
+    }
+
+    /// Runs the pattern on `input`, a state of as many qubits as there are
+    /// inputs, sampling each measurement outcome from `rng`.
+    pub fn run<R: Rng + ?Sized>(&self, input: &State<C>, rng: &mut R) -> PatternRun {
+        let amplitudes = input.amplitudes(self.inputs.len() as u32);
+        let (outcomes, output) = self
+            .execute(&amplitudes, |_, p1| rng.random::<f64>() < p1)
+            .expect("a sampled outcome has probability 0");
+        PatternRun {
+            outcomes,
+            output: State::from_amplitudes(&output),
+        }
+    }
+
+    /// Whether the pattern implements the unitary of `circuit`, up to a
+    /// global phase, on every branch of measurement outcomes. The inputs
+    /// and outputs stand for the circuit's qubits in order. Each branch is
+    /// run on the inputs maximally entangled with as many reference qubits,
+    /// which is taken to (U ⊗ I) 2^(−k/2) Σ_x |x⟩|x⟩ exactly when the branch
+    /// applies U.
+    pub fn equivalent_to(&self, circuit: &Circuit, eps: f64) -> bool {
+        let k = self.inputs.len();
+        assert_eq!(
+            circuit.num_qubits() as usize,
+            k,
+            "the circuit has {} qubits, the pattern {k} inputs",
+            circuit.num_qubits()
+        );
+        assert_eq!(
+            self.outputs.len(),
+            k,
+            "the pattern has {} outputs for {k} inputs",
+            self.outputs.len()
+        );
+        let norm = C::new(0.5f64.powf(k as f64 / 2.0), 0.0);
+        let mut input = vec![C::zero(); 1 << (2 * k)];
+        for x in 0..1 << k {
+            input[x | x << k] = norm;
+        }
+        let mut expected = vec![C::zero(); 1 << (2 * k)];
+        for (scalar, ket, bra, _) in circuit.unitary().terms() {
+            expected[(ket | bra << k) as usize] += scalar * norm;
+        }
+
+        let measurements = self
+            .commands
+            .iter()
+            .filter(|c| matches!(c, Command::Measure { .. }))
+            .count();
+        (0u64..1 << measurements).all(|branch| {
+            let mut next = 0;
+            let choose = |_, _| {
+                next += 1;
+                branch >> (next - 1) & 1 == 1
+            };
+            match self.execute(&input, choose) {
+                None => true,
+                Some((_, output)) => {
+                    let overlap = output
+                        .iter()
+                        .zip(&expected)
+                        .fold(C::zero(), |acc, (&a, &b)| acc + b.conjugate() * a);
+                    (overlap.modulus() - 1.0).abs() <= eps
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::Pattern;
+    use crate::circuit::Circuit;
+    use crate::Complex;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const EPSILON: f64 = 0.0000001;
+
+    /// J(αₙ₋₁)…J(α₀) as a circuit.
+    fn j_circuit(angles: &[f64]) -> Circuit {
+        let mut c = Circuit::new(1);
+        for &alpha in angles {
+            c.p(0, alpha).h(0);
+        }
+        c
+    }
+
+    #[test]
+    fn linear_clusters_apply_j_chains() {
+        for angles in [
+            vec![0.0],
+            vec![0.3, -1.2],
+            vec![0.4, 1.1, -0.6],
+            vec![PI / 4.0, 0.5, 2.0, 0.0],
+        ] {
+            let pattern = Pattern::linear(&angles);
+            assert!(
+                pattern.equivalent_to(&j_circuit(&angles), EPSILON),
+                "{angles:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn euler_rotation_on_five_qubits() {
+        // Rz(β) Rx(γ) Rz(δ) = J(0) J(β) J(γ) J(δ) up to a phase
+        let (beta, gamma, delta) = (0.9, -0.4, 1.7);
+        let mut c = Circuit::new(1);
+        c.rz(0, delta).rx(0, gamma).rz(0, beta);
+        let pattern = Pattern::linear(&[delta, gamma, beta, 0.0]);
+        assert!(pattern.equivalent_to(&c, EPSILON));
+        assert_eq!(pattern.commands().len(), 5 - 1 + 4 + 4 + 2);
+    }
+
+    #[test]
+    fn adaptivity_is_needed() {
+        // without feed-forward, the branches apply different unitaries
+        let angles = [0.3, -1.2, 0.8];
+        let mut path = vec![vec![false; 4]; 4];
+        for k in 0..3 {
+            path[k][k + 1] = true;
+            path[k + 1][k] = true;
+        }
+        let mut pattern = Pattern::on_graph(&path, &[0], &[3]);
+        for (k, &alpha) in angles.iter().enumerate() {
+            pattern.measure(k, -alpha, &[], &[]);
+        }
+        assert!(!pattern.equivalent_to(&j_circuit(&angles), EPSILON));
+    }
+
+    #[test]
+    fn cnot_pattern() {
+        // X₃^s₂ Z₃^s₁ Z₀^s₁ M₂ M₁ E₂₃ E₀₂ E₁₂ N₂ N₃, with the control on 0 and
+        // the target teleported from 1 to 3
+        let mut pattern = Pattern::new(&[0, 1], &[0, 3]);
+        pattern
+            .prepare(2)
+            .prepare(3)
+            .entangle(1, 2)
+            .entangle(0, 2)
+            .entangle(2, 3)
+            .measure(1, 0.0, &[], &[])
+            .measure(2, 0.0, &[], &[])
+            .x(3, &[2])
+            .z(3, &[1])
+            .z(0, &[1]);
+        let mut cnot = Circuit::new(2);
+        cnot.cnot(0, 1);
+        assert!(pattern.equivalent_to(&cnot, EPSILON));
+    }
+
+    #[test]
+    fn runs_on_a_given_input() {
+        let angles = [0.4, 1.1, -0.6, 0.2];
+        let pattern = Pattern::linear(&angles);
+        let mut prepare = Circuit::new(1);
+        prepare.ry(0, 0.8).rz(0, 0.3);
+        let mut c = prepare.clone();
+        c.compose(&j_circuit(&angles), &[0]);
+        let expected = c.simulate().amplitudes(1);
+
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut branches = std::collections::HashSet::new();
+        for _ in 0..20 {
+            let run = pattern.run(&prepare.simulate(), &mut rng);
+            assert_eq!(run.outcomes.len(), 4);
+            branches.insert(run.outcomes.values().copied().collect::<Vec<_>>());
+            let output = run.output.amplitudes(1);
+            let overlap = output
+                .iter()
+                .zip(&expected)
+                .fold(Complex::new(0.0, 0.0), |acc, (&a, &b)| {
+                    acc + b.conjugate() * a
+                });
+            assert!((overlap.modulus() - 1.0).abs() < EPSILON);
+        }
+        // every outcome is equally likely, so many branches are seen
+        assert!(branches.len() > 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "output qubit 1 is measured")]
+    fn outputs_stay_unmeasured() {
+        let mut pattern = Pattern::new(&[0], &[1]);
+        pattern.prepare(1).entangle(0, 1).measure(1, 0.0, &[], &[]);
+        pattern.equivalent_to(&Circuit::new(1), EPSILON);
+    }
+
+    #[test]
+    #[should_panic(expected = "qubit 2 is in a domain before it is measured")]
+    fn domains_refer_to_the_past() {
+        let mut pattern = Pattern::new(&[0], &[1]);
+        pattern
+            .prepare(1)
+            .prepare(2)
+            .entangle(0, 1)
+            .measure(0, 0.0, &[2], &[]);
+        pattern.equivalent_to(&Circuit::new(1), EPSILON);
+    }
+}
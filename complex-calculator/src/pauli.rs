@@ -0,0 +1,505 @@
+use crate::circuit::Counts;
+use crate::ket::{KetBra, Operator};
+use crate::{Complex, Conjugate};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z,
+}
+
+impl Pauli {
+    /// `self · other` as `(i^phase, pauli)`.
+    fn mul(self, other: Pauli) -> (u8, Pauli) {
+        use Pauli::*;
+
+        match (self, other) {
+            (I, p) | (p, I) => (0, p),
+            (X, X) | (Y, Y) | (Z, Z) => (0, I),
+            (X, Y) => (1, Z),
+            (Y, Z) => (1, X),
+            (Z, X) => (1, Y),
+            (Y, X) => (3, Z),
+            (Z, Y) => (3, X),
+            (X, Z) => (3, Y),
+        }
+    }
+}
+
+/// A tensor product of single-qubit Paulis with a phase i^k, where
+/// `paulis[q]` acts on qubit `q`. Written like a ket label, with qubit 0
+/// rightmost, so `"XZ"` is X ⊗ Z with the Z on qubit 0.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PauliString {
+    phase: u8,
+    paulis: Vec<Pauli>,
+}
+
+impl PauliString {
+    /// `i^phase` times the product of `paulis`, indexed by qubit.
+    pub fn new(phase: u8, paulis: Vec<Pauli>) -> Self {
+        PauliString {
+            phase: phase % 4,
+            paulis,
+        }
+    }
+
+    pub fn identity(n: usize) -> Self {
+        Self::new(0, vec![Pauli::I; n])
+    }
+
+    /// `pauli` on `qubit` and the identity elsewhere.
+    pub fn single(n: usize, qubit: usize, pauli: Pauli) -> Self {
+        let mut s = Self::identity(n);
+        s.paulis[qubit] = pauli;
+        s
+    }
+
+    /// The exponent k of the phase i^k.
+    pub fn phase(&self) -> u8 {
+        self.phase
+    }
+
+    pub fn paulis(&self) -> &[Pauli] {
+        &self.paulis
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.paulis.len()
+    }
+
+    /// Number of qubits acted on non-trivially.
+    pub fn weight(&self) -> usize {
+        self.paulis.iter().filter(|&&p| p != Pauli::I).count()
+    }
+
+    /// Pauli strings commute exactly when they anticommute on an even number
+    /// of qubits.
+    pub fn commutes_with(&self, other: &PauliString) -> bool {
+        assert_eq!(
+            self.num_qubits(),
+            other.num_qubits(),
+            "Pauli strings on different qubit counts"
+        );
+
+        self.paulis
+            .iter()
+            .zip(&other.paulis)
+            .filter(|(&a, &b)| a != Pauli::I && b != Pauli::I && a != b)
+            .count()
+            % 2
+            == 0
+    }
+
+    pub fn to_operator(&self) -> Operator<Complex<f64>> {
+        let n = self.paulis.len() as u32;
+        let phases = [
+            Complex::new(1.0, 0.0),
+            Complex::new(0.0, 1.0),
+            Complex::new(-1.0, 0.0),
+            Complex::new(0.0, -1.0),
+        ];
+
+        let flip = self
+            .paulis
+            .iter()
+            .enumerate()
+            .filter(|(_, &p)| p == Pauli::X || p == Pauli::Y)
+            .fold(0u64, |acc, (q, _)| acc | 1 << q);
+
+        let ones = (0..1u64 << n)
+            .map(|bra| {
+                // X|b⟩ = |¬b⟩, Y|b⟩ = i(-1)^b |¬b⟩, Z|b⟩ = (-1)^b |b⟩
+                let mut phase = self.phase as u32;
+                for (q, &p) in self.paulis.iter().enumerate() {
+                    let bit = (bra >> q) & 1 == 1;
+                    phase += match (p, bit) {
+                        (Pauli::Y, false) => 1,
+                        (Pauli::Y, true) => 3,
+                        (Pauli::Z, true) => 2,
+                        _ => 0,
+                    };
+                }
+
+                KetBra {
+                    scalar: phases[phase as usize % 4],
+                    ket: bra ^ flip,
+                    bra,
+                    n,
+                    d: 2,
+                }
+            })
+            .collect();
+
+        Operator {
+            scalar: Complex::new(1.0, 0.0),
+            ones,
+        }
+    }
+}
+
+impl std::ops::Mul<&PauliString> for &PauliString {
+    type Output = PauliString;
+
+    fn mul(self, rhs: &PauliString) -> Self::Output {
+        assert_eq!(
+            self.num_qubits(),
+            rhs.num_qubits(),
+            "Pauli strings on different qubit counts"
+        );
+
+        let mut phase = (self.phase + rhs.phase) % 4;
+        let paulis = self
+            .paulis
+            .iter()
+            .zip(&rhs.paulis)
+            .map(|(&a, &b)| {
+                let (k, p) = a.mul(b);
+                phase = (phase + k) % 4;
+                p
+            })
+            .collect();
+
+        PauliString::new(phase, paulis)
+    }
+}
+
+impl std::ops::Mul<PauliString> for PauliString {
+    type Output = PauliString;
+
+    fn mul(self, rhs: PauliString) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl PauliString {
+    /// The expectation value ⟨ψ|P|ψ⟩ in the state with the given dense
+    /// amplitudes, index x the amplitude of |x⟩. Only the real part is
+    /// returned, which is all of it when the phase is ±1.
+    pub fn expectation(&self, amplitudes: &[Complex<f64>]) -> f64 {
+        assert_eq!(
+            amplitudes.len(),
+            1 << self.num_qubits(),
+            "{} amplitudes for {} qubits",
+            amplitudes.len(),
+            self.num_qubits()
+        );
+        let (mut flip, mut y_mask, mut z_mask) = (0usize, 0usize, 0usize);
+        for (q, &p) in self.paulis.iter().enumerate() {
+            match p {
+                Pauli::X => flip |= 1 << q,
+                Pauli::Y => {
+                    flip |= 1 << q;
+                    y_mask |= 1 << q;
+                }
+                Pauli::Z => z_mask |= 1 << q,
+                Pauli::I => {}
+            }
+        }
+
+        // P|x⟩ = i^(phase + #Y) (−1)^(ones of x under Y or Z) |x ⊕ flip⟩
+        let base = self.phase as u32 + y_mask.count_ones();
+        let mut sum = Complex::new(0.0, 0.0);
+        for (x, &a) in amplitudes.iter().enumerate() {
+            let sign = 2 * ((x & (y_mask | z_mask)).count_ones() % 2);
+            let phase = [
+                Complex::new(1.0, 0.0),
+                Complex::new(0.0, 1.0),
+                Complex::new(-1.0, 0.0),
+                Complex::new(0.0, -1.0),
+            ][((base + sign) % 4) as usize];
+            sum += amplitudes[x ^ flip].conj() * phase * a;
+        }
+        sum.re
+    }
+}
+
+/// A Hermitian operator Σ cₖ Pₖ as a real combination of Pauli strings on
+/// the same qubits, as Hamiltonians are given to variational algorithms.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PauliSum {
+    terms: Vec<(f64, PauliString)>,
+}
+
+impl PauliSum {
+    pub fn new() -> Self {
+        PauliSum::default()
+    }
+
+    /// Adds `coefficient` times `pauli`, which must have phase ±1 so that
+    /// the sum stays Hermitian.
+    pub fn add(&mut self, coefficient: f64, pauli: PauliString) -> &mut Self {
+        assert!(pauli.phase.is_multiple_of(2), "{pauli} is not Hermitian");
+        if let Some((_, first)) = self.terms.first() {
+            assert_eq!(
+                first.num_qubits(),
+                pauli.num_qubits(),
+                "Pauli strings on different qubit counts"
+            );
+        }
+        self.terms.push((coefficient, pauli));
+        self
+    }
+
+    pub fn terms(&self) -> &[(f64, PauliString)] {
+        &self.terms
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.terms.first().map_or(0, |(_, p)| p.num_qubits())
+    }
+
+    /// The expectation value ⟨ψ|H|ψ⟩ in the state with the given dense
+    /// amplitudes.
+    pub fn expectation(&self, amplitudes: &[Complex<f64>]) -> f64 {
+        self.terms
+            .iter()
+            .map(|(c, p)| c * p.expectation(amplitudes))
+            .sum()
+    }
+
+    /// The expectation value estimated from measurement outcomes, bit q of
+    /// each outcome read from qubit q, for a sum of Z and I only. Panics on
+    /// any X or Y, which a computational-basis measurement does not see.
+    pub fn expectation_from_counts(&self, counts: &Counts) -> f64 {
+        let shots = counts.shots();
+        assert!(shots > 0, "no shots to estimate from");
+        self.terms
+            .iter()
+            .map(|(c, p)| {
+                let mut z_mask = 0u64;
+                for (q, &pauli) in p.paulis.iter().enumerate() {
+                    match pauli {
+                        Pauli::Z => z_mask |= 1 << q,
+                        Pauli::I => {}
+                        _ => panic!("{p} is not diagonal in the computational basis"),
+                    }
+                }
+                let sign = if p.phase == 2 { -1.0 } else { 1.0 };
+                let total: f64 = counts
+                    .iter()
+                    .map(|(outcome, n)| {
+                        let parity = (outcome & z_mask).count_ones() % 2;
+                        n as f64 * if parity == 0 { 1.0 } else { -1.0 }
+                    })
+                    .sum();
+                c * sign * total / shots as f64
+            })
+            .sum()
+    }
+
+    pub fn to_operator(&self) -> Operator<Complex<f64>> {
+        let n = self.num_qubits() as u32;
+        let mut ones = vec![];
+        for (c, p) in &self.terms {
+            ones.extend(p.to_operator().ones.into_iter().map(|kb| KetBra {
+                scalar: kb.scalar * Complex::new(*c, 0.0),
+                ..kb
+            }));
+        }
+        if ones.is_empty() {
+            return Operator::identity(n) * Complex::new(0.0, 0.0);
+        }
+        Operator {
+            scalar: Complex::new(1.0, 0.0),
+            ones,
+        }
+    }
+}
+
+impl std::fmt::Display for PauliSum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (c, p)) in self.terms.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{c} {p}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for PauliString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", ["+", "+i", "-", "-i"][self.phase as usize])?;
+        for p in self.paulis.iter().rev() {
+            write!(f, "{:?}", p)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for PauliString {
+    type Err = String;
+
+    /// Parses an optional phase (`+`, `-`, `i`, `+i`, `-i`) followed by
+    /// the Paulis, qubit 0 last.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (phase, rest) = if let Some(rest) = s.strip_prefix("-i") {
+            (3, rest)
+        } else if let Some(rest) = s.strip_prefix("+i").or(s.strip_prefix('i')) {
+            (1, rest)
+        } else if let Some(rest) = s.strip_prefix('-') {
+            (2, rest)
+        } else {
+            (0, s.strip_prefix('+').unwrap_or(s))
+        };
+
+        let paulis = rest
+            .chars()
+            .rev()
+            .map(|c| match c {
+                'I' | '_' => Ok(Pauli::I),
+                'X' => Ok(Pauli::X),
+                'Y' => Ok(Pauli::Y),
+                'Z' => Ok(Pauli::Z),
+                c => Err(format!("invalid Pauli '{c}'")),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(PauliString::new(phase, paulis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pauli, PauliString, PauliSum};
+    use crate::ket::{Operator, State};
+    use crate::Complex as C;
+
+    fn p(s: &str) -> PauliString {
+        s.parse().unwrap()
+    }
+
+    /// The columns of an operator on at most two qubits, as dense vectors.
+    fn columns(op: &Operator<C<f64>>, n: u32) -> Vec<crate::matrix::Vector<C<f64>, 4>> {
+        (0..1 << n)
+            .map(|x| (op.clone() * State::basis(x, n)).to_vector::<4>())
+            .collect()
+    }
+
+    #[test]
+    fn single_qubit_products() {
+        assert_eq!(p("X") * p("Y"), p("iZ"));
+        assert_eq!(p("Y") * p("X"), p("-iZ"));
+        assert_eq!(p("Z") * p("Z"), p("I"));
+        assert_eq!(p("iX") * p("iX"), p("-I"));
+    }
+
+    #[test]
+    fn long_products_keep_the_phase_in_range() {
+        // each Y X is −iZ, and (−i)^100 = 1
+        let y = PauliString::new(0, vec![Pauli::Y; 100]);
+        let x = PauliString::new(0, vec![Pauli::X; 100]);
+        assert_eq!(&y * &x, PauliString::new(0, vec![Pauli::Z; 100]));
+        let x = PauliString::new(0, vec![Pauli::X; 101]);
+        let y = PauliString::new(3, vec![Pauli::Y; 101]);
+        assert_eq!(&y * &x, PauliString::new(2, vec![Pauli::Z; 101]));
+    }
+
+    #[test]
+    fn multi_qubit_product_and_display() {
+        let a = p("XZ");
+        let b = p("ZX");
+
+        assert_eq!(a.paulis(), &[Pauli::Z, Pauli::X]);
+        assert_eq!((&a * &b).to_string(), "+YY");
+        assert_eq!(a.weight(), 2);
+        assert_eq!(PauliString::single(3, 0, Pauli::Y).to_string(), "+IIY");
+    }
+
+    #[test]
+    fn commutation() {
+        assert!(p("XX").commutes_with(&p("ZZ")));
+        assert!(!p("XI").commutes_with(&p("ZI")));
+        assert!(p("XI").commutes_with(&p("IZ")));
+    }
+
+    #[test]
+    fn to_operator_matches_dirac() {
+        let y = Operator::parse("-i|0⟩⟨1| + i|1⟩⟨0|").unwrap();
+        assert_eq!(columns(&p("Y").to_operator(), 1), columns(&y, 1));
+    }
+
+    #[test]
+    fn product_matches_operator_product() {
+        let a = p("XZ");
+        let b = p("YY");
+        let lhs = (&a * &b).to_operator();
+        let rhs = a.to_operator() * b.to_operator();
+
+        assert_eq!(columns(&lhs, 2), columns(&rhs, 2));
+    }
+
+    #[test]
+    fn expectation_matches_the_operator() {
+        use crate::circuit::Circuit;
+        use crate::Conjugate;
+
+        let state = Circuit::random(
+            3,
+            2,
+            &mut <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(1),
+        )
+        .simulate();
+        let amplitudes = state.amplitudes(3);
+        for s in ["XYZ", "-YIY", "ZZI", "III"] {
+            let op = p(s).to_operator();
+            let expected: C<f64> = (op * state.clone())
+                .amplitudes(3)
+                .iter()
+                .zip(&amplitudes)
+                .map(|(b, a)| a.conj() * *b)
+                .fold(C::new(0.0, 0.0), |s, t| s + t);
+            assert!(
+                (p(s).expectation(&amplitudes) - expected.re).abs() < 1e-9,
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn sums() {
+        let mut h = PauliSum::new();
+        h.add(0.5, p("ZZ")).add(-1.5, p("XI"));
+        assert_eq!(h.num_qubits(), 2);
+        assert_eq!(h.to_string(), "0.5 +ZZ -1.5 +XI");
+
+        // |00⟩ has ⟨ZZ⟩ = 1 and ⟨XI⟩ = 0
+        let zero = [
+            C::new(1.0, 0.0),
+            C::new(0.0, 0.0),
+            C::new(0.0, 0.0),
+            C::new(0.0, 0.0),
+        ];
+        assert!((h.expectation(&zero) - 0.5).abs() < 1e-12);
+        assert_eq!(h.to_operator().trace(), C::new(0.0, 0.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "+iZ is not Hermitian")]
+    fn sums_are_hermitian() {
+        PauliSum::new().add(1.0, p("iZ"));
+    }
+
+    #[test]
+    fn expectation_from_z_counts() {
+        let mut counts = crate::circuit::Counts::new(2);
+        counts.insert(0b01, 5);
+        let mut h = PauliSum::new();
+        h.add(1.0, p("-ZI"));
+        assert_eq!(h.expectation_from_counts(&counts), -1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "+XZ is not diagonal in the computational basis")]
+    fn counts_only_see_z() {
+        let mut counts = crate::circuit::Counts::new(2);
+        counts.insert(0b01, 5);
+        let mut h = PauliSum::new();
+        h.add(1.0, p("XZ"));
+        h.expectation_from_counts(&counts);
+    }
+}
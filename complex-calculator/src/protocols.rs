@@ -0,0 +1,123 @@
+//! Textbook protocols as ready-made circuits, with checks that they do what
+//! they promise.
+
+use crate::circuit::{Circuit, Gate};
+use crate::density::DensityMatrix;
+
+/// Quantum teleportation of qubit 0 onto qubit 2. The input state is
+/// U3(θ, φ, λ)|0⟩, left as the parameters `theta`, `phi` and `lambda` to be
+/// bound with [`Circuit::bind_parameters`]. Qubits 1 and 2 are put in a Bell
+/// pair, qubits 0 and 1 are measured in the Bell basis into bits 0 and 1,
+/// and X and Z on qubit 2 conditioned on those bits undo the Pauli the
+/// measurement left it with.
+///
+/// ```
+/// use complex_calculator::protocols::{assert_teleports, teleportation};
+///
+/// assert_teleports(&teleportation(), 1.2, 0.4, -0.3);
+/// ```
+pub fn teleportation() -> Circuit {
+    let mut c = Circuit::new(3);
+    c.u3(0, "theta", "phi", "lambda")
+        .barrier(&[0, 1, 2])
+        .h(1)
+        .cnot(1, 2)
+        .barrier(&[0, 1, 2])
+        .cnot(0, 1)
+        .h(0)
+        .measure(0, 0)
+        .measure(1, 1)
+        .c_if(1, Gate::X, &[2])
+        .c_if(0, Gate::Z, &[2]);
+    c
+}
+
+/// Panics unless `circuit`, with `theta`, `phi` and `lambda` bound, leaves
+/// qubit 2 in U3(θ, φ, λ)|0⟩ whatever its measurements read, as
+/// [`teleportation`] should. Every branch is followed by
+/// [`Circuit::simulate_density`], and the Bloch vector of qubit 2 compared
+/// with the input's.
+pub fn assert_teleports(circuit: &Circuit, theta: f64, phi: f64, lambda: f64) {
+    let bound = circuit.bind_parameters(&[("theta", theta), ("phi", phi), ("lambda", lambda)]);
+    let others: Vec<usize> = (0..bound.num_qubits() as usize)
+        .filter(|&q| q != 2)
+        .collect();
+    let output = bound.simulate_density().trace_out(&others).bloch_vector();
+
+    let mut input = Circuit::new(1);
+    input.u3(0, theta, phi, lambda);
+    let input = DensityMatrix::from_state(&input.simulate()).bloch_vector();
+
+    let distance = ((output.0 - input.0).powi(2)
+        + (output.1 - input.1).powi(2)
+        + (output.2 - input.2).powi(2))
+    .sqrt();
+    assert!(
+        distance < 1e-9,
+        "qubit 2 ends at Bloch vector {output:?}, not the input's {input:?}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::{assert_teleports, teleportation};
+    use crate::circuit::{Circuit, Gate, Instruction};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn teleports_arbitrary_states() {
+        let c = teleportation();
+        assert_eq!(c.parameters(), ["lambda", "phi", "theta"]);
+        for (theta, phi, lambda) in [
+            (0.0, 0.0, 0.0),
+            (PI, 0.0, 0.0),
+            (1.2, 0.4, -0.3),
+            (2.5, -1.0, 3.0),
+        ] {
+            assert_teleports(&c, theta, phi, lambda);
+        }
+    }
+
+    #[test]
+    fn every_measurement_outcome_occurs() {
+        let c = teleportation().bind_parameters(&[("theta", 0.7), ("phi", 0.0), ("lambda", 0.0)]);
+        let counts = c.sample(400, &mut StdRng::seed_from_u64(2));
+        for outcome in 0..4 {
+            assert!(
+                counts.get(outcome) > 60,
+                "{outcome} read {} times",
+                counts.get(outcome)
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not the input's")]
+    fn corrections_are_needed() {
+        let mut c = Circuit::new(3);
+        for instruction in teleportation().instructions() {
+            match instruction {
+                Instruction::Conditional { gate: Gate::Z, .. } => {}
+                Instruction::Conditional {
+                    clbit,
+                    gate,
+                    qubits,
+                } => {
+                    c.c_if(*clbit, gate.clone(), qubits);
+                }
+                Instruction::Gate { gate, qubits } => {
+                    c.append(gate.clone(), qubits);
+                }
+                Instruction::Measure { qubit, clbit } => {
+                    c.measure(*qubit, *clbit);
+                }
+                _ => {}
+            }
+        }
+        assert_teleports(&c, PI / 2.0, 0.0, 0.0);
+    }
+}
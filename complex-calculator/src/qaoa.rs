@@ -0,0 +1,146 @@
+//! The quantum approximate optimisation algorithm for MaxCut: alternating
+//! layers of the cost of a cut and of a mixer, their angles tuned classically
+//! so that sampled cuts come out large.
+
+use crate::circuit::{Circuit, Counts};
+use crate::pauli::{Pauli, PauliString, PauliSum};
+
+/// The number of vertices of `graph`, one more than the largest vertex on an
+/// edge.
+fn vertices(graph: &[(usize, usize)]) -> usize {
+    graph.iter().map(|&(a, b)| a.max(b) + 1).max().unwrap_or(0)
+}
+
+/// The depth-`p` QAOA circuit for MaxCut on `graph`, given by its edges, with
+/// a qubit per vertex measured into the bit of the same number. From the
+/// uniform superposition, layer k applies exp(−iγₖ ZᵢZⱼ/2) for each edge
+/// (i, j), which is a CNOT, an Rz(γₖ) and a CNOT, then Rx(βₖ) on every
+/// qubit. The angles are the parameters `gamma0`, `beta0`, `gamma1`, and
+/// so on.
+///
+/// ```
+/// use std::f64::consts::FRAC_PI_4;
+///
+/// use complex_calculator::qaoa;
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+///
+/// // the best angles at depth 1 on a square cut 3 of its 4 edges on average,
+/// // against 2 for a random cut
+/// let square = [(0, 1), (1, 2), (2, 3), (3, 0)];
+/// let c = qaoa::maxcut(&square, 1).bind_parameters(&[("gamma0", FRAC_PI_4), ("beta0", -FRAC_PI_4)]);
+/// let counts = c.sample(500, &mut StdRng::seed_from_u64(1));
+/// assert!((qaoa::expected_cut(&square, &counts) - 3.0).abs() < 0.2);
+/// ```
+pub fn maxcut(graph: &[(usize, usize)], p: usize) -> Circuit {
+    let n = vertices(graph);
+    let mut c = Circuit::new(n as u32);
+    for q in 0..n {
+        c.h(q);
+    }
+    for layer in 0..p {
+        let gamma = format!("gamma{layer}");
+        let beta = format!("beta{layer}");
+        for &(a, b) in graph {
+            c.cnot(a, b).rz(b, gamma.as_str()).cnot(a, b);
+        }
+        for q in 0..n {
+            c.rx(q, beta.as_str());
+        }
+    }
+    for q in 0..n {
+        c.measure(q, q);
+    }
+    c
+}
+
+/// The cut size Σ (I − ZᵢZⱼ)/2 over the edges of `graph`, whose expectation
+/// is the mean number of edges cut.
+pub fn maxcut_hamiltonian(graph: &[(usize, usize)]) -> PauliSum {
+    let n = vertices(graph);
+    let mut h = PauliSum::new();
+    for &(a, b) in graph {
+        let mut zz = PauliString::single(n, a, Pauli::Z);
+        zz = zz * PauliString::single(n, b, Pauli::Z);
+        h.add(0.5, PauliString::identity(n)).add(-0.5, zz);
+    }
+    h
+}
+
+/// The number of edges of `graph` between the vertices whose bit of
+/// `assignment` is 0 and those whose bit is 1.
+pub fn cut_value(graph: &[(usize, usize)], assignment: u64) -> usize {
+    graph
+        .iter()
+        .filter(|&&(a, b)| (assignment >> a ^ assignment >> b) & 1 == 1)
+        .count()
+}
+
+/// The mean number of edges cut by the sampled assignments, the
+/// expectation of [`maxcut_hamiltonian`] estimated from `counts`.
+pub fn expected_cut(graph: &[(usize, usize)], counts: &Counts) -> f64 {
+    maxcut_hamiltonian(graph).expectation_from_counts(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cut_value, expected_cut, maxcut, maxcut_hamiltonian};
+    use crate::circuit::{Circuit, Counts};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const TRIANGLE: [(usize, usize); 3] = [(0, 1), (1, 2), (0, 2)];
+
+    #[test]
+    fn cut_values() {
+        assert_eq!(cut_value(&TRIANGLE, 0b000), 0);
+        assert_eq!(cut_value(&TRIANGLE, 0b001), 2);
+        assert_eq!(cut_value(&TRIANGLE, 0b110), 2);
+
+        let mut counts = Counts::new(3);
+        counts.insert(0b000, 1);
+        counts.insert(0b011, 3);
+        assert!((expected_cut(&TRIANGLE, &counts) - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn hamiltonian_counts_cut_edges() {
+        let h = maxcut_hamiltonian(&TRIANGLE);
+        for x in 0..8u64 {
+            let mut basis = Circuit::new(3);
+            for q in (0..3).filter(|q| x >> q & 1 == 1) {
+                basis.x(q);
+            }
+            let cut = h.expectation(&basis.simulate().amplitudes(3));
+            assert!((cut - cut_value(&TRIANGLE, x) as f64).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn layers() {
+        let c = maxcut(&TRIANGLE, 2);
+        assert_eq!(c.parameters(), ["beta0", "beta1", "gamma0", "gamma1"]);
+        assert_eq!(c.gate_counts()["cx"], 12);
+        assert_eq!(c.gate_counts()["rx"], 6);
+        assert_eq!(c.num_clbits(), 3);
+    }
+
+    #[test]
+    fn tuned_angles_beat_random_cuts() {
+        // a coarse grid over p = 1 on a square, whose maximum cut is 4
+        let square = [(0, 1), (1, 2), (2, 3), (3, 0)];
+        let c = maxcut(&square, 1);
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut best = 0.0f64;
+        for i in 0..8 {
+            for j in 0..8 {
+                let (gamma, beta) = (i as f64 * 0.4, j as f64 * 0.4);
+                let bound = c.bind_parameters(&[("gamma0", gamma), ("beta0", beta)]);
+                best = best.max(expected_cut(&square, &bound.sample(100, &mut rng)));
+            }
+        }
+        // p = 1 reaches 3 on average on the square, against 2 at random
+        assert!(best > 2.7, "{best}");
+    }
+}
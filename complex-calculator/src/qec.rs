@@ -0,0 +1,675 @@
+//! Quantum error correction with stabilizer codes protecting one logical
+//! qubit. A [`Code`] is given by an encoding circuit and the
+//! [`StabilizerGroup`] of the codespace, from which it derives the rest: a circuit
+//! measuring the generators onto ancillas, a lookup table from syndromes to
+//! the single-qubit Paulis that cause them, and a circuit undoing them.
+//!
+//! Every circuit uses the same layout: the n data qubits first, with the
+//! logical qubit entering and leaving on qubit 0, then one ancilla per
+//! generator, generator i measured into classical bit i.
+//!
+//! The corrections are multi-controlled X gates on all the ancillas, which
+//! [`Circuit::run_sparse`] applies as permutations of the basis states. On
+//! the larger codes it runs a round far faster than [`Circuit::run`], which
+//! multiplies in their dense matrices.
+
+mod decoder;
+mod experiment;
+mod stabilizer;
+
+pub use decoder::{LookupTable, MajorityVote};
+pub use experiment::{LogicalErrorRate, PauliChannel, Trial};
+pub use stabilizer::StabilizerGroup;
+
+use crate::circuit::{Circuit, Gate};
+use crate::pauli::{Pauli, PauliString};
+
+/// A stabilizer code encoding one qubit in [`Code::num_data`] qubits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code {
+    encoder: Circuit,
+    stabilizers: StabilizerGroup,
+}
+
+/// Turns a syndrome into the Pauli correction for the data qubits, or
+/// `None` when it gives up. Closures from syndromes to corrections are
+/// decoders, such as `|syndrome| code.correction(syndrome)`.
+pub trait Decoder {
+    fn decode(&self, syndrome: u64) -> Option<PauliString>;
+}
+
+impl<F: Fn(u64) -> Option<PauliString>> Decoder for F {
+    fn decode(&self, syndrome: u64) -> Option<PauliString> {
+        self(syndrome)
+    }
+}
+
+/// Applies `pauli` to `qubit` controlled on all of `controls`, changing its
+/// basis so that a multi-controlled X does the work, as Y = S X S† and
+/// Z = H X H.
+fn controlled_pauli(c: &mut Circuit, controls: &[usize], qubit: usize, pauli: Pauli) {
+    match pauli {
+        Pauli::I => {}
+        Pauli::X => {
+            c.mcx(controls, qubit);
+        }
+        Pauli::Y => {
+            c.sdg(qubit).mcx(controls, qubit).s(qubit);
+        }
+        Pauli::Z => {
+            c.h(qubit).mcx(controls, qubit).h(qubit);
+        }
+    }
+}
+
+/// `pauli` on each of `qubits` of `n`, and the identity elsewhere.
+fn on(n: usize, pauli: Pauli, qubits: &[usize]) -> PauliString {
+    let mut paulis = vec![Pauli::I; n];
+    for &q in qubits {
+        paulis[q] = pauli;
+    }
+    PauliString::new(0, paulis)
+}
+
+impl Code {
+    /// The code whose codespace `encoder` maps the states of qubit 0 into,
+    /// with the other qubits starting at |0⟩, and which `stabilizers`
+    /// generate, as for [`StabilizerGroup::new`].
+    pub fn new(encoder: Circuit, stabilizers: Vec<PauliString>) -> Self {
+        let stabilizers = StabilizerGroup::new(stabilizers);
+        assert_eq!(
+            stabilizers.num_qubits(),
+            encoder.num_qubits() as usize,
+            "stabilizers on {} qubits for an encoder on {}",
+            stabilizers.num_qubits(),
+            encoder.num_qubits()
+        );
+        Code {
+            encoder,
+            stabilizers,
+        }
+    }
+
+    /// The `[[3,1]]` bit-flip code, |0⟩ ↦ |000⟩ and |1⟩ ↦ |111⟩, stabilized by
+    /// Z₀Z₁ and Z₁Z₂. It corrects an X on any one qubit, and is blind to Z.
+    ///
+    /// ```
+    /// use complex_calculator::circuit::Circuit;
+    /// use complex_calculator::qec::Code;
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let code = Code::bit_flip();
+    /// let mut error = Circuit::new(3);
+    /// error.x(1);
+    /// let run = code.round_trip(&error).run(&mut StdRng::seed_from_u64(1));
+    /// // both parities flipped, and the logical |0⟩ recovered
+    /// assert_eq!(run.register(), 0b11);
+    /// assert!((run.state().amplitudes(5)[0b11 << 3].re - 1.0).abs() < 1e-9);
+    /// ```
+    pub fn bit_flip() -> Self {
+        Code::repetition(3)
+    }
+
+    /// The bit-flip repetition code on `n` qubits, |0⟩ ↦ |0…0⟩ and
+    /// |1⟩ ↦ |1…1⟩, stabilized by Z_i Z_(i+1) for i from 0 to n − 2. It
+    /// corrects up to ⌊(n − 1)/2⌋ X errors, as [`MajorityVote`] decodes.
+    pub fn repetition(n: usize) -> Self {
+        assert!(n >= 2, "a repetition code needs at least two qubits");
+        let mut encoder = Circuit::new(n as u32);
+        for q in 1..n {
+            encoder.cnot(0, q);
+        }
+        let stabilizers = (0..n - 1).map(|i| on(n, Pauli::Z, &[i, i + 1])).collect();
+        Code::new(encoder, stabilizers)
+    }
+
+    /// The `[[3,1]]` phase-flip code, the bit-flip code conjugated by
+    /// Hadamards: |0⟩ ↦ |+++⟩ and |1⟩ ↦ |−−−⟩, stabilized by X₀X₁ and
+    /// X₁X₂. As H Z H = X, it corrects a Z on any one qubit. An X on any one
+    /// qubit commutes with the stabilizers and acts on the codewords as a
+    /// logical Z, so it goes unseen.
+    pub fn phase_flip() -> Self {
+        let mut encoder = Circuit::new(3);
+        encoder.cnot(0, 1).cnot(0, 2).h(0).h(1).h(2);
+        Code::new(
+            encoder,
+            vec!["IXX".parse().unwrap(), "XXI".parse().unwrap()],
+        )
+    }
+
+    /// Shor's `[[9,1,3]]` code, the phase-flip code with each of its qubits
+    /// protected in turn by a bit-flip code: |0⟩ ↦ (|000⟩ + |111⟩)^⊗3/2√2
+    /// and |1⟩ ↦ (|000⟩ − |111⟩)^⊗3/2√2, in blocks of qubits 0–2, 3–5 and
+    /// 6–8. Six ZZ generators find an X within a block, and two X^⊗6
+    /// generators find which block a Z flipped the sign of. It corrects any
+    /// single-qubit error, as the syndrome measurement projects one onto I,
+    /// X, Y or Z.
+    pub fn shor() -> Self {
+        let mut encoder = Circuit::new(9);
+        encoder.cnot(0, 3).cnot(0, 6);
+        for block in [0, 3, 6] {
+            encoder
+                .h(block)
+                .cnot(block, block + 1)
+                .cnot(block, block + 2);
+        }
+        let mut stabilizers = vec![];
+        for block in [0, 3, 6] {
+            stabilizers.push(on(9, Pauli::Z, &[block, block + 1]));
+            stabilizers.push(on(9, Pauli::Z, &[block + 1, block + 2]));
+        }
+        stabilizers.push(on(9, Pauli::X, &[0, 1, 2, 3, 4, 5]));
+        stabilizers.push(on(9, Pauli::X, &[3, 4, 5, 6, 7, 8]));
+        Code::new(encoder, stabilizers)
+    }
+
+    /// Steane's `[[7,1,3]]` code, the CSS code of the `[7,4]` Hamming code:
+    /// qubit q stands for position q + 1 of the Hamming code, and the
+    /// generators put Z, then X, on the positions with bit r set, for r = 0
+    /// to 2. The Z generators read the position of an X error in binary
+    /// into syndrome bits 0 to 2, and the X generators that of a Z error
+    /// into bits 3 to 5, which [`steane_correction`] decodes.
+    ///
+    /// X^⊗7 and Z^⊗7 are its logical X and Z, and it has transversal gates
+    /// beyond the Paulis: H^⊗7 is a logical H, S†^⊗7 a logical S, and
+    /// CNOT^⊗7 between two blocks a logical CNOT, as [`Code::transversal`]
+    /// and [`Code::transversal_cnot`] build.
+    pub fn steane() -> Self {
+        let rows: Vec<Vec<usize>> = (0..3)
+            .map(|r| (0..7).filter(|q| (q + 1) >> r & 1 == 1).collect())
+            .collect();
+        // the input spreads as X₀X₁X₂ = X^⊗7 times the third X generator,
+        // and each X generator of the group below is imposed from a pivot
+        // qubit, 4, 5 or 6, that the others leave alone
+        let mut encoder = Circuit::new(7);
+        encoder.cnot(0, 1).cnot(0, 2);
+        for support in [[4, 1, 2, 3], [5, 0, 2, 3], [6, 0, 1, 3]] {
+            encoder.h(support[0]);
+            for &q in &support[1..] {
+                encoder.cnot(support[0], q);
+            }
+        }
+        let mut stabilizers: Vec<PauliString> =
+            rows.iter().map(|row| on(7, Pauli::Z, row)).collect();
+        stabilizers.extend(rows.iter().map(|row| on(7, Pauli::X, row)));
+        Code::new(encoder, stabilizers)
+    }
+
+    pub fn num_data(&self) -> usize {
+        self.encoder.num_qubits() as usize
+    }
+
+    /// One ancilla, and one syndrome bit, per stabilizer generator.
+    pub fn num_ancillas(&self) -> usize {
+        self.stabilizers.generators().len()
+    }
+
+    pub fn stabilizers(&self) -> &StabilizerGroup {
+        &self.stabilizers
+    }
+
+    /// The encoding circuit on the data qubits.
+    pub fn encoder(&self) -> &Circuit {
+        &self.encoder
+    }
+
+    /// The inverse of the encoder, returning the logical state to qubit 0.
+    pub fn decoder(&self) -> Circuit {
+        self.encoder.inverse()
+    }
+
+    /// The syndrome of `error` on the data qubits, as from
+    /// [`StabilizerGroup::syndrome`].
+    pub fn syndrome(&self, error: &PauliString) -> u64 {
+        self.stabilizers.syndrome(error)
+    }
+
+    /// The correction for `syndrome`: the identity for 0, else the first
+    /// single-qubit Pauli with that syndrome, trying X, Z and then Y on
+    /// each qubit in turn. `None` when no single-qubit error explains it.
+    pub fn correction(&self, syndrome: u64) -> Option<PauliString> {
+        let n = self.num_data();
+        if syndrome == 0 {
+            return Some(PauliString::identity(n));
+        }
+        (0..n)
+            .flat_map(|q| [Pauli::X, Pauli::Z, Pauli::Y].map(|p| PauliString::single(n, q, p)))
+            .find(|error| self.syndrome(error) == syndrome)
+    }
+
+    /// Measures the generators onto the ancillas, each by the Hadamard test:
+    /// the ancilla put in |+⟩ controls the generator's Paulis, and reads 1
+    /// in the X basis exactly when the data is in its −1 eigenspace. The
+    /// syndrome lands in the classical register.
+    pub fn syndrome_circuit(&self) -> Circuit {
+        let n = self.num_data();
+        let mut c = Circuit::new((n + self.num_ancillas()) as u32);
+        for (i, s) in self.stabilizers.generators().iter().enumerate() {
+            let ancilla = n + i;
+            c.h(ancilla);
+            for (q, &p) in s.paulis().iter().enumerate() {
+                controlled_pauli(&mut c, &[ancilla], q, p);
+            }
+            c.h(ancilla).measure(ancilla, i);
+        }
+        c
+    }
+
+    /// Applies [`Code::correction`] of the syndrome held by the ancillas,
+    /// controlled on them, to the data. Run after
+    /// [`Code::syndrome_circuit`], the ancillas are classical and this is
+    /// the feed-forward of the lookup table.
+    pub fn correction_circuit(&self) -> Circuit {
+        self.correction_circuit_with(&|syndrome| self.correction(syndrome))
+    }
+
+    /// [`Code::correction_circuit`] with the corrections of `decoder`, for
+    /// every syndrome it does not give up on.
+    pub fn correction_circuit_with(&self, decoder: &impl Decoder) -> Circuit {
+        let (n, k) = (self.num_data(), self.num_ancillas());
+        let ancillas: Vec<usize> = (n..n + k).collect();
+        let mut c = Circuit::new((n + k) as u32);
+        for syndrome in 1..1u64 << k {
+            let Some(correction) = decoder.decode(syndrome) else {
+                continue;
+            };
+            let unset: Vec<usize> = (0..k).filter(|i| syndrome >> i & 1 == 0).collect();
+            for &i in &unset {
+                c.x(n + i);
+            }
+            for (q, &p) in correction.paulis().iter().enumerate() {
+                controlled_pauli(&mut c, &ancillas, q, p);
+            }
+            for &i in &unset {
+                c.x(n + i);
+            }
+        }
+        c
+    }
+
+    /// `gate`, on one qubit, applied to every data qubit of a block.
+    pub fn transversal(&self, gate: Gate) -> Circuit {
+        let mut c = Circuit::new(self.num_data() as u32);
+        for q in 0..self.num_data() {
+            c.append(gate.clone(), &[q]);
+        }
+        c
+    }
+
+    /// CNOTs from each data qubit of a block on the first n qubits to the
+    /// same qubit of a block on the next n. On a CSS code such as
+    /// [`Code::steane`], this is a logical CNOT.
+    pub fn transversal_cnot(&self) -> Circuit {
+        let n = self.num_data();
+        let mut c = Circuit::new(2 * n as u32);
+        for q in 0..n {
+            c.cnot(q, n + q);
+        }
+        c
+    }
+
+    /// A full round on the data qubits and ancillas: encode qubit 0, apply
+    /// `error` to the data, extract the syndrome, correct and decode.
+    pub fn round_trip(&self, error: &Circuit) -> Circuit {
+        let n = self.num_data();
+        let data: Vec<usize> = (0..n).collect();
+        let all: Vec<usize> = (0..n + self.num_ancillas()).collect();
+        let mut c = Circuit::new(all.len() as u32);
+        c.compose(&self.encoder, &data)
+            .compose(error, &data)
+            .compose(&self.syndrome_circuit(), &all)
+            .compose(&self.correction_circuit(), &all)
+            .compose(&self.decoder(), &data);
+        c
+    }
+}
+
+/// The correction of [`Code::steane`] for `syndrome` by Hamming decoding:
+/// bits 0 to 2 give the position of an X error and bits 3 to 5 that of a Z
+/// error, 0 meaning none. X and Z errors are corrected independently, so
+/// unlike [`Code::correction`] this also undoes an X and a Z on different
+/// qubits.
+pub fn steane_correction(syndrome: u64) -> PauliString {
+    let mut paulis = vec![Pauli::I; 7];
+    let (x, z) = ((syndrome & 7) as usize, (syndrome >> 3 & 7) as usize);
+    if x > 0 {
+        paulis[x - 1] = Pauli::X;
+    }
+    if z > 0 {
+        paulis[z - 1] = if z == x { Pauli::Y } else { Pauli::Z };
+    }
+    PauliString::new(0, paulis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{steane_correction, Code};
+    use crate::circuit::{Circuit, Gate};
+    use crate::pauli::{Pauli, PauliString};
+    use crate::Complex as C;
+
+    use std::f64::consts::PI;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const EPSILON: f64 = 0.0000001;
+
+    fn pauli_gate(pauli: Pauli) -> Gate {
+        match pauli {
+            Pauli::I => Gate::I,
+            Pauli::X => Gate::X,
+            Pauli::Y => Gate::Y,
+            Pauli::Z => Gate::Z,
+        }
+    }
+
+    /// Runs a round of `code` on U3(θ, φ, λ)|0⟩ with `error` between encoding
+    /// and correction, and returns the syndrome and whether qubit 0 came
+    /// back to the input with the other data qubits at |0⟩.
+    fn recovers(code: &Code, error: &Circuit, seed: u64) -> (u64, bool) {
+        recovers_input(code, (1.1, 0.4, -0.8), error, seed)
+    }
+
+    fn recovers_input(
+        code: &Code,
+        (theta, phi, lambda): (f64, f64, f64),
+        error: &Circuit,
+        seed: u64,
+    ) -> (u64, bool) {
+        let n = code.num_data();
+        let mut c = Circuit::new((n + code.num_ancillas()) as u32);
+        c.u3(0, theta, phi, lambda).compose(
+            &code.round_trip(error),
+            &(0..n + code.num_ancillas()).collect::<Vec<_>>(),
+        );
+        let run = c.run_sparse(&mut StdRng::seed_from_u64(seed));
+        let syndrome = run.register();
+
+        let mut input = Circuit::new(1);
+        input.u3(0, theta, phi, lambda);
+        let input = input.simulate().amplitudes(1);
+        let amplitudes = run.state().amplitudes(c.num_qubits());
+        let expected = |x: u64| (x as usize) | (syndrome as usize) << n;
+        let overlap = (0..2).fold(C::new(0.0, 0.0), |acc, x| {
+            acc + input[x].conjugate() * amplitudes[expected(x as u64)]
+        });
+        (syndrome, (overlap.modulus() - 1.0).abs() < EPSILON)
+    }
+
+    #[test]
+    fn bit_flip_encodes_repetition() {
+        let code = Code::bit_flip();
+        assert_eq!((code.num_data(), code.num_ancillas()), (3, 2));
+        let mut c = Circuit::new(3);
+        c.x(0).compose(code.encoder(), &[0, 1, 2]);
+        assert!((c.simulate().amplitudes(3)[0b111].re - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn syndromes_locate_bit_flips() {
+        let code = Code::bit_flip();
+        let x = |q| PauliString::single(3, q, Pauli::X);
+        assert_eq!(code.syndrome(&x(0)), 0b01);
+        assert_eq!(code.syndrome(&x(1)), 0b11);
+        assert_eq!(code.syndrome(&x(2)), 0b10);
+        assert_eq!(code.syndrome(&PauliString::single(3, 1, Pauli::Z)), 0);
+        for q in 0..3 {
+            assert_eq!(code.correction(code.syndrome(&x(q))), Some(x(q)));
+        }
+    }
+
+    #[test]
+    fn corrects_every_single_bit_flip() {
+        let code = Code::bit_flip();
+        assert_eq!(recovers(&code, &Circuit::new(3), 1), (0, true));
+        for q in 0..3 {
+            let mut error = Circuit::new(3);
+            error.x(q);
+            let (syndrome, recovered) = recovers(&code, &error, q as u64);
+            assert_eq!(
+                syndrome,
+                code.syndrome(&PauliString::single(3, q, Pauli::X))
+            );
+            assert!(recovered, "X on qubit {q}");
+        }
+    }
+
+    #[test]
+    fn corrects_partial_bit_flips() {
+        // Rx(θ) = cos(θ/2) I − i sin(θ/2) X is projected onto no error or an
+        // X by the syndrome measurement, both of which are corrected
+        let code = Code::bit_flip();
+        let mut error = Circuit::new(3);
+        error.rx(2, 0.9);
+        for seed in 0..10 {
+            assert!(recovers(&code, &error, seed).1);
+        }
+    }
+
+    #[test]
+    fn two_bit_flips_are_a_logical_error() {
+        let code = Code::bit_flip();
+        let mut error = Circuit::new(3);
+        error.x(0).x(1);
+        let (syndrome, recovered) = recovers(&code, &error, 2);
+        assert_eq!(syndrome, 0b10);
+        assert!(!recovered);
+    }
+
+    #[test]
+    fn corrects_every_single_phase_flip() {
+        let code = Code::phase_flip();
+        for q in 0..3 {
+            let z = PauliString::single(3, q, Pauli::Z);
+            assert_eq!(code.correction(code.syndrome(&z)), Some(z));
+            let mut error = Circuit::new(3);
+            error.z(q);
+            assert!(recovers(&code, &error, q as u64).1, "Z on qubit {q}");
+        }
+        // Rz projects onto no error or a Z as Rx does onto an X
+        let mut error = Circuit::new(3);
+        error.rz(1, 1.3);
+        for seed in 0..10 {
+            assert!(recovers(&code, &error, seed).1);
+        }
+    }
+
+    #[test]
+    fn each_repetition_code_fails_against_the_other_error() {
+        // the syndrome reads 0 and the error passes through as a logical one
+        for (code, pauli) in [(Code::phase_flip(), Pauli::X), (Code::bit_flip(), Pauli::Z)] {
+            for q in 0..3 {
+                assert_eq!(code.syndrome(&PauliString::single(3, q, pauli)), 0);
+                let mut error = Circuit::new(3);
+                error.append(pauli_gate(pauli), &[q]);
+                assert_eq!(recovers(&code, &error, 3), (0, false), "{pauli:?} on {q}");
+            }
+        }
+    }
+
+    #[test]
+    fn shor_code_stabilizes_its_codewords() {
+        let code = Code::shor();
+        assert_eq!((code.num_data(), code.num_ancillas()), (9, 8));
+        for x in 0..2 {
+            let mut c = Circuit::new(9);
+            if x == 1 {
+                c.x(0);
+            }
+            let amplitudes = c
+                .compose(code.encoder(), &(0..9).collect::<Vec<_>>())
+                .simulate()
+                .amplitudes(9);
+            for s in code.stabilizers().generators() {
+                assert!((s.expectation(&amplitudes) - 1.0).abs() < EPSILON, "{s}");
+            }
+        }
+    }
+
+    #[test]
+    fn shor_code_corrects_random_paulis() {
+        let code = Code::shor();
+        let mut rng = StdRng::seed_from_u64(9);
+        for seed in 0..12 {
+            let input = (
+                rng.random_range(0.0..PI),
+                rng.random_range(-PI..PI),
+                rng.random_range(-PI..PI),
+            );
+            let q = rng.random_range(0..9);
+            let pauli = [Pauli::X, Pauli::Y, Pauli::Z][rng.random_range(0..3)];
+            let mut error = Circuit::new(9);
+            error.append(pauli_gate(pauli), &[q]);
+            let (syndrome, recovered) = recovers_input(&code, input, &error, seed);
+            assert_eq!(syndrome, code.syndrome(&PauliString::single(9, q, pauli)));
+            assert!(recovered, "{pauli:?} on qubit {q}");
+        }
+    }
+
+    #[test]
+    fn shor_code_corrects_arbitrary_single_qubit_errors() {
+        let code = Code::shor();
+        let mut rng = StdRng::seed_from_u64(10);
+        for seed in 0..6 {
+            let q = rng.random_range(0..9);
+            let mut error = Circuit::new(9);
+            error.u3(
+                q,
+                rng.random_range(0.0..PI),
+                rng.random_range(-PI..PI),
+                rng.random_range(-PI..PI),
+            );
+            assert!(recovers(&code, &error, seed).1, "error on qubit {q}");
+        }
+    }
+
+    #[test]
+    fn shor_code_identifies_phase_flips_up_to_their_block() {
+        // Z₀, Z₁ and Z₂ act alike on the codewords, so one correction serves
+        let code = Code::shor();
+        let z = |q| PauliString::single(9, q, Pauli::Z);
+        assert_eq!(code.syndrome(&z(0)), code.syndrome(&z(2)));
+        assert_eq!(code.correction(code.syndrome(&z(2))), Some(z(0)));
+        assert!(code.stabilizers().contains(&(z(0) * z(2))));
+        let mut error = Circuit::new(9);
+        error.z(2);
+        assert!(recovers(&code, &error, 0).1);
+    }
+
+    /// U3(θ, φ, λ)|0⟩ on qubit 0 encoded, `logical` applied, decoded, and
+    /// compared with `expected` applied to the input.
+    fn assert_logical(code: &Code, logical: &Circuit, expected: &Circuit) {
+        let (theta, phi, lambda) = (0.9, -0.6, 1.7);
+        let n = code.num_data();
+        let blocks = logical.num_qubits() as usize / n;
+        let mut encoded = Circuit::new(logical.num_qubits());
+        let mut bare = Circuit::new(blocks as u32);
+        for b in 0..blocks {
+            let block: Vec<usize> = (b * n..(b + 1) * n).collect();
+            encoded
+                .u3(b * n, theta + b as f64, phi, lambda)
+                .compose(code.encoder(), &block);
+            bare.u3(b, theta + b as f64, phi, lambda);
+        }
+        encoded.compose(logical, &(0..blocks * n).collect::<Vec<_>>());
+        for b in 0..blocks {
+            encoded.compose(&code.decoder(), &(b * n..(b + 1) * n).collect::<Vec<_>>());
+        }
+        bare.compose(expected, &(0..blocks).collect::<Vec<_>>());
+
+        let amplitudes = encoded.simulate().amplitudes(encoded.num_qubits());
+        let expected = bare.simulate().amplitudes(blocks as u32);
+        for (x, a) in expected.iter().enumerate() {
+            let index = (0..blocks).fold(0, |i, b| i | (x >> b & 1) << (b * n));
+            assert!((amplitudes[index] - *a).modulus() < EPSILON, "|{x}⟩");
+        }
+    }
+
+    #[test]
+    fn steane_code_stabilizes_its_codewords() {
+        let code = Code::steane();
+        assert_eq!((code.num_data(), code.num_ancillas()), (7, 6));
+        for x in 0..2 {
+            let mut c = Circuit::new(7);
+            if x == 1 {
+                c.x(0);
+            }
+            let amplitudes = c
+                .compose(code.encoder(), &(0..7).collect::<Vec<_>>())
+                .simulate()
+                .amplitudes(7);
+            for s in code.stabilizers().generators() {
+                assert!((s.expectation(&amplitudes) - 1.0).abs() < EPSILON, "{s}");
+            }
+            // logical Z^⊗7 reads the encoded bit, undetected and outside
+            // the group
+            let z: PauliString = "ZZZZZZZ".parse().unwrap();
+            assert!(code.stabilizers().commutes_with(&z));
+            assert!(!code.stabilizers().contains(&z));
+            let sign = if x == 0 { 1.0 } else { -1.0 };
+            assert!((z.expectation(&amplitudes) - sign).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn hamming_decoding_agrees_with_the_lookup_table() {
+        let code = Code::steane();
+        for q in 0..7 {
+            for pauli in [Pauli::X, Pauli::Y, Pauli::Z] {
+                let error = PauliString::single(7, q, pauli);
+                let syndrome = code.syndrome(&error);
+                assert_eq!(steane_correction(syndrome), error);
+                assert_eq!(code.correction(syndrome), Some(error));
+            }
+        }
+        // an X and a Z on different qubits, beyond a single-qubit lookup
+        let error: PauliString = "IZIIIXI".parse().unwrap();
+        let syndrome = code.syndrome(&error);
+        assert_eq!(syndrome, 2 | 6 << 3);
+        assert_eq!(steane_correction(syndrome), error);
+        assert_eq!(code.correction(syndrome), None);
+    }
+
+    #[test]
+    fn steane_code_corrects_every_single_qubit_pauli() {
+        let code = Code::steane();
+        for q in 0..7 {
+            for pauli in [Pauli::X, Pauli::Y, Pauli::Z] {
+                let mut error = Circuit::new(7);
+                error.append(pauli_gate(pauli), &[q]);
+                let (syndrome, recovered) = recovers(&code, &error, q as u64);
+                assert_eq!(syndrome, code.syndrome(&PauliString::single(7, q, pauli)));
+                assert!(recovered, "{pauli:?} on qubit {q}");
+            }
+        }
+    }
+
+    #[test]
+    fn steane_code_has_transversal_clifford_gates() {
+        let code = Code::steane();
+        let single = |gate: Gate| {
+            let mut c = Circuit::new(1);
+            c.append(gate, &[0]);
+            c
+        };
+        assert_logical(&code, &code.transversal(Gate::X), &single(Gate::X));
+        assert_logical(&code, &code.transversal(Gate::Z), &single(Gate::Z));
+        assert_logical(&code, &code.transversal(Gate::H), &single(Gate::H));
+        assert_logical(&code, &code.transversal(Gate::Sdg), &single(Gate::S));
+
+        let mut cnot = Circuit::new(2);
+        cnot.cnot(0, 1);
+        assert_logical(&code, &code.transversal_cnot(), &cnot);
+    }
+
+    #[test]
+    #[should_panic(expected = "do not commute")]
+    fn rejects_anticommuting_stabilizers() {
+        Code::new(
+            Circuit::new(2),
+            vec!["XX".parse().unwrap(), "ZI".parse().unwrap()],
+        );
+    }
+}
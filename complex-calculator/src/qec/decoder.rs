@@ -0,0 +1,262 @@
+//! Decoders turning syndromes into corrections: a lookup table of the
+//! lowest-weight error behind each syndrome, built from the stabilizers of
+//! any small code, and majority voting for repetition codes.
+
+use std::collections::HashMap;
+
+use super::{Decoder, StabilizerGroup};
+use crate::pauli::{Pauli, PauliString};
+
+/// The Paulis tried on each qubit, in order.
+const PAULIS: [Pauli; 3] = [Pauli::X, Pauli::Z, Pauli::Y];
+
+/// The minimum-weight decoder by table: for each syndrome, the first error
+/// of lowest weight found to cause it, up to a maximum weight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupTable {
+    table: HashMap<u64, PauliString>,
+}
+
+/// Every choice of `k` of the qubits 0 to n − 1, in lexicographic order.
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![vec![]];
+    }
+    (k - 1..n)
+        .flat_map(|last| {
+            combinations(last, k - 1).into_iter().map(move |mut c| {
+                c.push(last);
+                c
+            })
+        })
+        .collect()
+}
+
+impl LookupTable {
+    /// The table for the code of `stabilizers`, from the errors of weight at
+    /// most `max_weight`: the qubits are chosen in lexicographic order and
+    /// X, Z and Y tried on each, the first qubit slowest, so that the single
+    /// errors come in the order of [`Code::correction`]. Sizes grow as
+    /// 3^w (n choose w), so this is for small codes.
+    ///
+    /// [`Code::correction`]: super::Code::correction
+    ///
+    /// ```
+    /// use complex_calculator::qec::{Code, Decoder, LookupTable};
+    ///
+    /// let code = Code::steane();
+    /// let table = LookupTable::new(code.stabilizers(), 1);
+    /// // all 64 syndromes are reached by weight 2, but 21 are by weight 1
+    /// assert_eq!(table.len(), 22);
+    /// assert_eq!(table.decode(0b011), Some("IIIIXII".parse().unwrap()));
+    /// ```
+    pub fn new(stabilizers: &StabilizerGroup, max_weight: usize) -> Self {
+        let n = stabilizers.num_qubits();
+        let mut table = HashMap::from([(0, PauliString::identity(n))]);
+        let reachable = 1usize
+            .checked_shl(stabilizers.generators().len() as u32)
+            .unwrap_or(usize::MAX);
+        for weight in 1..=max_weight.min(n) {
+            for qubits in combinations(n, weight) {
+                for choice in 0..3usize.pow(weight as u32) {
+                    let mut paulis = vec![Pauli::I; n];
+                    for (i, &q) in qubits.iter().enumerate() {
+                        paulis[q] = PAULIS[choice / 3usize.pow((weight - 1 - i) as u32) % 3];
+                    }
+                    let error = PauliString::new(0, paulis);
+                    table.entry(stabilizers.syndrome(&error)).or_insert(error);
+                }
+                if table.len() == reachable {
+                    return LookupTable { table };
+                }
+            }
+        }
+        LookupTable { table }
+    }
+
+    /// The number of syndromes in the table, 0 included.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+impl Decoder for LookupTable {
+    fn decode(&self, syndrome: u64) -> Option<PauliString> {
+        self.table.get(&syndrome).cloned()
+    }
+}
+
+/// Majority voting on a repetition code of `n` qubits stabilized by the
+/// neighbouring pairs P_i P_(i+1), as [`Code::repetition`] with P = Z.
+/// The syndrome fixes the flipped qubits up to flipping them all, and the
+/// vote picks the smaller of the two sets, correcting any ⌊(n − 1)/2⌋
+/// flips.
+///
+/// [`Code::repetition`]: super::Code::repetition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MajorityVote {
+    n: usize,
+    flip: Pauli,
+}
+
+impl MajorityVote {
+    /// The decoder of bit flips, X, for the code stabilized by Z_i Z_(i+1).
+    pub fn bit_flip(n: usize) -> Self {
+        MajorityVote { n, flip: Pauli::X }
+    }
+
+    /// The decoder of phase flips, Z, for the code stabilized by
+    /// X_i X_(i+1), such as [`Code::phase_flip`].
+    ///
+    /// [`Code::phase_flip`]: super::Code::phase_flip
+    pub fn phase_flip(n: usize) -> Self {
+        MajorityVote { n, flip: Pauli::Z }
+    }
+}
+
+impl Decoder for MajorityVote {
+    fn decode(&self, syndrome: u64) -> Option<PauliString> {
+        // qubit i + 1 differs from qubit i exactly when bit i is set
+        let mut flipped = vec![false; self.n];
+        for i in 1..self.n {
+            flipped[i] = flipped[i - 1] ^ (syndrome >> (i - 1) & 1 == 1);
+        }
+        let complement = 2 * flipped.iter().filter(|&&f| f).count() > self.n;
+        let paulis = flipped
+            .iter()
+            .map(|&f| if f != complement { self.flip } else { Pauli::I })
+            .collect();
+        Some(PauliString::new(0, paulis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{combinations, LookupTable, MajorityVote};
+    use crate::circuit::Circuit;
+    use crate::pauli::{Pauli, PauliString};
+    use crate::qec::{steane_correction, Code, Decoder, PauliChannel};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn combinations_in_order() {
+        assert_eq!(
+            combinations(4, 2),
+            [[0, 1], [0, 2], [1, 2], [0, 3], [1, 3], [2, 3]]
+        );
+        assert_eq!(combinations(3, 0), [Vec::<usize>::new()]);
+        assert_eq!(combinations(5, 3).len(), 10);
+    }
+
+    #[test]
+    fn single_errors_agree_with_the_code() {
+        for code in [Code::bit_flip(), Code::shor(), Code::steane()] {
+            let table = LookupTable::new(code.stabilizers(), 1);
+            for syndrome in 0..1 << code.num_ancillas() {
+                assert_eq!(table.decode(syndrome), code.correction(syndrome));
+            }
+        }
+    }
+
+    #[test]
+    fn weight_two_tables_reach_further() {
+        let code = Code::steane();
+        let table = LookupTable::new(code.stabilizers(), 2);
+        assert_eq!(table.len(), 64);
+        for syndrome in 0..64 {
+            let correction = table.decode(syndrome).unwrap();
+            assert_eq!(code.syndrome(&correction), syndrome);
+            assert!(correction.weight() <= 2);
+            assert!(correction.weight() <= steane_correction(syndrome).weight());
+        }
+    }
+
+    #[test]
+    fn majority_vote_corrects_minorities() {
+        let code = Code::repetition(5);
+        let vote = MajorityVote::bit_flip(5);
+        for flips in 0..32u64 {
+            let paulis = (0..5)
+                .map(|q| {
+                    if flips >> q & 1 == 1 {
+                        Pauli::X
+                    } else {
+                        Pauli::I
+                    }
+                })
+                .collect();
+            let error = PauliString::new(0, paulis);
+            let trial = code.trial_with(error, &vote);
+            assert_eq!(trial.logical_error, flips.count_ones() > 2, "{flips:05b}");
+        }
+    }
+
+    #[test]
+    fn majority_vote_matches_the_table_on_repetition_codes() {
+        let code = Code::repetition(7);
+        let (vote, table) = (
+            MajorityVote::bit_flip(7),
+            LookupTable::new(code.stabilizers(), 3),
+        );
+        for syndrome in 0..1 << 6 {
+            assert_eq!(
+                vote.decode(syndrome),
+                table.decode(syndrome),
+                "{syndrome:06b}"
+            );
+        }
+        let code = Code::phase_flip();
+        let vote = MajorityVote::phase_flip(3);
+        for syndrome in 0..4 {
+            assert_eq!(vote.decode(syndrome), code.correction(syndrome));
+        }
+    }
+
+    #[test]
+    fn decoders_drive_correction_circuits() {
+        // an X on qubit 1 and a Z on qubit 5 of |1⟩ encoded, which only
+        // Hamming decoding undoes
+        let code = Code::steane();
+        let all: Vec<usize> = (0..13).collect();
+        let mut c = Circuit::new(13);
+        c.x(0)
+            .compose(code.encoder(), &all[..7])
+            .x(1)
+            .z(5)
+            .compose(&code.syndrome_circuit(), &all)
+            .compose(
+                &code.correction_circuit_with(&|s| Some(steane_correction(s))),
+                &all,
+            )
+            .compose(&code.decoder(), &all[..7]);
+        let run = c.run_sparse(&mut StdRng::seed_from_u64(1));
+        assert_eq!(run.register(), 2 | 6 << 3);
+        let amplitude = run.state().amplitudes(13)[1 | (run.register() as usize) << 7];
+        assert!((amplitude.modulus() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn longer_repetition_codes_fail_less() {
+        let noise = PauliChannel::bit_flip(0.1);
+        let mut rng = StdRng::seed_from_u64(3);
+        let failures = |n: usize, rng: &mut StdRng| {
+            let code = Code::repetition(n);
+            (0..4000)
+                .filter(|_| {
+                    code.trial(&noise, &MajorityVote::bit_flip(n), rng)
+                        .logical_error
+                })
+                .count()
+        };
+        let (three, seven) = (failures(3, &mut rng), failures(7, &mut rng));
+        // 0.028 and 0.0027 of the rounds
+        assert!((80..150).contains(&three), "{three}");
+        assert!(seven < 30, "{seven}");
+    }
+}
@@ -0,0 +1,432 @@
+//! Memory experiments under Pauli noise: an encoded qubit suffers a random
+//! Pauli error on its data qubits, the syndrome is read, a [`Decoder`]
+//! picks a correction, and the round fails when the residual error acts on
+//! the logical qubit.
+//!
+//! The crate has no stabilizer simulator, but with perfect encoding and
+//! syndrome extraction none is needed: a Pauli error commutes through the
+//! round up to a sign, so it is tracked as a [`PauliString`], the Pauli
+//! frame, and its syndrome and effect read off the [`StabilizerGroup`].
+//! Sampling a frame costs a few bits per qubit rather than a state vector,
+//! and [`Code::round_trip`] runs the same round on the simulators for
+//! comparison.
+//!
+//! [`StabilizerGroup`]: super::StabilizerGroup
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{Code, Decoder};
+use crate::channel::Channel;
+use crate::circuit::Gate;
+use crate::ket::Operator;
+use crate::pauli::{Pauli, PauliString};
+use crate::Complex;
+
+/// Independent noise on every qubit: X, Y or Z with probabilities `px`, `py`
+/// and `pz`, and the identity otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PauliChannel {
+    px: f64,
+    py: f64,
+    pz: f64,
+}
+
+impl PauliChannel {
+    pub fn new(px: f64, py: f64, pz: f64) -> Self {
+        for p in [px, py, pz] {
+            assert!((0.0..=1.0).contains(&p), "probability {p} outside [0, 1]");
+        }
+        let total = px + py + pz;
+        assert!(
+            total <= 1.0 + 1e-12,
+            "error probabilities sum to {total} > 1"
+        );
+        PauliChannel { px, py, pz }
+    }
+
+    /// X with probability `p`, as [`Channel::bit_flip`].
+    pub fn bit_flip(p: f64) -> Self {
+        Self::new(p, 0.0, 0.0)
+    }
+
+    /// Z with probability `p`, as [`Channel::phase_flip`].
+    pub fn phase_flip(p: f64) -> Self {
+        Self::new(0.0, 0.0, p)
+    }
+
+    /// X, Y and Z each with probability p/4, as [`Channel::depolarizing`],
+    /// which replaces the qubit by the maximally mixed state with
+    /// probability `p`.
+    pub fn depolarizing(p: f64) -> Self {
+        Self::new(p / 4.0, p / 4.0, p / 4.0)
+    }
+
+    /// The probability of any error on a qubit.
+    pub fn error_probability(&self) -> f64 {
+        self.px + self.py + self.pz
+    }
+
+    /// The channel on one qubit, for the density-matrix and trajectory
+    /// simulators.
+    pub fn to_channel(&self) -> Channel {
+        let scaled = |gate: Gate, p: f64| gate.operator() * Complex::new(p.sqrt(), 0.0);
+        let identity = Operator::identity(1)
+            * Complex::new((1.0 - self.error_probability()).max(0.0).sqrt(), 0.0);
+        Channel::new(vec![
+            identity,
+            scaled(Gate::X, self.px),
+            scaled(Gate::Y, self.py),
+            scaled(Gate::Z, self.pz),
+        ])
+    }
+
+    /// An error on `n` qubits, drawn independently on each.
+    pub fn sample<R: Rng + ?Sized>(&self, n: usize, rng: &mut R) -> PauliString {
+        let paulis = (0..n)
+            .map(|_| {
+                let r = rng.random::<f64>();
+                if r < self.px {
+                    Pauli::X
+                } else if r < self.px + self.py {
+                    Pauli::Y
+                } else if r < self.error_probability() {
+                    Pauli::Z
+                } else {
+                    Pauli::I
+                }
+            })
+            .collect();
+        PauliString::new(0, paulis)
+    }
+}
+
+/// One round of a memory experiment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trial {
+    /// The error on the data qubits.
+    pub error: PauliString,
+    /// Its syndrome, as [`Code::syndrome`].
+    pub syndrome: u64,
+    /// What the decoder applied, if it did not give up.
+    pub correction: Option<PauliString>,
+    /// Whether the logical qubit was left changed, or out of the codespace.
+    pub logical_error: bool,
+}
+
+/// The rounds run together from one seeded generator, so that the results
+/// are the same however the chunks are shared out between threads.
+const CHUNK: usize = 1024;
+
+/// The logical error rate measured at one physical error probability.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogicalErrorRate {
+    /// The probability the noise model was built from.
+    pub physical: f64,
+    pub shots: usize,
+    /// The rounds ending in a logical error.
+    pub failures: usize,
+    /// failures / shots.
+    pub rate: f64,
+    /// The Wilson score interval around the rate at about 95% confidence,
+    /// which unlike the normal approximation stays inside [0, 1] and is
+    /// not empty when there are no failures.
+    pub interval: (f64, f64),
+}
+
+/// The Wilson score interval for `failures` out of `shots`, at z = 1.96.
+fn wilson_interval(failures: usize, shots: usize) -> (f64, f64) {
+    const Z: f64 = 1.96;
+    let (n, p) = (shots as f64, failures as f64 / shots as f64);
+    let denominator = 1.0 + Z * Z / n;
+    let centre = (p + Z * Z / (2.0 * n)) / denominator;
+    let half_width = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt() / denominator;
+    (
+        (centre - half_width).max(0.0),
+        (centre + half_width).min(1.0),
+    )
+}
+
+impl Code {
+    /// Whether correcting `error` by `correction` leaves the codeword as it
+    /// was: their product, up to phase, is in the stabilizer group.
+    pub fn corrects(&self, error: &PauliString, correction: &PauliString) -> bool {
+        let residual = correction * error;
+        let residual = PauliString::new(0, residual.paulis().to_vec());
+        let negated = PauliString::new(2, residual.paulis().to_vec());
+        self.stabilizers().contains(&residual) || self.stabilizers().contains(&negated)
+    }
+
+    /// Runs the round for `error`: its syndrome is decoded, and the round
+    /// fails unless the decoder returns a correction that
+    /// [`Code::corrects`] the error.
+    pub fn trial_with(&self, error: PauliString, decoder: &impl Decoder) -> Trial {
+        let syndrome = self.syndrome(&error);
+        let correction = decoder.decode(syndrome);
+        let logical_error = !correction
+            .as_ref()
+            .is_some_and(|correction| self.corrects(&error, correction));
+        Trial {
+            error,
+            syndrome,
+            correction,
+            logical_error,
+        }
+    }
+
+    /// Runs a round with an error drawn from `noise` on every data qubit.
+    ///
+    /// ```
+    /// use complex_calculator::qec::{Code, PauliChannel};
+    /// use rand::rngs::StdRng;
+    /// use rand::SeedableRng;
+    ///
+    /// let code = Code::bit_flip();
+    /// let decoder = |syndrome| code.correction(syndrome);
+    /// let mut rng = StdRng::seed_from_u64(1);
+    /// let failures = (0..1000)
+    ///     .filter(|_| code.trial(&PauliChannel::bit_flip(0.1), &decoder, &mut rng).logical_error)
+    ///     .count();
+    /// // two or three flips out of three: 3p² − 2p³ = 0.028
+    /// assert!((10..50).contains(&failures));
+    /// ```
+    pub fn trial<R: Rng + ?Sized>(
+        &self,
+        noise: &PauliChannel,
+        decoder: &impl Decoder,
+        rng: &mut R,
+    ) -> Trial {
+        self.trial_with(noise.sample(self.num_data(), rng), decoder)
+    }
+
+    /// The logical error rate of `shots` rounds at each of `probabilities`,
+    /// with the noise built from it by `noise` and corrected by `decoder`,
+    /// as for plotting against the physical rate to find a threshold.
+    ///
+    /// The rounds run in chunks, each from a generator seeded by `seed`, the
+    /// probability and the chunk, so the results depend on nothing else.
+    /// With the `parallel` feature the chunks are shared out between
+    /// threads.
+    ///
+    /// ```
+    /// use complex_calculator::qec::{Code, MajorityVote, PauliChannel};
+    ///
+    /// let code = Code::repetition(5);
+    /// let rates = code.logical_error_rates(
+    ///     PauliChannel::bit_flip,
+    ///     &MajorityVote::bit_flip(5),
+    ///     &[0.01, 0.1, 0.3],
+    ///     5000,
+    ///     1,
+    /// );
+    /// // below the threshold of 1/2, encoding helps
+    /// for rate in &rates {
+    ///     assert!(rate.interval.1 < rate.physical);
+    /// }
+    /// assert!(rates[0].rate < rates[1].rate && rates[1].rate < rates[2].rate);
+    /// ```
+    pub fn logical_error_rates<D: Decoder + Sync>(
+        &self,
+        noise: impl Fn(f64) -> PauliChannel + Sync,
+        decoder: &D,
+        probabilities: &[f64],
+        shots: usize,
+        seed: u64,
+    ) -> Vec<LogicalErrorRate> {
+        assert!(shots > 0, "an error rate needs at least one shot");
+        let chunks = shots.div_ceil(CHUNK);
+        let jobs: Vec<(usize, usize)> = (0..probabilities.len())
+            .flat_map(|point| (0..chunks).map(move |chunk| (point, chunk)))
+            .collect();
+        let run = |&(point, chunk): &(usize, usize)| {
+            let channel = noise(probabilities[point]);
+            let mut rng =
+                StdRng::seed_from_u64(seed.wrapping_add((point as u64) << 32 | chunk as u64));
+            let rounds = CHUNK.min(shots - chunk * CHUNK);
+            (0..rounds)
+                .filter(|_| self.trial(&channel, decoder, &mut rng).logical_error)
+                .count()
+        };
+
+        #[cfg(feature = "parallel")]
+        let failures: Vec<usize> = {
+            use rayon::prelude::*;
+            jobs.par_iter().map(run).collect()
+        };
+
+        #[cfg(not(feature = "parallel"))]
+        let failures: Vec<usize> = jobs.iter().map(run).collect();
+
+        probabilities
+            .iter()
+            .zip(failures.chunks(chunks))
+            .map(|(&physical, counts)| {
+                let failures = counts.iter().sum();
+                LogicalErrorRate {
+                    physical,
+                    shots,
+                    failures,
+                    rate: failures as f64 / shots as f64,
+                    interval: wilson_interval(failures, shots),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{wilson_interval, PauliChannel};
+    use crate::channel::Channel;
+    use crate::circuit::{Circuit, Gate};
+    use crate::pauli::{Pauli, PauliString};
+    use crate::qec::{steane_correction, Code};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn error_circuit(error: &PauliString) -> Circuit {
+        let mut c = Circuit::new(error.num_qubits() as u32);
+        for (q, p) in error.paulis().iter().enumerate() {
+            let gate = match p {
+                Pauli::I => continue,
+                Pauli::X => Gate::X,
+                Pauli::Y => Gate::Y,
+                Pauli::Z => Gate::Z,
+            };
+            c.append(gate, &[q]);
+        }
+        c
+    }
+
+    #[test]
+    fn samples_follow_the_probabilities() {
+        let noise = PauliChannel::new(0.1, 0.2, 0.3);
+        let error = noise.sample(20000, &mut StdRng::seed_from_u64(1));
+        for (pauli, p) in [(Pauli::X, 0.1), (Pauli::Y, 0.2), (Pauli::Z, 0.3)] {
+            let frequency = error.paulis().iter().filter(|&&q| q == pauli).count() as f64 / 20000.0;
+            assert!((frequency - p).abs() < 0.01, "{pauli:?}: {frequency}");
+        }
+    }
+
+    #[test]
+    fn channels_match_the_noise_models() {
+        assert_eq!(
+            PauliChannel::bit_flip(0.2).to_channel().kraus()[1],
+            Channel::bit_flip(0.2).kraus()[1]
+        );
+        let ours = PauliChannel::depolarizing(0.3).to_channel();
+        let theirs = Channel::depolarizing(0.3);
+        for (a, b) in ours.kraus().iter().zip(theirs.kraus()) {
+            for ((x, ..), (y, ..)) in a.terms().zip(b.terms()) {
+                assert!((x - y).modulus() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_corrections_succeed() {
+        // Z₂ corrected by Z₀ differs from it by the stabilizer Z₀Z₂
+        let code = Code::shor();
+        let z = |q| PauliString::single(9, q, Pauli::Z);
+        assert!(code.corrects(&z(2), &z(0)));
+        assert!(!code.corrects(&z(3), &z(0)));
+        let trial = code.trial_with(z(2), &|syndrome| code.correction(syndrome));
+        assert_eq!(trial.correction, Some(z(0)));
+        assert!(!trial.logical_error);
+    }
+
+    #[test]
+    fn giving_up_is_a_failure() {
+        let code = Code::steane();
+        let error: PauliString = "IZIIIXI".parse().unwrap();
+        assert!(
+            code.trial_with(error.clone(), &|s| code.correction(s))
+                .logical_error
+        );
+        assert!(
+            !code
+                .trial_with(error, &|s| Some(steane_correction(s)))
+                .logical_error
+        );
+    }
+
+    #[test]
+    fn wilson_intervals() {
+        let (low, high) = wilson_interval(0, 100);
+        assert_eq!(low, 0.0);
+        assert!(high > 0.03 && high < 0.04);
+        let (low, high) = wilson_interval(50, 100);
+        assert!((low + high - 1.0).abs() < 1e-12);
+        assert!(low > 0.4 && high < 0.6);
+    }
+
+    #[test]
+    fn rates_cover_the_exact_values() {
+        // a round of the three-qubit code fails on two or more flips
+        let code = Code::bit_flip();
+        let decoder = |s| code.correction(s);
+        let probabilities = [0.02, 0.05, 0.1, 0.2, 0.4];
+        let rates =
+            code.logical_error_rates(PauliChannel::bit_flip, &decoder, &probabilities, 3000, 7);
+        for (rate, p) in rates.iter().zip(probabilities) {
+            let exact = 3.0 * p * p - 2.0 * p * p * p;
+            assert_eq!(rate.shots, 3000);
+            assert!(
+                rate.interval.0 <= exact && exact <= rate.interval.1,
+                "{rate:?}"
+            );
+        }
+        let again =
+            code.logical_error_rates(PauliChannel::bit_flip, &decoder, &probabilities, 3000, 7);
+        assert_eq!(rates, again);
+    }
+
+    #[test]
+    fn steane_code_suppresses_depolarizing_noise() {
+        let code = Code::steane();
+        let decoder = |s| Some(steane_correction(s));
+        let rates =
+            code.logical_error_rates(PauliChannel::depolarizing, &decoder, &[0.01, 0.03], 4000, 2);
+        for rate in &rates {
+            // the qubit unprotected errs with probability 3p/4
+            assert!(rate.interval.1 < 0.75 * rate.physical, "{rate:?}");
+        }
+    }
+
+    #[test]
+    fn pauli_frame_agrees_with_simulation() {
+        // the round on the sparse simulator recovers the input exactly when
+        // the frame says it does
+        let mut rng = StdRng::seed_from_u64(4);
+        let noise = PauliChannel::depolarizing(0.4);
+        for code in [Code::bit_flip(), Code::steane()] {
+            let n = code.num_data();
+            let all: Vec<usize> = (0..n + code.num_ancillas()).collect();
+            for _ in 0..15 {
+                let trial = code.trial(&noise, &|s| code.correction(s), &mut rng);
+                let mut c = Circuit::new(all.len() as u32);
+                c.h(0)
+                    .t(0)
+                    .compose(&code.round_trip(&error_circuit(&trial.error)), &all);
+                let run = c.run_sparse(&mut rng);
+                assert_eq!(run.register(), trial.syndrome);
+
+                let mut expected = Circuit::new(1);
+                expected.h(0).t(0);
+                let expected = expected.simulate().amplitudes(1);
+                let amplitudes = run.state().amplitudes(c.num_qubits());
+                let offset = (trial.syndrome as usize) << n;
+                let overlap = (expected[0].conjugate() * amplitudes[offset]
+                    + expected[1].conjugate() * amplitudes[offset | 1])
+                    .modulus();
+                assert_eq!(
+                    (overlap - 1.0).abs() > 1e-9,
+                    trial.logical_error,
+                    "{}",
+                    trial.error
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Stabilizer groups: abelian groups of Pauli strings not containing −I,
+//! given by generators. Each Pauli is handled as its binary symplectic
+//! vector, an X bit and a Z bit per qubit, with the sign carried along by
+//! multiplying the strings themselves.
+
+use crate::pauli::{Pauli, PauliString};
+
+/// The group generated by commuting, Hermitian Pauli strings on the same
+/// qubits, with the generators kept in the order given, as syndrome bits
+/// are numbered by them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StabilizerGroup {
+    generators: Vec<PauliString>,
+}
+
+/// Bit `column` of the symplectic vector of `p`: the X bit of qubit
+/// column / 2 at even columns, its Z bit at odd ones.
+fn bit(p: &PauliString, column: usize) -> bool {
+    matches!(
+        (p.paulis()[column / 2], column % 2),
+        (Pauli::X | Pauli::Y, 0) | (Pauli::Y | Pauli::Z, 1)
+    )
+}
+
+/// The first column set in the symplectic vector of `p`, if any.
+fn pivot(p: &PauliString) -> Option<usize> {
+    (0..2 * p.num_qubits()).find(|&column| bit(p, column))
+}
+
+impl StabilizerGroup {
+    /// The group generated by `generators`, which must act on the same
+    /// qubits, be Hermitian and commute, and must not generate −I.
+    ///
+    /// ```
+    /// use complex_calculator::qec::StabilizerGroup;
+    ///
+    /// let bell = StabilizerGroup::new(vec!["XX".parse().unwrap(), "ZZ".parse().unwrap()]);
+    /// assert!(bell.contains(&"-YY".parse().unwrap()));
+    /// assert!(!bell.contains(&"YY".parse().unwrap()));
+    /// assert_eq!(bell.syndrome(&"IZ".parse().unwrap()), 0b01);
+    /// ```
+    pub fn new(generators: Vec<PauliString>) -> Self {
+        assert!(
+            !generators.is_empty(),
+            "a stabilizer group needs generators"
+        );
+        let n = generators[0].num_qubits();
+        for g in &generators {
+            assert_eq!(g.num_qubits(), n, "generator {g} is not on {n} qubits");
+            assert!(g.phase() % 2 == 0, "generator {g} is not Hermitian");
+        }
+        for (i, a) in generators.iter().enumerate() {
+            for b in &generators[i + 1..] {
+                assert!(a.commutes_with(b), "stabilizers {a} and {b} do not commute");
+            }
+        }
+        let group = StabilizerGroup { generators };
+        group.canonical();
+        group
+    }
+
+    pub fn generators(&self) -> &[PauliString] {
+        &self.generators
+    }
+
+    pub fn num_qubits(&self) -> usize {
+        self.generators[0].num_qubits()
+    }
+
+    /// The number of independent generators, so that the group has 2^rank
+    /// elements and fixes a space of 2^(n − rank) dimensions.
+    pub fn rank(&self) -> usize {
+        self.canonical().len()
+    }
+
+    /// The generators in reduced row echelon form over the symplectic
+    /// columns X₀, Z₀, X₁, Z₁, …: each has a leading column no other one
+    /// has set, in increasing order. The form only depends on the group, so
+    /// two sets of generators give the same group exactly when their
+    /// canonical forms are equal.
+    pub fn canonical(&self) -> Vec<PauliString> {
+        let mut rows = self.generators.clone();
+        let mut rank = 0;
+        for column in 0..2 * self.num_qubits() {
+            let Some(r) = (rank..rows.len()).find(|&r| bit(&rows[r], column)) else {
+                continue;
+            };
+            rows.swap(rank, r);
+            for r in 0..rows.len() {
+                if r != rank && bit(&rows[r], column) {
+                    rows[r] = &rows[r] * &rows[rank];
+                }
+            }
+            rank += 1;
+        }
+        for dependent in &rows[rank..] {
+            assert!(dependent.phase() == 0, "the stabilizers generate −I");
+        }
+        rows.truncate(rank);
+        rows
+    }
+
+    /// Whether `p`, with its sign, is an element of the group: it reduces
+    /// to +I against the canonical generators.
+    pub fn contains(&self, p: &PauliString) -> bool {
+        assert_eq!(
+            p.num_qubits(),
+            self.num_qubits(),
+            "{p} is not on {} qubits",
+            self.num_qubits()
+        );
+        let mut rest = p.clone();
+        for row in self.canonical() {
+            let column = pivot(&row).unwrap();
+            if bit(&rest, column) {
+                rest = &rest * &row;
+            }
+        }
+        rest == PauliString::identity(self.num_qubits())
+    }
+
+    /// Whether `p` commutes with every element of the group, as the
+    /// errors a code cannot detect and its logical operators do.
+    pub fn commutes_with(&self, p: &PauliString) -> bool {
+        self.generators.iter().all(|g| g.commutes_with(p))
+    }
+
+    /// The syndrome of the error `p`: bit i is set when it anticommutes with
+    /// generator i.
+    pub fn syndrome(&self, p: &PauliString) -> u64 {
+        self.generators
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| !g.commutes_with(p))
+            .fold(0, |syndrome, (i, _)| syndrome | 1 << i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StabilizerGroup;
+    use crate::pauli::PauliString;
+
+    fn p(s: &str) -> PauliString {
+        s.parse().unwrap()
+    }
+
+    fn group(generators: &[&str]) -> StabilizerGroup {
+        StabilizerGroup::new(generators.iter().map(|g| p(g)).collect())
+    }
+
+    #[test]
+    fn membership_keeps_signs() {
+        let g = group(&["IZZ", "ZZI"]);
+        assert!(g.contains(&p("ZIZ")));
+        assert!(g.contains(&p("III")));
+        assert!(!g.contains(&p("-ZIZ")));
+        assert!(!g.contains(&p("IIZ")));
+
+        let g = group(&["-XX", "ZZ"]);
+        assert!(g.contains(&p("YY")));
+        assert!(g.contains(&(p("-XX") * p("ZZ") * p("ZZ"))));
+    }
+
+    #[test]
+    fn canonical_form_identifies_the_group() {
+        let a = group(&["XXXX", "ZZZZ", "XXII"]);
+        let b = group(&["IIXX", "XXII", "ZZZZ", "XXXX"]);
+        assert_eq!(a.canonical(), b.canonical());
+        assert_eq!(b.rank(), 3);
+        assert_ne!(a.canonical(), group(&["-XXXX", "ZZZZ", "XXII"]).canonical());
+        for row in a.canonical() {
+            assert!(a.contains(&row));
+        }
+    }
+
+    #[test]
+    fn commutation_and_syndromes() {
+        let g = group(&["IZZ", "ZZI"]);
+        assert!(g.commutes_with(&p("XXX")));
+        assert!(!g.commutes_with(&p("IXI")));
+        assert_eq!(g.syndrome(&p("IXI")), 0b11);
+        assert_eq!(g.syndrome(&p("XII")), 0b10);
+        assert_eq!(g.syndrome(&p("ZZZ")), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "generate −I")]
+    fn rejects_minus_identity() {
+        group(&["ZI", "-ZI"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not Hermitian")]
+    fn rejects_imaginary_generators() {
+        group(&["iZ"]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Shor's algorithm on toy moduli such as 15 and 21: order finding by phase
+//! estimation, the order read off the measured phase by continued
+//! fractions, and the classical reduction from factoring to order finding.
+//!
+//! The modular multiplications are synthesised from their truth tables by
+//! [`Circuit::from_permutation`], so the circuits grow exponentially with
+//! the size of the modulus and are only meant for the few qubits that can be
+//! simulated anyway. They are run with [`Circuit::run_sparse`], which applies
+//! the multi-controlled X gates they are made of as permutations, and keeps
+//! only the r values the work register takes for a base of order r.
+//! [`Circuit::modular_exponentiation`] builds the same multiplications from
+//! adders, at a size polynomial in the bits of the modulus, but with more
+//! gates than the truth tables need at these sizes.
+
+use rand::Rng;
+
+use crate::circuit::Circuit;
+
+/// Bits needed to hold the numbers below `modulus`.
+fn bits(modulus: u64) -> u32 {
+    u64::BITS - (modulus - 1).leading_zeros()
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// a^e mod m by repeated squaring.
+pub fn mod_pow(a: u64, mut e: u64, m: u64) -> u64 {
+    let (mut base, mut result) = (a as u128 % m as u128, 1 % m as u128);
+    while e > 0 {
+        if e & 1 == 1 {
+            result = result * base % m as u128;
+        }
+        base = base * base % m as u128;
+        e >>= 1;
+    }
+    result as u64
+}
+
+/// The order-finding circuit for `a` modulo `modulus`, with a counting
+/// register of `precision` qubits 0 to t − 1 and a work register of L =
+/// ⌈log₂ N⌉ qubits t to t + L − 1 starting in |1⟩. Counting qubit j controls
+/// y ↦ a^(2^j) y mod N on the work register, after which the inverse QFT
+/// turns the phases s/r into the integer nearest 2^t s/r, measured into bits
+/// 0 to t − 1. The usual precision is 2L + 1.
+pub fn order_finding(a: u64, modulus: u64, precision: u32) -> Circuit {
+    assert!(modulus > 1, "no order modulo {modulus}");
+    assert!(
+        gcd(a, modulus) == 1,
+        "{a} shares a factor with {modulus}, so has no order"
+    );
+    let (t, l) = (precision, bits(modulus));
+    let counting: Vec<usize> = (0..t as usize).collect();
+    let work: Vec<usize> = (t as usize..(t + l) as usize).collect();
+
+    let mut c = Circuit::new(t + l);
+    c.x(work[0]);
+    for &q in &counting {
+        c.h(q);
+    }
+    for &j in &counting {
+        let factor = mod_pow(a, 1 << j, modulus);
+        // bit 0 the control, bits 1 to L the work register
+        let multiply = Circuit::from_permutation(l + 1, |x| {
+            let y = x >> 1;
+            if x & 1 == 1 && y < modulus {
+                (factor * y % modulus) << 1 | 1
+            } else {
+                x
+            }
+        });
+        let mut qubits = vec![j];
+        qubits.extend(&work);
+        c.compose(&multiply, &qubits);
+    }
+    c.compose(&Circuit::iqft(t), &counting);
+    for &q in &counting {
+        c.measure(q, q);
+    }
+    c
+}
+
+/// The convergents p/q of the continued fraction of `numerator` /
+/// `denominator`, in order.
+pub fn convergents(mut numerator: u64, mut denominator: u64) -> Vec<(u64, u64)> {
+    let (mut p, mut q) = ((1u64, 0u64), (0u64, 1u64));
+    let mut out = vec![];
+    while denominator != 0 {
+        let a = numerator / denominator;
+        p = (a * p.0 + p.1, p.0);
+        q = (a * q.0 + q.1, q.0);
+        out.push((p.0, q.0));
+        (numerator, denominator) = (denominator, numerator % denominator);
+    }
+    out
+}
+
+/// Multiples of a convergent's denominator tried as the order.
+const MULTIPLES: u64 = 4;
+
+/// The order of `a` modulo `modulus` read from a phase estimate `measured`
+/// out of 2^`precision`: the first r that a^r ≡ 1 holds for among the
+/// denominators q > 1 of the convergents below the modulus, and their
+/// multiples up to 4q. The multiples catch the phases s/r whose s shares a
+/// small factor with r. `None` if no candidate works, as for the outcome 0.
+pub fn order_from_phase(measured: u64, precision: u32, a: u64, modulus: u64) -> Option<u64> {
+    convergents(measured, 1 << precision)
+        .into_iter()
+        .map(|(_, q)| q)
+        .filter(|&q| q > 1 && q < modulus)
+        .flat_map(|q| (1..=MULTIPLES).map(move |k| k * q))
+        .find(|&r| mod_pow(a, r, modulus) == 1)
+}
+
+/// The order of `a` modulo `modulus` from one run of [`order_finding`] at
+/// precision 2L + 1, or `None` if the measured phase did not give it away.
+pub fn find_order<R: Rng + ?Sized>(a: u64, modulus: u64, rng: &mut R) -> Option<u64> {
+    assert!(modulus > 1, "no order modulo {modulus}");
+    let precision = 2 * bits(modulus) + 1;
+    let run = order_finding(a, modulus, precision).run_sparse(rng);
+    order_from_phase(run.register(), precision, a, modulus)
+}
+
+/// A non-trivial factorisation (p, q), p ≤ q, of `n` by Shor's algorithm,
+/// trying up to `attempts` random bases. Even numbers and perfect powers
+/// are split classically, as is any base that happens to share a factor with
+/// n. `None` for primes, and when every attempt fails.
+///
+/// ```
+/// use complex_calculator::shor;
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+///
+/// assert_eq!(shor::factor(15, 10, &mut StdRng::seed_from_u64(1)), Some((3, 5)));
+/// ```
+pub fn factor<R: Rng + ?Sized>(n: u64, attempts: usize, rng: &mut R) -> Option<(u64, u64)> {
+    let split = |p: u64| Some((p.min(n / p), p.max(n / p)));
+    if n < 4 {
+        return None;
+    }
+    if n.is_multiple_of(2) {
+        return split(2);
+    }
+    for k in 2..bits(n) {
+        let root = (n as f64).powf(1.0 / f64::from(k)).round() as u64;
+        if root.checked_pow(k) == Some(n) {
+            return split(root);
+        }
+    }
+
+    for _ in 0..attempts {
+        let a = rng.random_range(2..n);
+        let shared = gcd(a, n);
+        if shared > 1 {
+            return split(shared);
+        }
+        let Some(r) = find_order(a, n, rng) else {
+            continue;
+        };
+        let half = mod_pow(a, r / 2, n);
+        if r % 2 == 1 || half == n - 1 {
+            continue;
+        }
+        let p = gcd(half - 1, n);
+        if p > 1 && p < n {
+            return split(p);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convergents, factor, find_order, order_finding, order_from_phase};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn continued_fractions() {
+        // 41/64 = [0; 1, 1, 1, 3, 1, 1, 2]
+        assert_eq!(
+            convergents(41, 64),
+            [
+                (0, 1),
+                (1, 1),
+                (1, 2),
+                (2, 3),
+                (7, 11),
+                (9, 14),
+                (16, 25),
+                (41, 64)
+            ]
+        );
+        // 2^11 · 5/6 rounds to 1707, and the order of 2 mod 21 is 6
+        assert_eq!(order_from_phase(1707, 11, 2, 21), Some(6));
+        // s/r = 2/4 reduces to 1/2, so the multiple 4 is found
+        assert_eq!(order_from_phase(128, 8, 7, 15), Some(4));
+        assert_eq!(order_from_phase(0, 8, 7, 15), None);
+        assert_eq!(order_from_phase(64, 8, 7, 15), Some(4));
+    }
+
+    #[test]
+    fn phase_estimation_peaks_at_multiples_of_one_over_the_order() {
+        // 7 has order 4 mod 15, so the 8-bit phase reads 0, 64, 128 or 192
+        let c = order_finding(7, 15, 8);
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut seen = [false; 4];
+        for _ in 0..40 {
+            let outcome = c.run_sparse(&mut rng).register();
+            assert_eq!(outcome % 64, 0, "{outcome}");
+            seen[outcome as usize / 64] = true;
+        }
+        assert_eq!(seen, [true; 4]);
+    }
+
+    #[test]
+    fn finds_orders() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for (a, modulus, order) in [(7, 15, 4), (2, 15, 4), (11, 15, 2), (2, 21, 6), (4, 21, 3)] {
+            let found = (0..10).find_map(|_| find_order(a, modulus, &mut rng));
+            assert_eq!(found, Some(order), "{a} mod {modulus}");
+        }
+    }
+
+    #[test]
+    fn factors_toy_moduli() {
+        let mut rng = StdRng::seed_from_u64(4);
+        assert_eq!(factor(15, 10, &mut rng), Some((3, 5)));
+        assert_eq!(factor(21, 10, &mut rng), Some((3, 7)));
+        assert_eq!(factor(22, 10, &mut rng), Some((2, 11)));
+        assert_eq!(factor(25, 10, &mut rng), Some((5, 5)));
+        assert_eq!(factor(13, 3, &mut rng), None);
+        // the rounded square root 2^32 overflows when squared back
+        assert_eq!(factor(u64::MAX, 0, &mut rng), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "no order modulo 0")]
+    fn order_needs_a_modulus_above_one() {
+        find_order(1, 0, &mut StdRng::seed_from_u64(5));
+    }
+}
@@ -0,0 +1,141 @@
+//! A minimal variational quantum eigensolver: the energy of a
+//! parameterised ansatz under a Pauli-sum Hamiltonian is brought down by
+//! gradient descent, with exact gradients from the parameter-shift rule.
+
+use crate::circuit::Circuit;
+use crate::pauli::PauliSum;
+
+/// The electronic Hamiltonian of molecular hydrogen at its equilibrium bond
+/// length of 0.735 Å, in the STO-3G basis reduced to two qubits by the
+/// parity mapping, in hartree. Its ground-state energy is −1.857275 Ha,
+/// which with the nuclear repulsion of 0.719969 Ha gives the −1.137 Ha of
+/// the molecule.
+pub fn h2_hamiltonian() -> PauliSum {
+    let mut h = PauliSum::new();
+    for (c, p) in [
+        (-1.052373245772859, "II"),
+        (0.39793742484318045, "IZ"),
+        (-0.39793742484318045, "ZI"),
+        (-0.01128010425623538, "ZZ"),
+        (0.18093119978423156, "XX"),
+    ] {
+        h.add(c, p.parse().unwrap());
+    }
+    h
+}
+
+/// The outcome of [`minimize`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimum {
+    /// The energy at the final parameters.
+    pub energy: f64,
+    /// The final value of each parameter of the ansatz.
+    pub parameters: Vec<(String, f64)>,
+    /// The energy before each step, then after the last.
+    pub history: Vec<f64>,
+}
+
+/// Runs `steps` steps of gradient descent with rate `learning_rate` on the
+/// energy [`Circuit::expectation`] of `ansatz` under `hamiltonian`, starting
+/// from `initial`, which must give every parameter a value. Gradients come
+/// from [`Circuit::parameter_shift_gradient`].
+///
+/// ```
+/// use complex_calculator::circuit::Circuit;
+/// use complex_calculator::vqe;
+///
+/// let ansatz = Circuit::hardware_efficient_ansatz(2, 1);
+/// let start: Vec<_> = ansatz.parameters().into_iter().map(|p| (p, 0.1)).collect();
+/// let minimum = vqe::minimize(&ansatz, &vqe::h2_hamiltonian(), &start, 0.4, 200);
+/// assert!((minimum.energy - -1.857275).abs() < 1e-4);
+/// ```
+pub fn minimize(
+    ansatz: &Circuit,
+    hamiltonian: &PauliSum,
+    initial: &[(&str, f64)],
+    learning_rate: f64,
+    steps: usize,
+) -> Minimum {
+    let names = ansatz.parameters();
+    let mut values: Vec<(&str, f64)> = names
+        .iter()
+        .map(|&name| {
+            let value = initial
+                .iter()
+                .find(|(n, _)| *n == name)
+                .unwrap_or_else(|| panic!("no initial value for parameter {name}"))
+                .1;
+            (name, value)
+        })
+        .collect();
+
+    let mut history = vec![];
+    for _ in 0..steps {
+        history.push(ansatz.expectation(hamiltonian, &values));
+        let gradient = ansatz.parameter_shift_gradient(hamiltonian, &values);
+        for ((_, value), g) in values.iter_mut().zip(gradient) {
+            *value -= learning_rate * g;
+        }
+    }
+    let energy = ansatz.expectation(hamiltonian, &values);
+    history.push(energy);
+
+    Minimum {
+        energy,
+        parameters: values
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+        history,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{h2_hamiltonian, minimize};
+    use crate::circuit::Circuit;
+
+    #[test]
+    fn h2_ground_state() {
+        let h = h2_hamiltonian();
+        let ansatz = Circuit::hardware_efficient_ansatz(2, 1);
+        let start: Vec<_> = ansatz
+            .parameters()
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| (p, 0.3 * i as f64 - 0.5))
+            .collect();
+        let minimum = minimize(&ansatz, &h, &start, 0.4, 200);
+
+        assert!((minimum.energy - -1.857275030202378).abs() < 1e-5);
+        assert_eq!(minimum.history.len(), 201);
+        assert!(minimum.history[0] > minimum.energy + 0.1);
+        // the reported parameters reproduce the energy
+        let values: Vec<(&str, f64)> = minimum
+            .parameters
+            .iter()
+            .map(|(n, v)| (n.as_str(), *v))
+            .collect();
+        assert!((ansatz.expectation(&h, &values) - minimum.energy).abs() < 1e-12);
+    }
+
+    #[test]
+    fn descent_never_climbs_with_a_small_step() {
+        let mut h = crate::pauli::PauliSum::new();
+        h.add(1.0, "ZI".parse().unwrap())
+            .add(0.5, "XX".parse().unwrap());
+        let ansatz = Circuit::hardware_efficient_ansatz(2, 1);
+        let start: Vec<_> = ansatz.parameters().into_iter().map(|p| (p, 0.7)).collect();
+        let minimum = minimize(&ansatz, &h, &start, 0.1, 30);
+        for pair in minimum.history.windows(2) {
+            assert!(pair[1] <= pair[0] + 1e-12);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "no initial value for parameter theta1")]
+    fn every_parameter_starts_somewhere() {
+        let ansatz = Circuit::hardware_efficient_ansatz(2, 1);
+        minimize(&ansatz, &h2_hamiltonian(), &[("theta0", 0.0)], 0.1, 1);
+    }
+}